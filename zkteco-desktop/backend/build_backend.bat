@@ -117,12 +117,14 @@ pyinstaller --name "zkteco-backend" ^
             --hidden-import=zk.exception ^
             --hidden-import=prettytable ^
             --hidden-import=sqlite3 ^
+            --hidden-import=sqlcipher3 ^
             --hidden-import=dotenv ^
             --hidden-import=chrono ^
             --collect-all=flask ^
             --collect-all=zk ^
             --collect-all=prettytable ^
             --collect-all=openpyxl ^
+            --collect-all=sqlcipher3 ^
             --add-data="src/app;app" ^
             --add-data="src/pyzk/zk;zk" ^
             service_app.py
@@ -195,8 +195,20 @@ class ZKTecoService:
             host = os.getenv("HOST", "0.0.0.0")
             port = int(os.getenv("PORT", 57575))
 
-            self.logger.info(f"Service starting on {host}:{port}")
-            self.app.run(host=host, port=port, debug=False, use_reloader=False)
+            # The desktop shell passes a self-signed certificate when it wants HTTPS
+            cert_file = os.getenv("ZKTECO_TLS_CERT")
+            key_file = os.getenv("ZKTECO_TLS_KEY")
+            ssl_context = (cert_file, key_file) if cert_file and key_file else None
+
+            scheme = "https" if ssl_context else "http"
+            self.logger.info(f"Service starting on {scheme}://{host}:{port}")
+            self.app.run(
+                host=host,
+                port=port,
+                debug=False,
+                use_reloader=False,
+                ssl_context=ssl_context,
+            )
 
         except Exception as e:
             self.logger.error(f"Failed to start service: {e}")
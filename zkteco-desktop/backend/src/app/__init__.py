@@ -1,6 +1,7 @@
 from dotenv import load_dotenv
-from flask import Flask
+from flask import Flask, jsonify, request
 from flask_cors import CORS
+import hmac
 import os
 import logging
 import sentry_sdk
@@ -15,6 +16,7 @@ from app.api.events import bp as event_blueprint
 from app.api.push_devices import push_devices_bp
 from app.api.settings import bp as settings_blueprint
 from app.api.doors import bp as doors_blueprint
+from app.api.openapi import bp as openapi_blueprint
 from app.shared.logger import create_log_handler
 from app.services.scheduler_service import scheduler_service
 from app.services.live_capture_service import (
@@ -37,6 +39,32 @@ class EndpointFilter(logging.Filter):
 
 load_dotenv()
 
+# Routes that stay open without the shell token: the health check used to find the backend
+# and the push endpoints devices call directly
+SHELL_TOKEN_EXEMPT_PREFIXES = ("/service/status", "/iclock/")
+
+
+def register_shell_auth(app):
+    """Reject API calls without the desktop shell's per-session token (ZKTECO_SHELL_TOKEN)."""
+    token = os.environ.get("ZKTECO_SHELL_TOKEN")
+    if not token:
+        app.logger.warning("ZKTECO_SHELL_TOKEN not set - API is not authenticated")
+        return
+
+    @app.before_request
+    def require_shell_token():
+        if request.method == "OPTIONS" or request.path.startswith(
+            SHELL_TOKEN_EXEMPT_PREFIXES
+        ):
+            return None
+        # EventSource can't send headers, so SSE clients pass the token as a query parameter
+        provided = request.headers.get("X-Shell-Token") or request.args.get(
+            "shell_token", ""
+        )
+        if hmac.compare_digest(provided.encode(), token.encode()):
+            return None
+        return jsonify({"error": "Missing or invalid shell token"}), 401
+
 
 def create_app():
     init_sentry()
@@ -47,12 +75,18 @@ def create_app():
     CORS(
         app,
         origins=["*"],
-        allow_headers=["Content-Type", "Authorization", "X-Requested-With"],
+        allow_headers=[
+            "Content-Type",
+            "Authorization",
+            "X-Requested-With",
+            "X-Shell-Token",
+        ],
         methods=["GET", "POST", "PUT", "DELETE", "OPTIONS"],
         supports_credentials=True,
     )
 
     app.config.from_object("app.config.settings")
+    register_shell_auth(app)
 
     handler = create_log_handler()
 
@@ -71,6 +105,7 @@ def create_app():
     app.register_blueprint(event_blueprint)
     app.register_blueprint(settings_blueprint)
     app.register_blueprint(doors_blueprint)
+    app.register_blueprint(openapi_blueprint)
 
     # Register push protocol blueprint (for SenseFace 4 and other push devices)
     app.register_blueprint(push_devices_bp)
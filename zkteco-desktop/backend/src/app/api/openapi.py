@@ -0,0 +1,66 @@
+import inspect
+import re
+
+from flask import Blueprint, current_app, jsonify
+
+bp = Blueprint("openapi", __name__, url_prefix="/")
+
+API_VERSION = "1.0.0"
+PATH_PARAM = re.compile(r"<(?:(\w+):)?(\w+)>")
+PARAM_TYPES = {
+    "int": {"type": "integer"},
+    "float": {"type": "number"},
+    "uuid": {"type": "string", "format": "uuid"},
+}
+
+
+def _operation(view, rule, method):
+    doc = inspect.getdoc(view) or ""
+    summary, _, description = doc.partition("\n")
+    operation = {
+        "operationId": f"{rule.endpoint.replace('.', '_')}_{method}",
+        "tags": [rule.endpoint.split(".")[0]],
+        "responses": {"default": {"description": "JSON response"}},
+    }
+    if summary:
+        operation["summary"] = summary.strip()
+    if description.strip():
+        operation["description"] = description.strip()
+    parameters = [
+        {
+            "name": name,
+            "in": "path",
+            "required": True,
+            "schema": PARAM_TYPES.get(converter, {"type": "string"}),
+        }
+        for converter, name in PATH_PARAM.findall(rule.rule)
+    ]
+    if parameters:
+        operation["parameters"] = parameters
+    return operation
+
+
+@bp.route("/openapi.json", methods=["GET"])
+def get_openapi_spec():
+    """OpenAPI description of the routes this backend serves
+
+    Built from the URL map, so it always matches the running build; request and response
+    bodies are not described.
+    """
+    paths = {}
+    for rule in sorted(current_app.url_map.iter_rules(), key=lambda r: r.rule):
+        if rule.endpoint == "static":
+            continue
+        view = current_app.view_functions[rule.endpoint]
+        path = PATH_PARAM.sub(lambda m: "{" + m.group(2) + "}", rule.rule)
+        for method in sorted(rule.methods - {"HEAD", "OPTIONS"}):
+            paths.setdefault(path, {})[method.lower()] = _operation(
+                view, rule, method.lower()
+            )
+    return jsonify(
+        {
+            "openapi": "3.0.3",
+            "info": {"title": "ZKTeco backend", "version": API_VERSION},
+            "paths": paths,
+        }
+    )
@@ -6,6 +6,22 @@ from contextlib import contextmanager
 from typing import Optional, Any, Dict, List, Set
 from datetime import datetime
 
+# Hex key of a SQLCipher-encrypted database, handed over by the desktop shell
+DB_KEY_ENV = "ZKTECO_DB_KEY"
+
+
+def _sqlite_module():
+    """sqlite3, or the API-compatible SQLCipher binding when the database is encrypted"""
+    if not os.environ.get(DB_KEY_ENV):
+        return sqlite3
+    try:
+        import sqlcipher3
+    except ImportError as exc:
+        raise RuntimeError(
+            "The database is encrypted but the sqlcipher3 module is not installed"
+        ) from exc
+    return sqlcipher3
+
 
 class DatabaseManager:
     """SQLite database manager for ZKTeco application"""
@@ -28,6 +44,8 @@ class DatabaseManager:
                 ) from exc
 
         self.db_path = resolved_path
+        self._db_key = os.environ.get(DB_KEY_ENV)
+        self._sqlite = _sqlite_module()
         self._local = threading.local()
         self._connections: Set[sqlite3.Connection] = (
             set()
@@ -42,10 +60,18 @@ class DatabaseManager:
 
         self.init_database()
 
+    def _connect(self, **kwargs):
+        """Open the database, unlocking it first when it is encrypted"""
+        conn = self._sqlite.connect(self.db_path, **kwargs)
+        if self._db_key:
+            # The key must be set before anything else touches the file
+            conn.execute(f"PRAGMA key = \"x'{self._db_key}'\"")
+        return conn
+
     def get_connection(self) -> sqlite3.Connection:
         """Get thread-local database connection"""
         if not hasattr(self._local, "connection") or self._local.connection is None:
-            conn = sqlite3.connect(self.db_path, check_same_thread=False, timeout=30.0)
+            conn = self._connect(check_same_thread=False, timeout=30.0)
             # Enable performance-oriented pragmas. WAL improves concurrent access, while
             # NORMAL synchronous/cache/temp settings trade a little durability for speed.
             conn.execute("PRAGMA foreign_keys = ON")
@@ -53,7 +79,7 @@ class DatabaseManager:
             conn.execute("PRAGMA synchronous = NORMAL")
             conn.execute("PRAGMA cache_size = 10000")
             conn.execute("PRAGMA temp_store = MEMORY")
-            conn.row_factory = sqlite3.Row
+            conn.row_factory = self._sqlite.Row
 
             # Store connection in thread-local storage
             self._local.connection = conn
@@ -594,8 +620,7 @@ class DatabaseManager:
             # Try to open database to check if WAL files are stale
             # If we can open it successfully, SQLite will handle recovery automatically
             try:
-                test_conn = sqlite3.connect(
-                    self.db_path,
+                test_conn = self._connect(
                     timeout=5.0,
                     isolation_level=None,  # Autocommit mode
                 )
@@ -620,7 +645,7 @@ class DatabaseManager:
                     "Database opened successfully, WAL recovery (if needed) completed"
                 )
 
-            except sqlite3.Error as db_err:
+            except (sqlite3.Error, self._sqlite.Error) as db_err:
                 print(f"Database check failed: {db_err}")
 
                 # If database is locked or corrupted, try to remove stale files
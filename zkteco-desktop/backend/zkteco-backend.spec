@@ -3,7 +3,7 @@ from PyInstaller.utils.hooks import collect_all
 
 datas = [('src/app', 'app')]
 binaries = []
-hiddenimports = ['flask', 'flask.json', 'werkzeug', 'requests', 'psutil', 'zk', 'pyzatt', 'prettytable', 'sqlite3', 'dotenv', 'flask_cors', 'sentry_sdk', 'apscheduler', 'logging.handlers', 'app.models.door', 'app.models.door_access_log', 'app.repositories.door_repository', 'app.repositories.door_access_repository']
+hiddenimports = ['flask', 'flask.json', 'werkzeug', 'requests', 'psutil', 'zk', 'pyzatt', 'prettytable', 'sqlite3', 'sqlcipher3', 'dotenv', 'flask_cors', 'sentry_sdk', 'apscheduler', 'logging.handlers', 'app.models.door', 'app.models.door_access_log', 'app.repositories.door_repository', 'app.repositories.door_access_repository']
 tmp_ret = collect_all('flask')
 datas += tmp_ret[0]; binaries += tmp_ret[1]; hiddenimports += tmp_ret[2]
 tmp_ret = collect_all('zk')
@@ -14,6 +14,8 @@ tmp_ret = collect_all('prettytable')
 datas += tmp_ret[0]; binaries += tmp_ret[1]; hiddenimports += tmp_ret[2]
 tmp_ret = collect_all('openpyxl')
 datas += tmp_ret[0]; binaries += tmp_ret[1]; hiddenimports += tmp_ret[2]
+tmp_ret = collect_all('sqlcipher3')
+datas += tmp_ret[0]; binaries += tmp_ret[1]; hiddenimports += tmp_ret[2]
 
 
 a = Analysis(
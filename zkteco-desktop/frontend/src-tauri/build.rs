@@ -1,3 +1,51 @@
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+// SHA-256 of the sidecar being bundled, compiled into the (code-signed) shell as the manifest
+// the binary is checked against before every spawn. Empty when the sidecar hasn't been built,
+// e.g. in development, which disables the check.
+fn sidecar_hash() -> String {
+    let target = std::env::var("TARGET").unwrap_or_default();
+    let suffix = if target.contains("windows") {
+        ".exe"
+    } else {
+        ""
+    };
+    let path =
+        PathBuf::from("../../backend/dist").join(format!("zkteco-backend-{}{}", target, suffix));
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let mut hasher = Sha256::new();
+    match File::open(&path).and_then(|mut file| io::copy(&mut file, &mut hasher)) {
+        Ok(_) => hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        Err(_) => String::new(),
+    }
+}
+
+// Version of the bundled backend from its pyproject.toml, the baseline for backend updates
+fn sidecar_version() -> String {
+    let path = PathBuf::from("../../backend/pyproject.toml");
+    println!("cargo:rerun-if-changed={}", path.display());
+    std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                let value = line.trim().strip_prefix("version")?.trim_start();
+                Some(value.strip_prefix('=')?.trim().trim_matches('"').to_string())
+            })
+        })
+        .unwrap_or_else(|| "0.0.0".to_string())
+}
+
 fn main() {
+    println!("cargo:rustc-env=ZKTECO_SIDECAR_SHA256={}", sidecar_hash());
+    println!("cargo:rustc-env=ZKTECO_SIDECAR_VERSION={}", sidecar_version());
     tauri_build::build()
 }
@@ -0,0 +1,377 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tauri::State;
+
+use crate::backend_auth::backend_client;
+use crate::photos::{is_photo_upload, max_photo_bytes, store_pushed_photo};
+use crate::settings::{current_settings, SharedSettings};
+use crate::{
+    append_app_log, backend_base_url, current_backend_port, resolve_app_data_dir, BackendPort,
+    ConnectionSlot,
+};
+
+// Embedded listener for the ZKTeco PUSH (ADMS) protocol. Devices behind NAT phone home to it
+// over HTTP; requests are forwarded to the backend's /iclock endpoints, and data uploads are
+// spooled to disk while the backend is unreachable so the device still gets its "OK". The port
+// is open to the LAN, so request size, header count, time per request and the number of
+// connections handled at once are all capped.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+// Request line and headers together
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+const MAX_HEADER_LINES: usize = 64;
+const MAX_CONNECTIONS: usize = 32;
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+const FALLBACK_RESPONSE: &str = "OK\r\n";
+
+// Connections are handled on their own threads; the spool file is only touched under this lock
+// and only one connection replays it at a time
+static SPOOL_LOCK: Mutex<()> = Mutex::new(());
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct AdmsStats {
+    listening: bool,
+    port: Option<u16>,
+    requests_forwarded: u64,
+    requests_spooled: u64,
+    spool_pending: usize,
+    last_request_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+pub type AdmsStatus = Arc<Mutex<AdmsStats>>;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct PushRequest {
+    method: String,
+    target: String, // path and query, e.g. /iclock/cdata?SN=...&table=ATTLOG
+    content_type: Option<String>,
+    body: String,
+    received_at: DateTime<Utc>,
+    // Undecoded body, for binary uploads such as verification photos
+    #[serde(skip)]
+    raw_body: Vec<u8>,
+}
+
+fn spool_path() -> PathBuf {
+    resolve_app_data_dir().join("adms_spool.jsonl")
+}
+
+fn read_spool_locked() -> Vec<PushRequest> {
+    let _guard = SPOOL_LOCK.lock();
+    read_spool()
+}
+
+// Returns the number of pending uploads after appending
+fn append_spool(request: PushRequest) -> Result<usize, String> {
+    let _guard = SPOOL_LOCK.lock();
+    let mut pending = read_spool();
+    pending.push(request);
+    write_spool(&pending)?;
+    Ok(pending.len())
+}
+
+// Drop the first `count` entries (new uploads are only ever appended)
+fn remove_spool_prefix(count: usize) -> Result<usize, String> {
+    let _guard = SPOOL_LOCK.lock();
+    let pending = read_spool();
+    let remaining = &pending[count.min(pending.len())..];
+    write_spool(remaining)?;
+    Ok(remaining.len())
+}
+
+fn read_spool() -> Vec<PushRequest> {
+    fs::read_to_string(spool_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn write_spool(requests: &[PushRequest]) -> Result<(), String> {
+    let content: String = requests
+        .iter()
+        .filter_map(|request| serde_json::to_string(request).ok())
+        .map(|line| line + "\n")
+        .collect();
+    fs::write(spool_path(), content).map_err(|e| format!("Failed to write ADMS spool: {}", e))
+}
+
+// Time left of CLIENT_TIMEOUT for the whole request, applied as the socket's read timeout
+fn arm_deadline(stream: &TcpStream, started: Instant) -> Result<(), String> {
+    let remaining = CLIENT_TIMEOUT
+        .checked_sub(started.elapsed())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or("Request timed out")?;
+    stream
+        .set_read_timeout(Some(remaining))
+        .map_err(|e| format!("Failed to set read timeout: {}", e))
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<PushRequest, String> {
+    let started = Instant::now();
+    // Reads stop at MAX_HEAD_SIZE until the headers are in, then at the announced body size
+    let mut reader = BufReader::new((&*stream).take(MAX_HEAD_SIZE));
+    let mut request_line = String::new();
+    arm_deadline(stream, started)?;
+    reader
+        .read_line(&mut request_line)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    if !request_line.ends_with('\n') {
+        return Err("Request line incomplete or too long".to_string());
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+    if method.is_empty() || !target.starts_with('/') {
+        return Err(format!("Malformed request line: {}", request_line.trim()));
+    }
+
+    let mut content_length = 0usize;
+    let mut content_type = None;
+    let mut complete = false;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        arm_deadline(stream, started)?;
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read headers: {}", e))?;
+        if !line.ends_with('\n') {
+            return Err("Request headers incomplete or too large".to_string());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            complete = true;
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "content-type" => content_type = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    if !complete {
+        return Err("Too many request headers".to_string());
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(format!("Request body too large ({} bytes)", content_length));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.get_mut().set_limit(content_length as u64);
+    arm_deadline(stream, started)?;
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| format!("Failed to read request body: {}", e))?;
+
+    Ok(PushRequest {
+        method,
+        target,
+        content_type,
+        body: String::from_utf8_lossy(&body).to_string(),
+        received_at: Utc::now(),
+        raw_body: body,
+    })
+}
+
+fn write_response(stream: &mut TcpStream, status: u16, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        if status < 400 { "OK" } else { "Error" },
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+async fn forward(request: &PushRequest, port: u16) -> Result<(u16, String), String> {
+    let client = backend_client(Duration::from_secs(20))?;
+    let url = format!("{}{}", backend_base_url(port), request.target);
+    let mut builder = if request.method.eq_ignore_ascii_case("POST") {
+        client.post(url).body(request.body.clone())
+    } else {
+        client.get(url)
+    };
+    if let Some(content_type) = &request.content_type {
+        builder = builder.header("Content-Type", content_type);
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| format!("Backend unreachable: {}", e))?;
+    let status = response.status().as_u16();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read backend response: {}", e))?;
+    Ok((status, body))
+}
+
+// Deliver spooled uploads in arrival order; stops at the first failure to keep ordering
+async fn replay_spool(port: u16, status: &AdmsStatus) {
+    if REPLAYING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let pending = read_spool_locked();
+
+    let mut delivered = 0;
+    for request in &pending {
+        match forward(request, port).await {
+            Ok((code, _)) if code < 500 => delivered += 1,
+            _ => break,
+        }
+    }
+    if delivered > 0 {
+        match remove_spool_prefix(delivered) {
+            Ok(remaining) => {
+                append_app_log(&format!(
+                    "Replayed {} spooled ADMS uploads to the backend",
+                    delivered
+                ));
+                if let Ok(mut stats) = status.lock() {
+                    stats.spool_pending = remaining;
+                }
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    REPLAYING.store(false, Ordering::SeqCst);
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    settings: &SharedSettings,
+    backend_port: &BackendPort,
+    status: &AdmsStatus,
+) {
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(err) => {
+            write_response(&mut stream, 400, &err);
+            return;
+        }
+    };
+    if !request.target.starts_with("/iclock/") {
+        write_response(&mut stream, 404, "Not found");
+        return;
+    }
+    // Verification photos stay on this PC; the backend has no use for them
+    if is_photo_upload(&request.target) {
+        let max_bytes = max_photo_bytes(settings);
+        if max_bytes > 0 {
+            if let Err(err) = store_pushed_photo(&request.target, &request.raw_body, max_bytes) {
+                eprintln!("{}", err);
+                append_app_log(&format!("Failed to store pushed photo: {}", err));
+            }
+        }
+        write_response(&mut stream, 200, FALLBACK_RESPONSE);
+        return;
+    }
+
+    let port = current_backend_port(backend_port);
+    let forwarded = tauri::async_runtime::block_on(async {
+        replay_spool(port, status).await;
+        forward(&request, port).await
+    });
+
+    let mut stats = match status.lock() {
+        Ok(stats) => stats,
+        Err(_) => return,
+    };
+    stats.last_request_at = Some(request.received_at);
+    match forwarded {
+        Ok((code, body)) => {
+            stats.requests_forwarded += 1;
+            drop(stats);
+            write_response(&mut stream, code, &body);
+        }
+        Err(err) => {
+            stats.last_error = Some(err);
+            // Uploads must not be lost; pings and handshakes just get the default answer
+            if request.method.eq_ignore_ascii_case("POST") {
+                match append_spool(request) {
+                    Ok(pending) => {
+                        stats.requests_spooled += 1;
+                        stats.spool_pending = pending;
+                    }
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+            drop(stats);
+            write_response(&mut stream, 200, FALLBACK_RESPONSE);
+        }
+    }
+}
+
+pub fn spawn_adms_server(settings: SharedSettings, backend_port: BackendPort, status: AdmsStatus) {
+    let config = current_settings(&settings);
+    if !config.adms_server_enabled {
+        return;
+    }
+
+    let port = config.adms_server_port;
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Failed to start ADMS listener on port {}: {}", port, err);
+            append_app_log(&format!(
+                "Failed to start ADMS listener on port {}: {}",
+                port, err
+            ));
+            if let Ok(mut stats) = status.lock() {
+                stats.last_error = Some(err.to_string());
+            }
+            return;
+        }
+    };
+
+    if let Ok(mut stats) = status.lock() {
+        stats.listening = true;
+        stats.port = Some(port);
+        stats.spool_pending = read_spool_locked().len();
+    }
+    append_app_log(&format!("ADMS listener started on port {}", port));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    let Some(slot) =
+                        ConnectionSlot::try_acquire(&ACTIVE_CONNECTIONS, MAX_CONNECTIONS)
+                    else {
+                        write_response(&mut stream, 503, "Too many connections");
+                        continue;
+                    };
+                    let settings = settings.clone();
+                    let backend_port = backend_port.clone();
+                    let status = status.clone();
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        handle_connection(stream, &settings, &backend_port, &status)
+                    });
+                }
+                Err(err) => eprintln!("ADMS listener accept failed: {}", err),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_adms_status(status: State<AdmsStatus>) -> Result<AdmsStats, String> {
+    status
+        .lock()
+        .map(|stats| stats.clone())
+        .map_err(|e| format!("Failed to read ADMS status: {}", e))
+}
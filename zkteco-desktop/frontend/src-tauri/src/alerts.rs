@@ -0,0 +1,422 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::audit::audited;
+use crate::monitor::{BackendState, MonitorBus, MonitorEvent};
+use crate::secrets::{alert_webhook_url, random_hex, set_alert_webhook_url};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{append_app_log, email, lock, resolve_app_data_dir};
+
+// Operator alerts posted to Slack or Microsoft Teams incoming webhooks. Each channel picks the
+// alert types it wants:
+//   backend_crash_loop  the backend failed CRASH_LOOP_COUNT times within CRASH_LOOP_WINDOW
+//   device_offline      a device stayed offline for alert_device_offline_minutes (and, for the
+//                       same channels, when it comes back)
+//   sync_failed         a scheduled device poll failed, at most once per SYNC_ALERT_COOLDOWN
+// The webhook URL embeds the channel's credential, so it is kept in the OS keychain. Alert
+// types listed in email_alerts are also mailed (email.rs).
+const ALERT_TYPES: [&str; 3] = ["backend_crash_loop", "device_offline", "sync_failed"];
+const CHANNEL_KINDS: [&str; 2] = ["slack", "teams"];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const CRASH_LOOP_COUNT: usize = 3;
+const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(600);
+const SYNC_ALERT_COOLDOWN: Duration = Duration::from_secs(3600);
+// How often offline devices are checked against the threshold when no events arrive
+const OFFLINE_CHECK: Duration = Duration::from_secs(30);
+const APP_NAME: &str = "ZKTeco Desktop";
+
+static CHANNELS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AlertChannel {
+    id: String,
+    kind: String, // "slack" or "teams"
+    name: String,
+    alerts: Vec<String>,
+    enabled: bool,
+    created_at: DateTime<Utc>,
+}
+
+struct OfflineDevice {
+    label: String,
+    since: Instant,
+    last_error: Option<String>,
+    alerted: bool,
+}
+
+// Alert state built from the monitor bus
+#[derive(Default)]
+struct Tracker {
+    crashes: VecDeque<Instant>,
+    last_crash_alert: Option<Instant>,
+    offline: HashMap<String, OfflineDevice>,
+    sync_alerts: HashMap<String, Instant>,
+}
+
+fn channels_path() -> PathBuf {
+    resolve_app_data_dir().join("alert_channels.json")
+}
+
+fn load_channels() -> Vec<AlertChannel> {
+    fs::read_to_string(channels_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_channels(channels: &[AlertChannel]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(channels)
+        .map_err(|e| format!("Failed to serialize alert channels: {}", e))?;
+    fs::write(channels_path(), content).map_err(|e| format!("Failed to save alert channels: {}", e))
+}
+
+// Which PC is talking, for offices that share one alert channel between sites
+fn source_label() -> String {
+    match std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")) {
+        Ok(host) if !host.trim().is_empty() => format!("{} on {}", APP_NAME, host.trim()),
+        _ => APP_NAME.to_string(),
+    }
+}
+
+fn message_body(kind: &str, title: &str, text: &str) -> serde_json::Value {
+    let title = format!("{}: {}", source_label(), title);
+    match kind {
+        "teams" => serde_json::json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": title,
+            "title": title,
+            "text": text,
+        }),
+        _ => serde_json::json!({ "text": format!("*{}*\n{}", title, text) }),
+    }
+}
+
+async fn post_alert(channel: &AlertChannel, title: &str, text: &str) -> Result<(), String> {
+    let url =
+        alert_webhook_url(&channel.id).ok_or("Alert webhook URL is missing from the keychain")?;
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .post(url)
+        .json(&message_body(&channel.kind, title, text))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to post alert: {}", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "Alert webhook answered {}: {}",
+            status,
+            body.trim()
+        ));
+    }
+    Ok(())
+}
+
+fn raise(config: &ShellSettings, alert: &'static str, title: String, text: String) {
+    append_app_log(&format!("Alert {}: {}", alert, text));
+    email::send_alert(config, alert, &title, &text);
+    for channel in load_channels()
+        .into_iter()
+        .filter(|channel| channel.enabled && channel.alerts.iter().any(|a| a == alert))
+    {
+        let (title, text) = (title.clone(), text.clone());
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = post_alert(&channel, &title, &text).await {
+                eprintln!("Alert to {} failed: {}", channel.name, err);
+                append_app_log(&format!("Alert to {} failed: {}", channel.name, err));
+            }
+        });
+    }
+}
+
+impl Tracker {
+    fn backend_failed(&mut self, config: &ShellSettings, reason: &str) {
+        let now = Instant::now();
+        self.crashes.push_back(now);
+        while self
+            .crashes
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > CRASH_LOOP_WINDOW)
+        {
+            self.crashes.pop_front();
+        }
+        let quiet = self
+            .last_crash_alert
+            .is_none_or(|at| now.duration_since(at) > CRASH_LOOP_WINDOW);
+        if self.crashes.len() >= CRASH_LOOP_COUNT && quiet {
+            self.last_crash_alert = Some(now);
+            raise(
+                config,
+                "backend_crash_loop",
+                "Backend is crash-looping".to_string(),
+                format!(
+                    "The backend failed {} times in the last {} minutes. Last error: {}",
+                    self.crashes.len(),
+                    CRASH_LOOP_WINDOW.as_secs() / 60,
+                    reason
+                ),
+            );
+        }
+    }
+
+    fn device_status(&mut self, config: &ShellSettings, status: &serde_json::Value) {
+        let text = |key: &str| {
+            status
+                .get(key)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let device_id = text("device_id");
+        if status.get("online").and_then(|online| online.as_bool()) == Some(true) {
+            if let Some(device) = self.offline.remove(&device_id) {
+                if device.alerted {
+                    raise(
+                        config,
+                        "device_offline",
+                        "Device back online".to_string(),
+                        format!(
+                            "{} is online again after {} minutes",
+                            device.label,
+                            device.since.elapsed().as_secs() / 60
+                        ),
+                    );
+                }
+            }
+            return;
+        }
+        let label = format!("{} ({})", text("name"), text("ip"));
+        let last_error = Some(text("last_error")).filter(|error| !error.is_empty());
+        self.offline
+            .entry(device_id)
+            .and_modify(|device| device.last_error = last_error.clone())
+            .or_insert(OfflineDevice {
+                label,
+                since: Instant::now(),
+                last_error,
+                alerted: false,
+            });
+    }
+
+    fn check_offline(&mut self, config: &ShellSettings) {
+        let threshold = Duration::from_secs(config.alert_device_offline_minutes * 60);
+        if threshold.is_zero() {
+            return;
+        }
+        for device in self.offline.values_mut() {
+            if device.alerted || device.since.elapsed() < threshold {
+                continue;
+            }
+            device.alerted = true;
+            let mut text = format!(
+                "{} has been offline for {} minutes",
+                device.label,
+                device.since.elapsed().as_secs() / 60
+            );
+            if let Some(error) = &device.last_error {
+                text.push_str(&format!(". Last error: {}", error));
+            }
+            raise(config, "device_offline", "Device offline".to_string(), text);
+        }
+    }
+
+    fn sync_failed(&mut self, config: &ShellSettings, failure: &serde_json::Value) {
+        let field = |key: &str| {
+            failure
+                .get(key)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let key = format!("{}:{}", field("job"), field("device_id"));
+        if self
+            .sync_alerts
+            .get(&key)
+            .is_some_and(|at| at.elapsed() < SYNC_ALERT_COOLDOWN)
+        {
+            return;
+        }
+        self.sync_alerts.insert(key, Instant::now());
+        raise(
+            config,
+            "sync_failed",
+            "Scheduled sync failed".to_string(),
+            format!(
+                "Scheduled poll of device {} failed: {}",
+                field("device_id"),
+                field("error")
+            ),
+        );
+    }
+}
+
+pub fn spawn_alert_dispatcher(settings: SharedSettings, bus: &MonitorBus) {
+    let mut receiver = bus.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let mut tracker = Tracker::default();
+        loop {
+            let event = tokio::time::timeout(OFFLINE_CHECK, receiver.recv()).await;
+            let config = current_settings(&settings);
+            match event {
+                Ok(Ok(MonitorEvent::Backend(BackendState::Failed(reason)))) => {
+                    tracker.backend_failed(&config, &reason)
+                }
+                Ok(Ok(MonitorEvent::DeviceStatus(status))) => {
+                    if let Ok(status) = serde_json::to_value(&status) {
+                        tracker.device_status(&config, &status);
+                    }
+                }
+                Ok(Ok(MonitorEvent::SyncFailed(failure))) => tracker.sync_failed(&config, &failure),
+                Ok(Ok(_)) | Err(_) => {}
+                Ok(Err(RecvError::Lagged(skipped))) => {
+                    append_app_log(&format!("Alerts missed {} monitor events", skipped));
+                }
+                Ok(Err(RecvError::Closed)) => break,
+            }
+            tracker.check_offline(&config);
+        }
+    });
+}
+
+fn validate(kind: &str, url: &str, alerts: &[String]) -> Result<(), String> {
+    if !CHANNEL_KINDS.contains(&kind) {
+        return Err(format!(
+            "Unknown alert channel '{}'; expected slack or teams",
+            kind
+        ));
+    }
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL '{}': {}", url, e))?;
+    if parsed.scheme() != "https" || parsed.host_str().is_none() {
+        return Err("The incoming webhook URL must be an https address".to_string());
+    }
+    validate_alerts(alerts)
+}
+
+fn validate_alerts(alerts: &[String]) -> Result<(), String> {
+    if let Some(unknown) = alerts
+        .iter()
+        .find(|alert| !ALERT_TYPES.contains(&alert.as_str()))
+    {
+        return Err(format!(
+            "Unknown alert type '{}'; expected one of {}",
+            unknown,
+            ALERT_TYPES.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_alert_channels() -> Vec<AlertChannel> {
+    load_channels()
+}
+
+#[tauri::command]
+pub fn add_alert_channel(
+    kind: String,
+    name: String,
+    url: String,
+    alerts: Vec<String>,
+) -> Result<AlertChannel, String> {
+    lock::ensure_unlocked()?;
+    let (kind, url) = (kind.trim().to_lowercase(), url.trim().to_string());
+    let name = match name.trim() {
+        "" => kind.clone(),
+        name => name.to_string(),
+    };
+    let result = validate(&kind, &url, &alerts).and_then(|_| {
+        let _guard = CHANNELS_LOCK.lock();
+        let channel = AlertChannel {
+            id: random_hex(8),
+            kind: kind.clone(),
+            name: name.clone(),
+            alerts,
+            enabled: true,
+            created_at: Utc::now(),
+        };
+        set_alert_webhook_url(&channel.id, &url)?;
+        let mut channels = load_channels();
+        channels.push(channel.clone());
+        if let Err(err) = save_channels(&channels) {
+            let _ = set_alert_webhook_url(&channel.id, "");
+            return Err(err);
+        }
+        Ok(channel)
+    });
+    audited("add_alert_channel", &name, result)
+}
+
+// Turn a channel on or off and/or change the alert types it receives
+#[tauri::command]
+pub fn update_alert_channel(
+    id: String,
+    enabled: Option<bool>,
+    alerts: Option<Vec<String>>,
+) -> Result<AlertChannel, String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        if let Some(alerts) = &alerts {
+            validate_alerts(alerts)?;
+        }
+        let _guard = CHANNELS_LOCK.lock();
+        let mut channels = load_channels();
+        let channel = channels
+            .iter_mut()
+            .find(|channel| channel.id == id)
+            .ok_or_else(|| format!("Alert channel {} not found", id))?;
+        if let Some(enabled) = enabled {
+            channel.enabled = enabled;
+        }
+        if let Some(alerts) = alerts {
+            channel.alerts = alerts;
+        }
+        let updated = channel.clone();
+        save_channels(&channels)?;
+        Ok(updated)
+    })();
+    audited("update_alert_channel", &id, result)
+}
+
+#[tauri::command]
+pub fn remove_alert_channel(id: String) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        let _guard = CHANNELS_LOCK.lock();
+        let mut channels = load_channels();
+        let before = channels.len();
+        channels.retain(|channel| channel.id != id);
+        if channels.len() == before {
+            return Err(format!("Alert channel {} not found", id));
+        }
+        save_channels(&channels)?;
+        set_alert_webhook_url(&id, "")
+    })();
+    audited("remove_alert_channel", &id, result)
+}
+
+#[tauri::command]
+pub async fn test_alert_channel(id: String) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let channel = load_channels()
+        .into_iter()
+        .find(|channel| channel.id == id)
+        .ok_or_else(|| format!("Alert channel {} not found", id))?;
+    post_alert(
+        &channel,
+        "Test alert",
+        "Alerts from this PC will be posted to this channel.",
+    )
+    .await
+}
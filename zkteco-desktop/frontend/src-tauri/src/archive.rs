@@ -0,0 +1,168 @@
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime};
+use rusqlite::Connection;
+use tauri::State;
+
+use crate::append_app_log;
+use crate::audit::audited;
+use crate::database::{archive_db_path, open_read_write};
+use crate::lock;
+use crate::settings::{current_settings, SharedSettings};
+
+// Moves old attendance out of zkteco_app.db into zkteco_archive.db so the primary database
+// (and every sync that scans it) stays small. Records still waiting to be pushed upstream are
+// never archived. Work is done in small batches so the backend is never blocked for long.
+const ARCHIVE_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+const ARCHIVE_RUN_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+const ARCHIVE_BATCH_SIZE: usize = 5000;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ArchiveResult {
+    archived: usize,
+    cutoff: String,
+    duration_ms: u128,
+}
+
+fn columns(connection: &Connection, schema: &str) -> Result<Vec<String>, String> {
+    let mut statement = connection
+        .prepare(&format!("PRAGMA {}.table_info(attendance_logs)", schema))
+        .map_err(|e| format!("Failed to inspect {} schema: {}", schema, e))?;
+    let names = statement
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Failed to inspect {} schema: {}", schema, e))?;
+    names
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to inspect {} schema: {}", schema, e))
+}
+
+// Create the archive table on first use and add columns the backend introduced since
+fn prepare_archive(connection: &Connection) -> Result<Vec<String>, String> {
+    let main_columns = columns(connection, "main")?;
+    connection
+        .execute_batch(
+            "CREATE TABLE IF NOT EXISTS archive.attendance_logs AS
+                SELECT * FROM main.attendance_logs WHERE 0;
+             CREATE UNIQUE INDEX IF NOT EXISTS archive.idx_archive_attendance_id
+                ON attendance_logs(id);
+             CREATE INDEX IF NOT EXISTS archive.idx_archive_attendance_timestamp
+                ON attendance_logs(timestamp);",
+        )
+        .map_err(|e| format!("Failed to create attendance archive: {}", e))?;
+    let archived_columns = columns(connection, "archive")?;
+    for column in main_columns
+        .iter()
+        .filter(|c| !archived_columns.contains(c))
+    {
+        connection
+            .execute_batch(&format!(
+                "ALTER TABLE archive.attendance_logs ADD COLUMN \"{}\"",
+                column
+            ))
+            .map_err(|e| format!("Failed to extend attendance archive: {}", e))?;
+    }
+    Ok(main_columns)
+}
+
+pub fn archive_older_than(cutoff: NaiveDateTime) -> Result<usize, String> {
+    let connection = open_read_write()?;
+    connection
+        .execute(
+            "ATTACH DATABASE ?1 AS archive",
+            [archive_db_path().to_string_lossy().to_string()],
+        )
+        .map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+
+    let column_list = prepare_archive(&connection)?
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let cutoff = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+    let batch = format!(
+        "CREATE TEMP TABLE IF NOT EXISTS archive_batch (id INTEGER PRIMARY KEY);
+         DELETE FROM temp.archive_batch;
+         INSERT INTO temp.archive_batch
+            SELECT id FROM main.attendance_logs
+            WHERE timestamp < '{cutoff}' AND COALESCE(sync_status, 'pending') != 'pending'
+            LIMIT {limit};
+         INSERT OR IGNORE INTO archive.attendance_logs ({columns})
+            SELECT {columns} FROM main.attendance_logs
+            WHERE id IN (SELECT id FROM temp.archive_batch);
+         DELETE FROM main.attendance_logs WHERE id IN (SELECT id FROM temp.archive_batch);",
+        cutoff = cutoff,
+        limit = ARCHIVE_BATCH_SIZE,
+        columns = column_list
+    );
+
+    let mut archived = 0;
+    loop {
+        let tx = connection
+            .unchecked_transaction()
+            .map_err(|e| format!("Failed to start archive batch: {}", e))?;
+        tx.execute_batch(&batch)
+            .map_err(|e| format!("Failed to archive attendance: {}", e))?;
+        let moved: usize = tx
+            .query_row("SELECT COUNT(*) FROM temp.archive_batch", [], |row| {
+                row.get(0)
+            })
+            .map_err(|e| format!("Failed to archive attendance: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit archive batch: {}", e))?;
+        archived += moved;
+        if moved < ARCHIVE_BATCH_SIZE {
+            return Ok(archived);
+        }
+    }
+}
+
+async fn run_archive(older_than_days: u64) -> Result<ArchiveResult, String> {
+    let started = Instant::now();
+    let cutoff = (Local::now() - chrono::Duration::days(older_than_days as i64)).naive_local();
+    let archived = tauri::async_runtime::spawn_blocking(move || archive_older_than(cutoff))
+        .await
+        .map_err(|e| format!("Archive task failed: {}", e))??;
+    if archived > 0 {
+        append_app_log(&format!(
+            "Archived {} attendance records older than {} days",
+            archived, older_than_days
+        ));
+    }
+    Ok(ArchiveResult {
+        archived,
+        cutoff: cutoff.format("%Y-%m-%d %H:%M:%S").to_string(),
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+pub fn spawn_archive_job(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_run: Option<Instant> = None;
+        loop {
+            tokio::time::sleep(ARCHIVE_CHECK_INTERVAL).await;
+            let days = current_settings(&settings).archive_after_days;
+            if days == 0 || last_run.is_some_and(|at| at.elapsed() < ARCHIVE_RUN_INTERVAL) {
+                continue;
+            }
+            last_run = Some(Instant::now());
+            if let Err(err) = run_archive(days).await {
+                eprintln!("{}", err);
+                append_app_log(&format!("Scheduled attendance archiving failed: {}", err));
+            }
+        }
+    });
+}
+
+// Archive now; defaults to the configured age
+#[tauri::command]
+pub async fn archive_attendance(
+    older_than_days: Option<u64>,
+    settings: State<'_, SharedSettings>,
+) -> Result<ArchiveResult, String> {
+    lock::ensure_unlocked()?;
+    let days = older_than_days.unwrap_or(current_settings(&settings).archive_after_days);
+    if days == 0 {
+        return Err("Set an age in days (archiving is disabled)".to_string());
+    }
+    audited("archive_attendance", "attendance", run_archive(days).await)
+}
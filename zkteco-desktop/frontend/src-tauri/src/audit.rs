@@ -0,0 +1,79 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Append-only record (audit.log, one JSON entry per line) of destructive or sensitive commands:
+// device administration, backend control, log clearing, restores and data exports. The file is
+// only ever appended to; clear_log_file and storage cleanup leave it alone.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub action: String,
+    pub target: String,
+    pub outcome: String, // "success", "failed", "rejected"
+    pub detail: Option<String>,
+}
+
+fn audit_log_path() -> PathBuf {
+    resolve_app_data_dir().join("audit.log")
+}
+
+pub fn record_audit(action: &str, target: &str, outcome: &str, detail: Option<String>) {
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        action: action.to_string(),
+        target: target.to_string(),
+        outcome: outcome.to_string(),
+        detail,
+    };
+    append_app_log(&format!("Audit: {} on {} - {}", action, target, outcome));
+
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(err) => {
+            eprintln!("Failed to serialize audit entry: {}", err);
+            return;
+        }
+    };
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_log_path())
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(err) = result {
+        eprintln!("Failed to write audit log: {}", err);
+    }
+}
+
+// Convenience for commands: audit the outcome of an action and pass the result through
+pub fn audited<T>(action: &str, target: &str, result: Result<T, String>) -> Result<T, String> {
+    match &result {
+        Ok(_) => record_audit(action, target, "success", None),
+        Err(err) => record_audit(action, target, "failed", Some(err.clone())),
+    }
+    result
+}
+
+#[tauri::command]
+pub fn get_audit_log(limit: Option<usize>) -> Result<Vec<AuditEntry>, String> {
+    let content = match fs::read_to_string(audit_log_path()) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to read audit log: {}", err)),
+    };
+    let mut entries: Vec<AuditEntry> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    // Most recent first
+    entries.reverse();
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
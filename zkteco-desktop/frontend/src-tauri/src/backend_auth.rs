@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use reqwest::header::{HeaderMap, HeaderValue};
+use tauri::State;
+
+use crate::lock;
+use crate::secrets::random_hex;
+use crate::service;
+use crate::tls::trusted_certificate;
+use crate::{backend_base_url, current_backend_port, BackendPort};
+
+// Per-session shared secret between the shell and the sidecar. The backend gets it through
+// ZKTECO_SHELL_TOKEN and rejects API calls without it, so other local processes can't drive
+// the API on its port. Every request the shell makes carries it in X-Shell-Token; the webview
+// never sees it, reaching the API only through proxy_backend_request and event_bridge.rs.
+pub const TOKEN_ENV: &str = "ZKTECO_SHELL_TOKEN";
+pub const TOKEN_HEADER: &str = "X-Shell-Token";
+const PROXY_TIMEOUT: Duration = Duration::from_secs(60);
+const PROXY_MAX_TIMEOUT: Duration = Duration::from_secs(600);
+const PROXY_RETRIES: u32 = 3;
+const PROXY_RETRY_DELAY: Duration = Duration::from_secs(1);
+const API_SPEC_PATH: &str = "/openapi.json";
+const API_SPEC_TIMEOUT: Duration = Duration::from_secs(10);
+
+static SESSION_TOKEN: OnceLock<String> = OnceLock::new();
+// The spec only changes with the sidecar build, so it is fetched once per backend port
+static API_SPEC: Mutex<Option<(u16, serde_json::Value)>> = Mutex::new(None);
+
+// New for every launch of the shell and never written to disk, except that a backend service
+// (service.rs) keeps the one it was installed with, held in the keychain
+pub fn session_token() -> &'static str {
+    SESSION_TOKEN.get_or_init(|| service::shared_token().unwrap_or_else(|| random_hex(32)))
+}
+
+fn client_builder() -> Result<reqwest::ClientBuilder, String> {
+    let mut headers = HeaderMap::new();
+    let token = HeaderValue::from_str(session_token())
+        .map_err(|e| format!("Invalid session token: {}", e))?;
+    headers.insert(TOKEN_HEADER, token);
+    let mut builder = reqwest::Client::builder().default_headers(headers);
+    if let Some(pem) = trusted_certificate() {
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| format!("Invalid backend certificate: {}", e))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+// HTTP client for calls to the backend, with the session token on every request
+pub fn backend_client(timeout: Duration) -> Result<reqwest::Client, String> {
+    client_builder()?
+        .timeout(timeout)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// Same for long-lived event streams, where only connecting is bounded
+pub fn backend_stream_client(connect_timeout: Duration) -> Result<reqwest::Client, String> {
+    client_builder()?
+        .connect_timeout(connect_timeout)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProxyResponse {
+    status: u16,
+    // Parsed JSON when the backend answered with JSON, the raw text otherwise; base64 of the
+    // bytes when binary was requested
+    body: serde_json::Value,
+    headers: HashMap<String, String>,
+}
+
+// Body sent as-is instead of JSON, e.g. a multipart form serialized by the webview
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawBody {
+    content_type: String,
+    base64: String,
+}
+
+fn is_idempotent(method: &reqwest::Method) -> bool {
+    matches!(
+        *method,
+        reqwest::Method::GET | reqwest::Method::HEAD | reqwest::Method::OPTIONS
+    )
+}
+
+// Forward an API call to the backend on behalf of the UI, e.g. ("GET", "/devices"). The port
+// is looked up again for every attempt, so a call made while the backend restarts (possibly
+// on a fallback port) is retried against where it came back; calls that failed to connect are
+// retried for every method, timeouts only for reads.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn proxy_backend_request(
+    method: String,
+    path: String,
+    query: Option<HashMap<String, String>>,
+    body: Option<serde_json::Value>,
+    raw_body: Option<RawBody>,
+    timeout_ms: Option<u64>,
+    binary: Option<bool>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<ProxyResponse, String> {
+    lock::ensure_unlocked()?;
+    if !path.starts_with('/') {
+        return Err(format!("Backend path must start with '/': {}", path));
+    }
+    let method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+        .map_err(|_| format!("Unsupported HTTP method: {}", method))?;
+    let raw_body = raw_body
+        .map(|raw| {
+            BASE64
+                .decode(raw.base64.as_bytes())
+                .map(|bytes| (raw.content_type, bytes))
+                .map_err(|e| format!("Invalid request body: {}", e))
+        })
+        .transpose()?;
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(PROXY_TIMEOUT)
+        .min(PROXY_MAX_TIMEOUT);
+    let client = backend_client(timeout)?;
+
+    let mut attempt = 0;
+    let response = loop {
+        let url = format!(
+            "{}{}",
+            backend_base_url(current_backend_port(&backend_port)),
+            path
+        );
+        let mut request = client.request(method.clone(), url);
+        if let Some(query) = &query {
+            request = request.query(query);
+        }
+        if let Some((content_type, bytes)) = &raw_body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .body(bytes.clone());
+        } else if let Some(body) = &body {
+            request = request.json(body);
+        }
+        match request.send().await {
+            Ok(response) => break response,
+            Err(err)
+                if attempt < PROXY_RETRIES
+                    && (err.is_connect() || (err.is_timeout() && is_idempotent(&method))) =>
+            {
+                attempt += 1;
+                tokio::time::sleep(PROXY_RETRY_DELAY * attempt).await;
+            }
+            Err(err) if err.is_timeout() => {
+                return Err(format!(
+                    "Backend did not answer within {} seconds",
+                    timeout.as_secs()
+                ))
+            }
+            Err(err) => return Err(format!("Backend unreachable: {}", err)),
+        }
+    };
+
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read backend response: {}", e))?;
+    let body = if binary.unwrap_or(false) {
+        serde_json::Value::String(BASE64.encode(&bytes))
+    } else {
+        let text = String::from_utf8_lossy(&bytes).to_string();
+        serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))
+    };
+    Ok(ProxyResponse {
+        status,
+        body,
+        headers,
+    })
+}
+
+// OpenAPI description of the bundled backend's routes, for the API explorer and smoke tests.
+// refresh skips the cache, e.g. after swapping the sidecar during development.
+#[tauri::command]
+pub async fn get_backend_api_spec(
+    refresh: Option<bool>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<serde_json::Value, String> {
+    lock::ensure_unlocked()?;
+    let port = current_backend_port(&backend_port);
+    if !refresh.unwrap_or(false) {
+        if let Ok(cached) = API_SPEC.lock() {
+            if let Some((cached_port, spec)) = cached.as_ref() {
+                if *cached_port == port {
+                    return Ok(spec.clone());
+                }
+            }
+        }
+    }
+
+    let response = backend_client(API_SPEC_TIMEOUT)?
+        .get(format!("{}{}", backend_base_url(port), API_SPEC_PATH))
+        .send()
+        .await
+        .map_err(|e| format!("Backend unreachable: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Backend returned {} for its API spec",
+            response.status()
+        ));
+    }
+    let spec: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse backend API spec: {}", e))?;
+    if spec.get("openapi").is_none() || !spec.get("paths").is_some_and(|p| p.is_object()) {
+        return Err("Backend API spec is not an OpenAPI document".to_string());
+    }
+    if let Ok(mut cached) = API_SPEC.lock() {
+        *cached = Some((port, spec.clone()));
+    }
+    Ok(spec)
+}
@@ -0,0 +1,529 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audit::audited;
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::database::{integrity_problems, open_read_only};
+use crate::encryption::{match_primary, unlock};
+use crate::lock;
+use crate::path_policy::{check_path, Access};
+use crate::protect;
+use crate::remote;
+use crate::s3;
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{
+    append_app_log, check_backend_health, current_backend_port, health_endpoint, kill_backend,
+    resolve_app_data_dir, resolve_backend_db_path, start_backend, wait_for_backend_shutdown,
+    BackendLogs, BackendPort, BackendProcess, ProcessStatus,
+};
+
+// Snapshots of zkteco_app.db taken with the SQLite online backup API, which copies a consistent
+// state page by page while the backend keeps writing (a plain file copy can tear mid-write)
+pub const BACKUP_PAGES_PER_STEP: std::os::raw::c_int = 256;
+pub const BACKUP_STEP_PAUSE: Duration = Duration::from_millis(20);
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const BACKUP_PREFIX: &str = "zkteco_app_";
+// Tables every usable backend database has
+const REQUIRED_TABLES: [&str; 3] = ["devices", "users", "attendance_logs"];
+const SHUTDOWN_TIMEOUT_SECS: u64 = 15;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BackupResult {
+    pub path: String,
+    size_bytes: u64,
+    duration_ms: u128,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BackupFile {
+    name: String,
+    path: String,
+    size_bytes: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RestoreResult {
+    restored_from: String,
+    safety_copy: String, // the database that was replaced
+    backend_restarted: bool,
+    message: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OptimizeResult {
+    size_before: u64,
+    size_after: u64,
+    reclaimed_bytes: u64,
+    free_pages_before: u64,
+    duration_ms: u128,
+    backend_restarted: bool,
+}
+
+// Payload of "backup-completed", emitted after every scheduled backup
+#[derive(Debug, Clone, serde::Serialize)]
+struct BackupNotification {
+    success: bool,
+    backup: Option<BackupResult>,
+    removed: usize,
+    error: Option<String>,
+}
+
+pub fn default_backup_dir() -> PathBuf {
+    resolve_app_data_dir().join("backups")
+}
+
+// Write a timestamped snapshot into `dir`
+pub fn snapshot_database(dir: &Path) -> Result<BackupResult, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    let path = dir.join(format!(
+        "{}{}.db",
+        BACKUP_PREFIX,
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    if path.exists() {
+        return Err(format!("Backup {:?} already exists", path));
+    }
+
+    let started = Instant::now();
+    let source = open_read_only()?;
+    let mut destination =
+        Connection::open(&path).map_err(|e| format!("Failed to create backup file: {}", e))?;
+    // The backup API only copies between databases sharing the same key
+    if let Err(err) = match_primary(&destination) {
+        drop(destination);
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+    let copied = Backup::new(&source, &mut destination)
+        .and_then(|backup| backup.run_to_completion(BACKUP_PAGES_PER_STEP, BACKUP_STEP_PAUSE, None))
+        .map_err(|e| format!("Database backup failed: {}", e));
+    drop(destination);
+    if let Err(err) = copied {
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+
+    let size_bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    Ok(BackupResult {
+        path: path.to_string_lossy().to_string(),
+        size_bytes,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+// Backups in the default folder, newest first (names start with the snapshot time)
+fn backup_files() -> Vec<BackupFile> {
+    let mut files: Vec<BackupFile> = fs::read_dir(default_backup_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if !name.starts_with(BACKUP_PREFIX) || !name.ends_with(".db") {
+                        return None;
+                    }
+                    let metadata = entry.metadata().ok()?;
+                    Some(BackupFile {
+                        name,
+                        path: entry.path().to_string_lossy().to_string(),
+                        size_bytes: metadata.len(),
+                        modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by(|a, b| b.name.cmp(&a.name));
+    files
+}
+
+// Delete all but the newest `keep` backups; returns how many were removed
+pub fn apply_retention(keep: usize) -> usize {
+    backup_files()
+        .into_iter()
+        .skip(keep.max(1))
+        .filter(|file| match fs::remove_file(&file.path) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Failed to remove old backup {}: {}", file.name, err);
+                false
+            }
+        })
+        .count()
+}
+
+// The scheduled run the current time falls in, if a backup is due today
+fn scheduled_slot(config: &ShellSettings, now: DateTime<Local>) -> Option<NaiveDate> {
+    let at = NaiveTime::parse_from_str(&config.backup_time, "%H:%M").ok()?;
+    let today = now.date_naive();
+    let due = match config.backup_schedule.as_str() {
+        "daily" => true,
+        "weekly" => today.weekday().number_from_monday() == config.backup_weekday,
+        _ => false,
+    };
+    (due && now.time() >= at).then_some(today)
+}
+
+pub fn spawn_backup_scheduler(app: AppHandle, settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        // Resume after a restart without taking a second backup the same day
+        let mut last_slot: Option<NaiveDate> = backup_files()
+            .first()
+            .and_then(|file| file.modified)
+            .map(|modified| modified.with_timezone(&Local).date_naive());
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let config = current_settings(&settings);
+            let Some(slot) = scheduled_slot(&config, Local::now()) else {
+                continue;
+            };
+            if last_slot == Some(slot) {
+                continue;
+            }
+            last_slot = Some(slot);
+
+            let dir = default_backup_dir();
+            let result = tauri::async_runtime::spawn_blocking(move || snapshot_database(&dir))
+                .await
+                .map_err(|e| format!("Backup task failed: {}", e))
+                .and_then(|result| result);
+            let notification = match result {
+                Ok(backup) => {
+                    if config.remote_upload_backups {
+                        remote::upload_backup(&config, PathBuf::from(&backup.path));
+                    }
+                    if config.s3_upload_backups {
+                        s3::upload_in_background(&config, PathBuf::from(&backup.path), "backups");
+                    }
+                    let removed = apply_retention(config.backup_keep_last);
+                    append_app_log(&format!(
+                        "Scheduled database backup written to {} ({} old backups removed)",
+                        backup.path, removed
+                    ));
+                    BackupNotification {
+                        success: true,
+                        backup: Some(backup),
+                        removed,
+                        error: None,
+                    }
+                }
+                Err(err) => {
+                    append_app_log(&format!("Scheduled database backup failed: {}", err));
+                    BackupNotification {
+                        success: false,
+                        backup: None,
+                        removed: 0,
+                        error: Some(err),
+                    }
+                }
+            };
+            if let Err(err) = app.emit("backup-completed", &notification) {
+                eprintln!("Failed to emit backup-completed event: {}", err);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_backups() -> Result<Vec<BackupFile>, String> {
+    Ok(backup_files())
+}
+
+// Snapshot the backend database into `destination` (a directory), or the app's backups folder.
+// With a password the snapshot is sealed into a .ztkenc file (see protect.rs), which has to be
+// opened with decrypt_export before it can be restored.
+#[tauri::command]
+pub async fn backup_database(
+    destination: Option<String>,
+    password: Option<String>,
+) -> Result<BackupResult, String> {
+    lock::ensure_unlocked()?;
+    let password = protect::check_password(password)?;
+    let dir = match destination.filter(|d| !d.trim().is_empty()) {
+        Some(destination) => check_path(&destination, Access::Write)?,
+        None => default_backup_dir(),
+    };
+    let snapshot_dir = match password {
+        Some(_) => protect::staging_dir()?,
+        None => dir.clone(),
+    };
+    let result = tauri::async_runtime::spawn_blocking(move || snapshot_database(&snapshot_dir))
+        .await
+        .map_err(|e| format!("Backup task failed: {}", e))?;
+    let result = match result {
+        Ok(backup) => {
+            let written = PathBuf::from(&backup.path);
+            let target = dir.join(written.file_name().unwrap_or_default());
+            protect::finish(Ok(backup), written, &target, password)
+                .await
+                .map(|(backup, path)| BackupResult {
+                    size_bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                    path,
+                    ..backup
+                })
+        }
+        Err(err) => Err(err),
+    };
+
+    match &result {
+        Ok(backup) => append_app_log(&format!(
+            "Database backed up to {} ({} bytes in {} ms)",
+            backup.path, backup.size_bytes, backup.duration_ms
+        )),
+        Err(err) => append_app_log(&format!("Database backup failed: {}", err)),
+    }
+    audited("backup_database", "database", result)
+}
+
+fn applied_migrations(connection: &Connection) -> Vec<String> {
+    connection
+        .prepare("SELECT migration_name FROM migration_history")
+        .and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or_default()
+}
+
+// A backup is restorable when it passes the integrity check, has the core tables and was not
+// made by a newer schema than the current database (older ones are migrated on backend start)
+pub fn validate_backup(path: &Path) -> Result<(), String> {
+    let backup = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open backup: {}", e))?;
+    unlock(&backup, path)?;
+    let problems = integrity_problems(&backup, false)?;
+    if !problems.is_empty() {
+        return Err(format!(
+            "Backup failed the integrity check: {}",
+            problems.join("; ")
+        ));
+    }
+    for table in REQUIRED_TABLES {
+        let present: bool = backup
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to inspect backup schema: {}", e))?;
+        if !present {
+            return Err(format!("Backup is missing the {} table", table));
+        }
+    }
+    if let Ok(current) = open_read_only() {
+        let known = applied_migrations(&current);
+        let unknown: Vec<String> = applied_migrations(&backup)
+            .into_iter()
+            .filter(|name| !known.contains(name))
+            .collect();
+        if !known.is_empty() && !unknown.is_empty() {
+            return Err(format!(
+                "Backup was made by a newer schema (unknown migrations: {})",
+                unknown.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Move the live database (with its WAL/SHM files) aside and copy the backup into its place;
+// returns the safety copy path. The original is put back if the copy fails.
+pub fn swap_database(backup: &Path) -> Result<PathBuf, String> {
+    let db_path = resolve_backend_db_path();
+    let dir = default_backup_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    let safety = dir.join(format!(
+        "pre_restore_{}.db",
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    let sidecar =
+        |base: &Path, suffix: &str| PathBuf::from(format!("{}{}", base.display(), suffix));
+
+    if db_path.exists() {
+        fs::rename(&db_path, &safety)
+            .or_else(|_| fs::copy(&db_path, &safety).and_then(|_| fs::remove_file(&db_path)))
+            .map_err(|e| format!("Failed to move current database aside: {}", e))?;
+        for suffix in ["-wal", "-shm"] {
+            let _ = fs::rename(sidecar(&db_path, suffix), sidecar(&safety, suffix));
+        }
+    }
+
+    let staging = sidecar(&db_path, ".restoring");
+    let copied = fs::copy(backup, &staging).and_then(|_| fs::rename(&staging, &db_path));
+    if let Err(err) = copied {
+        let _ = fs::remove_file(&staging);
+        if safety.exists() {
+            let _ = fs::rename(&safety, &db_path);
+            for suffix in ["-wal", "-shm"] {
+                let _ = fs::rename(sidecar(&safety, suffix), sidecar(&db_path, suffix));
+            }
+        }
+        return Err(format!("Failed to copy backup into place: {}", err));
+    }
+    Ok(safety)
+}
+
+// Replace the backend database with a backup: validate it, stop the backend, swap the files
+// (keeping the old database as a safety copy) and start the backend again
+#[tauri::command]
+pub async fn restore_database(
+    app: AppHandle,
+    backup_path: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<RestoreResult, String> {
+    let source = check_path(&backup_path, Access::Read)?;
+    consume_token(&tokens, &confirm_token, "restore_database", &backup_path)?;
+    let result = restore_from(&app, &source.to_string_lossy()).await;
+    match &result {
+        Ok(restore) => append_app_log(&restore.message),
+        Err(err) => append_app_log(&format!("Database restore failed: {}", err)),
+    }
+    audited("restore_database", &backup_path, result)
+}
+
+// The backend is the only writer; maintenance that rewrites the file needs it gone first.
+// Returns whether it was running.
+pub async fn stop_backend_for_maintenance(app: &AppHandle) -> Result<bool, String> {
+    let settings = app.state::<SharedSettings>();
+    let endpoint = health_endpoint(current_backend_port(&app.state::<BackendPort>()), &settings);
+    let was_running = check_backend_health(&endpoint).await;
+    let _ = kill_backend(&app.state::<BackendProcess>());
+    wait_for_backend_shutdown(SHUTDOWN_TIMEOUT_SECS, &endpoint)
+        .await
+        .map_err(|e| format!("Backend is still running: {}", e))?;
+    Ok(was_running)
+}
+
+pub async fn start_backend_after_maintenance(app: &AppHandle) -> Result<String, String> {
+    start_backend(
+        app.clone(),
+        app.state::<BackendProcess>(),
+        app.state::<ProcessStatus>(),
+        app.state::<BackendLogs>(),
+        app.state::<BackendPort>(),
+        app.state::<SharedSettings>(),
+    )
+    .await
+}
+
+pub async fn restore_from(app: &AppHandle, backup_path: &str) -> Result<RestoreResult, String> {
+    let backup = PathBuf::from(backup_path);
+    if !backup.is_file() {
+        return Err(format!("Backup not found: {}", backup_path));
+    }
+    {
+        let backup = backup.clone();
+        tauri::async_runtime::spawn_blocking(move || validate_backup(&backup))
+            .await
+            .map_err(|e| format!("Backup validation failed: {}", e))??;
+    }
+
+    stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, restore aborted", e))?;
+
+    let swapped = {
+        let backup = backup.clone();
+        tauri::async_runtime::spawn_blocking(move || swap_database(&backup))
+            .await
+            .map_err(|e| format!("Database swap failed: {}", e))
+            .and_then(|result| result)
+    };
+
+    // Bring the backend back either way - with the restored or the untouched database
+    let started = start_backend_after_maintenance(app).await;
+
+    let safety = swapped?;
+    Ok(RestoreResult {
+        restored_from: backup_path.to_string(),
+        safety_copy: safety.to_string_lossy().to_string(),
+        backend_restarted: started.is_ok(),
+        message: match started {
+            Ok(_) => format!("Database restored from {}", backup_path),
+            Err(err) => format!(
+                "Database restored from {}, but the backend failed to start: {}",
+                backup_path, err
+            ),
+        },
+    })
+}
+
+fn pragma_u64(connection: &Connection, pragma: &str) -> Result<u64, String> {
+    connection
+        .query_row(&format!("PRAGMA {}", pragma), [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read {}: {}", pragma, e))
+}
+
+// Rebuild the file without free pages and refresh the planner statistics.
+// Returns (size_before, size_after, free_pages_before).
+fn vacuum_database() -> Result<(u64, u64, u64), String> {
+    let path = resolve_backend_db_path();
+    let connection = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open backend database: {}", e))?;
+    unlock(&connection, &path)?;
+    let size = |c: &Connection| -> Result<u64, String> {
+        Ok(pragma_u64(c, "page_count")? * pragma_u64(c, "page_size")?)
+    };
+
+    let size_before = size(&connection)?;
+    let free_pages_before = pragma_u64(&connection, "freelist_count")?;
+    connection
+        .execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM; ANALYZE;")
+        .map_err(|e| format!("Failed to optimize database: {}", e))?;
+    Ok((size_before, size(&connection)?, free_pages_before))
+}
+
+// VACUUM + ANALYZE with the backend stopped for the duration; it is restarted afterwards if it
+// was running
+#[tauri::command]
+pub async fn optimize_database(app: AppHandle) -> Result<OptimizeResult, String> {
+    lock::ensure_unlocked()?;
+    audited("optimize_database", "database", optimize(&app).await)
+}
+
+async fn optimize(app: &AppHandle) -> Result<OptimizeResult, String> {
+    let started = Instant::now();
+    let was_running = stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, optimize aborted", e))?;
+
+    let vacuumed = tauri::async_runtime::spawn_blocking(vacuum_database)
+        .await
+        .map_err(|e| format!("Optimize task failed: {}", e))
+        .and_then(|result| result);
+    let backend_restarted = was_running && start_backend_after_maintenance(app).await.is_ok();
+
+    let (size_before, size_after, free_pages_before) = match vacuumed {
+        Ok(sizes) => sizes,
+        Err(err) => {
+            append_app_log(&format!("Database optimize failed: {}", err));
+            return Err(err);
+        }
+    };
+    let result = OptimizeResult {
+        size_before,
+        size_after,
+        reclaimed_bytes: size_before.saturating_sub(size_after),
+        free_pages_before,
+        duration_ms: started.elapsed().as_millis(),
+        backend_restarted,
+    };
+    append_app_log(&format!(
+        "Database optimized: {} -> {} bytes ({} reclaimed)",
+        result.size_before, result.size_after, result.reclaimed_bytes
+    ));
+    Ok(result)
+}
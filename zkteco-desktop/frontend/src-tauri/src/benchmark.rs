@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use crate::append_app_log;
+use crate::zk::{ZkSession, CMD_ATTLOG_RRQ, CMD_GET_FREE_SIZES, DEFAULT_DEVICE_PORT};
+
+const DEFAULT_ITERATIONS: u32 = 3;
+const MAX_ITERATIONS: u32 = 20;
+// Small status requests sent per iteration to estimate command loss
+const PROBES_PER_ITERATION: u32 = 5;
+const BENCHMARK_TIMEOUT: Duration = Duration::from_secs(5);
+// Offset of the attendance record counter in the CMD_GET_FREE_SIZES reply
+const ATTLOG_COUNT_OFFSET: usize = 32;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BenchmarkIteration {
+    iteration: u32,
+    handshake_ms: Option<f64>,
+    probe_avg_ms: Option<f64>,
+    probes_lost: u32,
+    read_ms: Option<f64>,
+    bytes_read: usize,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BenchmarkReport {
+    ip: String,
+    port: u16,
+    iterations: u32,
+    successful_iterations: u32,
+    handshake_min_ms: Option<f64>,
+    handshake_avg_ms: Option<f64>,
+    handshake_max_ms: Option<f64>,
+    probe_avg_ms: Option<f64>,
+    packet_loss_percent: f64,
+    records_on_device: Option<u32>,
+    read_throughput_kbps: Option<f64>,
+    read_records_per_sec: Option<f64>,
+    diagnosis: String,
+    details: Vec<BenchmarkIteration>,
+}
+
+fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn average(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn run_iteration(
+    ip: &str,
+    port: u16,
+    comm_key: u32,
+    iteration: u32,
+    records: &mut Option<u32>,
+) -> BenchmarkIteration {
+    let mut result = BenchmarkIteration {
+        iteration,
+        handshake_ms: None,
+        probe_avg_ms: None,
+        probes_lost: PROBES_PER_ITERATION,
+        read_ms: None,
+        bytes_read: 0,
+        error: None,
+    };
+
+    let start = Instant::now();
+    let mut session = match ZkSession::connect(ip, port, comm_key, BENCHMARK_TIMEOUT) {
+        Ok(session) => session,
+        Err(err) => {
+            result.error = Some(err);
+            return result;
+        }
+    };
+    result.handshake_ms = Some(elapsed_ms(start));
+
+    // On TCP a timed out probe leaves the stream out of sync, so the remaining probes count as lost
+    let mut probe_times = Vec::new();
+    for _ in 0..PROBES_PER_ITERATION {
+        let probe_start = Instant::now();
+        match session.send_command(CMD_GET_FREE_SIZES, &[]) {
+            Ok(reply) => {
+                probe_times.push(elapsed_ms(probe_start));
+                result.probes_lost -= 1;
+                if reply.data.len() >= ATTLOG_COUNT_OFFSET + 4 {
+                    let count = &reply.data[ATTLOG_COUNT_OFFSET..ATTLOG_COUNT_OFFSET + 4];
+                    *records = Some(u32::from_le_bytes([count[0], count[1], count[2], count[3]]));
+                }
+            }
+            Err(err) => {
+                result.error = Some(err);
+                break;
+            }
+        }
+    }
+    result.probe_avg_ms = average(&probe_times);
+
+    if result.error.is_some() {
+        return result;
+    }
+
+    let read_start = Instant::now();
+    match session.read_with_buffer(CMD_ATTLOG_RRQ, 0, 0) {
+        Ok(data) => {
+            result.read_ms = Some(elapsed_ms(read_start));
+            result.bytes_read = data.len();
+        }
+        Err(err) => {
+            result.error = Some(err);
+            return result;
+        }
+    }
+
+    if let Err(err) = session.disconnect() {
+        result.error = Some(err);
+    }
+
+    result
+}
+
+// Rough classification so users know whether to call IT (network) or look at the terminal
+fn diagnose(handshake_avg: Option<f64>, loss_percent: f64, throughput_kbps: Option<f64>) -> String {
+    match (handshake_avg, throughput_kbps) {
+        (None, _) => "Device unreachable - check IP address, cabling and firewall".to_string(),
+        _ if loss_percent >= 5.0 => format!(
+            "Network problem likely - {:.1}% of requests were lost",
+            loss_percent
+        ),
+        (Some(handshake), _) if handshake > 500.0 => format!(
+            "Network problem likely - slow handshake ({:.0} ms average)",
+            handshake
+        ),
+        (Some(_), Some(throughput)) if throughput < 20.0 => format!(
+            "Device problem likely - network is responsive but record reads are slow ({:.1} KB/s)",
+            throughput
+        ),
+        (Some(_), None) => {
+            "Device problem likely - handshake works but record reads fail".to_string()
+        }
+        _ => "Network and device performance look healthy".to_string(),
+    }
+}
+
+fn run_benchmark(ip: String, port: u16, comm_key: u32, iterations: u32) -> BenchmarkReport {
+    let mut records_on_device = None;
+    let details: Vec<BenchmarkIteration> = (1..=iterations)
+        .map(|iteration| run_iteration(&ip, port, comm_key, iteration, &mut records_on_device))
+        .collect();
+
+    let handshakes: Vec<f64> = details.iter().filter_map(|d| d.handshake_ms).collect();
+    let probes: Vec<f64> = details.iter().filter_map(|d| d.probe_avg_ms).collect();
+    let successful_iterations = details.iter().filter(|d| d.error.is_none()).count() as u32;
+
+    // A failed handshake counts as every probe of that iteration being lost
+    let total_requests = iterations * (PROBES_PER_ITERATION + 1);
+    let lost_requests: u32 = details
+        .iter()
+        .map(|d| {
+            if d.handshake_ms.is_none() {
+                PROBES_PER_ITERATION + 1
+            } else {
+                d.probes_lost
+            }
+        })
+        .sum();
+    let packet_loss_percent = lost_requests as f64 * 100.0 / total_requests as f64;
+
+    let (read_bytes, read_ms) = details
+        .iter()
+        .filter_map(|d| d.read_ms.map(|ms| (d.bytes_read, ms)))
+        .fold((0usize, 0f64), |(bytes, ms), (b, m)| (bytes + b, ms + m));
+    let reads = details.iter().filter(|d| d.read_ms.is_some()).count() as f64;
+    let read_throughput_kbps = if read_ms > 0.0 {
+        Some(read_bytes as f64 / 1024.0 / (read_ms / 1000.0))
+    } else {
+        None
+    };
+    let read_records_per_sec = match records_on_device {
+        Some(records) if read_ms > 0.0 => Some(records as f64 * reads / (read_ms / 1000.0)),
+        _ => None,
+    };
+
+    let handshake_avg_ms = average(&handshakes);
+    let diagnosis = diagnose(handshake_avg_ms, packet_loss_percent, read_throughput_kbps);
+
+    BenchmarkReport {
+        ip,
+        port,
+        iterations,
+        successful_iterations,
+        handshake_min_ms: handshakes.iter().cloned().reduce(f64::min),
+        handshake_avg_ms,
+        handshake_max_ms: handshakes.iter().cloned().reduce(f64::max),
+        probe_avg_ms: average(&probes),
+        packet_loss_percent,
+        records_on_device,
+        read_throughput_kbps,
+        read_records_per_sec,
+        diagnosis,
+        details,
+    }
+}
+
+#[tauri::command]
+pub async fn benchmark_device(
+    ip: String,
+    port: Option<u16>,
+    comm_key: Option<u32>,
+    iterations: Option<u32>,
+) -> Result<BenchmarkReport, String> {
+    let port = port.unwrap_or(DEFAULT_DEVICE_PORT);
+    let iterations = iterations
+        .unwrap_or(DEFAULT_ITERATIONS)
+        .clamp(1, MAX_ITERATIONS);
+    append_app_log(&format!(
+        "benchmark_device started for {}:{} ({} iterations)",
+        ip, port, iterations
+    ));
+
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        run_benchmark(ip, port, comm_key.unwrap_or(0), iterations)
+    })
+    .await
+    .map_err(|e| format!("Device benchmark task failed: {}", e))?;
+
+    append_app_log(&format!(
+        "benchmark_device finished for {}:{} - {}",
+        report.ip, report.port, report.diagnosis
+    ));
+    Ok(report)
+}
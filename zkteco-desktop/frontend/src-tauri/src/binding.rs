@@ -0,0 +1,89 @@
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Emitter};
+
+use crate::append_app_log;
+
+// The sidecar binds to HOST, which the backend defaults to 0.0.0.0, so the API is reachable from
+// the LAN unless backend_localhost_only passes HOST=127.0.0.1. Once the backend is up the shell
+// connects to the port through this PC's LAN address; if that works, anyone on the network can
+// drive the API, which is logged and reported with "backend-exposed".
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+pub const LOCALHOST: &str = "127.0.0.1";
+
+static LAST_CHECK: Mutex<Option<BindingCheck>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BindingCheck {
+    port: u16,
+    // LAN address the port was probed on; None when the PC has no network to probe from
+    probed_address: Option<String>,
+    exposed: bool,
+    localhost_only: bool,
+    checked_at: DateTime<Utc>,
+}
+
+// Address of the interface that routes off this PC; nothing is sent by a UDP connect
+fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("192.0.2.1", 9)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+fn probe(port: u16, localhost_only: bool) -> BindingCheck {
+    let address = lan_address();
+    let exposed = address.is_some_and(|ip| {
+        TcpStream::connect_timeout(&SocketAddr::new(ip, port), PROBE_TIMEOUT).is_ok()
+    });
+    BindingCheck {
+        port,
+        probed_address: address.map(|ip| ip.to_string()),
+        exposed,
+        localhost_only,
+        checked_at: Utc::now(),
+    }
+}
+
+// Run after every backend start
+pub async fn verify_backend_binding(app: &AppHandle, port: u16, localhost_only: bool) {
+    let check =
+        match tauri::async_runtime::spawn_blocking(move || probe(port, localhost_only)).await {
+            Ok(check) => check,
+            Err(err) => {
+                eprintln!("Backend binding check failed: {}", err);
+                return;
+            }
+        };
+
+    if check.exposed {
+        let advice = if localhost_only {
+            "even though localhost-only is enforced; check the backend's HOST configuration"
+        } else {
+            "enable backend_localhost_only in shell settings to restrict it to this PC"
+        };
+        let warning = format!(
+            "SECURITY WARNING: backend API on port {} is reachable from the network via {} ({})",
+            port,
+            check.probed_address.as_deref().unwrap_or("?"),
+            advice
+        );
+        eprintln!("{}", warning);
+        append_app_log(&warning);
+        if let Err(err) = app.emit("backend-exposed", &check) {
+            eprintln!("Failed to emit backend-exposed event: {}", err);
+        }
+    }
+    if let Ok(mut guard) = LAST_CHECK.lock() {
+        *guard = Some(check);
+    }
+}
+
+// Result of the check after the latest backend start, None before the first one
+#[tauri::command]
+pub fn get_backend_binding() -> Option<BindingCheck> {
+    LAST_CHECK.lock().ok().and_then(|check| check.clone())
+}
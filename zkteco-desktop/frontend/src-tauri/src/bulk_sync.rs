@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use tauri::{Emitter, Manager, State};
+use tokio::sync::Semaphore;
+
+use crate::devices::pull_and_stage_progress;
+use crate::lock;
+use crate::monitor::{MonitorBus, MonitorEvent};
+use crate::registry::{active_pull_devices, lookup_device, DeviceEntry, DeviceRegistry};
+use crate::{append_app_log, BackendPort};
+
+// Parallel attendance sync across terminals: devices are pulled with bounded concurrency
+// (terminals are slow, but independent), each reporting progress as it goes, and the run ends
+// with one aggregated summary
+pub const DEFAULT_SYNC_CONCURRENCY: usize = 4;
+const MAX_SYNC_CONCURRENCY: usize = 16;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncProgress {
+    device_id: String,
+    stage: String, // "queued", "fetching", "done" or "failed"
+    fetched: u32,
+    total: u32,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceSyncResult {
+    device_id: String,
+    success: bool,
+    records_on_device: usize,
+    staged: usize,
+    duration_ms: u128,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncSummary {
+    devices: usize,
+    succeeded: usize,
+    failed: usize,
+    records_on_devices: usize,
+    records_staged: usize,
+    duration_ms: u128,
+    results: Vec<DeviceSyncResult>,
+}
+
+fn emit_progress(app: &tauri::AppHandle, progress: SyncProgress) {
+    if let Err(err) = app.emit("device-sync-progress", &progress) {
+        eprintln!("Failed to emit device-sync-progress: {}", err);
+    }
+}
+
+async fn sync_one(app: tauri::AppHandle, device: DeviceEntry) -> DeviceSyncResult {
+    let started = Instant::now();
+    let device_id = device.id.clone();
+
+    let progress_app = app.clone();
+    let progress_id = device_id.clone();
+    let mut last_fetched = None;
+    let result = pull_and_stage_progress(device, None, move |fetched, total| {
+        if last_fetched != Some(fetched) {
+            last_fetched = Some(fetched);
+            emit_progress(
+                &progress_app,
+                SyncProgress {
+                    device_id: progress_id.clone(),
+                    stage: "fetching".to_string(),
+                    fetched,
+                    total,
+                    error: None,
+                },
+            );
+        }
+    })
+    .await;
+
+    let duration_ms = started.elapsed().as_millis();
+    match result {
+        Ok(pull) => {
+            emit_progress(
+                &app,
+                SyncProgress {
+                    device_id: device_id.clone(),
+                    stage: "done".to_string(),
+                    fetched: pull.records_on_device as u32,
+                    total: pull.records_on_device as u32,
+                    error: None,
+                },
+            );
+            DeviceSyncResult {
+                device_id,
+                success: true,
+                records_on_device: pull.records_on_device,
+                staged: pull.staged,
+                duration_ms,
+                error: None,
+            }
+        }
+        Err(err) => {
+            emit_progress(
+                &app,
+                SyncProgress {
+                    device_id: device_id.clone(),
+                    stage: "failed".to_string(),
+                    fetched: 0,
+                    total: 0,
+                    error: Some(err.clone()),
+                },
+            );
+            DeviceSyncResult {
+                device_id,
+                success: false,
+                records_on_device: 0,
+                staged: 0,
+                duration_ms,
+                error: Some(err),
+            }
+        }
+    }
+}
+
+// Pull every device with at most `concurrency` sessions open at once
+pub async fn run_sync(
+    app: tauri::AppHandle,
+    devices: Vec<DeviceEntry>,
+    concurrency: usize,
+) -> SyncSummary {
+    let started = Instant::now();
+    let concurrency = concurrency.clamp(1, MAX_SYNC_CONCURRENCY);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    append_app_log(&format!(
+        "Syncing {} devices ({} at a time)",
+        devices.len(),
+        concurrency
+    ));
+
+    let tasks: Vec<_> = devices
+        .into_iter()
+        .map(|device| {
+            emit_progress(
+                &app,
+                SyncProgress {
+                    device_id: device.id.clone(),
+                    stage: "queued".to_string(),
+                    fetched: 0,
+                    total: 0,
+                    error: None,
+                },
+            );
+            let app = app.clone();
+            let semaphore = semaphore.clone();
+            let device_id = device.id.clone();
+            let task = tauri::async_runtime::spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                sync_one(app, device).await
+            });
+            (device_id, task)
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (device_id, task) in tasks {
+        results.push(task.await.unwrap_or_else(|err| DeviceSyncResult {
+            device_id,
+            success: false,
+            records_on_device: 0,
+            staged: 0,
+            duration_ms: 0,
+            error: Some(format!("Device sync task failed: {}", err)),
+        }));
+    }
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    let summary = SyncSummary {
+        devices: results.len(),
+        succeeded,
+        failed: results.len() - succeeded,
+        records_on_devices: results.iter().map(|r| r.records_on_device).sum(),
+        records_staged: results.iter().map(|r| r.staged).sum(),
+        duration_ms: started.elapsed().as_millis(),
+        results,
+    };
+    append_app_log(&format!(
+        "Device sync finished: {} succeeded, {} failed, {} records staged in {} ms",
+        summary.succeeded, summary.failed, summary.records_staged, summary.duration_ms
+    ));
+    if let Err(err) = app.emit("device-sync-complete", &summary) {
+        eprintln!("Failed to emit device-sync-complete: {}", err);
+    }
+    if let Ok(value) = serde_json::to_value(&summary) {
+        let _ = app
+            .state::<MonitorBus>()
+            .send(MonitorEvent::SyncComplete(value));
+    }
+    summary
+}
+
+// Sync the given devices, or every active pull device when none are given
+#[tauri::command]
+pub async fn sync_devices(
+    app: tauri::AppHandle,
+    device_ids: Option<Vec<String>>,
+    max_concurrency: Option<usize>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<SyncSummary, String> {
+    lock::ensure_unlocked()?;
+    let devices = match device_ids {
+        Some(ids) => {
+            let mut devices = Vec::with_capacity(ids.len());
+            for id in ids {
+                devices.push(lookup_device(&registry, &backend_port, &id).await?);
+            }
+            devices
+        }
+        None => active_pull_devices(&registry),
+    };
+    if devices.is_empty() {
+        return Err("No devices to sync".to_string());
+    }
+    Ok(run_sync(
+        app,
+        devices,
+        max_concurrency.unwrap_or(DEFAULT_SYNC_CONCURRENCY),
+    )
+    .await)
+}
@@ -0,0 +1,412 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{DateTime, Local, Utc};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use tauri::{AppHandle, State};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::audit::audited;
+use crate::backup::{
+    default_backup_dir, restore_from, snapshot_database, RestoreResult, BACKUP_PAGES_PER_STEP,
+    BACKUP_STEP_PAUSE,
+};
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::database::archive_db_path;
+use crate::encryption::{export_plaintext, is_encrypted, unlock};
+use crate::export::resolve_save_path;
+use crate::lock;
+use crate::path_policy::{check_path, Access};
+use crate::profiles::{active_profile, reload_profile_state, DEFAULT_PROFILE};
+use crate::s3;
+use crate::settings::{current_settings, load_settings, save_settings, SharedSettings};
+use crate::{append_app_log, resolve_app_data_dir};
+
+// One-file copy of an installation for moving it to another PC: a database snapshot (plus the
+// attendance archive), shell settings, the device registry and the tail of the logs, zipped
+// with a manifest. COMM keys and the upstream token live in the OS keychain and are not
+// included; the backend reports its device keys again on the new PC.
+const BUNDLE_FORMAT_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const BUNDLE_DATABASE: &str = "database/zkteco_app.db";
+const BUNDLE_ARCHIVE: &str = "database/zkteco_archive.db";
+const LOG_TAIL_LINES: usize = 5000;
+// Bundle entry -> file in the app data folder
+const DATA_FILES: [(&str, &str); 2] = [
+    ("settings/shell_settings.json", "shell_settings.json"),
+    ("settings/device_registry.json", "device_registry.json"),
+];
+const LOG_FILES: [(&str, &str); 2] = [
+    ("logs/zkteco_app.log", "zkteco_app.log"),
+    ("logs/audit.log", "audit.log"),
+];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct BundleManifest {
+    format_version: u32,
+    created_at: DateTime<Utc>,
+    app_version: String,
+    profile: String,
+    // The database is still keyed with the source PC's keychain key
+    database_encrypted: bool,
+    files: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BundleExportResult {
+    path: String,
+    size_bytes: u64,
+    files: Vec<String>,
+    database_encrypted: bool,
+    duration_ms: u128,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BundleImportResult {
+    created_at: DateTime<Utc>,
+    source_profile: String,
+    restore: RestoreResult,
+    files_applied: Vec<String>,
+}
+
+fn staging_dir(kind: &str) -> PathBuf {
+    resolve_app_data_dir().join(format!(
+        "bundle_{}_{}",
+        kind,
+        Local::now().format("%Y%m%d_%H%M%S")
+    ))
+}
+
+// Consistent copy of the attendance archive, keyed like the original
+fn snapshot_archive(target: &Path) -> Result<(), String> {
+    let archive = archive_db_path();
+    let source = Connection::open_with_flags(&archive, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+    unlock(&source, &archive)?;
+    let mut destination =
+        Connection::open(target).map_err(|e| format!("Failed to create archive copy: {}", e))?;
+    unlock(&destination, &archive)?;
+    Backup::new(&source, &mut destination)
+        .and_then(|backup| backup.run_to_completion(BACKUP_PAGES_PER_STEP, BACKUP_STEP_PAUSE, None))
+        .map_err(|e| format!("Archive snapshot failed: {}", e))
+}
+
+pub fn log_tail(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.len().saturating_sub(LOG_TAIL_LINES);
+    Some(lines[start..].join("\n") + "\n")
+}
+
+fn add_file(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    source: &Path,
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    let mut file = File::open(source).map_err(|e| format!("Failed to read {:?}: {}", source, e))?;
+    zip.start_file(name, options)
+        .map_err(|e| format!("Failed to add {} to bundle: {}", name, e))?;
+    io::copy(&mut file, zip).map_err(|e| format!("Failed to add {} to bundle: {}", name, e))?;
+    Ok(())
+}
+
+fn write_bundle(
+    path: &Path,
+    staging: &Path,
+    decrypt_database: bool,
+    app_version: String,
+) -> Result<BundleManifest, String> {
+    let data_dir = resolve_app_data_dir();
+    let mut database = PathBuf::from(snapshot_database(staging)?.path);
+    if decrypt_database && is_encrypted(&database) {
+        let plaintext = staging.join("zkteco_app_plaintext.db");
+        export_plaintext(&database, &plaintext)?;
+        database = plaintext;
+    }
+    let archive = staging.join("zkteco_archive.db");
+    let with_archive = archive_db_path().exists();
+    if with_archive {
+        snapshot_archive(&archive)?;
+        if decrypt_database && is_encrypted(&archive) {
+            let plaintext = staging.join("zkteco_archive_plaintext.db");
+            export_plaintext(&archive, &plaintext)?;
+            fs::rename(&plaintext, &archive)
+                .map_err(|e| format!("Failed to stage archive: {}", e))?;
+        }
+    }
+
+    let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    let mut files = vec![BUNDLE_DATABASE.to_string()];
+    add_file(&mut zip, BUNDLE_DATABASE, &database, options)?;
+    if with_archive {
+        add_file(&mut zip, BUNDLE_ARCHIVE, &archive, options)?;
+        files.push(BUNDLE_ARCHIVE.to_string());
+    }
+    for (entry, name) in DATA_FILES {
+        let source = data_dir.join(name);
+        if source.exists() {
+            add_file(&mut zip, entry, &source, options)?;
+            files.push(entry.to_string());
+        }
+    }
+    for (entry, name) in LOG_FILES {
+        let Some(tail) = log_tail(&data_dir.join(name)) else {
+            continue;
+        };
+        zip.start_file(entry, options)
+            .and_then(|_| zip.write_all(tail.as_bytes()).map_err(Into::into))
+            .map_err(|e| format!("Failed to add {} to bundle: {}", entry, e))?;
+        files.push(entry.to_string());
+    }
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        created_at: Utc::now(),
+        app_version,
+        profile: active_profile().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        database_encrypted: is_encrypted(&database),
+        files,
+    };
+    let content = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize bundle manifest: {}", e))?;
+    zip.start_file(MANIFEST, options)
+        .and_then(|_| zip.write_all(&content).map_err(Into::into))
+        .and_then(|_| zip.finish().map(drop))
+        .map_err(|e| format!("Failed to write bundle: {}", e))?;
+    Ok(manifest)
+}
+
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<BundleManifest, String> {
+    let mut content = String::new();
+    archive
+        .by_name(MANIFEST)
+        .map_err(|_| "Not an app bundle (manifest.json is missing)".to_string())?
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Failed to read bundle manifest: {}", e))?;
+    let manifest: BundleManifest =
+        serde_json::from_str(&content).map_err(|e| format!("Invalid bundle manifest: {}", e))?;
+    if manifest.format_version > BUNDLE_FORMAT_VERSION {
+        return Err(format!(
+            "Bundle format {} is newer than this app supports ({})",
+            manifest.format_version, BUNDLE_FORMAT_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+// Only the known entry names are extracted, never paths taken from the archive
+fn extract_entry(
+    archive: &mut ZipArchive<File>,
+    entry: &str,
+    target: &Path,
+) -> Result<bool, String> {
+    let mut file = match archive.by_name(entry) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(false),
+        Err(err) => return Err(format!("Failed to read {} from bundle: {}", entry, err)),
+    };
+    let mut output =
+        File::create(target).map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+    io::copy(&mut file, &mut output)
+        .map_err(|e| format!("Failed to extract {} from bundle: {}", entry, e))?;
+    Ok(true)
+}
+
+fn extract_bundle(path: &Path, staging: &Path) -> Result<BundleManifest, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Failed to read bundle {:?}: {}", path, e))?;
+    let manifest = read_manifest(&mut archive)?;
+    fs::create_dir_all(staging).map_err(|e| format!("Failed to create staging folder: {}", e))?;
+    if !extract_entry(
+        &mut archive,
+        BUNDLE_DATABASE,
+        &staging.join("zkteco_app.db"),
+    )? {
+        return Err("Bundle contains no database".to_string());
+    }
+    extract_entry(
+        &mut archive,
+        BUNDLE_ARCHIVE,
+        &staging.join("zkteco_archive.db"),
+    )?;
+    for (entry, name) in DATA_FILES {
+        extract_entry(&mut archive, entry, &staging.join(name))?;
+    }
+    Ok(manifest)
+}
+
+// Settings, registry and archive from the bundle, applied once the database is restored
+fn apply_staged_files(staging: &Path, database_dir: Option<String>) -> Result<Vec<String>, String> {
+    let data_dir = resolve_app_data_dir();
+    let mut applied = Vec::new();
+    for (entry, name) in DATA_FILES {
+        let source = staging.join(name);
+        if source.exists() {
+            fs::copy(&source, data_dir.join(name))
+                .map_err(|e| format!("Failed to apply {}: {}", name, e))?;
+            applied.push(entry.to_string());
+        }
+    }
+    // The database folder is a property of this PC, not of the bundle
+    let mut settings = load_settings();
+    settings.database_dir = database_dir;
+    save_settings(&settings)?;
+
+    let archive = staging.join("zkteco_archive.db");
+    if archive.exists() {
+        let current = archive_db_path();
+        if current.exists() {
+            fs::create_dir_all(default_backup_dir())
+                .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+            let aside = default_backup_dir().join(format!(
+                "pre_import_archive_{}.db",
+                Local::now().format("%Y%m%d_%H%M%S")
+            ));
+            fs::copy(&current, &aside)
+                .map_err(|e| format!("Failed to keep the current archive: {}", e))?;
+        }
+        fs::copy(&archive, &current).map_err(|e| format!("Failed to apply archive: {}", e))?;
+        applied.push(BUNDLE_ARCHIVE.to_string());
+    }
+    Ok(applied)
+}
+
+// Write a bundle; without a path the user picks one in a save dialog (None when cancelled).
+// decrypt_database stores a plaintext database for PCs that don't have this one's key.
+#[tauri::command]
+pub async fn export_app_bundle(
+    app: AppHandle,
+    path: Option<String>,
+    decrypt_database: Option<bool>,
+    settings: State<'_, SharedSettings>,
+) -> Result<Option<BundleExportResult>, String> {
+    lock::ensure_unlocked()?;
+    let Some(path) = resolve_save_path(
+        &app,
+        path,
+        "App bundle",
+        "zip",
+        format!("ztkapp_bundle_{}.zip", Local::now().format("%Y%m%d")),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let staging = staging_dir("export");
+    let app_version = app.package_info().version.to_string();
+    let (target, dir) = (path.clone(), staging.clone());
+    let written = tauri::async_runtime::spawn_blocking(move || {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create staging folder: {}", e))?;
+        write_bundle(
+            &target,
+            &dir,
+            decrypt_database.unwrap_or(false),
+            app_version,
+        )
+    })
+    .await
+    .map_err(|e| format!("Bundle task failed: {}", e))
+    .and_then(|result| result);
+    let _ = fs::remove_dir_all(&staging);
+
+    let path_text = path.to_string_lossy().to_string();
+    let audit_target = path_text.clone();
+    let result = match written {
+        Ok(manifest) => {
+            let result = BundleExportResult {
+                size_bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                path: path_text,
+                files: manifest.files,
+                database_encrypted: manifest.database_encrypted,
+                duration_ms: started.elapsed().as_millis(),
+            };
+            append_app_log(&format!(
+                "Exported app bundle to {} ({} bytes)",
+                result.path, result.size_bytes
+            ));
+            let config = current_settings(&settings);
+            if config.s3_upload_bundles {
+                s3::upload_in_background(&config, path.clone(), "bundles");
+            }
+            Ok(Some(result))
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&path);
+            append_app_log(&format!("App bundle export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_app_bundle", &audit_target, result)
+}
+
+// Replace this installation's data with a bundle's; the current database is kept as a
+// pre_restore safety copy in the backups folder
+#[tauri::command]
+pub async fn import_app_bundle(
+    app: AppHandle,
+    path: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    settings: State<'_, SharedSettings>,
+) -> Result<BundleImportResult, String> {
+    let source = check_path(&path, Access::Read)?;
+    consume_token(&tokens, &confirm_token, "import_app_bundle", &path)?;
+    let database_dir = settings
+        .lock()
+        .map(|settings| settings.database_dir.clone())
+        .map_err(|e| format!("Failed to read shell settings: {}", e))?;
+    let staging = staging_dir("import");
+    let result = import_staged(&app, source, &staging, database_dir).await;
+    let _ = fs::remove_dir_all(&staging);
+    match &result {
+        Ok(imported) => append_app_log(&format!(
+            "Imported app bundle {} (created {}, {} files applied)",
+            path,
+            imported.created_at,
+            imported.files_applied.len() + 1
+        )),
+        Err(err) => append_app_log(&format!("App bundle import failed: {}", err)),
+    }
+    audited("import_app_bundle", &path, result)
+}
+
+async fn import_staged(
+    app: &AppHandle,
+    source: PathBuf,
+    staging: &Path,
+    database_dir: Option<String>,
+) -> Result<BundleImportResult, String> {
+    let dir = staging.to_path_buf();
+    let manifest = tauri::async_runtime::spawn_blocking(move || extract_bundle(&source, &dir))
+        .await
+        .map_err(|e| format!("Bundle task failed: {}", e))??;
+
+    // Validates the database, swaps it in and restarts the backend
+    let database = staging.join("zkteco_app.db");
+    let restore = restore_from(app, &database.to_string_lossy()).await?;
+
+    let dir = staging.to_path_buf();
+    let files_applied =
+        tauri::async_runtime::spawn_blocking(move || apply_staged_files(&dir, database_dir))
+            .await
+            .map_err(|e| format!("Bundle task failed: {}", e))??;
+    reload_profile_state(app);
+    Ok(BundleImportResult {
+        created_at: manifest.created_at,
+        source_profile: manifest.profile,
+        restore,
+        files_applied,
+    })
+}
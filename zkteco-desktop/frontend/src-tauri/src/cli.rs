@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::audit::record_audit;
+use crate::backend_auth::backend_client;
+use crate::backup::backup_database;
+use crate::export::{parse_range, write_attendance_csv, ExportRange};
+use crate::settings::{apply_runtime_settings, load_settings, SharedSettings};
+use crate::{
+    backend_env, backend_port_candidates, health_endpoint, is_port_available, lock, migrations,
+    resolve_backend_db_path, sidecar, DEFAULT_BACKEND_PORT,
+};
+
+// Headless administration for scripts and SSH sessions: `<app> --cli <command>` runs the Rust
+// logic behind a few shell commands and exits without creating a window or tray.
+//   backend status     whether a backend answers, and on which port
+//   backend start      run the backend in the foreground until it exits or Ctrl+C
+//   backend stop       stop the backend the app (or a CLI run) started
+//   export --range FROM..TO [--output FILE] [--devices ID,ID]
+//   backup [--destination DIR]
+// With an app lock PIN set, stop, export and backup need it via --pin or ZTKAPP_PIN.
+// Exit codes: 0 success, 1 failure, 2 bad usage, 3 backend not running.
+const USAGE: &str = "Usage: --cli <command>
+  backend status
+  backend start
+  backend stop [--pin PIN]
+  export --range YYYY-MM-DD..YYYY-MM-DD [--output FILE] [--devices ID,ID] [--pin PIN]
+  backup [--destination DIR] [--pin PIN]";
+const PIN_ENV: &str = "ZTKAPP_PIN";
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(3);
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_RUNNING: i32 = 3;
+
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(err: String) -> Self {
+        CliError::Failed(err)
+    }
+}
+
+struct Options {
+    positional: Vec<String>,
+    flags: HashMap<String, String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, CliError> {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                positional.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None => (
+                    name.to_string(),
+                    args.next()
+                        .cloned()
+                        .ok_or_else(|| CliError::Usage(format!("--{} needs a value", name)))?,
+                ),
+            };
+            flags.insert(name, value);
+        }
+        Ok(Options { positional, flags })
+    }
+
+    fn flag(&self, name: &str) -> Option<String> {
+        self.flags
+            .get(name)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+// Arguments after --cli, when the app was started in CLI mode
+pub fn cli_args() -> Option<Vec<String>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let index = args.iter().position(|arg| arg == "--cli")?;
+    Some(args[index + 1..].to_vec())
+}
+
+// Windows release builds have no console of their own; borrow the one we were started from
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
+
+pub fn run_cli(args: Vec<String>) -> i32 {
+    attach_console();
+    let settings = load_settings();
+    apply_runtime_settings(&settings);
+    let settings: SharedSettings = Arc::new(Mutex::new(settings));
+
+    let result = Options::parse(&args)
+        .and_then(|options| tauri::async_runtime::block_on(dispatch(&options, &settings)));
+    match result {
+        Ok(code) => code,
+        Err(CliError::Usage(message)) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            EXIT_USAGE
+        }
+        Err(CliError::Failed(message)) => {
+            eprintln!("Error: {}", message);
+            EXIT_FAILURE
+        }
+    }
+}
+
+async fn dispatch(options: &Options, settings: &SharedSettings) -> Result<i32, CliError> {
+    let words: Vec<&str> = options.positional.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["backend", "status"] => backend_status(settings).await,
+        ["backend", "start"] => backend_start(settings).await,
+        ["backend", "stop"] => backend_stop(options, settings).await,
+        ["export"] => export(options).await,
+        ["backup"] => backup(options).await,
+        [] => Err(CliError::Usage("No command given".to_string())),
+        _ => Err(CliError::Usage(format!(
+            "Unknown command '{}'",
+            words.join(" ")
+        ))),
+    }
+}
+
+fn print_json(value: &impl serde::Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(err) => eprintln!("Failed to format result: {}", err),
+    }
+}
+
+// Relative paths are taken from the working directory; the path policy wants absolute ones
+fn absolute(path: &str) -> Result<String, String> {
+    std::path::absolute(path)
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("Invalid path '{}': {}", path, e))
+}
+
+async fn require_pin(options: &Options) -> Result<(), CliError> {
+    let pin = options
+        .flag("pin")
+        .or_else(|| std::env::var(PIN_ENV).ok())
+        .unwrap_or_default();
+    match lock::check_pin(pin).await? {
+        None | Some(true) => Ok(()),
+        Some(false) => Err(CliError::Failed(format!(
+            "Wrong or missing app lock PIN (pass --pin or set {})",
+            PIN_ENV
+        ))),
+    }
+}
+
+async fn answers(port: u16, settings: &SharedSettings) -> bool {
+    let endpoint = health_endpoint(port, settings);
+    let Ok(client) = backend_client(HEALTH_TIMEOUT) else {
+        return false;
+    };
+    let mut request = client.get(&endpoint.url);
+    if let Some(token) = &endpoint.token {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .await
+        .is_ok_and(|response| response.status().is_success())
+}
+
+// Port of the backend that answers, trying the recorded one first
+async fn find_backend(settings: &SharedSettings) -> Option<u16> {
+    let first = sidecar::recorded_pid()
+        .map(|process| process.port)
+        .unwrap_or(DEFAULT_BACKEND_PORT);
+    for port in backend_port_candidates(first) {
+        if answers(port, settings).await {
+            return Some(port);
+        }
+    }
+    None
+}
+
+async fn backend_status(settings: &SharedSettings) -> Result<i32, CliError> {
+    let port = find_backend(settings).await;
+    print_json(&serde_json::json!({
+        "running": port.is_some(),
+        "port": port,
+        "process": sidecar::recorded_pid().filter(|_| port.is_some()),
+    }));
+    Ok(if port.is_some() { 0 } else { EXIT_NOT_RUNNING })
+}
+
+async fn backend_start(settings: &SharedSettings) -> Result<i32, CliError> {
+    if let Some(port) = find_backend(settings).await {
+        println!("Backend is already running on port {}", port);
+        return Ok(0);
+    }
+    let sidecar_path = sidecar::verified_sidecar_path()?;
+    if let Some(parent) = resolve_backend_db_path().parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create database directory: {}", e))?;
+    }
+    migrations::prepare_database().await;
+    let port = backend_port_candidates(DEFAULT_BACKEND_PORT)
+        .into_iter()
+        .find(|port| is_port_available(*port))
+        .ok_or_else(|| "No free port available for the backend".to_string())?;
+
+    let config = crate::settings::current_settings(settings);
+    let mut child = Command::new(&sidecar_path)
+        .envs(backend_env(port, &config)?)
+        .spawn()
+        .map_err(|e| format!("Failed to start {:?}: {}", sidecar_path, e))?;
+    sidecar::record_pid(child.id(), port);
+    record_audit("cli_backend_start", "backend", "success", None);
+    println!(
+        "Backend started on port {} (pid {}); press Ctrl+C to stop it",
+        port,
+        child.id()
+    );
+
+    let status = tauri::async_runtime::spawn_blocking(move || child.wait())
+        .await
+        .map_err(|e| format!("Backend supervisor failed: {}", e))?
+        .map_err(|e| format!("Failed to wait for the backend: {}", e))?;
+    sidecar::clear_pid();
+    println!("Backend exited with {}", status);
+    Ok(if status.success() { 0 } else { EXIT_FAILURE })
+}
+
+fn terminate(pid: u32) -> Result<(), String> {
+    let pid = pid.to_string();
+    let status = if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/PID", &pid, "/T", "/F"])
+            .status()
+    } else {
+        Command::new("kill").arg(&pid).status()
+    };
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Stopping process {} failed ({})", pid, status)),
+        Err(err) => Err(format!("Failed to stop process {}: {}", pid, err)),
+    }
+}
+
+async fn backend_stop(options: &Options, settings: &SharedSettings) -> Result<i32, CliError> {
+    require_pin(options).await?;
+    let Some(process) = sidecar::recorded_pid() else {
+        println!("No backend process is recorded");
+        return Ok(EXIT_NOT_RUNNING);
+    };
+    // A stale record could name an unrelated process by now
+    if !answers(process.port, settings).await {
+        sidecar::clear_pid();
+        println!("Backend is not running");
+        return Ok(EXIT_NOT_RUNNING);
+    }
+
+    let result = terminate(process.pid);
+    record_audit(
+        "cli_backend_stop",
+        "backend",
+        if result.is_ok() { "success" } else { "failed" },
+        result.as_ref().err().cloned(),
+    );
+    result?;
+    let deadline = tokio::time::Instant::now() + STOP_TIMEOUT;
+    while answers(process.port, settings).await {
+        if tokio::time::Instant::now() >= deadline {
+            return Err(CliError::Failed(format!(
+                "Backend (pid {}) is still answering after {} seconds",
+                process.pid,
+                STOP_TIMEOUT.as_secs()
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    sidecar::clear_pid();
+    println!("Backend (pid {}) stopped", process.pid);
+    Ok(0)
+}
+
+async fn export(options: &Options) -> Result<i32, CliError> {
+    let range = options
+        .flag("range")
+        .ok_or_else(|| CliError::Usage("export needs --range FROM..TO".to_string()))?;
+    let (from, to) = range
+        .split_once("..")
+        .ok_or_else(|| CliError::Usage(format!("Invalid range '{}'", range)))?;
+    let range = parse_range(&ExportRange {
+        from: from.to_string(),
+        to: to.to_string(),
+    })?;
+    require_pin(options).await?;
+
+    let output = options
+        .flag("output")
+        .unwrap_or_else(|| format!("attendance_{}_{}.csv", range.0, range.1));
+    let devices = options.flag("devices").map(|devices| {
+        devices
+            .split(',')
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .collect()
+    });
+    let result = write_attendance_csv(range, devices, &absolute(&output)?, None, |progress| {
+        eprint!("\rExported {}/{}", progress.exported, progress.total);
+    })
+    .await;
+    eprintln!();
+    print_json(&result?);
+    Ok(0)
+}
+
+async fn backup(options: &Options) -> Result<i32, CliError> {
+    require_pin(options).await?;
+    let destination = options
+        .flag("destination")
+        .map(|dir| absolute(&dir))
+        .transpose()?;
+    let result = backup_database(destination, None).await?;
+    print_json(&result);
+    Ok(0)
+}
@@ -0,0 +1,334 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use chrono::Local;
+use rusqlite::{params, Connection};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::{daily_rows, device_filter, parse_range, time_part, ExportRange, RANGE_FILTER};
+use crate::printing::powershell;
+use crate::{lock, read_log_file, BackendLogs};
+
+// Quick copy of what is on screen for pasting into tickets and emails: log lines or
+// attendance rows are formatted here as TSV (pastes into spreadsheet cells) or a Markdown table
+// and written to the system clipboard through the platform's own tool - Set-Clipboard on
+// Windows, pbcopy on macOS, wl-copy/xclip/xsel on Linux.
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 5000;
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ClipboardFilters {
+    // "tsv" (default) or "markdown"
+    format: Option<String>,
+    // Attendance kinds only; both default to today
+    from: Option<String>,
+    to: Option<String>,
+    user_id: Option<String>,
+    devices: Option<Vec<String>>,
+    // Log kinds only, e.g. "error"
+    level: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ClipboardResult {
+    kind: String,
+    format: String,
+    rows: usize,
+    characters: usize,
+}
+
+struct Table {
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+fn tsv(table: &Table) -> String {
+    let cell = |value: &str| value.replace(['\t', '\r', '\n'], " ");
+    let mut text = table.headers.join("\t");
+    for row in &table.rows {
+        text.push('\n');
+        text.push_str(
+            &row.iter()
+                .map(|value| cell(value))
+                .collect::<Vec<_>>()
+                .join("\t"),
+        );
+    }
+    text.push('\n');
+    text
+}
+
+fn markdown(table: &Table) -> String {
+    let cell = |value: &str| value.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+    let mut text = line(table.headers.iter().map(|h| h.to_string()).collect());
+    text.push_str(&line(
+        table.headers.iter().map(|_| "---".to_string()).collect(),
+    ));
+    for row in &table.rows {
+        text.push_str(&line(row.iter().map(|value| cell(value)).collect()));
+    }
+    text
+}
+
+fn punches(
+    connection: &Connection,
+    range: (chrono::NaiveDate, chrono::NaiveDate),
+    devices: Option<String>,
+    user_id: Option<String>,
+    limit: usize,
+) -> Result<Table, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT a.timestamp, a.user_id,
+                COALESCE((SELECT u.name FROM users u WHERE u.user_id = a.user_id LIMIT 1), ''),
+                COALESCE(a.device_id, ''), COALESCE(a.sync_status, '')
+             FROM attendance_logs a
+             WHERE {} AND (?4 IS NULL OR a.user_id = ?4)
+             ORDER BY a.timestamp, a.user_id
+             LIMIT ?5",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let rows = statement
+        .query_map(
+            params![
+                range.0.to_string(),
+                range.1.to_string(),
+                devices,
+                user_id,
+                limit as i64
+            ],
+            |row| {
+                Ok(vec![
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ])
+            },
+        )
+        .map_err(|e| format!("Failed to query attendance: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance: {}", e))?;
+    Ok(Table {
+        headers: &["Timestamp", "User ID", "Name", "Device", "Sync status"],
+        rows,
+    })
+}
+
+fn daily(
+    connection: &Connection,
+    range: (chrono::NaiveDate, chrono::NaiveDate),
+    devices: Option<String>,
+    user_id: Option<String>,
+    limit: usize,
+) -> Result<Table, String> {
+    let rows = daily_rows(connection, range, &devices)?
+        .into_iter()
+        .filter(|day| user_id.as_ref().is_none_or(|user| &day.user_id == user))
+        .take(limit)
+        .map(|day| {
+            vec![
+                day.date.clone(),
+                day.user_id.clone(),
+                day.name.clone(),
+                time_part(&day.first_in).to_string(),
+                if day.punches > 1 {
+                    time_part(&day.last_out).to_string()
+                } else {
+                    String::new()
+                },
+                day.punches.to_string(),
+                format!("{:.2}", day.hours),
+            ]
+        })
+        .collect();
+    Ok(Table {
+        headers: &[
+            "Date", "User ID", "Name", "First in", "Last out", "Punches", "Hours",
+        ],
+        rows,
+    })
+}
+
+fn level_matches(filter: &Option<String>, level: &str) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|wanted| wanted.eq_ignore_ascii_case(level))
+}
+
+// The newest `limit` lines of the shell's in-memory backend output
+fn backend_log_table(logs: &BackendLogs, level: &Option<String>, limit: usize) -> Table {
+    let logs = logs.lock().map(|logs| logs.clone()).unwrap_or_default();
+    let matching: Vec<_> = logs
+        .iter()
+        .filter(|log| level_matches(level, &log.level))
+        .collect();
+    let rows = matching[matching.len().saturating_sub(limit)..]
+        .iter()
+        .map(|log| {
+            vec![
+                log.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                log.level.clone(),
+                log.source.clone(),
+                log.message.clone(),
+            ]
+        })
+        .collect();
+    Table {
+        headers: &["Time (UTC)", "Level", "Source", "Message"],
+        rows,
+    }
+}
+
+fn app_log_table(level: &Option<String>, limit: usize) -> Result<Table, String> {
+    let entries = read_log_file(Some(limit))?;
+    let rows = entries
+        .into_iter()
+        .filter(|entry| level_matches(level, &entry.level))
+        .map(|entry| vec![entry.timestamp, entry.level, entry.module, entry.message])
+        .collect();
+    Ok(Table {
+        headers: &["Time", "Level", "Module", "Message"],
+        rows,
+    })
+}
+
+// Write stdin to a clipboard tool. xclip and wl-copy stay in the background to serve the
+// selection, so only their exit status is awaited, never their output.
+fn pipe_to(mut command: Command, text: &str) -> Result<(), String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("exited with {}", status))
+    }
+}
+
+fn write_clipboard(text: &str) -> Result<(), String> {
+    if cfg!(windows) {
+        return pipe_to(
+            powershell(
+                "[Console]::InputEncoding = [Text.Encoding]::UTF8; \
+                 Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+            ),
+            text,
+        )
+        .map_err(|e| format!("Failed to copy to the clipboard: {}", e));
+    }
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("pbcopy");
+        command.env("LANG", "en_US.UTF-8");
+        return pipe_to(command, text)
+            .map_err(|e| format!("Failed to copy to the clipboard: {}", e));
+    }
+
+    let mut tools: Vec<(&str, &[&str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        tools.push(("wl-copy", &[]));
+    }
+    tools.push(("xclip", &["-selection", "clipboard"]));
+    tools.push(("xsel", &["--clipboard", "--input"]));
+    let mut errors = Vec::new();
+    for (program, args) in tools {
+        let mut command = Command::new(program);
+        command.args(args);
+        match pipe_to(command, text) {
+            Ok(()) => return Ok(()),
+            Err(err) => errors.push(format!("{}: {}", program, err)),
+        }
+    }
+    Err(format!(
+        "Failed to copy to the clipboard; install wl-clipboard, xclip or xsel ({})",
+        errors.join("; ")
+    ))
+}
+
+// payload_kind: "attendance" (raw punches), "daily" (first in/last out per day),
+// "backend_logs" (the backend output captured by the shell) or "app_log" (the log file)
+#[tauri::command]
+pub async fn copy_to_clipboard(
+    payload_kind: String,
+    filters: Option<ClipboardFilters>,
+    backend_logs: State<'_, BackendLogs>,
+) -> Result<ClipboardResult, String> {
+    lock::ensure_unlocked()?;
+    let filters = filters.unwrap_or_default();
+    let format = filters
+        .format
+        .clone()
+        .unwrap_or_else(|| "tsv".to_string())
+        .to_ascii_lowercase();
+    if !["tsv", "markdown"].contains(&format.as_str()) {
+        return Err(format!(
+            "Unknown clipboard format '{}'; use tsv or markdown",
+            format
+        ));
+    }
+    let limit = filters.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let user_id = filters
+        .user_id
+        .as_ref()
+        .map(|user| user.trim().to_string())
+        .filter(|user| !user.is_empty());
+
+    let result = async {
+        let table = match payload_kind.as_str() {
+            "attendance" | "daily" => {
+                let today = Local::now().date_naive().to_string();
+                let range = parse_range(&ExportRange {
+                    from: filters.from.clone().unwrap_or_else(|| today.clone()),
+                    to: filters.to.clone().unwrap_or(today),
+                })?;
+                let devices = device_filter(&filters.devices);
+                let daily_kind = payload_kind == "daily";
+                run_db(move |connection| {
+                    if daily_kind {
+                        daily(connection, range, devices, user_id, limit)
+                    } else {
+                        punches(connection, range, devices, user_id, limit)
+                    }
+                })
+                .await?
+            }
+            "backend_logs" => backend_log_table(&backend_logs, &filters.level, limit),
+            "app_log" => app_log_table(&filters.level, limit)?,
+            other => return Err(format!("Unknown clipboard payload '{}'", other)),
+        };
+        let text = if format == "markdown" {
+            markdown(&table)
+        } else {
+            tsv(&table)
+        };
+        let rows = table.rows.len();
+        let characters = text.chars().count();
+        tauri::async_runtime::spawn_blocking(move || write_clipboard(&text))
+            .await
+            .map_err(|e| format!("Failed to copy to the clipboard: {}", e))??;
+        Ok(ClipboardResult {
+            kind: payload_kind.clone(),
+            format,
+            rows,
+            characters,
+        })
+    }
+    .await;
+    audited("copy_to_clipboard", &payload_kind, result)
+}
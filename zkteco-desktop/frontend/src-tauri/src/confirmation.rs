@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tauri::State;
+
+use crate::audit::record_audit;
+use crate::lock;
+use crate::secrets::random_hex;
+
+// Destructive device actions are two-step: the UI asks for a token describing the exact
+// action and target, then passes it back to the command within the validity window. The
+// riskiest ones need a second factor before the token is issued.
+const TOKEN_VALIDITY: Duration = Duration::from_secs(60);
+// Actions that also need a second factor when the token is requested: the lock PIN when one
+// is set, otherwise the action's typed phrase (step_up_phrase)
+const STEP_UP_ACTIONS: [&str; 3] = [
+    "clear_device_attendance",
+    "restore_database",
+    "upload_firmware",
+];
+
+#[derive(Debug, Clone)]
+pub struct PendingConfirmation {
+    action: String,
+    target: String,
+    issued_at: Instant,
+}
+
+pub type ConfirmationTokens = Arc<Mutex<HashMap<String, PendingConfirmation>>>;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConfirmationToken {
+    token: String,
+    action: String,
+    target: String,
+    expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StepUpRequirement {
+    action: String,
+    // "none", "pin" or "phrase"
+    method: String,
+    phrase: Option<String>,
+}
+
+// "restore_database" -> "RESTORE DATABASE"
+fn step_up_phrase(action: &str) -> String {
+    action.replace('_', " ").to_uppercase()
+}
+
+fn step_up_method(action: &str) -> &'static str {
+    if !STEP_UP_ACTIONS.contains(&action) {
+        "none"
+    } else if lock::is_enabled() {
+        "pin"
+    } else {
+        "phrase"
+    }
+}
+
+async fn verify_step_up(action: &str, target: &str, proof: Option<String>) -> Result<(), String> {
+    let method = step_up_method(action);
+    if method == "none" {
+        return Ok(());
+    }
+    let Some(proof) = proof.filter(|p| !p.trim().is_empty()) else {
+        return Err(format!(
+            "{} needs re-entering the app PIN or typing the confirmation phrase",
+            action
+        ));
+    };
+    let verified = match method {
+        "pin" => lock::check_pin(proof).await? != Some(false),
+        _ => proof.trim() == step_up_phrase(action),
+    };
+    if verified {
+        return Ok(());
+    }
+    record_audit(
+        action,
+        target,
+        "rejected",
+        Some(format!("wrong step-up {}", method)),
+    );
+    Err(match method {
+        "pin" => "Wrong PIN".to_string(),
+        _ => format!("Type \"{}\" to confirm", step_up_phrase(action)),
+    })
+}
+
+fn random_token() -> String {
+    random_hex(16)
+}
+
+// Consume a token, failing unless it was issued for this action and target and hasn't expired
+pub fn consume_token(
+    tokens: &ConfirmationTokens,
+    token: &str,
+    action: &str,
+    target: &str,
+) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let pending = tokens
+        .lock()
+        .map_err(|e| format!("Failed to lock confirmation tokens: {}", e))?
+        .remove(token);
+
+    let valid = match pending {
+        Some(pending) => {
+            pending.action == action
+                && pending.target == target
+                && pending.issued_at.elapsed() <= TOKEN_VALIDITY
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        record_audit(
+            action,
+            target,
+            "rejected",
+            Some("invalid or expired confirmation token".to_string()),
+        );
+        Err("Confirmation token is invalid or expired - request a new one".to_string())
+    }
+}
+
+// What request_confirmation_token needs as proof for an action
+#[tauri::command]
+pub fn get_step_up_requirement(action: String) -> StepUpRequirement {
+    let method = step_up_method(&action);
+    StepUpRequirement {
+        phrase: (method == "phrase").then(|| step_up_phrase(&action)),
+        method: method.to_string(),
+        action,
+    }
+}
+
+// proof is the app PIN or typed phrase for STEP_UP_ACTIONS, ignored otherwise
+#[tauri::command]
+pub async fn request_confirmation_token(
+    action: String,
+    target: String,
+    proof: Option<String>,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<ConfirmationToken, String> {
+    lock::ensure_unlocked()?;
+    verify_step_up(&action, &target, proof).await?;
+    let mut tokens = tokens
+        .lock()
+        .map_err(|e| format!("Failed to lock confirmation tokens: {}", e))?;
+    tokens.retain(|_, pending| pending.issued_at.elapsed() <= TOKEN_VALIDITY);
+
+    let token = random_token();
+    tokens.insert(
+        token.clone(),
+        PendingConfirmation {
+            action: action.clone(),
+            target: target.clone(),
+            issued_at: Instant::now(),
+        },
+    );
+
+    Ok(ConfirmationToken {
+        token,
+        action,
+        target,
+        expires_in_secs: TOKEN_VALIDITY.as_secs(),
+    })
+}
@@ -0,0 +1,332 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::Local;
+use tauri::{AppHandle, Emitter};
+
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Crash reports for the shell. A panic hook writes crashes/<id>.json with the message,
+// location, thread, a backtrace and the last lines of zkteco_app.log; on Windows an unhandled
+// exception filter also covers native crashes that never reach the hook, writing a minidump
+// (<id>.dmp) next to the report. Reports stay pending until the next launch announces them
+// with "crash-reports-found". export_diagnostics takes them along when asked to and moves
+// them to crashes/reported/; dismiss_crash_reports deletes them. Minidumps hold process
+// memory, so they only go into unredacted diagnostics.
+const CRASH_DIR: &str = "crashes";
+const REPORTED_DIR: &str = "reported";
+const CRASH_LOG_LINES: usize = 50;
+// A panic loop shouldn't fill the disk; later crashes are dropped until these are handled
+const MAX_PENDING_REPORTS: usize = 20;
+// Give the webview time to subscribe before announcing pending reports
+const CHECK_DELAY: Duration = Duration::from_secs(5);
+
+// Resolved once at startup, so writing a report doesn't depend on state a crash may have broken
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CrashReport {
+    id: String,
+    created_at: String,
+    kind: String, // "panic" or "native"
+    app_version: String,
+    os: String,
+    arch: String,
+    thread: String,
+    message: String,
+    location: Option<String>,
+    backtrace: Option<String>,
+    minidump: Option<String>, // file name next to the report
+    log_tail: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CrashSummary {
+    id: String,
+    created_at: String,
+    kind: String,
+    message: String,
+    location: Option<String>,
+    has_minidump: bool,
+}
+
+fn data_dir() -> PathBuf {
+    DATA_DIR.get().cloned().unwrap_or_else(resolve_app_data_dir)
+}
+
+fn crash_dir() -> PathBuf {
+    data_dir().join(CRASH_DIR)
+}
+
+fn report_id() -> String {
+    format!("crash_{}", Local::now().format("%Y%m%d_%H%M%S_%3f"))
+}
+
+fn recent_log_lines() -> Vec<String> {
+    let Ok(content) = fs::read_to_string(data_dir().join("zkteco_app.log")) else {
+        return Vec::new();
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.len().saturating_sub(CRASH_LOG_LINES);
+    lines[start..].iter().map(|line| line.to_string()).collect()
+}
+
+fn pending_files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == extension))
+        .collect();
+    files.sort();
+    files
+}
+
+fn write_report(
+    id: String,
+    kind: &str,
+    message: String,
+    location: Option<String>,
+    backtrace: Option<String>,
+    minidump: Option<String>,
+) -> Result<PathBuf, String> {
+    let dir = crash_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    let report = CrashReport {
+        created_at: Local::now().to_rfc3339(),
+        kind: kind.to_string(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        thread: std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string(),
+        message,
+        location,
+        backtrace,
+        minidump,
+        log_tail: recent_log_lines(),
+        id,
+    };
+    let path = dir.join(format!("{}.json", report.id));
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|e| format!("Failed to serialize crash report: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    Ok(path)
+}
+
+// Install the hooks; call once, after the active profile is known
+pub fn install_crash_handler() {
+    let _ = DATA_DIR.set(resolve_app_data_dir());
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if pending_files(&crash_dir(), "json").len() < MAX_PENDING_REPORTS {
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_string());
+            let location = info
+                .location()
+                .map(|location| format!("{}:{}", location.file(), location.line()));
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            match write_report(
+                report_id(),
+                "panic",
+                message,
+                location,
+                Some(backtrace),
+                None,
+            ) {
+                Ok(path) => eprintln!("Crash report written to {:?}", path),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+        default_hook(info);
+    }));
+    install_native_handler();
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn on_unhandled_exception(
+    pointers: *const windows_sys::Win32::System::Diagnostics::Debug::EXCEPTION_POINTERS,
+) -> i32 {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::System::Diagnostics::Debug::{
+        MiniDumpNormal, MiniDumpWithThreadInfo, MiniDumpWriteDump, EXCEPTION_CONTINUE_SEARCH,
+        MINIDUMP_EXCEPTION_INFORMATION,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId,
+    };
+
+    if pending_files(&crash_dir(), "json").len() >= MAX_PENDING_REPORTS {
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+    let code = if pointers.is_null() || unsafe { (*pointers).ExceptionRecord.is_null() } {
+        0
+    } else {
+        unsafe { (*(*pointers).ExceptionRecord).ExceptionCode as u32 }
+    };
+    let id = report_id();
+    let dir = crash_dir();
+    let _ = fs::create_dir_all(&dir);
+    let dump_name = format!("{}.dmp", id);
+    let dumped = fs::File::create(dir.join(&dump_name)).is_ok_and(|file| {
+        let exception = MINIDUMP_EXCEPTION_INFORMATION {
+            ThreadId: unsafe { GetCurrentThreadId() },
+            ExceptionPointers: pointers as *mut _,
+            ClientPointers: 0,
+        };
+        unsafe {
+            MiniDumpWriteDump(
+                GetCurrentProcess(),
+                GetCurrentProcessId(),
+                file.as_raw_handle() as _,
+                MiniDumpNormal | MiniDumpWithThreadInfo,
+                &exception,
+                std::ptr::null(),
+                std::ptr::null(),
+            ) != 0
+        }
+    });
+    if !dumped {
+        let _ = fs::remove_file(dir.join(&dump_name));
+    }
+    let _ = write_report(
+        id,
+        "native",
+        format!("Unhandled exception 0x{:08X}", code),
+        None,
+        None,
+        dumped.then_some(dump_name),
+    );
+    // Let Windows Error Reporting and attached debuggers see it as well
+    EXCEPTION_CONTINUE_SEARCH
+}
+
+#[cfg(windows)]
+fn install_native_handler() {
+    unsafe {
+        windows_sys::Win32::System::Diagnostics::Debug::SetUnhandledExceptionFilter(Some(
+            on_unhandled_exception,
+        ));
+    }
+}
+
+#[cfg(not(windows))]
+fn install_native_handler() {}
+
+fn read_report(path: &Path) -> Option<CrashReport> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn pending_reports() -> Vec<(PathBuf, CrashReport)> {
+    pending_files(&crash_dir(), "json")
+        .into_iter()
+        .filter_map(|path| read_report(&path).map(|report| (path, report)))
+        .collect()
+}
+
+fn summary(report: &CrashReport) -> CrashSummary {
+    CrashSummary {
+        id: report.id.clone(),
+        created_at: report.created_at.clone(),
+        kind: report.kind.clone(),
+        message: report.message.clone(),
+        location: report.location.clone(),
+        has_minidump: report.minidump.is_some(),
+    }
+}
+
+// Zip entries for the pending reports: (entry name, source file, is a minidump)
+pub fn diagnostics_entries() -> Vec<(String, PathBuf, bool)> {
+    let dir = crash_dir();
+    let mut entries = Vec::new();
+    for (path, report) in pending_reports() {
+        entries.push((format!("crashes/{}.json", report.id), path.clone(), false));
+        if let Some(dump) = report.minidump {
+            entries.push((format!("crashes/{}", dump), dir.join(dump), true));
+        }
+    }
+    entries
+}
+
+// Move reports that went out with a diagnostics export so they aren't offered again
+pub fn mark_reported(files: &[PathBuf]) {
+    let reported = crash_dir().join(REPORTED_DIR);
+    if let Err(err) = fs::create_dir_all(&reported) {
+        eprintln!("Failed to create {:?}: {}", reported, err);
+        return;
+    }
+    for file in files {
+        if let Some(name) = file.file_name() {
+            if let Err(err) = fs::rename(file, reported.join(name)) {
+                eprintln!("Failed to move crash report {:?}: {}", file, err);
+            }
+        }
+    }
+}
+
+// Announce crash reports left by the previous run
+pub fn spawn_crash_check(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(CHECK_DELAY).await;
+        let summaries: Vec<CrashSummary> = pending_reports()
+            .iter()
+            .map(|(_, report)| summary(report))
+            .collect();
+        if summaries.is_empty() {
+            return;
+        }
+        append_app_log(&format!(
+            "{} crash report(s) from earlier runs are pending",
+            summaries.len()
+        ));
+        if let Err(err) = app.emit("crash-reports-found", &summaries) {
+            eprintln!("Failed to emit crash-reports-found event: {}", err);
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_crash_reports() -> Vec<CrashSummary> {
+    pending_reports()
+        .iter()
+        .map(|(_, report)| summary(report))
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_crash_report(id: String) -> Result<CrashReport, String> {
+    pending_reports()
+        .into_iter()
+        .find(|(_, report)| report.id == id)
+        .map(|(_, report)| report)
+        .ok_or_else(|| format!("Crash report {} not found", id))
+}
+
+// Delete pending reports and their minidumps; returns how many reports were removed
+#[tauri::command]
+pub fn dismiss_crash_reports() -> Result<usize, String> {
+    lock::ensure_unlocked()?;
+    let dir = crash_dir();
+    let mut removed = 0;
+    for extension in ["json", "dmp"] {
+        for path in pending_files(&dir, extension) {
+            fs::remove_file(&path).map_err(|e| format!("Failed to delete {:?}: {}", path, e))?;
+            if extension == "json" {
+                removed += 1;
+            }
+        }
+    }
+    append_app_log(&format!("Dismissed {} crash report(s)", removed));
+    Ok(removed)
+}
@@ -0,0 +1,260 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::encryption::unlock;
+use crate::{append_app_log, resolve_backend_db_path};
+
+// Read-only access to the backend's zkteco_app.db so the shell can answer the common queries
+// (recent punches, user counts, tray badges) while Flask is stopped or crashed. The backend
+// stays the only writer; readers wait out its write locks instead of failing immediately.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_RECENT_LIMIT: u32 = 50;
+const MAX_RECENT_LIMIT: u32 = 1000;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttendanceRow {
+    id: i64,
+    user_id: String,
+    user_name: Option<String>,
+    device_id: Option<String>,
+    timestamp: String,
+    method: i64,
+    action: i64,
+    sync_status: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct IntegrityReport {
+    mode: String, // "quick" or "full"
+    ok: bool,
+    problems: Vec<String>,
+    duration_ms: u128,
+    recommendation: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct AttendanceBadges {
+    pub today: u64,   // punches since local midnight
+    pub pending: u64, // records not yet synced upstream
+}
+
+// Attendance moved out of the primary database by the archive job
+pub fn archive_db_path() -> PathBuf {
+    resolve_backend_db_path().with_file_name("zkteco_archive.db")
+}
+
+pub fn open_read_only() -> Result<Connection, String> {
+    let path = resolve_backend_db_path();
+    if !path.exists() {
+        return Err(format!("Backend database not found at {:?}", path));
+    }
+    let connection = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open backend database: {}", e))?;
+    unlock(&connection, &path)?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to configure backend database: {}", e))?;
+    Ok(connection)
+}
+
+// For the maintenance jobs that do write (archiving, duplicate merges); same busy timeout
+pub fn open_read_write() -> Result<Connection, String> {
+    let path = resolve_backend_db_path();
+    if !path.exists() {
+        return Err(format!("Backend database not found at {:?}", path));
+    }
+    let connection = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open backend database: {}", e))?;
+    unlock(&connection, &path)?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to configure backend database: {}", e))?;
+    Ok(connection)
+}
+
+// Database work is blocking; keep it off the async runtime threads
+pub async fn run_db<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || task(&open_read_only()?))
+        .await
+        .map_err(|e| format!("Database task failed: {}", e))?
+}
+
+// Rows reported by PRAGMA integrity_check (or the faster quick_check); empty when healthy
+pub fn integrity_problems(connection: &Connection, quick: bool) -> Result<Vec<String>, String> {
+    let pragma = if quick {
+        "PRAGMA quick_check"
+    } else {
+        "PRAGMA integrity_check"
+    };
+    let mut statement = connection
+        .prepare(pragma)
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?;
+    let rows = statement
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read integrity check result: {}", e))?;
+    Ok(rows.into_iter().filter(|row| row != "ok").collect())
+}
+
+pub fn attendance_badges(connection: &Connection) -> Result<AttendanceBadges, String> {
+    connection
+        .query_row(
+            "SELECT
+                (SELECT COUNT(*) FROM attendance_logs WHERE timestamp >= date('now', 'localtime')),
+                (SELECT COUNT(*) FROM attendance_logs WHERE sync_status = 'pending')",
+            [],
+            |row| {
+                Ok(AttendanceBadges {
+                    today: row.get(0)?,
+                    pending: row.get(1)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to count attendance: {}", e))
+}
+
+const ATTENDANCE_COLUMNS: &str = "SELECT a.id, a.user_id,
+        (SELECT u.name FROM users u WHERE u.user_id = a.user_id LIMIT 1),
+        a.device_id, a.timestamp, a.method, a.action, a.sync_status";
+
+fn query_attendance(
+    connection: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<AttendanceRow>, String> {
+    let mut statement = connection
+        .prepare(sql)
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let rows = statement
+        .query_map(params, |row| {
+            Ok(AttendanceRow {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                user_name: row.get(2)?,
+                device_id: row.get(3)?,
+                timestamp: row.get(4)?,
+                method: row.get(5)?,
+                action: row.get(6)?,
+                sync_status: row.get(7)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance row: {}", e))
+}
+
+#[tauri::command]
+pub async fn get_recent_attendance(
+    limit: Option<u32>,
+    device_id: Option<String>,
+) -> Result<Vec<AttendanceRow>, String> {
+    let limit = limit.unwrap_or(DEFAULT_RECENT_LIMIT).min(MAX_RECENT_LIMIT);
+    run_db(move |connection| {
+        query_attendance(
+            connection,
+            &format!(
+                "{} FROM attendance_logs a
+                 WHERE ?1 IS NULL OR a.device_id = ?1
+                 ORDER BY a.timestamp DESC
+                 LIMIT ?2",
+                ATTENDANCE_COLUMNS
+            ),
+            params![device_id, limit],
+        )
+    })
+    .await
+}
+
+// Archived records between two dates (inclusive, YYYY-MM-DD), newest first; names are resolved
+// against the live users table
+#[tauri::command]
+pub async fn get_archived_attendance(
+    from: String,
+    to: String,
+    user_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<AttendanceRow>, String> {
+    let limit = limit.unwrap_or(DEFAULT_RECENT_LIMIT).min(MAX_RECENT_LIMIT);
+    run_db(move |connection| {
+        let path = archive_db_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        connection
+            .execute(
+                "ATTACH DATABASE ?1 AS archive",
+                [path.to_string_lossy().to_string()],
+            )
+            .map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+        query_attendance(
+            connection,
+            &format!(
+                "{} FROM archive.attendance_logs a
+                 WHERE a.timestamp >= ?1 AND a.timestamp < date(?2, '+1 day')
+                   AND (?3 IS NULL OR a.user_id = ?3)
+                 ORDER BY a.timestamp DESC
+                 LIMIT ?4",
+                ATTENDANCE_COLUMNS
+            ),
+            params![from, to, user_id, limit],
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_user_count(device_id: Option<String>) -> Result<u64, String> {
+    run_db(move |connection| {
+        connection
+            .query_row(
+                "SELECT COUNT(*) FROM users WHERE ?1 IS NULL OR device_id = ?1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count users: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_attendance_badges() -> Result<AttendanceBadges, String> {
+    run_db(attendance_badges).await
+}
+
+// quick_check skips the index consistency checks and is much faster on large databases
+#[tauri::command]
+pub async fn check_database_integrity(quick: Option<bool>) -> Result<IntegrityReport, String> {
+    let quick = quick.unwrap_or(false);
+    let started = Instant::now();
+    let problems = run_db(move |connection| integrity_problems(connection, quick)).await?;
+    let ok = problems.is_empty();
+    if !ok {
+        append_app_log(&format!(
+            "Database integrity check found {} problems: {}",
+            problems.len(),
+            problems.first().cloned().unwrap_or_default()
+        ));
+    }
+    Ok(IntegrityReport {
+        mode: if quick { "quick" } else { "full" }.to_string(),
+        ok,
+        problems,
+        duration_ms: started.elapsed().as_millis(),
+        recommendation: (!ok).then(|| {
+            "The database is corrupted: take a backup of what is readable, then restore the most recent healthy backup".to_string()
+        }),
+    })
+}
@@ -0,0 +1,254 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tauri::{Emitter, State};
+use tokio::sync::broadcast::error::TryRecvError;
+
+use crate::backend_auth::backend_client;
+use crate::monitor::{BackendState, DeviceState, MonitorBus, MonitorEvent};
+use crate::registry::{active_pull_devices, DeviceEntry, DeviceRegistry};
+use crate::{append_app_log, backend_base_url, current_backend_port, BackendPort};
+
+const MANAGER_INTERVAL: Duration = Duration::from_secs(30);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceStatus {
+    device_id: String,
+    name: String,
+    ip: String,
+    online: bool,
+    source: String, // "backend" while the backend owns device connections, otherwise "native"
+    last_seen: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    consecutive_failures: u32,
+    #[serde(skip)]
+    next_attempt: Option<Instant>,
+}
+
+pub type DeviceManager = Arc<Mutex<HashMap<String, DeviceStatus>>>;
+
+#[derive(serde::Deserialize)]
+struct CaptureStatusResponse {
+    devices: Vec<CaptureDeviceStatus>,
+}
+
+#[derive(serde::Deserialize)]
+struct CaptureDeviceStatus {
+    device_id: String,
+    is_healthy: bool,
+}
+
+// Exponential backoff from `base`, capped at `max`, with up to 20% random jitter so devices
+// that dropped together don't all reconnect in the same instant
+pub fn backoff_with_jitter(base: Duration, failures: u32, max: Duration) -> Duration {
+    let delay = base
+        .saturating_mul(2u32.saturating_pow(failures.min(16)))
+        .min(max);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(failures);
+    let jitter = hasher.finish() % 1000;
+    delay + delay.mul_f64(jitter as f64 / 5000.0)
+}
+
+// Exponential backoff between native reconnection attempts
+fn retry_delay(failures: u32) -> Duration {
+    backoff_with_jitter(MANAGER_INTERVAL, failures, MAX_RETRY_DELAY)
+}
+
+fn new_status(device: &DeviceEntry) -> DeviceStatus {
+    DeviceStatus {
+        device_id: device.id.clone(),
+        name: device.name.clone(),
+        ip: device.ip.clone(),
+        online: false,
+        source: "native".to_string(),
+        last_seen: None,
+        last_error: None,
+        consecutive_failures: 0,
+        next_attempt: None,
+    }
+}
+
+async fn fetch_backend_health(port: u16) -> Option<HashMap<String, bool>> {
+    let client = backend_client(Duration::from_secs(5)).ok()?;
+    let body = client
+        .get(format!("{}/devices/capture/status", backend_base_url(port)))
+        .send()
+        .await
+        .ok()?
+        .json::<CaptureStatusResponse>()
+        .await
+        .ok()?;
+    Some(
+        body.devices
+            .into_iter()
+            .map(|d| (d.device_id, d.is_healthy))
+            .collect(),
+    )
+}
+
+// Short on-demand session: connect, read counters, disconnect
+async fn probe_device(device: DeviceEntry) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut session = device.open_session(PROBE_TIMEOUT)?;
+        session.read_sizes()?;
+        session.disconnect()
+    })
+    .await
+    .map_err(|e| format!("Device probe task failed: {}", e))?
+}
+
+fn apply_result(status: &mut DeviceStatus, source: &str, result: Result<(), String>) {
+    status.source = source.to_string();
+    match result {
+        Ok(()) => {
+            status.online = true;
+            status.last_seen = Some(Utc::now());
+            status.last_error = None;
+            status.consecutive_failures = 0;
+            status.next_attempt = None;
+        }
+        Err(err) => {
+            status.online = false;
+            status.last_error = Some(err);
+            status.consecutive_failures += 1;
+            status.next_attempt = Some(Instant::now() + retry_delay(status.consecutive_failures));
+        }
+    }
+}
+
+fn summarize(statuses: &HashMap<String, DeviceStatus>) -> DeviceState {
+    let mut offline: Vec<String> = statuses
+        .values()
+        .filter(|s| !s.online)
+        .map(|s| s.name.clone())
+        .collect();
+    offline.sort();
+    DeviceState {
+        total: statuses.len(),
+        online: statuses.len() - offline.len(),
+        offline,
+    }
+}
+
+// Background task tracking every active pull device. While the backend is running it owns the
+// device connections (terminals accept few concurrent sessions), so its capture health is used;
+// otherwise devices are probed natively with backoff between reconnection attempts.
+pub fn spawn_device_manager(
+    app: tauri::AppHandle,
+    manager: DeviceManager,
+    registry: DeviceRegistry,
+    bus: MonitorBus,
+    backend_port: BackendPort,
+) {
+    let mut receiver = bus.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let mut backend_running = false;
+        let mut last_summary: Option<DeviceState> = None;
+
+        loop {
+            loop {
+                match receiver.try_recv() {
+                    Ok(MonitorEvent::Backend(state)) => {
+                        backend_running = state == BackendState::Running
+                    }
+                    Ok(_) | Err(TryRecvError::Lagged(_)) => {}
+                    Err(_) => break,
+                }
+            }
+
+            let devices = active_pull_devices(&registry);
+
+            let backend_health = if backend_running {
+                fetch_backend_health(current_backend_port(&backend_port)).await
+            } else {
+                None
+            };
+
+            let mut results = Vec::new();
+            for device in &devices {
+                let result = match &backend_health {
+                    Some(health) => Some((
+                        "backend",
+                        match health.get(&device.id) {
+                            Some(true) => Ok(()),
+                            Some(false) => Err("Backend reports device unhealthy".to_string()),
+                            None => Err("Device is not being captured by the backend".to_string()),
+                        },
+                    )),
+                    None => {
+                        let due = manager
+                            .lock()
+                            .ok()
+                            .and_then(|m| m.get(&device.id).and_then(|s| s.next_attempt))
+                            .map(|at| Instant::now() >= at)
+                            .unwrap_or(true);
+                        if due {
+                            Some(("native", probe_device(device.clone()).await))
+                        } else {
+                            None
+                        }
+                    }
+                };
+                if let Some(result) = result {
+                    results.push((device, result));
+                }
+            }
+
+            let summary = match manager.lock() {
+                Ok(mut statuses) => {
+                    statuses.retain(|id, _| devices.iter().any(|d| &d.id == id));
+                    for (device, (source, result)) in results {
+                        let status = statuses
+                            .entry(device.id.clone())
+                            .or_insert_with(|| new_status(device));
+                        let was_online = status.online;
+                        let is_first = status.last_seen.is_none() && status.last_error.is_none();
+                        apply_result(status, source, result);
+
+                        if !is_first && was_online != status.online {
+                            append_app_log(&format!(
+                                "Device {} is now {}",
+                                status.name,
+                                if status.online { "online" } else { "offline" }
+                            ));
+                            let _ = app.emit("device-status-changed", status.clone());
+                            let _ = bus.send(MonitorEvent::DeviceStatus(status.clone()));
+                        }
+                    }
+                    Some(summarize(&statuses))
+                }
+                Err(_) => None,
+            };
+
+            if let Some(summary) = summary.filter(|s| last_summary.as_ref() != Some(s)) {
+                let _ = app.emit("device-state-changed", &summary);
+                let _ = bus.send(MonitorEvent::Devices(summary.clone()));
+                last_summary = Some(summary);
+            }
+
+            tokio::time::sleep(MANAGER_INTERVAL).await;
+        }
+    });
+}
+
+// Every tracked device, sorted by name
+pub fn device_statuses(manager: &DeviceManager) -> Result<Vec<DeviceStatus>, String> {
+    let statuses = manager
+        .lock()
+        .map_err(|e| format!("Failed to read device status: {}", e))?;
+    let mut statuses: Vec<DeviceStatus> = statuses.values().cloned().collect();
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(statuses)
+}
+
+#[tauri::command]
+pub fn get_devices_status(manager: State<DeviceManager>) -> Result<Vec<DeviceStatus>, String> {
+    device_statuses(&manager)
+}
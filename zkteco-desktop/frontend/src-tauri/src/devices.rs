@@ -0,0 +1,1482 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, State};
+
+use crate::audit::{audited, record_audit};
+use crate::backend_auth::backend_client;
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::drift::{measure_drift, record_drift};
+use crate::lock;
+use crate::path_policy::{check_path, Access};
+use crate::registry::{
+    active_pull_devices, lookup_device, update_device_address, DeviceEntry, DeviceRegistry,
+};
+use crate::secrets::hex;
+use crate::settings::{current_settings, SharedSettings};
+use crate::timezones::{normalize, normalize_stored, parse_zone, registered_zone, NormalizedTime};
+use crate::zk::{
+    describe_operation, AttendanceRecord, DeviceSizes, DeviceUser, ZkSession, CMD_REG_EVENT,
+    DEFAULT_DEVICE_PORT, EF_HIDNUM,
+};
+use crate::{
+    append_app_log, backend_base_url, current_backend_port, resolve_app_data_dir, BackendPort,
+};
+
+// Native (backend independent) device operations built on the zk protocol module
+pub const NATIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NativeConnectionTest {
+    ip: String,
+    port: u16,
+    handshake_ms: f64,
+    firmware_version: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NativeDeviceInfo {
+    ip: String,
+    port: u16,
+    serial_number: String,
+    device_name: String,
+    firmware_version: String,
+    platform: String,
+    sizes: DeviceSizes,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TimeSyncResult {
+    device_id: String,
+    previous_device_time: String,
+    new_device_time: String,
+    drift_seconds: i64,
+}
+
+// One line of the attendance queue file, shaped like the backend's attendance_logs rows.
+// (device_id, user_id, timestamp) is the deduplication key.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StagedAttendance {
+    pub device_id: String,
+    pub serial_number: Option<String>,
+    user_id: String,
+    pub timestamp: String,
+    method: u8,
+    action: u8,
+    pulled_at: String,
+    // UTC reading of the device-local timestamp, per the device's registered zone
+    #[serde(flatten, default)]
+    pub normalized: Option<NormalizedTime>,
+}
+
+impl StagedAttendance {
+    // A punch reported by a realtime event rather than read from the device log
+    pub fn captured(
+        device_id: &str,
+        serial_number: Option<String>,
+        user_id: &str,
+        timestamp: &str,
+        method: u8,
+        action: u8,
+    ) -> Self {
+        StagedAttendance {
+            device_id: device_id.to_string(),
+            serial_number,
+            user_id: user_id.to_string(),
+            timestamp: timestamp.to_string(),
+            method,
+            action,
+            pulled_at: Utc::now().to_rfc3339(),
+            normalized: normalize_stored(timestamp, registered_zone(device_id)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NativePullResult {
+    pub device_id: String,
+    pub records_on_device: usize,
+    pub staged: usize,
+    pub queue_path: String,
+}
+
+const DEVICE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Serializes writers of the queue file (native pulls, the poller, queue hand-off)
+static QUEUE_LOCK: Mutex<()> = Mutex::new(());
+
+fn attendance_queue_path() -> PathBuf {
+    resolve_app_data_dir().join("attendance_queue.jsonl")
+}
+
+fn parse_since(since: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(since, DEVICE_TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(since, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(since, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default())
+        })
+        .map_err(|_| format!("Invalid 'since' timestamp: {}", since))
+}
+
+// Keys of records already waiting in the queue, so repeated pulls don't duplicate them
+fn staged_keys(path: &PathBuf) -> HashSet<(String, String, String)> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<StagedAttendance>(line).ok())
+        .map(|record| (record.device_id, record.user_id, record.timestamp))
+        .collect()
+}
+
+pub fn to_staged(device: &DeviceEntry, records: &[AttendanceRecord]) -> Vec<StagedAttendance> {
+    let pulled_at = Utc::now().to_rfc3339();
+    let zone = device
+        .timezone
+        .as_deref()
+        .and_then(|name| parse_zone(name).ok());
+    records
+        .iter()
+        .map(|record| StagedAttendance {
+            device_id: device.id.clone(),
+            serial_number: device.serial_number.clone(),
+            user_id: record.user_id.clone(),
+            timestamp: record.timestamp.format(DEVICE_TIME_FORMAT).to_string(),
+            method: record.verify_mode,
+            action: record.punch,
+            pulled_at: pulled_at.clone(),
+            normalized: normalize(&record.timestamp, zone),
+        })
+        .collect()
+}
+
+pub fn stage_records(records: &[StagedAttendance]) -> Result<usize, String> {
+    let _guard = QUEUE_LOCK.lock();
+    let path = attendance_queue_path();
+    let existing = staged_keys(&path);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open attendance queue: {}", e))?;
+
+    let mut staged = 0;
+    for record in records {
+        let key = (
+            record.device_id.clone(),
+            record.user_id.clone(),
+            record.timestamp.clone(),
+        );
+        if existing.contains(&key) {
+            continue;
+        }
+        let line = serde_json::to_string(record)
+            .map_err(|e| format!("Failed to serialize attendance record: {}", e))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| format!("Failed to write attendance queue: {}", e))?;
+        staged += 1;
+    }
+    Ok(staged)
+}
+
+fn read_queue() -> Vec<StagedAttendance> {
+    fs::read_to_string(attendance_queue_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+pub fn queued_attendance() -> Vec<StagedAttendance> {
+    let _guard = QUEUE_LOCK.lock();
+    read_queue()
+}
+
+// Hand queued records to the backend through its PUSH ATTLOG endpoint (which resolves the
+// device by serial number and skips duplicates); delivered records leave the queue.
+// Records of devices without a known serial number stay queued.
+pub async fn flush_attendance_queue(backend_port: u16) -> Result<usize, String> {
+    let queued = {
+        let _guard = QUEUE_LOCK.lock();
+        read_queue()
+    };
+    if queued.is_empty() {
+        return Ok(0);
+    }
+
+    let mut batches: HashMap<String, Vec<&StagedAttendance>> = HashMap::new();
+    for record in &queued {
+        if let Some(serial) = record.serial_number.as_deref().filter(|s| !s.is_empty()) {
+            batches.entry(serial.to_string()).or_default().push(record);
+        }
+    }
+
+    let client = backend_client(Duration::from_secs(30))?;
+    let mut delivered = HashSet::new();
+    for (serial, records) in batches {
+        let body: String = records
+            .iter()
+            .map(|r| {
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    r.user_id, r.timestamp, r.action, r.method
+                )
+            })
+            .collect();
+        let response = client
+            .post(format!("{}/iclock/cdata", backend_base_url(backend_port)))
+            .query(&[("SN", serial.as_str()), ("table", "ATTLOG")])
+            .header("Content-Type", "text/plain")
+            .body(body)
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => {
+                delivered.extend(
+                    records
+                        .iter()
+                        .map(|r| (r.device_id.clone(), r.user_id.clone(), r.timestamp.clone())),
+                );
+            }
+            Ok(response) => {
+                return Err(format!(
+                    "Backend rejected queued attendance ({})",
+                    response.status()
+                ))
+            }
+            Err(err) => return Err(format!("Failed to hand off queued attendance: {}", err)),
+        }
+    }
+    if delivered.is_empty() {
+        return Ok(0);
+    }
+
+    // Re-read under the lock: records may have been staged while the upload ran
+    let _guard = QUEUE_LOCK.lock();
+    let remaining: String = read_queue()
+        .into_iter()
+        .filter(|r| {
+            !delivered.contains(&(r.device_id.clone(), r.user_id.clone(), r.timestamp.clone()))
+        })
+        .filter_map(|r| serde_json::to_string(&r).ok())
+        .map(|line| line + "\n")
+        .collect();
+    fs::write(attendance_queue_path(), remaining)
+        .map_err(|e| format!("Failed to rewrite attendance queue: {}", e))?;
+    Ok(delivered.len())
+}
+
+// Run blocking protocol work off the async runtime
+pub async fn run_native<T, F>(task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("Native device task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn test_device_connection_native(
+    ip: String,
+    port: Option<u16>,
+    comm_key: Option<u32>,
+) -> Result<NativeConnectionTest, String> {
+    let port = port.unwrap_or(DEFAULT_DEVICE_PORT);
+    append_app_log(&format!(
+        "Native connection test started for {}:{}",
+        ip, port
+    ));
+
+    let result = run_native(move || {
+        let start = Instant::now();
+        let mut session = ZkSession::connect(&ip, port, comm_key.unwrap_or(0), NATIVE_TIMEOUT)?;
+        let handshake_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let firmware_version = session.get_firmware_version()?;
+        session.disconnect()?;
+
+        Ok(NativeConnectionTest {
+            ip,
+            port,
+            handshake_ms,
+            firmware_version,
+        })
+    })
+    .await;
+
+    match &result {
+        Ok(test) => append_app_log(&format!(
+            "Native connection test succeeded for {}:{} (firmware {})",
+            test.ip, test.port, test.firmware_version
+        )),
+        Err(err) => append_app_log(&format!("Native connection test failed: {}", err)),
+    }
+    result
+}
+
+// Read attendance straight from the terminal and stage it in the local queue file for the
+// backend to ingest once it is back
+#[tauri::command]
+pub async fn pull_attendance_native(
+    device_id: String,
+    since: Option<String>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<NativePullResult, String> {
+    let since = since.as_deref().map(parse_since).transpose()?;
+    let device: DeviceEntry = lookup_device(&registry, &backend_port, &device_id).await?;
+    pull_and_stage(device, since).await
+}
+
+// Read the device's attendance log and stage records at or after `since` in the queue
+pub async fn pull_and_stage(
+    device: DeviceEntry,
+    since: Option<NaiveDateTime>,
+) -> Result<NativePullResult, String> {
+    pull_and_stage_progress(device, since, |_, _| {}).await
+}
+
+// Same as pull_and_stage, reporting (records_fetched, records_total) during the transfer
+pub async fn pull_and_stage_progress(
+    device: DeviceEntry,
+    since: Option<NaiveDateTime>,
+    on_progress: impl FnMut(u32, u32) + Send + 'static,
+) -> Result<NativePullResult, String> {
+    append_app_log(&format!(
+        "Native attendance pull started for {} ({}:{})",
+        device.id, device.ip, device.port
+    ));
+
+    let records = {
+        let device = device.clone();
+        run_native(move || {
+            let mut session = device.open_session(NATIVE_TIMEOUT)?;
+            let drift = measure_drift(&mut session);
+            let records = session.read_attendance_progress(on_progress)?;
+            session.disconnect()?;
+            if let Ok((drift_seconds, corrected)) = drift {
+                record_drift(&device.id, drift_seconds, corrected);
+            }
+            Ok(records)
+        })
+        .await?
+    };
+
+    let staged: Vec<StagedAttendance> = to_staged(&device, &records)
+        .into_iter()
+        .zip(&records)
+        .filter(|(_, record)| since.map(|since| record.timestamp >= since).unwrap_or(true))
+        .map(|(staged, _)| staged)
+        .collect();
+    let staged_count = stage_records(&staged)?;
+
+    append_app_log(&format!(
+        "Native attendance pull for {} staged {} new records ({} on device)",
+        device.id,
+        staged_count,
+        records.len()
+    ));
+    Ok(NativePullResult {
+        device_id: device.id,
+        records_on_device: records.len(),
+        staged: staged_count,
+        queue_path: attendance_queue_path().to_string_lossy().to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_device_info_native(
+    ip: String,
+    port: Option<u16>,
+    comm_key: Option<u32>,
+) -> Result<NativeDeviceInfo, String> {
+    let port = port.unwrap_or(DEFAULT_DEVICE_PORT);
+    run_native(move || {
+        let mut session = ZkSession::connect(&ip, port, comm_key.unwrap_or(0), NATIVE_TIMEOUT)?;
+        // Older firmware doesn't know every option; report those as empty instead of failing
+        let serial_number = session.read_option("~SerialNumber").unwrap_or_default();
+        let device_name = session.read_option("~DeviceName").unwrap_or_default();
+        let platform = session.read_option("~Platform").unwrap_or_default();
+        let firmware_version = session.get_firmware_version()?;
+        let sizes = session.read_sizes()?;
+        session.disconnect()?;
+
+        Ok(NativeDeviceInfo {
+            ip,
+            port,
+            serial_number,
+            device_name,
+            firmware_version,
+            platform,
+            sizes,
+        })
+    })
+    .await
+}
+
+// Set the terminal clock from the host clock (devices keep local time, no timezone)
+pub fn set_device_clock(device: DeviceEntry) -> Result<TimeSyncResult, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    let previous = session.get_time()?;
+    let now = Local::now().naive_local();
+    session.set_time(now)?;
+    session.disconnect()?;
+    let drift_seconds = previous.signed_duration_since(now).num_seconds();
+    record_drift(&device.id, drift_seconds, true);
+
+    Ok(TimeSyncResult {
+        device_id: device.id,
+        previous_device_time: previous.format(DEVICE_TIME_FORMAT).to_string(),
+        new_device_time: now.format(DEVICE_TIME_FORMAT).to_string(),
+        drift_seconds,
+    })
+}
+
+#[tauri::command]
+pub async fn sync_device_time(
+    device_id: String,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<TimeSyncResult, String> {
+    lock::ensure_unlocked()?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let result = run_native(move || set_device_clock(device)).await?;
+    append_app_log(&format!(
+        "Device clock synced for {} (drift {}s)",
+        result.device_id, result.drift_seconds
+    ));
+    Ok(result)
+}
+
+// Background task: periodically sync the clock of every active pull device
+pub fn spawn_time_sync_job(registry: DeviceRegistry, settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval_hours = current_settings(&settings).device_time_sync_interval_hours;
+            if interval_hours == 0 {
+                // Disabled - check again later in case the setting changes
+                tokio::time::sleep(Duration::from_secs(300)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(interval_hours * 3600)).await;
+
+            let devices = active_pull_devices(&registry);
+
+            for device in devices {
+                let device_id = device.id.clone();
+                match run_native(move || set_device_clock(device)).await {
+                    Ok(result) => append_app_log(&format!(
+                        "Scheduled clock sync for {} (drift {}s)",
+                        result.device_id, result.drift_seconds
+                    )),
+                    Err(err) => append_app_log(&format!(
+                        "Scheduled clock sync failed for {}: {}",
+                        device_id, err
+                    )),
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_device_users(
+    device_id: String,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<Vec<DeviceUser>, String> {
+    lock::ensure_unlocked()?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    run_native(move || {
+        let mut session = device.open_session(NATIVE_TIMEOUT)?;
+        let (users, _) = session.read_users()?;
+        session.disconnect()?;
+        Ok(users)
+    })
+    .await
+}
+
+// Create or update a user; an existing user_id keeps its slot, new users get the next free one
+#[tauri::command]
+pub async fn set_device_user(
+    device_id: String,
+    user: DeviceUser,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<DeviceUser, String> {
+    lock::ensure_unlocked()?;
+    if user.user_id.trim().is_empty() {
+        return Err("User id is required".to_string());
+    }
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+
+    let saved = run_native(move || {
+        let mut session = device.open_session(NATIVE_TIMEOUT)?;
+        let (existing, record_size) = session.read_users()?;
+        let mut user = user;
+        user.uid = match existing.iter().find(|u| u.user_id == user.user_id) {
+            Some(current) => current.uid,
+            None => existing
+                .iter()
+                .map(|u| u.uid)
+                .max()
+                .unwrap_or(0)
+                .checked_add(1)
+                .ok_or_else(|| "Device has no free user slot".to_string())?,
+        };
+        session.write_user(&user, record_size)?;
+        session.disconnect()?;
+        Ok(user)
+    })
+    .await?;
+
+    append_app_log(&format!(
+        "Device user {} written to {} natively",
+        saved.user_id, device_id
+    ));
+    Ok(saved)
+}
+
+const FACE_NOT_SUPPORTED: &str = "Face templates are not supported by this device";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FaceTemplate {
+    user_id: String,
+    template: Vec<u8>,
+}
+
+// Connect, check face capability and resolve the device slot of `user_id`
+fn open_face_session(device: &DeviceEntry, user_id: &str) -> Result<(ZkSession, u16), String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    if !session.supports_faces() {
+        let _ = session.disconnect();
+        return Err(FACE_NOT_SUPPORTED.to_string());
+    }
+    let (users, _) = session.read_users()?;
+    match users.iter().find(|u| u.user_id == user_id) {
+        Some(user) => Ok((session, user.uid)),
+        None => {
+            let _ = session.disconnect();
+            Err(format!("User {} does not exist on the device", user_id))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_face_template(
+    device_id: String,
+    user_id: String,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<FaceTemplate, String> {
+    lock::ensure_unlocked()?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    run_native(move || {
+        let (mut session, uid) = open_face_session(&device, &user_id)?;
+        let template = session.read_face_template(uid)?;
+        session.disconnect()?;
+        Ok(FaceTemplate { user_id, template })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_face_template(
+    device_id: String,
+    face: FaceTemplate,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    if face.template.is_empty() {
+        return Err("Face template is empty".to_string());
+    }
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let user_id = face.user_id.clone();
+    run_native(move || {
+        let (mut session, uid) = open_face_session(&device, &face.user_id)?;
+        session.write_face_template(uid, &face.template)?;
+        session.disconnect()
+    })
+    .await?;
+
+    append_app_log(&format!(
+        "Face template uploaded for user {} on {}",
+        user_id, device_id
+    ));
+    Ok(format!("Face template uploaded for user {}", user_id))
+}
+
+// Shared path for reboot/power-off: confirm, run, audit
+async fn run_power_action(
+    action: &str,
+    device_id: String,
+    confirm_token: String,
+    tokens: &ConfirmationTokens,
+    registry: &DeviceRegistry,
+    backend_port: &BackendPort,
+) -> Result<String, String> {
+    consume_token(tokens, &confirm_token, action, &device_id)?;
+    let device = lookup_device(registry, backend_port, &device_id).await;
+    let power_off = action == "poweroff_device";
+    let result = match device {
+        Ok(device) => {
+            run_native(move || {
+                let session = device.open_session(NATIVE_TIMEOUT)?;
+                if power_off {
+                    session.power_off()
+                } else {
+                    session.restart()
+                }
+            })
+            .await
+        }
+        Err(err) => Err(err),
+    };
+
+    audited(action, &device_id, result)?;
+    Ok(if power_off {
+        format!("Device {} is powering off", device_id)
+    } else {
+        format!("Device {} is rebooting", device_id)
+    })
+}
+
+#[tauri::command]
+pub async fn reboot_device(
+    device_id: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    run_power_action(
+        "reboot_device",
+        device_id,
+        confirm_token,
+        &tokens,
+        &registry,
+        &backend_port,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn poweroff_device(
+    device_id: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    run_power_action(
+        "poweroff_device",
+        device_id,
+        confirm_token,
+        &tokens,
+        &registry,
+        &backend_port,
+    )
+    .await
+}
+
+const MAX_UNLOCK_SECONDS: u32 = 60;
+
+// Pulse the door relay of a terminal used as an access controller
+#[tauri::command]
+pub async fn unlock_door(
+    device_id: String,
+    duration: Option<u32>,
+    pin: Option<String>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    if let Some(false) = lock::check_door_pin(pin).await? {
+        record_audit(
+            "unlock_door",
+            &device_id,
+            "rejected",
+            Some("incorrect PIN".to_string()),
+        );
+        return Err("Incorrect PIN".to_string());
+    }
+
+    let seconds = duration.unwrap_or(3).clamp(1, MAX_UNLOCK_SECONDS);
+    let result = match lookup_device(&registry, &backend_port, &device_id).await {
+        Ok(device) => {
+            run_native(move || {
+                let mut session = device.open_session(NATIVE_TIMEOUT)?;
+                session.unlock(seconds)?;
+                session.disconnect()
+            })
+            .await
+        }
+        Err(err) => Err(err),
+    };
+
+    audited("unlock_door", &device_id, result)?;
+    Ok(format!("Door on {} unlocked for {}s", device_id, seconds))
+}
+
+#[tauri::command]
+pub async fn send_device_message(
+    device_id: String,
+    user_id: Option<String>,
+    text: String,
+    duration: Option<u16>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return Err("Message text is required".to_string());
+    }
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let recipient = user_id.clone();
+
+    run_native(move || {
+        let mut session = device.open_session(NATIVE_TIMEOUT)?;
+        let uid = match &user_id {
+            Some(user_id) => {
+                let (users, _) = session.read_users()?;
+                let user = users
+                    .iter()
+                    .find(|u| &u.user_id == user_id)
+                    .ok_or_else(|| format!("User {} does not exist on the device", user_id))?;
+                Some(user.uid)
+            }
+            None => None,
+        };
+        // Message ids only need to be unique on the device
+        let sms_id = (Utc::now().timestamp() % 60000) as u16 + 1;
+        session.write_sms(sms_id, &text, duration.unwrap_or(60), uid)?;
+        session.disconnect()
+    })
+    .await?;
+
+    let target = recipient
+        .map(|user_id| format!("user {}", user_id))
+        .unwrap_or_else(|| "all users".to_string());
+    append_app_log(&format!("Message sent to {} on {}", target, device_id));
+    Ok(format!("Message sent to {} on {}", target, device_id))
+}
+
+// Attendance memory usage above which the UI should warn before the terminal drops punches
+const CAPACITY_WARNING_PERCENT: f64 = 90.0;
+// Privilege value of administrators in user records
+const ADMIN_PRIVILEGE: u8 = 14;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CapacityUsage {
+    used: u32,
+    total: Option<u32>,
+    percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceCapacity {
+    device_id: String,
+    users: CapacityUsage,
+    fingerprints: CapacityUsage,
+    faces: Option<CapacityUsage>,
+    attendance_records: CapacityUsage,
+    admins: CapacityUsage,
+    warnings: Vec<String>,
+}
+
+fn usage(used: u32, total: Option<u32>) -> CapacityUsage {
+    let total = total.filter(|total| *total > 0);
+    CapacityUsage {
+        used,
+        total,
+        percent: total.map(|total| used as f64 * 100.0 / total as f64),
+    }
+}
+
+#[tauri::command]
+pub async fn get_device_capacity(
+    device_id: String,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<DeviceCapacity, String> {
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let (sizes, admins) = run_native(move || {
+        let mut session = device.open_session(NATIVE_TIMEOUT)?;
+        let sizes = session.read_sizes()?;
+        // Admin count isn't part of the size counters, so derive it from the user table
+        let (users, _) = session.read_users()?;
+        session.disconnect()?;
+        let admins = users
+            .iter()
+            .filter(|u| u.privilege == ADMIN_PRIVILEGE)
+            .count() as u32;
+        Ok((sizes, admins))
+    })
+    .await?;
+
+    let capacity = DeviceCapacity {
+        device_id,
+        users: usage(sizes.users, Some(sizes.users_cap)),
+        fingerprints: usage(sizes.fingers, Some(sizes.fingers_cap)),
+        faces: sizes.faces.map(|faces| usage(faces, sizes.faces_cap)),
+        attendance_records: usage(sizes.records, Some(sizes.records_cap)),
+        admins: usage(admins, None),
+        warnings: Vec::new(),
+    };
+
+    let mut warnings = Vec::new();
+    for (label, item) in [
+        ("Attendance log memory", Some(&capacity.attendance_records)),
+        ("User storage", Some(&capacity.users)),
+        ("Fingerprint storage", Some(&capacity.fingerprints)),
+        ("Face storage", capacity.faces.as_ref()),
+    ] {
+        if let Some(percent) = item.and_then(|item| item.percent) {
+            if percent >= CAPACITY_WARNING_PERCENT {
+                warnings.push(format!("{} is {:.0}% full", label, percent));
+            }
+        }
+    }
+
+    Ok(DeviceCapacity {
+        warnings,
+        ..capacity
+    })
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ClearAttendanceResult {
+    device_id: String,
+    dry_run: bool,
+    records_backed_up: usize,
+    backup_path: String,
+    backup_sha256: String,
+    cleared: bool,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+// Write the backup plus a .sha256 file next to it, then read it back and compare
+fn write_verified_backup(path: &PathBuf, content: &str) -> Result<String, String> {
+    let checksum = sha256_hex(content.as_bytes());
+    fs::write(path, content).map_err(|e| format!("Failed to write attendance backup: {}", e))?;
+    fs::write(path.with_extension("jsonl.sha256"), &checksum)
+        .map_err(|e| format!("Failed to write backup checksum: {}", e))?;
+
+    let written =
+        fs::read(path).map_err(|e| format!("Failed to read back attendance backup: {}", e))?;
+    if sha256_hex(&written) != checksum {
+        return Err("Attendance backup failed checksum verification".to_string());
+    }
+    Ok(checksum)
+}
+
+// Back up every record, verify the backup, and only then clear the log. The device is disabled
+// for the whole operation so no punch can land between the backup and the clear.
+fn backup_and_clear(device: DeviceEntry, dry_run: bool) -> Result<ClearAttendanceResult, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    session.disable_device()?;
+    let result = (|| {
+        let expected = session.read_sizes()?.records as usize;
+        let records = session.read_attendance()?;
+        if records.len() != expected {
+            return Err(format!(
+                "Read {} of {} attendance records - refusing to clear",
+                records.len(),
+                expected
+            ));
+        }
+
+        let staged = to_staged(&device, &records);
+        let content: String = staged
+            .iter()
+            .map(|record| serde_json::to_string(record).map(|line| line + "\n"))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to serialize attendance backup: {}", e))?;
+
+        let backup_dir = resolve_app_data_dir().join("attendance_backups");
+        fs::create_dir_all(&backup_dir)
+            .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+        let backup_path = backup_dir.join(format!(
+            "{}_{}.jsonl",
+            device.id,
+            Local::now().format("%Y%m%d_%H%M%S")
+        ));
+        let checksum = write_verified_backup(&backup_path, &content)?;
+
+        // Also queue the records so the backend ingests anything it hasn't seen yet
+        stage_records(&staged)?;
+
+        if !dry_run {
+            session.clear_attendance()?;
+        }
+
+        Ok(ClearAttendanceResult {
+            device_id: device.id.clone(),
+            dry_run,
+            records_backed_up: records.len(),
+            backup_path: backup_path.to_string_lossy().to_string(),
+            backup_sha256: checksum,
+            cleared: !dry_run,
+        })
+    })();
+
+    let enabled = session.enable_device();
+    let _ = session.disconnect();
+    let result = result?;
+    enabled?;
+    Ok(result)
+}
+
+// Clearing needs a confirmation token for "clear_device_attendance" on the device; dry runs
+// only back up
+#[tauri::command]
+pub async fn clear_device_attendance(
+    device_id: String,
+    dry_run: Option<bool>,
+    confirm_token: Option<String>,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<ClearAttendanceResult, String> {
+    lock::ensure_unlocked()?;
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        consume_token(
+            &tokens,
+            confirm_token.as_deref().unwrap_or_default(),
+            "clear_device_attendance",
+            &device_id,
+        )?;
+    }
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let result = run_native(move || backup_and_clear(device, dry_run)).await;
+
+    if dry_run {
+        return result;
+    }
+    audited("clear_device_attendance", &device_id, result)
+}
+
+// How long to wait for a terminal to come back on its new address after a network change
+const NETWORK_CHANGE_TIMEOUT: Duration = Duration::from_secs(90);
+const NETWORK_CHANGE_POLL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct NetworkSettings {
+    ip: String,
+    netmask: String,
+    gateway: String,
+    port: Option<u16>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NetworkChangeResult {
+    device_id: String,
+    ip: String,
+    port: u16,
+    reachable: bool,
+    backend_updated: bool,
+}
+
+fn parse_ipv4(label: &str, value: &str) -> Result<Ipv4Addr, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {}: {}", label, value))
+}
+
+fn validate_network(settings: &NetworkSettings) -> Result<(Ipv4Addr, Ipv4Addr, Ipv4Addr), String> {
+    let ip = parse_ipv4("IP address", &settings.ip)?;
+    let mask = parse_ipv4("netmask", &settings.netmask)?;
+    let gateway = parse_ipv4("gateway", &settings.gateway)?;
+
+    let mask_bits = u32::from(mask);
+    if mask_bits == 0 || mask_bits.leading_ones() + mask_bits.trailing_zeros() != 32 {
+        return Err(format!("Invalid netmask: {}", mask));
+    }
+    if ip.is_unspecified() || ip.is_broadcast() || ip.is_multicast() || ip.is_loopback() {
+        return Err(format!("{} cannot be assigned to a device", ip));
+    }
+    let network = u32::from(ip) & mask_bits;
+    let host = u32::from(ip) & !mask_bits;
+    if host == 0 || host == !mask_bits {
+        return Err(format!("{} is a network or broadcast address", ip));
+    }
+    if u32::from(gateway) & mask_bits != network {
+        return Err(format!("Gateway {} is not in the device's subnet", gateway));
+    }
+    if settings.port == Some(0) {
+        return Err("Port must be between 1 and 65535".to_string());
+    }
+    Ok((ip, mask, gateway))
+}
+
+fn apply_network_settings(
+    device: DeviceEntry,
+    ip: Ipv4Addr,
+    mask: Ipv4Addr,
+    gateway: Ipv4Addr,
+    port: u16,
+) -> Result<bool, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    session.write_option("IPAddress", &ip.to_string())?;
+    session.write_option("NetMask", &mask.to_string())?;
+    session.write_option("GATEWAYIPAddress", &gateway.to_string())?;
+    if port != device.port {
+        session.write_option("TCPPort", &port.to_string())?;
+    }
+    session.refresh_options()?;
+    // Network settings only take effect after a restart
+    session.restart()?;
+
+    let deadline = Instant::now() + NETWORK_CHANGE_TIMEOUT;
+    while Instant::now() < deadline {
+        std::thread::sleep(NETWORK_CHANGE_POLL);
+        if let Ok(session) =
+            ZkSession::connect(&ip.to_string(), port, device.comm_key, NATIVE_TIMEOUT)
+        {
+            let _ = session.disconnect();
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Best effort: tell the backend about the new address so its captures follow the device
+async fn update_backend_address(backend_port: u16, device_id: &str, ip: &str, port: u16) -> bool {
+    let client = match backend_client(Duration::from_secs(30)) {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+    client
+        .put(format!(
+            "{}/devices/{}",
+            backend_base_url(backend_port),
+            device_id
+        ))
+        .json(&serde_json::json!({ "ip": ip, "port": port }))
+        .send()
+        .await
+        .map(|response| response.status().is_success())
+        .unwrap_or(false)
+}
+
+#[tauri::command]
+pub async fn set_device_network(
+    device_id: String,
+    network: NetworkSettings,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<NetworkChangeResult, String> {
+    let (ip, mask, gateway) = validate_network(&network)?;
+    consume_token(&tokens, &confirm_token, "set_device_network", &device_id)?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+    let port = network.port.unwrap_or(device.port);
+
+    let result = run_native(move || apply_network_settings(device, ip, mask, gateway, port)).await;
+    let reachable = audited("set_device_network", &device_id, result)?;
+    append_app_log(&format!(
+        "Device {} moved to {}:{} (reachable: {})",
+        device_id, ip, port, reachable
+    ));
+
+    let ip = ip.to_string();
+    update_device_address(&registry, &device_id, &ip, port)?;
+    let backend_updated =
+        update_backend_address(current_backend_port(&backend_port), &device_id, &ip, port).await;
+
+    Ok(NetworkChangeResult {
+        device_id,
+        ip,
+        port,
+        reachable,
+        backend_updated,
+    })
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SerialPortEntry {
+    port_name: String,
+    port_type: String, // "usb", "pci", "bluetooth" or "unknown"
+    description: Option<String>,
+}
+
+// Serial ports available for terminals wired over RS232/RS485 adapters
+#[tauri::command]
+pub fn list_serial_ports() -> Result<Vec<SerialPortEntry>, String> {
+    let ports = serialport::available_ports()
+        .map_err(|e| format!("Failed to enumerate serial ports: {}", e))?;
+    Ok(ports
+        .into_iter()
+        .map(|port| {
+            let (port_type, description) = match port.port_type {
+                serialport::SerialPortType::UsbPort(usb) => (
+                    "usb",
+                    usb.product
+                        .or(usb.manufacturer)
+                        .or(Some(format!("{:04x}:{:04x}", usb.vid, usb.pid))),
+                ),
+                serialport::SerialPortType::PciPort => ("pci", None),
+                serialport::SerialPortType::BluetoothPort => ("bluetooth", None),
+                serialport::SerialPortType::Unknown => ("unknown", None),
+            };
+            SerialPortEntry {
+                port_name: port.port_name,
+                port_type: port_type.to_string(),
+                description,
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceLogEntry {
+    timestamp: String,
+    admin: u16,
+    code: u8,
+    description: String,
+    user: u16,
+    params: [u16; 3],
+}
+
+// Terminal operation log (admin logins, door openings, alarms, tamper, ...), oldest first
+#[tauri::command]
+pub async fn get_device_event_log(
+    device_id: String,
+    since: Option<String>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<Vec<DeviceLogEntry>, String> {
+    let since = since.as_deref().map(parse_since).transpose()?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+
+    let mut records = run_native(move || {
+        let mut session = device.open_session(NATIVE_TIMEOUT)?;
+        let records = session.read_operation_log()?;
+        session.disconnect()?;
+        Ok(records)
+    })
+    .await?;
+    records.retain(|record| since.map(|since| record.timestamp >= since).unwrap_or(true));
+    records.sort_by_key(|record| record.timestamp);
+
+    Ok(records
+        .into_iter()
+        .map(|record| DeviceLogEntry {
+            timestamp: record.timestamp.format(DEVICE_TIME_FORMAT).to_string(),
+            admin: record.admin,
+            code: record.operation,
+            description: describe_operation(record.operation).to_string(),
+            user: record.user,
+            params: record.params,
+        })
+        .collect())
+}
+
+const CARD_ENROLL_TIMEOUT: Duration = Duration::from_secs(30);
+const CARD_ENROLL_MAX_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CardEnrollResult {
+    device_id: String,
+    user_id: String,
+    card: u32,
+    previous_card: u32,
+}
+
+// Wait for a card swipe on the terminal and bind the card number to `user_id`
+fn enroll_card_blocking(
+    device: DeviceEntry,
+    user_id: String,
+    timeout: Duration,
+) -> Result<CardEnrollResult, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    let (users, record_size) = session.read_users()?;
+    let mut user = users
+        .iter()
+        .find(|u| u.user_id == user_id)
+        .cloned()
+        .ok_or_else(|| format!("User {} does not exist on the device", user_id))?;
+
+    session.register_events(EF_HIDNUM)?;
+    let deadline = Instant::now() + timeout;
+    let card = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            let _ = session.register_events(0);
+            let _ = session.disconnect();
+            return Err(format!(
+                "No card was presented within {} seconds",
+                timeout.as_secs()
+            ));
+        }
+        let Some(packet) = session.wait_event(remaining.min(Duration::from_secs(1)))? else {
+            continue;
+        };
+        if packet.command != CMD_REG_EVENT {
+            continue;
+        }
+        session.ack_event()?;
+        if packet.session_id as u32 != EF_HIDNUM || packet.data.len() < 4 {
+            continue;
+        }
+        let card = u32::from_le_bytes([
+            packet.data[0],
+            packet.data[1],
+            packet.data[2],
+            packet.data[3],
+        ]);
+        if card != 0 {
+            break card;
+        }
+    };
+    session.register_events(0)?;
+
+    if let Some(owner) = users
+        .iter()
+        .find(|u| u.card == card && u.user_id != user_id)
+    {
+        let _ = session.disconnect();
+        return Err(format!(
+            "Card {} is already assigned to user {}",
+            card, owner.user_id
+        ));
+    }
+
+    let previous_card = user.card;
+    user.card = card;
+    session.write_user(&user, record_size)?;
+    session.disconnect()?;
+
+    Ok(CardEnrollResult {
+        device_id: device.id,
+        user_id,
+        card,
+        previous_card,
+    })
+}
+
+// Put the terminal in card-read mode and assign the next presented card to a user, instead of
+// going through the on-device enrollment menu
+#[tauri::command]
+pub async fn enroll_card(
+    app: tauri::AppHandle,
+    device_id: String,
+    user_id: String,
+    timeout_secs: Option<u64>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<CardEnrollResult, String> {
+    lock::ensure_unlocked()?;
+    let timeout = timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(CARD_ENROLL_TIMEOUT)
+        .min(CARD_ENROLL_MAX_TIMEOUT);
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+
+    let _ = app.emit(
+        "card-enrollment",
+        serde_json::json!({ "device_id": device_id, "user_id": user_id, "status": "waiting" }),
+    );
+    let target = format!("{}/{}", device_id, user_id);
+    let result = audited(
+        "enroll_card",
+        &target,
+        run_native({
+            let user_id = user_id.clone();
+            move || enroll_card_blocking(device, user_id, timeout)
+        })
+        .await,
+    );
+
+    let _ = app.emit(
+        "card-enrollment",
+        serde_json::json!({
+            "device_id": device_id,
+            "user_id": user_id,
+            "status": if result.is_ok() { "enrolled" } else { "failed" },
+            "error": result.as_ref().err(),
+        }),
+    );
+    if let Ok(enrolled) = &result {
+        append_app_log(&format!(
+            "Card {} enrolled for user {} on {}",
+            enrolled.card, user_id, device_id
+        ));
+    }
+    result
+}
+
+// Images larger than this are certainly not terminal firmware
+const MAX_FIRMWARE_SIZE: u64 = 64 * 1024 * 1024;
+
+// Firmware image to install and the model/version it was built for
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FirmwarePackage {
+    file_path: String,
+    model: String,
+    version: String,
+    #[serde(default)]
+    allow_downgrade: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FirmwareUploadResult {
+    device_id: String,
+    model: String,
+    previous_version: String,
+    target_version: String,
+    bytes_sent: usize,
+}
+
+// Numeric parts of a firmware version such as "Ver 6.60 Apr 13 2017" -> [6, 60]
+fn version_parts(version: &str) -> Vec<u32> {
+    version
+        .split_whitespace()
+        .find(|part| part.chars().next().is_some_and(|c| c.is_ascii_digit()))
+        .unwrap_or_default()
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn upload_firmware_blocking(
+    app: tauri::AppHandle,
+    device: DeviceEntry,
+    file_name: String,
+    image: Vec<u8>,
+    expected_model: String,
+    target_version: String,
+    allow_downgrade: bool,
+) -> Result<FirmwareUploadResult, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+
+    // Pre-flight: never send an image meant for another model, or an older version by accident
+    let model = session.read_option("~DeviceName").unwrap_or_default();
+    let previous_version = session.get_firmware_version()?;
+    if !model.trim().eq_ignore_ascii_case(expected_model.trim()) {
+        let _ = session.disconnect();
+        return Err(format!(
+            "Firmware is for model {} but the device reports {}",
+            expected_model, model
+        ));
+    }
+    let (current, target) = (
+        version_parts(&previous_version),
+        version_parts(&target_version),
+    );
+    if target.is_empty() {
+        let _ = session.disconnect();
+        return Err(format!(
+            "Invalid target firmware version: {}",
+            target_version
+        ));
+    }
+    if target <= current && !allow_downgrade {
+        let _ = session.disconnect();
+        return Err(format!(
+            "Device already runs {} - refusing to install {} without allow_downgrade",
+            previous_version, target_version
+        ));
+    }
+
+    let device_id = device.id.clone();
+    let mut last_percent = None;
+    session.upload_firmware(&file_name, &image, |sent, total| {
+        let percent = sent * 100 / total.max(1);
+        if last_percent != Some(percent) {
+            last_percent = Some(percent);
+            let _ = app.emit(
+                "firmware-upload-progress",
+                serde_json::json!({
+                    "device_id": device_id,
+                    "sent": sent,
+                    "total": total,
+                    "percent": percent,
+                }),
+            );
+        }
+    })?;
+    session.restart()?;
+
+    Ok(FirmwareUploadResult {
+        device_id: device.id,
+        model,
+        previous_version,
+        target_version,
+        bytes_sent: image.len(),
+    })
+}
+
+// Install a firmware image on a terminal. Requires a confirmation token for "upload_firmware";
+// the device restarts to apply it.
+#[tauri::command]
+pub async fn upload_firmware(
+    app: tauri::AppHandle,
+    device_id: String,
+    firmware: FirmwarePackage,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<FirmwareUploadResult, String> {
+    consume_token(&tokens, &confirm_token, "upload_firmware", &device_id)?;
+    let FirmwarePackage {
+        file_path,
+        model,
+        version,
+        allow_downgrade,
+    } = firmware;
+    let path = check_path(&file_path, Access::Read)?;
+    let size = fs::metadata(&path)
+        .map_err(|e| format!("Failed to read firmware file {}: {}", file_path, e))?
+        .len();
+    if size == 0 || size > MAX_FIRMWARE_SIZE {
+        return Err(format!(
+            "Firmware file has an unexpected size ({} bytes)",
+            size
+        ));
+    }
+    let image = fs::read(&path)
+        .map_err(|e| format!("Failed to read firmware file {}: {}", file_path, e))?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "firmware.bin".to_string());
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+
+    append_app_log(&format!(
+        "Uploading firmware {} ({} bytes) to {}",
+        file_name, size, device_id
+    ));
+    let result = run_native(move || {
+        upload_firmware_blocking(
+            app,
+            device,
+            file_name,
+            image,
+            model,
+            version,
+            allow_downgrade,
+        )
+    })
+    .await;
+    audited("upload_firmware", &device_id, result)
+}
@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{Local, Utc};
+use tauri::{AppHandle, State};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::audit::audited;
+use crate::bundle::log_tail;
+use crate::confirmation::ConfirmationTokens;
+use crate::crash;
+use crate::export::resolve_save_path;
+use crate::lock;
+use crate::profiles::{active_profile, DEFAULT_PROFILE};
+use crate::redact::{confirm_unredacted, redact};
+use crate::sidecar::get_sidecar_integrity;
+use crate::tls::get_backend_tls_status;
+use crate::{append_app_log, get_log_file_path, resolve_app_data_dir, BackendLogs};
+
+// Diagnostic bundle for support: log tails, the in-memory backend log, settings, the device
+// registry and a summary of the environment, zipped. Everything goes through redact() unless
+// the caller confirmed an unredacted export. Databases and protocol traces are never included.
+// Pending crash reports (crash.rs) are added when asked for, their minidumps only unredacted.
+const SHELL_FILES: [(&str, &str); 4] = [
+    ("logs/zkteco_app.log", "zkteco_app.log"),
+    ("logs/audit.log", "audit.log"),
+    ("settings/shell_settings.json", "shell_settings.json"),
+    ("settings/device_registry.json", "device_registry.json"),
+];
+const BACKEND_LOG: &str = "logs/backend_app.log";
+const BACKEND_SESSION_LOG: &str = "logs/backend_session.json";
+const SYSTEM_INFO: &str = "system.json";
+const DIAGNOSTICS_TARGET: &str = "diagnostics";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DiagnosticsResult {
+    path: String,
+    size_bytes: u64,
+    files: Vec<String>,
+    redacted: bool,
+    duration_ms: u128,
+}
+
+fn system_info(app: &AppHandle, redacted: bool) -> serde_json::Value {
+    serde_json::json!({
+        "created_at": Utc::now(),
+        "app_version": app.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "profile": active_profile().unwrap_or_else(|| DEFAULT_PROFILE.to_string()),
+        "redacted": redacted,
+        "sidecar_integrity": get_sidecar_integrity(),
+        "backend_tls": get_backend_tls_status(),
+    })
+}
+
+fn write_diagnostics(
+    path: &Path,
+    entries: Vec<(String, String)>,
+    attachments: Vec<(String, PathBuf)>,
+    redacted: bool,
+) -> Result<Vec<String>, String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut files = Vec::new();
+    for (name, content) in entries {
+        let content = if redacted { redact(&content) } else { content };
+        zip.start_file(name.as_str(), options)
+            .and_then(|_| zip.write_all(content.as_bytes()).map_err(Into::into))
+            .map_err(|e| format!("Failed to add {} to diagnostics: {}", name, e))?;
+        files.push(name);
+    }
+    // Binary files, copied as they are
+    for (name, source) in attachments {
+        let mut file =
+            File::open(&source).map_err(|e| format!("Failed to read {:?}: {}", source, e))?;
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("Failed to add {} to diagnostics: {}", name, e))?;
+        std::io::copy(&mut file, &mut zip)
+            .map_err(|e| format!("Failed to add {} to diagnostics: {}", name, e))?;
+        files.push(name);
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to write diagnostics: {}", e))?;
+    Ok(files)
+}
+
+// Without a path the user picks one in a save dialog (None when cancelled). unredacted needs
+// a confirmation token for redact::UNREDACTED_ACTION on "diagnostics". include_crash_reports
+// adds the pending crash reports, which are then no longer offered.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: AppHandle,
+    path: Option<String>,
+    unredacted: Option<bool>,
+    confirm_token: Option<String>,
+    include_crash_reports: Option<bool>,
+    tokens: State<'_, ConfirmationTokens>,
+    backend_logs: State<'_, BackendLogs>,
+) -> Result<Option<DiagnosticsResult>, String> {
+    lock::ensure_unlocked()?;
+    let redacted = !unredacted.unwrap_or(false);
+    if !redacted {
+        confirm_unredacted(&tokens, confirm_token.as_deref(), DIAGNOSTICS_TARGET)?;
+    }
+    let Some(path) = resolve_save_path(
+        &app,
+        path,
+        "Diagnostics",
+        "zip",
+        format!(
+            "ztkapp_diagnostics_{}.zip",
+            Local::now().format("%Y%m%d_%H%M")
+        ),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let data_dir = resolve_app_data_dir();
+    let mut entries = vec![(
+        SYSTEM_INFO.to_string(),
+        serde_json::to_string_pretty(&system_info(&app, redacted)).unwrap_or_default(),
+    )];
+    for (entry, name) in SHELL_FILES {
+        if let Some(content) = log_tail(&data_dir.join(name)) {
+            entries.push((entry.to_string(), content));
+        }
+    }
+    if let Some(content) = get_log_file_path().ok().and_then(|log| log_tail(&log)) {
+        entries.push((BACKEND_LOG.to_string(), content));
+    }
+    if let Ok(logs) = backend_logs.lock() {
+        entries.push((
+            BACKEND_SESSION_LOG.to_string(),
+            serde_json::to_string_pretty(&*logs).unwrap_or_default(),
+        ));
+    }
+    let mut attachments = Vec::new();
+    let mut crash_files = Vec::new();
+    if include_crash_reports.unwrap_or(false) {
+        for (entry, source, minidump) in crash::diagnostics_entries() {
+            if !minidump {
+                if let Ok(content) = fs::read_to_string(&source) {
+                    entries.push((entry, content));
+                }
+            } else if !redacted {
+                attachments.push((entry, source.clone()));
+            }
+            crash_files.push(source);
+        }
+    }
+
+    let target = path.clone();
+    let written = tauri::async_runtime::spawn_blocking(move || {
+        write_diagnostics(&target, entries, attachments, redacted)
+    })
+    .await
+    .map_err(|e| format!("Diagnostics task failed: {}", e))
+    .and_then(|result| result);
+    let path_text = path.to_string_lossy().to_string();
+    let result = match written {
+        Ok(files) => {
+            crash::mark_reported(&crash_files);
+            let result = DiagnosticsResult {
+                size_bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                path: path_text.clone(),
+                files,
+                redacted,
+                duration_ms: started.elapsed().as_millis(),
+            };
+            append_app_log(&format!(
+                "Exported {} diagnostics to {}",
+                if redacted { "redacted" } else { "unredacted" },
+                result.path
+            ));
+            Ok(Some(result))
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&path);
+            append_app_log(&format!("Diagnostics export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_diagnostics", &path_text, result)
+}
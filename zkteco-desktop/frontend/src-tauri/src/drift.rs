@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Local, Utc};
+use tauri::{AppHandle, Emitter};
+
+use crate::zk::ZkSession;
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Clock drift bookkeeping: every sync compares the terminal clock with the host clock, keeps the
+// latest measurement per device in device_drift.json and raises "device-drift-alert" when the
+// drift exceeds the configured threshold (optionally correcting the clock on the spot)
+static THRESHOLD_SECONDS: AtomicU64 = AtomicU64::new(120);
+static AUTO_CORRECT: AtomicBool = AtomicBool::new(false);
+static DRIFT_LOCK: Mutex<()> = Mutex::new(());
+// Set during setup so syncs without an AppHandle of their own can still alert
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DriftRecord {
+    device_id: String,
+    drift_seconds: i64, // positive when the device clock is ahead
+    measured_at: DateTime<Utc>,
+    corrected: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DriftAlert {
+    device_id: String,
+    drift_seconds: i64,
+    threshold_seconds: u64,
+    corrected: bool,
+    message: String,
+}
+
+pub fn set_drift_policy(threshold_seconds: u64, auto_correct: bool) {
+    THRESHOLD_SECONDS.store(threshold_seconds, Ordering::Relaxed);
+    AUTO_CORRECT.store(auto_correct, Ordering::Relaxed);
+}
+
+pub fn init_drift_alerts(app: AppHandle) {
+    let _ = APP.set(app);
+}
+
+fn drift_path() -> PathBuf {
+    resolve_app_data_dir().join("device_drift.json")
+}
+
+fn load_drift() -> HashMap<String, DriftRecord> {
+    fs::read_to_string(drift_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn exceeds(drift_seconds: i64) -> bool {
+    drift_seconds.unsigned_abs() > THRESHOLD_SECONDS.load(Ordering::Relaxed)
+}
+
+// Read the device clock inside an open session; with auto-correct on, a clock beyond the
+// threshold is reset to host time. Returns (drift_seconds, corrected).
+pub fn measure_drift(session: &mut ZkSession) -> Result<(i64, bool), String> {
+    let device_time = session.get_time()?;
+    let now = Local::now().naive_local();
+    let drift = device_time.signed_duration_since(now).num_seconds();
+    if exceeds(drift) && AUTO_CORRECT.load(Ordering::Relaxed) {
+        session.set_time(now)?;
+        return Ok((drift, true));
+    }
+    Ok((drift, false))
+}
+
+// Persist a measurement and alert when the device crosses the threshold; a device that stays
+// out of tolerance uncorrected is only reported once
+pub fn record_drift(device_id: &str, drift_seconds: i64, corrected: bool) {
+    let previous = {
+        let _guard = DRIFT_LOCK.lock();
+        let mut records = load_drift();
+        let previous = records.insert(
+            device_id.to_string(),
+            DriftRecord {
+                device_id: device_id.to_string(),
+                drift_seconds,
+                measured_at: Utc::now(),
+                corrected,
+            },
+        );
+        let written = serde_json::to_string_pretty(&records)
+            .map_err(|e| e.to_string())
+            .and_then(|content| fs::write(drift_path(), content).map_err(|e| e.to_string()));
+        if let Err(err) = written {
+            eprintln!("Failed to save device drift: {}", err);
+        }
+        previous
+    };
+
+    let already_reported = previous
+        .map(|p| exceeds(p.drift_seconds) && !p.corrected)
+        .unwrap_or(false);
+    if !exceeds(drift_seconds) || already_reported {
+        return;
+    }
+
+    let threshold_seconds = THRESHOLD_SECONDS.load(Ordering::Relaxed);
+    let alert = DriftAlert {
+        device_id: device_id.to_string(),
+        drift_seconds,
+        threshold_seconds,
+        corrected,
+        message: format!(
+            "Clock of device {} is {}s {} (threshold {}s){}",
+            device_id,
+            drift_seconds.unsigned_abs(),
+            if drift_seconds > 0 { "ahead" } else { "behind" },
+            threshold_seconds,
+            if corrected { ", corrected" } else { "" }
+        ),
+    };
+    eprintln!("Drift alert: {}", alert.message);
+    append_app_log(&format!("Drift alert raised: {}", alert.message));
+    if let Some(app) = APP.get() {
+        if let Err(err) = app.emit("device-drift-alert", &alert) {
+            eprintln!("Failed to emit device-drift-alert event: {}", err);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_device_drift() -> Result<Vec<DriftRecord>, String> {
+    let _guard = DRIFT_LOCK.lock();
+    let mut records: Vec<DriftRecord> = load_drift().into_values().collect();
+    records.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+    Ok(records)
+}
@@ -0,0 +1,341 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{Local, NaiveDateTime};
+use rusqlite::types::Value;
+use rusqlite::{params, Connection};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::database::{open_read_write, run_db};
+use crate::export::{device_filter, parse_range, ExportRange, RANGE_FILTER};
+use crate::lock;
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Duplicate punches: double taps on one terminal and the same punch picked up by two terminals.
+// Punches of one employee within the window of the first punch of a cluster are duplicates of
+// it. Merging keeps one record per cluster (the one already synced upstream if any, otherwise
+// the earliest) and writes the removed rows to an undo log.
+const DEFAULT_WINDOW_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PunchRef {
+    id: i64,
+    device_id: Option<String>,
+    timestamp: String,
+    action: i64,
+    sync_status: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateGroup {
+    user_id: String,
+    kind: String, // "double_tap" or "cross_device"
+    keep: PunchRef,
+    duplicates: Vec<PunchRef>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DuplicateReport {
+    groups: Vec<DuplicateGroup>,
+    duplicate_count: usize,
+    window_seconds: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct MergeLog {
+    merge_id: String,
+    merged_at: String,
+    from: String,
+    to: String,
+    window_seconds: u64,
+    // Removed rows, column name -> value
+    rows: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MergeResult {
+    merge_id: String,
+    groups: usize,
+    removed: usize,
+    undo_log: String,
+}
+
+fn merges_dir() -> PathBuf {
+    resolve_app_data_dir().join("duplicate_merges")
+}
+
+fn parse_timestamp(value: &str) -> Option<NaiveDateTime> {
+    let value = value.get(..19).unwrap_or(value);
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+}
+
+fn find_groups(
+    connection: &Connection,
+    range: &ExportRange,
+    devices: &Option<String>,
+    window_seconds: u64,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let (from, to) = parse_range(range)?;
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT a.user_id, a.id, a.device_id, a.timestamp, a.action, a.sync_status
+             FROM attendance_logs a
+             WHERE {}
+             ORDER BY a.user_id, a.timestamp, a.id",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let rows = statement
+        .query_map(params![from.to_string(), to.to_string(), devices], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                PunchRef {
+                    id: row.get(1)?,
+                    device_id: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    action: row.get(4)?,
+                    sync_status: row.get(5)?,
+                },
+            ))
+        })
+        .map_err(|e| format!("Failed to query attendance: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance row: {}", e))?;
+
+    let window = chrono::Duration::seconds(window_seconds as i64);
+    let mut clusters: Vec<(String, NaiveDateTime, Vec<PunchRef>)> = Vec::new();
+    for (user_id, punch) in rows {
+        let Some(at) = parse_timestamp(&punch.timestamp) else {
+            continue;
+        };
+        match clusters.last_mut() {
+            Some((user, start, punches)) if *user == user_id && at - *start <= window => {
+                punches.push(punch)
+            }
+            _ => clusters.push((user_id, at, vec![punch])),
+        }
+    }
+
+    Ok(clusters
+        .into_iter()
+        .filter(|(_, _, punches)| punches.len() > 1)
+        .map(|(user_id, _, mut punches)| {
+            let keep_index = punches
+                .iter()
+                .position(|p| p.sync_status.as_deref() == Some("synced"))
+                .unwrap_or(0);
+            let keep = punches.remove(keep_index);
+            let cross_device = punches.iter().any(|p| p.device_id != keep.device_id);
+            DuplicateGroup {
+                user_id,
+                kind: if cross_device {
+                    "cross_device"
+                } else {
+                    "double_tap"
+                }
+                .to_string(),
+                keep,
+                duplicates: punches,
+            }
+        })
+        .collect())
+}
+
+fn to_json(value: Value) -> serde_json::Value {
+    match value {
+        Value::Null | Value::Blob(_) => serde_json::Value::Null,
+        Value::Integer(n) => n.into(),
+        Value::Real(n) => n.into(),
+        Value::Text(text) => text.into(),
+    }
+}
+
+fn from_json(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Bool(flag) => Value::Integer(*flag as i64),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Value::Integer)
+            .or_else(|| n.as_f64().map(Value::Real))
+            .unwrap_or(Value::Null),
+        serde_json::Value::String(text) => Value::Text(text.clone()),
+        _ => Value::Null,
+    }
+}
+
+// Full rows for the undo log, read before they are deleted
+fn snapshot_rows(
+    connection: &Connection,
+    ids: &[i64],
+) -> Result<Vec<serde_json::Map<String, serde_json::Value>>, String> {
+    let ids = serde_json::to_string(ids).map_err(|e| e.to_string())?;
+    let mut statement = connection
+        .prepare("SELECT * FROM attendance_logs WHERE id IN (SELECT value FROM json_each(?1))")
+        .map_err(|e| format!("Failed to read duplicate rows: {}", e))?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let rows = statement
+        .query_map([ids], |row| {
+            let mut record = serde_json::Map::new();
+            for (index, column) in columns.iter().enumerate() {
+                record.insert(column.clone(), to_json(row.get::<_, Value>(index)?));
+            }
+            Ok(record)
+        })
+        .map_err(|e| format!("Failed to read duplicate rows: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read duplicate rows: {}", e))
+}
+
+fn merge(
+    range: ExportRange,
+    devices: Option<String>,
+    window_seconds: u64,
+) -> Result<MergeResult, String> {
+    let connection = open_read_write()?;
+    let groups = find_groups(&connection, &range, &devices, window_seconds)?;
+    let ids: Vec<i64> = groups
+        .iter()
+        .flat_map(|group| group.duplicates.iter().map(|p| p.id))
+        .collect();
+    let merge_id = Local::now().format("%Y%m%d_%H%M%S").to_string();
+    let log = MergeLog {
+        merge_id: merge_id.clone(),
+        merged_at: Local::now().to_rfc3339(),
+        from: range.from,
+        to: range.to,
+        window_seconds,
+        rows: snapshot_rows(&connection, &ids)?,
+    };
+
+    // The undo log is written before anything is deleted
+    fs::create_dir_all(merges_dir())
+        .map_err(|e| format!("Failed to create undo log directory: {}", e))?;
+    let undo_log = merges_dir().join(format!("merge_{}.json", merge_id));
+    let content = serde_json::to_string_pretty(&log)
+        .map_err(|e| format!("Failed to serialize undo log: {}", e))?;
+    fs::write(&undo_log, content).map_err(|e| format!("Failed to write undo log: {}", e))?;
+
+    let removed = connection
+        .execute(
+            "DELETE FROM attendance_logs WHERE id IN (SELECT value FROM json_each(?1))",
+            [serde_json::to_string(&ids).map_err(|e| e.to_string())?],
+        )
+        .map_err(|e| format!("Failed to remove duplicates: {}", e))?;
+    Ok(MergeResult {
+        merge_id,
+        groups: groups.len(),
+        removed,
+        undo_log: undo_log.to_string_lossy().to_string(),
+    })
+}
+
+fn undo(merge_id: &str) -> Result<usize, String> {
+    if merge_id.is_empty() || !merge_id.chars().all(|c| c.is_ascii_digit() || c == '_') {
+        return Err(format!("Invalid merge id '{}'", merge_id));
+    }
+    let path = merges_dir().join(format!("merge_{}.json", merge_id));
+    let log: MergeLog = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read undo log {:?}: {}", path, e))
+        .and_then(|content| {
+            serde_json::from_str(&content).map_err(|e| format!("Invalid undo log: {}", e))
+        })?;
+
+    let mut connection = open_read_write()?;
+    let tx = connection
+        .transaction()
+        .map_err(|e| format!("Failed to start undo: {}", e))?;
+    let mut restored = 0;
+    for row in &log.rows {
+        let columns: Vec<&String> = row.keys().collect();
+        let sql = format!(
+            "INSERT OR IGNORE INTO attendance_logs ({}) VALUES ({})",
+            columns
+                .iter()
+                .map(|c| format!("\"{}\"", c))
+                .collect::<Vec<_>>()
+                .join(", "),
+            (1..=columns.len())
+                .map(|i| format!("?{}", i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let values: Vec<Value> = row.values().map(from_json).collect();
+        restored += tx
+            .execute(&sql, rusqlite::params_from_iter(values))
+            .map_err(|e| format!("Failed to restore record: {}", e))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit undo: {}", e))?;
+    let _ = fs::rename(&path, path.with_extension("undone.json"));
+    Ok(restored)
+}
+
+#[tauri::command]
+pub async fn find_duplicate_punches(
+    range: ExportRange,
+    window_seconds: Option<u64>,
+    devices: Option<Vec<String>>,
+) -> Result<DuplicateReport, String> {
+    let window_seconds = window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS);
+    let devices = device_filter(&devices);
+    let groups =
+        run_db(move |connection| find_groups(connection, &range, &devices, window_seconds)).await?;
+    Ok(DuplicateReport {
+        duplicate_count: groups.iter().map(|g| g.duplicates.len()).sum(),
+        groups,
+        window_seconds,
+    })
+}
+
+// Remove the duplicates find_duplicate_punches reports for the same arguments
+#[tauri::command]
+pub async fn merge_duplicates(
+    range: ExportRange,
+    window_seconds: Option<u64>,
+    devices: Option<Vec<String>>,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<MergeResult, String> {
+    let target = format!("{}..{}", range.from, range.to);
+    consume_token(&tokens, &confirm_token, "merge_duplicates", &target)?;
+    let window_seconds = window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS);
+    let devices = device_filter(&devices);
+    let result =
+        tauri::async_runtime::spawn_blocking(move || merge(range, devices, window_seconds))
+            .await
+            .map_err(|e| format!("Merge task failed: {}", e))
+            .and_then(|result| result);
+    if let Ok(merge) = &result {
+        append_app_log(&format!(
+            "Merged {} duplicate punches in {} groups (undo log {})",
+            merge.removed, merge.groups, merge.undo_log
+        ));
+    }
+    audited("merge_duplicates", &target, result)
+}
+
+// Put back the records removed by a merge
+#[tauri::command]
+pub async fn undo_merge_duplicates(merge_id: String) -> Result<usize, String> {
+    lock::ensure_unlocked()?;
+    let id = merge_id.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || undo(&id))
+        .await
+        .map_err(|e| format!("Undo task failed: {}", e))
+        .and_then(|result| result);
+    if let Ok(restored) = &result {
+        append_app_log(&format!(
+            "Undid duplicate merge {} ({} records restored)",
+            merge_id, restored
+        ));
+    }
+    audited("undo_merge_duplicates", &merge_id, result)
+}
@@ -0,0 +1,498 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc};
+use openssl::ssl::{SslConnector, SslMethod};
+use tauri::State;
+
+use crate::database::run_db;
+use crate::export::{parse_range, ExportRange};
+use crate::report::{write_report, ReportTemplate};
+use crate::secrets::{random_hex, smtp_password};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{append_app_log, lock};
+
+// Outgoing mail over SMTP: the attendance report PDF on the email_report_schedule (or on
+// demand) and copies of the alerts.rs alert types listed in email_alerts. The client is just
+// enough SMTP to submit a message: EHLO, STARTTLS or implicit TLS, AUTH PLAIN/LOGIN, one
+// MAIL/RCPT/DATA transaction. Messages are MIME with base64 parts, so the body never needs
+// 8-bit transport.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const MAX_REPLY_LINE: usize = 4096;
+
+static STATS: Mutex<Option<EmailStats>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct EmailStats {
+    configured: bool,
+    messages_sent: u64,
+    last_sent_at: Option<DateTime<Utc>>,
+    last_subject: Option<String>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Security {
+    StartTls,
+    Tls,
+    None,
+}
+
+#[derive(Debug, Clone)]
+struct SmtpConfig {
+    host: String,
+    port: u16,
+    security: Security,
+    username: Option<String>,
+    from: String,
+}
+
+pub struct Attachment {
+    pub name: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+trait Transport: Read + Write + Send {}
+impl<T: Read + Write + Send> Transport for T {}
+
+fn update_stats(f: impl FnOnce(&mut EmailStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+fn smtp_config(settings: &ShellSettings) -> Result<SmtpConfig, String> {
+    let host = settings
+        .smtp_host
+        .as_deref()
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .ok_or("No SMTP server is configured")?;
+    let security = match settings.smtp_security.trim().to_ascii_lowercase().as_str() {
+        "starttls" | "" => Security::StartTls,
+        "tls" | "ssl" => Security::Tls,
+        "none" => Security::None,
+        other => return Err(format!("Unknown SMTP security '{}'", other)),
+    };
+    let username = settings
+        .smtp_username
+        .clone()
+        .filter(|user| !user.trim().is_empty());
+    let from = settings
+        .smtp_from
+        .clone()
+        .or_else(|| username.clone())
+        .filter(|from| !from.trim().is_empty())
+        .ok_or("No sender address (smtp_from) is configured")?;
+    check_address(&from)?;
+    Ok(SmtpConfig {
+        host: host.to_string(),
+        port: settings.smtp_port,
+        security,
+        username,
+        from: from.trim().to_string(),
+    })
+}
+
+// Plain addresses only; anything that could break out of a header or command is refused
+fn check_address(address: &str) -> Result<(), String> {
+    let address = address.trim();
+    let valid = address.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty() && domain.contains('.') && !domain.ends_with('.')
+    }) && !address
+        .chars()
+        .any(|c| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ',' | ';'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a valid email address", address))
+    }
+}
+
+fn wrapped_base64(data: &[u8]) -> String {
+    let encoded = BASE64.encode(data);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 38);
+    for chunk in encoded.as_bytes().chunks(76) {
+        wrapped.push_str(&String::from_utf8_lossy(chunk));
+        wrapped.push_str("\r\n");
+    }
+    wrapped
+}
+
+fn build_message(
+    config: &SmtpConfig,
+    recipients: &[String],
+    subject: &str,
+    body: &str,
+    attachments: &[Attachment],
+) -> String {
+    let boundary = format!("ztkapp-{}", random_hex(12));
+    let domain = config.from.split('@').nth(1).unwrap_or("localhost");
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+         MIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n",
+        config.from,
+        recipients.join(", "),
+        BASE64.encode(subject),
+        Local::now().to_rfc2822(),
+        random_hex(16),
+        domain,
+        boundary
+    );
+    message.push_str(&format!(
+        "--{}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+        boundary,
+        wrapped_base64(body.as_bytes())
+    ));
+    for attachment in attachments {
+        let name = attachment.name.replace(['"', '\r', '\n'], "_");
+        message.push_str(&format!(
+            "--{}\r\nContent-Type: {}; name=\"{}\"\r\nContent-Disposition: attachment; \
+             filename=\"{}\"\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            boundary,
+            attachment.content_type,
+            name,
+            name,
+            wrapped_base64(&attachment.data)
+        ));
+    }
+    message.push_str(&format!("--{}--\r\n", boundary));
+    message
+}
+
+struct SmtpSession {
+    stream: Box<dyn Transport>,
+}
+
+impl SmtpSession {
+    fn command(&mut self, line: &str) -> Result<(u16, String), String> {
+        self.stream
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .and_then(|_| self.stream.flush())
+            .map_err(|e| format!("Failed to write to SMTP server: {}", e))?;
+        self.reply()
+    }
+
+    // Read a (possibly multi-line) reply: "250-..." lines until "250 ..."
+    fn reply(&mut self) -> Result<(u16, String), String> {
+        let mut text = String::new();
+        loop {
+            let mut line = Vec::new();
+            let mut byte = [0u8; 1];
+            while !line.ends_with(b"\r\n") {
+                self.stream
+                    .read_exact(&mut byte)
+                    .map_err(|e| format!("Failed to read from SMTP server: {}", e))?;
+                line.push(byte[0]);
+                if line.len() > MAX_REPLY_LINE {
+                    return Err("SMTP server sent an overlong reply".to_string());
+                }
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| format!("Unexpected SMTP reply '{}'", line))?;
+            text.push_str(line.get(4..).unwrap_or_default());
+            text.push('\n');
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+        }
+    }
+
+    fn expect(&mut self, line: &str, codes: &[u16], what: &str) -> Result<String, String> {
+        let (code, text) = self.command(line)?;
+        if codes.contains(&code) {
+            Ok(text)
+        } else {
+            Err(format!("SMTP {} failed ({}): {}", what, code, text.trim()))
+        }
+    }
+}
+
+fn tls_wrap(host: &str, tcp: TcpStream) -> Result<Box<dyn Transport>, String> {
+    let connector = SslConnector::builder(SslMethod::tls_client())
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?
+        .build();
+    Ok(Box::new(connector.connect(host, tcp).map_err(|e| {
+        format!("TLS handshake with {} failed: {}", host, e)
+    })?))
+}
+
+fn send_blocking(config: &SmtpConfig, recipients: &[String], message: &str) -> Result<(), String> {
+    let address = (config.host.as_str(), config.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", config.host, e))?
+        .next()
+        .ok_or_else(|| format!("No address found for {}", config.host))?;
+    let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| format!("Failed to configure SMTP socket: {}", e))?;
+
+    let mut session = SmtpSession {
+        stream: if config.security == Security::Tls {
+            tls_wrap(&config.host, tcp.try_clone().map_err(|e| e.to_string())?)?
+        } else {
+            Box::new(tcp.try_clone().map_err(|e| e.to_string())?)
+        },
+    };
+    let (code, text) = session.reply()?;
+    if code != 220 {
+        return Err(format!(
+            "SMTP server refused the connection: {}",
+            text.trim()
+        ));
+    }
+    let mut capabilities = session.expect("EHLO ztkapp.local", &[250], "EHLO")?;
+    if config.security == Security::StartTls {
+        session.expect("STARTTLS", &[220], "STARTTLS")?;
+        session.stream = tls_wrap(&config.host, tcp)?;
+        capabilities = session.expect("EHLO ztkapp.local", &[250], "EHLO")?;
+    }
+
+    if let Some(username) = &config.username {
+        let password = smtp_password().unwrap_or_default();
+        let auth_line = capabilities
+            .lines()
+            .find(|line| line.to_ascii_uppercase().starts_with("AUTH"))
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        if auth_line.contains("PLAIN") || !auth_line.contains("LOGIN") {
+            let token = BASE64.encode(format!("\0{}\0{}", username, password));
+            session.expect(&format!("AUTH PLAIN {}", token), &[235], "login")?;
+        } else {
+            session.expect("AUTH LOGIN", &[334], "login")?;
+            session.expect(&BASE64.encode(username), &[334], "login")?;
+            session.expect(&BASE64.encode(&password), &[235], "login")?;
+        }
+    }
+
+    session.expect(&format!("MAIL FROM:<{}>", config.from), &[250], "MAIL FROM")?;
+    for recipient in recipients {
+        session.expect(&format!("RCPT TO:<{}>", recipient), &[250, 251], "RCPT TO")?;
+    }
+    session.expect("DATA", &[354], "DATA")?;
+    // Dot-stuffing; the base64 parts never start a line with '.', the headers might
+    let stuffed = message.replace("\r\n.", "\r\n..");
+    session.expect(&format!("{}\r\n.", stuffed.trim_end()), &[250], "message")?;
+    let _ = session.command("QUIT");
+    Ok(())
+}
+
+pub async fn send_email(
+    settings: &ShellSettings,
+    recipients: &[String],
+    subject: &str,
+    body: &str,
+    attachments: Vec<Attachment>,
+) -> Result<(), String> {
+    let config = smtp_config(settings)?;
+    let recipients: Vec<String> = recipients
+        .iter()
+        .map(|recipient| recipient.trim().to_string())
+        .filter(|recipient| !recipient.is_empty())
+        .collect();
+    if recipients.is_empty() {
+        return Err("No email recipients are configured".to_string());
+    }
+    for recipient in &recipients {
+        check_address(recipient)?;
+    }
+    let message = build_message(&config, &recipients, subject, body, &attachments);
+
+    let to = recipients.clone();
+    let result =
+        tauri::async_runtime::spawn_blocking(move || send_blocking(&config, &to, &message))
+            .await
+            .map_err(|e| format!("Email task failed: {}", e))
+            .and_then(|result| result);
+    let subject = subject.to_string();
+    match &result {
+        Ok(()) => update_stats(|stats| {
+            stats.messages_sent += 1;
+            stats.last_sent_at = Some(Utc::now());
+            stats.last_subject = Some(subject);
+            stats.last_error = None;
+        }),
+        Err(err) => update_stats(|stats| stats.last_error = Some(err.clone())),
+    }
+    result
+}
+
+// Mail an alerts.rs alert when its type is listed in email_alerts
+pub fn send_alert(settings: &ShellSettings, alert: &str, title: &str, text: &str) {
+    if settings.email_alert_recipients.is_empty()
+        || !settings.email_alerts.iter().any(|a| a == alert)
+    {
+        return;
+    }
+    let settings = settings.clone();
+    let subject = format!("[ZKTeco Desktop] {}", title);
+    let body = text.to_string();
+    tauri::async_runtime::spawn(async move {
+        let recipients = settings.email_alert_recipients.clone();
+        if let Err(err) = send_email(&settings, &recipients, &subject, &body, Vec::new()).await {
+            eprintln!("Alert email failed: {}", err);
+            append_app_log(&format!("Alert email failed: {}", err));
+        }
+    });
+}
+
+async fn report_attachment(range: (NaiveDate, NaiveDate)) -> Result<Attachment, String> {
+    let name = format!("attendance_{}_{}.pdf", range.0, range.1);
+    let path = std::env::temp_dir().join(format!("ztkapp_{}_{}", random_hex(8), name));
+    let target = path.clone();
+    let template = ReportTemplate {
+        title: Some("Attendance report".to_string()),
+        ..Default::default()
+    };
+    let written =
+        run_db(move |connection| write_report(connection, range, None, &template, &target)).await;
+    let data = written.and_then(|_| {
+        std::fs::read(&path).map_err(|e| format!("Failed to read the report: {}", e))
+    });
+    let _ = std::fs::remove_file(&path);
+    Ok(Attachment {
+        name,
+        content_type: "application/pdf".to_string(),
+        data: data?,
+    })
+}
+
+async fn send_report(
+    settings: &ShellSettings,
+    range: (NaiveDate, NaiveDate),
+    recipients: &[String],
+) -> Result<(), String> {
+    let attachment = report_attachment(range).await?;
+    let period = if range.0 == range.1 {
+        range.0.to_string()
+    } else {
+        format!("{} to {}", range.0, range.1)
+    };
+    send_email(
+        settings,
+        recipients,
+        &format!("Attendance report {}", period),
+        &format!(
+            "The attendance report for {} is attached.\r\n\r\nSent by ZKTeco Desktop.",
+            period
+        ),
+        vec![attachment],
+    )
+    .await
+}
+
+// The scheduled report the current time falls in, if one is due today
+fn scheduled_slot(config: &ShellSettings, now: DateTime<Local>) -> Option<NaiveDate> {
+    let at = NaiveTime::parse_from_str(&config.email_report_time, "%H:%M").ok()?;
+    let today = now.date_naive();
+    let due = match config.email_report_schedule.as_str() {
+        "daily" => true,
+        "weekly" => today.weekday().number_from_monday() == config.email_report_weekday,
+        _ => false,
+    };
+    (due && now.time() >= at).then_some(today)
+}
+
+pub fn spawn_email_scheduler(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_slot: Option<NaiveDate> = None;
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let config = current_settings(&settings);
+            let Some(slot) = scheduled_slot(&config, Local::now()) else {
+                continue;
+            };
+            if last_slot == Some(slot) {
+                continue;
+            }
+            last_slot = Some(slot);
+
+            // Complete days only: yesterday, or the seven days before today
+            let Some(yesterday) = slot.pred_opt() else {
+                continue;
+            };
+            let from = match config.email_report_schedule.as_str() {
+                "weekly" => yesterday - chrono::Duration::days(6),
+                _ => yesterday,
+            };
+            let recipients = config.email_report_recipients.clone();
+            match send_report(&config, (from, yesterday), &recipients).await {
+                Ok(()) => append_app_log(&format!(
+                    "Scheduled attendance report for {} to {} emailed to {} recipients",
+                    from,
+                    yesterday,
+                    recipients.len()
+                )),
+                Err(err) => {
+                    eprintln!("Scheduled report email failed: {}", err);
+                    append_app_log(&format!("Scheduled report email failed: {}", err));
+                }
+            }
+        }
+    });
+}
+
+// Send a short message to check the SMTP settings, to `to` or the report recipients
+#[tauri::command]
+pub async fn send_test_email(
+    to: Option<String>,
+    settings: State<'_, SharedSettings>,
+) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let config = current_settings(&settings);
+    let recipients = match to {
+        Some(to) => vec![to],
+        None => config.email_report_recipients.clone(),
+    };
+    send_email(
+        &config,
+        &recipients,
+        "Test message from ZKTeco Desktop",
+        "Email delivery from ZKTeco Desktop is working.",
+        Vec::new(),
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn email_attendance_report(
+    range: ExportRange,
+    recipients: Option<Vec<String>>,
+    settings: State<'_, SharedSettings>,
+) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let config = current_settings(&settings);
+    let range = parse_range(&range)?;
+    let recipients = recipients.unwrap_or_else(|| config.email_report_recipients.clone());
+    send_report(&config, range, &recipients).await?;
+    append_app_log(&format!(
+        "Attendance report for {} to {} emailed to {} recipients",
+        range.0,
+        range.1,
+        recipients.len()
+    ));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_email_status(settings: State<SharedSettings>) -> EmailStats {
+    let mut stats = STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default();
+    stats.configured = smtp_config(&current_settings(&settings)).is_ok();
+    stats
+}
@@ -0,0 +1,328 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use rusqlite::{Connection, OpenFlags};
+use tauri::{AppHandle, State};
+
+use crate::audit::audited;
+use crate::backup::{start_backend_after_maintenance, stop_backend_for_maintenance};
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::database::{archive_db_path, integrity_problems};
+use crate::secrets::hex;
+use crate::{append_app_log, resolve_backend_db_path};
+
+// SQLCipher encryption at rest for zkteco_app.db (and the attendance archive). The raw 256-bit
+// key lives in the OS keychain, never on disk; whether a database is encrypted is read from its
+// header, so plaintext backups stay restorable after the switch.
+pub const KEYRING_SERVICE: &str = "ztkapp";
+pub const KEYRING_ACCOUNT: &str = "database-key";
+const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// Read from the keychain on first use
+static DATABASE_KEY: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EncryptionStatus {
+    encrypted: bool,
+    archive_encrypted: bool,
+    key_available: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EncryptionResult {
+    database: String,
+    archive_encrypted: bool,
+    backend_restarted: bool,
+    message: String,
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access the OS keychain: {}", e))
+}
+
+// An existing file whose header is not the plaintext SQLite magic
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|_| &header != PLAINTEXT_HEADER)
+        .unwrap_or(false)
+}
+
+fn stored_key() -> Result<Option<String>, String> {
+    let mut cached = DATABASE_KEY
+        .lock()
+        .map_err(|e| format!("Failed to lock database key: {}", e))?;
+    if cached.is_none() {
+        *cached = match keyring_entry()?.get_password() {
+            Ok(key) => Some(key),
+            Err(keyring::Error::NoEntry) => None,
+            Err(err) => return Err(format!("Failed to read database key: {}", err)),
+        };
+    }
+    Ok(cached.clone())
+}
+
+fn load_or_create_key() -> Result<String, String> {
+    if let Some(key) = stored_key()? {
+        return Ok(key);
+    }
+    let key = hex(&Aes256Gcm::generate_key(OsRng));
+    keyring_entry()?
+        .set_password(&key)
+        .map_err(|e| format!("Failed to store database key in the OS keychain: {}", e))?;
+    if let Ok(mut cached) = DATABASE_KEY.lock() {
+        *cached = Some(key.clone());
+    }
+    Ok(key)
+}
+
+fn required_key() -> Result<String, String> {
+    stored_key()?.ok_or_else(|| {
+        "The database is encrypted but its key is missing from the OS keychain".to_string()
+    })
+}
+
+fn raw_key(key: &str) -> String {
+    format!("x'{}'", key)
+}
+
+fn set_key(connection: &Connection, key: &str) -> Result<(), String> {
+    connection
+        .pragma_update(None, "key", raw_key(key))
+        .map_err(|e| format!("Failed to unlock database: {}", e))
+}
+
+// Key a connection to `path` when that file is encrypted; must run before any other statement
+pub fn unlock(connection: &Connection, path: &Path) -> Result<(), String> {
+    if !is_encrypted(path) {
+        return Ok(());
+    }
+    set_key(connection, &required_key()?)
+}
+
+// Key a connection to a new file (e.g. a backup) the same way as the backend database
+pub fn match_primary(connection: &Connection) -> Result<(), String> {
+    unlock(connection, &resolve_backend_db_path())
+}
+
+// Key handed to the backend sidecar in ZKTECO_DB_KEY, only when the database is encrypted
+pub fn backend_key() -> Option<String> {
+    if !is_encrypted(&resolve_backend_db_path()) {
+        return None;
+    }
+    match required_key() {
+        Ok(key) => Some(key),
+        Err(err) => {
+            eprintln!("{}", err);
+            append_app_log(&err);
+            None
+        }
+    }
+}
+
+fn sidecar(base: &Path, suffix: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", base.display(), suffix))
+}
+
+// Write an encrypted copy of a plaintext database with sqlcipher_export, check it and move it
+// over the original. The plaintext file and its WAL are deleted.
+fn encrypt_file(path: &Path, key: &str) -> Result<(), String> {
+    let encrypted = sidecar(path, ".encrypting");
+    let _ = fs::remove_file(&encrypted);
+    {
+        let connection = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        let user_version: i64 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read schema version: {}", e))?;
+        connection
+            .execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")
+            .map_err(|e| format!("Failed to checkpoint database: {}", e))?;
+        connection
+            .execute(
+                "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+                [encrypted.to_string_lossy().to_string(), raw_key(key)],
+            )
+            .map_err(|e| format!("Failed to create encrypted database: {}", e))?;
+        let exported = connection
+            .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            .and_then(|_| {
+                connection.execute_batch(&format!(
+                    "PRAGMA encrypted.user_version = {}; DETACH DATABASE encrypted;",
+                    user_version
+                ))
+            });
+        if let Err(err) = exported {
+            let _ = fs::remove_file(&encrypted);
+            return Err(format!("Failed to encrypt database: {}", err));
+        }
+    }
+
+    let verified = Connection::open_with_flags(&encrypted, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open encrypted database: {}", e))
+        .and_then(|connection| {
+            set_key(&connection, key)?;
+            integrity_problems(&connection, true)
+        });
+    match verified {
+        Ok(problems) if problems.is_empty() => {}
+        Ok(problems) => {
+            let _ = fs::remove_file(&encrypted);
+            return Err(format!(
+                "Encrypted database failed the integrity check: {}",
+                problems.join("; ")
+            ));
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&encrypted);
+            return Err(err);
+        }
+    }
+
+    let plaintext = sidecar(path, ".plaintext");
+    fs::rename(path, &plaintext)
+        .map_err(|e| format!("Failed to move plaintext database aside: {}", e))?;
+    if let Err(err) = fs::rename(&encrypted, path) {
+        let _ = fs::rename(&plaintext, path);
+        let _ = fs::remove_file(&encrypted);
+        return Err(format!(
+            "Failed to move encrypted database into place: {}",
+            err
+        ));
+    }
+    for leftover in [
+        plaintext,
+        sidecar(path, "-wal"),
+        sidecar(path, "-shm"),
+        sidecar(path, "-journal"),
+    ] {
+        if let Err(err) = fs::remove_file(&leftover) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("Failed to remove {:?}: {}", leftover, err);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Decrypt a copy of an encrypted database in place of `target` (e.g. for a bundle that is
+// opened on another PC, where the key is not in the keychain)
+pub fn export_plaintext(path: &Path, target: &Path) -> Result<(), String> {
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    set_key(&connection, &required_key()?)?;
+    let user_version: i64 = connection
+        .query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+    connection
+        .execute(
+            "ATTACH DATABASE ?1 AS plaintext KEY ''",
+            [target.to_string_lossy().to_string()],
+        )
+        .map_err(|e| format!("Failed to create plaintext database: {}", e))?;
+    let exported = connection
+        .query_row("SELECT sqlcipher_export('plaintext')", [], |_| Ok(()))
+        .and_then(|_| {
+            connection.execute_batch(&format!(
+                "PRAGMA plaintext.user_version = {}; DETACH DATABASE plaintext;",
+                user_version
+            ))
+        });
+    if let Err(err) = exported {
+        let _ = fs::remove_file(target);
+        return Err(format!("Failed to decrypt database: {}", err));
+    }
+    Ok(())
+}
+
+// Returns whether the archive was encrypted as well
+fn encrypt_databases() -> Result<bool, String> {
+    let database = resolve_backend_db_path();
+    if !database.exists() {
+        return Err(format!("Backend database not found at {:?}", database));
+    }
+    if is_encrypted(&database) {
+        return Err("The database is already encrypted".to_string());
+    }
+    let key = load_or_create_key()?;
+
+    // The archive first: once the main database is keyed, ATTACH expects the same key
+    let archive = archive_db_path();
+    let archive_encrypted = archive.exists() && !is_encrypted(&archive);
+    if archive_encrypted {
+        encrypt_file(&archive, &key)?;
+    }
+    encrypt_file(&database, &key)?;
+    Ok(archive_encrypted)
+}
+
+#[tauri::command]
+pub fn get_database_encryption() -> Result<EncryptionStatus, String> {
+    Ok(EncryptionStatus {
+        encrypted: is_encrypted(&resolve_backend_db_path()),
+        archive_encrypted: is_encrypted(&archive_db_path()),
+        key_available: stored_key()?.is_some(),
+    })
+}
+
+// One-time migration of a plaintext database to SQLCipher. The backend is stopped for the
+// duration and started again with the key. Existing backups are left as they are.
+#[tauri::command]
+pub async fn encrypt_database(
+    app: AppHandle,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<EncryptionResult, String> {
+    let database = resolve_backend_db_path().to_string_lossy().to_string();
+    consume_token(&tokens, &confirm_token, "encrypt_database", &database)?;
+    let result = encrypt_with_backend_stopped(&app, database.clone()).await;
+    match &result {
+        Ok(encryption) => append_app_log(&encryption.message),
+        Err(err) => append_app_log(&format!("Database encryption failed: {}", err)),
+    }
+    audited("encrypt_database", &database, result)
+}
+
+async fn encrypt_with_backend_stopped(
+    app: &AppHandle,
+    database: String,
+) -> Result<EncryptionResult, String> {
+    let was_running = stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, encryption aborted", e))?;
+    let encrypted = tauri::async_runtime::spawn_blocking(encrypt_databases)
+        .await
+        .map_err(|e| format!("Encryption task failed: {}", e))
+        .and_then(|result| result);
+    let started = if was_running {
+        Some(start_backend_after_maintenance(app).await)
+    } else {
+        None
+    };
+
+    let archive_encrypted = encrypted?;
+    Ok(EncryptionResult {
+        database: database.clone(),
+        archive_encrypted,
+        backend_restarted: matches!(started, Some(Ok(_))),
+        message: match started {
+            Some(Err(err)) => format!(
+                "Database {} encrypted, but the backend failed to start: {}",
+                database, err
+            ),
+            _ => format!("Database {} encrypted", database),
+        },
+    })
+}
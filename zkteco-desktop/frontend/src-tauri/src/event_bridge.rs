@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter};
+
+use crate::backend_auth::backend_stream_client;
+use crate::device_manager::backoff_with_jitter;
+use crate::monitor::{MonitorBus, MonitorEvent};
+use crate::{append_app_log, backend_base_url, current_backend_port, BackendPort};
+
+// One connection from the shell to the backend's event stream, re-emitted as Tauri events so
+// the UI has a single event source that survives backend restarts and port changes:
+//   backend-attendance     punches (live capture and PUSH devices)
+//   backend-door-access    door access logs
+//   backend-device-ping    device health pings
+//   backend-event          anything else, as {type, payload}
+//   backend-events-status  {connected} whenever the stream connects or drops
+// Punches also go out on the monitor bus (MQTT and other integrations).
+const STREAM_PATH: &str = "/devices/events";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// The backend sends a keep-alive every 25 seconds; longer silence means a dead connection
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BridgeStatus {
+    connected: bool,
+}
+
+fn set_connected(app: &AppHandle, connected: bool) {
+    if CONNECTED.swap(connected, Ordering::SeqCst) == connected {
+        return;
+    }
+    append_app_log(&format!(
+        "Backend event stream {}",
+        if connected {
+            "connected"
+        } else {
+            "disconnected"
+        }
+    ));
+    if let Err(err) = app.emit("backend-events-status", BridgeStatus { connected }) {
+        eprintln!("Failed to emit backend-events-status event: {}", err);
+    }
+}
+
+// The data of an unnamed SSE message; named ones ("ready") and comments carry nothing
+fn message_data(block: &str) -> Option<String> {
+    let mut data = Vec::new();
+    for line in block.lines() {
+        if line.starts_with("event:") {
+            return None;
+        }
+        if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    (!data.is_empty()).then(|| data.join("\n"))
+}
+
+fn dispatch(app: &AppHandle, bus: &MonitorBus, data: &str) {
+    let Ok(payload) = serde_json::from_str::<serde_json::Value>(data) else {
+        return;
+    };
+    let kind = payload.get("type").and_then(|kind| kind.as_str());
+    let result = match kind {
+        // PUSH devices publish punches without a type
+        None | Some("attendance_log") => {
+            let _ = bus.send(MonitorEvent::Punch(payload.clone()));
+            app.emit("backend-attendance", &payload)
+        }
+        Some("door_log") => app.emit("backend-door-access", &payload),
+        Some("device_ping") => app.emit("backend-device-ping", &payload),
+        Some(other) => app.emit(
+            "backend-event",
+            serde_json::json!({ "type": other, "payload": payload }),
+        ),
+    };
+    if let Err(err) = result {
+        eprintln!("Failed to re-emit backend event: {}", err);
+    }
+}
+
+// Read the stream until it closes, fails or goes silent
+async fn stream_events(app: &AppHandle, bus: &MonitorBus, port: u16) -> Result<(), String> {
+    let url = format!("{}{}", backend_base_url(port), STREAM_PATH);
+    let mut response = backend_stream_client(CONNECT_TIMEOUT)?
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Backend event stream unreachable: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Backend event stream answered {}",
+            response.status()
+        ));
+    }
+    set_connected(app, true);
+
+    // Raw bytes until a message is complete, so characters split across chunks survive
+    let mut buffer: Vec<u8> = Vec::new();
+    loop {
+        let chunk = tokio::time::timeout(IDLE_TIMEOUT, response.chunk())
+            .await
+            .map_err(|_| "Backend event stream went silent".to_string())?
+            .map_err(|e| format!("Backend event stream failed: {}", e))?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        buffer.extend(chunk.iter().filter(|byte| **byte != b'\r'));
+        while let Some(end) = buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let block: Vec<u8> = buffer.drain(..end + 2).collect();
+            if let Some(data) = message_data(&String::from_utf8_lossy(&block)) {
+                dispatch(app, bus, &data);
+            }
+        }
+    }
+}
+
+pub fn spawn_event_bridge(app: AppHandle, backend_port: BackendPort, bus: MonitorBus) {
+    tauri::async_runtime::spawn(async move {
+        let mut failures = 0;
+        loop {
+            let result = stream_events(&app, &bus, current_backend_port(&backend_port)).await;
+            if CONNECTED.load(Ordering::SeqCst) {
+                failures = 0;
+            }
+            set_connected(&app, false);
+            // Only the first failure is worth logging while the backend stays down
+            if let (Err(err), 0) = (result, failures) {
+                eprintln!("{}", err);
+            }
+            tokio::time::sleep(backoff_with_jitter(
+                RECONNECT_BASE_DELAY,
+                failures,
+                RECONNECT_MAX_DELAY,
+            ))
+            .await;
+            failures += 1;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_backend_events_status() -> BridgeStatus {
+    BridgeStatus {
+        connected: CONNECTED.load(Ordering::SeqCst),
+    }
+}
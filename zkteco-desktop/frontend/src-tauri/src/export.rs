@@ -0,0 +1,534 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use rusqlite::{params, Connection};
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, Worksheet, XlsxError};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_dialog::DialogExt;
+
+use crate::append_app_log;
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::lock;
+use crate::path_policy::{self, check_path, Access};
+use crate::protect;
+use crate::timezones::{normalize_stored, registered_zones};
+
+// Attendance exports written straight from zkteco_app.db, row by row, so month-end exports
+// neither need a healthy backend nor hold the whole range in memory
+const PROGRESS_EVERY: u64 = 1000;
+const CSV_HEADER: &str =
+    "id,user_id,user_name,device_id,serial_number,timestamp,method,action,sync_status,timestamp_utc,timezone,utc_offset";
+
+// Inclusive local dates, YYYY-MM-DD
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ExportRange {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ExportProgress {
+    pub path: String,
+    pub exported: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ExportResult {
+    path: String,
+    rows: u64,
+    duration_ms: u128,
+}
+
+impl ExportResult {
+    pub fn new(path: String, rows: u64, started: Instant) -> Self {
+        ExportResult {
+            path,
+            rows,
+            duration_ms: started.elapsed().as_millis(),
+        }
+    }
+}
+
+pub fn parse_range(range: &ExportRange) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |value: &str| {
+        NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", value))
+    };
+    let (from, to) = (parse(&range.from)?, parse(&range.to)?);
+    if from > to {
+        return Err(format!(
+            "Export range starts after it ends ({} > {})",
+            from, to
+        ));
+    }
+    Ok((from, to))
+}
+
+// Device filter bound as a JSON array (NULL = all devices) so one statement covers any selection
+pub fn device_filter(devices: &Option<Vec<String>>) -> Option<String> {
+    devices
+        .as_ref()
+        .filter(|devices| !devices.is_empty())
+        .and_then(|devices| serde_json::to_string(devices).ok())
+}
+
+pub const RANGE_FILTER: &str = "a.timestamp >= ?1 AND a.timestamp < date(?2, '+1 day')
+    AND (?3 IS NULL OR a.device_id IN (SELECT value FROM json_each(?3)))";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write_csv(
+    connection: &Connection,
+    range: (NaiveDate, NaiveDate),
+    devices: Option<String>,
+    zones: &HashMap<String, Tz>,
+    path: &str,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<u64, String> {
+    let (from, to) = (range.0.to_string(), range.1.to_string());
+    let total: u64 = connection
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM attendance_logs a WHERE {}",
+                RANGE_FILTER
+            ),
+            params![from, to, devices],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to count attendance: {}", e))?;
+
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = BufWriter::new(file);
+    let write_error = |e: std::io::Error| format!("Failed to write {}: {}", path, e);
+    writeln!(writer, "{}", CSV_HEADER).map_err(write_error)?;
+
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT a.id, a.user_id,
+                (SELECT u.name FROM users u WHERE u.user_id = a.user_id LIMIT 1),
+                a.device_id, a.serial_number, a.timestamp, a.method, a.action, a.sync_status
+             FROM attendance_logs a
+             WHERE {}
+             ORDER BY a.timestamp, a.id",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let mut rows = statement
+        .query(params![from, to, devices])
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+
+    let mut exported = 0u64;
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| format!("Failed to read attendance row: {}", e))?
+    {
+        let text = |index: usize| -> String {
+            row.get::<_, Option<String>>(index)
+                .ok()
+                .flatten()
+                .unwrap_or_default()
+        };
+        let number = |index: usize| -> String {
+            row.get::<_, Option<i64>>(index)
+                .ok()
+                .flatten()
+                .map(|n| n.to_string())
+                .unwrap_or_default()
+        };
+        // timestamp stays the device's wall clock; the UTC columns make sites comparable
+        let normalized = normalize_stored(&text(5), zones.get(&text(3)).copied());
+        let (utc, timezone, offset) = normalized
+            .map(|n| (n.timestamp_utc, n.timezone, n.utc_offset))
+            .unwrap_or_default();
+        let line = [
+            number(0),
+            text(1),
+            text(2),
+            text(3),
+            text(4),
+            text(5),
+            number(6),
+            number(7),
+            text(8),
+            utc,
+            timezone,
+            offset,
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",");
+        writeln!(writer, "{}", line).map_err(write_error)?;
+
+        exported += 1;
+        if exported.is_multiple_of(PROGRESS_EVERY) {
+            on_progress(exported, total);
+        }
+    }
+    writer.flush().map_err(write_error)?;
+    on_progress(exported, total);
+    Ok(exported)
+}
+
+#[tauri::command]
+pub async fn export_attendance_csv(
+    app: AppHandle,
+    range: ExportRange,
+    devices: Option<Vec<String>>,
+    path: String,
+    password: Option<String>,
+) -> Result<ExportResult, String> {
+    lock::ensure_unlocked()?;
+    let range = parse_range(&range)?;
+    let password = protect::check_password(password)?;
+    write_attendance_csv(range, devices, &path, password, move |progress| {
+        if let Err(err) = app.emit("export-progress", &progress) {
+            eprintln!("Failed to emit export-progress event: {}", err);
+        }
+    })
+    .await
+}
+
+// The CSV export behind export_attendance_csv and the headless CLI
+pub async fn write_attendance_csv(
+    range: (NaiveDate, NaiveDate),
+    devices: Option<Vec<String>>,
+    path: &str,
+    password: Option<String>,
+    on_progress: impl Fn(ExportProgress) + Send + 'static,
+) -> Result<ExportResult, String> {
+    let devices = device_filter(&devices);
+    let started = Instant::now();
+
+    let path = check_path(path, Access::Write)?
+        .to_string_lossy()
+        .to_string();
+    let written = protect::write_path(Path::new(&path), &password)?;
+    let progress_path = path.clone();
+    let target = written.to_string_lossy().to_string();
+    let audit_target = path.clone();
+    let zones = registered_zones();
+    let rows = run_db(move |connection| {
+        write_csv(
+            connection,
+            range,
+            devices,
+            &zones,
+            &target,
+            |exported, total| {
+                on_progress(ExportProgress {
+                    path: progress_path.clone(),
+                    exported,
+                    total,
+                })
+            },
+        )
+    })
+    .await;
+    let rows = protect::finish(rows, written, Path::new(&audit_target), password).await;
+
+    let result = match rows {
+        Ok((rows, path)) => {
+            append_app_log(&format!(
+                "Exported {} attendance records ({} to {}) to {}",
+                rows, range.0, range.1, path
+            ));
+            Ok(ExportResult {
+                path,
+                rows,
+                duration_ms: started.elapsed().as_millis(),
+            })
+        }
+        Err(err) => {
+            append_app_log(&format!("Attendance CSV export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_attendance_csv", &audit_target, result)
+}
+
+// One employee-day: first and last punch, as used by the XLSX and PDF reports
+pub struct DailyRow {
+    pub user_id: String,
+    pub name: String,
+    pub date: String,
+    pub first_in: String,
+    pub last_out: String,
+    pub punches: u32,
+    pub hours: f64,
+}
+
+pub fn daily_rows(
+    connection: &Connection,
+    range: (NaiveDate, NaiveDate),
+    devices: &Option<String>,
+) -> Result<Vec<DailyRow>, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT a.user_id,
+                COALESCE((SELECT u.name FROM users u WHERE u.user_id = a.user_id LIMIT 1), ''),
+                substr(a.timestamp, 1, 10), MIN(a.timestamp), MAX(a.timestamp), COUNT(*),
+                (julianday(MAX(a.timestamp)) - julianday(MIN(a.timestamp))) * 24
+             FROM attendance_logs a
+             WHERE {}
+             GROUP BY a.user_id, substr(a.timestamp, 1, 10)
+             ORDER BY a.user_id, substr(a.timestamp, 1, 10)",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to query daily attendance: {}", e))?;
+    let rows = statement
+        .query_map(
+            params![range.0.to_string(), range.1.to_string(), devices],
+            |row| {
+                Ok(DailyRow {
+                    user_id: row.get(0)?,
+                    name: row.get(1)?,
+                    date: row.get(2)?,
+                    first_in: row.get(3)?,
+                    last_out: row.get(4)?,
+                    punches: row.get(5)?,
+                    hours: row.get::<_, Option<f64>>(6)?.unwrap_or(0.0),
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to query daily attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read daily attendance: {}", e))
+}
+
+// Time of day part of a stored timestamp ("2024-05-01 08:02:11" -> "08:02:11")
+pub fn time_part(timestamp: &str) -> &str {
+    timestamp.get(11..19).unwrap_or(timestamp)
+}
+
+fn write_header(
+    sheet: &mut Worksheet,
+    columns: &[(&str, f64)],
+    format: &Format,
+) -> Result<(), XlsxError> {
+    for (col, (title, width)) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, format)?;
+        sheet.set_column_width(col as u16, *width)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+fn write_workbook(
+    connection: &Connection,
+    range: (NaiveDate, NaiveDate),
+    devices: Option<String>,
+    path: &Path,
+) -> Result<u64, String> {
+    let daily = daily_rows(connection, range, &devices)?;
+    let xlsx_error = |e: XlsxError| format!("Failed to build workbook: {}", e);
+
+    let mut workbook = Workbook::new();
+    let header = Format::new()
+        .set_bold()
+        .set_background_color(Color::RGB(0xD9E1F2))
+        .set_border_bottom(FormatBorder::Thin);
+    let hours_format = Format::new().set_num_format("0.00");
+
+    // Per-employee daily first in / last out
+    let sheet = workbook
+        .add_worksheet()
+        .set_name("Daily")
+        .map_err(xlsx_error)?;
+    write_header(
+        sheet,
+        &[
+            ("User ID", 12.0),
+            ("Name", 28.0),
+            ("Date", 12.0),
+            ("First in", 10.0),
+            ("Last out", 10.0),
+            ("Punches", 9.0),
+            ("Hours", 9.0),
+        ],
+        &header,
+    )
+    .map_err(xlsx_error)?;
+    for (index, day) in daily.iter().enumerate() {
+        let row = index as u32 + 1;
+        sheet
+            .write_string(row, 0, &day.user_id)
+            .map_err(xlsx_error)?;
+        sheet.write_string(row, 1, &day.name).map_err(xlsx_error)?;
+        sheet.write_string(row, 2, &day.date).map_err(xlsx_error)?;
+        sheet
+            .write_string(row, 3, time_part(&day.first_in))
+            .map_err(xlsx_error)?;
+        // A single punch has no matching out
+        if day.punches > 1 {
+            sheet
+                .write_string(row, 4, time_part(&day.last_out))
+                .map_err(xlsx_error)?;
+        }
+        sheet
+            .write_number(row, 5, day.punches)
+            .map_err(xlsx_error)?;
+        sheet
+            .write_number_with_format(row, 6, day.hours, &hours_format)
+            .map_err(xlsx_error)?;
+    }
+    if !daily.is_empty() {
+        sheet
+            .autofilter(0, 0, daily.len() as u32, 6)
+            .map_err(xlsx_error)?;
+    }
+
+    // Totals per employee
+    let mut totals: Vec<(&str, &str, u32, u32, f64)> = Vec::new();
+    for day in &daily {
+        match totals.last_mut() {
+            Some(total) if total.0 == day.user_id => {
+                total.2 += 1;
+                total.3 += day.punches;
+                total.4 += day.hours;
+            }
+            _ => totals.push((&day.user_id, &day.name, 1, day.punches, day.hours)),
+        }
+    }
+    let sheet = workbook
+        .add_worksheet()
+        .set_name("Summary")
+        .map_err(xlsx_error)?;
+    write_header(
+        sheet,
+        &[
+            ("User ID", 12.0),
+            ("Name", 28.0),
+            ("Days present", 13.0),
+            ("Punches", 9.0),
+            ("Total hours", 12.0),
+        ],
+        &header,
+    )
+    .map_err(xlsx_error)?;
+    for (index, (user_id, name, days, punches, hours)) in totals.iter().enumerate() {
+        let row = index as u32 + 1;
+        sheet.write_string(row, 0, *user_id).map_err(xlsx_error)?;
+        sheet.write_string(row, 1, *name).map_err(xlsx_error)?;
+        sheet.write_number(row, 2, *days).map_err(xlsx_error)?;
+        sheet.write_number(row, 3, *punches).map_err(xlsx_error)?;
+        sheet
+            .write_number_with_format(row, 4, *hours, &hours_format)
+            .map_err(xlsx_error)?;
+    }
+    let sheet = workbook
+        .add_worksheet()
+        .set_name("Info")
+        .map_err(xlsx_error)?;
+    sheet
+        .write_string(0, 0, format!("Attendance from {} to {}", range.0, range.1))
+        .map_err(xlsx_error)?;
+    sheet
+        .write_string(
+            1,
+            0,
+            format!(
+                "Devices: {}",
+                devices.as_deref().unwrap_or("all").trim_matches(['[', ']'])
+            ),
+        )
+        .map_err(xlsx_error)?;
+
+    workbook.save(path).map_err(xlsx_error)?;
+    Ok(daily.len() as u64)
+}
+
+// The given path, or one picked by the user in a save dialog (None when cancelled); both pass
+// the path policy, a dialog pick being approved first
+pub async fn resolve_save_path(
+    app: &AppHandle,
+    path: Option<String>,
+    filter_name: &str,
+    extension: &str,
+    default_name: String,
+) -> Result<Option<PathBuf>, String> {
+    if let Some(path) = path.filter(|p| !p.trim().is_empty()) {
+        return check_path(&path, Access::Write).map(Some);
+    }
+    let dialog = app
+        .dialog()
+        .file()
+        .add_filter(filter_name, &[extension])
+        .set_file_name(default_name);
+    let picked = tauri::async_runtime::spawn_blocking(move || dialog.blocking_save_file())
+        .await
+        .map_err(|e| format!("Save dialog failed: {}", e))?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| format!("Invalid save location: {}", e))?;
+    path_policy::approve(&path);
+    check_path(&path.to_string_lossy(), Access::Write).map(Some)
+}
+
+// Formatted daily/summary workbook; without a path the user picks one in a save dialog.
+// Returns None when the dialog is cancelled.
+#[tauri::command]
+pub async fn export_attendance_xlsx(
+    app: AppHandle,
+    range: ExportRange,
+    devices: Option<Vec<String>>,
+    path: Option<String>,
+    password: Option<String>,
+) -> Result<Option<ExportResult>, String> {
+    lock::ensure_unlocked()?;
+    let range = parse_range(&range)?;
+    let devices = device_filter(&devices);
+    let password = protect::check_password(password)?;
+    let Some(path) = resolve_save_path(
+        &app,
+        path,
+        "Excel workbook",
+        "xlsx",
+        format!("attendance_{}_{}.xlsx", range.0, range.1),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let written = protect::write_path(&path, &password)?;
+    let target = written.clone();
+    let rows = run_db(move |connection| write_workbook(connection, range, devices, &target)).await;
+    let rows = protect::finish(rows, written, &path, password).await;
+    let audit_target = path.to_string_lossy().to_string();
+    let result = match rows {
+        Ok((rows, path)) => {
+            append_app_log(&format!(
+                "Exported attendance workbook ({} to {}, {} employee-days) to {}",
+                range.0, range.1, rows, path
+            ));
+            Ok(Some(ExportResult {
+                path,
+                rows,
+                duration_ms: started.elapsed().as_millis(),
+            }))
+        }
+        Err(err) => {
+            append_app_log(&format!("Attendance XLSX export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_attendance_xlsx", &audit_target, result)
+}
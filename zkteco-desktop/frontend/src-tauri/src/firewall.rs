@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use tauri::State;
+
+use crate::audit::audited;
+use crate::settings::{current_settings, SharedSettings};
+use crate::{current_backend_port, lock, sidecar, BackendPort};
+
+// Inbound Windows Firewall rules for devices that reach this PC: TCP for the embedded ADMS
+// listener and the backend port (PUSH devices), and UDP for the app and backend programs, since
+// replies to discovery broadcasts come from addresses the PC never sent to. The rules live in
+// the "ZKTeco" group and cover domain and private networks, public ones only when asked for.
+// Adding or removing them runs PowerShell as administrator, so Windows shows a UAC prompt first;
+// reading them needs no elevation. Ports that change later need the rules added again.
+const RULE_GROUP: &str = "ZKTeco";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PlannedRule {
+    name: String,
+    protocol: &'static str,
+    port: Option<u16>,
+    program: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InstalledRule {
+    name: String,
+    enabled: bool,
+    profile: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FirewallStatus {
+    supported: bool,
+    planned: Vec<PlannedRule>,
+    installed: Vec<InstalledRule>,
+    missing: Vec<String>, // planned rules not in the firewall, by name
+}
+
+fn ensure_supported() -> Result<(), String> {
+    if cfg!(windows) {
+        Ok(())
+    } else {
+        Err("Firewall rules are only managed on Windows".to_string())
+    }
+}
+
+fn tcp_rule(purpose: &str, port: u16) -> PlannedRule {
+    PlannedRule {
+        name: format!("ZKTeco {} (TCP {})", purpose, port),
+        protocol: "TCP",
+        port: Some(port),
+        program: None,
+    }
+}
+
+fn udp_rule(purpose: &str, program: PathBuf) -> PlannedRule {
+    PlannedRule {
+        name: format!("ZKTeco {} (UDP)", purpose),
+        protocol: "UDP",
+        port: None,
+        program: Some(program.to_string_lossy().to_string()),
+    }
+}
+
+// Rules the current settings call for
+fn planned_rules(settings: &SharedSettings, backend_port: &BackendPort) -> Vec<PlannedRule> {
+    let config = current_settings(settings);
+    let mut rules = Vec::new();
+    if config.adms_server_enabled {
+        rules.push(tcp_rule("ADMS listener", config.adms_server_port));
+    }
+    // A backend bound to 127.0.0.1 can't take PUSH connections anyway
+    if !config.backend_localhost_only {
+        rules.push(tcp_rule("backend", current_backend_port(backend_port)));
+    }
+    if let Ok(shell) = std::env::current_exe() {
+        rules.push(udp_rule("app", shell));
+    }
+    if let Ok(backend) = sidecar::current_binary() {
+        rules.push(udp_rule("backend", backend));
+    }
+    #[cfg(windows)]
+    if crate::service::installed().is_some() {
+        rules.push(udp_rule(
+            "backend service",
+            crate::service::service_backend(),
+        ));
+    }
+    rules
+}
+
+#[cfg(windows)]
+fn installed_rules() -> Result<Vec<InstalledRule>, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let script = format!(
+        "$rules = @(Get-NetFirewallRule -Group {} -ErrorAction SilentlyContinue | Select-Object \
+         @{{n='name';e={{$_.DisplayName}}}}, @{{n='enabled';e={{[string]$_.Enabled -eq 'True'}}}}, \
+         @{{n='profile';e={{[string]$_.Profile}}}}); ConvertTo-Json -InputObject $rules -Compress",
+        crate::service::ps_quote(RULE_GROUP)
+    );
+    let output = std::process::Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to read firewall rules: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(stdout.trim())
+        .map_err(|e| format!("Failed to parse firewall rules: {}", e))
+}
+
+#[cfg(not(windows))]
+fn installed_rules() -> Result<Vec<InstalledRule>, String> {
+    Ok(Vec::new())
+}
+
+// Replace the group's rules with the planned ones
+#[cfg(windows)]
+fn apply_rules(rules: &[PlannedRule], include_public: bool) -> Result<(), String> {
+    use crate::service::ps_quote;
+
+    let profile = if include_public {
+        "Domain,Private,Public"
+    } else {
+        "Domain,Private"
+    };
+    let mut script = format!(
+        "Get-NetFirewallRule -Group {} -ErrorAction SilentlyContinue | Remove-NetFirewallRule\n",
+        ps_quote(RULE_GROUP)
+    );
+    for rule in rules {
+        let target = match (&rule.port, &rule.program) {
+            (Some(port), _) => format!("-LocalPort {}", port),
+            (None, Some(program)) => format!("-Program {}", ps_quote(program)),
+            (None, None) => continue,
+        };
+        script.push_str(&format!(
+            "New-NetFirewallRule -DisplayName {} -Group {} -Direction Inbound -Action Allow \
+             -Protocol {} {} -Profile {} | Out-Null\n",
+            ps_quote(&rule.name),
+            ps_quote(RULE_GROUP),
+            rule.protocol,
+            target,
+            profile
+        ));
+    }
+    crate::service::run_elevated(&script, "Firewall setup")
+}
+
+#[cfg(not(windows))]
+fn apply_rules(_rules: &[PlannedRule], _include_public: bool) -> Result<(), String> {
+    ensure_supported()
+}
+
+#[cfg(windows)]
+fn remove_rules() -> Result<(), String> {
+    let script = format!(
+        "Get-NetFirewallRule -Group {} -ErrorAction SilentlyContinue | Remove-NetFirewallRule",
+        crate::service::ps_quote(RULE_GROUP)
+    );
+    crate::service::run_elevated(&script, "Firewall cleanup")
+}
+
+#[cfg(not(windows))]
+fn remove_rules() -> Result<(), String> {
+    ensure_supported()
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Firewall task failed: {}", e))?
+}
+
+async fn status(planned: Vec<PlannedRule>) -> Result<FirewallStatus, String> {
+    let installed = blocking(installed_rules).await?;
+    let missing = planned
+        .iter()
+        .filter(|rule| {
+            !installed
+                .iter()
+                .any(|existing| existing.name == rule.name && existing.enabled)
+        })
+        .map(|rule| rule.name.clone())
+        .collect();
+    Ok(FirewallStatus {
+        supported: cfg!(windows),
+        planned,
+        installed,
+        missing,
+    })
+}
+
+#[tauri::command]
+pub async fn get_firewall_rules(
+    settings: State<'_, SharedSettings>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<FirewallStatus, String> {
+    status(planned_rules(&settings, &backend_port)).await
+}
+
+// Prompts for administrator approval (UAC); declining it leaves the rules as they were
+#[tauri::command]
+pub async fn add_firewall_rules(
+    settings: State<'_, SharedSettings>,
+    backend_port: State<'_, BackendPort>,
+    include_public: Option<bool>,
+) -> Result<FirewallStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_supported()?;
+    let planned = planned_rules(&settings, &backend_port);
+    let result = {
+        let rules = planned.clone();
+        let include_public = include_public.unwrap_or(false);
+        blocking(move || apply_rules(&rules, include_public)).await
+    };
+    audited("add_firewall_rules", RULE_GROUP, result)?;
+    status(planned).await
+}
+
+#[tauri::command]
+pub async fn remove_firewall_rules(
+    settings: State<'_, SharedSettings>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<FirewallStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_supported()?;
+    audited(
+        "remove_firewall_rules",
+        RULE_GROUP,
+        blocking(remove_rules).await,
+    )?;
+    status(planned_rules(&settings, &backend_port)).await
+}
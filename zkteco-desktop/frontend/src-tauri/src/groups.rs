@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+
+use tauri::State;
+
+use crate::append_app_log;
+use crate::audit::audited;
+use crate::bulk_sync::{run_sync, SyncSummary, DEFAULT_SYNC_CONCURRENCY};
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::devices::{run_native, set_device_clock, NATIVE_TIMEOUT};
+use crate::lock;
+use crate::registry::{save_registry, DeviceEntry, DeviceRegistry};
+
+// Device groups live in the local registry; bulk commands fan out to every device of a group
+// concurrently and report one result per device instead of failing on the first error
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceGroup {
+    name: String,
+    device_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceOperationResult {
+    device_id: String,
+    success: bool,
+    detail: Option<serde_json::Value>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GroupOperationResult {
+    group: String,
+    operation: String,
+    succeeded: usize,
+    failed: usize,
+    results: Vec<DeviceOperationResult>,
+}
+
+fn group_members(registry: &DeviceRegistry, group: &str) -> Result<Vec<DeviceEntry>, String> {
+    let devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    let members: Vec<DeviceEntry> = devices
+        .iter()
+        .filter(|d| d.is_active && d.group.as_deref() == Some(group))
+        .cloned()
+        .map(DeviceEntry::with_stored_comm_key)
+        .collect();
+    if members.is_empty() {
+        return Err(format!("Group {} has no active devices", group));
+    }
+    Ok(members)
+}
+
+// Run `operation` on every device concurrently and collect the outcomes
+async fn fan_out<F, Fut, T>(
+    group: &str,
+    operation: &str,
+    devices: Vec<DeviceEntry>,
+    run: F,
+) -> GroupOperationResult
+where
+    F: Fn(DeviceEntry) -> Fut,
+    Fut: Future<Output = Result<T, String>> + Send + 'static,
+    T: serde::Serialize + Send + 'static,
+{
+    let tasks: Vec<_> = devices
+        .into_iter()
+        .map(|device| {
+            let device_id = device.id.clone();
+            (device_id, tauri::async_runtime::spawn(run(device)))
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (device_id, task) in tasks {
+        let outcome = task
+            .await
+            .map_err(|e| format!("Device task failed: {}", e))
+            .and_then(|result| result);
+        results.push(match outcome {
+            Ok(detail) => DeviceOperationResult {
+                device_id,
+                success: true,
+                detail: serde_json::to_value(detail).ok(),
+                error: None,
+            },
+            Err(err) => DeviceOperationResult {
+                device_id,
+                success: false,
+                detail: None,
+                error: Some(err),
+            },
+        });
+    }
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    append_app_log(&format!(
+        "Group {} {}: {} succeeded, {} failed",
+        group,
+        operation,
+        succeeded,
+        results.len() - succeeded
+    ));
+    GroupOperationResult {
+        group: group.to_string(),
+        operation: operation.to_string(),
+        succeeded,
+        failed: results.len() - succeeded,
+        results,
+    }
+}
+
+// Assign a device to a group (or with None, remove it from its group)
+#[tauri::command]
+pub fn set_device_group(
+    device_id: String,
+    group: Option<String>,
+    registry: State<DeviceRegistry>,
+) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let mut devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    let device = devices
+        .iter_mut()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| format!("Unknown device: {}", device_id))?;
+    device.group = group
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    save_registry(&devices)
+}
+
+#[tauri::command]
+pub fn list_device_groups(registry: State<DeviceRegistry>) -> Result<Vec<DeviceGroup>, String> {
+    let devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to read device registry: {}", e))?;
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for device in devices.iter() {
+        if let Some(group) = &device.group {
+            groups
+                .entry(group.clone())
+                .or_default()
+                .push(device.id.clone());
+        }
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(name, device_ids)| DeviceGroup { name, device_ids })
+        .collect())
+}
+
+// Pull and stage attendance from every device of the group, with progress events
+#[tauri::command]
+pub async fn sync_group(
+    app: tauri::AppHandle,
+    group: String,
+    max_concurrency: Option<usize>,
+    registry: State<'_, DeviceRegistry>,
+) -> Result<SyncSummary, String> {
+    lock::ensure_unlocked()?;
+    let devices = group_members(&registry, &group)?;
+    Ok(run_sync(
+        app,
+        devices,
+        max_concurrency.unwrap_or(DEFAULT_SYNC_CONCURRENCY),
+    )
+    .await)
+}
+
+#[tauri::command]
+pub async fn set_time_group(
+    group: String,
+    registry: State<'_, DeviceRegistry>,
+) -> Result<GroupOperationResult, String> {
+    lock::ensure_unlocked()?;
+    let devices = group_members(&registry, &group)?;
+    Ok(fan_out(&group, "set_time", devices, |device| {
+        run_native(move || set_device_clock(device))
+    })
+    .await)
+}
+
+// Requires a confirmation token for action "reboot_group" and the group name as target
+#[tauri::command]
+pub async fn reboot_group(
+    group: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+    registry: State<'_, DeviceRegistry>,
+) -> Result<GroupOperationResult, String> {
+    consume_token(&tokens, &confirm_token, "reboot_group", &group)?;
+    let devices = group_members(&registry, &group)?;
+    Ok(fan_out(&group, "reboot", devices, |device| async move {
+        let device_id = device.id.clone();
+        let result = run_native(move || device.open_session(NATIVE_TIMEOUT)?.restart()).await;
+        audited("reboot_device", &device_id, result)
+    })
+    .await)
+}
@@ -0,0 +1,928 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::params;
+use serde_json::{Map, Value};
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::secrets::{hr_adapter_secret, random_hex, set_hr_adapter_secret};
+use crate::timezones::{normalize_stored, registered_zones};
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Pushes attendance to external HR/payroll APIs through adapters described in hr_adapters.json,
+// so a new integration is configuration rather than backend code. An adapter says where to send
+// (endpoint, method, extra headers), how to authenticate (none, bearer, basic, an API key header
+// or OAuth2 client credentials; the secret part is kept in the OS keychain per adapter), how to
+// shape each record (field_mapping from attendance fields to dotted JSON paths, with value maps
+// and timestamp formats), how many records go in one request (page_size, as an array or one
+// request per record) and how fast (requests_per_minute, Retry-After on 429). Every adapter
+// keeps its own cursor over attendance ids in hr_adapter_state.json, advanced only once a page
+// is accepted. "rest" is the only kind so far; another kind adds an AdapterKind variant and an
+// arm in send_page.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const RETRY_BASE_SECS: u64 = 30;
+const RETRY_MAX_SECS: u64 = 60 * 60;
+// Longest Retry-After honoured within a run; anything longer becomes the next attempt time
+const MAX_INLINE_WAIT: Duration = Duration::from_secs(120);
+const MAX_PAGE_SIZE: usize = 1000;
+// Records pushed per adapter per run, so one busy adapter can't hold the scheduler for long
+const MAX_RECORDS_PER_RUN: usize = 10_000;
+const PREVIEW_LIMIT: usize = 5;
+const EXPIRY_MARGIN_SECS: i64 = 60;
+const SOURCES: [&str; 13] = [
+    "id",
+    "user_id",
+    "name",
+    "device_id",
+    "serial_number",
+    "timestamp",
+    "timestamp_utc",
+    "timezone",
+    "utc_offset",
+    "date",
+    "time",
+    "method",
+    "action",
+];
+
+static ADAPTERS_LOCK: Mutex<()> = Mutex::new(());
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+// OAuth2 access tokens per adapter id
+static TOKENS: Mutex<Option<HashMap<String, CachedToken>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdapterKind {
+    #[default]
+    Rest,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthKind {
+    #[default]
+    None,
+    Bearer,
+    Basic,
+    // The secret is sent as the value of header_name, e.g. X-Api-Key
+    Header,
+    Oauth2ClientCredentials,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AdapterAuth {
+    kind: AuthKind,
+    username: Option<String>,
+    header_name: Option<String>,
+    token_url: Option<String>,
+    client_id: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FieldMapping {
+    // Dotted path in the outgoing record, e.g. "employee.code"
+    target: String,
+    // One of SOURCES; without it `value` is sent as a constant
+    source: Option<String>,
+    value: Option<Value>,
+    // chrono format for timestamp sources, e.g. "%s" for epoch seconds
+    format: Option<String>,
+    // Replaces matching source values, e.g. {"0": "check_in", "1": "check_out"} for action
+    values: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct HrAdapter {
+    id: String,
+    name: String,
+    kind: AdapterKind,
+    enabled: bool,
+    endpoint: String,
+    // POST, PUT or PATCH
+    method: String,
+    headers: HashMap<String, String>,
+    auth: AdapterAuth,
+    field_mapping: Vec<FieldMapping>,
+    page_size: usize,
+    // "array" sends a page per request, "single" one request per record
+    body_mode: String,
+    // Wraps an array page as {"<key>": [...]}
+    wrapper_key: Option<String>,
+    // 0 = no limit
+    requests_per_minute: u32,
+    // 0 = only when run by hand
+    interval_minutes: u64,
+    // First run starts at this date (YYYY-MM-DD); without it only new attendance is pushed
+    start_date: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl Default for HrAdapter {
+    fn default() -> Self {
+        HrAdapter {
+            id: String::new(),
+            name: String::new(),
+            kind: AdapterKind::Rest,
+            enabled: true,
+            endpoint: String::new(),
+            method: "POST".to_string(),
+            headers: HashMap::new(),
+            auth: AdapterAuth::default(),
+            field_mapping: Vec::new(),
+            page_size: 100,
+            body_mode: "array".to_string(),
+            wrapper_key: None,
+            requests_per_minute: 0,
+            interval_minutes: 15,
+            start_date: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AdapterState {
+    // Highest attendance id accepted by the adapter's API
+    cursor: Option<i64>,
+    attempts: u32,
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_run_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    delivered: u64,
+    rejected: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AdapterStatus {
+    id: String,
+    name: String,
+    enabled: bool,
+    has_secret: bool,
+    #[serde(flatten)]
+    state: AdapterState,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct AdapterRun {
+    adapter_id: String,
+    sent: usize,
+    rejected: usize,
+    requests: usize,
+    cursor: Option<i64>,
+    error: Option<String>,
+}
+
+struct CachedToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+struct Record {
+    id: i64,
+    fields: HashMap<&'static str, Value>,
+}
+
+enum Delivery {
+    Accepted,
+    // 4xx other than 408/429: retrying the same page won't help
+    Rejected(String),
+    Failed(String, Option<Duration>),
+}
+
+// Spaces requests out to the adapter's requests_per_minute
+struct Pacer {
+    gap: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Pacer {
+    fn new(requests_per_minute: u32) -> Self {
+        Pacer {
+            gap: (requests_per_minute > 0).then(|| Duration::from_secs(60) / requests_per_minute),
+            last: None,
+        }
+    }
+
+    async fn wait(&mut self) {
+        if let (Some(gap), Some(last)) = (self.gap, self.last) {
+            let elapsed = last.elapsed();
+            if elapsed < gap {
+                tokio::time::sleep(gap - elapsed).await;
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+fn adapters_path() -> PathBuf {
+    resolve_app_data_dir().join("hr_adapters.json")
+}
+
+fn state_path() -> PathBuf {
+    resolve_app_data_dir().join("hr_adapter_state.json")
+}
+
+fn load_adapters() -> Vec<HrAdapter> {
+    fs::read_to_string(adapters_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_adapters(adapters: &[HrAdapter]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(adapters)
+        .map_err(|e| format!("Failed to serialize HR adapters: {}", e))?;
+    fs::write(adapters_path(), content).map_err(|e| format!("Failed to save HR adapters: {}", e))
+}
+
+fn load_states() -> HashMap<String, AdapterState> {
+    fs::read_to_string(state_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn update_state(id: &str, f: impl FnOnce(&mut AdapterState)) -> AdapterState {
+    let _guard = STATE_LOCK.lock();
+    let mut states = load_states();
+    let state = states.entry(id.to_string()).or_default();
+    f(state);
+    let updated = state.clone();
+    let written = serde_json::to_string_pretty(&states)
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(state_path(), content).map_err(|e| e.to_string()));
+    if let Err(err) = written {
+        eprintln!("Failed to save HR adapter state: {}", err);
+    }
+    updated
+}
+
+fn backoff(attempts: u32) -> chrono::Duration {
+    let secs = RETRY_BASE_SECS
+        .saturating_mul(1 << attempts.min(16))
+        .min(RETRY_MAX_SECS);
+    chrono::Duration::seconds(secs as i64)
+}
+
+fn validate(adapter: &HrAdapter) -> Result<(), String> {
+    if adapter.name.trim().is_empty() {
+        return Err("The adapter needs a name".to_string());
+    }
+    let endpoint = reqwest::Url::parse(adapter.endpoint.trim())
+        .map_err(|_| format!("'{}' is not a valid URL", adapter.endpoint))?;
+    if !["http", "https"].contains(&endpoint.scheme()) {
+        return Err("The endpoint must be an http:// or https:// URL".to_string());
+    }
+    if !["POST", "PUT", "PATCH"].contains(&adapter.method.as_str()) {
+        return Err(format!(
+            "Unsupported method '{}'; use POST, PUT or PATCH",
+            adapter.method
+        ));
+    }
+    if !["array", "single"].contains(&adapter.body_mode.as_str()) {
+        return Err(format!(
+            "Unknown body mode '{}'; use array or single",
+            adapter.body_mode
+        ));
+    }
+    if adapter.page_size == 0 || adapter.page_size > MAX_PAGE_SIZE {
+        return Err(format!("Page size must be 1 to {}", MAX_PAGE_SIZE));
+    }
+    if adapter.field_mapping.is_empty() {
+        return Err("Map at least one field".to_string());
+    }
+    for mapping in &adapter.field_mapping {
+        if mapping.target.trim().is_empty() || mapping.target.split('.').any(str::is_empty) {
+            return Err(format!("Invalid target path '{}'", mapping.target));
+        }
+        match &mapping.source {
+            Some(source) if !SOURCES.contains(&source.as_str()) => {
+                return Err(format!(
+                    "Unknown source '{}' for {}; expected one of {}",
+                    source,
+                    mapping.target,
+                    SOURCES.join(", ")
+                ))
+            }
+            None if mapping.value.is_none() => {
+                return Err(format!("{} needs a source or a value", mapping.target))
+            }
+            _ => {}
+        }
+    }
+    if let Some(date) = &adapter.start_date {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid start date '{}', expected YYYY-MM-DD", date))?;
+    }
+    let auth = &adapter.auth;
+    let missing = match auth.kind {
+        AuthKind::Basic if auth.username.is_none() => Some("a username"),
+        AuthKind::Header if auth.header_name.is_none() => Some("a header name"),
+        AuthKind::Oauth2ClientCredentials
+            if auth.token_url.is_none() || auth.client_id.is_none() =>
+        {
+            Some("a token URL and client id")
+        }
+        _ => None,
+    };
+    match missing {
+        Some(missing) => Err(format!("This authentication needs {}", missing)),
+        None => Ok(()),
+    }
+}
+
+// Attendance after `cursor`, with every SOURCES field resolved
+async fn read_records(cursor: i64, limit: usize) -> Result<Vec<Record>, String> {
+    let zones = registered_zones();
+    run_db(move |connection| {
+        let mut statement = connection
+            .prepare(
+                "SELECT a.id, a.user_id,
+                    COALESCE((SELECT u.name FROM users u WHERE u.user_id = a.user_id LIMIT 1), ''),
+                    a.device_id, a.serial_number, a.timestamp, a.method, a.action
+                 FROM attendance_logs a WHERE a.id > ?1 ORDER BY a.id LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        let rows = statement
+            .query_map(params![cursor, limit as i64], |row| {
+                let id: i64 = row.get(0)?;
+                let device_id: Option<String> = row.get(3)?;
+                let timestamp: String = row.get(5)?;
+                let zone = device_id.as_ref().and_then(|id| zones.get(id)).copied();
+                let normalized = normalize_stored(&timestamp, zone);
+                let (date, time) = timestamp
+                    .split_once(' ')
+                    .map(|(date, time)| (date.to_string(), time.to_string()))
+                    .unwrap_or_else(|| (timestamp.clone(), String::new()));
+                let mut fields: HashMap<&'static str, Value> = HashMap::new();
+                fields.insert("id", id.into());
+                fields.insert("user_id", row.get::<_, String>(1)?.into());
+                fields.insert("name", row.get::<_, String>(2)?.into());
+                fields.insert("device_id", device_id.into());
+                fields.insert("serial_number", row.get::<_, Option<String>>(4)?.into());
+                fields.insert(
+                    "timestamp_utc",
+                    normalized.as_ref().map(|t| t.timestamp_utc.clone()).into(),
+                );
+                fields.insert(
+                    "timezone",
+                    normalized.as_ref().map(|t| t.timezone.clone()).into(),
+                );
+                fields.insert(
+                    "utc_offset",
+                    normalized.as_ref().map(|t| t.utc_offset.clone()).into(),
+                );
+                fields.insert("timestamp", timestamp.into());
+                fields.insert("date", date.into());
+                fields.insert("time", time.into());
+                fields.insert("method", row.get::<_, i64>(6)?.into());
+                fields.insert("action", row.get::<_, i64>(7)?.into());
+                Ok(Record { id, fields })
+            })
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read attendance row: {}", e))
+    })
+    .await
+}
+
+fn formatted(source: &str, value: Value, format: &str) -> Value {
+    let Some(text) = value.as_str() else {
+        return value;
+    };
+    let text = match source {
+        "timestamp" => NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+            .map(|at| at.format(format).to_string()),
+        "timestamp_utc" => DateTime::parse_from_rfc3339(text)
+            .map(|at| at.with_timezone(&Utc).format(format).to_string()),
+        _ => return value,
+    };
+    text.map(Value::String).unwrap_or(value)
+}
+
+fn set_path(object: &mut Map<String, Value>, path: &str, value: Value) {
+    let mut parts = path.split('.').peekable();
+    let mut current = object;
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            current.insert(part.to_string(), value);
+            return;
+        }
+        let next = current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !next.is_object() {
+            *next = Value::Object(Map::new());
+        }
+        let Value::Object(next) = next else {
+            return;
+        };
+        current = next;
+    }
+}
+
+fn map_record(adapter: &HrAdapter, record: &Record) -> Value {
+    let mut object = Map::new();
+    for mapping in &adapter.field_mapping {
+        let mut value = match &mapping.source {
+            Some(source) => {
+                let value = record
+                    .fields
+                    .get(source.as_str())
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                match &mapping.format {
+                    Some(format) => formatted(source, value, format),
+                    None => value,
+                }
+            }
+            None => mapping.value.clone().unwrap_or(Value::Null),
+        };
+        let key = match &value {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        if let Some(mapped) = mapping.values.get(&key) {
+            value = mapped.clone();
+        }
+        set_path(&mut object, mapping.target.trim(), value);
+    }
+    Value::Object(object)
+}
+
+fn page_body(adapter: &HrAdapter, records: Vec<Value>) -> Value {
+    let array = Value::Array(records);
+    match adapter.wrapper_key.as_deref().map(str::trim) {
+        Some(key) if !key.is_empty() => {
+            let mut object = Map::new();
+            object.insert(key.to_string(), array);
+            Value::Object(object)
+        }
+        _ => array,
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+async fn oauth_token(adapter: &HrAdapter, client_secret: &str) -> Result<String, String> {
+    if let Some(token) = TOKENS.lock().ok().and_then(|tokens| {
+        tokens
+            .as_ref()?
+            .get(&adapter.id)
+            .filter(|cached| cached.expires_at > Utc::now())
+            .map(|cached| cached.token.clone())
+    }) {
+        return Ok(token);
+    }
+
+    let auth = &adapter.auth;
+    let token_url = auth.token_url.as_deref().unwrap_or_default();
+    let mut form = vec![
+        ("grant_type", "client_credentials"),
+        ("client_id", auth.client_id.as_deref().unwrap_or_default()),
+        ("client_secret", client_secret),
+    ];
+    if let Some(scope) = auth.scope.as_deref() {
+        form.push(("scope", scope));
+    }
+    let response = http_client()?
+        .post(token_url)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| format!("Token request to {} failed: {}", token_url, e))?;
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid token response from {}: {}", token_url, e))?;
+    let token = body
+        .get("access_token")
+        .and_then(|token| token.as_str())
+        .filter(|_| status.is_success())
+        .ok_or_else(|| format!("Token request was refused ({}): {}", status, body))?
+        .to_string();
+    let expires_in = body
+        .get("expires_in")
+        .and_then(|seconds| seconds.as_i64())
+        .unwrap_or(3600);
+    if let Ok(mut tokens) = TOKENS.lock() {
+        tokens.get_or_insert_with(HashMap::new).insert(
+            adapter.id.clone(),
+            CachedToken {
+                token: token.clone(),
+                expires_at: Utc::now()
+                    + chrono::Duration::seconds((expires_in - EXPIRY_MARGIN_SECS).max(0)),
+            },
+        );
+    }
+    Ok(token)
+}
+
+fn forget_token(adapter_id: &str) {
+    if let Some(tokens) = TOKENS.lock().ok().as_mut().and_then(|t| t.as_mut()) {
+        tokens.remove(adapter_id);
+    }
+}
+
+async fn authorize(
+    adapter: &HrAdapter,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::RequestBuilder, String> {
+    let auth = &adapter.auth;
+    if auth.kind == AuthKind::None {
+        return Ok(request);
+    }
+    let secret = hr_adapter_secret(&adapter.id)
+        .ok_or_else(|| format!("No credential is stored for {}", adapter.name))?;
+    Ok(match auth.kind {
+        AuthKind::None => request,
+        AuthKind::Bearer => request.bearer_auth(secret),
+        AuthKind::Basic => {
+            request.basic_auth(auth.username.clone().unwrap_or_default(), Some(secret))
+        }
+        AuthKind::Header => request.header(auth.header_name.clone().unwrap_or_default(), secret),
+        AuthKind::Oauth2ClientCredentials => {
+            request.bearer_auth(oauth_token(adapter, &secret).await?)
+        }
+    })
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+async fn send_rest(adapter: &HrAdapter, body: &Value) -> Delivery {
+    let client = match http_client() {
+        Ok(client) => client,
+        Err(err) => return Delivery::Failed(err, None),
+    };
+    let method =
+        reqwest::Method::from_bytes(adapter.method.as_bytes()).unwrap_or(reqwest::Method::POST);
+    let mut request = client.request(method, adapter.endpoint.trim()).json(body);
+    for (name, value) in &adapter.headers {
+        request = request.header(name, value);
+    }
+    let request = match authorize(adapter, request).await {
+        Ok(request) => request,
+        Err(err) => return Delivery::Failed(err, None),
+    };
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Delivery::Accepted,
+        Ok(response) => {
+            let status = response.status();
+            let wait = retry_after(&response);
+            let text = response.text().await.unwrap_or_default();
+            let message = format!("{} returned {}: {}", adapter.name, status, text.trim());
+            if status.as_u16() == 401 {
+                // The cached OAuth token may have been revoked; fetch a new one next time
+                forget_token(&adapter.id);
+                Delivery::Failed(message, None)
+            } else if status.is_client_error() && status.as_u16() != 408 && status.as_u16() != 429 {
+                Delivery::Rejected(message)
+            } else {
+                Delivery::Failed(message, wait)
+            }
+        }
+        Err(err) => Delivery::Failed(format!("Failed to reach {}: {}", adapter.name, err), None),
+    }
+}
+
+async fn send_page(adapter: &HrAdapter, body: &Value) -> Delivery {
+    match adapter.kind {
+        AdapterKind::Rest => send_rest(adapter, body).await,
+    }
+}
+
+// One request, waiting out a short Retry-After once before giving up
+async fn deliver(adapter: &HrAdapter, pacer: &mut Pacer, body: &Value) -> Delivery {
+    pacer.wait().await;
+    match send_page(adapter, body).await {
+        Delivery::Failed(_, Some(wait)) if wait <= MAX_INLINE_WAIT => {
+            tokio::time::sleep(wait).await;
+            pacer.wait().await;
+            send_page(adapter, body).await
+        }
+        delivery => delivery,
+    }
+}
+
+async fn starting_cursor(adapter: &HrAdapter) -> Result<i64, String> {
+    let start_date = adapter.start_date.clone();
+    run_db(move |connection| {
+        let cursor = match start_date {
+            Some(date) => connection.query_row(
+                "SELECT COALESCE(MIN(id), (SELECT MAX(id) FROM attendance_logs), 0) - 1
+                 FROM attendance_logs WHERE timestamp >= ?1",
+                params![date],
+                |row| row.get(0),
+            ),
+            None => connection.query_row(
+                "SELECT COALESCE(MAX(id), 0) FROM attendance_logs",
+                [],
+                |row| row.get(0),
+            ),
+        };
+        cursor.map_err(|e| format!("Failed to read attendance: {}", e))
+    })
+    .await
+}
+
+async fn run_adapter(adapter: &HrAdapter) -> AdapterRun {
+    let mut run = AdapterRun {
+        adapter_id: adapter.id.clone(),
+        ..Default::default()
+    };
+    let state = update_state(&adapter.id, |state| state.last_run_at = Some(Utc::now()));
+    let mut cursor = match state.cursor {
+        Some(cursor) => cursor,
+        None => match starting_cursor(adapter).await {
+            Ok(cursor) => {
+                update_state(&adapter.id, |state| state.cursor = Some(cursor));
+                cursor
+            }
+            Err(err) => {
+                run.error = Some(err);
+                return run;
+            }
+        },
+    };
+
+    let mut pacer = Pacer::new(adapter.requests_per_minute);
+    let mut failure: Option<(String, Option<Duration>)> = None;
+    while run.sent + run.rejected < MAX_RECORDS_PER_RUN && failure.is_none() {
+        let records = match read_records(cursor, adapter.page_size).await {
+            Ok(records) => records,
+            Err(err) => {
+                failure = Some((err, None));
+                break;
+            }
+        };
+        let Some(last) = records.last().map(|record| record.id) else {
+            break;
+        };
+        let mapped: Vec<(i64, Value)> = records
+            .iter()
+            .map(|record| (record.id, map_record(adapter, record)))
+            .collect();
+        let bodies: Vec<(i64, usize, Value)> = if adapter.body_mode == "single" {
+            mapped.into_iter().map(|(id, body)| (id, 1, body)).collect()
+        } else {
+            let count = mapped.len();
+            let body = page_body(adapter, mapped.into_iter().map(|(_, body)| body).collect());
+            vec![(last, count, body)]
+        };
+
+        for (through, count, body) in bodies {
+            run.requests += 1;
+            match deliver(adapter, &mut pacer, &body).await {
+                Delivery::Accepted => run.sent += count,
+                Delivery::Rejected(err) => {
+                    // Skipped so one bad record doesn't block everything behind it
+                    append_app_log(&format!(
+                        "HR adapter {} rejected {} attendance records: {}",
+                        adapter.name, count, err
+                    ));
+                    run.rejected += count;
+                    run.error = Some(err);
+                }
+                Delivery::Failed(err, wait) => {
+                    failure = Some((err, wait));
+                    break;
+                }
+            }
+            cursor = through;
+            update_state(&adapter.id, |state| state.cursor = Some(through));
+        }
+        if records.len() < adapter.page_size {
+            break;
+        }
+    }
+
+    let (sent, rejected) = (run.sent as u64, run.rejected as u64);
+    let state = update_state(&adapter.id, |state| {
+        state.delivered += sent;
+        state.rejected += rejected;
+        if sent > 0 {
+            state.last_success_at = Some(Utc::now());
+        }
+        match &failure {
+            Some((err, wait)) => {
+                let delay = wait
+                    .and_then(|wait| chrono::Duration::from_std(wait).ok())
+                    .unwrap_or_else(|| backoff(state.attempts));
+                state.next_attempt_at = Some(Utc::now() + delay);
+                state.attempts += 1;
+                state.last_error = Some(err.clone());
+            }
+            None => {
+                state.attempts = 0;
+                state.next_attempt_at = None;
+                state.last_error = run.error.clone();
+            }
+        }
+    });
+    if let Some((err, _)) = failure {
+        if state.attempts == 1 {
+            append_app_log(&format!(
+                "HR adapter {} failed, retrying: {}",
+                adapter.name, err
+            ));
+        }
+        run.error = Some(err);
+    }
+    run.cursor = state.cursor;
+    run
+}
+
+fn due(adapter: &HrAdapter, state: Option<&AdapterState>, now: DateTime<Utc>) -> bool {
+    if !adapter.enabled || adapter.interval_minutes == 0 {
+        return false;
+    }
+    let Some(state) = state else {
+        return true;
+    };
+    if state.next_attempt_at.is_some_and(|at| at > now) {
+        return false;
+    }
+    state
+        .last_run_at
+        .is_none_or(|at| now - at >= chrono::Duration::minutes(adapter.interval_minutes as i64))
+        || state.attempts > 0
+}
+
+pub fn spawn_hr_sync() {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+            let states = load_states();
+            let now = Utc::now();
+            for adapter in load_adapters() {
+                if !due(&adapter, states.get(&adapter.id), now) {
+                    continue;
+                }
+                let run = run_adapter(&adapter).await;
+                if let Some(err) = &run.error {
+                    eprintln!("HR adapter {} failed: {}", adapter.name, err);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_hr_adapters() -> Vec<HrAdapter> {
+    load_adapters()
+}
+
+// Create (empty id) or replace an adapter. `secret` is the bearer token, basic password, API key
+// or OAuth2 client secret; None keeps the stored one and "" removes it.
+#[tauri::command]
+pub fn save_hr_adapter(adapter: HrAdapter, secret: Option<String>) -> Result<HrAdapter, String> {
+    lock::ensure_unlocked()?;
+    let name = adapter.name.trim().to_string();
+    let result = (|| {
+        let mut adapter = adapter;
+        adapter.name = name.clone();
+        adapter.endpoint = adapter.endpoint.trim().to_string();
+        adapter.method = adapter.method.trim().to_uppercase();
+        validate(&adapter)?;
+
+        let _guard = ADAPTERS_LOCK.lock();
+        let mut adapters = load_adapters();
+        match adapters.iter_mut().find(|a| a.id == adapter.id) {
+            Some(existing) if !adapter.id.is_empty() => {
+                adapter.created_at = existing.created_at;
+                *existing = adapter.clone();
+            }
+            _ if !adapter.id.is_empty() => {
+                return Err(format!("HR adapter {} not found", adapter.id));
+            }
+            _ => {
+                adapter.id = random_hex(8);
+                adapter.created_at = Utc::now();
+                adapters.push(adapter.clone());
+            }
+        }
+        if let Some(secret) = &secret {
+            set_hr_adapter_secret(&adapter.id, secret.trim())?;
+        }
+        save_adapters(&adapters)?;
+        forget_token(&adapter.id);
+        Ok(adapter)
+    })();
+    audited("save_hr_adapter", &name, result)
+}
+
+#[tauri::command]
+pub fn remove_hr_adapter(id: String) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        let _guard = ADAPTERS_LOCK.lock();
+        let mut adapters = load_adapters();
+        let before = adapters.len();
+        adapters.retain(|adapter| adapter.id != id);
+        if adapters.len() == before {
+            return Err(format!("HR adapter {} not found", id));
+        }
+        save_adapters(&adapters)?;
+        {
+            let _guard = STATE_LOCK.lock();
+            let mut states = load_states();
+            if states.remove(&id).is_some() {
+                let _ = serde_json::to_string_pretty(&states)
+                    .map(|content| fs::write(state_path(), content));
+            }
+        }
+        forget_token(&id);
+        set_hr_adapter_secret(&id, "")
+    })();
+    audited("remove_hr_adapter", &id, result)
+}
+
+// The request bodies the newest few records would produce, without sending anything
+#[tauri::command]
+pub async fn preview_hr_adapter(adapter: HrAdapter) -> Result<Vec<Value>, String> {
+    lock::ensure_unlocked()?;
+    validate(&adapter)?;
+    let newest: i64 = run_db(|connection| {
+        connection
+            .query_row(
+                "SELECT COALESCE(MAX(id), 0) FROM attendance_logs",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to read attendance: {}", e))
+    })
+    .await?;
+    let records = read_records(newest - PREVIEW_LIMIT as i64, PREVIEW_LIMIT).await?;
+    let mapped: Vec<Value> = records
+        .iter()
+        .map(|record| map_record(&adapter, record))
+        .collect();
+    Ok(if adapter.body_mode == "single" {
+        mapped
+    } else {
+        vec![page_body(&adapter, mapped)]
+    })
+}
+
+// Push now, ignoring the interval and any backoff
+#[tauri::command]
+pub async fn run_hr_adapter(id: String) -> Result<AdapterRun, String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        let adapter = load_adapters()
+            .into_iter()
+            .find(|adapter| adapter.id == id)
+            .ok_or_else(|| format!("HR adapter {} not found", id))?;
+        let run = run_adapter(&adapter).await;
+        append_app_log(&format!(
+            "HR adapter {} pushed {} attendance records ({} rejected)",
+            adapter.name, run.sent, run.rejected
+        ));
+        Ok(run)
+    }
+    .await;
+    audited("run_hr_adapter", &id, result)
+}
+
+#[tauri::command]
+pub fn get_hr_adapter_status() -> Vec<AdapterStatus> {
+    let states = load_states();
+    load_adapters()
+        .into_iter()
+        .map(|adapter| AdapterStatus {
+            has_secret: hr_adapter_secret(&adapter.id).is_some(),
+            state: states.get(&adapter.id).cloned().unwrap_or_default(),
+            id: adapter.id,
+            name: adapter.name,
+            enabled: adapter.enabled,
+        })
+        .collect()
+}
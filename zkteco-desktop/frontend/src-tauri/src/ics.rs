@@ -0,0 +1,237 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{params, Connection};
+use tauri::AppHandle;
+
+use crate::append_app_log;
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::{
+    parse_range, resolve_save_path, time_part, ExportRange, ExportResult, RANGE_FILTER,
+};
+use crate::lock;
+use crate::protect;
+use crate::timezones::{normalize_stored, registered_zones};
+
+// iCalendar (RFC 5545) file of one employee's worked intervals, first punch to last punch per
+// day, for overlaying on a personal calendar. Times are written in UTC using the zone of the
+// device punched at, so calendars in any zone show them at the right moment. Days with a
+// single punch have no interval and are left out.
+const PRODUCT_ID: &str = "-//ZKTeco Desktop//Attendance//EN";
+const MAX_LINE_OCTETS: usize = 75;
+
+struct WorkedDay {
+    date: String,
+    first_in: String,
+    last_out: String,
+    punches: u32,
+    device_id: Option<String>,
+}
+
+// TEXT value escaping (RFC 5545 3.3.11)
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// Content lines longer than 75 octets continue on lines starting with a space
+fn write_line(out: &mut impl Write, line: &str) -> std::io::Result<()> {
+    let mut start = 0;
+    let mut limit = MAX_LINE_OCTETS;
+    while line.len() - start > limit {
+        let mut end = start + limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.write_all(&line.as_bytes()[start..end])?;
+        out.write_all(b"\r\n ")?;
+        start = end;
+        limit = MAX_LINE_OCTETS - 1;
+    }
+    out.write_all(&line.as_bytes()[start..])?;
+    out.write_all(b"\r\n")
+}
+
+fn ics_time(timestamp_utc: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(timestamp_utc)
+        .ok()
+        .map(|at| at.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn worked_days(
+    connection: &Connection,
+    user_id: &str,
+    range: (NaiveDate, NaiveDate),
+) -> Result<(String, Vec<WorkedDay>), String> {
+    let name: String = connection
+        .query_row(
+            "SELECT COALESCE((SELECT name FROM users WHERE user_id = ?1 LIMIT 1), '')",
+            params![user_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to look up user {}: {}", user_id, e))?;
+    // A day's punches are taken to come from one site, so any of its devices gives the zone
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT substr(a.timestamp, 1, 10), MIN(a.timestamp), MAX(a.timestamp), COUNT(*),
+                MAX(a.device_id)
+             FROM attendance_logs a
+             WHERE {} AND a.user_id = ?4
+             GROUP BY substr(a.timestamp, 1, 10)
+             ORDER BY substr(a.timestamp, 1, 10)",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let days = statement
+        .query_map(
+            params![
+                range.0.to_string(),
+                range.1.to_string(),
+                None::<String>,
+                user_id
+            ],
+            |row| {
+                Ok(WorkedDay {
+                    date: row.get(0)?,
+                    first_in: row.get(1)?,
+                    last_out: row.get(2)?,
+                    punches: row.get(3)?,
+                    device_id: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to query attendance: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance: {}", e))?;
+    Ok((name, days))
+}
+
+fn write_calendar(
+    connection: &Connection,
+    user_id: &str,
+    range: (NaiveDate, NaiveDate),
+    path: &Path,
+) -> Result<u64, String> {
+    let (name, days) = worked_days(connection, user_id, range)?;
+    let zones = registered_zones();
+    let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    let write_error = |e: std::io::Error| format!("Failed to write calendar: {}", e);
+    let label = if name.is_empty() {
+        user_id.to_string()
+    } else {
+        name.clone()
+    };
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    for line in [
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!(
+            "X-WR-CALNAME:{}",
+            escape_text(&format!("Attendance - {}", label))
+        ),
+    ] {
+        write_line(&mut out, &line).map_err(write_error)?;
+    }
+
+    let mut events = 0;
+    for day in days.iter().filter(|day| day.punches > 1) {
+        let zone = day
+            .device_id
+            .as_ref()
+            .and_then(|device| zones.get(device).copied());
+        let start = normalize_stored(&day.first_in, zone).and_then(|t| ics_time(&t.timestamp_utc));
+        let end = normalize_stored(&day.last_out, zone).and_then(|t| ics_time(&t.timestamp_utc));
+        let (Some(start), Some(end)) = (start, end) else {
+            continue;
+        };
+        let description = format!(
+            "First in {}, last out {}, {} punches",
+            time_part(&day.first_in),
+            time_part(&day.last_out),
+            day.punches
+        );
+        for line in [
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}-{}@ztkapp", escape_text(user_id), day.date),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", start),
+            format!("DTEND:{}", end),
+            "SUMMARY:Work".to_string(),
+            format!("DESCRIPTION:{}", escape_text(&description)),
+            "TRANSP:OPAQUE".to_string(),
+            "END:VEVENT".to_string(),
+        ] {
+            write_line(&mut out, &line).map_err(write_error)?;
+        }
+        events += 1;
+    }
+
+    write_line(&mut out, "END:VCALENDAR").map_err(write_error)?;
+    out.flush().map_err(write_error)?;
+    Ok(events)
+}
+
+// Worked intervals of one employee as an .ics file; without a path the user picks one in a
+// save dialog. Returns None when the dialog is cancelled.
+#[tauri::command]
+pub async fn export_attendance_ics(
+    app: AppHandle,
+    user_id: String,
+    range: ExportRange,
+    path: Option<String>,
+    password: Option<String>,
+) -> Result<Option<ExportResult>, String> {
+    lock::ensure_unlocked()?;
+    let user_id = user_id.trim().to_string();
+    if user_id.is_empty() {
+        return Err("Choose an employee to export".to_string());
+    }
+    let range = parse_range(&range)?;
+    let password = protect::check_password(password)?;
+    let Some(path) = resolve_save_path(
+        &app,
+        path,
+        "iCalendar file",
+        "ics",
+        format!("attendance_{}_{}_{}.ics", user_id, range.0, range.1),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let written = protect::write_path(&path, &password)?;
+    let target = written.clone();
+    let user = user_id.clone();
+    let events = run_db(move |connection| write_calendar(connection, &user, range, &target)).await;
+    let events = protect::finish(events, written, &path, password).await;
+    let audit_target = path.to_string_lossy().to_string();
+    let result = match events {
+        Ok((events, path)) => {
+            append_app_log(&format!(
+                "Exported attendance calendar for {} ({} to {}, {} days) to {}",
+                user_id, range.0, range.1, events, path
+            ));
+            Ok(Some(ExportResult::new(path, events, started)))
+        }
+        Err(err) => {
+            append_app_log(&format!("Attendance calendar export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_attendance_ics", &audit_target, result)
+}
@@ -0,0 +1,99 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::append_app_log;
+use crate::lock;
+use crate::settings::{current_settings, SharedSettings};
+
+// Inactivity tracking for reception PCs left unattended. The UI reports user input (throttled)
+// with report_activity; after idle_timeout_minutes without any, the app locks itself when a
+// lock PIN is set, or otherwise hides to the tray if idle_hide_to_tray is on. "session-idle"
+// is emitted first so the UI can leave screens showing employee data.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+// Last reported activity, and whether the idle action already ran since then
+static ACTIVITY: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct SessionIdle {
+    action: &'static str, // "lock" or "hide"
+    idle_minutes: u64,
+}
+
+// Restart the idle timer, e.g. on user input or after unlocking
+pub fn touch() {
+    if let Ok(mut activity) = ACTIVITY.lock() {
+        *activity = Some((Instant::now(), false));
+    }
+}
+
+// True once per idle period, when the timeout has passed
+fn take_idle(timeout: Duration) -> bool {
+    let Ok(mut activity) = ACTIVITY.lock() else {
+        return false;
+    };
+    let (since, handled) = activity.get_or_insert_with(|| (Instant::now(), false));
+    if *handled || since.elapsed() < timeout {
+        return false;
+    }
+    *handled = true;
+    true
+}
+
+fn hide_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(err) = window.hide() {
+            eprintln!("Failed to hide window after idle timeout: {}", err);
+        }
+    }
+}
+
+pub fn spawn_idle_watch(app: AppHandle, settings: SharedSettings) {
+    touch();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(IDLE_CHECK_INTERVAL).await;
+            let current = current_settings(&settings);
+            if current.idle_timeout_minutes == 0 || lock::is_locked() {
+                continue;
+            }
+            if !take_idle(Duration::from_secs(current.idle_timeout_minutes * 60)) {
+                continue;
+            }
+
+            let action = if lock::is_enabled() {
+                "lock"
+            } else if current.idle_hide_to_tray {
+                "hide"
+            } else {
+                continue;
+            };
+            let event = SessionIdle {
+                action,
+                idle_minutes: current.idle_timeout_minutes,
+            };
+            if let Err(err) = app.emit("session-idle", &event) {
+                eprintln!("Failed to emit session-idle event: {}", err);
+            }
+            if action == "lock" {
+                lock::lock_now(&app, "idle timeout");
+            } else {
+                append_app_log(&format!(
+                    "Hid window to tray after {} idle minutes",
+                    current.idle_timeout_minutes
+                ));
+                hide_main_window(&app);
+            }
+        }
+    });
+}
+
+// Called by the UI on user input (throttled) to push back the idle timeout
+#[tauri::command]
+pub fn report_activity() {
+    if !lock::is_locked() {
+        touch();
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use rusqlite::{params, Connection};
+
+use crate::audit::audited;
+use crate::database::open_read_write;
+use crate::path_policy::{check_path, Access};
+use crate::settings::{current_settings, SharedSettings};
+use crate::user_import::parse_csv;
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Watch folder for attendance exported by other systems. Every CSV dropped into ingest_dir is
+// matched against the mapping profiles (file name pattern plus the columns the profile needs),
+// validated row by row and, when the whole file is clean, written to attendance_logs; punches
+// already in the database are skipped. Imported files move to processed/, anything else to
+// quarantine/, each with a <file>.report.json beside it. Reports are also kept in
+// ingest_reports.jsonl for get_ingest_reports.
+const DEVICE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const PROCESSED_DIR: &str = "processed";
+const QUARANTINE_DIR: &str = "quarantine";
+// Files changed more recently may still be being copied in
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+const MIN_INTERVAL_SECS: u64 = 5;
+const MAX_REPORTED_ISSUES: usize = 200;
+
+static PROFILES_LOCK: Mutex<()> = Mutex::new(());
+static REPORTS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct IngestColumns {
+    pub user_id: String,
+    pub timestamp: String,
+    pub device_id: Option<String>,
+    pub serial_number: Option<String>,
+    pub method: Option<String>,
+    pub action: Option<String>,
+}
+
+impl Default for IngestColumns {
+    fn default() -> Self {
+        IngestColumns {
+            user_id: "user_id".to_string(),
+            timestamp: "timestamp".to_string(),
+            device_id: Some("device_id".to_string()),
+            serial_number: None,
+            method: Some("method".to_string()),
+            action: Some("action".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct IngestProfile {
+    pub name: String,
+    // File names this profile applies to; `*` and `?` wildcards, case-insensitive
+    pub file_pattern: String,
+    pub columns: IngestColumns,
+    // chrono format of the timestamp column
+    pub timestamp_format: String,
+    // Used when the file has no device column
+    pub device_id: Option<String>,
+    pub default_method: i64,
+    pub default_action: i64,
+}
+
+impl Default for IngestProfile {
+    fn default() -> Self {
+        IngestProfile {
+            name: String::new(),
+            file_pattern: "*.csv".to_string(),
+            columns: IngestColumns::default(),
+            timestamp_format: DEVICE_TIME_FORMAT.to_string(),
+            device_id: None,
+            default_method: 0,
+            default_action: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IngestIssue {
+    row: usize, // 1-based line number in the file, header included
+    field: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct IngestReport {
+    file: String,
+    profile: Option<String>,
+    processed_at: DateTime<Utc>,
+    total_rows: usize,
+    imported: usize,
+    duplicates: usize,
+    errors: Vec<IngestIssue>,
+    outcome: String, // "imported" or "quarantined"
+    moved_to: Option<String>,
+}
+
+struct Punch {
+    user_id: String,
+    device_id: Option<String>,
+    serial_number: Option<String>,
+    timestamp: String,
+    method: i64,
+    action: i64,
+}
+
+fn profiles_path() -> PathBuf {
+    resolve_app_data_dir().join("ingest_profiles.json")
+}
+
+fn reports_path() -> PathBuf {
+    resolve_app_data_dir().join("ingest_reports.jsonl")
+}
+
+fn load_profiles() -> Vec<IngestProfile> {
+    fs::read_to_string(profiles_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_profiles(profiles: &[IngestProfile]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(profiles)
+        .map_err(|e| format!("Failed to serialize ingest profiles: {}", e))?;
+    fs::write(profiles_path(), content)
+        .map_err(|e| format!("Failed to save ingest profiles: {}", e))
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn matches_pattern(pattern: &str, file_name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = file_name.to_lowercase().chars().collect();
+    wildcard_match(&pattern, &name)
+}
+
+fn column_index(header: &[String], name: &str) -> Option<usize> {
+    header
+        .iter()
+        .position(|column| column.trim().eq_ignore_ascii_case(name.trim()))
+}
+
+// The first profile whose pattern fits the file and whose required columns are all present
+fn select_profile<'a>(
+    profiles: &'a [IngestProfile],
+    file_name: &str,
+    header: &[String],
+) -> Option<&'a IngestProfile> {
+    profiles.iter().find(|profile| {
+        let columns = &profile.columns;
+        matches_pattern(&profile.file_pattern, file_name)
+            && [
+                Some(&columns.user_id),
+                Some(&columns.timestamp),
+                columns.device_id.as_ref(),
+                columns.serial_number.as_ref(),
+                columns.method.as_ref(),
+                columns.action.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .all(|name| column_index(header, name).is_some())
+    })
+}
+
+// Validate every row; punches are only returned for a file without errors
+fn validate_rows(
+    rows: &[Vec<String>],
+    profile: &IngestProfile,
+    errors: &mut Vec<IngestIssue>,
+) -> Vec<Punch> {
+    let Some(header) = rows.first() else {
+        return Vec::new();
+    };
+    let columns = &profile.columns;
+    let index = |name: &Option<String>| name.as_ref().and_then(|name| column_index(header, name));
+    let user_col = column_index(header, &columns.user_id);
+    let time_col = column_index(header, &columns.timestamp);
+    let (device_col, serial_col) = (index(&columns.device_id), index(&columns.serial_number));
+    let (method_col, action_col) = (index(&columns.method), index(&columns.action));
+
+    let mut punches = Vec::new();
+    for (offset, row) in rows.iter().enumerate().skip(1) {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let line = offset + 1;
+        let cell = |col: Option<usize>| {
+            col.and_then(|col| row.get(col))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+        let mut issue = |field: &str, message: String| {
+            errors.push(IngestIssue {
+                row: line,
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        let user_id = cell(user_col);
+        if user_id.is_empty() {
+            issue("user_id", "User id is empty".to_string());
+        }
+        let raw_time = cell(time_col);
+        let timestamp = NaiveDateTime::parse_from_str(&raw_time, &profile.timestamp_format)
+            .map(|time| time.format(DEVICE_TIME_FORMAT).to_string());
+        if timestamp.is_err() {
+            issue(
+                "timestamp",
+                format!(
+                    "'{}' does not match the format {}",
+                    raw_time, profile.timestamp_format
+                ),
+            );
+        }
+        let mut number = |col: Option<usize>, field: &str, default: i64| {
+            let value = cell(col);
+            if value.is_empty() {
+                return default;
+            }
+            value.parse().unwrap_or_else(|_| {
+                issue(field, format!("'{}' is not a number", value));
+                default
+            })
+        };
+        let method = number(method_col, "method", profile.default_method);
+        let action = number(action_col, "action", profile.default_action);
+
+        let device_id = Some(cell(device_col))
+            .filter(|id| !id.is_empty())
+            .or_else(|| profile.device_id.clone());
+        let serial_number = Some(cell(serial_col)).filter(|serial| !serial.is_empty());
+        if let Ok(timestamp) = timestamp {
+            punches.push(Punch {
+                user_id,
+                device_id,
+                serial_number,
+                timestamp,
+                method,
+                action,
+            });
+        }
+    }
+    if errors.is_empty() {
+        punches
+    } else {
+        Vec::new()
+    }
+}
+
+// Insert the punches in one transaction; returns (imported, duplicates)
+fn insert_punches(
+    connection: &Connection,
+    punches: &[Punch],
+    source: &str,
+) -> Result<(usize, usize), String> {
+    let tx = connection
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to start import: {}", e))?;
+    let mut seen = HashSet::new();
+    let (mut imported, mut duplicates) = (0, 0);
+    {
+        let mut exists = tx
+            .prepare(
+                "SELECT EXISTS(SELECT 1 FROM attendance_logs
+                 WHERE user_id = ?1 AND timestamp = ?2 AND device_id IS ?3)",
+            )
+            .map_err(|e| format!("Failed to check existing attendance: {}", e))?;
+        let mut insert = tx
+            .prepare(
+                "INSERT INTO attendance_logs
+                 (user_id, device_id, serial_number, timestamp, method, action, raw_data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .map_err(|e| format!("Failed to prepare import: {}", e))?;
+        for punch in punches {
+            let key = (
+                punch.user_id.clone(),
+                punch.timestamp.clone(),
+                punch.device_id.clone(),
+            );
+            let known: bool = exists
+                .query_row(
+                    params![punch.user_id, punch.timestamp, punch.device_id],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to check existing attendance: {}", e))?;
+            if known || !seen.insert(key) {
+                duplicates += 1;
+                continue;
+            }
+            insert
+                .execute(params![
+                    punch.user_id,
+                    punch.device_id,
+                    punch.serial_number,
+                    punch.timestamp,
+                    punch.method,
+                    punch.action,
+                    serde_json::json!({ "source": "csv_ingest", "file": source }).to_string(),
+                ])
+                .map_err(|e| format!("Failed to import attendance: {}", e))?;
+            imported += 1;
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+    Ok((imported, duplicates))
+}
+
+// Move the file into `subdir` under a time-prefixed name and write its report beside it
+fn file_away(path: &Path, subdir: &str, report: &mut IngestReport) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| format!("{:?} has no parent folder", path))?
+        .join(subdir);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    let target = dir.join(format!(
+        "{}_{}",
+        Local::now().format("%Y%m%d_%H%M%S"),
+        report.file
+    ));
+    fs::rename(path, &target).map_err(|e| format!("Failed to move {:?}: {}", path, e))?;
+    report.moved_to = Some(target.to_string_lossy().to_string());
+
+    let mut report_name = target.file_name().unwrap_or_default().to_os_string();
+    report_name.push(".report.json");
+    let content = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize ingest report: {}", e))?;
+    fs::write(dir.join(report_name), content)
+        .map_err(|e| format!("Failed to write ingest report: {}", e))
+}
+
+fn record_report(report: &IngestReport) {
+    let _guard = REPORTS_LOCK.lock();
+    let appended = serde_json::to_string(report)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(reports_path())
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(err) = appended {
+        eprintln!("Failed to record ingest report: {}", err);
+    }
+}
+
+fn ingest_file(path: &Path, profiles: &[IngestProfile]) -> IngestReport {
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut report = IngestReport {
+        file: file.clone(),
+        processed_at: Utc::now(),
+        ..Default::default()
+    };
+    let failure = |message: String| IngestIssue {
+        row: 0,
+        field: "file".to_string(),
+        message,
+    };
+
+    let result = fs::read(path)
+        .map_err(|e| format!("Failed to read file: {}", e))
+        .map(|content| parse_csv(&String::from_utf8_lossy(&content)));
+    match result {
+        Err(err) => report.errors.push(failure(err)),
+        Ok(rows) => {
+            let header = rows.first().cloned().unwrap_or_default();
+            report.total_rows = rows.len().saturating_sub(1);
+            match select_profile(profiles, &file, &header) {
+                None => report.errors.push(failure(
+                    "No mapping profile matches this file name and its columns".to_string(),
+                )),
+                Some(profile) => {
+                    report.profile = Some(profile.name.clone());
+                    let punches = validate_rows(&rows, profile, &mut report.errors);
+                    if report.errors.is_empty() {
+                        match open_read_write()
+                            .and_then(|connection| insert_punches(&connection, &punches, &file))
+                        {
+                            Ok((imported, duplicates)) => {
+                                report.imported = imported;
+                                report.duplicates = duplicates;
+                            }
+                            Err(err) => report.errors.push(failure(err)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    report.errors.truncate(MAX_REPORTED_ISSUES);
+
+    let (outcome, subdir) = if report.errors.is_empty() {
+        ("imported", PROCESSED_DIR)
+    } else {
+        ("quarantined", QUARANTINE_DIR)
+    };
+    report.outcome = outcome.to_string();
+    if let Err(err) = file_away(path, subdir, &mut report) {
+        eprintln!("{}", err);
+        append_app_log(&format!("CSV ingest could not file away {}: {}", file, err));
+    }
+    append_app_log(&format!(
+        "CSV ingest {} {}: {} imported, {} duplicates, {} errors",
+        outcome,
+        file,
+        report.imported,
+        report.duplicates,
+        report.errors.len()
+    ));
+    record_report(&report);
+    report
+}
+
+// CSV files in the folder that have stopped changing
+fn ready_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age >= SETTLE_TIME)
+        })
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn scan(dir: &str) -> Result<Vec<IngestReport>, String> {
+    let dir = check_path(dir, Access::Write)?;
+    let files = ready_files(&dir);
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+    let profiles = load_profiles();
+    Ok(files
+        .iter()
+        .map(|path| ingest_file(path, &profiles))
+        .collect())
+}
+
+pub fn spawn_ingest_watcher(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_error: Option<String> = None;
+        loop {
+            let config = current_settings(&settings);
+            let interval = config.ingest_interval_seconds.max(MIN_INTERVAL_SECS);
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let Some(dir) = config.ingest_dir.filter(|dir| !dir.trim().is_empty()) else {
+                continue;
+            };
+
+            let result = tauri::async_runtime::spawn_blocking(move || scan(&dir))
+                .await
+                .map_err(|e| format!("Ingest task failed: {}", e))
+                .and_then(|result| result);
+            // Log a failing folder once, not every interval
+            match result {
+                Ok(_) => last_error = None,
+                Err(err) if last_error.as_ref() != Some(&err) => {
+                    eprintln!("CSV ingest failed: {}", err);
+                    append_app_log(&format!("CSV ingest failed: {}", err));
+                    last_error = Some(err);
+                }
+                Err(_) => {}
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn list_ingest_profiles() -> Vec<IngestProfile> {
+    load_profiles()
+}
+
+// Add a profile, or replace the one with the same name
+#[tauri::command]
+pub fn save_ingest_profile(profile: IngestProfile) -> Result<IngestProfile, String> {
+    lock::ensure_unlocked()?;
+    let name = profile.name.trim().to_string();
+    let result = (|| {
+        if name.is_empty() {
+            return Err("Ingest profile needs a name".to_string());
+        }
+        if profile.columns.user_id.trim().is_empty() || profile.columns.timestamp.trim().is_empty()
+        {
+            return Err("Ingest profile needs user id and timestamp columns".to_string());
+        }
+        let profile = IngestProfile {
+            name: name.clone(),
+            ..profile
+        };
+        let _guard = PROFILES_LOCK.lock();
+        let mut profiles = load_profiles();
+        match profiles.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => *existing = profile.clone(),
+            None => profiles.push(profile.clone()),
+        }
+        save_profiles(&profiles)?;
+        Ok(profile)
+    })();
+    audited("save_ingest_profile", &name, result)
+}
+
+#[tauri::command]
+pub fn delete_ingest_profile(name: String) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        let _guard = PROFILES_LOCK.lock();
+        let mut profiles = load_profiles();
+        let before = profiles.len();
+        profiles.retain(|profile| profile.name != name);
+        if profiles.len() == before {
+            return Err(format!("Ingest profile '{}' not found", name));
+        }
+        save_profiles(&profiles)
+    })();
+    audited("delete_ingest_profile", &name, result)
+}
+
+// Most recent first
+#[tauri::command]
+pub fn get_ingest_reports(limit: Option<usize>) -> Result<Vec<IngestReport>, String> {
+    let content = match fs::read_to_string(reports_path()) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to read ingest reports: {}", err)),
+    };
+    let mut reports: Vec<IngestReport> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    reports.reverse();
+    if let Some(limit) = limit {
+        reports.truncate(limit);
+    }
+    Ok(reports)
+}
+
+// Process the watch folder now instead of waiting for the next scan
+#[tauri::command]
+pub async fn run_ingest_now(
+    settings: tauri::State<'_, SharedSettings>,
+) -> Result<Vec<IngestReport>, String> {
+    lock::ensure_unlocked()?;
+    let dir = current_settings(&settings)
+        .ingest_dir
+        .filter(|dir| !dir.trim().is_empty())
+        .ok_or("No ingest folder is configured")?;
+    tauri::async_runtime::spawn_blocking(move || scan(&dir))
+        .await
+        .map_err(|e| format!("Ingest task failed: {}", e))?
+}
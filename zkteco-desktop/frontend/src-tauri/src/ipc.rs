@@ -0,0 +1,219 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::append_app_log;
+use crate::device_manager::DeviceManager;
+use crate::local_api;
+use crate::monitor::MonitorBus;
+use crate::settings::{current_settings, SharedSettings};
+
+// Local IPC channel for scripts that shouldn't deal with HTTP or ports: a Unix domain socket
+// (ipc.sock in the data folder, owner-only) on macOS/Linux, a named pipe
+// (\\.\pipe\ztkapp-<profile>, local clients only) on Windows. It carries the integration API's
+// calls (local_api.rs) as newline-delimited JSON, any number per connection:
+//   {"id": 1, "token": "<local API token>", "method": "attendance", "params": {"since_id": 10}}
+// is answered with {"id": 1, "result": ...} or {"id": 1, "error": "..."}.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+static STATS: Mutex<Option<IpcStats>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct IpcStats {
+    listening: bool,
+    endpoint: Option<String>,
+    requests_served: u64,
+    requests_rejected: u64,
+    last_request_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct IpcRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+    #[serde(default)]
+    token: String,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Map<String, serde_json::Value>,
+}
+
+fn update_stats(f: impl FnOnce(&mut IpcStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+fn started(endpoint: String) {
+    append_app_log(&format!("IPC channel listening on {}", endpoint));
+    update_stats(|stats| {
+        stats.listening = true;
+        stats.endpoint = Some(endpoint);
+    });
+}
+
+// JSON params as the query pairs the integration API takes
+fn query_params(params: &serde_json::Map<String, serde_json::Value>) -> Vec<(String, String)> {
+    params
+        .iter()
+        .filter_map(|(key, value)| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(text) => Some((key.clone(), text.clone())),
+            other => Some((key.clone(), other.to_string())),
+        })
+        .collect()
+}
+
+async fn respond(line: &str, manager: &DeviceManager) -> serde_json::Value {
+    let request: IpcRequest = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => {
+            return serde_json::json!({
+                "id": null,
+                "error": format!("Malformed request: {}", err),
+            })
+        }
+    };
+    update_stats(|stats| stats.last_request_at = Some(Utc::now()));
+    if !local_api::token_matches(&request.token) {
+        update_stats(|stats| stats.requests_rejected += 1);
+        return serde_json::json!({ "id": request.id, "error": "Missing or invalid API token" });
+    }
+
+    let manager = manager.clone();
+    let params = query_params(&request.params);
+    let method = request.method;
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        local_api::call(&method, &params, &manager).map_err(|err| err.message())
+    })
+    .await
+    .map_err(|e| format!("IPC task failed: {}", e))
+    .and_then(|result| result);
+    match result {
+        Ok(result) => {
+            update_stats(|stats| stats.requests_served += 1);
+            serde_json::json!({ "id": request.id, "result": result })
+        }
+        Err(err) => serde_json::json!({ "id": request.id, "error": err }),
+    }
+}
+
+async fn serve<S>(stream: S, manager: DeviceManager)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut line = Vec::new();
+        match (&mut reader)
+            .take(MAX_REQUEST_BYTES as u64 + 1)
+            .read_until(b'\n', &mut line)
+            .await
+        {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.len() > MAX_REQUEST_BYTES {
+            let _ = writer
+                .write_all(b"{\"id\":null,\"error\":\"Request too large\"}\n")
+                .await;
+            return;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let mut response = respond(text.trim(), &manager).await.to_string();
+        response.push('\n');
+        if writer.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn listen(manager: DeviceManager) -> Result<(), String> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let path = crate::resolve_app_data_dir().join("ipc.sock");
+    // A socket left behind by a previous run; never remove anything else
+    if std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to bind IPC socket {:?}: {}", path, e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict IPC socket {:?}: {}", path, e))?;
+    started(path.display().to_string());
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tauri::async_runtime::spawn(serve(stream, manager.clone()));
+            }
+            Err(err) => eprintln!("IPC accept failed: {}", err),
+        }
+    }
+}
+
+#[cfg(windows)]
+async fn listen(manager: DeviceManager) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let profile = crate::profiles::active_profile()
+        .unwrap_or_else(|| crate::profiles::DEFAULT_PROFILE.to_string());
+    let name = format!(r"\\.\pipe\ztkapp-{}", profile);
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(&name)
+        .map_err(|e| format!("Failed to create IPC pipe {}: {}", name, e))?;
+    started(name.clone());
+
+    loop {
+        server
+            .connect()
+            .await
+            .map_err(|e| format!("IPC pipe {} failed: {}", name, e))?;
+        // The next instance has to exist before the connected one is handed off
+        let connected = server;
+        server = ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(&name)
+            .map_err(|e| format!("Failed to create IPC pipe {}: {}", name, e))?;
+        tauri::async_runtime::spawn(serve(connected, manager.clone()));
+    }
+}
+
+pub fn spawn_ipc_server(settings: SharedSettings, manager: DeviceManager, bus: &MonitorBus) {
+    if !current_settings(&settings).ipc_enabled {
+        return;
+    }
+    if let Err(err) = local_api::prepare(bus) {
+        eprintln!("{}", err);
+        update_stats(|stats| stats.last_error = Some(err));
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = listen(manager).await {
+            eprintln!("{}", err);
+            append_app_log(&err);
+            update_stats(|stats| {
+                stats.listening = false;
+                stats.last_error = Some(err);
+            });
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_ipc_status() -> IpcStats {
+    STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default()
+}
@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use ldap3::adapters::{Adapter, EntriesOnly, PagedResults};
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::registry::DeviceRegistry;
+use crate::secrets::ldap_password;
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::user_import::{commit_users, DevicePushResult, ImportOptions, MAX_USER_ID_LEN};
+use crate::zk::DeviceUser;
+use crate::{append_app_log, lock, BackendPort};
+
+// Employee list from Active Directory or another LDAP directory. The accounts selected by the
+// ldap_* settings become device users (id, name and card from the configured attributes) and
+// go through the same backend import and terminal push as a CSV import (user_import.rs).
+// preview_ldap_import shows what would be imported and which entries are skipped and why;
+// run_ldap_import writes it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(60);
+// Active Directory returns at most 1000 entries per request without paging
+const PAGE_SIZE: i32 = 500;
+const MAX_ENTRIES: usize = 20_000;
+// userAccountControl flag of a disabled AD account
+const ACCOUNT_DISABLED: u32 = 0x2;
+
+struct LdapConfig {
+    url: String,
+    starttls: bool,
+    bind_dn: Option<String>,
+    base_dn: String,
+    filter: String,
+    id_attribute: String,
+    name_attribute: String,
+    card_attribute: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SkippedEntry {
+    dn: String,
+    reason: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LdapPreview {
+    entries_found: usize,
+    users: Vec<DeviceUser>,
+    skipped: Vec<SkippedEntry>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LdapImportReport {
+    entries_found: usize,
+    imported: usize,
+    skipped: Vec<SkippedEntry>,
+    committed: bool,
+    backend_response: Option<serde_json::Value>,
+    pushed: Vec<DevicePushResult>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn ldap_config(settings: &ShellSettings) -> Result<LdapConfig, String> {
+    let url = non_empty(&settings.ldap_url).ok_or("No LDAP server is configured")?;
+    if !["ldap://", "ldaps://"]
+        .iter()
+        .any(|scheme| url.to_ascii_lowercase().starts_with(scheme))
+    {
+        return Err(format!(
+            "LDAP server '{}' must be an ldap:// or ldaps:// URL",
+            url
+        ));
+    }
+    let base_dn = non_empty(&settings.ldap_base_dn).ok_or("No LDAP base DN is configured")?;
+
+    let mut filter = settings.ldap_user_filter.trim().to_string();
+    if filter.is_empty() {
+        filter = "(objectClass=*)".to_string();
+    } else if !filter.starts_with('(') {
+        filter = format!("({})", filter);
+    }
+    if let Some(group) = non_empty(&settings.ldap_group_dn) {
+        filter = format!("(&{}(memberOf={}))", filter, ldap_escape(group.as_str()));
+    }
+
+    Ok(LdapConfig {
+        url,
+        starttls: settings.ldap_starttls,
+        bind_dn: non_empty(&settings.ldap_bind_dn),
+        base_dn,
+        filter,
+        id_attribute: settings.ldap_user_id_attribute.trim().to_string(),
+        name_attribute: settings.ldap_name_attribute.trim().to_string(),
+        card_attribute: non_empty(&settings.ldap_card_attribute),
+    })
+}
+
+async fn fetch_entries(config: &LdapConfig) -> Result<Vec<SearchEntry>, String> {
+    let connection = LdapConnSettings::new()
+        .set_conn_timeout(CONNECT_TIMEOUT)
+        .set_starttls(config.starttls);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(connection, &config.url)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {}", config.url, e))?;
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = conn.drive().await {
+            eprintln!("LDAP connection error: {}", err);
+        }
+    });
+
+    if let Some(bind_dn) = &config.bind_dn {
+        let password = ldap_password().unwrap_or_default();
+        ldap.with_timeout(CONNECT_TIMEOUT)
+            .simple_bind(bind_dn, &password)
+            .await
+            .and_then(|result| result.success())
+            .map_err(|e| format!("LDAP bind as {} failed: {}", bind_dn, e))?;
+    }
+
+    let mut attributes = vec![
+        config.id_attribute.clone(),
+        config.name_attribute.clone(),
+        "userAccountControl".to_string(),
+    ];
+    attributes.extend(config.card_attribute.clone());
+    let adapters: Vec<Box<dyn Adapter<_, _>>> = vec![
+        Box::new(EntriesOnly::new()),
+        Box::new(PagedResults::new(PAGE_SIZE)),
+    ];
+    let mut search = ldap
+        .with_timeout(SEARCH_TIMEOUT)
+        .streaming_search_with(
+            adapters,
+            &config.base_dn,
+            Scope::Subtree,
+            &config.filter,
+            attributes,
+        )
+        .await
+        .map_err(|e| format!("LDAP search failed: {}", e))?;
+
+    let mut entries = Vec::new();
+    while let Some(entry) = search
+        .next()
+        .await
+        .map_err(|e| format!("LDAP search failed: {}", e))?
+    {
+        if entries.len() >= MAX_ENTRIES {
+            return Err(format!(
+                "The LDAP search matched more than {} entries; narrow the filter or group",
+                MAX_ENTRIES
+            ));
+        }
+        entries.push(SearchEntry::construct(entry));
+    }
+    search
+        .finish()
+        .await
+        .success()
+        .map_err(|e| format!("LDAP search failed: {}", e))?;
+    let _ = ldap.unbind().await;
+    Ok(entries)
+}
+
+// First value of an attribute; servers may return the name in another case
+fn attribute(entry: &SearchEntry, name: &str) -> Option<String> {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .and_then(|(_, values)| values.first())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn map_entries(entries: &[SearchEntry], config: &LdapConfig) -> LdapPreview {
+    let mut users = Vec::new();
+    let mut skipped = Vec::new();
+    let mut seen_ids = HashSet::new();
+    let mut seen_cards = HashSet::new();
+    for entry in entries {
+        let mut skip = |reason: String| {
+            skipped.push(SkippedEntry {
+                dn: entry.dn.clone(),
+                reason,
+            })
+        };
+        let disabled = attribute(entry, "userAccountControl")
+            .and_then(|flags| flags.parse::<u32>().ok())
+            .is_some_and(|flags| flags & ACCOUNT_DISABLED != 0);
+        if disabled {
+            skip("Account is disabled".to_string());
+            continue;
+        }
+
+        let Some(user_id) = attribute(entry, &config.id_attribute) else {
+            skip(format!("No {} attribute", config.id_attribute));
+            continue;
+        };
+        if user_id.len() > MAX_USER_ID_LEN || !user_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            skip(format!(
+                "{} '{}' must be letters/digits, at most {} characters",
+                config.id_attribute, user_id, MAX_USER_ID_LEN
+            ));
+            continue;
+        }
+        let Some(name) = attribute(entry, &config.name_attribute) else {
+            skip(format!("No {} attribute", config.name_attribute));
+            continue;
+        };
+        let card = match config
+            .card_attribute
+            .as_ref()
+            .and_then(|card| attribute(entry, card))
+        {
+            None => 0,
+            Some(text) => match text.parse::<u32>() {
+                Ok(card) => card,
+                Err(_) => {
+                    skip(format!("'{}' is not a valid card number", text));
+                    continue;
+                }
+            },
+        };
+        if !seen_ids.insert(user_id.clone()) {
+            skip(format!("User id {} is used by another entry", user_id));
+            continue;
+        }
+        if card != 0 && !seen_cards.insert(card) {
+            skip(format!("Card {} is used by another entry", card));
+            continue;
+        }
+
+        users.push(DeviceUser {
+            uid: 0,
+            user_id,
+            name,
+            privilege: 0,
+            password: String::new(),
+            card,
+            group_id: "1".to_string(),
+        });
+    }
+    LdapPreview {
+        entries_found: entries.len(),
+        users,
+        skipped,
+    }
+}
+
+async fn load_directory(settings: &SharedSettings) -> Result<LdapPreview, String> {
+    let config = ldap_config(&current_settings(settings))?;
+    let entries = fetch_entries(&config).await?;
+    Ok(map_entries(&entries, &config))
+}
+
+#[tauri::command]
+pub async fn preview_ldap_import(
+    settings: State<'_, SharedSettings>,
+) -> Result<LdapPreview, String> {
+    lock::ensure_unlocked()?;
+    load_directory(&settings).await
+}
+
+// Import the directory's users; with dry_run only the report is produced
+#[tauri::command]
+pub async fn run_ldap_import(
+    options: Option<ImportOptions>,
+    settings: State<'_, SharedSettings>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<LdapImportReport, String> {
+    lock::ensure_unlocked()?;
+    let options = options.unwrap_or_default();
+    let target = current_settings(&settings).ldap_base_dn.unwrap_or_default();
+    let result = async {
+        let preview = load_directory(&settings).await?;
+        let mut report = LdapImportReport {
+            entries_found: preview.entries_found,
+            imported: preview.users.len(),
+            skipped: preview.skipped,
+            ..Default::default()
+        };
+        if options.dry_run || preview.users.is_empty() {
+            return Ok(report);
+        }
+
+        let (response, pushed) =
+            commit_users(&preview.users, &options, &registry, &backend_port).await?;
+        report.backend_response = Some(response);
+        report.committed = true;
+        report.pushed = pushed;
+        append_app_log(&format!(
+            "Imported {} users from the LDAP directory ({} skipped)",
+            report.imported,
+            report.skipped.len()
+        ));
+        Ok(report)
+    }
+    .await;
+    audited("run_ldap_import", &target, result)
+}
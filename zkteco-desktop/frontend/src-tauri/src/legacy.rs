@@ -0,0 +1,334 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::audit::audited;
+use crate::backup::{
+    start_backend_after_maintenance, stop_backend_for_maintenance, swap_database, validate_backup,
+    BACKUP_PAGES_PER_STEP, BACKUP_STEP_PAUSE,
+};
+use crate::settings::{current_settings, save_settings, SharedSettings};
+use crate::{
+    append_app_log, lock, resolve_app_data_dir, resolve_backend_db_path, resolve_base_data_dir,
+};
+
+// Builds before the data folder existed left zkteco_app.db wherever the backend was started
+// (its working directory, /opt/zkteco for the old systemd unit, the app bundle for the old
+// LaunchAgent) and app.log in ~/zkteco_logs, ~/.local/share/ZKTeco or the roaming AppData
+// folder. The first launch looks for them and emits "legacy-data-found"; migrate_legacy_data
+// imports the database (the current one is kept as a safety copy) and moves the logs into
+// legacy_logs/, dismiss_legacy_data declines. Either outcome, or finding nothing, is recorded
+// in legacy_data_migration so later launches don't look again. The old files are left in place
+// apart from the moved logs.
+const DATABASE_FILE: &str = "zkteco_app.db";
+const LOG_FILES: [&str; 4] = ["app.log", "app.log.1", "app.log.2", "app.log.3"];
+const LEGACY_LOG_DIR: &str = "legacy_logs";
+const STAGING_FILE: &str = "legacy_import.db";
+// Give the webview time to subscribe before announcing what was found
+const SCAN_DELAY: Duration = Duration::from_secs(5);
+pub const OUTCOME_MIGRATED: &str = "migrated";
+pub const OUTCOME_DISMISSED: &str = "dismissed";
+pub const OUTCOME_NONE_FOUND: &str = "none_found";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LegacyLocation {
+    dir: String,
+    database: Option<String>,
+    database_bytes: u64,
+    database_modified: Option<String>,
+    logs: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LegacyScan {
+    outcome: Option<String>, // what was decided on an earlier launch, None while undecided
+    source: Option<String>,
+    locations: Vec<LegacyLocation>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LegacyMigrationResult {
+    source: String,
+    database_imported: Option<String>,
+    safety_copy: Option<String>,
+    logs_moved: Vec<String>,
+    backend_restarted: bool,
+}
+
+// Folders older builds may have written to, and whether logs there are ours (a generic
+// app.log in the home or program folder is not)
+fn candidate_dirs() -> Vec<(PathBuf, bool)> {
+    let mut candidates = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        candidates.push((home.join("zkteco_logs"), true));
+        candidates.push((home.join(".local").join("share").join("ZKTeco"), true));
+        candidates.push((home.join("ZKTeco"), true));
+        candidates.push((home, false));
+    }
+    if let Some(roaming) = dirs::data_dir() {
+        candidates.push((roaming.join("ZKTeco"), true));
+    }
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        candidates.push((dir, false));
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        candidates.push((dir, false));
+    }
+    if cfg!(target_os = "linux") {
+        candidates.push((PathBuf::from("/opt/zkteco"), false));
+    }
+    if cfg!(target_os = "macos") {
+        candidates.push((
+            PathBuf::from("/Applications/ZKTeco.app/Contents/Resources"),
+            false,
+        ));
+    }
+
+    // Folders the app uses today are not legacy
+    let current: Vec<PathBuf> = [
+        Some(resolve_base_data_dir()),
+        Some(resolve_app_data_dir()),
+        resolve_backend_db_path().parent().map(Path::to_path_buf),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|dir| dir.canonicalize().unwrap_or(dir))
+    .collect();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    candidates
+        .into_iter()
+        .filter(|(dir, _)| {
+            let dir = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+            if current.contains(&dir) || seen.contains(&dir) {
+                return false;
+            }
+            seen.push(dir);
+            true
+        })
+        .collect()
+}
+
+fn inspect(dir: &Path, logs_are_ours: bool) -> Option<LegacyLocation> {
+    let database = dir.join(DATABASE_FILE);
+    let metadata = fs::metadata(&database).ok().filter(|meta| meta.is_file());
+    let logs: Vec<String> = if logs_are_ours {
+        LOG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .filter(|path| path.is_file())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    if metadata.is_none() && logs.is_empty() {
+        return None;
+    }
+    Some(LegacyLocation {
+        dir: dir.to_string_lossy().to_string(),
+        database: metadata
+            .as_ref()
+            .map(|_| database.to_string_lossy().to_string()),
+        database_bytes: metadata.as_ref().map(|meta| meta.len()).unwrap_or(0),
+        database_modified: metadata
+            .and_then(|meta| meta.modified().ok())
+            .map(|time| DateTime::<Local>::from(time).to_rfc3339()),
+        logs,
+    })
+}
+
+fn scan() -> Vec<LegacyLocation> {
+    candidate_dirs()
+        .into_iter()
+        .filter_map(|(dir, logs_are_ours)| inspect(&dir, logs_are_ours))
+        .collect()
+}
+
+fn record_outcome(
+    settings: &SharedSettings,
+    outcome: &str,
+    source: Option<String>,
+) -> Result<(), String> {
+    let mut guard = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))?;
+    let mut updated = guard.clone();
+    updated.legacy_data_migration = Some(outcome.to_string());
+    updated.legacy_data_source = source;
+    save_settings(&updated)?;
+    *guard = updated;
+    Ok(())
+}
+
+// Look for legacy data once, on the first launch that hasn't settled it yet
+pub fn spawn_legacy_scan(app: AppHandle, settings: SharedSettings) {
+    if current_settings(&settings).legacy_data_migration.is_some() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SCAN_DELAY).await;
+        let locations = match tauri::async_runtime::spawn_blocking(scan).await {
+            Ok(locations) => locations,
+            Err(err) => {
+                eprintln!("Legacy data scan failed: {}", err);
+                return;
+            }
+        };
+        if locations.is_empty() {
+            if let Err(err) = record_outcome(&settings, OUTCOME_NONE_FOUND, None) {
+                eprintln!("{}", err);
+            }
+            return;
+        }
+        append_app_log(&format!(
+            "Found data from an older install in {}",
+            locations
+                .iter()
+                .map(|location| location.dir.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if let Err(err) = app.emit("legacy-data-found", &locations) {
+            eprintln!("Failed to emit legacy-data-found event: {}", err);
+        }
+    });
+}
+
+// Copy the legacy database, WAL included, into one file next to the live one
+fn stage_database(source: &Path) -> Result<PathBuf, String> {
+    let staging = resolve_app_data_dir().join(STAGING_FILE);
+    let _ = fs::remove_file(&staging);
+    let legacy = Connection::open_with_flags(source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open {:?}: {}", source, e))?;
+    let mut destination =
+        Connection::open(&staging).map_err(|e| format!("Failed to create {:?}: {}", staging, e))?;
+    let copied = Backup::new(&legacy, &mut destination)
+        .and_then(|backup| backup.run_to_completion(BACKUP_PAGES_PER_STEP, BACKUP_STEP_PAUSE, None))
+        .map_err(|e| format!("Failed to copy {:?}: {}", source, e));
+    drop(destination);
+    if let Err(err) = copied.and_then(|_| validate_backup(&staging)) {
+        let _ = fs::remove_file(&staging);
+        return Err(err);
+    }
+    Ok(staging)
+}
+
+// Move logs into legacy_logs/, named after the folder they came from
+fn move_logs(location: &LegacyLocation) -> Result<Vec<String>, String> {
+    let target_dir = resolve_app_data_dir().join(LEGACY_LOG_DIR);
+    fs::create_dir_all(&target_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", target_dir, e))?;
+    let origin = Path::new(&location.dir)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "legacy".to_string());
+    let mut moved = Vec::new();
+    for log in &location.logs {
+        let source = Path::new(log);
+        let Some(name) = source.file_name() else {
+            continue;
+        };
+        let target = target_dir.join(format!("{}-{}", origin, name.to_string_lossy()));
+        fs::rename(source, &target)
+            .or_else(|_| fs::copy(source, &target).and_then(|_| fs::remove_file(source)))
+            .map_err(|e| format!("Failed to move {:?}: {}", source, e))?;
+        moved.push(target.to_string_lossy().to_string());
+    }
+    Ok(moved)
+}
+
+async fn migrate(
+    app: &AppHandle,
+    settings: &SharedSettings,
+    source: &str,
+) -> Result<LegacyMigrationResult, String> {
+    // Only folders the scan reports, never an arbitrary path
+    let location = tauri::async_runtime::spawn_blocking(scan)
+        .await
+        .map_err(|e| format!("Legacy data scan failed: {}", e))?
+        .into_iter()
+        .find(|location| location.dir == source)
+        .ok_or_else(|| format!("No data from an older install in {}", source))?;
+
+    let mut result = LegacyMigrationResult {
+        source: location.dir.clone(),
+        database_imported: None,
+        safety_copy: None,
+        logs_moved: Vec::new(),
+        backend_restarted: false,
+    };
+    if let Some(database) = location.database.clone() {
+        let staging = {
+            let database = PathBuf::from(&database);
+            tauri::async_runtime::spawn_blocking(move || stage_database(&database))
+                .await
+                .map_err(|e| format!("Legacy database import failed: {}", e))??
+        };
+        if let Err(err) = stop_backend_for_maintenance(app).await {
+            let _ = fs::remove_file(&staging);
+            return Err(format!("{}, migration aborted", err));
+        }
+        let swapped = {
+            let staging = staging.clone();
+            tauri::async_runtime::spawn_blocking(move || swap_database(&staging))
+                .await
+                .map_err(|e| format!("Database swap failed: {}", e))
+                .and_then(|result| result)
+        };
+        let _ = fs::remove_file(&staging);
+        // Bring the backend back either way - with the imported or the untouched database
+        let started = start_backend_after_maintenance(app).await;
+        result.safety_copy = Some(swapped?.to_string_lossy().to_string());
+        result.database_imported = Some(database);
+        result.backend_restarted = started.is_ok();
+    }
+    result.logs_moved = move_logs(&location)?;
+
+    record_outcome(settings, OUTCOME_MIGRATED, Some(location.dir.clone()))?;
+    append_app_log(&format!(
+        "Migrated data from an older install in {} (database: {}, logs: {})",
+        location.dir,
+        result.database_imported.is_some(),
+        result.logs_moved.len()
+    ));
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn get_legacy_data(settings: State<'_, SharedSettings>) -> Result<LegacyScan, String> {
+    let config = current_settings(&settings);
+    let locations = tauri::async_runtime::spawn_blocking(scan)
+        .await
+        .map_err(|e| format!("Legacy data scan failed: {}", e))?;
+    Ok(LegacyScan {
+        outcome: config.legacy_data_migration,
+        source: config.legacy_data_source,
+        locations,
+    })
+}
+
+// Import the database and logs found in `source`, one of the folders get_legacy_data reports
+#[tauri::command]
+pub async fn migrate_legacy_data(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+    source: String,
+) -> Result<LegacyMigrationResult, String> {
+    lock::ensure_unlocked()?;
+    let result = migrate(&app, &settings, &source).await;
+    audited("migrate_legacy_data", &source, result)
+}
+
+#[tauri::command]
+pub fn dismiss_legacy_data(settings: State<SharedSettings>) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    record_outcome(&settings, OUTCOME_DISMISSED, None)
+}
@@ -5,15 +5,90 @@ use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{Menu, MenuItem, PredefinedMenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    Manager, State,
+    Emitter, Manager, State,
 };
 use tauri_plugin_shell::process::CommandChild;
-use tauri_plugin_shell::ShellExt;
+
+mod adms;
+mod alerts;
+mod archive;
+mod audit;
+mod backend_auth;
+mod backup;
+mod benchmark;
+mod binding;
+mod bulk_sync;
+mod bundle;
+mod cli;
+mod clipboard;
+mod confirmation;
+mod crash;
+mod database;
+mod device_manager;
+mod devices;
+mod diagnostics;
+mod drift;
+mod duplicates;
+mod email;
+mod encryption;
+mod event_bridge;
+mod export;
+mod firewall;
+mod groups;
+mod hr_sync;
+mod ics;
+mod idle;
+mod ingest;
+mod ipc;
+mod ldap;
+mod legacy;
+mod local_api;
+mod lock;
+mod migrations;
+mod monitor;
+mod mqtt;
+mod offline_queue;
+mod path_policy;
+mod photos;
+mod poller;
+mod printing;
+mod profiles;
+mod protect;
+mod realtime;
+mod reconcile;
+mod redact;
+mod registry;
+mod relocate;
+mod remote;
+mod report;
+mod s3;
+mod secrets;
+mod service;
+mod settings;
+mod sheets;
+mod sidecar;
+mod sidecar_update;
+mod simulator;
+mod stats;
+mod storage;
+mod sync_status;
+mod timezones;
+mod tls;
+mod trace;
+mod tray;
+mod updater;
+mod upstream;
+mod user_import;
+mod watcher;
+mod webhooks;
+mod wipe;
+mod zk;
 
 #[cfg(target_os = "windows")]
 use std::io::Read;
@@ -24,6 +99,7 @@ use std::os::windows::fs::OpenOptionsExt;
 type BackendProcess = Arc<Mutex<Option<CommandChild>>>;
 type ProcessStatus = Arc<Mutex<HashMap<String, String>>>;
 type MinimizeToTraySetting = Arc<Mutex<bool>>;
+type BackendPort = Arc<Mutex<u16>>;
 
 #[derive(Debug, Clone, serde::Serialize)]
 struct LogEntry {
@@ -36,8 +112,13 @@ struct LogEntry {
 type BackendLogs = Arc<Mutex<Vec<LogEntry>>>;
 
 const BACKEND_STARTING_KEY: &str = "backend_starting";
+const DEFAULT_BACKEND_PORT: u16 = 57575;
+// Ports tried in order when the default one is taken by another program
+const BACKEND_FALLBACK_PORTS: std::ops::RangeInclusive<u16> = 57576..=57585;
 
-fn resolve_app_data_dir() -> PathBuf {
+// Data folder shared by all profiles (profiles.json lives here)
+#[allow(clippy::redundant_closure)]
+fn resolve_base_data_dir() -> PathBuf {
     let mut base_dir = data_local_dir().unwrap_or_else(|| env::temp_dir());
     base_dir.push("ZKTeco");
 
@@ -55,8 +136,13 @@ fn resolve_app_data_dir() -> PathBuf {
     }
 }
 
+// Data folder of the active profile
+fn resolve_app_data_dir() -> PathBuf {
+    profiles::profile_dir(resolve_base_data_dir())
+}
+
 fn resolve_backend_db_path() -> PathBuf {
-    let mut db_path = resolve_app_data_dir();
+    let mut db_path = relocate::database_dir().unwrap_or_else(resolve_app_data_dir);
     db_path.push("zkteco_app.db");
     db_path
 }
@@ -96,6 +182,27 @@ fn set_minimize_to_tray(
     }
 }
 
+// One of a capped number of concurrent connections on the shell's own listeners (ADMS, local
+// API); released on drop
+struct ConnectionSlot(&'static AtomicUsize);
+
+impl ConnectionSlot {
+    fn try_acquire(active: &'static AtomicUsize, limit: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < limit).then_some(count + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(active))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
 struct BackendStartupGuard {
     status: ProcessStatus,
     acquired: bool,
@@ -147,18 +254,94 @@ impl Drop for BackendStartupGuard {
     }
 }
 
+fn backend_base_url(port: u16) -> String {
+    format!("{}://127.0.0.1:{}", tls::scheme(), port)
+}
+
+fn current_backend_port(backend_port: &BackendPort) -> u16 {
+    backend_port
+        .lock()
+        .map(|guard| *guard)
+        .unwrap_or(DEFAULT_BACKEND_PORT)
+}
+
+// Record the port the sidecar was launched on and tell the frontend about it
+fn set_backend_port(app: &tauri::AppHandle, backend_port: &BackendPort, port: u16) {
+    let changed = match backend_port.lock() {
+        Ok(mut guard) => {
+            let changed = *guard != port;
+            *guard = port;
+            changed
+        }
+        Err(_) => false,
+    };
+
+    if changed {
+        println!("Backend port changed to {}", port);
+        append_app_log(&format!("Backend port set to {}", port));
+        if let Err(err) = app.emit("backend-port-changed", port) {
+            eprintln!("Failed to emit backend-port-changed event: {}", err);
+        }
+    }
+}
+
+fn is_port_available(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+// Flask/werkzeug bind errors differ per platform (errno 98 on Linux, 48 on macOS, 10048 on Windows)
+fn is_port_conflict_message(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    lower.contains("address already in use")
+        || lower.contains("is in use by another program")
+        || lower.contains("only one usage of each socket address")
+        || lower.contains("winerror 10048")
+}
+
+// Ports to try for the sidecar: the last used one first, then the default, then the fallback range
+fn backend_port_candidates(current: u16) -> Vec<u16> {
+    let mut candidates = vec![current];
+    for port in std::iter::once(DEFAULT_BACKEND_PORT).chain(BACKEND_FALLBACK_PORTS) {
+        if !candidates.contains(&port) {
+            candidates.push(port);
+        }
+    }
+    candidates
+}
+
+// Resolved health check target (port from the running backend, path/token from shell settings)
+#[derive(Debug, Clone)]
+struct HealthEndpoint {
+    url: String,
+    token: Option<String>,
+}
+
+fn health_endpoint(port: u16, settings: &settings::SharedSettings) -> HealthEndpoint {
+    let settings = settings::current_settings(settings);
+    let path = settings.backend_health_path.trim();
+    let path = if path.is_empty() {
+        settings::DEFAULT_HEALTH_PATH.to_string()
+    } else if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+
+    HealthEndpoint {
+        url: format!("{}{}", backend_base_url(port), path),
+        token: secrets::backend_health_token().filter(|token| !token.trim().is_empty()),
+    }
+}
+
 // Helper function to check if backend is responding via HTTP
-async fn check_backend_health() -> bool {
-    match reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-    {
+async fn check_backend_health(endpoint: &HealthEndpoint) -> bool {
+    match backend_auth::backend_client(Duration::from_secs(5)) {
         Ok(client) => {
-            match client
-                .get("http://127.0.0.1:57575/service/status")
-                .send()
-                .await
-            {
+            let mut request = client.get(&endpoint.url);
+            if let Some(token) = &endpoint.token {
+                request = request.bearer_auth(token);
+            }
+            match request.send().await {
                 Ok(response) => {
                     let is_healthy = response.status().is_success();
                     println!(
@@ -174,14 +357,17 @@ async fn check_backend_health() -> bool {
             }
         }
         Err(e) => {
-            println!("Failed to create HTTP client: {}", e);
+            println!("{}", e);
             false
         }
     }
 }
 
 // Helper function to detect existing backend process
-async fn detect_existing_backend(backend_process: &BackendProcess) -> bool {
+async fn detect_existing_backend(
+    backend_process: &BackendProcess,
+    endpoint: &HealthEndpoint,
+) -> bool {
     // First check if we have a process tracked
     let has_tracked_process = {
         match backend_process.lock() {
@@ -198,7 +384,7 @@ async fn detect_existing_backend(backend_process: &BackendProcess) -> bool {
     }
 
     // Then check HTTP health
-    let is_http_healthy = check_backend_health().await;
+    let is_http_healthy = check_backend_health(endpoint).await;
 
     println!(
         "Backend detection - no tracked process, HTTP healthy: {}",
@@ -212,7 +398,10 @@ async fn detect_existing_backend(backend_process: &BackendProcess) -> bool {
 }
 
 // Helper function to wait for backend shutdown (called after kill signal sent)
-async fn wait_for_backend_shutdown(timeout_secs: u64) -> Result<(), String> {
+async fn wait_for_backend_shutdown(
+    timeout_secs: u64,
+    endpoint: &HealthEndpoint,
+) -> Result<(), String> {
     use std::time::Instant;
 
     println!("Waiting for backend graceful shutdown...");
@@ -237,7 +426,7 @@ async fn wait_for_backend_shutdown(timeout_secs: u64) -> Result<(), String> {
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         // Check if backend is still responding
-        if !check_backend_health().await {
+        if !check_backend_health(endpoint).await {
             println!("Backend has stopped responding - shutdown successful");
             append_app_log("Backend shutdown verified - no longer responding");
             return Ok(());
@@ -310,6 +499,8 @@ async fn start_backend(
     backend_process: State<'_, BackendProcess>,
     process_status: State<'_, ProcessStatus>,
     backend_logs: State<'_, BackendLogs>,
+    backend_port: State<'_, BackendPort>,
+    shell_settings: State<'_, settings::SharedSettings>,
 ) -> Result<String, String> {
     println!("Start backend command called");
     append_app_log("start_backend command invoked");
@@ -332,8 +523,17 @@ async fn start_backend(
 
     let _startup_guard = startup_guard;
 
+    if let Some(attached) = service::attach(&app, &backend_port).await {
+        append_app_log(&format!(
+            "start_backend using the backend service: {:?}",
+            attached
+        ));
+        return attached;
+    }
+
     // Check for existing backend (comprehensive detection)
-    if detect_existing_backend(&backend_process).await {
+    let endpoint = health_endpoint(current_backend_port(&backend_port), &shell_settings);
+    if detect_existing_backend(&backend_process, &endpoint).await {
         println!("Backend already exists - skipping startup");
         append_app_log("start_backend skipped - backend already running");
         return Ok("Backend is already running".to_string());
@@ -341,6 +541,113 @@ async fn start_backend(
 
     println!("No existing backend detected - proceeding with startup");
 
+    launch_backend(
+        &app,
+        &backend_process,
+        &process_status,
+        &backend_logs,
+        &backend_port,
+    )
+    .await
+}
+
+// Spawn the sidecar on the first free port, moving on when the backend reports a port conflict.
+// Used by start_backend and by the launch at app startup, which hold the startup guard.
+async fn launch_backend(
+    app: &tauri::AppHandle,
+    backend_process: &BackendProcess,
+    process_status: &ProcessStatus,
+    backend_logs: &BackendLogs,
+    backend_port: &BackendPort,
+) -> Result<String, String> {
+    let mut last_error = "No free port available for the backend".to_string();
+    for port in backend_port_candidates(current_backend_port(backend_port)) {
+        if !is_port_available(port) {
+            append_app_log(&format!(
+                "start_backend skipping port {} - already in use",
+                port
+            ));
+            last_error = format!("Port {} is already in use", port);
+            continue;
+        }
+
+        set_backend_port(app, backend_port, port);
+        match spawn_backend_on_port(app, port, backend_process, process_status, backend_logs).await
+        {
+            Err(err) if is_port_conflict_message(&err) => {
+                append_app_log(&format!(
+                    "start_backend port {} conflict - trying next fallback port",
+                    port
+                ));
+                if let Ok(mut process_guard) = backend_process.lock() {
+                    if let Some(child) = process_guard.take() {
+                        let _ = child.kill();
+                    }
+                }
+                last_error = err;
+            }
+            result => return result,
+        }
+    }
+
+    append_app_log(&format!(
+        "start_backend exhausted fallback ports: {}",
+        last_error
+    ));
+    Err(last_error)
+}
+
+// Environment the backend sidecar runs with; also used by the headless CLI
+fn backend_env(
+    port: u16,
+    settings: &settings::ShellSettings,
+) -> Result<Vec<(&'static str, String)>, String> {
+    let mut env = vec![
+        (
+            "SECRET_KEY",
+            "b7ad3ec8a8262756372175c8d4f83cdce82d9bc85878ff0b4258ca91a3a1e641".to_string(),
+        ),
+        ("LOG_LEVEL", "INFO".to_string()),
+        ("FLASK_DEBUG", "0".to_string()),
+        ("FLASK_ENV", "production".to_string()),
+        (
+            "ZKTECO_DB_PATH",
+            resolve_backend_db_path().to_string_lossy().to_string(),
+        ),
+        ("PORT", port.to_string()),
+        (
+            backend_auth::TOKEN_ENV,
+            backend_auth::session_token().to_string(),
+        ),
+    ];
+    if let Some(key) = encryption::backend_key() {
+        env.push(("ZKTECO_DB_KEY", key));
+    }
+    if settings.backend_localhost_only {
+        env.push(("HOST", binding::LOCALHOST.to_string()));
+    }
+    let tls_files = if settings.backend_tls_enabled {
+        Some(tls::prepare_certificate()?)
+    } else {
+        None
+    };
+    if let Some(files) = &tls_files {
+        env.push((tls::CERT_ENV, files.cert_path.to_string_lossy().to_string()));
+        env.push((tls::KEY_ENV, files.key_path.to_string_lossy().to_string()));
+    }
+    tls::set_active(tls_files.as_ref());
+    Ok(env)
+}
+
+// Spawn the sidecar bound to `port` and verify it survives the first seconds of startup
+#[allow(clippy::useless_format)]
+async fn spawn_backend_on_port(
+    app: &tauri::AppHandle,
+    port: u16,
+    backend_process: &BackendProcess,
+    process_status: &ProcessStatus,
+    backend_logs: &BackendLogs,
+) -> Result<String, String> {
     let db_path = resolve_backend_db_path();
     let db_path_str = db_path.to_string_lossy().to_string();
     if let Some(parent) = db_path.parent() {
@@ -356,9 +663,10 @@ async fn start_backend(
         }
     }
     println!("Using backend database at: {}", db_path_str);
+    migrations::prepare_database().await;
     append_app_log(&format!(
-        "start_backend proceeding - DB path {}",
-        db_path_str
+        "start_backend proceeding - DB path {}, port {}",
+        db_path_str, port
     ));
 
     // Clear any previous status
@@ -366,21 +674,25 @@ async fn start_backend(
         status_guard.remove("backend_status");
     }
 
+    if let Err(err) = sidecar::verify_sidecar(app).await {
+        if let Ok(mut status_guard) = process_status.lock() {
+            status_guard.insert(
+                "backend_status".to_string(),
+                format!("Failed to start backend: {}", err),
+            );
+        }
+        return Err(err);
+    }
+
     // Start the backend sidecar
-    match app.shell().sidecar("zkteco-backend") {
+    match sidecar::backend_command(app) {
         Ok(sidecar_command) => {
-            let sidecar_with_env = sidecar_command
-                .env(
-                    "SECRET_KEY",
-                    "b7ad3ec8a8262756372175c8d4f83cdce82d9bc85878ff0b4258ca91a3a1e641",
-                )
-                .env("LOG_LEVEL", "INFO")
-                .env("FLASK_DEBUG", "0")
-                .env("FLASK_ENV", "production")
-                .env("ZKTECO_DB_PATH", &db_path_str);
+            let settings = settings::current_settings(&app.state::<settings::SharedSettings>());
+            let sidecar_with_env = sidecar_command.envs(backend_env(port, &settings)?);
             match sidecar_with_env.spawn() {
                 Ok((mut rx, child)) => {
                     println!("Backend sidecar started successfully");
+                    sidecar::record_pid(child.pid(), port);
                     append_app_log("start_backend succeeded in spawning backend sidecar");
 
                     // Store the child process
@@ -398,9 +710,9 @@ async fn start_backend(
                         }
                     }
 
-                    let status_for_monitor = process_status.inner().clone();
-                    let backend_for_monitor = backend_process.inner().clone();
-                    let logs_for_monitor = backend_logs.inner().clone();
+                    let status_for_monitor = process_status.clone();
+                    let backend_for_monitor = backend_process.clone();
+                    let logs_for_monitor = backend_logs.clone();
 
                     // Log backend start attempt
                     if let Ok(mut logs) = logs_for_monitor.lock() {
@@ -473,6 +785,7 @@ async fn start_backend(
                                     // Check for critical errors
                                     if stderr_str.contains("ModuleNotFoundError")
                                         || stderr_str.contains("Failed to execute script")
+                                        || is_port_conflict_message(&stderr_str)
                                     {
                                         if let Ok(mut status_guard) = status_for_monitor.lock() {
                                             status_guard.insert(
@@ -518,9 +831,17 @@ async fn start_backend(
                                         });
                                     }
 
-                                    // Mark as startup failure if early termination
+                                    // Mark as startup failure if early termination, keeping the
+                                    // reason stderr gave (launch_backend looks for port conflicts)
                                     if let Ok(mut status_guard) = status_for_monitor.lock() {
-                                        status_guard.insert("backend_status".to_string(), format!("Backend failed to start - terminated with code: {:?}", payload.code));
+                                        let mut status = format!(
+                                            "Backend failed to start - terminated with code: {:?}",
+                                            payload.code
+                                        );
+                                        if let Some(reason) = status_guard.get("backend_status") {
+                                            status = format!("{} ({})", status, reason);
+                                        }
+                                        status_guard.insert("backend_status".to_string(), status);
                                     }
 
                                     // Clear the process from our tracking
@@ -587,9 +908,8 @@ async fn start_backend(
     }
 }
 
-#[tauri::command]
-fn stop_backend(backend_process: State<BackendProcess>) -> Result<String, String> {
-    append_app_log("stop_backend command invoked");
+// Shared by stop_backend, restart_backend and database maintenance, which audit on their own
+fn kill_backend(backend_process: &BackendProcess) -> Result<String, String> {
     match backend_process.lock() {
         Ok(mut process_guard) => {
             if let Some(child) = process_guard.take() {
@@ -608,6 +928,9 @@ fn stop_backend(backend_process: State<BackendProcess>) -> Result<String, String
                         Err(format!("Failed to stop backend process: {}", e))
                     }
                 }
+            } else if service::attached().is_some() {
+                // Attached to the backend service rather than a sidecar of our own
+                service::stop_service()
             } else {
                 append_app_log("stop_backend found no running backend process");
                 Err("No backend process is running".to_string())
@@ -624,22 +947,40 @@ fn stop_backend(backend_process: State<BackendProcess>) -> Result<String, String
     }
 }
 
+#[tauri::command]
+fn stop_backend(backend_process: State<BackendProcess>) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    append_app_log("stop_backend command invoked");
+    audit::audited("stop_backend", "backend", kill_backend(&backend_process))
+}
+
 #[tauri::command]
 async fn restart_backend(
     app: tauri::AppHandle,
     backend_process: State<'_, BackendProcess>,
     process_status: State<'_, ProcessStatus>,
     backend_logs: State<'_, BackendLogs>,
+    backend_port: State<'_, BackendPort>,
+    shell_settings: State<'_, settings::SharedSettings>,
 ) -> Result<String, String> {
+    lock::ensure_unlocked()?;
     append_app_log("restart_backend command invoked");
     // Stop first
-    let _ = stop_backend(backend_process.clone());
+    let _ = kill_backend(&backend_process);
 
     // Wait a moment for cleanup
     tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
 
     // Start again
-    let result = start_backend(app, backend_process, process_status, backend_logs).await;
+    let result = start_backend(
+        app,
+        backend_process,
+        process_status,
+        backend_logs,
+        backend_port,
+        shell_settings,
+    )
+    .await;
     if let Err(ref err) = result {
         append_app_log(&format!(
             "restart_backend failed to restart backend: {}",
@@ -648,19 +989,33 @@ async fn restart_backend(
     } else {
         append_app_log("restart_backend completed successfully");
     }
-    result
+    audit::audited("restart_backend", "backend", result)
 }
 
 #[tauri::command]
-async fn is_backend_running(backend_process: State<'_, BackendProcess>) -> Result<bool, String> {
-    let is_running = detect_existing_backend(&backend_process).await;
+async fn is_backend_running(
+    backend_process: State<'_, BackendProcess>,
+    backend_port: State<'_, BackendPort>,
+    shell_settings: State<'_, settings::SharedSettings>,
+) -> Result<bool, String> {
+    let endpoint = health_endpoint(current_backend_port(&backend_port), &shell_settings);
+    let is_running = detect_existing_backend(&backend_process, &endpoint).await;
     println!("Backend running check: {}", is_running);
     Ok(is_running)
 }
 
 #[tauri::command]
-async fn check_backend_http_health() -> bool {
-    check_backend_health().await
+async fn check_backend_http_health(
+    backend_port: State<'_, BackendPort>,
+    shell_settings: State<'_, settings::SharedSettings>,
+) -> Result<bool, String> {
+    let endpoint = health_endpoint(current_backend_port(&backend_port), &shell_settings);
+    Ok(check_backend_health(&endpoint).await)
+}
+
+#[tauri::command]
+fn get_backend_port(backend_port: State<BackendPort>) -> u16 {
+    current_backend_port(&backend_port)
 }
 
 #[tauri::command]
@@ -673,13 +1028,15 @@ fn get_backend_logs(backend_logs: State<BackendLogs>) -> Result<Vec<LogEntry>, S
 
 #[tauri::command]
 fn clear_backend_logs(backend_logs: State<BackendLogs>) -> Result<String, String> {
-    match backend_logs.lock() {
+    lock::ensure_unlocked()?;
+    let result = match backend_logs.lock() {
         Ok(mut logs) => {
             logs.clear();
             Ok("Backend logs cleared".to_string())
         }
         Err(e) => Err(format!("Failed to clear backend logs: {}", e)),
-    }
+    };
+    audit::audited("clear_backend_logs", "backend logs", result)
 }
 
 #[tauri::command]
@@ -858,47 +1215,109 @@ fn parse_log_line(line: &str, line_number: usize) -> Option<FileLogEntry> {
 
 #[tauri::command]
 fn clear_log_file() -> Result<String, String> {
+    lock::ensure_unlocked()?;
     let log_path = get_log_file_path()?;
 
     if !log_path.exists() {
         return Ok("Log file does not exist".to_string());
     }
 
-    fs::write(&log_path, "").map_err(|e| format!("Failed to clear log file: {}", e))?;
-
-    Ok("Log file cleared successfully".to_string())
+    let result = fs::write(&log_path, "")
+        .map(|_| "Log file cleared successfully".to_string())
+        .map_err(|e| format!("Failed to clear log file: {}", e));
+    audit::audited("clear_log_file", &log_path.to_string_lossy(), result)
 }
 
+// Redacted unless unredacted is set with a confirmation token for
+// redact::UNREDACTED_ACTION on the destination ("log file" when picked in the dialog).
+// Without a destination the user picks one in a save dialog; returns None when cancelled.
 #[tauri::command]
-fn export_log_file(destination: String) -> Result<String, String> {
+async fn export_log_file(
+    app: tauri::AppHandle,
+    destination: Option<String>,
+    unredacted: Option<bool>,
+    confirm_token: Option<String>,
+    tokens: State<'_, confirmation::ConfirmationTokens>,
+) -> Result<Option<String>, String> {
+    lock::ensure_unlocked()?;
+    let redacted = !unredacted.unwrap_or(false);
+    if !redacted {
+        let target = destination.as_deref().unwrap_or("log file");
+        redact::confirm_unredacted(&tokens, confirm_token.as_deref(), target)?;
+    }
     let log_path = get_log_file_path()?;
 
     if !log_path.exists() {
         return Err("Log file does not exist".to_string());
     }
 
-    let dest_path = PathBuf::from(destination);
-
-    fs::copy(&log_path, &dest_path).map_err(|e| format!("Failed to export log file: {}", e))?;
+    let Some(dest_path) = export::resolve_save_path(
+        &app,
+        destination,
+        "Log Files",
+        "log",
+        format!("zkteco-logs-{}.log", Local::now().format("%Y-%m-%d")),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
 
-    Ok(format!("Log file exported to: {}", dest_path.display()))
+    let result = if redacted {
+        fs::read(&log_path).and_then(|content| {
+            fs::write(
+                &dest_path,
+                redact::redact(&String::from_utf8_lossy(&content)),
+            )
+        })
+    } else {
+        fs::copy(&log_path, &dest_path).map(drop)
+    }
+    .map(|_| Some(format!("Log file exported to: {}", dest_path.display())))
+    .map_err(|e| format!("Failed to export log file: {}", e));
+    audit::audited("export_log_file", &dest_path.to_string_lossy(), result)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
+#[allow(clippy::collapsible_match)]
 pub fn run() {
+    // Started by the service manager as the backend supervisor, before any per-user state
+    if let Some(config) = service::service_args() {
+        std::process::exit(service::run_service(config));
+    }
+    // Everything below reads the active profile's data folder
+    profiles::load_active_profile();
+    crash::install_crash_handler();
+    if let Some(args) = cli::cli_args() {
+        std::process::exit(cli::run_cli(args));
+    }
     append_app_log("Tauri application run() invoked");
     let backend_process: BackendProcess = Arc::new(Mutex::new(None));
     let process_status: ProcessStatus = Arc::new(Mutex::new(HashMap::new()));
     let backend_logs: BackendLogs = Arc::new(Mutex::new(Vec::new()));
     let minimize_to_tray_setting: MinimizeToTraySetting = Arc::new(Mutex::new(false));
+    let backend_port: BackendPort = Arc::new(Mutex::new(DEFAULT_BACKEND_PORT));
+    let shell_settings: settings::SharedSettings = Arc::new(Mutex::new(settings::load_settings()));
+    settings::apply_runtime_settings(&settings::current_settings(&shell_settings));
+    let sync_tracker: sync_status::SyncTracker =
+        Arc::new(Mutex::new(sync_status::load_sync_state()));
+    let monitor_bus: monitor::MonitorBus = monitor::create_monitor_bus();
+    let realtime_sessions: realtime::RealtimeSessions = Arc::new(Mutex::new(HashMap::new()));
+    let device_registry: registry::DeviceRegistry = Arc::new(Mutex::new(registry::load_registry()));
+    let device_manager: device_manager::DeviceManager = Arc::new(Mutex::new(HashMap::new()));
+    let adms_status: adms::AdmsStatus = Arc::new(Mutex::new(adms::AdmsStats::default()));
+    let poller_status: poller::PollerStatus = Arc::new(Mutex::new(HashMap::new()));
 
     let backend_process_for_run = backend_process.clone();
     let minimize_setting_for_run = minimize_to_tray_setting.clone();
+    let backend_port_for_run = backend_port.clone();
+    let shell_settings_for_run = shell_settings.clone();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
+        .plugin(updater::plugin())
         .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
             // When a second instance is detected, show and focus the existing window
             append_app_log("Second instance detected - showing existing window");
@@ -913,18 +1332,42 @@ pub fn run() {
         .manage(process_status.clone())
         .manage(backend_logs.clone())
         .manage(minimize_to_tray_setting.clone())
+        .manage(backend_port.clone())
+        .manage(shell_settings.clone())
+        .manage(sync_tracker.clone())
+        .manage(monitor_bus.clone())
+        .manage(realtime_sessions)
+        .manage(device_registry.clone())
+        .manage(confirmation::ConfirmationTokens::default())
+        .manage(device_manager.clone())
+        .manage(adms_status.clone())
+        .manage(poller_status.clone())
+        .manage(simulator::SimulatorHandle::default())
         .setup(move |app| {
             append_app_log("Tauri setup hook executing");
+            drift::init_drift_alerts(app.handle().clone());
             // Create system tray
+            let status_i =
+                MenuItem::with_id(app, "status", "Backend: starting...", false, None::<&str>)?;
+            let restart_i =
+                MenuItem::with_id(app, "restart", "Restart Backend", false, None::<&str>)?;
             let show_i = MenuItem::with_id(app, "show", "Show App", true, None::<&str>)?;
             let hide_i = MenuItem::with_id(app, "hide", "Hide to Tray", true, None::<&str>)?;
             let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show_i, &hide_i, &quit_i])?;
+            let separator = PredefinedMenuItem::separator(app)?;
+            let menu = Menu::with_items(
+                app,
+                &[&status_i, &restart_i, &separator, &show_i, &hide_i, &quit_i],
+            )?;
 
             let backend_process_for_tray = backend_process.clone();
+            let backend_port_for_tray = backend_port.clone();
+            let shell_settings_for_tray = shell_settings.clone();
             let minimize_setting_for_window = minimize_to_tray_setting.clone();
             let backend_process_for_window = backend_process.clone();
-            let _tray = TrayIconBuilder::new()
+            let backend_port_for_window = backend_port.clone();
+            let shell_settings_for_window = shell_settings.clone();
+            let _tray = TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
                 .menu(&menu)
                 .show_menu_on_left_click(false)
@@ -942,9 +1385,31 @@ pub fn run() {
                             let _ = window.hide();
                         }
                     }
+                    "restart" => {
+                        append_app_log("Backend restart requested from tray");
+                        let app_handle = app.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(err) = restart_backend(
+                                app_handle.clone(),
+                                app_handle.state(),
+                                app_handle.state(),
+                                app_handle.state(),
+                                app_handle.state(),
+                                app_handle.state(),
+                            )
+                            .await
+                            {
+                                eprintln!("Tray backend restart failed: {}", err);
+                            }
+                        });
+                    }
                     "quit" => {
                         // Cleanup backend before exiting with graceful shutdown
                         let backend_for_quit = backend_process_for_tray.clone();
+                        let endpoint_for_quit = health_endpoint(
+                            current_backend_port(&backend_port_for_tray),
+                            &shell_settings_for_tray,
+                        );
                         let app_handle = app.clone();
 
                         tauri::async_runtime::spawn(async move {
@@ -974,7 +1439,7 @@ pub fn run() {
 
                             // Now wait for graceful shutdown
                             if killed {
-                                match wait_for_backend_shutdown(5).await {
+                                match wait_for_backend_shutdown(5, &endpoint_for_quit).await {
                                     Ok(()) => {
                                         println!("Backend gracefully terminated on app quit");
                                         append_app_log("Backend gracefully terminated on app quit");
@@ -1016,6 +1481,9 @@ pub fn run() {
 
             // Check for existing backend first
             let backend_process_for_setup = backend_process.clone();
+            let backend_port_for_setup = backend_port.clone();
+            let shell_settings_for_setup = shell_settings.clone();
+            let process_status_for_setup = process_status.clone();
             let app_for_startup = app.handle().clone();
 
             tauri::async_runtime::spawn(async move {
@@ -1023,7 +1491,15 @@ pub fn run() {
                 tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
 
                 // Check if backend already exists
-                if detect_existing_backend(&backend_process_for_setup).await {
+                tls::trust_existing(
+                    settings::current_settings(&shell_settings_for_setup).backend_tls_enabled,
+                );
+                let endpoint = health_endpoint(
+                    current_backend_port(&backend_port_for_setup),
+                    &shell_settings_for_setup,
+                );
+                if detect_existing_backend(&backend_process_for_setup, &endpoint).await
+                {
                     println!("Backend already running - skipping startup backend launch");
                     append_app_log("Startup check found existing backend - skipping auto launch");
                     return;
@@ -1040,11 +1516,95 @@ pub fn run() {
                 startup_backend_sidecar(
                     app_for_startup,
                     backend_process_for_setup,
-                    process_status.clone(),
+                    process_status_for_setup,
+                    backend_port_for_setup,
                 )
                 .await;
             });
 
+            secrets::migrate_plaintext_credentials(&device_registry, &shell_settings);
+
+            // Backend/device state monitor and the tray updater that follows it
+            tray::spawn_tray_updater(
+                app.handle().clone(),
+                &monitor_bus,
+                tray::TrayStatusItems {
+                    status: status_i,
+                    restart: restart_i,
+                },
+            );
+            device_manager::spawn_device_manager(
+                app.handle().clone(),
+                device_manager.clone(),
+                device_registry.clone(),
+                monitor_bus.clone(),
+                backend_port.clone(),
+            );
+            monitor::spawn_state_monitor(
+                app.handle().clone(),
+                monitor_bus.clone(),
+                process_status.clone(),
+                backend_port.clone(),
+                shell_settings.clone(),
+                device_registry.clone(),
+            );
+
+            adms::spawn_adms_server(
+                shell_settings.clone(),
+                backend_port.clone(),
+                adms_status.clone(),
+            );
+            devices::spawn_time_sync_job(device_registry.clone(), shell_settings.clone());
+            sheets::spawn_sheets_scheduler(shell_settings.clone());
+            email::spawn_email_scheduler(shell_settings.clone());
+            hr_sync::spawn_hr_sync();
+            remote::spawn_remote_export_scheduler(shell_settings.clone());
+            backup::spawn_backup_scheduler(app.handle().clone(), shell_settings.clone());
+            updater::spawn_update_checker(app.handle().clone(), shell_settings.clone());
+            legacy::spawn_legacy_scan(app.handle().clone(), shell_settings.clone());
+            crash::spawn_crash_check(app.handle().clone());
+            archive::spawn_archive_job(shell_settings.clone());
+            photos::spawn_photo_maintenance(shell_settings.clone());
+            upstream::spawn_upstream_sync(app.handle().clone(), shell_settings.clone());
+            offline_queue::spawn_queue_drain(shell_settings.clone(), backend_port.clone());
+            mqtt::spawn_mqtt_publisher(shell_settings.clone(), &monitor_bus);
+            webhooks::spawn_webhook_dispatcher(&monitor_bus);
+            alerts::spawn_alert_dispatcher(shell_settings.clone(), &monitor_bus);
+            local_api::spawn_local_api(
+                shell_settings.clone(),
+                device_manager.clone(),
+                &monitor_bus,
+            );
+            ingest::spawn_ingest_watcher(shell_settings.clone());
+            ipc::spawn_ipc_server(
+                shell_settings.clone(),
+                device_manager.clone(),
+                &monitor_bus,
+            );
+            event_bridge::spawn_event_bridge(
+                app.handle().clone(),
+                backend_port.clone(),
+                monitor_bus.clone(),
+            );
+            poller::spawn_attendance_poller(
+                device_registry.clone(),
+                shell_settings.clone(),
+                backend_port.clone(),
+                poller_status.clone(),
+                monitor_bus.clone(),
+            );
+            watcher::spawn_data_watcher(app.handle().clone());
+            lock::lock_at_startup(app.handle());
+            idle::spawn_idle_watch(app.handle().clone(), shell_settings.clone());
+
+            // Track last successful device pull / upstream push
+            sync_status::spawn_sync_monitor(
+                app.handle().clone(),
+                sync_tracker.clone(),
+                shell_settings.clone(),
+                backend_port.clone(),
+            );
+
             // Set up window close behavior - minimize to tray instead of closing
             let main_window = app.get_webview_window("main");
             if let Some(window) = main_window {
@@ -1069,6 +1629,10 @@ pub fn run() {
 
                             // Spawn async task for graceful shutdown
                             let backend_for_close = backend_process_for_window.clone();
+                            let endpoint_for_close = health_endpoint(
+                                current_backend_port(&backend_port_for_window),
+                                &shell_settings_for_window,
+                            );
                             tauri::async_runtime::spawn(async move {
                                 // Kill process (in sync block to avoid holding lock across await)
                                 let killed = {
@@ -1094,7 +1658,7 @@ pub fn run() {
 
                                 // Now wait for graceful shutdown
                                 if killed {
-                                    match wait_for_backend_shutdown(5).await {
+                                    match wait_for_backend_shutdown(5, &endpoint_for_close).await {
                                         Ok(()) => {
                                             println!("Backend gracefully terminated on window close");
                                             append_app_log(
@@ -1132,6 +1696,81 @@ pub fn run() {
             restart_backend,
             is_backend_running,
             check_backend_http_health,
+            get_backend_port,
+            backend_auth::proxy_backend_request,
+            event_bridge::get_backend_events_status,
+            mqtt::get_mqtt_status,
+            webhooks::list_webhooks,
+            webhooks::register_webhook,
+            webhooks::set_webhook_enabled,
+            webhooks::remove_webhook,
+            webhooks::test_webhook,
+            webhooks::get_webhook_deliveries,
+            local_api::get_local_api_status,
+            local_api::get_local_api_token,
+            local_api::rotate_local_api_token,
+            ipc::get_ipc_status,
+            ingest::list_ingest_profiles,
+            ingest::save_ingest_profile,
+            ingest::delete_ingest_profile,
+            ingest::get_ingest_reports,
+            ingest::run_ingest_now,
+            ldap::preview_ldap_import,
+            ldap::run_ldap_import,
+            sheets::connect_google_sheets,
+            sheets::disconnect_google_sheets,
+            sheets::push_to_google_sheets,
+            sheets::get_google_sheets_status,
+            alerts::list_alert_channels,
+            alerts::add_alert_channel,
+            alerts::update_alert_channel,
+            alerts::remove_alert_channel,
+            alerts::test_alert_channel,
+            email::send_test_email,
+            email::email_attendance_report,
+            email::get_email_status,
+            printing::list_printers,
+            printing::print_check_in_sheet,
+            printing::print_attendance_slip,
+            clipboard::copy_to_clipboard,
+            hr_sync::list_hr_adapters,
+            hr_sync::save_hr_adapter,
+            hr_sync::remove_hr_adapter,
+            hr_sync::preview_hr_adapter,
+            hr_sync::run_hr_adapter,
+            hr_sync::get_hr_adapter_status,
+            remote::scan_remote_host_key,
+            remote::test_remote_destination,
+            remote::upload_to_remote,
+            remote::get_remote_status,
+            s3::test_s3_connection,
+            s3::upload_to_s3,
+            s3::get_s3_uploads,
+            backend_auth::get_backend_api_spec,
+            updater::check_for_updates,
+            updater::download_update,
+            updater::install_update,
+            updater::cancel_scheduled_update,
+            updater::get_update_status,
+            sidecar_update::check_sidecar_update,
+            sidecar_update::install_sidecar_update,
+            sidecar_update::rollback_backend,
+            sidecar_update::get_sidecar_versions,
+            service::install_windows_service,
+            service::remove_windows_service,
+            service::install_launch_agent,
+            service::load_launch_agent,
+            service::unload_launch_agent,
+            service::remove_launch_agent,
+            service::install_systemd_unit,
+            service::uninstall_systemd_unit,
+            firewall::get_firewall_rules,
+            firewall::add_firewall_rules,
+            firewall::remove_firewall_rules,
+            service::get_backend_service_status,
+            legacy::get_legacy_data,
+            legacy::migrate_legacy_data,
+            legacy::dismiss_legacy_data,
             get_backend_logs,
             clear_backend_logs,
             get_backend_error_logs,
@@ -1139,7 +1778,107 @@ pub fn run() {
             read_log_file,
             clear_log_file,
             export_log_file,
-            set_minimize_to_tray
+            set_minimize_to_tray,
+            benchmark::benchmark_device,
+            devices::test_device_connection_native,
+            devices::pull_attendance_native,
+            devices::get_device_info_native,
+            devices::sync_device_time,
+            devices::get_device_users,
+            devices::set_device_user,
+            devices::get_face_template,
+            devices::set_face_template,
+            devices::reboot_device,
+            devices::poweroff_device,
+            devices::unlock_door,
+            devices::send_device_message,
+            devices::get_device_capacity,
+            devices::clear_device_attendance,
+            devices::set_device_network,
+            devices::list_serial_ports,
+            devices::get_device_event_log,
+            devices::enroll_card,
+            devices::upload_firmware,
+            trace::list_protocol_traces,
+            trace::get_protocol_trace,
+            registry::set_device_serial_link,
+            registry::set_device_timezone,
+            secrets::set_device_comm_key,
+            secrets::get_devices_with_comm_key,
+            confirmation::request_confirmation_token,
+            confirmation::get_step_up_requirement,
+            audit::get_audit_log,
+            device_manager::get_devices_status,
+            adms::get_adms_status,
+            poller::get_poller_status,
+            groups::set_device_group,
+            groups::list_device_groups,
+            groups::sync_group,
+            groups::set_time_group,
+            groups::reboot_group,
+            bulk_sync::sync_devices,
+            drift::get_device_drift,
+            database::get_recent_attendance,
+            database::get_user_count,
+            database::get_attendance_badges,
+            stats::get_attendance_stats,
+            database::check_database_integrity,
+            database::get_archived_attendance,
+            archive::archive_attendance,
+            duplicates::find_duplicate_punches,
+            duplicates::merge_duplicates,
+            duplicates::undo_merge_duplicates,
+            reconcile::reconcile_device,
+            photos::get_punch_photo,
+            photos::get_photo_store_status,
+            photos::cleanup_photo_store,
+            storage::analyze_storage,
+            storage::run_cleanup,
+            backup::backup_database,
+            backup::list_backups,
+            backup::restore_database,
+            backup::optimize_database,
+            relocate::move_database,
+            profiles::list_profiles,
+            profiles::create_profile,
+            profiles::switch_profile,
+            profiles::delete_profile,
+            bundle::export_app_bundle,
+            bundle::import_app_bundle,
+            diagnostics::export_diagnostics,
+            crash::get_crash_reports,
+            crash::get_crash_report,
+            crash::dismiss_crash_reports,
+            protect::decrypt_export,
+            wipe::secure_wipe,
+            encryption::get_database_encryption,
+            encryption::encrypt_database,
+            migrations::get_schema_status,
+            upstream::get_upstream_status,
+            upstream::flush_upstream_queue,
+            offline_queue::get_offline_queue_status,
+            export::export_attendance_csv,
+            export::export_attendance_xlsx,
+            ics::export_attendance_ics,
+            report::export_attendance_pdf,
+            user_import::import_users_csv,
+            simulator::start_device_simulator,
+            simulator::stop_device_simulator,
+            registry::refresh_device_registry,
+            registry::get_device_registry,
+            realtime::start_realtime_events,
+            realtime::stop_realtime_events,
+            settings::get_shell_settings,
+            settings::update_shell_settings,
+            binding::get_backend_binding,
+            tls::get_backend_tls_status,
+            sidecar::get_sidecar_integrity,
+            lock::get_lock_status,
+            idle::report_activity,
+            lock::lock_app,
+            lock::unlock_app,
+            lock::set_lock_pin,
+            sync_status::get_sync_status
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
@@ -1168,6 +1907,11 @@ pub fn run() {
 
                 // Clone for async task
                 let backend_for_exit = backend_process_for_run.clone();
+                let app_for_exit = app_handle.clone();
+                let endpoint_for_exit = health_endpoint(
+                    current_backend_port(&backend_port_for_run),
+                    &shell_settings_for_run,
+                );
 
                 // Prevent immediate exit
                 api.prevent_exit();
@@ -1198,7 +1942,7 @@ pub fn run() {
 
                     // Now wait for graceful shutdown
                     if killed {
-                        match wait_for_backend_shutdown(5).await {
+                        match wait_for_backend_shutdown(5, &endpoint_for_exit).await {
                             Ok(()) => {
                                 println!("Backend gracefully terminated on app exit");
                                 append_app_log("Backend gracefully terminated on app exit");
@@ -1213,6 +1957,7 @@ pub fn run() {
                         }
                     }
 
+                    updater::install_on_exit(&app_for_exit);
                     append_app_log("Graceful shutdown complete - exiting application");
                     std::process::exit(0);
                 });
@@ -1226,115 +1971,52 @@ async fn startup_backend_sidecar(
     app: tauri::AppHandle,
     backend_process: BackendProcess,
     process_status: ProcessStatus,
+    backend_port: BackendPort,
 ) {
-    match app.shell().sidecar("zkteco-backend") {
-        Ok(sidecar_command) => {
-            append_app_log("startup_backend_sidecar invoked");
-
-            let (startup_guard, acquired) = match BackendStartupGuard::try_acquire(&process_status)
-            {
-                Ok(result) => result,
-                Err(err) => {
-                    append_app_log(&format!(
-                        "startup_backend_sidecar failed to acquire startup guard: {}",
-                        err
-                    ));
-                    return;
-                }
-            };
-
-            if !acquired {
-                append_app_log(
-                    "startup_backend_sidecar skipped - backend startup already in progress",
-                );
-                return;
-            }
-
-            let _startup_guard = startup_guard;
+    append_app_log("startup_backend_sidecar invoked");
 
-            let db_path = resolve_backend_db_path();
-            let db_path_str = db_path.to_string_lossy().to_string();
-            if let Some(parent) = db_path.parent() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    eprintln!(
-                        "Failed to ensure database directory at {:?}: {}",
-                        parent, err
-                    );
-                    append_app_log(&format!(
-                        "startup_backend_sidecar failed to ensure database directory at {:?}: {}",
-                        parent, err
-                    ));
-                }
-            }
-            println!("Using backend database at startup: {}", db_path_str);
+    let (startup_guard, acquired) = match BackendStartupGuard::try_acquire(&process_status) {
+        Ok(result) => result,
+        Err(err) => {
             append_app_log(&format!(
-                "startup_backend_sidecar using DB path {}",
-                db_path_str
+                "startup_backend_sidecar failed to acquire startup guard: {}",
+                err
             ));
+            return;
+        }
+    };
 
-            let sidecar_with_env = sidecar_command
-                .env("SECRET_KEY", "your-secret-key-here")
-                .env("LOG_LEVEL", "INFO")
-                .env("FLASK_DEBUG", "0")
-                .env("FLASK_ENV", "production")
-                .env("ZKTECO_DB_PATH", &db_path_str);
-            match sidecar_with_env.spawn() {
-                Ok((mut rx, child)) => {
-                    println!("Backend sidecar started successfully during startup");
-                    append_app_log("startup_backend_sidecar spawned backend sidecar successfully");
-
-                    // Store the child process for later cleanup
-                    if let Ok(mut process_guard) = backend_process.lock() {
-                        *process_guard = Some(child);
-                        println!("Backend process stored for cleanup management");
-                    } else {
-                        eprintln!("Failed to store backend process reference");
-                    }
+    if !acquired {
+        append_app_log("startup_backend_sidecar skipped - backend startup already in progress");
+        return;
+    }
 
-                    // Listen for sidecar output
-                    tauri::async_runtime::spawn(async move {
-                        while let Some(event) = rx.recv().await {
-                            match event {
-                                tauri_plugin_shell::process::CommandEvent::Stdout(output) => {
-                                    println!(
-                                        "Backend stdout: {}",
-                                        String::from_utf8_lossy(&output)
-                                    );
-                                }
-                                tauri_plugin_shell::process::CommandEvent::Stderr(output) => {
-                                    eprintln!(
-                                        "Backend stderr: {}",
-                                        String::from_utf8_lossy(&output)
-                                    );
-                                }
-                                tauri_plugin_shell::process::CommandEvent::Error(error) => {
-                                    eprintln!("Backend error: {}", error);
-                                }
-                                tauri_plugin_shell::process::CommandEvent::Terminated(payload) => {
-                                    eprintln!("Backend terminated with code: {:?}", payload.code);
-                                    break;
-                                }
-                                _ => {
-                                    println!("Backend event: {:?}", event);
-                                }
-                            }
-                        }
-                    });
-                }
-                Err(e) => {
-                    eprintln!("Failed to spawn backend sidecar during startup: {}. This may indicate permission issues or missing dependencies.", e);
-                    append_app_log(&format!(
-                        "startup_backend_sidecar failed to spawn backend sidecar: {}",
-                        e
-                    ));
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("Failed to create backend sidecar command during startup: {}. The backend executable might be missing from the bundle.", e);
+    let _startup_guard = startup_guard;
+
+    if let Some(attached) = service::attach(&app, &backend_port).await {
+        append_app_log(&format!(
+            "startup_backend_sidecar using the backend service: {:?}",
+            attached
+        ));
+        return;
+    }
+
+    let backend_logs = app.state::<BackendLogs>().inner().clone();
+    match launch_backend(
+        &app,
+        &backend_process,
+        &process_status,
+        &backend_logs,
+        &backend_port,
+    )
+    .await
+    {
+        Ok(message) => append_app_log(&format!("startup_backend_sidecar: {}", message)),
+        Err(err) => {
+            eprintln!("Failed to start backend during startup: {}", err);
             append_app_log(&format!(
-                "startup_backend_sidecar failed to create sidecar command: {}",
-                e
+                "startup_backend_sidecar failed to start backend: {}",
+                err
             ));
         }
     }
@@ -0,0 +1,487 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use openssl::memcmp;
+use rusqlite::types::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::device_manager::{device_statuses, DeviceManager};
+use crate::monitor::{BackendState, DeviceState, MonitorBus, MonitorEvent};
+use crate::secrets::{local_api_token, random_hex, set_local_api_token};
+use crate::settings::{current_settings, SharedSettings};
+use crate::timezones::{normalize_stored, registered_zones, NormalizedTime};
+use crate::{append_app_log, lock, ConnectionSlot};
+
+// Read-only HTTP API for tools on this PC (payroll exporters, dashboards), so they don't have to
+// talk to the backend directly. It listens on 127.0.0.1 only and every request needs
+// "Authorization: Bearer <token>", the token living in the OS keychain (created on first start,
+// replaced with rotate_local_api_token). The IPC channel (ipc.rs) serves the same calls with the
+// same token. Endpoints, all GET with JSON answers:
+//   /api/v1/health      shell version, backend state and device counts
+//   /api/v1/devices     per-device connection status
+//   /api/v1/attendance  punches; since_id, from, to, user_id, device_id and limit narrow it down
+// A small HTTP/1.1 reader on std threads rather than axum: axum isn't among the crate's
+// dependencies and three bodiless GETs don't need it. Request heads are capped at
+// MAX_HEAD_SIZE and at most MAX_CONNECTIONS requests are handled at once.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+const MAX_HEADER_LINES: usize = 64;
+const MAX_CONNECTIONS: usize = 16;
+const DEFAULT_ATTENDANCE_LIMIT: u32 = 500;
+const MAX_ATTENDANCE_LIMIT: u32 = 5000;
+
+static STATS: Mutex<Option<LocalApiStats>> = Mutex::new(None);
+static TOKEN: Mutex<Option<String>> = Mutex::new(None);
+// Latest monitor readings, for /health
+static BACKEND: Mutex<Option<BackendState>> = Mutex::new(None);
+static DEVICES: Mutex<Option<DeviceState>> = Mutex::new(None);
+static WATCHING: AtomicBool = AtomicBool::new(false);
+static ACTIVE_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct LocalApiStats {
+    listening: bool,
+    port: Option<u16>,
+    requests_served: u64,
+    requests_rejected: u64,
+    last_request_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct AttendanceRecord {
+    id: i64,
+    user_id: String,
+    device_id: Option<String>,
+    serial_number: Option<String>,
+    timestamp: String,
+    method: i64,
+    action: i64,
+    #[serde(flatten)]
+    normalized: Option<NormalizedTime>,
+}
+
+// Why a call produced no result
+pub enum CallError {
+    BadRequest(String),
+    NotFound,
+    Failed(String),
+}
+
+impl CallError {
+    pub fn message(&self) -> String {
+        match self {
+            CallError::BadRequest(message) | CallError::Failed(message) => message.clone(),
+            CallError::NotFound => "Not found".to_string(),
+        }
+    }
+}
+
+struct ApiRequest {
+    method: String,
+    target: String,
+    authorization: Option<String>,
+}
+
+fn update_stats(f: impl FnOnce(&mut LocalApiStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+fn new_token() -> String {
+    random_hex(32)
+}
+
+pub fn token_matches(given: &str) -> bool {
+    let Ok(token) = TOKEN.lock() else {
+        return false;
+    };
+    token.as_deref().is_some_and(|expected| {
+        given.len() == expected.len() && memcmp::eq(given.as_bytes(), expected.as_bytes())
+    })
+}
+
+fn authorized(header: Option<&str>) -> bool {
+    header
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(token_matches)
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<ApiRequest, String> {
+    let mut reader = BufReader::new((&*stream).take(MAX_HEAD_SIZE));
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    if !request_line.ends_with('\n') {
+        return Err("Request line incomplete or too long".to_string());
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+    if method.is_empty() || !target.starts_with('/') {
+        return Err(format!("Malformed request line: {}", request_line.trim()));
+    }
+
+    let mut authorization = None;
+    for _ in 0..MAX_HEADER_LINES {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read headers: {}", e))?;
+        if !line.ends_with('\n') {
+            return Err("Request headers incomplete or too large".to_string());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            return Ok(ApiRequest {
+                method,
+                target,
+                authorization,
+            });
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    Err("Too many request headers".to_string())
+}
+
+fn write_json(stream: &mut TcpStream, status: u16, body: &serde_json::Value) {
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Error",
+    };
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n",
+        status,
+        reason,
+        body.len()
+    );
+    if status == 401 {
+        response.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    response.push_str("\r\n");
+    response.push_str(&body);
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn error_body(message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": message })
+}
+
+fn health() -> serde_json::Value {
+    let backend = BACKEND.lock().ok().and_then(|state| state.clone());
+    let devices = DEVICES.lock().ok().and_then(|state| state.clone());
+    serde_json::json!({
+        "shell_version": env!("CARGO_PKG_VERSION"),
+        "backend": backend,
+        "devices": devices,
+        "time": Utc::now(),
+    })
+}
+
+fn devices(manager: &DeviceManager) -> Result<serde_json::Value, String> {
+    Ok(serde_json::json!({ "devices": device_statuses(manager)? }))
+}
+
+fn attendance(query: &[(String, String)]) -> Result<serde_json::Value, CallError> {
+    let param = |name: &str| {
+        query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .filter(|value| !value.is_empty())
+    };
+    let limit = match param("limit") {
+        Some(limit) => limit
+            .parse::<u32>()
+            .map_err(|_| CallError::BadRequest(format!("Invalid limit '{}'", limit)))?
+            .clamp(1, MAX_ATTENDANCE_LIMIT),
+        None => DEFAULT_ATTENDANCE_LIMIT,
+    };
+
+    let mut clauses = Vec::new();
+    let mut values: Vec<Value> = Vec::new();
+    if let Some(since_id) = param("since_id") {
+        let since_id = since_id
+            .parse::<i64>()
+            .map_err(|_| CallError::BadRequest(format!("Invalid since_id '{}'", since_id)))?;
+        clauses.push("id > ?");
+        values.push(Value::Integer(since_id));
+    }
+    // Stored timestamps are device-local "YYYY-MM-DD HH:MM:SS"; a bare date for "to" means the
+    // whole day
+    if let Some(from) = param("from") {
+        clauses.push("timestamp >= ?");
+        values.push(Value::Text(from.replace('T', " ")));
+    }
+    if let Some(to) = param("to") {
+        let to = to.replace('T', " ");
+        clauses.push("timestamp <= ?");
+        values.push(Value::Text(if to.len() == 10 {
+            format!("{} 23:59:59", to)
+        } else {
+            to
+        }));
+    }
+    for (name, clause) in [("user_id", "user_id = ?"), ("device_id", "device_id = ?")] {
+        if let Some(value) = param(name) {
+            clauses.push(clause);
+            values.push(Value::Text(value));
+        }
+    }
+    let mut sql = "SELECT id, user_id, device_id, serial_number, timestamp, method, action \
+                   FROM attendance_logs"
+        .to_string();
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(" ORDER BY id LIMIT ?");
+    values.push(Value::Integer(limit as i64));
+
+    let zones = registered_zones();
+    let records = tauri::async_runtime::block_on(run_db(move |connection| {
+        let mut statement = connection
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        let rows = statement
+            .query_map(rusqlite::params_from_iter(values), |row| {
+                let device_id: Option<String> = row.get(2)?;
+                let timestamp: String = row.get(4)?;
+                let zone = device_id.as_ref().and_then(|id| zones.get(id)).copied();
+                Ok(AttendanceRecord {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    normalized: normalize_stored(&timestamp, zone),
+                    device_id,
+                    serial_number: row.get(3)?,
+                    timestamp,
+                    method: row.get(5)?,
+                    action: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read attendance row: {}", e))
+    }))
+    .map_err(CallError::Failed)?;
+
+    // Pass next_since_id back as since_id to page through newer records
+    let next_since_id = records.last().map(|record| record.id);
+    Ok(serde_json::json!({
+        "records": records,
+        "next_since_id": next_since_id,
+    }))
+}
+
+// Run one endpoint ("health", "devices" or "attendance") with its query parameters; blocks on
+// the database, so async callers go through spawn_blocking
+pub fn call(
+    endpoint: &str,
+    params: &[(String, String)],
+    manager: &DeviceManager,
+) -> Result<serde_json::Value, CallError> {
+    match endpoint {
+        "health" => Ok(health()),
+        "devices" => devices(manager).map_err(CallError::Failed),
+        "attendance" => attendance(params),
+        _ => Err(CallError::NotFound),
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, manager: &DeviceManager) {
+    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(err) => {
+            write_json(&mut stream, 400, &error_body(&err));
+            return;
+        }
+    };
+    update_stats(|stats| stats.last_request_at = Some(Utc::now()));
+    if !authorized(request.authorization.as_deref()) {
+        update_stats(|stats| stats.requests_rejected += 1);
+        write_json(
+            &mut stream,
+            401,
+            &error_body("Missing or invalid API token"),
+        );
+        return;
+    }
+    if !request.method.eq_ignore_ascii_case("GET") {
+        write_json(&mut stream, 405, &error_body("Only GET is supported"));
+        return;
+    }
+
+    let Ok(url) = reqwest::Url::parse(&format!("http://localhost{}", request.target)) else {
+        write_json(&mut stream, 400, &error_body("Malformed request path"));
+        return;
+    };
+    let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let endpoint = url
+        .path()
+        .trim_end_matches('/')
+        .strip_prefix("/api/v1/")
+        .unwrap_or_default();
+    match call(endpoint, &query, manager) {
+        Ok(body) => {
+            update_stats(|stats| stats.requests_served += 1);
+            write_json(&mut stream, 200, &body);
+        }
+        Err(err) => {
+            let status = match &err {
+                CallError::BadRequest(_) => 400,
+                CallError::NotFound => 404,
+                CallError::Failed(message) => {
+                    update_stats(|stats| stats.last_error = Some(message.clone()));
+                    500
+                }
+            };
+            write_json(&mut stream, status, &error_body(&err.message()));
+        }
+    }
+}
+
+// Keep the latest backend and device readings for /health
+fn watch_monitor(bus: &MonitorBus) {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let mut receiver = bus.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(MonitorEvent::Backend(state)) => {
+                    if let Ok(mut backend) = BACKEND.lock() {
+                        *backend = Some(state);
+                    }
+                }
+                Ok(MonitorEvent::Devices(state)) => {
+                    if let Ok(mut devices) = DEVICES.lock() {
+                        *devices = Some(state);
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// Load (or create) the API token and start following the monitor; shared with the IPC channel
+pub fn prepare(bus: &MonitorBus) -> Result<(), String> {
+    let token = match local_api_token() {
+        Some(token) => token,
+        None => {
+            let token = new_token();
+            set_local_api_token(&token)?;
+            token
+        }
+    };
+    if let Ok(mut current) = TOKEN.lock() {
+        *current = Some(token);
+    }
+    watch_monitor(bus);
+    Ok(())
+}
+
+pub fn spawn_local_api(settings: SharedSettings, manager: DeviceManager, bus: &MonitorBus) {
+    let config = current_settings(&settings);
+    if !config.local_api_enabled {
+        return;
+    }
+    if let Err(err) = prepare(bus) {
+        eprintln!("{}", err);
+        update_stats(|stats| stats.last_error = Some(err));
+        return;
+    }
+
+    let port = config.local_api_port;
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Failed to start local API on port {}: {}", port, err);
+            append_app_log(&format!(
+                "Failed to start local API on port {}: {}",
+                port, err
+            ));
+            update_stats(|stats| stats.last_error = Some(err.to_string()));
+            return;
+        }
+    };
+    update_stats(|stats| {
+        stats.listening = true;
+        stats.port = Some(port);
+    });
+    append_app_log(&format!("Local API listening on 127.0.0.1:{}", port));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    let Some(slot) =
+                        ConnectionSlot::try_acquire(&ACTIVE_CONNECTIONS, MAX_CONNECTIONS)
+                    else {
+                        write_json(&mut stream, 503, &error_body("Too many connections"));
+                        continue;
+                    };
+                    let manager = manager.clone();
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        handle_connection(stream, &manager)
+                    });
+                }
+                Err(err) => eprintln!("Local API accept failed: {}", err),
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_local_api_status() -> LocalApiStats {
+    STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default()
+}
+
+// The token local tools authenticate with
+#[tauri::command]
+pub fn get_local_api_token() -> Result<Option<String>, String> {
+    lock::ensure_unlocked()?;
+    audited("reveal_local_api_token", "local API", Ok(local_api_token()))
+}
+
+// Replace the token; tools using the old one are refused from now on
+#[tauri::command]
+pub fn rotate_local_api_token() -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    let token = new_token();
+    let result = set_local_api_token(&token).map(|_| {
+        if let Ok(mut current) = TOKEN.lock() {
+            if current.is_some() {
+                *current = Some(token.clone());
+            }
+        }
+        token
+    });
+    audited("rotate_local_api_token", "local API", result)
+}
@@ -0,0 +1,364 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use openssl::memcmp;
+use openssl::pkcs5::scrypt;
+use tauri::{AppHandle, Emitter};
+
+use crate::append_app_log;
+use crate::audit::record_audit;
+use crate::encryption::KEYRING_SERVICE;
+use crate::idle;
+use crate::secrets::{self, hex};
+use crate::settings::{current_settings, SharedSettings};
+
+// App-level lock for reception PCs left unattended. With a PIN set the app starts locked and
+// locks again on lock_app or when the idle timeout passes (idle.rs); while locked, privileged
+// commands fail with APP_LOCKED and the UI shows its unlock screen ("app-locked" /
+// "app-unlocked"). Only a salted scrypt hash of the PIN is kept, in the OS keychain. The door
+// unlock PIN (devices::unlock_door) is kept the same way, per profile, with its own backoff.
+pub const APP_LOCKED: &str = "AppLocked";
+pub const KEYRING_ACCOUNT: &str = "app-lock-pin";
+const MIN_PIN_LENGTH: usize = 4;
+const SCRYPT_N: u64 = 1 << 15;
+const SCRYPT_R: u64 = 8;
+const SCRYPT_P: u64 = 1;
+const SCRYPT_MAXMEM: u64 = 64 * 1024 * 1024;
+// After this many wrong PINs each further attempt has to wait out UNLOCK_BACKOFF
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const UNLOCK_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct LockState {
+    // Keychain value, read on first use: "scrypt$<salt hex>$<hash hex>"
+    pin_hash: Option<Option<String>>,
+    locked: bool,
+    failed_attempts: u32,
+    last_failure: Option<Instant>,
+}
+
+static LOCK: Mutex<Option<LockState>> = Mutex::new(None);
+// Wrong door PINs and when the last one was entered
+static DOOR_FAILURES: Mutex<(u32, Option<Instant>)> = Mutex::new((0, None));
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LockStatus {
+    enabled: bool,
+    locked: bool,
+    idle_minutes: u64,
+}
+
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+        .map_err(|e| format!("Failed to access the OS keychain: {}", e))
+}
+
+fn derive(pin: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut hash = [0u8; 32];
+    scrypt(
+        pin.as_bytes(),
+        salt,
+        SCRYPT_N,
+        SCRYPT_R,
+        SCRYPT_P,
+        SCRYPT_MAXMEM,
+        &mut hash,
+    )
+    .map_err(|e| format!("Failed to hash PIN: {}", e))?;
+    Ok(hash)
+}
+
+fn hash_pin(pin: &str) -> Result<String, String> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    Ok(format!(
+        "scrypt${}${}",
+        hex(&salt),
+        hex(&derive(pin, &salt)?)
+    ))
+}
+
+fn pin_matches(pin: &str, stored: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    let [_, salt, expected] = parts[..] else {
+        return false;
+    };
+    let salt: Option<Vec<u8>> = (0..salt.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(salt.get(i..i + 2)?, 16).ok())
+        .collect();
+    match salt.map(|salt| derive(pin, &salt)) {
+        Some(Ok(hash)) => {
+            let actual = hex(&hash);
+            actual.len() == expected.len() && memcmp::eq(actual.as_bytes(), expected.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+fn with_state<T>(f: impl FnOnce(&mut LockState) -> T) -> Result<T, String> {
+    let mut guard = LOCK
+        .lock()
+        .map_err(|e| format!("Failed to lock app lock state: {}", e))?;
+    let state = guard.get_or_insert_with(Default::default);
+    if state.pin_hash.is_none() {
+        state.pin_hash = Some(match keyring_entry()?.get_password() {
+            Ok(hash) => Some(hash),
+            Err(keyring::Error::NoEntry) => None,
+            Err(err) => return Err(format!("Failed to read app lock PIN: {}", err)),
+        });
+    }
+    Ok(f(state))
+}
+
+fn stored_hash(state: &LockState) -> Option<String> {
+    state.pin_hash.clone().flatten()
+}
+
+// Guard for privileged commands
+pub fn ensure_unlocked() -> Result<(), String> {
+    let locked = LOCK
+        .lock()
+        .map(|guard| guard.as_ref().is_some_and(|state| state.locked))
+        .unwrap_or(true);
+    if locked {
+        Err(APP_LOCKED.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Doesn't go through with_state: locking must still work when the keychain can't be read
+fn set_locked(app: &AppHandle, locked: bool, reason: &str) {
+    let changed = LOCK
+        .lock()
+        .map(|mut guard| {
+            let state = guard.get_or_insert_with(Default::default);
+            let changed = state.locked != locked;
+            state.locked = locked;
+            changed
+        })
+        .unwrap_or(false);
+    idle::touch();
+    if changed {
+        append_app_log(&format!(
+            "App {} ({})",
+            if locked { "locked" } else { "unlocked" },
+            reason
+        ));
+        let event = if locked { "app-locked" } else { "app-unlocked" };
+        if let Err(err) = app.emit(event, reason) {
+            eprintln!("Failed to emit {} event: {}", event, err);
+        }
+    }
+}
+
+pub fn is_locked() -> bool {
+    ensure_unlocked().is_err()
+}
+
+// Whether a lock PIN is set
+pub fn is_enabled() -> bool {
+    with_state(|state| stored_hash(state).is_some()).unwrap_or(false)
+}
+
+pub fn lock_now(app: &AppHandle, reason: &str) {
+    set_locked(app, true, reason);
+}
+
+// Start locked when a PIN is set, or when the keychain can't tell whether one is
+pub fn lock_at_startup(app: &AppHandle) {
+    match with_state(|state| stored_hash(state).is_some()) {
+        Ok(false) => {}
+        Ok(true) => lock_now(app, "startup"),
+        Err(err) => {
+            append_app_log(&format!("{}; starting locked", err));
+            lock_now(app, "startup, PIN unreadable");
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_lock_status(settings: tauri::State<SharedSettings>) -> Result<LockStatus, String> {
+    let (enabled, locked) = with_state(|state| (stored_hash(state).is_some(), state.locked))?;
+    Ok(LockStatus {
+        enabled,
+        locked,
+        idle_minutes: current_settings(&settings).idle_timeout_minutes,
+    })
+}
+
+#[tauri::command]
+pub fn lock_app(app: AppHandle) -> Result<(), String> {
+    if !with_state(|state| stored_hash(state).is_some())? {
+        return Err("Set a PIN before locking the app".to_string());
+    }
+    set_locked(&app, true, "manual");
+    Ok(())
+}
+
+// Count an attempt as failed before the slow hash runs, so parallel guesses can't all get past
+// the backoff check; a matching PIN clears the count afterwards. False while backing off.
+fn reserve_attempt(failed_attempts: &mut u32, last_failure: &mut Option<Instant>) -> bool {
+    if *failed_attempts >= MAX_FAILED_ATTEMPTS
+        && last_failure.is_some_and(|at| at.elapsed() < UNLOCK_BACKOFF)
+    {
+        return false;
+    }
+    *failed_attempts = failed_attempts.saturating_add(1);
+    *last_failure = Some(Instant::now());
+    true
+}
+
+fn backoff_error() -> String {
+    format!(
+        "Too many wrong PINs - wait {} seconds and try again",
+        UNLOCK_BACKOFF.as_secs()
+    )
+}
+
+// Check a PIN against the stored hash; wrong PINs count towards the unlock backoff. None when
+// no PIN is set.
+pub async fn check_pin(pin: String) -> Result<Option<bool>, String> {
+    let (stored, reserved) = with_state(|state| {
+        let stored = stored_hash(state);
+        let reserved = stored.is_some()
+            && reserve_attempt(&mut state.failed_attempts, &mut state.last_failure);
+        (stored, reserved)
+    })?;
+    let Some(stored) = stored else {
+        return Ok(None);
+    };
+    if !reserved {
+        return Err(backoff_error());
+    }
+
+    let matches = tauri::async_runtime::spawn_blocking(move || pin_matches(&pin, &stored))
+        .await
+        .map_err(|e| format!("PIN check failed: {}", e))?;
+    if matches {
+        with_state(|state| {
+            state.failed_attempts = 0;
+            state.last_failure = None;
+        })?;
+    }
+    Ok(Some(matches))
+}
+
+#[tauri::command]
+pub async fn unlock_app(app: AppHandle, pin: String) -> Result<(), String> {
+    match check_pin(pin).await? {
+        None => {
+            set_locked(&app, false, "no PIN set");
+            Ok(())
+        }
+        Some(false) => {
+            record_audit(
+                "unlock_app",
+                "app",
+                "rejected",
+                Some("wrong PIN".to_string()),
+            );
+            Err("Wrong PIN".to_string())
+        }
+        Some(true) => {
+            set_locked(&app, false, "PIN");
+            Ok(())
+        }
+    }
+}
+
+// Set, change (current_pin required) or remove (new_pin None) the lock PIN
+#[tauri::command]
+pub async fn set_lock_pin(
+    current_pin: Option<String>,
+    new_pin: Option<String>,
+) -> Result<(), String> {
+    ensure_unlocked()?;
+    if let Some(stored) = with_state(|state| stored_hash(state))? {
+        let current = current_pin.unwrap_or_default();
+        let matches = tauri::async_runtime::spawn_blocking(move || pin_matches(&current, &stored))
+            .await
+            .map_err(|e| format!("PIN check failed: {}", e))?;
+        if !matches {
+            record_audit(
+                "set_lock_pin",
+                "app",
+                "rejected",
+                Some("wrong PIN".to_string()),
+            );
+            return Err("Current PIN is wrong".to_string());
+        }
+    }
+
+    let result = match new_pin {
+        Some(pin) if pin.chars().count() < MIN_PIN_LENGTH => Err(format!(
+            "PIN must have at least {} characters",
+            MIN_PIN_LENGTH
+        )),
+        Some(pin) => {
+            let hash = tauri::async_runtime::spawn_blocking(move || hash_pin(&pin))
+                .await
+                .map_err(|e| format!("PIN hashing failed: {}", e))??;
+            keyring_entry()?
+                .set_password(&hash)
+                .map_err(|e| format!("Failed to store PIN in the OS keychain: {}", e))
+                .and_then(|_| with_state(|state| state.pin_hash = Some(Some(hash))))
+        }
+        None => match keyring_entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {
+                with_state(|state| state.pin_hash = Some(None))
+            }
+            Err(err) => Err(format!(
+                "Failed to remove PIN from the OS keychain: {}",
+                err
+            )),
+        },
+    };
+    crate::audit::audited("set_lock_pin", "app", result)
+}
+
+// Store the door unlock PIN as a hash, or remove it when empty
+pub fn set_door_pin(pin: &str) -> Result<(), String> {
+    if pin.is_empty() {
+        return secrets::set_door_pin_hash("");
+    }
+    if pin.chars().count() < MIN_PIN_LENGTH {
+        return Err(format!(
+            "Door PIN must have at least {} characters",
+            MIN_PIN_LENGTH
+        ));
+    }
+    secrets::set_door_pin_hash(&hash_pin(pin)?)
+}
+
+// Check a PIN against the door unlock PIN, with the same backoff as unlocking the app. None when
+// no door PIN is set; a keychain that can't be read is an error, not "no PIN".
+pub async fn check_door_pin(pin: Option<String>) -> Result<Option<bool>, String> {
+    let Some(stored) = secrets::door_pin_hash()? else {
+        return Ok(None);
+    };
+    let reserved = DOOR_FAILURES
+        .lock()
+        .map(|mut failures| {
+            let (failed_attempts, last_failure) = &mut *failures;
+            reserve_attempt(failed_attempts, last_failure)
+        })
+        .unwrap_or(false);
+    if !reserved {
+        return Err(backoff_error());
+    }
+
+    let pin = pin.unwrap_or_default();
+    let matches = tauri::async_runtime::spawn_blocking(move || pin_matches(&pin, &stored))
+        .await
+        .map_err(|e| format!("PIN check failed: {}", e))?;
+    if matches {
+        if let Ok(mut failures) = DOOR_FAILURES.lock() {
+            *failures = (0, None);
+        }
+    }
+    Ok(Some(matches))
+}
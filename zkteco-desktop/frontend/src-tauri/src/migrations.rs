@@ -0,0 +1,216 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::backup::{default_backup_dir, snapshot_database};
+use crate::database::BUSY_TIMEOUT;
+use crate::encryption::unlock;
+use crate::{append_app_log, resolve_backend_db_path};
+
+// Versioned schema migrations applied by the shell before the backend starts, so a newer
+// backend never meets a schema it doesn't expect. Applied versions are kept in schema_version;
+// the backend's own migration_history is left alone.
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+    // Applied only when this query returns true; otherwise just recorded
+    only_if: Option<&'static str>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "core_tables",
+        sql: include_str!("migrations/0001_core_tables.sql"),
+        only_if: None,
+    },
+    Migration {
+        version: 2,
+        name: "attendance_sync_status",
+        sql: include_str!("migrations/0002_attendance_sync_status.sql"),
+        only_if: Some(
+            "SELECT COUNT(*) = 0 FROM pragma_table_info('attendance_logs') WHERE name = 'sync_status'",
+        ),
+    },
+    Migration {
+        version: 3,
+        name: "primary_device",
+        sql: include_str!("migrations/0003_primary_device.sql"),
+        only_if: Some(
+            "SELECT COUNT(*) = 0 FROM pragma_table_info('devices') WHERE name = 'is_primary'",
+        ),
+    },
+    Migration {
+        version: 4,
+        name: "indexes",
+        sql: include_str!("migrations/0004_indexes.sql"),
+        only_if: None,
+    },
+];
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SchemaStatus {
+    current_version: u32,
+    latest_version: u32,
+    pending: Vec<String>,
+}
+
+fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn current_version(connection: &Connection) -> Result<u32, String> {
+    connection
+        .execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .map_err(|e| format!("Failed to create schema_version table: {}", e))?;
+    connection
+        .query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+// One statement per `;` at the end of a line; comment lines are dropped
+fn statements(sql: &str) -> impl Iterator<Item = String> + '_ {
+    sql.split(";\n")
+        .map(|chunk| {
+            chunk
+                .lines()
+                .filter(|line| !line.trim_start().starts_with("--"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .map(|statement| statement.trim().trim_end_matches(';').to_string())
+        .filter(|statement| !statement.is_empty())
+}
+
+fn apply(connection: &Connection, migration: &Migration) -> Result<(), String> {
+    let tx = connection
+        .unchecked_transaction()
+        .map_err(|e| format!("Failed to start migration: {}", e))?;
+    let needed = match migration.only_if {
+        Some(query) => tx
+            .query_row(query, [], |row| row.get(0))
+            .map_err(|e| format!("Failed to check migration {}: {}", migration.name, e))?,
+        None => true,
+    };
+    if needed {
+        for statement in statements(migration.sql) {
+            match tx.execute_batch(&statement) {
+                Ok(()) => {}
+                // Older backends added some of these columns themselves
+                Err(err) if err.to_string().contains("duplicate column name") => {}
+                Err(err) => {
+                    return Err(format!(
+                        "Migration {:04}_{} failed: {}",
+                        migration.version, migration.name, err
+                    ))
+                }
+            }
+        }
+    }
+    tx.execute(
+        "INSERT INTO schema_version (version, name) VALUES (?1, ?2)",
+        rusqlite::params![migration.version, migration.name],
+    )
+    .map_err(|e| format!("Failed to record migration {}: {}", migration.name, e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit migration {}: {}", migration.name, e))
+}
+
+fn open_read_write(path: &Path) -> Result<Connection, String> {
+    let connection = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("Failed to open backend database: {}", e))?;
+    unlock(&connection, path)?;
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to configure backend database: {}", e))?;
+    Ok(connection)
+}
+
+// Bring the database up to the latest schema; returns the number of migrations applied.
+// A database that doesn't exist yet is left for the backend to create.
+pub fn migrate_database() -> Result<usize, String> {
+    let path = resolve_backend_db_path();
+    if !path.exists() {
+        return Ok(0);
+    }
+    let connection = open_read_write(&path)?;
+    let current = current_version(&connection)?;
+    if current > latest_version() {
+        append_app_log(&format!(
+            "Database schema version {} is newer than this shell knows ({}), not migrating",
+            current,
+            latest_version()
+        ));
+        return Ok(0);
+    }
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    // A snapshot to fall back on should a migration leave the data in a bad state
+    let backup = snapshot_database(&default_backup_dir())?;
+    append_app_log(&format!(
+        "Migrating database schema {} -> {} (backup at {})",
+        current,
+        latest_version(),
+        backup.path
+    ));
+    for migration in &pending {
+        apply(&connection, migration)?;
+        append_app_log(&format!(
+            "Applied schema migration {:04}_{}",
+            migration.version, migration.name
+        ));
+    }
+    Ok(pending.len())
+}
+
+// Run before every backend launch; a failure is logged and the backend started anyway since
+// each migration rolls back on its own
+pub async fn prepare_database() {
+    let migrated = tauri::async_runtime::spawn_blocking(migrate_database)
+        .await
+        .map_err(|e| format!("Migration task failed: {}", e))
+        .and_then(|result| result);
+    if let Err(err) = migrated {
+        eprintln!("Database migration failed: {}", err);
+        append_app_log(&format!("Database migration failed: {}", err));
+    }
+}
+
+#[tauri::command]
+pub async fn get_schema_status() -> Result<SchemaStatus, String> {
+    let path = resolve_backend_db_path();
+    let current = tauri::async_runtime::spawn_blocking(move || {
+        if !path.exists() {
+            return Ok(0);
+        }
+        current_version(&open_read_write(&path)?)
+    })
+    .await
+    .map_err(|e| format!("Database task failed: {}", e))??;
+    Ok(SchemaStatus {
+        current_version: current,
+        latest_version: latest_version(),
+        pending: MIGRATIONS
+            .iter()
+            .filter(|m| m.version > current)
+            .map(|m| format!("{:04}_{}", m.version, m.name))
+            .collect(),
+    })
+}
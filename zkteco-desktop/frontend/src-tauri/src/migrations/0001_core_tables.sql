@@ -0,0 +1,75 @@
+-- Core tables as created by the backend, plus every column added to them since the first
+-- release. Columns that already exist are skipped by the runner.
+CREATE TABLE IF NOT EXISTS devices (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    ip TEXT NOT NULL,
+    port INTEGER DEFAULT 4370,
+    password INTEGER DEFAULT 0,
+    timeout INTEGER DEFAULT 10,
+    retry_count INTEGER DEFAULT 3,
+    retry_delay INTEGER DEFAULT 2,
+    ping_interval INTEGER DEFAULT 30,
+    force_udp BOOLEAN DEFAULT FALSE,
+    is_active BOOLEAN DEFAULT TRUE,
+    device_info TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    device_id TEXT,
+    privilege INTEGER DEFAULT 0,
+    group_id INTEGER DEFAULT 0,
+    card INTEGER DEFAULT 0,
+    password TEXT DEFAULT '',
+    is_synced BOOLEAN DEFAULT FALSE,
+    synced_at DATETIME NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS attendance_logs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id TEXT NOT NULL,
+    device_id TEXT,
+    timestamp DATETIME NOT NULL,
+    method INTEGER NOT NULL,
+    action INTEGER NOT NULL,
+    raw_data TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS app_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    description TEXT,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE devices ADD COLUMN serial_number TEXT;
+ALTER TABLE devices ADD COLUMN device_type TEXT DEFAULT 'pull';
+
+ALTER TABLE users ADD COLUMN serial_number TEXT;
+ALTER TABLE users ADD COLUMN external_user_id INTEGER NULL;
+ALTER TABLE users ADD COLUMN avatar_url TEXT NULL;
+ALTER TABLE users ADD COLUMN full_name TEXT NULL;
+ALTER TABLE users ADD COLUMN employee_code TEXT NULL;
+ALTER TABLE users ADD COLUMN position TEXT NULL;
+ALTER TABLE users ADD COLUMN department TEXT NULL;
+ALTER TABLE users ADD COLUMN notes TEXT NULL;
+ALTER TABLE users ADD COLUMN employee_object TEXT NULL;
+ALTER TABLE users ADD COLUMN gender TEXT NULL;
+ALTER TABLE users ADD COLUMN hire_date TEXT NULL;
+
+ALTER TABLE attendance_logs ADD COLUMN serial_number TEXT;
+ALTER TABLE attendance_logs ADD COLUMN is_synced BOOLEAN DEFAULT FALSE;
+ALTER TABLE attendance_logs ADD COLUMN synced_at DATETIME NULL;
+ALTER TABLE attendance_logs ADD COLUMN is_pushed BOOLEAN DEFAULT FALSE;
+ALTER TABLE attendance_logs ADD COLUMN error_code TEXT NULL;
+ALTER TABLE attendance_logs ADD COLUMN error_message TEXT NULL;
+ALTER TABLE attendance_logs ADD COLUMN original_status INTEGER DEFAULT 0;
+ALTER TABLE attendance_logs ADD COLUMN error_count INTEGER DEFAULT 0;
@@ -0,0 +1,5 @@
+-- Only runs when sync_status is missing: records already pushed under the old is_synced flag
+-- must not be queued again.
+ALTER TABLE attendance_logs ADD COLUMN sync_status TEXT DEFAULT 'pending';
+UPDATE attendance_logs
+SET sync_status = CASE WHEN is_synced = 1 THEN 'synced' ELSE 'pending' END;
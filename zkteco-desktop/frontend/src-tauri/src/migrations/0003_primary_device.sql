@@ -0,0 +1,4 @@
+-- Only runs when is_primary is missing: the first active device becomes the primary one.
+ALTER TABLE devices ADD COLUMN is_primary BOOLEAN DEFAULT FALSE;
+UPDATE devices SET is_primary = TRUE
+WHERE id = (SELECT id FROM devices WHERE is_active = TRUE LIMIT 1);
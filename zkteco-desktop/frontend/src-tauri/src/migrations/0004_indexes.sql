@@ -0,0 +1,9 @@
+CREATE INDEX IF NOT EXISTS idx_users_device_id ON users(device_id);
+CREATE INDEX IF NOT EXISTS idx_users_sync_status ON users(is_synced);
+CREATE INDEX IF NOT EXISTS idx_attendance_user_id ON attendance_logs(user_id);
+CREATE INDEX IF NOT EXISTS idx_attendance_device_id ON attendance_logs(device_id);
+CREATE INDEX IF NOT EXISTS idx_attendance_timestamp ON attendance_logs(timestamp);
+CREATE INDEX IF NOT EXISTS idx_attendance_sync_status ON attendance_logs(is_synced);
+CREATE INDEX IF NOT EXISTS idx_attendance_sync_status_new ON attendance_logs(sync_status);
+CREATE INDEX IF NOT EXISTS idx_attendance_date_action ON attendance_logs(DATE(timestamp), action);
+CREATE INDEX IF NOT EXISTS idx_attendance_user_date ON attendance_logs(user_id, DATE(timestamp));
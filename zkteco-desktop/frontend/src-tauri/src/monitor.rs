@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use tauri::Emitter;
+use tokio::sync::broadcast;
+
+use crate::binding::verify_backend_binding;
+use crate::device_manager::DeviceStatus;
+use crate::registry::{refresh_from_backend, DeviceRegistry};
+use crate::settings::{current_settings, SharedSettings};
+use crate::{
+    check_backend_health, current_backend_port, health_endpoint, BackendPort, ProcessStatus,
+    BACKEND_STARTING_KEY,
+};
+
+const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+const MONITOR_CHANNEL_CAPACITY: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum BackendState {
+    Starting,
+    Running,
+    Stopped,
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DeviceState {
+    pub total: usize,
+    pub online: usize,
+    pub offline: Vec<String>, // names of active devices reported unhealthy
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum MonitorEvent {
+    Backend(BackendState),
+    Devices(DeviceState),
+    // A single device going online or offline
+    DeviceStatus(DeviceStatus),
+    // A punch from the backend's event stream or a native realtime capture
+    Punch(serde_json::Value),
+    // The summary of a finished multi-device attendance sync
+    SyncComplete(serde_json::Value),
+    // A scheduled collection job that failed, e.g. a device poll
+    SyncFailed(serde_json::Value),
+}
+
+// Subscribers (tray updater, ...) call `subscribe()` on the managed sender
+pub type MonitorBus = broadcast::Sender<MonitorEvent>;
+
+pub fn create_monitor_bus() -> MonitorBus {
+    broadcast::channel(MONITOR_CHANNEL_CAPACITY).0
+}
+
+fn resolve_backend_state(healthy: bool, process_status: &ProcessStatus) -> BackendState {
+    if healthy {
+        return BackendState::Running;
+    }
+
+    match process_status.lock() {
+        Ok(status) => {
+            if status
+                .get(BACKEND_STARTING_KEY)
+                .map(|value| value == "true")
+                .unwrap_or(false)
+            {
+                BackendState::Starting
+            } else if let Some(reason) = status.get("backend_status") {
+                BackendState::Failed(reason.clone())
+            } else {
+                BackendState::Stopped
+            }
+        }
+        Err(_) => BackendState::Stopped,
+    }
+}
+
+// Background task: poll backend health, publish changes on the bus and to the UI.
+// Device state is published by the device manager.
+pub fn spawn_state_monitor(
+    app: tauri::AppHandle,
+    bus: MonitorBus,
+    process_status: ProcessStatus,
+    backend_port: BackendPort,
+    settings: SharedSettings,
+    registry: DeviceRegistry,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_backend: Option<BackendState> = None;
+
+        loop {
+            let port = current_backend_port(&backend_port);
+            let healthy = check_backend_health(&health_endpoint(port, &settings)).await;
+            let backend = resolve_backend_state(healthy, &process_status);
+
+            if last_backend.as_ref() != Some(&backend) {
+                // Keep the local device registry current whenever the backend comes up
+                if backend == BackendState::Running {
+                    if let Err(err) = refresh_from_backend(&registry, port).await {
+                        eprintln!("Device registry refresh failed: {}", err);
+                    }
+                    let localhost_only = current_settings(&settings).backend_localhost_only;
+                    verify_backend_binding(&app, port, localhost_only).await;
+                }
+                let _ = app.emit("backend-state-changed", &backend);
+                let _ = bus.send(MonitorEvent::Backend(backend.clone()));
+                last_backend = Some(backend);
+            }
+
+            tokio::time::sleep(MONITOR_INTERVAL).await;
+        }
+    });
+}
@@ -0,0 +1,434 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use openssl::ssl::{SslConnector, SslMethod};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::append_app_log;
+use crate::monitor::{MonitorBus, MonitorEvent};
+use crate::secrets::{mqtt_password, random_hex};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+
+// Opt-in MQTT 3.1.1 publisher for building-management and home-automation systems. Topics
+// under mqtt_topic_prefix:
+//   <prefix>/status               "online", or "offline" (retained; also the last will)
+//   <prefix>/punches              each punch as JSON
+//   <prefix>/devices/<id>/status  a device going online or offline (retained)
+//   <prefix>/backend/health       backend state transitions (retained)
+// Only the client side of publishing is implemented (CONNECT, PUBLISH at QoS 0/1, PINGREQ,
+// DISCONNECT). The connection is opened on the first message; a publish that fails is retried
+// once on a new connection and then dropped.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+const KEEP_ALIVE_SECS: u16 = 60;
+const PING_INTERVAL: Duration = Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2);
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MqttStats {
+    enabled: bool,
+    connected: bool,
+    broker: Option<String>,
+    messages_published: u64,
+    messages_dropped: u64,
+    last_published_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+static STATS: Mutex<Option<MqttStats>> = Mutex::new(None);
+
+fn update_stats(f: impl FnOnce(&mut MqttStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct BrokerConfig {
+    host: String,
+    port: u16,
+    tls: bool,
+    ca_file: Option<String>,
+    username: Option<String>,
+    prefix: String,
+    client_id: String,
+    qos: u8,
+}
+
+impl BrokerConfig {
+    fn from_settings(settings: &ShellSettings, fallback_client_id: &str) -> Option<Self> {
+        let host = settings
+            .mqtt_broker_host
+            .as_deref()
+            .map(str::trim)
+            .filter(|host| !host.is_empty());
+        match host {
+            Some(host) if settings.mqtt_enabled => Some(BrokerConfig {
+                host: host.to_string(),
+                port: settings.mqtt_broker_port,
+                tls: settings.mqtt_tls,
+                ca_file: settings.mqtt_ca_file.clone().filter(|f| !f.is_empty()),
+                username: settings.mqtt_username.clone().filter(|u| !u.is_empty()),
+                prefix: settings.mqtt_topic_prefix.trim_end_matches('/').to_string(),
+                client_id: settings
+                    .mqtt_client_id
+                    .clone()
+                    .filter(|id| !id.is_empty())
+                    .unwrap_or_else(|| fallback_client_id.to_string()),
+                qos: settings.mqtt_qos.min(1),
+            }),
+            _ => None,
+        }
+    }
+
+    fn status_topic(&self) -> String {
+        format!("{}/status", self.prefix)
+    }
+}
+
+trait Transport: Read + Write + Send {}
+impl<T: Read + Write + Send> Transport for T {}
+
+fn push_length(packet: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+
+fn push_bytes(body: &mut Vec<u8>, bytes: &[u8]) {
+    body.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    body.extend_from_slice(bytes);
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    push_length(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn connect_packet(config: &BrokerConfig, password: Option<&str>) -> Vec<u8> {
+    // Clean session, will at QoS 0 retained
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if config.username.is_some() {
+        flags |= 0x80;
+        if password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    let mut body = Vec::new();
+    push_bytes(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    push_bytes(&mut body, config.client_id.as_bytes());
+    push_bytes(&mut body, config.status_topic().as_bytes());
+    push_bytes(&mut body, b"offline");
+    if let Some(username) = &config.username {
+        push_bytes(&mut body, username.as_bytes());
+        if let Some(password) = password {
+            push_bytes(&mut body, password.as_bytes());
+        }
+    }
+    packet(CONNECT, &body)
+}
+
+struct Session {
+    config: BrokerConfig,
+    stream: Box<dyn Transport>,
+    next_packet_id: u16,
+}
+
+impl Session {
+    fn open(config: &BrokerConfig) -> Result<Session, String> {
+        let address = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", config.host, e))?
+            .next()
+            .ok_or_else(|| format!("No address found for {}", config.host))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        tcp.set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+            .map_err(|e| format!("Failed to configure MQTT socket: {}", e))?;
+
+        let stream: Box<dyn Transport> = if config.tls {
+            let mut builder = SslConnector::builder(SslMethod::tls_client())
+                .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+            if let Some(ca_file) = &config.ca_file {
+                builder
+                    .set_ca_file(ca_file)
+                    .map_err(|e| format!("Failed to load MQTT CA file {}: {}", ca_file, e))?;
+            }
+            Box::new(
+                builder
+                    .build()
+                    .connect(&config.host, tcp)
+                    .map_err(|e| format!("TLS handshake with {} failed: {}", config.host, e))?,
+            )
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut session = Session {
+            config: config.clone(),
+            stream,
+            next_packet_id: 1,
+        };
+        let password = config.username.as_ref().and_then(|_| mqtt_password());
+        session.send(&connect_packet(config, password.as_deref()))?;
+        let (header, body) = session.read_packet()?;
+        if header != CONNACK || body.len() < 2 {
+            return Err("Broker sent an unexpected reply to CONNECT".to_string());
+        }
+        match body[1] {
+            0 => {}
+            4 => return Err("Broker rejected the MQTT user name or password".to_string()),
+            5 => return Err("Broker refused the connection: not authorized".to_string()),
+            code => return Err(format!("Broker refused the connection (code {})", code)),
+        }
+        session.publish(&config.status_topic(), b"online", true)?;
+        Ok(session)
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), String> {
+        self.stream
+            .write_all(packet)
+            .and_then(|_| self.stream.flush())
+            .map_err(|e| format!("Failed to write to MQTT broker: {}", e))
+    }
+
+    fn read_packet(&mut self) -> Result<(u8, Vec<u8>), String> {
+        let read_error = |e: std::io::Error| format!("Failed to read from MQTT broker: {}", e);
+        let mut byte = [0u8; 1];
+        self.stream.read_exact(&mut byte).map_err(read_error)?;
+        let header = byte[0];
+        let (mut length, mut shift) = (0usize, 0);
+        loop {
+            self.stream.read_exact(&mut byte).map_err(read_error)?;
+            length |= ((byte[0] & 0x7F) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift > 21 {
+                return Err("Malformed packet from MQTT broker".to_string());
+            }
+        }
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body).map_err(read_error)?;
+        Ok((header & 0xF0, body))
+    }
+
+    fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> Result<(), String> {
+        let qos = self.config.qos;
+        let mut body = Vec::new();
+        push_bytes(&mut body, topic.as_bytes());
+        let packet_id = self.next_packet_id;
+        if qos > 0 {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+            self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        }
+        body.extend_from_slice(payload);
+        self.send(&packet(PUBLISH | (qos << 1) | retain as u8, &body))?;
+
+        if qos == 0 {
+            return Ok(());
+        }
+        // Anything else the broker sends meanwhile (a late PINGRESP) is skipped
+        loop {
+            let (header, body) = self.read_packet()?;
+            if header == PUBACK && body.get(..2) == Some(&packet_id.to_be_bytes()[..]) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn ping(&mut self) -> Result<(), String> {
+        self.send(&packet(PINGREQ, &[]))?;
+        self.read_packet().map(drop)
+    }
+
+    // Graceful close: the will is not sent, so report offline first
+    fn close(mut self) {
+        let _ = self.publish(&self.config.status_topic(), b"offline", true);
+        let _ = self.send(&packet(DISCONNECT, &[]));
+    }
+}
+
+fn topic_segment(value: &str) -> String {
+    value.replace(['/', '+', '#'], "_")
+}
+
+// Topic, payload and retain flag for a monitor event; device summaries are not published
+fn message(prefix: &str, event: &MonitorEvent) -> Option<(String, serde_json::Value, bool)> {
+    let stamped = |value: serde_json::Value| match value {
+        serde_json::Value::Object(mut object) => {
+            object.insert("at".to_string(), Utc::now().to_rfc3339().into());
+            serde_json::Value::Object(object)
+        }
+        other => other,
+    };
+    match event {
+        MonitorEvent::Backend(state) => Some((
+            format!("{}/backend/health", prefix),
+            stamped(serde_json::to_value(state).ok()?),
+            true,
+        )),
+        MonitorEvent::DeviceStatus(status) => {
+            let status = serde_json::to_value(status).ok()?;
+            let device_id = status.get("device_id")?.as_str()?.to_string();
+            Some((
+                format!("{}/devices/{}/status", prefix, topic_segment(&device_id)),
+                stamped(status),
+                true,
+            ))
+        }
+        MonitorEvent::Punch(punch) => Some((format!("{}/punches", prefix), punch.clone(), false)),
+        MonitorEvent::Devices(_) | MonitorEvent::SyncComplete(_) | MonitorEvent::SyncFailed(_) => {
+            None
+        }
+    }
+}
+
+fn publish_event(
+    session: &mut Option<Session>,
+    config: &BrokerConfig,
+    topic: &str,
+    payload: &[u8],
+    retain: bool,
+) -> Result<(), String> {
+    if let Some(open) = session.as_mut() {
+        if open.publish(topic, payload, retain).is_ok() {
+            return Ok(());
+        }
+        *session = None;
+    }
+    let mut fresh = Session::open(config)?;
+    let result = fresh.publish(topic, payload, retain);
+    *session = Some(fresh);
+    result
+}
+
+fn run_publisher(settings: SharedSettings, events: Receiver<MonitorEvent>) {
+    let fallback_client_id = format!("ztkapp-{}", random_hex(4));
+    let mut session: Option<Session> = None;
+
+    loop {
+        let event = match events.recv_timeout(PING_INTERVAL) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let config = BrokerConfig::from_settings(&current_settings(&settings), &fallback_client_id);
+        if session
+            .as_ref()
+            .is_some_and(|open| Some(&open.config) != config.as_ref())
+        {
+            if let Some(open) = session.take() {
+                open.close();
+            }
+        }
+        update_stats(|stats| {
+            stats.enabled = config.is_some();
+            stats.broker = config
+                .as_ref()
+                .map(|config| format!("{}:{}", config.host, config.port));
+        });
+        let Some(config) = config else {
+            continue;
+        };
+
+        let result = match event {
+            None => match session.as_mut().map(Session::ping) {
+                Some(Err(err)) => {
+                    session = None;
+                    Err(err)
+                }
+                _ => Ok(()),
+            },
+            Some(event) => match message(&config.prefix, &event) {
+                Some((topic, payload, retain)) => publish_event(
+                    &mut session,
+                    &config,
+                    &topic,
+                    payload.to_string().as_bytes(),
+                    retain,
+                )
+                .map(|_| {
+                    update_stats(|stats| {
+                        stats.messages_published += 1;
+                        stats.last_published_at = Some(Utc::now());
+                    })
+                })
+                .inspect_err(|_| update_stats(|stats| stats.messages_dropped += 1)),
+                None => Ok(()),
+            },
+        };
+
+        let connected = session.is_some();
+        let error = result.err();
+        let changed = STATS
+            .lock()
+            .map(|guard| guard.as_ref().and_then(|s| s.last_error.clone()) != error)
+            .unwrap_or(false);
+        if let (true, Some(err)) = (changed, &error) {
+            append_app_log(&format!("MQTT publishing failed: {}", err));
+        }
+        update_stats(|stats| {
+            stats.connected = connected;
+            stats.last_error = error;
+        });
+    }
+}
+
+pub fn spawn_mqtt_publisher(settings: SharedSettings, bus: &MonitorBus) {
+    let mut receiver = bus.subscribe();
+    let (sender, events) = mpsc::channel();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    append_app_log(&format!(
+                        "MQTT publisher skipped {} monitor events",
+                        skipped
+                    ));
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    thread::spawn(move || run_publisher(settings, events));
+}
+
+#[tauri::command]
+pub fn get_mqtt_status() -> MqttStats {
+    STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default()
+}
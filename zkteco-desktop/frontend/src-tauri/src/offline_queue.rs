@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::State;
+
+use crate::devices::{flush_attendance_queue, queued_attendance};
+use crate::settings::SharedSettings;
+use crate::{
+    append_app_log, check_backend_health, current_backend_port, health_endpoint, BackendPort,
+};
+
+// Drains attendance_queue.jsonl (punches captured natively while the backend or network was
+// down) as soon as the backend answers its health check again, instead of waiting for the next
+// scheduled poll
+const DRAIN_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default)]
+struct DrainState {
+    last_drain_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    delivered_total: u64,
+}
+
+static DRAIN: Mutex<DrainState> = Mutex::new(DrainState {
+    last_drain_at: None,
+    last_error: None,
+    delivered_total: 0,
+});
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OfflineQueueStatus {
+    depth: usize,
+    per_device: BTreeMap<String, usize>,
+    // Records without a device serial number can't be handed to the backend
+    without_serial: usize,
+    oldest_timestamp: Option<String>,
+    backend_reachable: bool,
+    last_drain_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    delivered_total: u64,
+}
+
+// Hand the queue to the backend and remember the outcome for get_offline_queue_status
+pub async fn drain_queue(backend_port: u16) -> Result<usize, String> {
+    let result = flush_attendance_queue(backend_port).await;
+    if let Ok(mut state) = DRAIN.lock() {
+        match &result {
+            Ok(delivered) => {
+                state.last_drain_at = Some(Utc::now());
+                state.last_error = None;
+                state.delivered_total += *delivered as u64;
+            }
+            Err(err) => state.last_error = Some(err.clone()),
+        }
+    }
+    if let Ok(count @ 1..) = result {
+        append_app_log(&format!(
+            "Handed {} queued attendance records to the backend",
+            count
+        ));
+    }
+    result
+}
+
+pub fn spawn_queue_drain(settings: SharedSettings, backend_port: BackendPort) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(DRAIN_INTERVAL).await;
+            if queued_attendance().is_empty() {
+                continue;
+            }
+            let port = current_backend_port(&backend_port);
+            if !check_backend_health(&health_endpoint(port, &settings)).await {
+                continue;
+            }
+            // Still failing: records stay queued for the next round
+            if let Err(err) = drain_queue(port).await {
+                eprintln!("{}", err);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_offline_queue_status(
+    settings: State<'_, SharedSettings>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<OfflineQueueStatus, String> {
+    let queued = queued_attendance();
+    let mut per_device = BTreeMap::new();
+    for record in &queued {
+        *per_device.entry(record.device_id.clone()).or_insert(0) += 1;
+    }
+    let endpoint = health_endpoint(current_backend_port(&backend_port), &settings);
+    let backend_reachable = check_backend_health(&endpoint).await;
+    let state = DRAIN
+        .lock()
+        .map(|state| state.clone())
+        .map_err(|e| format!("Failed to read offline queue status: {}", e))?;
+
+    Ok(OfflineQueueStatus {
+        depth: queued.len(),
+        per_device,
+        without_serial: queued
+            .iter()
+            .filter(|r| r.serial_number.as_deref().unwrap_or("").is_empty())
+            .count(),
+        oldest_timestamp: queued.iter().map(|r| r.timestamp.clone()).min(),
+        backend_reachable,
+        last_drain_at: state.last_drain_at,
+        last_error: state.last_error,
+        delivered_total: state.delivered_total,
+    })
+}
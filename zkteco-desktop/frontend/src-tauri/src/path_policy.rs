@@ -0,0 +1,234 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::backup::default_backup_dir;
+use crate::resolve_base_data_dir;
+
+// Every command that reads or writes a file the frontend names goes through check_path. The
+// path must be absolute and resolve under a location the user picked in a save dialog, a
+// folder listed in export_allowed_dirs, their home/documents/downloads/desktop folders or
+// (for reading, and for the backups folder) the app data folder. Device namespaces (\\?\,
+// \\.\), unlisted UNC shares, alternate data streams, `..` hops, hidden folders in home and
+// system locations are refused, as is writing through a symbolic link.
+const MAX_APPROVED: usize = 32;
+
+static APPROVED: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static ALLOWED_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Folders from export_allowed_dirs (applied with the other runtime settings)
+pub fn set_allowed_dirs(dirs: &[String]) {
+    let dirs = dirs
+        .iter()
+        .map(|dir| dir.trim())
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| canonical(PathBuf::from(dir)))
+        .collect();
+    if let Ok(mut allowed) = ALLOWED_DIRS.lock() {
+        *allowed = dirs;
+    }
+}
+
+// Remember a location the user picked in a native dialog
+pub fn approve(path: &Path) {
+    let path = resolve(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Ok(mut approved) = APPROVED.lock() {
+        if approved.len() >= MAX_APPROVED {
+            approved.remove(0);
+        }
+        approved.push(path);
+    }
+}
+
+fn canonical(path: PathBuf) -> PathBuf {
+    path.canonicalize().unwrap_or(path)
+}
+
+// Canonical form of a path that may not exist yet: the nearest existing ancestor resolved
+// (following symlinks) with the missing tail appended
+fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    while fs::symlink_metadata(existing).is_err() {
+        missing.push(
+            existing
+                .file_name()
+                .ok_or_else(|| format!("Failed to resolve {:?}", path))?,
+        );
+        existing = existing
+            .parent()
+            .ok_or_else(|| format!("Failed to resolve {:?}", path))?;
+    }
+    let mut resolved = existing
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", path, e))?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+fn is_device_namespace(raw: &str) -> bool {
+    let raw = raw.replace('/', "\\");
+    raw.starts_with("\\\\?\\") || raw.starts_with("\\\\.\\") || raw.starts_with("\\??\\")
+}
+
+fn is_unc(raw: &str) -> bool {
+    raw.starts_with("\\\\") || raw.starts_with("//")
+}
+
+fn system_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = if cfg!(windows) {
+        [
+            "SystemRoot",
+            "windir",
+            "ProgramFiles",
+            "ProgramFiles(x86)",
+            "ProgramW6432",
+            "ProgramData",
+        ]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .collect()
+    } else {
+        [
+            "/etc",
+            "/usr",
+            "/bin",
+            "/sbin",
+            "/lib",
+            "/lib64",
+            "/boot",
+            "/proc",
+            "/sys",
+            "/dev",
+            "/var",
+            "/System",
+            "/Library",
+            "/Applications",
+            "/private/etc",
+            "/private/var",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect()
+    };
+    // The install folder holds the shell and the backend sidecar
+    if let Some(install_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(install_dir);
+    }
+    dirs.into_iter().map(canonical).collect()
+}
+
+fn user_dirs() -> Vec<PathBuf> {
+    [
+        dirs::home_dir(),
+        dirs::document_dir(),
+        dirs::download_dir(),
+        dirs::desktop_dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(canonical)
+    .collect()
+}
+
+// Dot folders (and AppData on Windows) hold other programs' configuration and keys
+fn is_hidden_in_home(path: &Path) -> bool {
+    let Some(home) = dirs::home_dir().map(canonical) else {
+        return false;
+    };
+    let Some(Component::Normal(first)) = path
+        .strip_prefix(&home)
+        .ok()
+        .and_then(|rest| rest.components().next())
+    else {
+        return false;
+    };
+    let first = first.to_string_lossy();
+    first.starts_with('.') || (cfg!(windows) && first.eq_ignore_ascii_case("AppData"))
+}
+
+fn is_under(path: &Path, roots: &Mutex<Vec<PathBuf>>) -> bool {
+    roots
+        .lock()
+        .map(|roots| roots.iter().any(|root| path.starts_with(root)))
+        .unwrap_or(false)
+}
+
+// Validate a path from the frontend and return its resolved form
+pub fn check_path(raw: &str, access: Access) -> Result<PathBuf, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err("No path given".to_string());
+    }
+    if is_device_namespace(raw) {
+        return Err(format!("'{}' is a device path, not a file location", raw));
+    }
+    let path = Path::new(raw);
+    if !path.is_absolute() {
+        return Err(format!("'{}' is not an absolute path", raw));
+    }
+    for component in path.components() {
+        match component {
+            Component::ParentDir => return Err(format!("'{}' must not contain '..'", raw)),
+            Component::Normal(part) if cfg!(windows) && part.to_string_lossy().contains(':') => {
+                return Err(format!("'{}' names an alternate data stream", raw))
+            }
+            _ => {}
+        }
+    }
+    if access == Access::Write
+        && fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+    {
+        return Err(format!(
+            "'{}' is a symbolic link; refusing to write through it",
+            raw
+        ));
+    }
+
+    let resolved = resolve(path)?;
+    if let Some(dir) = system_dirs().iter().find(|dir| resolved.starts_with(dir)) {
+        return Err(format!("'{}' is inside the system folder {:?}", raw, dir));
+    }
+    if is_under(&resolved, &APPROVED) || is_under(&resolved, &ALLOWED_DIRS) {
+        return Ok(resolved);
+    }
+    if is_unc(raw) {
+        return Err(format!(
+            "Network path '{}' is not listed in export_allowed_dirs",
+            raw
+        ));
+    }
+
+    // The app's own databases, keys and settings are never export targets
+    if resolved.starts_with(canonical(resolve_base_data_dir())) {
+        return match access {
+            Access::Read => Ok(resolved),
+            Access::Write if resolved.starts_with(canonical(default_backup_dir())) => Ok(resolved),
+            Access::Write => Err(format!(
+                "'{}' is inside the app data folder; choose another location",
+                raw
+            )),
+        };
+    }
+    if is_hidden_in_home(&resolved) {
+        return Err(format!("'{}' is inside a hidden folder", raw));
+    }
+    if user_dirs().iter().any(|dir| resolved.starts_with(dir)) {
+        return Ok(resolved);
+    }
+    Err(format!(
+        "'{}' is outside the folders the app may use; choose a location in your user folder \
+         or add its folder to export_allowed_dirs",
+        raw
+    ))
+}
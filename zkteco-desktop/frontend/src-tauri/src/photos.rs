@@ -0,0 +1,393 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::NaiveDateTime;
+use image::ImageFormat;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::ipc::Response;
+use tauri::State;
+
+use crate::database::{archive_db_path, run_db};
+use crate::lock;
+use crate::settings::{current_settings, SharedSettings};
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Verification photos pushed by ADMS terminals (fdata?table=ATTPHOTO). Photos are kept as the
+// device names them, punch_photos/<serial>/<YYYYMMDDHHMMSS>-<user id>.jpg, with a small JPEG
+// thumbnail under thumbs/. The store is capped at photo_store_max_mb (oldest photos go first)
+// and photos whose punch never reached the database are dropped after a grace period.
+const THUMBNAIL_SIZE: u32 = 160;
+const ORPHAN_GRACE: Duration = Duration::from_secs(7 * 24 * 3600);
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+const PHOTO_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PhotoStoreStatus {
+    path: String,
+    photos: usize,
+    bytes: u64,
+    max_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct PhotoCleanupResult {
+    orphans_removed: usize,
+    over_cap_removed: usize,
+    bytes_freed: u64,
+}
+
+struct StoredPhoto {
+    path: PathBuf,
+    thumbnail: PathBuf,
+    user_id: String,
+    taken_at: NaiveDateTime,
+    modified: SystemTime,
+    bytes: u64,
+}
+
+pub fn photos_dir() -> PathBuf {
+    resolve_app_data_dir().join("punch_photos")
+}
+
+fn thumbnail_path(photo: &Path) -> PathBuf {
+    let name = photo.file_name().unwrap_or_default();
+    photo.with_file_name("thumbs").join(name)
+}
+
+// Device serials and file names end up in paths, so only plain names are accepted
+fn safe_component(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// "20240501080211-42.jpg" -> (user id, punch time)
+fn parse_photo_name(name: &str) -> Option<(String, NaiveDateTime)> {
+    let stem = name.strip_suffix(".jpg")?;
+    let (taken_at, user_id) = stem.split_once('-')?;
+    if !safe_component(user_id) {
+        return None;
+    }
+    let taken_at = NaiveDateTime::parse_from_str(taken_at, PHOTO_TIME_FORMAT).ok()?;
+    Some((user_id.to_string(), taken_at))
+}
+
+fn photo_file_name(user_id: &str, timestamp: &str) -> Option<String> {
+    let value = timestamp.get(..19).unwrap_or(timestamp);
+    let taken_at = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()?;
+    Some(format!(
+        "{}-{}.jpg",
+        taken_at.format(PHOTO_TIME_FORMAT),
+        user_id
+    ))
+}
+
+fn write_thumbnail(photo: &Path, data: &[u8]) -> Result<PathBuf, String> {
+    let thumbnail = thumbnail_path(photo);
+    let image = image::load_from_memory(data)
+        .map_err(|e| format!("Unreadable photo {:?}: {}", photo, e))?;
+    let mut encoded = Cursor::new(Vec::new());
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgb8()
+        .write_to(&mut encoded, ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    if let Some(parent) = thumbnail.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    fs::write(&thumbnail, encoded.into_inner())
+        .map_err(|e| format!("Failed to write thumbnail: {}", e))?;
+    Ok(thumbnail)
+}
+
+pub fn is_photo_upload(target: &str) -> bool {
+    target.starts_with("/iclock/fdata") && target.contains("table=ATTPHOTO")
+}
+
+// Store an ATTPHOTO upload: "PIN=<file name>\nSN=<serial>\nsize=<n>\nCMD=uploadphoto\0<jpeg>"
+pub fn store_pushed_photo(target: &str, body: &[u8], max_bytes: u64) -> Result<String, String> {
+    let split = body
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or("Photo upload without image data")?;
+    let (header, data) = (String::from_utf8_lossy(&body[..split]), &body[split + 1..]);
+    let field = |name: &str| {
+        header
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(name))
+            .map(|value| value.trim().to_string())
+    };
+    let serial = target
+        .split(['?', '&'])
+        .find_map(|part| part.strip_prefix("SN="))
+        .map(str::to_string)
+        .or_else(|| field("SN="))
+        .filter(|serial| safe_component(serial))
+        .ok_or("Photo upload without a valid device serial number")?;
+    let name = field("PIN=")
+        .filter(|name| parse_photo_name(name).is_some())
+        .ok_or_else(|| format!("Unexpected photo name in upload from {}", serial))?;
+
+    let path = photos_dir().join(&serial).join(&name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    fs::write(&path, data).map_err(|e| format!("Failed to store photo {:?}: {}", path, e))?;
+    // A photo the UI can't thumbnail is still kept; get_punch_photo retries on demand
+    if let Err(err) = write_thumbnail(&path, data) {
+        eprintln!("{}", err);
+    }
+    enforce_cap(max_bytes);
+    Ok(format!("{}/{}", serial, name))
+}
+
+fn stored_photos() -> Vec<StoredPhoto> {
+    let mut photos = Vec::new();
+    let Ok(devices) = fs::read_dir(photos_dir()) else {
+        return photos;
+    };
+    for device in devices.flatten().filter(|entry| entry.path().is_dir()) {
+        let Ok(entries) = fs::read_dir(device.path()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (Some((user_id, taken_at)), Ok(metadata)) =
+                (parse_photo_name(&name), entry.metadata())
+            else {
+                continue;
+            };
+            let path = entry.path();
+            let thumbnail = thumbnail_path(&path);
+            let thumbnail_bytes = fs::metadata(&thumbnail).map(|m| m.len()).unwrap_or(0);
+            photos.push(StoredPhoto {
+                thumbnail,
+                path,
+                user_id,
+                taken_at,
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                bytes: metadata.len() + thumbnail_bytes,
+            });
+        }
+    }
+    photos
+}
+
+fn remove_photo(photo: &StoredPhoto) -> u64 {
+    let _ = fs::remove_file(&photo.thumbnail);
+    match fs::remove_file(&photo.path) {
+        Ok(()) => photo.bytes,
+        Err(err) => {
+            eprintln!("Failed to remove photo {:?}: {}", photo.path, err);
+            0
+        }
+    }
+}
+
+// Delete the oldest photos until the store fits in max_bytes; returns (removed, bytes freed)
+fn enforce_cap(max_bytes: u64) -> (usize, u64) {
+    let mut photos = stored_photos();
+    let mut total: u64 = photos.iter().map(|p| p.bytes).sum();
+    if total <= max_bytes {
+        return (0, 0);
+    }
+    photos.sort_by_key(|p| p.modified);
+    let (mut removed, mut freed) = (0, 0);
+    for photo in &photos {
+        if total <= max_bytes {
+            break;
+        }
+        let bytes = remove_photo(photo);
+        total = total.saturating_sub(bytes);
+        freed += bytes;
+        removed += 1;
+    }
+    (removed, freed)
+}
+
+// Whether the punch a photo belongs to is in zkteco_app.db or the archive
+fn punch_exists(
+    connection: &Connection,
+    with_archive: bool,
+    photo: &StoredPhoto,
+) -> Result<bool, String> {
+    let select = |table: &str| {
+        format!(
+            "SELECT 1 FROM {} WHERE user_id = ?1 AND timestamp IN (?2, ?3)",
+            table
+        )
+    };
+    let sql = if with_archive {
+        format!(
+            "{} UNION ALL {} LIMIT 1",
+            select("main.attendance_logs"),
+            select("archive.attendance_logs")
+        )
+    } else {
+        format!("{} LIMIT 1", select("main.attendance_logs"))
+    };
+    connection
+        .query_row(
+            &sql,
+            params![
+                photo.user_id,
+                photo.taken_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                photo.taken_at.format("%Y-%m-%dT%H:%M:%S").to_string()
+            ],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|found| found.is_some())
+        .map_err(|e| format!("Failed to look up punch for photo: {}", e))
+}
+
+async fn remove_orphans() -> Result<(usize, u64), String> {
+    let candidates: Vec<StoredPhoto> = stored_photos()
+        .into_iter()
+        .filter(|p| p.modified.elapsed().is_ok_and(|age| age > ORPHAN_GRACE))
+        .collect();
+    if candidates.is_empty() {
+        return Ok((0, 0));
+    }
+    run_db(move |connection| {
+        let archive = archive_db_path();
+        let with_archive = archive.exists();
+        if with_archive {
+            connection
+                .execute(
+                    "ATTACH DATABASE ?1 AS archive",
+                    [archive.to_string_lossy().to_string()],
+                )
+                .map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+        }
+        let (mut removed, mut freed) = (0, 0);
+        for photo in &candidates {
+            if !punch_exists(connection, with_archive, photo)? {
+                freed += remove_photo(photo);
+                removed += 1;
+            }
+        }
+        Ok((removed, freed))
+    })
+    .await
+}
+
+pub async fn run_cleanup(max_bytes: u64) -> Result<PhotoCleanupResult, String> {
+    let (orphans_removed, orphan_bytes) = remove_orphans().await?;
+    let (over_cap_removed, cap_bytes) = if max_bytes > 0 {
+        tauri::async_runtime::spawn_blocking(move || enforce_cap(max_bytes))
+            .await
+            .map_err(|e| format!("Photo cleanup failed: {}", e))?
+    } else {
+        (0, 0)
+    };
+    let result = PhotoCleanupResult {
+        orphans_removed,
+        over_cap_removed,
+        bytes_freed: orphan_bytes + cap_bytes,
+    };
+    if orphans_removed + over_cap_removed > 0 {
+        append_app_log(&format!(
+            "Photo store cleanup removed {} orphaned and {} over-cap photos ({} bytes)",
+            orphans_removed, over_cap_removed, result.bytes_freed
+        ));
+    }
+    Ok(result)
+}
+
+pub fn max_photo_bytes(settings: &SharedSettings) -> u64 {
+    current_settings(settings).photo_store_max_mb * 1024 * 1024
+}
+
+pub fn spawn_photo_maintenance(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(MAINTENANCE_INTERVAL).await;
+            if let Err(err) = run_cleanup(max_photo_bytes(&settings)).await {
+                eprintln!("{}", err);
+                append_app_log(&format!("Scheduled photo cleanup failed: {}", err));
+            }
+        }
+    });
+}
+
+// Photo (or thumbnail) of an attendance record as raw JPEG bytes
+#[tauri::command]
+pub async fn get_punch_photo(record_id: i64, thumbnail: Option<bool>) -> Result<Response, String> {
+    let (user_id, serial, timestamp) = run_db(move |connection| {
+        connection
+            .query_row(
+                "SELECT user_id, serial_number, timestamp FROM attendance_logs WHERE id = ?1",
+                [record_id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read attendance record: {}", e))?
+            .ok_or_else(|| format!("Attendance record {} not found", record_id))
+    })
+    .await?;
+
+    let name = photo_file_name(&user_id, &timestamp)
+        .ok_or_else(|| format!("Attendance record {} has no usable timestamp", record_id))?;
+    let path = match serial.filter(|serial| safe_component(serial)) {
+        Some(serial) => photos_dir().join(serial).join(&name),
+        // Records without a serial: look through every device folder
+        None => stored_photos()
+            .into_iter()
+            .find(|p| p.path.file_name().is_some_and(|n| n == name.as_str()))
+            .map(|p| p.path)
+            .unwrap_or_else(|| photos_dir().join(&name)),
+    };
+    if !path.exists() {
+        return Err(format!(
+            "No photo stored for attendance record {}",
+            record_id
+        ));
+    }
+
+    let data = tauri::async_runtime::spawn_blocking(move || {
+        if !thumbnail.unwrap_or(false) {
+            return fs::read(&path).map_err(|e| format!("Failed to read photo: {}", e));
+        }
+        let thumbnail = thumbnail_path(&path);
+        if !thumbnail.exists() {
+            let original = fs::read(&path).map_err(|e| format!("Failed to read photo: {}", e))?;
+            write_thumbnail(&path, &original)?;
+        }
+        fs::read(&thumbnail).map_err(|e| format!("Failed to read thumbnail: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Photo task failed: {}", e))??;
+    Ok(Response::new(data))
+}
+
+#[tauri::command]
+pub fn get_photo_store_status(settings: State<SharedSettings>) -> Result<PhotoStoreStatus, String> {
+    let photos = stored_photos();
+    Ok(PhotoStoreStatus {
+        path: photos_dir().to_string_lossy().to_string(),
+        photos: photos.len(),
+        bytes: photos.iter().map(|p| p.bytes).sum(),
+        max_bytes: max_photo_bytes(&settings),
+    })
+}
+
+// Remove orphaned photos and trim the store to its cap now
+#[tauri::command]
+pub async fn cleanup_photo_store(
+    settings: State<'_, SharedSettings>,
+) -> Result<PhotoCleanupResult, String> {
+    lock::ensure_unlocked()?;
+    run_cleanup(max_photo_bytes(&settings)).await
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use tauri::State;
+
+use crate::devices::{run_native, stage_records, to_staged};
+use crate::drift::{measure_drift, record_drift};
+use crate::monitor::{MonitorBus, MonitorEvent};
+use crate::offline_queue::drain_queue;
+use crate::registry::{active_pull_devices, DeviceEntry, DeviceRegistry};
+use crate::settings::{current_settings, SharedSettings};
+use crate::{append_app_log, current_backend_port, resolve_app_data_dir, BackendPort};
+
+// Scheduled attendance collection done by the shell itself: each active pull device is polled
+// natively on its own interval, new records are staged in the attendance queue and the queue is
+// handed to the backend whenever it is reachable. Keeps collection going while Flask is down.
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const POLL_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DevicePollStatus {
+    device_id: String,
+    interval_minutes: u64,
+    last_poll: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    records_staged: usize,
+    #[serde(skip)]
+    last_attempt: Option<Instant>,
+}
+
+pub type PollerStatus = Arc<Mutex<HashMap<String, DevicePollStatus>>>;
+
+// Newest record timestamp already staged per device, so each poll only stages new punches
+fn watermarks_path() -> PathBuf {
+    resolve_app_data_dir().join("poller_watermarks.json")
+}
+
+fn load_watermarks() -> HashMap<String, NaiveDateTime> {
+    fs::read_to_string(watermarks_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_watermarks(watermarks: &HashMap<String, NaiveDateTime>) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(watermarks)
+        .map_err(|e| format!("Failed to serialize poller watermarks: {}", e))?;
+    fs::write(watermarks_path(), content)
+        .map_err(|e| format!("Failed to save poller watermarks: {}", e))
+}
+
+// Per-device override first, then the global interval; 0 disables polling
+fn interval_for(settings: &SharedSettings, device_id: &str) -> u64 {
+    let config = current_settings(settings);
+    config
+        .device_poll_overrides
+        .get(device_id)
+        .copied()
+        .unwrap_or(config.device_poll_interval_minutes)
+}
+
+// Returns the number of newly staged records and the new watermark
+async fn poll_device(
+    device: DeviceEntry,
+    watermark: Option<NaiveDateTime>,
+) -> Result<(usize, Option<NaiveDateTime>), String> {
+    let records = {
+        let device = device.clone();
+        run_native(move || {
+            let mut session = device.open_session(POLL_TIMEOUT)?;
+            let drift = measure_drift(&mut session);
+            let records = session.read_attendance()?;
+            session.disconnect()?;
+            if let Ok((drift_seconds, corrected)) = drift {
+                record_drift(&device.id, drift_seconds, corrected);
+            }
+            Ok(records)
+        })
+        .await?
+    };
+
+    let fresh: Vec<_> = records
+        .into_iter()
+        .filter(|record| watermark.map(|w| record.timestamp > w).unwrap_or(true))
+        .collect();
+    let newest = fresh
+        .iter()
+        .map(|record| record.timestamp)
+        .max()
+        .or(watermark);
+    let staged = stage_records(&to_staged(&device, &fresh))?;
+    Ok((staged, newest))
+}
+
+pub fn spawn_attendance_poller(
+    registry: DeviceRegistry,
+    settings: SharedSettings,
+    backend_port: BackendPort,
+    status: PollerStatus,
+    bus: MonitorBus,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut watermarks = load_watermarks();
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let mut polled = false;
+            for device in active_pull_devices(&registry) {
+                let interval = interval_for(&settings, &device.id);
+                let due = interval > 0
+                    && status
+                        .lock()
+                        .ok()
+                        .and_then(|s| s.get(&device.id).and_then(|d| d.last_attempt))
+                        .map(|at| at.elapsed() >= Duration::from_secs(interval * 60))
+                        .unwrap_or(true);
+                if !due {
+                    continue;
+                }
+
+                let device_id = device.id.clone();
+                let result = poll_device(device, watermarks.get(&device_id).copied()).await;
+                polled = true;
+                if let Ok((staged, Some(newest))) = &result {
+                    watermarks.insert(device_id.clone(), *newest);
+                    if *staged > 0 {
+                        append_app_log(&format!(
+                            "Scheduled poll staged {} new records from {}",
+                            staged, device_id
+                        ));
+                    }
+                }
+
+                if let Ok(mut statuses) = status.lock() {
+                    let entry =
+                        statuses
+                            .entry(device_id.clone())
+                            .or_insert_with(|| DevicePollStatus {
+                                device_id: device_id.clone(),
+                                ..Default::default()
+                            });
+                    entry.interval_minutes = interval;
+                    entry.last_attempt = Some(Instant::now());
+                    match result {
+                        Ok((staged, _)) => {
+                            entry.last_poll = Some(Utc::now());
+                            entry.last_error = None;
+                            entry.records_staged += staged;
+                        }
+                        Err(err) => {
+                            append_app_log(&format!(
+                                "Scheduled poll failed for {}: {}",
+                                device_id, err
+                            ));
+                            let _ = bus.send(MonitorEvent::SyncFailed(serde_json::json!({
+                                "job": "scheduled_poll",
+                                "device_id": device_id,
+                                "error": err,
+                            })));
+                            entry.last_error = Some(err);
+                        }
+                    }
+                }
+            }
+
+            if polled {
+                if let Err(err) = save_watermarks(&watermarks) {
+                    eprintln!("{}", err);
+                }
+                // Backend down: records stay queued for the next round
+                if let Err(err) = drain_queue(current_backend_port(&backend_port)).await {
+                    eprintln!("{}", err);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_poller_status(status: State<PollerStatus>) -> Result<Vec<DevicePollStatus>, String> {
+    let statuses = status
+        .lock()
+        .map_err(|e| format!("Failed to read poller status: {}", e))?;
+    let mut statuses: Vec<DevicePollStatus> = statuses.values().cloned().collect();
+    statuses.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+    Ok(statuses)
+}
@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{Duration, SystemTime};
+
+use chrono::{Local, NaiveDate};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::{device_filter, parse_range, ExportRange};
+use crate::report::{write_attendance_slip, write_check_in_sheet, ReportTemplate};
+use crate::secrets::random_hex;
+use crate::settings::{current_settings, SharedSettings};
+use crate::{append_app_log, lock};
+
+// Printing without exporting first: the document is rendered to a PDF in a spool folder under
+// the temp dir and handed to the OS print queue - CUPS `lp` on Linux and macOS, the registered
+// PDF application's PrintTo verb on Windows. Windows hands the file over asynchronously, so
+// spooled files are kept and pruned once they are a day old instead of being deleted right
+// after the job is queued.
+const SPOOL_DIR: &str = "ztkapp_print";
+const SPOOL_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PrinterInfo {
+    name: String,
+    is_default: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PrintResult {
+    // None when the job went to the OS default printer
+    printer: Option<String>,
+    // Job id reported by the print queue, where it reports one
+    job: Option<String>,
+    rows: u64,
+}
+
+fn spool_path(name: &str) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join(SPOOL_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create print folder {:?}: {}", dir, e))?;
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let stale = entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|age| age > SPOOL_MAX_AGE);
+            if stale {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+    Ok(dir.join(format!("{}_{}", random_hex(8), name)))
+}
+
+// Single-quoted PowerShell literal
+fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+pub fn powershell(script: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", script]);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so printing does not flash a console
+        command.creation_flags(0x0800_0000);
+    }
+    command
+}
+
+async fn run(mut command: Command, what: &str) -> Result<String, String> {
+    let what = what.to_string();
+    let output: Output = tauri::async_runtime::spawn_blocking(move || command.output())
+        .await
+        .map_err(|e| format!("{} failed: {}", what, e))?
+        .map_err(|e| format!("Failed to start {}: {}", what, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("{} failed ({})", what, output.status)
+        } else {
+            format!("{} failed: {}", what, stderr)
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+async fn installed_printers() -> Result<Vec<PrinterInfo>, String> {
+    if cfg!(windows) {
+        let output = run(
+            powershell(
+                "Get-CimInstance Win32_Printer | Select-Object Name,Default | ConvertTo-Json -Compress",
+            ),
+            "Listing printers",
+        )
+        .await?;
+        // ConvertTo-Json gives a bare object for a single printer and nothing for none
+        let printers = match serde_json::from_str::<serde_json::Value>(output.trim()) {
+            Ok(serde_json::Value::Array(printers)) => printers,
+            Ok(printer @ serde_json::Value::Object(_)) => vec![printer],
+            _ => Vec::new(),
+        };
+        return Ok(printers
+            .iter()
+            .filter_map(|printer| {
+                Some(PrinterInfo {
+                    name: printer.get("Name")?.as_str()?.to_string(),
+                    is_default: printer
+                        .get("Default")
+                        .and_then(|d| d.as_bool())
+                        .unwrap_or(false),
+                })
+            })
+            .collect());
+    }
+
+    let mut destinations = Command::new("lpstat");
+    destinations.arg("-e").env("LC_ALL", "C");
+    let names = run(destinations, "Listing printers").await?;
+    let mut default = Command::new("lpstat");
+    default.arg("-d").env("LC_ALL", "C");
+    // "system default destination: NAME", or "no system default destination"
+    let default = run(default, "Reading the default printer")
+        .await
+        .ok()
+        .and_then(|output| {
+            output
+                .split_once(':')
+                .map(|(_, name)| name.trim().to_string())
+        });
+    Ok(names
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| PrinterInfo {
+            name: name.to_string(),
+            is_default: default.as_deref() == Some(name),
+        })
+        .collect())
+}
+
+// Queue the PDF and return the job id where the print system reports one
+async fn submit(path: &Path, printer: Option<&str>, title: &str) -> Result<Option<String>, String> {
+    if cfg!(windows) {
+        let file = ps_quote(&path.to_string_lossy());
+        let script = match printer {
+            Some(printer) => format!(
+                "Start-Process -FilePath {} -Verb PrintTo -ArgumentList {} -WindowStyle Hidden",
+                file,
+                ps_quote(&format!("\"{}\"", printer))
+            ),
+            None => format!(
+                "Start-Process -FilePath {} -Verb Print -WindowStyle Hidden",
+                file
+            ),
+        };
+        run(powershell(&script), "Printing")
+            .await
+            .map_err(|e| format!("{} (is a PDF reader installed?)", e))?;
+        return Ok(None);
+    }
+
+    let mut command = Command::new("lp");
+    command.env("LC_ALL", "C").args(["-t", title]);
+    if let Some(printer) = printer {
+        command.args(["-d", printer]);
+    }
+    command.arg(path);
+    let output = run(command, "Printing").await;
+    let _ = std::fs::remove_file(path);
+    // "request id is Office-42 (1 file(s))"
+    Ok(output?
+        .split_whitespace()
+        .skip_while(|word| *word != "is")
+        .nth(1)
+        .map(str::to_string))
+}
+
+fn selected_printer(printer: Option<String>, settings: &SharedSettings) -> Option<String> {
+    printer
+        .or_else(|| current_settings(settings).printer)
+        .map(|printer| printer.trim().to_string())
+        .filter(|printer| !printer.is_empty())
+}
+
+async fn print_document<F>(
+    name: String,
+    title: String,
+    printer: Option<String>,
+    render: F,
+) -> Result<PrintResult, String>
+where
+    F: FnOnce(&rusqlite::Connection, &Path) -> Result<u64, String> + Send + 'static,
+{
+    if let Some(printer) = &printer {
+        let printers = installed_printers().await?;
+        if !printers.iter().any(|p| &p.name == printer) {
+            return Err(format!("Printer '{}' is not installed", printer));
+        }
+    }
+    let path = spool_path(&name)?;
+    let target = path.clone();
+    let rendered = run_db(move |connection| render(connection, &target)).await;
+    let rows = match rendered {
+        Ok(rows) => rows,
+        Err(err) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(err);
+        }
+    };
+    let job = submit(&path, printer.as_deref(), &title).await?;
+    append_app_log(&format!(
+        "Printed {} on {}",
+        title,
+        printer.as_deref().unwrap_or("the default printer")
+    ));
+    Ok(PrintResult { printer, job, rows })
+}
+
+#[tauri::command]
+pub async fn list_printers() -> Result<Vec<PrinterInfo>, String> {
+    installed_printers().await
+}
+
+// Reception's daily check-in sheet; date defaults to today
+#[tauri::command]
+pub async fn print_check_in_sheet(
+    date: Option<String>,
+    devices: Option<Vec<String>>,
+    printer: Option<String>,
+    template: Option<ReportTemplate>,
+    settings: State<'_, SharedSettings>,
+) -> Result<PrintResult, String> {
+    lock::ensure_unlocked()?;
+    let date: NaiveDate = match date {
+        Some(date) => {
+            parse_range(&ExportRange {
+                from: date.clone(),
+                to: date,
+            })?
+            .0
+        }
+        None => Local::now().date_naive(),
+    };
+    let devices = device_filter(&devices);
+    let printer = selected_printer(printer, &settings);
+    let template = template.unwrap_or_else(|| ReportTemplate {
+        title: Some("Check-in sheet".to_string()),
+        ..Default::default()
+    });
+    let result = print_document(
+        format!("check_in_{}.pdf", date),
+        format!("Check-in sheet {}", date),
+        printer,
+        move |connection, path| write_check_in_sheet(connection, date, devices, &template, path),
+    )
+    .await;
+    audited("print_check_in_sheet", &date.to_string(), result)
+}
+
+// One employee's attendance slip for a period
+#[tauri::command]
+pub async fn print_attendance_slip(
+    user_id: String,
+    range: ExportRange,
+    printer: Option<String>,
+    template: Option<ReportTemplate>,
+    settings: State<'_, SharedSettings>,
+) -> Result<PrintResult, String> {
+    lock::ensure_unlocked()?;
+    let user_id = user_id.trim().to_string();
+    if user_id.is_empty() {
+        return Err("Choose an employee to print".to_string());
+    }
+    let range = parse_range(&range)?;
+    let printer = selected_printer(printer, &settings);
+    let template = template.unwrap_or_else(|| ReportTemplate {
+        title: Some("Attendance slip".to_string()),
+        ..Default::default()
+    });
+    let user = user_id.clone();
+    let file_user: String = user_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    let result = print_document(
+        format!("slip_{}_{}_{}.pdf", file_user, range.0, range.1),
+        format!("Attendance slip {} {} to {}", user_id, range.0, range.1),
+        printer,
+        move |connection, path| write_attendance_slip(connection, &user, range, &template, path),
+    )
+    .await;
+    audited("print_attendance_slip", &user_id, result)
+}
@@ -0,0 +1,281 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audit::audited;
+use crate::backup::{start_backend_after_maintenance, stop_backend_for_maintenance};
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::lock;
+use crate::registry::{load_registry, DeviceRegistry};
+use crate::secrets::{forget_cached_secrets, migrate_plaintext_credentials};
+use crate::settings::{apply_runtime_settings, load_settings, SharedSettings};
+use crate::sync_status::{load_sync_state, SyncTracker};
+use crate::{append_app_log, resolve_base_data_dir};
+
+// Independent data sets, e.g. one per client company. The default profile uses the data folder
+// itself; every other profile gets profiles/<id>/ with its own database, shell settings,
+// device registry, queues and logs. profiles.json in the data folder lists them and remembers
+// the active one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+static ACTIVE_PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct ProfileList {
+    active: Option<String>,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProfileOverview {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProfileSwitchResult {
+    active: String,
+    previous: String,
+    backend_restarted: bool,
+}
+
+fn profiles_path() -> PathBuf {
+    resolve_base_data_dir().join("profiles.json")
+}
+
+fn load_profiles() -> ProfileList {
+    fs::read_to_string(profiles_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_profiles(list: &ProfileList) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(list)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    fs::write(profiles_path(), content).map_err(|e| format!("Failed to save profiles: {}", e))
+}
+
+pub fn active_profile() -> Option<String> {
+    ACTIVE_PROFILE.lock().ok().and_then(|active| active.clone())
+}
+
+fn set_active_profile(profile: Option<String>) {
+    if let Ok(mut guard) = ACTIVE_PROFILE.lock() {
+        *guard = profile;
+    }
+}
+
+// Called first thing at startup, before anything reads the data folder
+pub fn load_active_profile() {
+    let list = load_profiles();
+    let active = list
+        .active
+        .filter(|id| list.profiles.iter().any(|p| &p.id == id));
+    set_active_profile(active);
+}
+
+// Data folder of the active profile below `base`
+pub fn profile_dir(base: PathBuf) -> PathBuf {
+    let Some(profile) = active_profile() else {
+        return base;
+    };
+    let dir = base.join("profiles").join(profile);
+    if let Err(err) = fs::create_dir_all(&dir) {
+        eprintln!("Failed to create profile directory at {:?}: {}", dir, err);
+    }
+    dir
+}
+
+fn profile_label(profile: &Option<String>) -> String {
+    profile.as_deref().unwrap_or(DEFAULT_PROFILE).to_string()
+}
+
+// "Acme Corp." -> "acme-corp"
+fn slug(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Reload everything the shell keeps in memory from the (now active) profile's folder
+pub fn reload_profile_state(app: &AppHandle) {
+    let settings = load_settings();
+    apply_runtime_settings(&settings);
+    if let Ok(mut guard) = app.state::<SharedSettings>().lock() {
+        *guard = settings;
+    }
+    if let Ok(mut guard) = app.state::<DeviceRegistry>().lock() {
+        *guard = load_registry();
+    }
+    if let Ok(mut guard) = app.state::<SyncTracker>().lock() {
+        *guard = load_sync_state();
+    }
+    forget_cached_secrets();
+    migrate_plaintext_credentials(
+        &app.state::<DeviceRegistry>(),
+        &app.state::<SharedSettings>(),
+    );
+}
+
+async fn switch_with_backend_stopped(
+    app: &AppHandle,
+    target: Option<String>,
+) -> Result<ProfileSwitchResult, String> {
+    let previous = active_profile();
+    let was_running = stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, profile switch aborted", e))?;
+
+    let activate = |profile: Option<String>| -> Result<(), String> {
+        let mut list = load_profiles();
+        list.active = profile.clone();
+        save_profiles(&list)?;
+        set_active_profile(profile);
+        reload_profile_state(app);
+        Ok(())
+    };
+
+    let switched = match activate(target.clone()) {
+        Ok(()) if was_running => start_backend_after_maintenance(app).await.map(drop),
+        other => other,
+    };
+    if let Err(err) = switched {
+        // Back to the profile that was working
+        let rolled_back = activate(previous.clone());
+        if rolled_back.is_ok() && was_running {
+            let _ = start_backend_after_maintenance(app).await;
+        }
+        return Err(format!(
+            "Failed to switch to profile {} ({}); staying on {}",
+            profile_label(&target),
+            err,
+            profile_label(&previous)
+        ));
+    }
+
+    let result = ProfileSwitchResult {
+        active: profile_label(&target),
+        previous: profile_label(&previous),
+        backend_restarted: was_running,
+    };
+    if let Err(err) = app.emit("profile-changed", &result) {
+        eprintln!("Failed to emit profile-changed event: {}", err);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn list_profiles() -> Result<ProfileOverview, String> {
+    Ok(ProfileOverview {
+        active: profile_label(&active_profile()),
+        profiles: load_profiles().profiles,
+    })
+}
+
+#[tauri::command]
+pub fn create_profile(name: String) -> Result<Profile, String> {
+    lock::ensure_unlocked()?;
+    let name = name.trim().to_string();
+    let base = slug(&name);
+    if base.is_empty() {
+        return Err("Profile name needs at least one letter or digit".to_string());
+    }
+
+    let mut list = load_profiles();
+    let taken = |id: &str| id == DEFAULT_PROFILE || list.profiles.iter().any(|p| p.id == id);
+    let id = (1..)
+        .map(|n| {
+            if n == 1 {
+                base.clone()
+            } else {
+                format!("{}-{}", base, n)
+            }
+        })
+        .find(|id| !taken(id))
+        .unwrap_or(base);
+    let profile = Profile {
+        id,
+        name,
+        created_at: Utc::now(),
+    };
+    list.profiles.push(profile.clone());
+    save_profiles(&list)?;
+    append_app_log(&format!(
+        "Created profile {} ({})",
+        profile.name, profile.id
+    ));
+    Ok(profile)
+}
+
+// Make another profile active; the backend is restarted against its database
+#[tauri::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    profile_id: String,
+) -> Result<ProfileSwitchResult, String> {
+    lock::ensure_unlocked()?;
+    let target = match profile_id.as_str() {
+        DEFAULT_PROFILE => None,
+        id if load_profiles().profiles.iter().any(|p| p.id == id) => Some(id.to_string()),
+        id => return Err(format!("Unknown profile: {}", id)),
+    };
+    if target == active_profile() {
+        return Err(format!("Profile {} is already active", profile_id));
+    }
+
+    let result = switch_with_backend_stopped(&app, target).await;
+    match &result {
+        Ok(switched) => append_app_log(&format!(
+            "Switched profile from {} to {}",
+            switched.previous, switched.active
+        )),
+        Err(err) => append_app_log(&format!("Profile switch failed: {}", err)),
+    }
+    audited("switch_profile", &profile_id, result)
+}
+
+fn remove_profile(profile_id: &str) -> Result<(), String> {
+    if active_profile().as_deref() == Some(profile_id) {
+        return Err("Switch to another profile before deleting this one".to_string());
+    }
+    let mut list = load_profiles();
+    let before = list.profiles.len();
+    list.profiles.retain(|p| p.id != profile_id);
+    if list.profiles.len() == before {
+        return Err(format!("Unknown profile: {}", profile_id));
+    }
+    let dir = resolve_base_data_dir().join("profiles").join(profile_id);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete profile data: {}", e))?;
+    }
+    save_profiles(&list)
+}
+
+// Remove an inactive profile and all of its data
+#[tauri::command]
+pub fn delete_profile(
+    profile_id: String,
+    confirm_token: String,
+    tokens: State<ConfirmationTokens>,
+) -> Result<(), String> {
+    consume_token(&tokens, &confirm_token, "delete_profile", &profile_id)?;
+    let result = remove_profile(&profile_id);
+    if result.is_ok() {
+        append_app_log(&format!("Deleted profile {}", profile_id));
+    }
+    audited("delete_profile", &profile_id, result)
+}
@@ -0,0 +1,340 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::audit::audited;
+use crate::path_policy::{check_path, Access};
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Password-protected exports: the export is zipped and the zip encrypted with AES-256-GCM under
+// a key derived from the user's password with Argon2id, into "<name>.ztkenc". The file is
+// encrypted in chunks so large backups never sit in memory; each chunk's nonce carries its
+// index and the last one is flagged, so reordered or truncated files fail to open.
+// Layout: MAGIC | m_cost, t_cost, p_cost (u32 LE) | salt | nonce prefix | chunks
+const ENCRYPTED_EXTENSION: &str = "ztkenc";
+const MAGIC: &[u8; 8] = b"ZTKENC01";
+const MIN_PASSWORD_LENGTH: usize = 8;
+const ARGON2_M_COST: u32 = 64 * 1024; // KiB
+const ARGON2_T_COST: u32 = 3;
+const ARGON2_P_COST: u32 = 1;
+// Upper bounds for parameters read from a file, so a crafted header can't exhaust memory
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+const SALT_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + 12 + SALT_LEN + NONCE_PREFIX_LEN;
+const CHUNK_SIZE: usize = 1024 * 1024;
+const TAG_LEN: usize = 16;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DecryptResult {
+    files: Vec<String>,
+    destination: String,
+}
+
+// Rejects passwords that are too short; empty or missing means no protection
+pub fn check_password(password: Option<String>) -> Result<Option<String>, String> {
+    match password.filter(|password| !password.is_empty()) {
+        Some(password) if password.chars().count() < MIN_PASSWORD_LENGTH => Err(format!(
+            "Export password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )),
+        password => Ok(password),
+    }
+}
+
+pub fn staging_dir() -> Result<PathBuf, String> {
+    let dir = resolve_app_data_dir().join("export_staging");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    Ok(dir)
+}
+
+fn staging_path(file_name: &str) -> Result<PathBuf, String> {
+    Ok(staging_dir()?.join(file_name))
+}
+
+// Where an export should be written: the target itself, or with a password a staging file in
+// the app data folder, so the plaintext never lands in the user's folder
+pub fn write_path(target: &Path, password: &Option<String>) -> Result<PathBuf, String> {
+    match password {
+        None => Ok(target.to_path_buf()),
+        Some(_) => staging_path(
+            &target
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .ok_or("Export path has no file name")?,
+        ),
+    }
+}
+
+// Completes an export written to write_path(): with a password it is sealed into
+// "<target>.ztkenc", without one it stays where it is. Returns the value and the final path;
+// a staged plaintext is removed either way.
+pub async fn finish<T>(
+    result: Result<T, String>,
+    written: PathBuf,
+    target: &Path,
+    password: Option<String>,
+) -> Result<(T, String), String> {
+    let value = match (result, &password) {
+        (Ok(value), _) => value,
+        (Err(err), Some(_)) => {
+            let _ = fs::remove_file(&written);
+            return Err(err);
+        }
+        (Err(err), None) => return Err(err),
+    };
+    let Some(password) = password else {
+        return Ok((value, written.to_string_lossy().to_string()));
+    };
+    let target = target.to_path_buf();
+    let sealed = tauri::async_runtime::spawn_blocking(move || seal(&written, &target, &password))
+        .await
+        .map_err(|e| format!("Encryption task failed: {}", e))
+        .and_then(|result| result)?;
+    Ok((value, sealed.to_string_lossy().to_string()))
+}
+
+fn sealed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(ENCRYPTED_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn derive_key(password: &str, salt: &[u8], costs: [u32; 3]) -> Result<Aes256Gcm, String> {
+    let params = Params::new(costs[0], costs[1], costs[2], Some(32))
+        .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive key from password: {}", e))?;
+    Aes256Gcm::new_from_slice(&key).map_err(|e| format!("Failed to create cipher: {}", e))
+}
+
+fn chunk_nonce(prefix: &[u8], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+// Fills buf as far as the reader allows; a short count means end of file
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn zip_file(source: &Path, target: &Path) -> Result<(), String> {
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("Export has no file name")?;
+    let file = File::create(target).map_err(|e| format!("Failed to create archive: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    let mut input = File::open(source).map_err(|e| format!("Failed to read export: {}", e))?;
+    zip.start_file(name, options)
+        .map_err(|e| format!("Failed to create archive: {}", e))?;
+    io::copy(&mut input, &mut zip).map_err(|e| format!("Failed to archive export: {}", e))?;
+    zip.finish()
+        .map_err(|e| format!("Failed to write archive: {}", e))?;
+    Ok(())
+}
+
+fn encrypt_file(source: &Path, target: &Path, password: &str) -> Result<(), String> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut prefix = [0u8; NONCE_PREFIX_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut prefix);
+    let costs = [ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST];
+    let cipher = derive_key(password, &salt, costs)?;
+
+    let mut header = MAGIC.to_vec();
+    for cost in costs {
+        header.extend_from_slice(&cost.to_le_bytes());
+    }
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&prefix);
+
+    let write_error = |e: io::Error| format!("Failed to write encrypted export: {}", e);
+    let mut input =
+        BufReader::new(File::open(source).map_err(|e| format!("Failed to read archive: {}", e))?);
+    let mut output = BufWriter::new(File::create(target).map_err(write_error)?);
+    output.write_all(&header).map_err(write_error)?;
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    let mut index: u32 = 0;
+    loop {
+        let read = read_full(&mut input, &mut chunk)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        let last = read < CHUNK_SIZE;
+        let nonce = chunk_nonce(&prefix, index, last);
+        let sealed = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &chunk[..read],
+                    aad: &header,
+                },
+            )
+            .map_err(|e| format!("Failed to encrypt export: {}", e))?;
+        output.write_all(&sealed).map_err(write_error)?;
+        if last {
+            break;
+        }
+        index = index
+            .checked_add(1)
+            .ok_or("Export is too large to encrypt")?;
+    }
+    output.flush().map_err(write_error)
+}
+
+fn decrypt_file(source: &Path, target: &Path, password: &str) -> Result<(), String> {
+    let mut input = BufReader::new(
+        File::open(source).map_err(|e| format!("Failed to open {:?}: {}", source, e))?,
+    );
+    let mut header = [0u8; HEADER_LEN];
+    if read_full(&mut input, &mut header)
+        .map_err(|e| format!("Failed to read {:?}: {}", source, e))?
+        < HEADER_LEN
+        || &header[..MAGIC.len()] != MAGIC
+    {
+        return Err("Not a password-protected export".to_string());
+    }
+    let mut costs = [0u32; 3];
+    for (i, cost) in costs.iter_mut().enumerate() {
+        let at = MAGIC.len() + i * 4;
+        *cost = u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]]);
+    }
+    if costs[0] > MAX_M_COST || costs[1] > MAX_T_COST || costs[2] > MAX_P_COST {
+        return Err("Unsupported key derivation parameters in export".to_string());
+    }
+    let salt_at = MAGIC.len() + 12;
+    let salt = &header[salt_at..salt_at + SALT_LEN];
+    let prefix = &header[salt_at + SALT_LEN..];
+    let cipher = derive_key(password, salt, costs)?;
+
+    let write_error = |e: io::Error| format!("Failed to write decrypted export: {}", e);
+    let mut output = BufWriter::new(File::create(target).map_err(write_error)?);
+    let mut chunk = vec![0u8; CHUNK_SIZE + TAG_LEN];
+    let mut index: u32 = 0;
+    loop {
+        let read = read_full(&mut input, &mut chunk)
+            .map_err(|e| format!("Failed to read {:?}: {}", source, e))?;
+        let last = read < CHUNK_SIZE + TAG_LEN;
+        let nonce = chunk_nonce(prefix, index, last);
+        let plain = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &chunk[..read],
+                    aad: &header,
+                },
+            )
+            .map_err(|_| "Wrong password, or the file is damaged".to_string())?;
+        output.write_all(&plain).map_err(write_error)?;
+        if last {
+            break;
+        }
+        index = index.checked_add(1).ok_or("Export is damaged")?;
+    }
+    output.flush().map_err(write_error)
+}
+
+// Encrypt a staged export into sealed_path(target) and remove the plaintext
+fn seal(staged: &Path, target: &Path, password: &str) -> Result<PathBuf, String> {
+    let archive = staged.with_extension("zip");
+    let sealed = sealed_path(target);
+    let result = zip_file(staged, &archive).and_then(|_| encrypt_file(&archive, &sealed, password));
+    let _ = fs::remove_file(&archive);
+    let _ = fs::remove_file(staged);
+    if result.is_err() {
+        let _ = fs::remove_file(&sealed);
+    }
+    result.map(|_| sealed)
+}
+
+fn extract(archive: &Path, destination: &Path) -> Result<Vec<String>, String> {
+    let file = File::open(archive).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("Invalid archive: {}", e))?;
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        // Only plain file names, never paths taken from the archive
+        let Some(name) = entry
+            .enclosed_name()
+            .and_then(|name| name.file_name().map(|name| name.to_owned()))
+        else {
+            continue;
+        };
+        let target = destination.join(&name);
+        if target.exists() {
+            return Err(format!("{:?} already exists", target));
+        }
+        let mut output =
+            File::create(&target).map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+        io::copy(&mut entry, &mut output)
+            .map_err(|e| format!("Failed to extract {:?}: {}", target, e))?;
+        files.push(target.to_string_lossy().to_string());
+    }
+    Ok(files)
+}
+
+// Open a .ztkenc export into `destination` (a directory), next to the file by default
+#[tauri::command]
+pub async fn decrypt_export(
+    path: String,
+    password: String,
+    destination: Option<String>,
+) -> Result<DecryptResult, String> {
+    lock::ensure_unlocked()?;
+    let source = check_path(&path, Access::Read)?;
+    let destination = match destination.filter(|d| !d.trim().is_empty()) {
+        Some(destination) => check_path(&destination, Access::Write)?,
+        None => source
+            .parent()
+            .map(|dir| check_path(&dir.to_string_lossy(), Access::Write))
+            .ok_or("No destination folder for the decrypted export")??,
+    };
+
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let archive = staging_path("decrypted.zip")?;
+        let files = decrypt_file(&source, &archive, &password)
+            .and_then(|_| extract(&archive, &destination));
+        let _ = fs::remove_file(&archive);
+        Ok(DecryptResult {
+            files: files?,
+            destination: destination.to_string_lossy().to_string(),
+        })
+    })
+    .await
+    .map_err(|e| format!("Decrypt task failed: {}", e))
+    .and_then(|result| result);
+
+    match &result {
+        Ok(decrypted) => {
+            append_app_log(&format!("Decrypted {} to {}", path, decrypted.destination))
+        }
+        Err(err) => append_app_log(&format!("Decrypting {} failed: {}", path, err)),
+    }
+    audited("decrypt_export", &path, result)
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use tauri::{Emitter, Manager, State};
+
+use crate::append_app_log;
+use crate::device_manager::backoff_with_jitter;
+use crate::devices::{stage_records, StagedAttendance};
+use crate::monitor::{MonitorBus, MonitorEvent};
+use crate::zk::{
+    decode_c_string, Reply, ZkSession, CMD_REG_EVENT, DEFAULT_DEVICE_PORT, EF_ALARM, EF_ATTLOG,
+    EF_UNLOCK,
+};
+
+const EVENT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const SESSION_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(300);
+// Consecutive failed reconnects before the capture gives up and reports "failed"
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
+
+// Stop flags of running capture threads, keyed by "ip:port"
+pub type RealtimeSessions = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttendanceEvent {
+    device: String,
+    user_id: String,
+    timestamp: String, // device local time, "YYYY-MM-DD HH:MM:SS"
+    verify_mode: u8,
+    punch: u8,
+}
+
+// Door and alarm notifications, for deployments that watch the terminals' security state
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SecurityEvent {
+    device: String,
+    kind: String, // "door_unlock" or "alarm"
+    code: u32,    // alarm type reported by the firmware, 0 for unlocks
+    received_at: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConnectionStateEvent {
+    device: String,
+    state: String, // "connected", "reconnecting", "failed" or "stopped"
+    attempt: u32,
+    retry_in_secs: Option<u64>,
+    error: Option<String>,
+}
+
+fn emit_connection_state(
+    app: &tauri::AppHandle,
+    device: &str,
+    state: &str,
+    attempt: u32,
+    retry_in: Option<Duration>,
+    error: Option<String>,
+) {
+    let event = ConnectionStateEvent {
+        device: device.to_string(),
+        state: state.to_string(),
+        attempt,
+        retry_in_secs: retry_in.map(|delay| delay.as_secs()),
+        error,
+    };
+    if let Err(err) = app.emit("realtime-connection-state", &event) {
+        eprintln!("Failed to emit realtime-connection-state: {}", err);
+    }
+}
+
+// Sleep in short steps so a stop request doesn't wait for the whole backoff
+fn sleep_unless_stopped(delay: Duration, stop: &AtomicBool) {
+    let step = Duration::from_millis(250);
+    let mut slept = Duration::ZERO;
+    while slept < delay && !stop.load(Ordering::Relaxed) {
+        thread::sleep(step);
+        slept += step;
+    }
+}
+
+fn decode_event_time(raw: &[u8]) -> Option<String> {
+    let date = NaiveDate::from_ymd_opt(2000 + raw[0] as i32, raw[1] as u32, raw[2] as u32)?;
+    let time = date.and_hms_opt(raw[3] as u32, raw[4] as u32, raw[5] as u32)?;
+    Some(time.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+// Event payload layout depends on firmware: short numeric ids on old devices,
+// 24 byte string ids (optionally followed by extra bytes) on newer ones
+fn parse_attendance_event(device: &str, data: &[u8]) -> Option<AttendanceEvent> {
+    let (user_id, rest) = match data.len() {
+        10 | 14 => (
+            u16::from_le_bytes([data[0], data[1]]).to_string(),
+            &data[2..],
+        ),
+        12 => (
+            u32::from_le_bytes([data[0], data[1], data[2], data[3]]).to_string(),
+            &data[4..],
+        ),
+        len if len >= 32 => (decode_c_string(&data[..24]), &data[24..]),
+        _ => return None,
+    };
+
+    Some(AttendanceEvent {
+        device: device.to_string(),
+        user_id,
+        timestamp: decode_event_time(&rest[2..8])?,
+        verify_mode: rest[0],
+        punch: rest[1],
+    })
+}
+
+// The event flag travels in the session id field of pushed packets
+fn dispatch_event(
+    app: &tauri::AppHandle,
+    device: &str,
+    serial_number: Option<&str>,
+    packet: &Reply,
+) {
+    let flag = packet.session_id as u32;
+    if flag == EF_UNLOCK || flag == EF_ALARM {
+        let event = SecurityEvent {
+            device: device.to_string(),
+            kind: if flag == EF_ALARM {
+                "alarm"
+            } else {
+                "door_unlock"
+            }
+            .to_string(),
+            code: match packet.data.get(..4) {
+                Some(raw) if flag == EF_ALARM => {
+                    u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]])
+                }
+                _ => 0,
+            },
+            received_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        append_app_log(&format!(
+            "Security event from {}: {} ({})",
+            device, event.kind, event.code
+        ));
+        if let Err(err) = app.emit("device-security-event", &event) {
+            eprintln!("Failed to emit device-security-event: {}", err);
+        }
+        return;
+    }
+
+    match parse_attendance_event(device, &packet.data) {
+        Some(event) => {
+            // Queued as well, so the punch reaches the backend even if it is down right now
+            let staged = StagedAttendance::captured(
+                device,
+                serial_number.map(str::to_string),
+                &event.user_id,
+                &event.timestamp,
+                event.verify_mode,
+                event.punch,
+            );
+            if let Err(err) = stage_records(&[staged]) {
+                eprintln!("Failed to queue realtime punch from {}: {}", device, err);
+            }
+            if let Err(err) = app.emit("attendance-event", &event) {
+                eprintln!("Failed to emit attendance-event: {}", err);
+            }
+            if let Ok(punch) = serde_json::to_value(&event) {
+                let _ = app.state::<MonitorBus>().send(MonitorEvent::Punch(punch));
+            }
+        }
+        None => eprintln!(
+            "Unrecognised realtime event from {} ({} bytes)",
+            device,
+            packet.data.len()
+        ),
+    }
+}
+
+fn capture_events(
+    app: &tauri::AppHandle,
+    ip: &str,
+    port: u16,
+    comm_key: u32,
+    device: &str,
+    stop: &AtomicBool,
+    failures: &mut u32,
+) -> Result<(), String> {
+    let mut session = ZkSession::connect(ip, port, comm_key, SESSION_TIMEOUT)?;
+    let serial_number = session
+        .read_option("~SerialNumber")
+        .ok()
+        .filter(|serial| !serial.is_empty());
+    session.register_events(EF_ATTLOG | EF_UNLOCK | EF_ALARM)?;
+    append_app_log(&format!("Realtime capture subscribed on {}", device));
+    *failures = 0;
+    emit_connection_state(app, device, "connected", 0, None, None);
+
+    while !stop.load(Ordering::Relaxed) {
+        let Some(packet) = session.wait_event(EVENT_POLL_INTERVAL)? else {
+            continue;
+        };
+        if packet.command != CMD_REG_EVENT {
+            continue;
+        }
+        session.ack_event()?;
+        dispatch_event(app, device, serial_number.as_deref(), &packet);
+    }
+
+    session.disconnect()
+}
+
+#[tauri::command]
+pub fn start_realtime_events(
+    app: tauri::AppHandle,
+    sessions: State<RealtimeSessions>,
+    ip: String,
+    port: Option<u16>,
+    comm_key: Option<u32>,
+    device_id: Option<String>,
+) -> Result<String, String> {
+    let port = port.unwrap_or(DEFAULT_DEVICE_PORT);
+    let key = format!("{}:{}", ip, port);
+    let stop = Arc::new(AtomicBool::new(false));
+
+    {
+        let mut sessions = sessions
+            .lock()
+            .map_err(|e| format!("Failed to lock realtime sessions: {}", e))?;
+        if sessions.contains_key(&key) {
+            return Ok(format!("Realtime capture already running for {}", key));
+        }
+        sessions.insert(key.clone(), stop.clone());
+    }
+
+    let device = device_id.unwrap_or_else(|| key.clone());
+    let comm_key = comm_key.unwrap_or(0);
+    append_app_log(&format!("Starting realtime capture for {}", device));
+
+    // Reconnect with backoff when the connection drops so a rebooted terminal resumes streaming
+    // on its own; give up after MAX_RECONNECT_ATTEMPTS consecutive failures
+    let sessions = sessions.inner().clone();
+    let session_key = key.clone();
+    thread::spawn(move || {
+        let mut failures = 0;
+        while !stop.load(Ordering::Relaxed) {
+            let Err(err) = capture_events(&app, &ip, port, comm_key, &device, &stop, &mut failures)
+            else {
+                continue;
+            };
+            failures += 1;
+            eprintln!("Realtime capture error for {}: {}", device, err);
+            append_app_log(&format!("Realtime capture error for {}: {}", device, err));
+
+            if failures > MAX_RECONNECT_ATTEMPTS {
+                append_app_log(&format!(
+                    "Realtime capture for {} gave up after {} failed reconnects",
+                    device, MAX_RECONNECT_ATTEMPTS
+                ));
+                emit_connection_state(&app, &device, "failed", failures, None, Some(err));
+                if let Ok(mut sessions) = sessions.lock() {
+                    if sessions
+                        .get(&session_key)
+                        .is_some_and(|flag| Arc::ptr_eq(flag, &stop))
+                    {
+                        sessions.remove(&session_key);
+                    }
+                }
+                return;
+            }
+
+            let delay =
+                backoff_with_jitter(RECONNECT_BASE_DELAY, failures - 1, RECONNECT_MAX_DELAY);
+            emit_connection_state(
+                &app,
+                &device,
+                "reconnecting",
+                failures,
+                Some(delay),
+                Some(err),
+            );
+            sleep_unless_stopped(delay, &stop);
+        }
+        append_app_log(&format!("Realtime capture stopped for {}", device));
+        emit_connection_state(&app, &device, "stopped", 0, None, None);
+    });
+
+    Ok(format!("Realtime capture started for {}", key))
+}
+
+#[tauri::command]
+pub fn stop_realtime_events(
+    sessions: State<RealtimeSessions>,
+    ip: String,
+    port: Option<u16>,
+) -> Result<String, String> {
+    let key = format!("{}:{}", ip, port.unwrap_or(DEFAULT_DEVICE_PORT));
+    let stop = sessions
+        .lock()
+        .map_err(|e| format!("Failed to lock realtime sessions: {}", e))?
+        .remove(&key);
+
+    match stop {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            Ok(format!("Realtime capture stopping for {}", key))
+        }
+        None => Ok(format!("No realtime capture running for {}", key)),
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::database::{archive_db_path, run_db};
+use crate::devices::{run_native, stage_records, to_staged, NATIVE_TIMEOUT};
+use crate::export::{parse_range, ExportRange};
+use crate::lock;
+use crate::offline_queue::drain_queue;
+use crate::registry::{lookup_device, DeviceEntry, DeviceRegistry};
+use crate::secrets::hex;
+use crate::zk::AttendanceRecord;
+use crate::{append_app_log, current_backend_port, BackendPort};
+
+// Proof that no punches were lost: the terminal's log for a date range is compared with what
+// zkteco_app.db (and the archive) holds for the device and with what was pushed upstream.
+// Records are matched on (user id, timestamp to the second); the checksums are SHA-256 over
+// the sorted "user_id|timestamp" lines so two sides can be compared at a glance.
+const DEVICE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const MAX_LISTED: usize = 500;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReconcileSide {
+    count: usize,
+    checksum: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReconcileRecord {
+    user_id: String,
+    timestamp: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ReconcileReport {
+    device_id: String,
+    from: String,
+    to: String,
+    device: ReconcileSide,
+    database: ReconcileSide,
+    upstream_synced: usize,
+    // On the terminal but not stored locally (first MAX_LISTED)
+    missing_locally: Vec<ReconcileRecord>,
+    missing_locally_count: usize,
+    // Stored locally but no longer on the terminal (cleared log, other device id)
+    missing_on_device_count: usize,
+    in_sync: bool,
+    // Set when stage_missing was requested
+    restaged: Option<usize>,
+}
+
+type PunchKey = (String, String);
+
+fn checksum(keys: &BTreeSet<PunchKey>) -> ReconcileSide {
+    let mut hasher = Sha256::new();
+    for (user_id, timestamp) in keys {
+        hasher.update(format!("{}|{}\n", user_id, timestamp).as_bytes());
+    }
+    ReconcileSide {
+        count: keys.len(),
+        checksum: hex(&hasher.finalize()),
+    }
+}
+
+// "2024-01-31T08:00:00.000" and "2024-01-31 08:00:00" are the same punch
+fn normalize(timestamp: &str) -> String {
+    timestamp
+        .get(..19)
+        .unwrap_or(timestamp)
+        .replacen('T', " ", 1)
+}
+
+// Locally stored punches of the device in the range as (key, synced upstream)
+fn stored_punches(
+    connection: &Connection,
+    device: &DeviceEntry,
+    range: (NaiveDate, NaiveDate),
+) -> Result<Vec<(PunchKey, bool)>, String> {
+    let archive = archive_db_path();
+    let with_archive = archive.exists();
+    if with_archive {
+        connection
+            .execute(
+                "ATTACH DATABASE ?1 AS archive",
+                [archive.to_string_lossy().to_string()],
+            )
+            .map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+    }
+    let select = |table: &str| {
+        format!(
+            "SELECT user_id, timestamp, COALESCE(sync_status, '') = 'synced' FROM {}
+             WHERE timestamp >= ?1 AND timestamp < date(?2, '+1 day')
+               AND (device_id = ?3 OR (?4 IS NOT NULL AND serial_number = ?4))",
+            table
+        )
+    };
+    let sql = if with_archive {
+        format!(
+            "{} UNION ALL {}",
+            select("main.attendance_logs"),
+            select("archive.attendance_logs")
+        )
+    } else {
+        select("main.attendance_logs")
+    };
+
+    let mut statement = connection
+        .prepare(&sql)
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    let rows = statement
+        .query_map(
+            params![
+                range.0.to_string(),
+                range.1.to_string(),
+                device.id,
+                device.serial_number.as_deref().filter(|s| !s.is_empty())
+            ],
+            |row| {
+                Ok((
+                    (
+                        row.get::<_, String>(0)?,
+                        normalize(&row.get::<_, String>(1)?),
+                    ),
+                    row.get::<_, bool>(2)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Failed to query attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance row: {}", e))
+}
+
+#[tauri::command]
+pub async fn reconcile_device(
+    device_id: String,
+    range: ExportRange,
+    stage_missing: Option<bool>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<ReconcileReport, String> {
+    lock::ensure_unlocked()?;
+    let range = parse_range(&range)?;
+    let device = lookup_device(&registry, &backend_port, &device_id).await?;
+
+    let on_device: Vec<AttendanceRecord> = {
+        let device = device.clone();
+        run_native(move || {
+            let mut session = device.open_session(NATIVE_TIMEOUT)?;
+            let records = session.read_attendance()?;
+            session.disconnect()?;
+            Ok(records)
+        })
+        .await?
+        .into_iter()
+        .filter(|r| r.timestamp.date() >= range.0 && r.timestamp.date() <= range.1)
+        .collect()
+    };
+    let stored = {
+        let device = device.clone();
+        run_db(move |connection| stored_punches(connection, &device, range)).await?
+    };
+
+    let device_keys: BTreeSet<PunchKey> = on_device
+        .iter()
+        .map(|r| {
+            (
+                r.user_id.clone(),
+                r.timestamp.format(DEVICE_TIME_FORMAT).to_string(),
+            )
+        })
+        .collect();
+    let stored_keys: BTreeSet<PunchKey> = stored.iter().map(|(key, _)| key.clone()).collect();
+    let upstream_synced = stored
+        .iter()
+        .filter(|(key, synced)| *synced && device_keys.contains(key))
+        .count();
+    let missing: Vec<&PunchKey> = device_keys.difference(&stored_keys).collect();
+
+    let restaged = if stage_missing.unwrap_or(false) && !missing.is_empty() {
+        let records: Vec<AttendanceRecord> = on_device
+            .iter()
+            .filter(|r| {
+                !stored_keys.contains(&(
+                    r.user_id.clone(),
+                    r.timestamp.format(DEVICE_TIME_FORMAT).to_string(),
+                ))
+            })
+            .cloned()
+            .collect();
+        let staged = stage_records(&to_staged(&device, &records))?;
+        if let Err(err) = drain_queue(current_backend_port(&backend_port)).await {
+            eprintln!("{}", err);
+        }
+        Some(staged)
+    } else {
+        None
+    };
+
+    let report = ReconcileReport {
+        device_id: device.id.clone(),
+        from: range.0.to_string(),
+        to: range.1.to_string(),
+        device: checksum(&device_keys),
+        database: checksum(&stored_keys),
+        upstream_synced,
+        missing_locally: missing
+            .iter()
+            .take(MAX_LISTED)
+            .map(|(user_id, timestamp)| ReconcileRecord {
+                user_id: user_id.clone(),
+                timestamp: timestamp.clone(),
+            })
+            .collect(),
+        missing_locally_count: missing.len(),
+        missing_on_device_count: stored_keys.difference(&device_keys).count(),
+        in_sync: missing.is_empty(),
+        restaged,
+    };
+    append_app_log(&format!(
+        "Reconciled {} ({} to {}): {} on device, {} stored, {} missing locally{}",
+        report.device_id,
+        report.from,
+        report.to,
+        report.device.count,
+        report.database.count,
+        report.missing_locally_count,
+        report
+            .restaged
+            .map(|n| format!(", {} re-staged", n))
+            .unwrap_or_default()
+    ));
+    Ok(report)
+}
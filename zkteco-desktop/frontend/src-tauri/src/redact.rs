@@ -0,0 +1,61 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::backend_auth::session_token;
+use crate::confirmation::{consume_token, ConfirmationTokens};
+
+// Sanitizer for diagnostics that leave the PC (exported logs, diagnostic bundles). It masks
+// COMM keys, passwords and tokens, employee personal fields and the OS user name in paths.
+// Unredacted exports need a confirmation token for UNREDACTED_ACTION.
+pub const UNREDACTED_ACTION: &str = "export_unredacted";
+const MASK: &str = "[REDACTED]";
+const USER_MASK: &str = "<user>";
+
+struct Rules {
+    secrets: Regex,
+    bearer: Regex,
+    personal: Regex,
+    home: Regex,
+}
+
+fn rules() -> &'static Rules {
+    static RULES: OnceLock<Rules> = OnceLock::new();
+    RULES.get_or_init(|| Rules {
+        // key=value, key: value and "key": "value" forms, as found in logs and JSON
+        secrets: Regex::new(
+            r#"(?i)(\b(?:comm_?key|commkey|password|passwd|pwd|secret|api_?key|[a-z_-]*token)["']?\s*[:=]\s*["']?)([^"'\s,&;}\]]+)"#,
+        )
+        .expect("valid secrets pattern"),
+        bearer: Regex::new(r"(?i)\b(bearer|basic)\s+[a-z0-9._~+/=-]+").expect("valid bearer pattern"),
+        personal: Regex::new(
+            r#"(?i)(\b(?:name|user_?name|full_?name|card|card_?no|card_?number|email|phone|birthday|address|fingerprint|template|face|photo)["']?\s*[:=]\s*)("[^"]*"|'[^']*'|[^\s,&;}\]]+)"#,
+        )
+        .expect("valid personal data pattern"),
+        home: Regex::new(r#"(?i)((?:[a-z]:)?[\\/](?:users|home)[\\/]+)([^\\/\s"':]+)"#)
+            .expect("valid home path pattern"),
+    })
+}
+
+pub fn redact(text: &str) -> String {
+    let rules = rules();
+    let text = text.replace(session_token(), MASK);
+    let text = rules.secrets.replace_all(&text, format!("${{1}}{}", MASK));
+    let text = rules.bearer.replace_all(&text, format!("${{1}} {}", MASK));
+    let text = rules.personal.replace_all(&text, format!("${{1}}{}", MASK));
+    rules
+        .home
+        .replace_all(&text, format!("${{1}}{}", USER_MASK))
+        .into_owned()
+}
+
+// Unredacted exports are opt-in per export: the token must be issued for UNREDACTED_ACTION
+// and this target
+pub fn confirm_unredacted(
+    tokens: &ConfirmationTokens,
+    confirm_token: Option<&str>,
+    target: &str,
+) -> Result<(), String> {
+    let token = confirm_token.ok_or("An unredacted export needs a confirmation token")?;
+    consume_token(tokens, token, UNREDACTED_ACTION, target)
+}
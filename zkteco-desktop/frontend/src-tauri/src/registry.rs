@@ -0,0 +1,310 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tauri::State;
+
+use crate::backend_auth::backend_client;
+use crate::lock;
+use crate::secrets::{device_comm_key, store_reported_keys};
+use crate::timezones::parse_zone;
+use crate::zk::{ZkSession, SUPPORTED_BAUD_RATES};
+use crate::{
+    append_app_log, backend_base_url, current_backend_port, resolve_app_data_dir, BackendPort,
+};
+
+// Local copy of the backend's device list so native commands can resolve a device id
+// (ip, port, COMM key) while the backend is down. COMM keys are kept in the OS keychain
+// (secrets.rs) and only the entry's credential_ref is saved.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceEntry {
+    pub id: String,
+    pub name: String,
+    pub ip: String,
+    #[serde(default = "default_device_port")]
+    pub port: u16,
+    // As reported by the backend; never serialized, resolved through credential_ref
+    #[serde(default, rename = "password", skip_serializing)]
+    pub comm_key: u32,
+    #[serde(default)]
+    pub credential_ref: Option<String>,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default = "default_device_type")]
+    pub device_type: String,
+    #[serde(default = "default_is_active")]
+    pub is_active: bool,
+    // Set locally for terminals wired through a serial adapter; the backend doesn't know about it
+    #[serde(default)]
+    pub serial: Option<SerialLink>,
+    // Local grouping used by the bulk commands, e.g. "HQ floor 1"
+    #[serde(default)]
+    pub group: Option<String>,
+    // IANA zone of the site the terminal is installed at, e.g. "Asia/Ho_Chi_Minh"
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SerialLink {
+    pub port_name: String,
+    #[serde(default = "default_baud_rate")]
+    pub baud_rate: u32,
+}
+
+impl DeviceEntry {
+    // The COMM key from the keychain (a key set on this PC wins over the backend's)
+    pub fn with_stored_comm_key(mut self) -> Self {
+        if let Some(key) = device_comm_key(self.credential_ref.as_deref()) {
+            self.comm_key = key;
+        }
+        self
+    }
+
+    // Native session over whichever transport the device is reachable through
+    pub fn open_session(&self, timeout: Duration) -> Result<ZkSession, String> {
+        match &self.serial {
+            Some(link) => {
+                ZkSession::connect_serial(&link.port_name, link.baud_rate, self.comm_key, timeout)
+            }
+            None => ZkSession::connect(&self.ip, self.port, self.comm_key, timeout),
+        }
+    }
+}
+
+fn default_device_port() -> u16 {
+    crate::zk::DEFAULT_DEVICE_PORT
+}
+
+fn default_baud_rate() -> u32 {
+    crate::zk::DEFAULT_BAUD_RATE
+}
+
+fn default_device_type() -> String {
+    "pull".to_string()
+}
+
+fn default_is_active() -> bool {
+    true
+}
+
+pub type DeviceRegistry = Arc<Mutex<Vec<DeviceEntry>>>;
+
+#[derive(serde::Deserialize)]
+struct DevicesResponse {
+    devices: Vec<DeviceEntry>,
+}
+
+fn registry_path() -> PathBuf {
+    resolve_app_data_dir().join("device_registry.json")
+}
+
+pub fn load_registry() -> Vec<DeviceEntry> {
+    fs::read_to_string(registry_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_registry(devices: &[DeviceEntry]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(devices)
+        .map_err(|e| format!("Failed to serialize device registry: {}", e))?;
+    fs::write(registry_path(), content)
+        .map_err(|e| format!("Failed to save device registry: {}", e))
+}
+
+// Pull the device list from the backend and persist it as the new local registry
+pub async fn refresh_from_backend(registry: &DeviceRegistry, port: u16) -> Result<usize, String> {
+    let client = backend_client(Duration::from_secs(10))?;
+    let response = client
+        .get(format!("{}/devices", backend_base_url(port)))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch devices from backend: {}", e))?;
+    let devices = response
+        .json::<DevicesResponse>()
+        .await
+        .map_err(|e| format!("Invalid devices response: {}", e))?
+        .devices;
+
+    let count = devices.len();
+    let mut guard = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    // Keep serial links, groups, timezones and credentials configured on this machine
+    let mut devices: Vec<DeviceEntry> = devices
+        .into_iter()
+        .map(|mut device| {
+            if let Some(existing) = guard.iter().find(|d| d.id == device.id) {
+                device.serial = existing.serial.clone();
+                device.group = existing.group.clone();
+                device.timezone = existing.timezone.clone();
+                device.credential_ref = existing.credential_ref.clone();
+            }
+            device
+        })
+        .collect();
+    if let Err(err) = store_reported_keys(&mut devices) {
+        // The keys stay usable in memory for this session
+        append_app_log(&format!(
+            "Failed to store COMM keys in the OS keychain: {}",
+            err
+        ));
+    }
+    save_registry(&devices)?;
+    *guard = devices;
+    Ok(count)
+}
+
+// Record a new address for a device after it was moved (e.g. by set_device_network)
+pub fn update_device_address(
+    registry: &DeviceRegistry,
+    device_id: &str,
+    ip: &str,
+    port: u16,
+) -> Result<(), String> {
+    let mut devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    let device = devices
+        .iter_mut()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| format!("Unknown device: {}", device_id))?;
+    device.ip = ip.to_string();
+    device.port = port;
+    save_registry(&devices)
+}
+
+// Active pull devices, as used by the background jobs that talk to every terminal
+pub fn active_pull_devices(registry: &DeviceRegistry) -> Vec<DeviceEntry> {
+    let devices: Vec<DeviceEntry> = registry
+        .lock()
+        .map(|devices| {
+            devices
+                .iter()
+                .filter(|d| d.is_active && d.device_type == "pull")
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    devices
+        .into_iter()
+        .map(DeviceEntry::with_stored_comm_key)
+        .collect()
+}
+
+// Resolve a device id, refreshing from the backend once if it is not cached yet
+pub async fn lookup_device(
+    registry: &DeviceRegistry,
+    backend_port: &BackendPort,
+    device_id: &str,
+) -> Result<DeviceEntry, String> {
+    let find = |registry: &DeviceRegistry| {
+        registry
+            .lock()
+            .ok()
+            .and_then(|devices| devices.iter().find(|d| d.id == device_id).cloned())
+            .map(DeviceEntry::with_stored_comm_key)
+    };
+
+    if let Some(device) = find(registry) {
+        return Ok(device);
+    }
+    let _ = refresh_from_backend(registry, current_backend_port(backend_port)).await;
+    find(registry).ok_or_else(|| format!("Unknown device: {}", device_id))
+}
+
+#[tauri::command]
+pub async fn refresh_device_registry(
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<usize, String> {
+    let count = refresh_from_backend(&registry, current_backend_port(&backend_port)).await?;
+    append_app_log(&format!("Device registry refreshed ({} devices)", count));
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn get_device_registry(registry: State<DeviceRegistry>) -> Result<Vec<DeviceEntry>, String> {
+    registry
+        .lock()
+        .map(|devices| devices.clone())
+        .map_err(|e| format!("Failed to read device registry: {}", e))
+}
+
+// Attach a device to a serial port (or with no port, back to TCP)
+#[tauri::command]
+pub fn set_device_serial_link(
+    device_id: String,
+    port_name: Option<String>,
+    baud_rate: Option<u32>,
+    registry: State<DeviceRegistry>,
+) -> Result<DeviceEntry, String> {
+    lock::ensure_unlocked()?;
+    let baud_rate = baud_rate.unwrap_or(crate::zk::DEFAULT_BAUD_RATE);
+    if !SUPPORTED_BAUD_RATES.contains(&baud_rate) {
+        return Err(format!(
+            "Unsupported baud rate {} (expected one of {:?})",
+            baud_rate, SUPPORTED_BAUD_RATES
+        ));
+    }
+
+    let mut devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    let device = devices
+        .iter_mut()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| format!("Unknown device: {}", device_id))?;
+    device.serial = port_name
+        .filter(|name| !name.trim().is_empty())
+        .map(|port_name| SerialLink {
+            port_name,
+            baud_rate,
+        });
+    let updated = device.clone();
+    save_registry(&devices)?;
+
+    append_app_log(&format!(
+        "Device {} now uses {}",
+        device_id,
+        match &updated.serial {
+            Some(link) => format!("serial port {} at {} baud", link.port_name, link.baud_rate),
+            None => "TCP".to_string(),
+        }
+    ));
+    Ok(updated)
+}
+
+// Record the zone a terminal's clock runs in (or with no zone, back to this PC's zone)
+#[tauri::command]
+pub fn set_device_timezone(
+    device_id: String,
+    timezone: Option<String>,
+    registry: State<DeviceRegistry>,
+) -> Result<DeviceEntry, String> {
+    lock::ensure_unlocked()?;
+    let timezone = timezone
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| parse_zone(&name).map(|zone| zone.name().to_string()))
+        .transpose()?;
+
+    let mut devices = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))?;
+    let device = devices
+        .iter_mut()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| format!("Unknown device: {}", device_id))?;
+    device.timezone = timezone;
+    let updated = device.clone();
+    save_registry(&devices)?;
+
+    append_app_log(&format!(
+        "Device {} clock zone set to {}",
+        device_id,
+        updated.timezone.as_deref().unwrap_or("this PC's zone")
+    ));
+    Ok(updated)
+}
@@ -0,0 +1,239 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use rusqlite::{Connection, OpenFlags};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit::audited;
+use crate::backup::{start_backend_after_maintenance, stop_backend_for_maintenance};
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::database::integrity_problems;
+use crate::encryption::unlock;
+use crate::path_policy::{check_path, Access};
+use crate::secrets::hex;
+use crate::settings::{save_settings, SharedSettings};
+use crate::{append_app_log, resolve_backend_db_path};
+
+// zkteco_app.db (with its WAL/SHM files and the attendance archive) may live outside the app
+// data folder, e.g. on a larger or backed-up drive. move_database copies the files with the
+// backend stopped, verifies every copy, switches the configured folder and restarts the
+// backend; any failure on the way puts everything back as it was.
+pub const DATABASE_FILES: [&str; 6] = [
+    "zkteco_app.db",
+    "zkteco_app.db-wal",
+    "zkteco_app.db-shm",
+    "zkteco_archive.db",
+    "zkteco_archive.db-wal",
+    "zkteco_archive.db-shm",
+];
+
+// Set from shell settings at startup and by move_database; None = the app data folder
+static DATABASE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub fn set_database_dir(dir: Option<PathBuf>) {
+    if let Ok(mut guard) = DATABASE_DIR.lock() {
+        *guard = dir;
+    }
+}
+
+pub fn database_dir() -> Option<PathBuf> {
+    DATABASE_DIR.lock().ok().and_then(|dir| dir.clone())
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MoveResult {
+    from: String,
+    to: String,
+    files_moved: usize,
+    bytes: u64,
+    backend_restarted: bool,
+    duration_ms: u128,
+}
+
+pub fn digest(path: &Path) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    File::open(path)
+        .and_then(|mut file| io::copy(&mut file, &mut hasher))
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        if let Err(err) = fs::remove_file(path) {
+            eprintln!("Failed to remove {:?}: {}", path, err);
+        }
+    }
+}
+
+fn copy_files(from: &Path, to: &Path, copied: &mut Vec<PathBuf>) -> Result<u64, String> {
+    let mut bytes = 0;
+    for name in DATABASE_FILES {
+        let source = from.join(name);
+        if !source.exists() {
+            continue;
+        }
+        let target = to.join(name);
+        bytes += fs::copy(&source, &target)
+            .map_err(|e| format!("Failed to copy {} to {:?}: {}", name, to, e))?;
+        copied.push(target.clone());
+        if digest(&source)? != digest(&target)? {
+            return Err(format!("Copy of {} does not match the original", name));
+        }
+    }
+
+    // The copy must open as a healthy database with the same key
+    let database = to.join(DATABASE_FILES[0]);
+    let connection = Connection::open_with_flags(&database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open copied database: {}", e))?;
+    unlock(&connection, &database)?;
+    let problems = integrity_problems(&connection, true)?;
+    if !problems.is_empty() {
+        return Err(format!(
+            "Copied database failed its integrity check: {}",
+            problems.join("; ")
+        ));
+    }
+    Ok(bytes)
+}
+
+// Copy and verify; on failure nothing is left behind in `to`
+fn copy_verified(from: &Path, to: &Path) -> Result<(Vec<PathBuf>, u64), String> {
+    let mut copied = Vec::new();
+    match copy_files(from, to, &mut copied) {
+        Ok(bytes) => Ok((copied, bytes)),
+        Err(err) => {
+            remove_files(&copied);
+            Err(err)
+        }
+    }
+}
+
+fn save_database_dir(settings: &SharedSettings, dir: Option<&Path>) -> Result<(), String> {
+    let mut guard = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))?;
+    let mut updated = guard.clone();
+    updated.database_dir = dir.map(|dir| dir.to_string_lossy().to_string());
+    save_settings(&updated)?;
+    *guard = updated;
+    set_database_dir(dir.map(Path::to_path_buf));
+    Ok(())
+}
+
+fn prepare_target(new_dir: &str, from: &Path) -> Result<PathBuf, String> {
+    let target = check_path(new_dir, Access::Write)?;
+    fs::create_dir_all(&target).map_err(|e| format!("Failed to create {:?}: {}", target, e))?;
+    let target = target
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", target, e))?;
+    if from.canonicalize().is_ok_and(|from| from == target) {
+        return Err(format!("The database is already in {:?}", target));
+    }
+    if target.join(DATABASE_FILES[0]).exists() {
+        return Err(format!(
+            "{:?} already contains a database; refusing to overwrite it",
+            target
+        ));
+    }
+    Ok(target)
+}
+
+async fn move_with_backend_stopped(app: &AppHandle, to: PathBuf) -> Result<MoveResult, String> {
+    let started = Instant::now();
+    let database = resolve_backend_db_path();
+    if !database.exists() {
+        return Err(format!("Backend database not found at {:?}", database));
+    }
+    let from = database
+        .parent()
+        .map(Path::to_path_buf)
+        .ok_or("Database path has no parent folder")?;
+    let previous = database_dir();
+    let settings = app.state::<SharedSettings>().inner().clone();
+
+    let was_running = stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, move aborted", e))?;
+    let restart = |reason: String| async move {
+        if was_running && start_backend_after_maintenance(app).await.is_err() {
+            return format!("{} (the backend could not be restarted)", reason);
+        }
+        reason
+    };
+
+    let (source, target) = (from.clone(), to.clone());
+    let copied = tauri::async_runtime::spawn_blocking(move || copy_verified(&source, &target))
+        .await
+        .map_err(|e| format!("Move task failed: {}", e))
+        .and_then(|result| result);
+    let (copied, bytes) = match copied {
+        Ok(copied) => copied,
+        Err(err) => return Err(restart(err).await),
+    };
+    if let Err(err) = save_database_dir(&settings, Some(&to)) {
+        remove_files(&copied);
+        return Err(restart(err).await);
+    }
+
+    if was_running {
+        if let Err(err) = start_backend_after_maintenance(app).await {
+            // Roll back to the original files, which are still in place
+            let rolled_back = save_database_dir(&settings, previous.as_deref());
+            remove_files(&copied);
+            let reason = format!(
+                "Backend failed to start from {:?} ({}); the database stays in {:?}",
+                to, err, from
+            );
+            return Err(match rolled_back {
+                Ok(()) => restart(reason).await,
+                Err(rollback) => {
+                    format!("{}, but restoring the setting failed: {}", reason, rollback)
+                }
+            });
+        }
+    }
+
+    // Originals go only once the new location is in use
+    let originals: Vec<PathBuf> = DATABASE_FILES
+        .iter()
+        .map(|name| from.join(name))
+        .filter(|path| path.exists())
+        .collect();
+    remove_files(&originals);
+    Ok(MoveResult {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+        files_moved: copied.len(),
+        bytes,
+        backend_restarted: was_running,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[tauri::command]
+pub async fn move_database(
+    app: AppHandle,
+    new_dir: String,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<MoveResult, String> {
+    consume_token(&tokens, &confirm_token, "move_database", &new_dir)?;
+    let from = resolve_backend_db_path();
+    let result = match prepare_target(&new_dir, from.parent().unwrap_or(&from)) {
+        Ok(target) => move_with_backend_stopped(&app, target).await,
+        Err(err) => Err(err),
+    };
+    match &result {
+        Ok(moved) => append_app_log(&format!(
+            "Moved database from {} to {} ({} files, {} bytes)",
+            moved.from, moved.to, moved.files_moved, moved.bytes
+        )),
+        Err(err) => append_app_log(&format!("Database move failed: {}", err)),
+    }
+    audited("move_database", &new_dir, result)
+}
@@ -0,0 +1,754 @@
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc};
+use openssl::hash::{hash, MessageDigest};
+use openssl::ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::write_csv;
+use crate::path_policy::{check_path, Access};
+use crate::secrets::{random_hex, remote_credential};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::timezones::registered_zones;
+use crate::{append_app_log, lock};
+
+// Off-site copies of backups and exports on an SFTP or FTPS server, the drop folder many
+// payroll providers still collect files from. The server is pinned: SFTP runs the system OpenSSH
+// client against a known_hosts file holding only remote_host_key, FTPS (explicit AUTH TLS)
+// accepts either a CA-trusted certificate or exactly the pinned fingerprint. The SFTP private
+// key or FTPS password lives in the keychain; a key is written to a private temp file only for
+// the length of one upload. Files are uploaded under a ".part" name and renamed when complete,
+// so the receiving side never picks up half a file.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(120);
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+const MAX_REPLY_LINE: usize = 4096;
+
+static STATS: Mutex<Option<RemoteStats>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RemoteStats {
+    protocol: String,
+    configured: bool,
+    uploads: u64,
+    last_upload_at: Option<DateTime<Utc>>,
+    last_file: Option<String>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UploadResult {
+    remote_path: String,
+    size_bytes: u64,
+    duration_ms: u128,
+}
+
+// A key the server presented, for the user to compare and pin
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RemoteHostKey {
+    key_type: String,
+    // The remote_host_key value that pins this key
+    key: String,
+    fingerprint: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    Sftp,
+    Ftps,
+}
+
+#[derive(Debug, Clone)]
+struct RemoteConfig {
+    protocol: Protocol,
+    host: String,
+    port: u16,
+    username: String,
+    host_key: Option<String>,
+    directory: Option<String>,
+}
+
+fn update_stats(f: impl FnOnce(&mut RemoteStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_ref()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// Names and folders end up in FTP commands and sftp batch lines
+fn check_name(value: &str, what: &str) -> Result<(), String> {
+    if value.chars().any(|c| c.is_control() || c == '"') {
+        return Err(format!(
+            "The {} '{}' contains unsupported characters",
+            what, value
+        ));
+    }
+    Ok(())
+}
+
+fn remote_config(settings: &ShellSettings) -> Result<RemoteConfig, String> {
+    let protocol = match settings
+        .remote_protocol
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "sftp" => Protocol::Sftp,
+        "ftps" => Protocol::Ftps,
+        "off" | "" => return Err("No SFTP/FTPS destination is configured".to_string()),
+        other => return Err(format!("Unknown remote protocol '{}'", other)),
+    };
+    let host = non_empty(&settings.remote_host).ok_or("No SFTP/FTPS server is configured")?;
+    let username =
+        non_empty(&settings.remote_username).ok_or("No SFTP/FTPS user name is configured")?;
+    let directory = non_empty(&settings.remote_directory);
+    check_name(&host, "server")?;
+    check_name(&username, "user name")?;
+    // Both end up on the sftp and ssh-keyscan command lines
+    if host.starts_with('-') || username.starts_with('-') {
+        return Err("Invalid SFTP/FTPS server or user name".to_string());
+    }
+    if let Some(directory) = &directory {
+        check_name(directory, "folder")?;
+    }
+    Ok(RemoteConfig {
+        protocol,
+        port: settings.remote_port.unwrap_or(match protocol {
+            Protocol::Sftp => 22,
+            Protocol::Ftps => 21,
+        }),
+        host,
+        username,
+        host_key: non_empty(&settings.remote_host_key),
+        directory,
+    })
+}
+
+fn remote_path(config: &RemoteConfig, name: &str) -> String {
+    match &config.directory {
+        Some(directory) => format!("{}/{}", directory.trim_end_matches('/'), name),
+        None => name.to_string(),
+    }
+}
+
+// SFTP through the system OpenSSH client
+
+fn sftp_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\"))
+}
+
+// OpenSSH looks plain host names up for port 22 and "[host]:port" otherwise
+fn known_hosts_name(config: &RemoteConfig) -> String {
+    if config.port == 22 {
+        config.host.clone()
+    } else {
+        format!("[{}]:{}", config.host, config.port)
+    }
+}
+
+fn private_file(path: &Path, content: &str) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn sftp_batch(config: &RemoteConfig, script: &str) -> Result<(), String> {
+    let host_key = config
+        .host_key
+        .as_ref()
+        .ok_or("Pin the server's host key before connecting (scan_remote_host_key)")?;
+    if host_key.split_whitespace().count() < 2 {
+        return Err(
+            "The pinned host key must be an OpenSSH line such as 'ssh-ed25519 AAAA...'".into(),
+        );
+    }
+    let key = remote_credential().ok_or("No SFTP private key is stored")?;
+
+    let workdir = std::env::temp_dir().join(format!("ztkapp_ssh_{}", random_hex(8)));
+    fs::create_dir(&workdir).map_err(|e| format!("Failed to create {:?}: {}", workdir, e))?;
+    let run = (|| {
+        let identity = workdir.join("id");
+        let known_hosts = workdir.join("known_hosts");
+        private_file(&identity, &format!("{}\n", key.trim()))?;
+        private_file(
+            &known_hosts,
+            &format!("{} {}\n", known_hosts_name(config), host_key),
+        )?;
+        let known_hosts = known_hosts.to_string_lossy().to_string();
+        let mut child = Command::new("sftp")
+            .arg("-b")
+            .arg("-")
+            .arg("-P")
+            .arg(config.port.to_string())
+            .arg("-i")
+            .arg(&identity)
+            .args(["-o", "BatchMode=yes", "-o", "IdentitiesOnly=yes"])
+            .args(["-o", "StrictHostKeyChecking=yes"])
+            .arg("-o")
+            .arg(format!("UserKnownHostsFile={}", known_hosts))
+            .arg("-o")
+            .arg(format!("GlobalKnownHostsFile={}", known_hosts))
+            .arg("-o")
+            .arg(format!("ConnectTimeout={}", CONNECT_TIMEOUT.as_secs()))
+            .arg(format!("{}@{}", config.username, config.host))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start sftp (is OpenSSH installed?): {}", e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(script.as_bytes())
+                .map_err(|e| format!("Failed to send sftp commands: {}", e))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("sftp failed: {}", e))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.contains("Host key verification failed") {
+            return Err(format!(
+                "{} did not present the pinned host key; check remote_host_key",
+                config.host
+            ));
+        }
+        Err(format!("SFTP transfer failed: {}", stderr))
+    })();
+    let _ = fs::remove_dir_all(&workdir);
+    run
+}
+
+fn sftp_upload(config: &RemoteConfig, local: &Path, name: &str) -> Result<(), String> {
+    let mut script = String::new();
+    if let Some(directory) = &config.directory {
+        script.push_str(&format!("cd {}\n", sftp_quote(directory)));
+    }
+    let part = format!("{}.part", name);
+    script.push_str(&format!(
+        "put {} {}\n",
+        sftp_quote(&local.to_string_lossy()),
+        sftp_quote(&part)
+    ));
+    // A leading '-' lets the batch go on when there is no older copy to replace
+    script.push_str(&format!("-rm {}\n", sftp_quote(name)));
+    script.push_str(&format!(
+        "rename {} {}\n",
+        sftp_quote(&part),
+        sftp_quote(name)
+    ));
+    sftp_batch(config, &script)
+}
+
+fn sftp_check(config: &RemoteConfig) -> Result<(), String> {
+    let script = match &config.directory {
+        Some(directory) => format!("cd {}\npwd\n", sftp_quote(directory)),
+        None => "pwd\n".to_string(),
+    };
+    sftp_batch(config, &script)
+}
+
+fn ssh_host_keys(config: &RemoteConfig) -> Result<Vec<RemoteHostKey>, String> {
+    let output = Command::new("ssh-keyscan")
+        .args(["-T", &CONNECT_TIMEOUT.as_secs().to_string()])
+        .args(["-p", &config.port.to_string()])
+        .arg(&config.host)
+        .output()
+        .map_err(|e| format!("Failed to start ssh-keyscan (is OpenSSH installed?): {}", e))?;
+    let keys: Vec<RemoteHostKey> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace().skip(1);
+            let (key_type, blob) = (parts.next()?, parts.next()?);
+            let digest = hash(MessageDigest::sha256(), &BASE64.decode(blob).ok()?).ok()?;
+            Some(RemoteHostKey {
+                key_type: key_type.to_string(),
+                key: format!("{} {}", key_type, blob),
+                fingerprint: format!("SHA256:{}", BASE64_NO_PAD.encode(digest)),
+            })
+        })
+        .collect();
+    if keys.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(format!(
+            "{} did not return any host keys: {}",
+            config.host, stderr
+        ));
+    }
+    Ok(keys)
+}
+
+// FTPS (explicit AUTH TLS)
+
+fn normalize_fingerprint(value: &str) -> String {
+    value
+        .trim()
+        .trim_start_matches("SHA256:")
+        .trim_start_matches("sha256:")
+        .replace(':', "")
+        .to_ascii_uppercase()
+}
+
+fn certificate_fingerprint(stream: &SslStream<TcpStream>) -> Result<String, String> {
+    let certificate = stream
+        .ssl()
+        .peer_certificate()
+        .ok_or("The FTPS server sent no certificate")?;
+    let digest = certificate
+        .digest(MessageDigest::sha256())
+        .map_err(|e| format!("Failed to read the server certificate: {}", e))?;
+    Ok(format!(
+        "SHA256:{}",
+        digest
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(":")
+    ))
+}
+
+fn read_reply(stream: &mut impl Read) -> Result<(u16, String), String> {
+    let mut text = String::new();
+    let mut first_code = None;
+    loop {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while !line.ends_with(b"\n") {
+            stream
+                .read_exact(&mut byte)
+                .map_err(|e| format!("Failed to read from FTP server: {}", e))?;
+            line.push(byte[0]);
+            if line.len() > MAX_REPLY_LINE {
+                return Err("FTP server sent an overlong reply".to_string());
+            }
+        }
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        let code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+        text.push_str(&line);
+        text.push('\n');
+        match (first_code, code) {
+            // "123-" opens a multi-line reply that ends at "123 "
+            (None, Some(code)) if line.as_bytes().get(3) == Some(&b'-') => first_code = Some(code),
+            (None, Some(code)) => return Ok((code, text)),
+            (Some(first), Some(code)) if code == first && line.as_bytes().get(3) != Some(&b'-') => {
+                return Ok((code, text))
+            }
+            (None, None) => return Err(format!("Unexpected FTP reply '{}'", line)),
+            _ => {}
+        }
+    }
+}
+
+fn send_line(stream: &mut impl Write, line: &str) -> Result<(), String> {
+    stream
+        .write_all(format!("{}\r\n", line).as_bytes())
+        .and_then(|_| stream.flush())
+        .map_err(|e| format!("Failed to write to FTP server: {}", e))
+}
+
+struct FtpSession {
+    control: SslStream<TcpStream>,
+    connector: SslConnector,
+    host: String,
+    peer: IpAddr,
+}
+
+impl FtpSession {
+    fn command(&mut self, line: &str) -> Result<(u16, String), String> {
+        send_line(&mut self.control, line)?;
+        read_reply(&mut self.control)
+    }
+
+    fn expect(&mut self, line: &str, codes: &[u16], what: &str) -> Result<String, String> {
+        let (code, text) = self.command(line)?;
+        if codes.contains(&code) {
+            Ok(text)
+        } else {
+            Err(format!("FTP {} failed ({}): {}", what, code, text.trim()))
+        }
+    }
+
+    // Passive data connection (EPSV, falling back to PASV) to the control connection's address,
+    // so servers behind NAT that advertise a private address still work
+    fn data_port(&mut self) -> Result<u16, String> {
+        let (code, text) = self.command("EPSV")?;
+        if code == 229 {
+            if let Some(port) = text
+                .split('|')
+                .filter_map(|part| part.parse::<u16>().ok())
+                .next()
+            {
+                return Ok(port);
+            }
+        }
+        let text = self.expect("PASV", &[227], "passive mode")?;
+        let numbers: Vec<u16> = text
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+            .collect();
+        match numbers.as_slice() {
+            [.., p1, p2] if numbers.len() >= 7 => Ok(p1 * 256 + p2),
+            _ => Err(format!("Unexpected passive mode reply '{}'", text.trim())),
+        }
+    }
+
+    fn open_data(&mut self, port: u16) -> Result<SslStream<TcpStream>, String> {
+        let tcp = TcpStream::connect_timeout(&SocketAddr::new(self.peer, port), CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to open the FTP data connection: {}", e))?;
+        tcp.set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+            .map_err(|e| format!("Failed to configure FTP data socket: {}", e))?;
+        let mut configuration = self
+            .connector
+            .configure()
+            .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+        // Many servers refuse a data connection that doesn't resume the control session
+        if let Some(session) = self.control.ssl().session().map(|s| s.to_owned()) {
+            // The session comes from a connection made with the same SslConnector
+            unsafe {
+                let _ = configuration.set_session(&session);
+            }
+        }
+        configuration
+            .connect(&self.host, tcp)
+            .map_err(|e| format!("TLS handshake on the FTP data connection failed: {}", e))
+    }
+}
+
+fn tls_connector(pinned: bool) -> Result<SslConnector, String> {
+    let mut builder = SslConnector::builder(SslMethod::tls_client())
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?;
+    if pinned {
+        // Checked against the pin after the handshake instead of the CA store
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    Ok(builder.build())
+}
+
+fn ftps_handshake(
+    config: &RemoteConfig,
+    pinned: bool,
+) -> Result<(SslStream<TcpStream>, SslConnector, IpAddr), String> {
+    let address = (config.host.as_str(), config.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", config.host, e))?
+        .next()
+        .ok_or_else(|| format!("No address found for {}", config.host))?;
+    let mut tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+        .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+    tcp.set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| tcp.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| format!("Failed to configure FTP socket: {}", e))?;
+    let (code, text) = read_reply(&mut tcp)?;
+    if code != 220 {
+        return Err(format!(
+            "FTP server refused the connection: {}",
+            text.trim()
+        ));
+    }
+    send_line(&mut tcp, "AUTH TLS")?;
+    let (code, text) = read_reply(&mut tcp)?;
+    if code != 234 {
+        return Err(format!(
+            "{} does not offer FTPS (AUTH TLS): {}",
+            config.host,
+            text.trim()
+        ));
+    }
+    let connector = tls_connector(pinned)?;
+    let control = connector
+        .connect(&config.host, tcp)
+        .map_err(|e| format!("TLS handshake with {} failed: {}", config.host, e))?;
+    Ok((control, connector, address.ip()))
+}
+
+fn ftps_login(config: &RemoteConfig) -> Result<FtpSession, String> {
+    let (control, connector, peer) = ftps_handshake(config, config.host_key.is_some())?;
+    if let Some(pin) = &config.host_key {
+        let presented = certificate_fingerprint(&control)?;
+        if normalize_fingerprint(&presented) != normalize_fingerprint(pin) {
+            return Err(format!(
+                "{} presented certificate {}, not the pinned one",
+                config.host, presented
+            ));
+        }
+    }
+    let mut session = FtpSession {
+        control,
+        connector,
+        host: config.host.clone(),
+        peer,
+    };
+    let (code, text) = session.command(&format!("USER {}", config.username))?;
+    match code {
+        230 => {}
+        331 => {
+            let password = remote_credential().ok_or("No FTPS password is stored")?;
+            if password.chars().any(|c| c == '\r' || c == '\n') {
+                return Err("The FTPS password contains unsupported characters".to_string());
+            }
+            session.expect(&format!("PASS {}", password), &[230], "login")?;
+        }
+        _ => return Err(format!("FTP login failed ({}): {}", code, text.trim())),
+    }
+    session.expect("PBSZ 0", &[200], "PBSZ")?;
+    session.expect("PROT P", &[200], "PROT")?;
+    session.expect("TYPE I", &[200], "TYPE")?;
+    if let Some(directory) = &config.directory {
+        session.expect(&format!("CWD {}", directory), &[250], "change folder")?;
+    }
+    Ok(session)
+}
+
+fn ftps_upload(config: &RemoteConfig, local: &Path, name: &str) -> Result<(), String> {
+    let mut file = File::open(local).map_err(|e| format!("Failed to open {:?}: {}", local, e))?;
+    let mut session = ftps_login(config)?;
+    let part = format!("{}.part", name);
+    let port = session.data_port()?;
+    session.expect(&format!("STOR {}", part), &[125, 150], "upload")?;
+    let mut data = session.open_data(port)?;
+    std::io::copy(&mut file, &mut data)
+        .map_err(|e| format!("Failed to upload {:?}: {}", local, e))?;
+    let _ = data.shutdown();
+    drop(data);
+    let (code, text) = read_reply(&mut session.control)?;
+    if code != 226 && code != 250 {
+        return Err(format!("FTP upload failed ({}): {}", code, text.trim()));
+    }
+    // Some servers won't rename over an existing file
+    let _ = session.command(&format!("DELE {}", name));
+    session.expect(&format!("RNFR {}", part), &[350], "rename")?;
+    session.expect(&format!("RNTO {}", name), &[250], "rename")?;
+    let _ = session.command("QUIT");
+    Ok(())
+}
+
+fn ftps_check(config: &RemoteConfig) -> Result<(), String> {
+    let mut session = ftps_login(config)?;
+    session.expect("PWD", &[257], "PWD")?;
+    let _ = session.command("QUIT");
+    Ok(())
+}
+
+fn ftps_certificate(config: &RemoteConfig) -> Result<Vec<RemoteHostKey>, String> {
+    let (control, _, _) = ftps_handshake(config, true)?;
+    let fingerprint = certificate_fingerprint(&control)?;
+    Ok(vec![RemoteHostKey {
+        key_type: "x509".to_string(),
+        key: fingerprint.clone(),
+        fingerprint,
+    }])
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Transfer task failed: {}", e))
+        .and_then(|result| result)
+}
+
+// Upload a local file as `name` into the configured folder
+pub async fn upload_file(
+    settings: &ShellSettings,
+    local: PathBuf,
+    name: &str,
+) -> Result<UploadResult, String> {
+    let config = remote_config(settings)?;
+    check_name(name, "file name")?;
+    let size_bytes = fs::metadata(&local)
+        .map_err(|e| format!("Failed to read {:?}: {}", local, e))?
+        .len();
+    let started = Instant::now();
+    let (worker_config, worker_name) = (config.clone(), name.to_string());
+    let result = blocking(move || match worker_config.protocol {
+        Protocol::Sftp => sftp_upload(&worker_config, &local, &worker_name),
+        Protocol::Ftps => ftps_upload(&worker_config, &local, &worker_name),
+    })
+    .await;
+    let remote = remote_path(&config, name);
+    match &result {
+        Ok(()) => update_stats(|stats| {
+            stats.uploads += 1;
+            stats.last_upload_at = Some(Utc::now());
+            stats.last_file = Some(remote.clone());
+            stats.last_error = None;
+        }),
+        Err(err) => update_stats(|stats| stats.last_error = Some(err.clone())),
+    }
+    result?;
+    append_app_log(&format!(
+        "Uploaded {} ({} bytes) to {}",
+        remote, size_bytes, config.host
+    ));
+    Ok(UploadResult {
+        remote_path: remote,
+        size_bytes,
+        duration_ms: started.elapsed().as_millis(),
+    })
+}
+
+// Copy a finished backup off-site without holding up the backup scheduler
+pub fn upload_backup(settings: &ShellSettings, path: PathBuf) {
+    let settings = settings.clone();
+    tauri::async_runtime::spawn(async move {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Err(err) = upload_file(&settings, path, &name).await {
+            eprintln!("Backup upload failed: {}", err);
+            append_app_log(&format!("Backup upload failed: {}", err));
+        }
+    });
+}
+
+async fn upload_export(
+    settings: &ShellSettings,
+    range: (NaiveDate, NaiveDate),
+) -> Result<UploadResult, String> {
+    let name = format!("attendance_{}_{}.csv", range.0, range.1);
+    let local = std::env::temp_dir().join(format!("ztkapp_{}_{}", random_hex(8), name));
+    let target = local.to_string_lossy().to_string();
+    let zones = registered_zones();
+    let written =
+        run_db(move |connection| write_csv(connection, range, None, &zones, &target, |_, _| {}))
+            .await;
+    let result = match written {
+        Ok(_) => upload_file(settings, local.clone(), &name).await,
+        Err(err) => Err(err),
+    };
+    let _ = fs::remove_file(&local);
+    result
+}
+
+// The scheduled run the current time falls in, if an export upload is due today
+fn scheduled_slot(config: &ShellSettings, now: DateTime<Local>) -> Option<NaiveDate> {
+    let at = NaiveTime::parse_from_str(&config.remote_export_time, "%H:%M").ok()?;
+    let today = now.date_naive();
+    let due = match config.remote_export_schedule.as_str() {
+        "daily" => true,
+        "weekly" => today.weekday().number_from_monday() == config.remote_export_weekday,
+        _ => false,
+    };
+    (due && now.time() >= at).then_some(today)
+}
+
+pub fn spawn_remote_export_scheduler(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_slot: Option<NaiveDate> = None;
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let config = current_settings(&settings);
+            let Some(slot) = scheduled_slot(&config, Local::now()) else {
+                continue;
+            };
+            if last_slot == Some(slot) {
+                continue;
+            }
+            last_slot = Some(slot);
+
+            // Complete days only: yesterday, or the seven days before today
+            let Some(yesterday) = slot.pred_opt() else {
+                continue;
+            };
+            let from = match config.remote_export_schedule.as_str() {
+                "weekly" => yesterday - chrono::Duration::days(6),
+                _ => yesterday,
+            };
+            if let Err(err) = upload_export(&config, (from, yesterday)).await {
+                eprintln!("Scheduled export upload failed: {}", err);
+                append_app_log(&format!("Scheduled export upload failed: {}", err));
+            }
+        }
+    });
+}
+
+// Keys the configured server presents, so the user can compare and pin one
+#[tauri::command]
+pub async fn scan_remote_host_key(
+    settings: State<'_, SharedSettings>,
+) -> Result<Vec<RemoteHostKey>, String> {
+    lock::ensure_unlocked()?;
+    let config = remote_config(&current_settings(&settings))?;
+    blocking(move || match config.protocol {
+        Protocol::Sftp => ssh_host_keys(&config),
+        Protocol::Ftps => ftps_certificate(&config),
+    })
+    .await
+}
+
+// Log in and open the folder without transferring anything
+#[tauri::command]
+pub async fn test_remote_destination(settings: State<'_, SharedSettings>) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let config = remote_config(&current_settings(&settings))?;
+    let result = blocking(move || match config.protocol {
+        Protocol::Sftp => sftp_check(&config),
+        Protocol::Ftps => ftps_check(&config),
+    })
+    .await;
+    if let Err(err) = &result {
+        update_stats(|stats| stats.last_error = Some(err.clone()));
+    }
+    result
+}
+
+// Upload an existing export or backup file
+#[tauri::command]
+pub async fn upload_to_remote(
+    path: String,
+    settings: State<'_, SharedSettings>,
+) -> Result<UploadResult, String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        let local = check_path(&path, Access::Read)?;
+        let name = local
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| format!("{} is not a file", path))?;
+        upload_file(&current_settings(&settings), local, &name).await
+    }
+    .await;
+    audited("upload_to_remote", &path, result)
+}
+
+#[tauri::command]
+pub fn get_remote_status(settings: State<SharedSettings>) -> RemoteStats {
+    let config = current_settings(&settings);
+    let mut stats = STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default();
+    stats.protocol = config.remote_protocol.clone();
+    stats.configured = remote_config(&config).is_ok();
+    stats
+}
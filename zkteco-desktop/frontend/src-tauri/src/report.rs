@@ -0,0 +1,405 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Instant;
+
+use chrono::{Local, NaiveDate};
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+use rusqlite::Connection;
+use tauri::AppHandle;
+
+use crate::append_app_log;
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::{
+    daily_rows, device_filter, parse_range, resolve_save_path, time_part, DailyRow, ExportRange,
+    ExportResult,
+};
+use crate::lock;
+use crate::protect;
+
+// Printable monthly attendance report: a summary table followed by one section per employee,
+// each starting on a new A4 page under the company header
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const ROW_HEIGHT: f32 = 6.0;
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ReportTemplate {
+    pub company_name: String,
+    pub company_address: Option<String>,
+    pub title: Option<String>,
+    // TrueType font for names outside Latin-1 (e.g. Vietnamese); the built-in Helvetica only
+    // covers Windows-1252
+    pub font_path: Option<String>,
+    pub include_employee_pages: Option<bool>,
+}
+
+struct ReportWriter<'a> {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    template: &'a ReportTemplate,
+    period: String,
+    page: u32,
+    y: f32,
+}
+
+impl<'a> ReportWriter<'a> {
+    fn new(template: &'a ReportTemplate, period: String) -> Result<Self, String> {
+        let title = template
+            .title
+            .clone()
+            .unwrap_or_else(|| "Attendance report".to_string());
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+        let pdf_error = |e: printpdf::Error| format!("Failed to prepare PDF fonts: {}", e);
+        let (font, bold) = match &template.font_path {
+            Some(path) => {
+                let file =
+                    File::open(path).map_err(|e| format!("Failed to open font {}: {}", path, e))?;
+                let font = doc.add_external_font(file).map_err(pdf_error)?;
+                (font.clone(), font)
+            }
+            None => (
+                doc.add_builtin_font(BuiltinFont::Helvetica)
+                    .map_err(pdf_error)?,
+                doc.add_builtin_font(BuiltinFont::HelveticaBold)
+                    .map_err(pdf_error)?,
+            ),
+        };
+        let layer = doc.get_page(page).get_layer(layer);
+        let mut writer = ReportWriter {
+            doc,
+            layer,
+            font,
+            bold,
+            template,
+            period,
+            page: 1,
+            y: 0.0,
+        };
+        writer.draw_header();
+        Ok(writer)
+    }
+
+    fn text(&self, text: &str, size: f32, x: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer.use_text(text, size, Mm(x), Mm(self.y), font);
+    }
+
+    fn rule(&self) {
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y - 1.5)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y - 1.5)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    // Company header and page number, repeated on every page
+    fn draw_header(&mut self) {
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.text(&self.template.company_name, 14.0, MARGIN, true);
+        self.text(
+            &format!("Page {}", self.page),
+            8.0,
+            PAGE_WIDTH - MARGIN - 15.0,
+            false,
+        );
+        if let Some(address) = &self.template.company_address {
+            self.y -= 5.0;
+            self.text(address, 9.0, MARGIN, false);
+        }
+        self.y -= 7.0;
+        let title = self
+            .template
+            .title
+            .clone()
+            .unwrap_or_else(|| "Attendance report".to_string());
+        self.text(&format!("{} - {}", title, self.period), 11.0, MARGIN, false);
+        self.rule();
+        self.y -= 10.0;
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Report");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.page += 1;
+        self.draw_header();
+    }
+
+    // Columns are (text, x offset from the margin in mm)
+    fn row(&mut self, columns: &[(String, f32)], bold: bool) {
+        if self.y < MARGIN + ROW_HEIGHT {
+            self.new_page();
+        }
+        for (text, x) in columns {
+            self.text(text, 9.0, MARGIN + x, bold);
+        }
+        if bold {
+            self.rule();
+        }
+        self.y -= ROW_HEIGHT;
+    }
+
+    fn heading(&mut self, text: &str) {
+        if self.y < MARGIN + ROW_HEIGHT * 4.0 {
+            self.new_page();
+        }
+        self.text(text, 11.0, MARGIN, true);
+        self.y -= ROW_HEIGHT + 2.0;
+    }
+
+    // One employee's days with a total row, as on the report's employee pages and the slip
+    fn employee_section(&mut self, days: &[&DailyRow]) {
+        self.heading(&format!("{} ({})", days[0].name, days[0].user_id));
+        self.row(
+            &day_columns([
+                "Date".into(),
+                "First in".into(),
+                "Last out".into(),
+                "Punches".into(),
+                "Hours".into(),
+            ]),
+            true,
+        );
+        for day in days {
+            self.row(
+                &day_columns([
+                    day.date.clone(),
+                    time_part(&day.first_in).to_string(),
+                    last_out(day),
+                    day.punches.to_string(),
+                    format!("{:.2}", day.hours),
+                ]),
+                false,
+            );
+        }
+        self.row(
+            &day_columns([
+                "Total".into(),
+                String::new(),
+                String::new(),
+                days.iter().map(|d| d.punches).sum::<u32>().to_string(),
+                format!("{:.2}", days.iter().map(|d| d.hours).sum::<f64>()),
+            ]),
+            true,
+        );
+    }
+
+    fn save(mut self, path: &Path) -> Result<(), String> {
+        self.y = MARGIN;
+        self.text(
+            &format!("Generated {}", Local::now().format("%Y-%m-%d %H:%M")),
+            7.0,
+            MARGIN,
+            false,
+        );
+        let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+        self.doc
+            .save(&mut BufWriter::new(file))
+            .map_err(|e| format!("Failed to write PDF report: {}", e))
+    }
+}
+
+// A single punch has no last out
+fn last_out(day: &DailyRow) -> String {
+    if day.punches > 1 {
+        time_part(&day.last_out).to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn summary_columns(values: [String; 5]) -> Vec<(String, f32)> {
+    values
+        .into_iter()
+        .zip([0.0, 25.0, 95.0, 125.0, 150.0])
+        .collect()
+}
+
+fn day_columns(values: [String; 5]) -> Vec<(String, f32)> {
+    values
+        .into_iter()
+        .zip([0.0, 35.0, 65.0, 95.0, 125.0])
+        .collect()
+}
+
+fn sheet_columns(values: [String; 5]) -> Vec<(String, f32)> {
+    values
+        .into_iter()
+        .zip([0.0, 25.0, 95.0, 125.0, 155.0])
+        .collect()
+}
+
+pub fn write_report(
+    connection: &Connection,
+    range: (NaiveDate, NaiveDate),
+    devices: Option<String>,
+    template: &ReportTemplate,
+    path: &Path,
+) -> Result<u64, String> {
+    let daily = daily_rows(connection, range, &devices)?;
+    let mut employees: Vec<Vec<&DailyRow>> = Vec::new();
+    for day in &daily {
+        match employees.last_mut() {
+            Some(days) if days[0].user_id == day.user_id => days.push(day),
+            _ => employees.push(vec![day]),
+        }
+    }
+
+    let mut report = ReportWriter::new(template, format!("{} to {}", range.0, range.1))?;
+    report.heading("Summary");
+    report.row(
+        &summary_columns([
+            "User ID".into(),
+            "Name".into(),
+            "Days".into(),
+            "Punches".into(),
+            "Hours".into(),
+        ]),
+        true,
+    );
+    for days in &employees {
+        report.row(
+            &summary_columns([
+                days[0].user_id.clone(),
+                days[0].name.clone(),
+                days.len().to_string(),
+                days.iter().map(|d| d.punches).sum::<u32>().to_string(),
+                format!("{:.2}", days.iter().map(|d| d.hours).sum::<f64>()),
+            ]),
+            false,
+        );
+    }
+    if employees.is_empty() {
+        report.row(&[("No attendance in this period".to_string(), 0.0)], false);
+    }
+
+    if template.include_employee_pages.unwrap_or(true) {
+        for days in &employees {
+            report.new_page();
+            report.employee_section(days);
+        }
+    }
+    report.save(path)?;
+    Ok(employees.len() as u64)
+}
+
+// Reception's check-in sheet for one day: everyone who punched, with first in and last out
+pub fn write_check_in_sheet(
+    connection: &Connection,
+    date: NaiveDate,
+    devices: Option<String>,
+    template: &ReportTemplate,
+    path: &Path,
+) -> Result<u64, String> {
+    let daily = daily_rows(connection, (date, date), &devices)?;
+    let mut sheet = ReportWriter::new(template, date.to_string())?;
+    sheet.row(
+        &sheet_columns([
+            "User ID".into(),
+            "Name".into(),
+            "First in".into(),
+            "Last out".into(),
+            "Punches".into(),
+        ]),
+        true,
+    );
+    for day in &daily {
+        sheet.row(
+            &sheet_columns([
+                day.user_id.clone(),
+                day.name.clone(),
+                time_part(&day.first_in).to_string(),
+                last_out(day),
+                day.punches.to_string(),
+            ]),
+            false,
+        );
+    }
+    if daily.is_empty() {
+        sheet.row(&[("Nobody has checked in".to_string(), 0.0)], false);
+    }
+    sheet.save(path)?;
+    Ok(daily.len() as u64)
+}
+
+// One employee's attendance slip for a period; returns the number of days worked
+pub fn write_attendance_slip(
+    connection: &Connection,
+    user_id: &str,
+    range: (NaiveDate, NaiveDate),
+    template: &ReportTemplate,
+    path: &Path,
+) -> Result<u64, String> {
+    let daily = daily_rows(connection, range, &None)?;
+    let days: Vec<&DailyRow> = daily.iter().filter(|d| d.user_id == user_id).collect();
+    let mut slip = ReportWriter::new(template, format!("{} to {}", range.0, range.1))?;
+    if days.is_empty() {
+        slip.heading(user_id);
+        slip.row(&[("No attendance in this period".to_string(), 0.0)], false);
+    } else {
+        slip.employee_section(&days);
+    }
+    slip.save(path)?;
+    Ok(days.len() as u64)
+}
+
+// Returns None when the save dialog is cancelled
+#[tauri::command]
+pub async fn export_attendance_pdf(
+    app: AppHandle,
+    range: ExportRange,
+    template: Option<ReportTemplate>,
+    devices: Option<Vec<String>>,
+    path: Option<String>,
+    password: Option<String>,
+) -> Result<Option<ExportResult>, String> {
+    lock::ensure_unlocked()?;
+    let range = parse_range(&range)?;
+    let devices = device_filter(&devices);
+    let template = template.unwrap_or_default();
+    let password = protect::check_password(password)?;
+    let Some(path) = resolve_save_path(
+        &app,
+        path,
+        "PDF document",
+        "pdf",
+        format!("attendance_{}_{}.pdf", range.0, range.1),
+    )
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let written = protect::write_path(&path, &password)?;
+    let target = written.clone();
+    let employees =
+        run_db(move |connection| write_report(connection, range, devices, &template, &target))
+            .await;
+    let employees = protect::finish(employees, written, &path, password).await;
+    let audit_target = path.to_string_lossy().to_string();
+    let result = match employees {
+        Ok((employees, path)) => {
+            append_app_log(&format!(
+                "Exported attendance PDF ({} to {}, {} employees) to {}",
+                range.0, range.1, employees, path
+            ));
+            Ok(Some(ExportResult::new(path, employees, started)))
+        }
+        Err(err) => {
+            append_app_log(&format!("Attendance PDF export failed: {}", err));
+            Err(err)
+        }
+    };
+    audited("export_attendance_pdf", &audit_target, result)
+}
@@ -0,0 +1,478 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::{Method, Url};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::path_policy::{check_path, Access};
+use crate::secrets::{hex, random_hex, s3_secret_key};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Off-machine copies of database backups and app bundles in an S3-compatible bucket (AWS S3,
+// MinIO, Wasabi, ...). Requests are signed with AWS Signature Version 4. Object keys are
+// "<prefix>/<kind>/<yyyy>/<mm>/<dd>/<host>-<file>" with kind "backups", "bundles" or "exports",
+// so bucket lifecycle rules can expire each kind by prefix and several PCs can share a bucket.
+// Failed uploads are retried a few times; every upload is appended to s3_uploads.jsonl.
+const KINDS: [&str; 3] = ["backups", "bundles", "exports"];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(120),
+];
+// The upload history is cut back to this many entries once it grows past twice that
+const HISTORY_KEEP: usize = 500;
+
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UploadRecord {
+    id: String,
+    kind: String,
+    file: String,
+    bucket: String,
+    key: String,
+    size_bytes: u64,
+    attempts: u32,
+    at: DateTime<Utc>,
+    success: bool,
+    etag: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct S3Config {
+    endpoint: Url,
+    region: String,
+    bucket: String,
+    prefix: String,
+    path_style: bool,
+    access_key_id: String,
+}
+
+enum Attempt {
+    Stored(Option<String>),
+    // 4xx other than 408/429: the same request will fail again
+    Refused(String),
+    Failed(String),
+}
+
+fn history_path() -> PathBuf {
+    resolve_app_data_dir().join("s3_uploads.jsonl")
+}
+
+fn s3_config(settings: &ShellSettings) -> Result<S3Config, String> {
+    let bucket = settings
+        .s3_bucket
+        .as_deref()
+        .map(str::trim)
+        .filter(|bucket| !bucket.is_empty())
+        .ok_or("No S3 bucket is configured")?
+        .to_string();
+    let access_key_id = settings
+        .s3_access_key_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .ok_or("No S3 access key id is configured")?
+        .to_string();
+    let region = match settings.s3_region.trim() {
+        "" => "us-east-1".to_string(),
+        region => region.to_string(),
+    };
+    let endpoint = match settings.s3_endpoint.as_deref().map(str::trim) {
+        Some(endpoint) if !endpoint.is_empty() => endpoint.to_string(),
+        _ => format!("https://s3.{}.amazonaws.com", region),
+    };
+    let endpoint =
+        Url::parse(&endpoint).map_err(|_| format!("'{}' is not a valid S3 endpoint", endpoint))?;
+    if !["http", "https"].contains(&endpoint.scheme()) || endpoint.host_str().is_none() {
+        return Err(format!("'{}' is not a valid S3 endpoint", endpoint));
+    }
+    Ok(S3Config {
+        endpoint,
+        region,
+        bucket,
+        prefix: settings.s3_prefix.trim().trim_matches('/').to_string(),
+        path_style: settings.s3_path_style,
+        access_key_id,
+    })
+}
+
+// Letters, digits, '-' and '_' only, so host names never need escaping in object keys
+fn host_label() -> String {
+    let host = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default();
+    let label: String = host
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if label.is_empty() {
+        "pc".to_string()
+    } else {
+        label.to_lowercase()
+    }
+}
+
+fn object_key(config: &S3Config, kind: &str, file: &str, now: DateTime<Utc>) -> String {
+    let key = format!(
+        "{}/{}/{}-{}",
+        kind,
+        now.format("%Y/%m/%d"),
+        host_label(),
+        file
+    );
+    if config.prefix.is_empty() {
+        key
+    } else {
+        format!("{}/{}", config.prefix, key)
+    }
+}
+
+// RFC 3986 percent-encoding as SigV4 expects; '/' is kept in object keys
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            b'/' if keep_slash => "/".to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// URL and canonical path of an object (or of the bucket itself for an empty key)
+fn object_url(config: &S3Config, key: &str) -> Result<(Url, String), String> {
+    let encoded = uri_encode(key, true);
+    let mut url = config.endpoint.clone();
+    let path = if config.path_style {
+        format!("/{}/{}", uri_encode(&config.bucket, false), encoded)
+    } else {
+        let host = format!(
+            "{}.{}",
+            config.bucket,
+            config.endpoint.host_str().unwrap_or_default()
+        );
+        url.set_host(Some(&host))
+            .map_err(|e| format!("Invalid bucket host {}: {}", host, e))?;
+        format!("/{}", encoded)
+    };
+    url.set_path(&path);
+    Ok((url, path))
+}
+
+fn hmac(key: &[u8], data: &str) -> Result<Vec<u8>, String> {
+    let key = PKey::hmac(key).map_err(|e| format!("Failed to create signing key: {}", e))?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)
+        .map_err(|e| format!("Failed to create signer: {}", e))?;
+    signer
+        .update(data.as_bytes())
+        .and_then(|_| signer.sign_to_vec())
+        .map_err(|e| format!("Failed to sign request: {}", e))
+}
+
+fn sha256_hex(data: &[u8]) -> Result<String, String> {
+    hash(MessageDigest::sha256(), data)
+        .map(|digest| hex(&digest))
+        .map_err(|e| format!("Failed to hash request: {}", e))
+}
+
+// AWS Signature Version 4 headers for a request without query parameters
+fn signed_headers(
+    config: &S3Config,
+    method: &Method,
+    url: &Url,
+    canonical_path: &str,
+    payload_hash: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<(String, String)>, String> {
+    let secret = s3_secret_key().ok_or("No S3 secret access key is stored")?;
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    // reqwest sends the port only when it isn't the scheme's default, and so must the signature
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let canonical_request = format!(
+        "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+        method, canonical_path, host, payload_hash, amz_date, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())?
+    );
+    let mut key = hmac(format!("AWS4{}", secret).as_bytes(), &date)?;
+    for part in [config.region.as_str(), "s3", "aws4_request"] {
+        key = hmac(&key, part)?;
+    }
+    let signature = hex(&hmac(&key, &string_to_sign)?);
+    Ok(vec![
+        ("x-amz-date".to_string(), amz_date),
+        ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+        (
+            "authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+                config.access_key_id, scope, signature
+            ),
+        ),
+    ])
+}
+
+async fn send(
+    config: &S3Config,
+    method: Method,
+    key: &str,
+    body: Vec<u8>,
+    payload_hash: &str,
+) -> Attempt {
+    let request = (|| {
+        let (url, canonical_path) = object_url(config, key)?;
+        let headers = signed_headers(
+            config,
+            &method,
+            &url,
+            &canonical_path,
+            payload_hash,
+            Utc::now(),
+        )?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        let mut request = client.request(method.clone(), url);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        Ok::<_, String>(request.body(body))
+    })();
+    let request = match request {
+        Ok(request) => request,
+        Err(err) => return Attempt::Refused(err),
+    };
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Attempt::Stored(
+            response
+                .headers()
+                .get("etag")
+                .and_then(|etag| etag.to_str().ok())
+                .map(|etag| etag.trim_matches('"').to_string()),
+        ),
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            // S3 errors are XML: <Error><Code>..</Code><Message>..</Message></Error>
+            let message = body
+                .split("<Message>")
+                .nth(1)
+                .and_then(|rest| rest.split("</Message>").next())
+                .unwrap_or(body.trim());
+            let error = format!("S3 returned {}: {}", status, message);
+            if status.is_client_error() && status.as_u16() != 408 && status.as_u16() != 429 {
+                Attempt::Refused(error)
+            } else {
+                Attempt::Failed(error)
+            }
+        }
+        Err(err) => Attempt::Failed(format!("Failed to reach {}: {}", config.endpoint, err)),
+    }
+}
+
+fn record_upload(record: &UploadRecord) {
+    let _guard = HISTORY_LOCK.lock();
+    let path = history_path();
+    let appended = serde_json::to_string(record)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(err) = appended {
+        eprintln!("Failed to record S3 upload: {}", err);
+        return;
+    }
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return;
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() > HISTORY_KEEP * 2 {
+        let mut kept = lines[lines.len() - HISTORY_KEEP..].join("\n");
+        kept.push('\n');
+        if let Err(err) = fs::write(&path, kept) {
+            eprintln!("Failed to trim S3 upload history: {}", err);
+        }
+    }
+}
+
+// Upload a file as a `kind` object, retrying transient failures
+pub async fn upload_file(
+    settings: &ShellSettings,
+    path: &Path,
+    kind: &str,
+) -> Result<UploadRecord, String> {
+    let config = s3_config(settings)?;
+    if !KINDS.contains(&kind) {
+        return Err(format!(
+            "Unknown upload kind '{}'; expected one of {}",
+            kind,
+            KINDS.join(", ")
+        ));
+    }
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("{:?} is not a file", path))?;
+    let body = fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let payload_hash = sha256_hex(&body)?;
+    let now = Utc::now();
+    let mut record = UploadRecord {
+        id: random_hex(8),
+        kind: kind.to_string(),
+        file: path.to_string_lossy().to_string(),
+        bucket: config.bucket.clone(),
+        key: object_key(&config, kind, &file, now),
+        size_bytes: body.len() as u64,
+        attempts: 0,
+        at: now,
+        success: false,
+        etag: None,
+        error: None,
+    };
+
+    let mut delays = RETRY_DELAYS.iter();
+    loop {
+        record.attempts += 1;
+        match send(
+            &config,
+            Method::PUT,
+            &record.key,
+            body.clone(),
+            &payload_hash,
+        )
+        .await
+        {
+            Attempt::Stored(etag) => {
+                record.success = true;
+                record.etag = etag;
+                record.error = None;
+                break;
+            }
+            Attempt::Refused(err) => {
+                record.error = Some(err);
+                break;
+            }
+            Attempt::Failed(err) => {
+                record.error = Some(err);
+                match delays.next() {
+                    Some(delay) => tokio::time::sleep(*delay).await,
+                    None => break,
+                }
+            }
+        }
+    }
+    record.at = Utc::now();
+    record_upload(&record);
+
+    match &record.error {
+        None => {
+            append_app_log(&format!(
+                "Uploaded {} to s3://{}/{} ({} bytes)",
+                file, record.bucket, record.key, record.size_bytes
+            ));
+            Ok(record)
+        }
+        Some(err) => {
+            append_app_log(&format!(
+                "S3 upload of {} failed after {} attempts: {}",
+                file, record.attempts, err
+            ));
+            Err(err.clone())
+        }
+    }
+}
+
+// Upload without holding up the caller (the backup scheduler, a bundle export)
+pub fn upload_in_background(settings: &ShellSettings, path: PathBuf, kind: &'static str) {
+    let settings = settings.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = upload_file(&settings, &path, kind).await {
+            eprintln!("S3 upload failed: {}", err);
+        }
+    });
+}
+
+// Check the bucket is reachable with the stored credentials
+#[tauri::command]
+pub async fn test_s3_connection(settings: State<'_, SharedSettings>) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let config = s3_config(&current_settings(&settings))?;
+    let empty_hash = sha256_hex(b"")?;
+    match send(&config, Method::HEAD, "", Vec::new(), &empty_hash).await {
+        Attempt::Stored(_) => Ok(()),
+        Attempt::Refused(err) | Attempt::Failed(err) => Err(err),
+    }
+}
+
+// Upload an existing backup, bundle or export; kind defaults to "exports"
+#[tauri::command]
+pub async fn upload_to_s3(
+    path: String,
+    kind: Option<String>,
+    settings: State<'_, SharedSettings>,
+) -> Result<UploadRecord, String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        let local = check_path(&path, Access::Read)?;
+        let kind = kind.unwrap_or_else(|| "exports".to_string());
+        upload_file(&current_settings(&settings), &local, &kind).await
+    }
+    .await;
+    audited("upload_to_s3", &path, result)
+}
+
+// Most recent first
+#[tauri::command]
+pub fn get_s3_uploads(limit: Option<usize>) -> Result<Vec<UploadRecord>, String> {
+    let content = match fs::read_to_string(history_path()) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to read S3 upload history: {}", err)),
+    };
+    let mut records: Vec<UploadRecord> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    records.reverse();
+    if let Some(limit) = limit {
+        records.truncate(limit);
+    }
+    Ok(records)
+}
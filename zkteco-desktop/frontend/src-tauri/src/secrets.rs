@@ -0,0 +1,621 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use tauri::State;
+
+use crate::audit::audited;
+use crate::encryption::KEYRING_SERVICE;
+use crate::lock;
+use crate::profiles::{active_profile, DEFAULT_PROFILE};
+use crate::registry::{save_registry, DeviceEntry, DeviceRegistry};
+use crate::settings::{save_settings, SharedSettings};
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Device COMM keys, the upstream API token, the backend health check token, the MQTT and LDAP
+// passwords, the local API token, the backend service token, the Google Sheets OAuth grant, the
+// SMTP password, the SFTP/FTPS credential, the S3 secret access key, the door unlock PIN (as a
+// scrypt hash, lock.rs), webhook signing secrets, Slack/Teams alert webhook URLs and HR adapter
+// credentials, kept only in the OS keychain. A registry entry holds an opaque
+// credential_ref (keychain account "device-credential:<ref>") instead of the key; tokens and
+// passwords are stored per profile, webhook secrets, alert URLs and HR adapter credentials per
+// webhook, channel or adapter id. migrate_plaintext_credentials moves keys left by older
+// versions (device_registry.json, shell_secrets.enc, shell_settings.json) into the keychain once.
+const DEVICE_ACCOUNT_PREFIX: &str = "device-credential:";
+const UPSTREAM_ACCOUNT_PREFIX: &str = "upstream-token:";
+const HEALTH_TOKEN_ACCOUNT_PREFIX: &str = "health-token:";
+const MQTT_ACCOUNT_PREFIX: &str = "mqtt-password:";
+const LDAP_ACCOUNT_PREFIX: &str = "ldap-password:";
+const GOOGLE_SHEETS_ACCOUNT_PREFIX: &str = "google-sheets:";
+const SMTP_ACCOUNT_PREFIX: &str = "smtp-password:";
+const REMOTE_ACCOUNT_PREFIX: &str = "remote-credential:";
+const S3_ACCOUNT_PREFIX: &str = "s3-secret:";
+const WEBHOOK_ACCOUNT_PREFIX: &str = "webhook-secret:";
+const ALERT_ACCOUNT_PREFIX: &str = "alert-webhook:";
+const HR_ADAPTER_ACCOUNT_PREFIX: &str = "hr-adapter:";
+const LOCAL_API_ACCOUNT_PREFIX: &str = "local-api-token:";
+const SERVICE_TOKEN_ACCOUNT_PREFIX: &str = "service-token:";
+const DOOR_PIN_ACCOUNT_PREFIX: &str = "door-pin:";
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct DeviceCredential {
+    // COMM key the backend reported for the device
+    reported: u32,
+    // Set on this PC with set_device_comm_key, wins over the reported key
+    override_key: Option<u32>,
+}
+
+// Former encrypted secrets file, only read by the migration
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct LegacySecrets {
+    device_comm_keys: HashMap<String, u32>,
+}
+
+// Credential ref -> credential, filled as keychain entries are read
+static CACHE: Mutex<Option<HashMap<String, DeviceCredential>>> = Mutex::new(None);
+
+pub fn device_account(reference: &str) -> String {
+    format!("{}{}", DEVICE_ACCOUNT_PREFIX, reference)
+}
+
+pub fn upstream_account(profile: &str) -> String {
+    format!("{}{}", UPSTREAM_ACCOUNT_PREFIX, profile)
+}
+
+pub fn health_token_account(profile: &str) -> String {
+    format!("{}{}", HEALTH_TOKEN_ACCOUNT_PREFIX, profile)
+}
+
+pub fn mqtt_account(profile: &str) -> String {
+    format!("{}{}", MQTT_ACCOUNT_PREFIX, profile)
+}
+
+pub fn ldap_account(profile: &str) -> String {
+    format!("{}{}", LDAP_ACCOUNT_PREFIX, profile)
+}
+
+pub fn google_sheets_account(profile: &str) -> String {
+    format!("{}{}", GOOGLE_SHEETS_ACCOUNT_PREFIX, profile)
+}
+
+pub fn smtp_account(profile: &str) -> String {
+    format!("{}{}", SMTP_ACCOUNT_PREFIX, profile)
+}
+
+pub fn remote_account(profile: &str) -> String {
+    format!("{}{}", REMOTE_ACCOUNT_PREFIX, profile)
+}
+
+pub fn s3_account(profile: &str) -> String {
+    format!("{}{}", S3_ACCOUNT_PREFIX, profile)
+}
+
+pub fn local_api_account(profile: &str) -> String {
+    format!("{}{}", LOCAL_API_ACCOUNT_PREFIX, profile)
+}
+
+pub fn service_token_account(profile: &str) -> String {
+    format!("{}{}", SERVICE_TOKEN_ACCOUNT_PREFIX, profile)
+}
+
+pub fn door_pin_account(profile: &str) -> String {
+    format!("{}{}", DOOR_PIN_ACCOUNT_PREFIX, profile)
+}
+
+pub fn webhook_account(webhook_id: &str) -> String {
+    format!("{}{}", WEBHOOK_ACCOUNT_PREFIX, webhook_id)
+}
+
+pub fn alert_account(channel_id: &str) -> String {
+    format!("{}{}", ALERT_ACCOUNT_PREFIX, channel_id)
+}
+
+pub fn hr_adapter_account(adapter_id: &str) -> String {
+    format!("{}{}", HR_ADAPTER_ACCOUNT_PREFIX, adapter_id)
+}
+
+fn keyring_entry(account: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .map_err(|e| format!("Failed to access the OS keychain: {}", e))
+}
+
+fn new_reference() -> String {
+    random_hex(16)
+}
+
+// Lowercase hex of bytes such as digests and signatures
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// len bytes from the OS RNG as hex, for tokens, ids and temporary names
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn read_credential(reference: &str) -> Result<Option<DeviceCredential>, String> {
+    if let Some(cached) = CACHE
+        .lock()
+        .ok()
+        .and_then(|cache| cache.as_ref()?.get(reference).cloned())
+    {
+        return Ok(Some(cached));
+    }
+    let credential: DeviceCredential =
+        match keyring_entry(&device_account(reference))?.get_password() {
+            Ok(value) => serde_json::from_str(&value)
+                .map_err(|e| format!("Invalid device credential in the OS keychain: {}", e))?,
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(err) => return Err(format!("Failed to read device credential: {}", err)),
+        };
+    if let Ok(mut cache) = CACHE.lock() {
+        cache
+            .get_or_insert_with(HashMap::new)
+            .insert(reference.to_string(), credential.clone());
+    }
+    Ok(Some(credential))
+}
+
+fn write_credential(reference: &str, credential: &DeviceCredential) -> Result<(), String> {
+    let value = serde_json::to_string(credential)
+        .map_err(|e| format!("Failed to serialize device credential: {}", e))?;
+    keyring_entry(&device_account(reference))?
+        .set_password(&value)
+        .map_err(|e| {
+            format!(
+                "Failed to store device credential in the OS keychain: {}",
+                e
+            )
+        })?;
+    if let Ok(mut cache) = CACHE.lock() {
+        cache
+            .get_or_insert_with(HashMap::new)
+            .insert(reference.to_string(), credential.clone());
+    }
+    Ok(())
+}
+
+// Drop cached credentials so the next access reads the keychain again, e.g. after a profile switch
+pub fn forget_cached_secrets() {
+    if let Ok(mut guard) = CACHE.lock() {
+        *guard = None;
+    }
+}
+
+// COMM key to use for a device, None when nothing is stored for its reference
+pub fn device_comm_key(reference: Option<&str>) -> Option<u32> {
+    match read_credential(reference?) {
+        Ok(credential) => credential.map(|c| c.override_key.unwrap_or(c.reported)),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    }
+}
+
+fn store_reported_key(device: &mut DeviceEntry) -> Result<bool, String> {
+    if device.comm_key == 0 && device.credential_ref.is_none() {
+        return Ok(false);
+    }
+    let reference = device.credential_ref.clone().unwrap_or_else(new_reference);
+    let mut credential = read_credential(&reference)?.unwrap_or_default();
+    let changed = credential.reported != device.comm_key || device.credential_ref.is_none();
+    if changed {
+        credential.reported = device.comm_key;
+        write_credential(&reference, &credential)?;
+    }
+    device.credential_ref = Some(reference);
+    device.comm_key = 0;
+    Ok(changed)
+}
+
+// Move the COMM keys of a device list fresh from the backend into the keychain, giving devices
+// a reference where needed; the keys are never saved to disk either way
+pub fn store_reported_keys(devices: &mut [DeviceEntry]) -> Result<(), String> {
+    for device in devices.iter_mut() {
+        store_reported_key(device)?;
+    }
+    Ok(())
+}
+
+// Keychain account of the active profile for a per-profile secret
+fn active_account(account: fn(&str) -> String) -> String {
+    account(&active_profile().unwrap_or_else(|| DEFAULT_PROFILE.to_string()))
+}
+
+fn read_secret(account: &str, label: &str) -> Option<String> {
+    let secret = keyring_entry(account).and_then(|entry| match entry.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(format!("Failed to read {}: {}", label, err)),
+    });
+    match secret {
+        Ok(secret) => secret.filter(|secret| !secret.is_empty()),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    }
+}
+
+// Store a secret, or remove it when empty
+fn write_secret(account: &str, label: &str, secret: &str) -> Result<(), String> {
+    let entry = keyring_entry(account)?;
+    if secret.is_empty() {
+        return match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(format!("Failed to remove {}: {}", label, err)),
+        };
+    }
+    entry
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store {} in the OS keychain: {}", label, e))
+}
+
+pub fn upstream_token() -> Option<String> {
+    read_secret(&active_account(upstream_account), "upstream token")
+}
+
+pub fn set_upstream_token(token: &str) -> Result<(), String> {
+    write_secret(&active_account(upstream_account), "upstream token", token)
+}
+
+// Bearer token sent with backend health checks
+pub fn backend_health_token() -> Option<String> {
+    read_secret(&active_account(health_token_account), "health check token")
+}
+
+pub fn set_backend_health_token(token: &str) -> Result<(), String> {
+    write_secret(
+        &active_account(health_token_account),
+        "health check token",
+        token,
+    )
+}
+
+pub fn mqtt_password() -> Option<String> {
+    read_secret(&active_account(mqtt_account), "MQTT password")
+}
+
+pub fn set_mqtt_password(password: &str) -> Result<(), String> {
+    write_secret(&active_account(mqtt_account), "MQTT password", password)
+}
+
+pub fn ldap_password() -> Option<String> {
+    read_secret(&active_account(ldap_account), "LDAP bind password")
+}
+
+pub fn set_ldap_password(password: &str) -> Result<(), String> {
+    write_secret(
+        &active_account(ldap_account),
+        "LDAP bind password",
+        password,
+    )
+}
+
+// OAuth client and refresh token as JSON (sheets.rs)
+pub fn google_sheets_grant() -> Option<String> {
+    read_secret(
+        &active_account(google_sheets_account),
+        "Google Sheets grant",
+    )
+}
+
+pub fn set_google_sheets_grant(grant: &str) -> Result<(), String> {
+    write_secret(
+        &active_account(google_sheets_account),
+        "Google Sheets grant",
+        grant,
+    )
+}
+
+pub fn smtp_password() -> Option<String> {
+    read_secret(&active_account(smtp_account), "SMTP password")
+}
+
+pub fn set_smtp_password(password: &str) -> Result<(), String> {
+    write_secret(&active_account(smtp_account), "SMTP password", password)
+}
+
+// FTPS password or SFTP private key
+pub fn remote_credential() -> Option<String> {
+    read_secret(&active_account(remote_account), "SFTP/FTPS credential")
+}
+
+pub fn set_remote_credential(credential: &str) -> Result<(), String> {
+    write_secret(
+        &active_account(remote_account),
+        "SFTP/FTPS credential",
+        credential,
+    )
+}
+
+pub fn s3_secret_key() -> Option<String> {
+    read_secret(&active_account(s3_account), "S3 secret access key")
+}
+
+pub fn set_s3_secret_key(key: &str) -> Result<(), String> {
+    write_secret(&active_account(s3_account), "S3 secret access key", key)
+}
+
+pub fn local_api_token() -> Option<String> {
+    read_secret(&active_account(local_api_account), "local API token")
+}
+
+pub fn set_local_api_token(token: &str) -> Result<(), String> {
+    write_secret(&active_account(local_api_account), "local API token", token)
+}
+
+pub fn service_token() -> Option<String> {
+    read_secret(
+        &active_account(service_token_account),
+        "backend service token",
+    )
+}
+
+pub fn set_service_token(token: &str) -> Result<(), String> {
+    write_secret(
+        &active_account(service_token_account),
+        "backend service token",
+        token,
+    )
+}
+
+// Unlike the other secrets a keychain error is returned, so the door PIN check fails closed
+pub fn door_pin_hash() -> Result<Option<String>, String> {
+    match keyring_entry(&active_account(door_pin_account))?.get_password() {
+        Ok(hash) => Ok(Some(hash).filter(|hash| !hash.is_empty())),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(format!("Failed to read door unlock PIN: {}", err)),
+    }
+}
+
+pub fn set_door_pin_hash(hash: &str) -> Result<(), String> {
+    write_secret(&active_account(door_pin_account), "door unlock PIN", hash)
+}
+
+pub fn webhook_secret(webhook_id: &str) -> Option<String> {
+    read_secret(&webhook_account(webhook_id), "webhook secret")
+}
+
+// An empty secret removes it
+pub fn set_webhook_secret(webhook_id: &str, secret: &str) -> Result<(), String> {
+    write_secret(&webhook_account(webhook_id), "webhook secret", secret)
+}
+
+pub fn alert_webhook_url(channel_id: &str) -> Option<String> {
+    read_secret(&alert_account(channel_id), "alert webhook URL")
+}
+
+// An empty URL removes it
+pub fn set_alert_webhook_url(channel_id: &str, url: &str) -> Result<(), String> {
+    write_secret(&alert_account(channel_id), "alert webhook URL", url)
+}
+
+pub fn hr_adapter_secret(adapter_id: &str) -> Option<String> {
+    read_secret(&hr_adapter_account(adapter_id), "HR adapter credential")
+}
+
+// An empty secret removes it
+pub fn set_hr_adapter_secret(adapter_id: &str, secret: &str) -> Result<(), String> {
+    write_secret(
+        &hr_adapter_account(adapter_id),
+        "HR adapter credential",
+        secret,
+    )
+}
+
+fn legacy_secrets_paths() -> (PathBuf, PathBuf) {
+    let dir = resolve_app_data_dir();
+    (dir.join("shell_secrets.enc"), dir.join("shell_secrets.key"))
+}
+
+fn read_legacy_secrets() -> Result<Option<LegacySecrets>, String> {
+    let (secrets_path, key_path) = legacy_secrets_paths();
+    let sealed = match fs::read(&secrets_path) {
+        Ok(sealed) => sealed,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(format!("Failed to read shell secrets: {}", err)),
+    };
+    let key = fs::read(&key_path).map_err(|e| format!("Failed to read secrets key: {}", e))?;
+    if sealed.len() <= NONCE_SIZE || key.len() != 32 {
+        return Err("Shell secrets file is corrupt".to_string());
+    }
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt shell secrets (wrong key or tampered file)".to_string())?;
+    serde_json::from_slice(&plain)
+        .map(Some)
+        .map_err(|e| format!("Invalid shell secrets: {}", e))
+}
+
+// COMM keys set with set_device_comm_key by older versions, kept in shell_secrets.enc
+fn migrate_legacy_overrides(devices: &mut [DeviceEntry]) -> Result<usize, String> {
+    let Some(legacy) = read_legacy_secrets()? else {
+        return Ok(0);
+    };
+    let mut moved = 0;
+    for (device_id, key) in legacy.device_comm_keys {
+        let Some(device) = devices.iter_mut().find(|d| d.id == device_id) else {
+            append_app_log(&format!(
+                "Dropped stored COMM key for unknown device {}",
+                device_id
+            ));
+            continue;
+        };
+        let reference = device.credential_ref.clone().unwrap_or_else(new_reference);
+        let mut credential = read_credential(&reference)?.unwrap_or_default();
+        credential.override_key = Some(key);
+        write_credential(&reference, &credential)?;
+        device.credential_ref = Some(reference);
+        moved += 1;
+    }
+    let (secrets_path, key_path) = legacy_secrets_paths();
+    let _ = fs::remove_file(secrets_path);
+    let _ = fs::remove_file(key_path);
+    Ok(moved)
+}
+
+// One-time move of plaintext credentials left by older versions; a no-op once done
+pub fn migrate_plaintext_credentials(registry: &DeviceRegistry, settings: &SharedSettings) {
+    let devices_result = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))
+        .and_then(|mut devices| {
+            // Registries saved by older versions still carry the key ("password")
+            let mut moved = 0;
+            for device in devices.iter_mut().filter(|d| d.comm_key != 0) {
+                if store_reported_key(device)? {
+                    moved += 1;
+                }
+            }
+            moved += migrate_legacy_overrides(&mut devices)?;
+            if moved > 0 {
+                save_registry(&devices)?;
+            }
+            Ok(moved)
+        });
+    match devices_result {
+        Ok(0) => {}
+        Ok(moved) => append_app_log(&format!(
+            "Moved {} device COMM keys to the OS keychain",
+            moved
+        )),
+        Err(err) => append_app_log(&format!(
+            "Failed to move COMM keys to the OS keychain: {}",
+            err
+        )),
+    }
+
+    let token_result = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))
+        .and_then(|mut guard| {
+            let Some(token) = guard.upstream_token.take() else {
+                return Ok(false);
+            };
+            set_upstream_token(&token)?;
+            save_settings(&guard)?;
+            Ok(true)
+        });
+    match token_result {
+        Ok(false) => {}
+        Ok(true) => append_app_log("Moved the upstream API token to the OS keychain"),
+        Err(err) => append_app_log(&format!(
+            "Failed to move the upstream token to the OS keychain: {}",
+            err
+        )),
+    }
+
+    let health_token_result = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))
+        .and_then(|mut guard| {
+            let Some(token) = guard.backend_health_token.take() else {
+                return Ok(false);
+            };
+            set_backend_health_token(&token)?;
+            save_settings(&guard)?;
+            Ok(true)
+        });
+    match health_token_result {
+        Ok(false) => {}
+        Ok(true) => append_app_log("Moved the health check token to the OS keychain"),
+        Err(err) => append_app_log(&format!(
+            "Failed to move the health check token to the OS keychain: {}",
+            err
+        )),
+    }
+
+    let door_pin_result = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))
+        .and_then(|mut guard| {
+            let Some(pin) = guard.door_unlock_pin.take() else {
+                return Ok(false);
+            };
+            lock::set_door_pin(&pin)?;
+            save_settings(&guard)?;
+            Ok(true)
+        });
+    match door_pin_result {
+        Ok(false) => {}
+        Ok(true) => append_app_log("Moved the door unlock PIN to the OS keychain"),
+        Err(err) => append_app_log(&format!(
+            "Failed to move the door unlock PIN to the OS keychain: {}",
+            err
+        )),
+    }
+}
+
+// Store (or with None, forget) the COMM key used for a device's native sessions
+#[tauri::command]
+pub fn set_device_comm_key(
+    device_id: String,
+    comm_key: Option<u32>,
+    registry: State<DeviceRegistry>,
+) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let stored = registry
+        .lock()
+        .map_err(|e| format!("Failed to lock device registry: {}", e))
+        .and_then(|mut devices| {
+            let device = devices
+                .iter_mut()
+                .find(|d| d.id == device_id)
+                .ok_or_else(|| format!("Unknown device: {}", device_id))?;
+            let reference = device.credential_ref.clone().unwrap_or_else(new_reference);
+            let mut credential = read_credential(&reference)?.unwrap_or_default();
+            credential.override_key = comm_key;
+            write_credential(&reference, &credential)?;
+            if device.credential_ref.is_none() {
+                device.credential_ref = Some(reference);
+                save_registry(&devices)?;
+            }
+            Ok(())
+        });
+    audited("set_device_comm_key", &device_id, stored)?;
+    append_app_log(&format!(
+        "COMM key {} for device {}",
+        if comm_key.is_some() {
+            "stored"
+        } else {
+            "cleared"
+        },
+        device_id
+    ));
+    Ok(())
+}
+
+// Ids of devices with a COMM key set on this PC; the keys themselves never leave the shell
+#[tauri::command]
+pub fn get_devices_with_comm_key(registry: State<DeviceRegistry>) -> Result<Vec<String>, String> {
+    let devices = registry
+        .lock()
+        .map(|devices| devices.clone())
+        .map_err(|e| format!("Failed to read device registry: {}", e))?;
+    let mut ids: Vec<String> = devices
+        .into_iter()
+        .filter(|device| {
+            device
+                .credential_ref
+                .as_deref()
+                .and_then(|reference| read_credential(reference).ok().flatten())
+                .is_some_and(|credential| credential.override_key.is_some())
+        })
+        .map(|device| device.id)
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
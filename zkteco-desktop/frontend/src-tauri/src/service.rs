@@ -0,0 +1,1138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit::audited;
+use crate::backup::{start_backend_after_maintenance, stop_backend_for_maintenance};
+use crate::relocate::digest;
+use crate::settings::{current_settings, SharedSettings};
+use crate::{
+    append_app_log, backend_auth, backend_env, check_backend_health, current_backend_port,
+    health_endpoint, lock, resolve_app_data_dir, secrets, set_backend_port, sidecar, tls,
+    BackendPort,
+};
+
+// The backend as a Windows service, a macOS LaunchAgent or a systemd user unit, running whether
+// or not anyone opens the app. install_windows_service copies the shell and the verified sidecar
+// into an administrators-only folder under ProgramData and registers `<shell> --service
+// <config>` to start automatically; that supervisor runs the backend with the environment
+// captured at install time and restarts it when it exits. The app and the service share a token
+// that outlives a launch (keychain "service-token:<profile>"), and backend_service.json in the
+// profile folder makes the app attach to the service instead of spawning its own sidecar;
+// stopping or restarting the backend from the app then controls the service. Settings the
+// backend reads at startup (TLS, localhost binding, database key) and backend updates reach the
+// service by reinstalling it.
+// On macOS install_launch_agent writes ~/Library/LaunchAgents/com.zkteco.backend.plist, which
+// launchd keeps alive from login on; unload_launch_agent disables it and the app goes back to
+// its own sidecar until load_launch_agent enables it again. On Linux install_systemd_unit copies
+// the sidecar to service/ in the profile folder (an AppImage's own copy vanishes when it exits)
+// and enables ~/.config/systemd/user/zkteco-backend.service; kiosks need lingering enabled for
+// it to start at boot rather than at login, which the install asks logind for.
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+const SERVICE_NAME: &str = "ZKTecoBackend";
+#[cfg(target_os = "macos")]
+const SERVICE_NAME: &str = "com.zkteco.backend";
+#[cfg(target_os = "linux")]
+const SERVICE_NAME: &str = "zkteco-backend";
+const SERVICE_FLAG: &str = "--service";
+const RECORD_FILE: &str = "backend_service.json";
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+const HEALTH_POLL: Duration = Duration::from_secs(1);
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+const AGENT_LOG_FILE: &str = "backend_service.log";
+// Seconds launchd or systemd waits before starting the backend again after it exited
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+const RESPAWN_DELAY_SECS: u32 = 5;
+#[cfg(target_os = "linux")]
+const UNIT_ENV_FILE: &str = "backend_service.env";
+#[cfg(target_os = "linux")]
+const UNIT_BINARY_DIR: &str = "service";
+#[cfg(windows)]
+const SERVICE_DISPLAY_NAME: &str = "ZKTeco backend";
+#[cfg(windows)]
+const SERVICE_DESCRIPTION: &str = "Runs the ZKTeco attendance backend for the desktop app";
+#[cfg(windows)]
+const SHELL_FILE: &str = "ztkapp-service.exe";
+#[cfg(windows)]
+const BACKEND_FILE: &str = "zkteco-backend.exe";
+#[cfg(windows)]
+const CONFIG_FILE: &str = "service.json";
+#[cfg(windows)]
+const LOG_FILE: &str = "backend.log";
+// Delay before the supervisor starts a backend that exited again
+#[cfg(windows)]
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+// The defaults, plus start and stop for the interactive user so the app needs no elevation
+#[cfg(windows)]
+const SERVICE_SDDL: &str = "D:(A;;CCLCSWRPWPDTLOCRRC;;;SY)(A;;CCDCLCSWRPWPDTLOCRSDRCWDWO;;;BA)(A;;CCLCSWRPWPDTLOCRRC;;;IU)(A;;CCLCSWLOCRRC;;;SU)";
+#[cfg(windows)]
+const ERROR_SERVICE_ALREADY_RUNNING: i32 = 1056;
+#[cfg(windows)]
+const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+#[cfg(windows)]
+const ERROR_SERVICE_NOT_ACTIVE: i32 = 1062;
+
+#[cfg(windows)]
+static SUPERVISOR_CONFIG: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+// What the app knows about the service it installed for this profile
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ServiceRecord {
+    pub name: String,
+    pub port: u16,
+    tls_cert: Option<String>,
+    sidecar_sha256: String,
+    installed_at: DateTime<Utc>,
+    // False while a LaunchAgent is unloaded and the app runs its own sidecar
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// How the supervisor runs the backend; readable by administrators and the service only
+#[cfg(windows)]
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SupervisorConfig {
+    backend: PathBuf,
+    sha256: String,
+    env: Vec<(String, String)>,
+    log: PathBuf,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BackendServiceStatus {
+    supported: bool,
+    installed: bool,
+    state: Option<String>, // running, starting, stopping, stopped or paused
+    healthy: bool,
+    record: Option<ServiceRecord>,
+}
+
+fn record_path() -> PathBuf {
+    resolve_app_data_dir().join(RECORD_FILE)
+}
+
+pub fn installed() -> Option<ServiceRecord> {
+    let content = fs::read_to_string(record_path()).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|e| eprintln!("Failed to parse {}: {}", RECORD_FILE, e))
+        .ok()
+}
+
+// The service the app uses as its backend, if any
+pub fn attached() -> Option<ServiceRecord> {
+    installed().filter(|record| record.enabled)
+}
+
+fn save_record(record: &ServiceRecord) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(record)
+        .map_err(|e| format!("Failed to serialize backend service record: {}", e))?;
+    fs::write(record_path(), content)
+        .map_err(|e| format!("Failed to save backend service record: {}", e))
+}
+
+// Token the installed service was given, so the backend accepts this launch's requests
+pub fn shared_token() -> Option<String> {
+    installed()?;
+    secrets::service_token()
+}
+
+fn ensure_windows() -> Result<(), String> {
+    if cfg!(windows) {
+        Ok(())
+    } else {
+        Err("Windows services are only available on Windows".to_string())
+    }
+}
+
+fn ensure_macos() -> Result<(), String> {
+    if cfg!(target_os = "macos") {
+        Ok(())
+    } else {
+        Err("LaunchAgents are only available on macOS".to_string())
+    }
+}
+
+fn ensure_linux() -> Result<(), String> {
+    if cfg!(target_os = "linux") {
+        Ok(())
+    } else {
+        Err("systemd units are only available on Linux".to_string())
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Backend service task failed: {}", e))?
+}
+
+async fn answers(app: &AppHandle, port: u16) -> bool {
+    check_backend_health(&health_endpoint(port, &app.state::<SharedSettings>())).await
+}
+
+async fn wait_until_healthy(app: &AppHandle, port: u16) -> bool {
+    let deadline = tokio::time::Instant::now() + START_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if answers(app, port).await {
+            return true;
+        }
+        tokio::time::sleep(HEALTH_POLL).await;
+    }
+    false
+}
+
+// Use the installed service as this app's backend, starting it when it isn't answering. None
+// when no service is installed and the app should spawn its own sidecar.
+pub async fn attach(app: &AppHandle, backend_port: &BackendPort) -> Option<Result<String, String>> {
+    let record = attached()?;
+    tls::trust_certificate(record.tls_cert.as_deref().map(Path::new));
+    set_backend_port(app, backend_port, record.port);
+    if answers(app, record.port).await {
+        return Some(Ok(format!(
+            "Attached to the {} service on port {}",
+            record.name, record.port
+        )));
+    }
+    if let Err(err) = blocking(start_service).await {
+        return Some(Err(err));
+    }
+    Some(if wait_until_healthy(app, record.port).await {
+        append_app_log(&format!("Started the {} service", record.name));
+        Ok(format!(
+            "Started the {} service on port {}",
+            record.name, record.port
+        ))
+    } else {
+        Err(format!(
+            "The {} service did not answer within {}s",
+            record.name,
+            START_TIMEOUT.as_secs()
+        ))
+    })
+}
+
+async fn status(app: &AppHandle) -> BackendServiceStatus {
+    let record = installed();
+    let state = query_state().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        None
+    });
+    let healthy = match &record {
+        Some(record) => answers(app, record.port).await,
+        None => false,
+    };
+    BackendServiceStatus {
+        supported: cfg!(any(windows, target_os = "macos", target_os = "linux")),
+        installed: record.is_some() || state.is_some(),
+        state: state.map(str::to_string),
+        healthy,
+        record,
+    }
+}
+
+async fn install(
+    app: &AppHandle,
+    settings: &SharedSettings,
+) -> Result<BackendServiceStatus, String> {
+    if installed().is_some() || query_state()?.is_some() {
+        return Err(format!(
+            "The {} service is already installed; remove it first to reinstall",
+            SERVICE_NAME
+        ));
+    }
+    let binary = sidecar::verified_sidecar_path()?;
+    let sha256 = {
+        let binary = binary.clone();
+        blocking(move || digest(&binary)).await?
+    };
+    let port = current_backend_port(&app.state::<BackendPort>());
+    let env: Vec<(String, String)> = backend_env(port, &current_settings(settings))?
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    let tls_cert = env
+        .iter()
+        .find(|(name, _)| name == tls::CERT_ENV)
+        .map(|(_, value)| value.clone());
+    // This launch's token becomes the one the service keeps
+    secrets::set_service_token(backend_auth::session_token())?;
+
+    stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, service installation aborted", e))?;
+    let registered = {
+        let sha256 = sha256.clone();
+        blocking(move || register_service(&binary, sha256, env)).await
+    };
+    if let Err(err) = registered {
+        let _ = secrets::set_service_token("");
+        let _ = start_backend_after_maintenance(app).await;
+        return Err(err);
+    }
+
+    save_record(&ServiceRecord {
+        name: SERVICE_NAME.to_string(),
+        port,
+        tls_cert,
+        sidecar_sha256: sha256,
+        installed_at: Utc::now(),
+        enabled: true,
+    })?;
+    append_app_log(&format!(
+        "Installed the {} service on port {}",
+        SERVICE_NAME, port
+    ));
+    start_backend_after_maintenance(app).await?;
+    Ok(status(app).await)
+}
+
+async fn remove(app: &AppHandle) -> Result<BackendServiceStatus, String> {
+    if installed().is_none() && query_state()?.is_none() {
+        return Err(format!("The {} service is not installed", SERVICE_NAME));
+    }
+    blocking(unregister_service).await?;
+    if let Err(err) = fs::remove_file(record_path()) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            return Err(format!("Failed to remove backend service record: {}", err));
+        }
+    }
+    // The token stays in use until the app quits, but the next launch makes a fresh one
+    secrets::set_service_token("")?;
+    append_app_log(&format!("Removed the {} service", SERVICE_NAME));
+    // The app runs its own sidecar again
+    start_backend_after_maintenance(app).await?;
+    Ok(status(app).await)
+}
+
+// Switch between the LaunchAgent and the app's own sidecar without uninstalling the agent
+async fn set_enabled(app: &AppHandle, enabled: bool) -> Result<BackendServiceStatus, String> {
+    let mut record =
+        installed().ok_or_else(|| format!("The {} service is not installed", SERVICE_NAME))?;
+    if record.enabled != enabled {
+        if enabled {
+            // The app's own sidecar makes way for the agent
+            stop_backend_for_maintenance(app).await?;
+        }
+        record.enabled = enabled;
+        save_record(&record)?;
+        if !enabled {
+            blocking(disable_agent).await?;
+            stop_backend_for_maintenance(app).await?;
+        }
+        append_app_log(&format!(
+            "{} the {} service",
+            if enabled { "Loaded" } else { "Unloaded" },
+            SERVICE_NAME
+        ));
+    }
+    // Loads the agent again when enabled, otherwise starts the app's own sidecar
+    start_backend_after_maintenance(app).await?;
+    Ok(status(app).await)
+}
+
+#[tauri::command]
+pub async fn install_windows_service(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_windows()?;
+    let result = install(&app, &settings).await;
+    audited("install_windows_service", SERVICE_NAME, result)
+}
+
+#[tauri::command]
+pub async fn remove_windows_service(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_windows()?;
+    let result = remove(&app).await;
+    audited("remove_windows_service", SERVICE_NAME, result)
+}
+
+// Write the LaunchAgent plist and load it
+#[tauri::command]
+pub async fn install_launch_agent(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_macos()?;
+    let result = install(&app, &settings).await;
+    audited("install_launch_agent", SERVICE_NAME, result)
+}
+
+#[tauri::command]
+pub async fn load_launch_agent(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_macos()?;
+    let result = set_enabled(&app, true).await;
+    audited("load_launch_agent", SERVICE_NAME, result)
+}
+
+#[tauri::command]
+pub async fn unload_launch_agent(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_macos()?;
+    let result = set_enabled(&app, false).await;
+    audited("unload_launch_agent", SERVICE_NAME, result)
+}
+
+// Unload the agent and delete its plist
+#[tauri::command]
+pub async fn remove_launch_agent(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_macos()?;
+    let result = remove(&app).await;
+    audited("remove_launch_agent", SERVICE_NAME, result)
+}
+
+// Write and enable the systemd user unit
+#[tauri::command]
+pub async fn install_systemd_unit(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_linux()?;
+    let result = install(&app, &settings).await;
+    audited("install_systemd_unit", SERVICE_NAME, result)
+}
+
+#[tauri::command]
+pub async fn uninstall_systemd_unit(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    lock::ensure_unlocked()?;
+    ensure_linux()?;
+    let result = remove(&app).await;
+    audited("uninstall_systemd_unit", SERVICE_NAME, result)
+}
+
+#[tauri::command]
+pub async fn get_backend_service_status(app: AppHandle) -> Result<BackendServiceStatus, String> {
+    Ok(status(&app).await)
+}
+
+// Config path when the process was started as the service: `<shell> --service <config>`
+pub fn service_args() -> Option<PathBuf> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let index = args.iter().position(|arg| arg == SERVICE_FLAG)?;
+    args.get(index + 1).map(PathBuf::from)
+}
+
+#[cfg(windows)]
+fn service_dir() -> PathBuf {
+    let program_data = std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+    PathBuf::from(program_data).join("ZKTeco").join("service")
+}
+
+// Backend binary the service runs, once installed
+#[cfg(windows)]
+pub fn service_backend() -> PathBuf {
+    service_dir().join(BACKEND_FILE)
+}
+
+// PowerShell single-quoted literal
+#[cfg(windows)]
+pub fn ps_quote(value: impl AsRef<std::ffi::OsStr>) -> String {
+    format!("'{}'", value.as_ref().to_string_lossy().replace('\'', "''"))
+}
+
+// Run a PowerShell script as administrator; Windows asks the user to approve it. `what` names
+// the task in errors, e.g. "Service setup".
+#[cfg(windows)]
+pub fn run_elevated(script: &str, what: &str) -> Result<(), String> {
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let error_file =
+        std::env::temp_dir().join(format!("ztkapp-elevated-{}.err", std::process::id()));
+    let _ = fs::remove_file(&error_file);
+    let script = format!(
+        "$ErrorActionPreference = 'Stop'\n\
+         trap {{ Set-Content -LiteralPath {} -Value $_; exit 1 }}\n\
+         function Invoke-Sc {{ & sc.exe @args | Out-Null; if ($LASTEXITCODE -ne 0) {{ throw \"sc.exe $($args[0]) failed with $LASTEXITCODE\" }} }}\n\
+         {}",
+        ps_quote(&error_file),
+        script
+    );
+    let utf16: Vec<u8> = script.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let launcher = format!(
+        "$p = Start-Process -FilePath powershell.exe -Verb RunAs -Wait -PassThru -WindowStyle Hidden -ArgumentList '-NoProfile','-NonInteractive','-EncodedCommand','{}'; exit $p.ExitCode",
+        BASE64.encode(utf16)
+    );
+    let status = std::process::Command::new("powershell.exe")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &launcher])
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    let detail = fs::read_to_string(&error_file).ok();
+    let _ = fs::remove_file(&error_file);
+    if status.success() {
+        return Ok(());
+    }
+    Err(match detail {
+        Some(detail) => format!("{} failed: {}", what, detail.trim()),
+        None => format!(
+            "{} was cancelled or failed; it needs administrator approval",
+            what
+        ),
+    })
+}
+
+#[cfg(windows)]
+fn register_service(
+    binary: &Path,
+    sha256: String,
+    env: Vec<(String, String)>,
+) -> Result<(), String> {
+    let dir = service_dir();
+    let shell = std::env::current_exe()
+        .map_err(|e| format!("Failed to locate the app executable: {}", e))?;
+    let config = SupervisorConfig {
+        backend: dir.join(BACKEND_FILE),
+        sha256,
+        env,
+        log: dir.join(LOG_FILE),
+    };
+    // Written where only this user can read it, then copied into the protected folder
+    let staged = std::env::temp_dir().join(format!("ztkapp-service-{}.json", std::process::id()));
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize service configuration: {}", e))?;
+    fs::write(&staged, content)
+        .map_err(|e| format!("Failed to write service configuration: {}", e))?;
+
+    let bin_path = format!(
+        "\"{}\" {} \"{}\"",
+        dir.join(SHELL_FILE).display(),
+        SERVICE_FLAG,
+        dir.join(CONFIG_FILE).display()
+    );
+    let script = format!(
+        "New-Item -ItemType Directory -Force -Path {dir} | Out-Null\n\
+         icacls {dir} /inheritance:r /grant:r '*S-1-5-18:(OI)(CI)F' '*S-1-5-32-544:(OI)(CI)F' | Out-Null\n\
+         Copy-Item -Force -LiteralPath {shell} -Destination {shell_copy}\n\
+         Copy-Item -Force -LiteralPath {binary} -Destination {binary_copy}\n\
+         Copy-Item -Force -LiteralPath {staged} -Destination {config}\n\
+         New-Service -Name '{name}' -BinaryPathName {bin_path} -DisplayName '{display}' -Description '{description}' -StartupType Automatic | Out-Null\n\
+         Invoke-Sc failure '{name}' reset= 86400 actions= restart/5000/restart/5000/restart/60000\n\
+         Invoke-Sc sdset '{name}' '{sddl}'\n\
+         Start-Service -Name '{name}'",
+        dir = ps_quote(&dir),
+        shell = ps_quote(&shell),
+        shell_copy = ps_quote(&dir.join(SHELL_FILE)),
+        binary = ps_quote(binary),
+        binary_copy = ps_quote(&config.backend),
+        staged = ps_quote(&staged),
+        config = ps_quote(&dir.join(CONFIG_FILE)),
+        name = SERVICE_NAME,
+        bin_path = ps_quote(&bin_path),
+        display = SERVICE_DISPLAY_NAME,
+        description = SERVICE_DESCRIPTION,
+        sddl = SERVICE_SDDL,
+    );
+    let result = run_elevated(&script, "Service setup");
+    let _ = fs::remove_file(&staged);
+    result
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn register_service(
+    _binary: &Path,
+    _sha256: String,
+    _env: Vec<(String, String)>,
+) -> Result<(), String> {
+    ensure_windows()
+}
+
+#[cfg(windows)]
+fn unregister_service() -> Result<(), String> {
+    let script = format!(
+        "$service = Get-Service -Name '{name}' -ErrorAction SilentlyContinue\n\
+         if ($service) {{\n\
+           if ($service.Status -ne 'Stopped') {{ Stop-Service -Name '{name}' -Force }}\n\
+           Invoke-Sc delete '{name}'\n\
+         }}\n\
+         if (Test-Path -LiteralPath {dir}) {{ Remove-Item -LiteralPath {dir} -Recurse -Force }}",
+        name = SERVICE_NAME,
+        dir = ps_quote(&service_dir()),
+    );
+    run_elevated(&script, "Service removal")
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn unregister_service() -> Result<(), String> {
+    ensure_windows()
+}
+
+#[cfg(windows)]
+fn open_service(
+    access: windows_service::service::ServiceAccess,
+) -> Result<Option<windows_service::service::Service>, String> {
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|e| format!("Failed to open the service manager: {}", e))?;
+    match manager.open_service(SERVICE_NAME, access) {
+        Ok(service) => Ok(Some(service)),
+        Err(windows_service::Error::Winapi(err))
+            if err.raw_os_error() == Some(ERROR_SERVICE_DOES_NOT_EXIST) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(format!(
+            "Failed to open the {} service: {}",
+            SERVICE_NAME, err
+        )),
+    }
+}
+
+// Current state of the service, None when it isn't registered
+#[cfg(windows)]
+fn query_state() -> Result<Option<&'static str>, String> {
+    use windows_service::service::{ServiceAccess, ServiceState};
+
+    let Some(service) = open_service(ServiceAccess::QUERY_STATUS)? else {
+        return Ok(None);
+    };
+    let status = service
+        .query_status()
+        .map_err(|e| format!("Failed to query the {} service: {}", SERVICE_NAME, e))?;
+    Ok(Some(match status.current_state {
+        ServiceState::Running => "running",
+        ServiceState::StartPending => "starting",
+        ServiceState::StopPending => "stopping",
+        ServiceState::Stopped => "stopped",
+        ServiceState::ContinuePending | ServiceState::PausePending | ServiceState::Paused => {
+            "paused"
+        }
+    }))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn query_state() -> Result<Option<&'static str>, String> {
+    Ok(None)
+}
+
+#[cfg(windows)]
+fn start_service() -> Result<(), String> {
+    use windows_service::service::ServiceAccess;
+
+    let service = open_service(ServiceAccess::START)?
+        .ok_or_else(|| format!("The {} service is not installed", SERVICE_NAME))?;
+    match service.start::<&str>(&[]) {
+        Ok(()) => Ok(()),
+        Err(windows_service::Error::Winapi(err))
+            if err.raw_os_error() == Some(ERROR_SERVICE_ALREADY_RUNNING) =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(format!(
+            "Failed to start the {} service: {}",
+            SERVICE_NAME, err
+        )),
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn start_service() -> Result<(), String> {
+    ensure_windows()
+}
+
+// Stop the service the app is attached to; the backend goes down until it is started again
+#[cfg(windows)]
+pub fn stop_service() -> Result<String, String> {
+    use windows_service::service::ServiceAccess;
+
+    let service = open_service(ServiceAccess::STOP)?
+        .ok_or_else(|| format!("The {} service is not installed", SERVICE_NAME))?;
+    match service.stop() {
+        Ok(_) => {}
+        Err(windows_service::Error::Winapi(err))
+            if err.raw_os_error() == Some(ERROR_SERVICE_NOT_ACTIVE) => {}
+        Err(err) => {
+            return Err(format!(
+                "Failed to stop the {} service: {}",
+                SERVICE_NAME, err
+            ))
+        }
+    }
+    append_app_log(&format!("Stopped the {} service", SERVICE_NAME));
+    Ok(format!("Stop requested for the {} service", SERVICE_NAME))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+pub fn stop_service() -> Result<String, String> {
+    ensure_windows().map(|_| String::new())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn disable_agent() -> Result<(), String> {
+    ensure_macos()
+}
+
+#[cfg(target_os = "macos")]
+fn plist_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Failed to get home directory")?;
+    Ok(home
+        .join("Library")
+        .join("LaunchAgents")
+        .join(format!("{}.plist", SERVICE_NAME)))
+}
+
+// stdout of a service manager tool, or its stderr as the error
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn run_tool(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn launchctl(args: &[&str]) -> Result<String, String> {
+    run_tool("launchctl", args)
+}
+
+#[cfg(target_os = "macos")]
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(target_os = "macos")]
+fn agent_plist(binary: &Path, env: &[(String, String)], log: &Path) -> String {
+    let variables: String = env
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "        <key>{}</key>\n        <string>{}</string>\n",
+                xml_escape(name),
+                xml_escape(value)
+            )
+        })
+        .collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{binary}</string>
+    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+{variables}    </dict>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>ThrottleInterval</key>
+    <integer>{throttle}</integer>
+    <key>ProcessType</key>
+    <string>Background</string>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = SERVICE_NAME,
+        binary = xml_escape(&binary.to_string_lossy()),
+        variables = variables,
+        throttle = RESPAWN_DELAY_SECS,
+        log = xml_escape(&log.to_string_lossy()),
+    )
+}
+
+// The plist carries the shared token and database key, so only this user may read it
+#[cfg(target_os = "macos")]
+fn register_service(
+    binary: &Path,
+    _sha256: String,
+    env: Vec<(String, String)>,
+) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = plist_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let log = resolve_app_data_dir().join(AGENT_LOG_FILE);
+    fs::write(&path, agent_plist(binary, &env, &log))
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict permissions of {:?}: {}", path, e))?;
+    launchctl(&["load", "-w", &path.to_string_lossy()]).map(|_| ())
+}
+
+#[cfg(target_os = "macos")]
+fn unregister_service() -> Result<(), String> {
+    let path = plist_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+    // Fails when the agent is already unloaded, which is fine here
+    let _ = launchctl(&["unload", "-w", &path.to_string_lossy()]);
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))
+}
+
+// "running" with a process, "stopped" when loaded without one or not loaded at all
+#[cfg(target_os = "macos")]
+fn query_state() -> Result<Option<&'static str>, String> {
+    match launchctl(&["list", SERVICE_NAME]) {
+        Ok(listing) if listing.contains("\"PID\" =") => Ok(Some("running")),
+        Ok(_) => Ok(Some("stopped")),
+        Err(_) => Ok(plist_path()?.exists().then_some("stopped")),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn start_service() -> Result<(), String> {
+    let path = plist_path()?;
+    if !path.exists() {
+        return Err(format!("The {} service is not installed", SERVICE_NAME));
+    }
+    if query_state()? == Some("running") {
+        return Ok(());
+    }
+    launchctl(&["load", "-w", &path.to_string_lossy()]).map(|_| ())
+}
+
+// Unloaded until the next start or login; disable_agent keeps it off across logins
+#[cfg(target_os = "macos")]
+pub fn stop_service() -> Result<String, String> {
+    launchctl(&["unload", &plist_path()?.to_string_lossy()])?;
+    append_app_log(&format!("Stopped the {} service", SERVICE_NAME));
+    Ok(format!("Unloaded the {} service", SERVICE_NAME))
+}
+
+#[cfg(target_os = "macos")]
+fn disable_agent() -> Result<(), String> {
+    // launchctl complains when the agent isn't loaded, but -w still keeps it off at login
+    let _ = launchctl(&["unload", "-w", &plist_path()?.to_string_lossy()]);
+    if query_state()? == Some("running") {
+        return Err(format!("The {} service is still running", SERVICE_NAME));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn unit_name() -> String {
+    format!("{}.service", SERVICE_NAME)
+}
+
+#[cfg(target_os = "linux")]
+fn unit_path() -> Result<PathBuf, String> {
+    let config = dirs::config_dir().ok_or("Failed to get config directory")?;
+    Ok(config.join("systemd").join("user").join(unit_name()))
+}
+
+#[cfg(target_os = "linux")]
+fn systemctl(args: &[&str]) -> Result<String, String> {
+    let mut user_args = vec!["--user"];
+    user_args.extend_from_slice(args);
+    run_tool("systemctl", &user_args)
+}
+
+// Unit files expand %-specifiers in every value
+#[cfg(target_os = "linux")]
+fn unit_escape(path: &Path) -> String {
+    path.to_string_lossy().replace('%', "%%")
+}
+
+#[cfg(target_os = "linux")]
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "linux")]
+fn unit_file(binary: &Path, env_file: &Path, log: &Path) -> String {
+    format!(
+        "[Unit]
+Description=ZKTeco attendance backend
+After=network-online.target
+
+[Service]
+ExecStart={binary}
+EnvironmentFile={env_file}
+Restart=always
+RestartSec={restart}
+StandardOutput=append:{log}
+StandardError=append:{log}
+
+[Install]
+WantedBy=default.target
+",
+        binary = quoted(&unit_escape(binary)),
+        env_file = unit_escape(env_file),
+        restart = RESPAWN_DELAY_SECS,
+        log = unit_escape(log),
+    )
+}
+
+// The environment file carries the shared token and database key, so only this user may read it
+#[cfg(target_os = "linux")]
+fn register_service(
+    binary: &Path,
+    _sha256: String,
+    env: Vec<(String, String)>,
+) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let data_dir = resolve_app_data_dir();
+    let binary_dir = data_dir.join(UNIT_BINARY_DIR);
+    fs::create_dir_all(&binary_dir)
+        .map_err(|e| format!("Failed to create {:?}: {}", binary_dir, e))?;
+    let binary_copy = binary_dir.join(SERVICE_NAME);
+    fs::copy(binary, &binary_copy)
+        .map_err(|e| format!("Failed to copy the backend to {:?}: {}", binary_copy, e))?;
+    fs::set_permissions(&binary_copy, fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Failed to make {:?} executable: {}", binary_copy, e))?;
+
+    let env_file = data_dir.join(UNIT_ENV_FILE);
+    let variables: String = env
+        .iter()
+        .map(|(name, value)| format!("{}={}\n", name, quoted(value)))
+        .collect();
+    fs::write(&env_file, variables)
+        .map_err(|e| format!("Failed to write {:?}: {}", env_file, e))?;
+    fs::set_permissions(&env_file, fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict permissions of {:?}: {}", env_file, e))?;
+
+    let unit = unit_path()?;
+    if let Some(parent) = unit.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+    }
+    let log = data_dir.join(AGENT_LOG_FILE);
+    fs::write(&unit, unit_file(&binary_copy, &env_file, &log))
+        .map_err(|e| format!("Failed to write {:?}: {}", unit, e))?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", &unit_name()])?;
+    // Without lingering the unit only runs while the user is logged in
+    if let Err(err) = run_tool("loginctl", &["enable-linger"]) {
+        append_app_log(&format!(
+            "Could not enable lingering for the {} unit: {}",
+            SERVICE_NAME, err
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn unregister_service() -> Result<(), String> {
+    let unit = unit_path()?;
+    if unit.exists() {
+        // Fails when the unit is already stopped and disabled, which is fine here
+        let _ = systemctl(&["disable", "--now", &unit_name()]);
+        fs::remove_file(&unit).map_err(|e| format!("Failed to remove {:?}: {}", unit, e))?;
+        systemctl(&["daemon-reload"])?;
+    }
+    let data_dir = resolve_app_data_dir();
+    let _ = fs::remove_file(data_dir.join(UNIT_ENV_FILE));
+    let _ = fs::remove_dir_all(data_dir.join(UNIT_BINARY_DIR));
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn query_state() -> Result<Option<&'static str>, String> {
+    if !unit_path()?.exists() {
+        return Ok(None);
+    }
+    // is-active exits non-zero for anything but active, still printing the state
+    let output = std::process::Command::new("systemctl")
+        .args(["--user", "is-active", &unit_name()])
+        .output()
+        .map_err(|e| format!("Failed to run systemctl: {}", e))?;
+    Ok(Some(match String::from_utf8_lossy(&output.stdout).trim() {
+        "active" | "reloading" => "running",
+        "activating" => "starting",
+        "deactivating" => "stopping",
+        _ => "stopped",
+    }))
+}
+
+#[cfg(target_os = "linux")]
+fn start_service() -> Result<(), String> {
+    systemctl(&["start", &unit_name()]).map(|_| ())
+}
+
+#[cfg(target_os = "linux")]
+pub fn stop_service() -> Result<String, String> {
+    systemctl(&["stop", &unit_name()])?;
+    append_app_log(&format!("Stopped the {} service", SERVICE_NAME));
+    Ok(format!("Stopped the {} service", SERVICE_NAME))
+}
+
+#[cfg(windows)]
+windows_service::define_windows_service!(ffi_service_main, service_main);
+
+// Entry point of the service process; blocks until the service is stopped
+#[cfg(windows)]
+pub fn run_service(config: PathBuf) -> i32 {
+    let _ = SUPERVISOR_CONFIG.set(config);
+    match windows_service::service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Failed to start the service dispatcher: {}", err);
+            1
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn run_service(_config: PathBuf) -> i32 {
+    eprintln!("{} is only supported on Windows", SERVICE_FLAG);
+    1
+}
+
+#[cfg(windows)]
+fn service_main(_arguments: Vec<std::ffi::OsString>) {
+    use std::sync::mpsc;
+    use windows_service::service::{
+        ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+        ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let handler = move |control: ServiceControl| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop_tx.send(());
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    };
+    let Ok(status_handle) = service_control_handler::register(SERVICE_NAME, handler) else {
+        return;
+    };
+    let report = |state: ServiceState, controls: ServiceControlAccept, exit_code: u32| {
+        let _ = status_handle.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: controls,
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::from_secs(10),
+            process_id: None,
+        });
+    };
+
+    report(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        0,
+    );
+    let result = SUPERVISOR_CONFIG
+        .get()
+        .ok_or_else(|| "Service started without a configuration".to_string())
+        .and_then(|path| supervise(path, &stop_rx));
+    // A failure exit code lets the recovery actions start the service again
+    let exit_code = match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    };
+    report(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    );
+}
+
+// Keep the backend running until the service is asked to stop
+#[cfg(windows)]
+fn supervise(path: &Path, stop: &std::sync::mpsc::Receiver<()>) -> Result<(), String> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use std::sync::mpsc::RecvTimeoutError;
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read service configuration {:?}: {}", path, e))?;
+    let config: SupervisorConfig = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse service configuration: {}", e))?;
+    let open_log = || {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.log)
+            .map_err(|e| format!("Failed to open {:?}: {}", config.log, e))
+    };
+
+    loop {
+        // Only ever run the binary that was verified at install time
+        let actual = digest(&config.backend)?;
+        if !actual.eq_ignore_ascii_case(&config.sha256) {
+            return Err(format!(
+                "{:?} does not match the backend installed with the service",
+                config.backend
+            ));
+        }
+        let log = open_log()?;
+        let mut child = Command::new(&config.backend)
+            .envs(config.env.iter().map(|(name, value)| (name, value)))
+            .stdout(Stdio::from(
+                log.try_clone()
+                    .map_err(|e| format!("Failed to open backend log: {}", e))?,
+            ))
+            .stderr(Stdio::from(log))
+            .spawn()
+            .map_err(|e| format!("Failed to start {:?}: {}", config.backend, e))?;
+
+        let exit = loop {
+            match stop.recv_timeout(Duration::from_secs(1)) {
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Ok(());
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                break status;
+            }
+        };
+        if let Ok(mut log) = open_log() {
+            let _ = writeln!(
+                log,
+                "{} supervisor: backend exited with {}, restarting in {}s",
+                Utc::now().to_rfc3339(),
+                exit,
+                RESTART_DELAY.as_secs()
+            );
+        }
+        if stop.recv_timeout(RESTART_DELAY).is_ok() {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tauri::State;
+
+use crate::audit::record_audit;
+use crate::lock;
+use crate::secrets::{
+    backend_health_token, set_backend_health_token, set_ldap_password, set_mqtt_password,
+    set_remote_credential, set_s3_secret_key, set_smtp_password, set_upstream_token,
+};
+use crate::{append_app_log, resolve_app_data_dir};
+
+pub const DEFAULT_HEALTH_PATH: &str = "/service/status";
+
+// Settings owned by the desktop shell (the backend keeps its own in app_settings)
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ShellSettings {
+    pub sync_alert_enabled: bool,
+    pub sync_alert_threshold_hours: u64,
+    // Route polled by the backend health checker, for deployments behind a proxy
+    pub backend_health_path: String,
+    // Optional bearer token sent with health checks. Write-only like upstream_token: it goes
+    // to the OS keychain and get_shell_settings only reports has_backend_health_token
+    #[serde(skip_serializing)]
+    pub backend_health_token: Option<String>,
+    #[serde(skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub has_backend_health_token: bool,
+    // Hours between automatic device clock syncs, 0 disables the job
+    pub device_time_sync_interval_hours: u64,
+    // Embedded PUSH (ADMS) listener for devices that phone home; applied on next start
+    pub adms_server_enabled: bool,
+    pub adms_server_port: u16,
+    // When set, unlock_door requires this PIN. Write-only: update_shell_settings keeps just a
+    // scrypt hash in the OS keychain (lock::set_door_pin), an empty string removes it
+    #[serde(skip_serializing)]
+    pub door_unlock_pin: Option<String>,
+    // Record every native protocol packet to protocol_traces/ (debugging odd firmware)
+    pub protocol_trace_enabled: bool,
+    // Minutes between scheduled native attendance polls, 0 disables the poller;
+    // per-device overrides (device id -> minutes, 0 skips the device) take precedence
+    pub device_poll_interval_minutes: u64,
+    pub device_poll_overrides: HashMap<String, u64>,
+    // Device clock drift (seconds) that raises device-drift-alert during syncs; with
+    // drift_auto_correct the clock is reset to host time as well
+    pub drift_alert_threshold_seconds: u64,
+    pub drift_auto_correct: bool,
+    // Automatic database backups: "off", "daily" or "weekly" at backup_time (local HH:MM);
+    // weekly backups run on backup_weekday (1 = Monday ... 7 = Sunday). Only the newest
+    // backup_keep_last snapshots in the backups folder are kept.
+    pub backup_schedule: String,
+    pub backup_time: String,
+    pub backup_weekday: u32,
+    pub backup_keep_last: usize,
+    // Attendance older than this many days is moved to zkteco_archive.db, 0 disables archiving
+    pub archive_after_days: u64,
+    // Push new attendance to upstream_endpoint (POST {"records": [...]}, optional bearer
+    // token) in batches of upstream_batch_size, checking every upstream_interval_seconds
+    pub upstream_sync_enabled: bool,
+    pub upstream_endpoint: Option<String>,
+    // Only carries a new token in update_shell_settings (or one left in the file by older
+    // versions) on its way to the OS keychain; never saved
+    #[serde(skip_serializing)]
+    pub upstream_token: Option<String>,
+    pub upstream_batch_size: usize,
+    pub upstream_interval_seconds: u64,
+    // Verification photos pushed by ADMS devices are kept up to this size, 0 disables storing
+    pub photo_store_max_mb: u64,
+    // Folder holding zkteco_app.db and the archive when not the app data folder; only
+    // move_database changes it
+    pub database_dir: Option<String>,
+    // Pass HOST=127.0.0.1 so the backend API is not reachable from the network; applied on
+    // next start
+    pub backend_localhost_only: bool,
+    // Serve the backend API over HTTPS with a self-signed certificate from the shell; the UI
+    // then reaches the API through the shell, and its live event streams are unavailable.
+    // Applied on next start
+    pub backend_tls_enabled: bool,
+    // Minutes without user activity before the app locks itself (with a lock PIN) or hides to
+    // the tray (with idle_hide_to_tray), 0 disables
+    #[serde(alias = "app_lock_idle_minutes")]
+    pub idle_timeout_minutes: u64,
+    pub idle_hide_to_tray: bool,
+    // Folders (network shares included) that exports, backups and imports may use besides the
+    // user's own folders and locations picked in a dialog
+    pub export_allowed_dirs: Vec<String>,
+    // Publish punches, device status changes and backend health transitions to an MQTT broker
+    // under mqtt_topic_prefix (mqtt.rs). Like upstream_token, a patched mqtt_password only
+    // passes through on its way to the OS keychain.
+    pub mqtt_enabled: bool,
+    pub mqtt_broker_host: Option<String>,
+    pub mqtt_broker_port: u16,
+    pub mqtt_tls: bool,
+    // PEM file with the CA of a broker certificate that isn't publicly trusted
+    pub mqtt_ca_file: Option<String>,
+    pub mqtt_username: Option<String>,
+    #[serde(skip_serializing)]
+    pub mqtt_password: Option<String>,
+    pub mqtt_topic_prefix: String,
+    // Random per start when unset
+    pub mqtt_client_id: Option<String>,
+    // 0 (fire and forget) or 1 (acknowledged by the broker)
+    pub mqtt_qos: u8,
+    // Read-only HTTP API on 127.0.0.1 for local tools (local_api.rs); applied on next start
+    pub local_api_enabled: bool,
+    pub local_api_port: u16,
+    // The same calls over a Unix socket / named pipe (ipc.rs); applied on next start
+    pub ipc_enabled: bool,
+    // Folder scanned for CSVs from other attendance systems (ingest.rs)
+    pub ingest_dir: Option<String>,
+    pub ingest_interval_seconds: u64,
+    // Directory the employee list is imported from (ldap.rs): ldap:// or ldaps:// server,
+    // accounts under ldap_base_dn matching ldap_user_filter, narrowed to members of
+    // ldap_group_dn when set. ldap_bind_password goes to the OS keychain like mqtt_password.
+    pub ldap_url: Option<String>,
+    pub ldap_starttls: bool,
+    pub ldap_bind_dn: Option<String>,
+    #[serde(skip_serializing)]
+    pub ldap_bind_password: Option<String>,
+    pub ldap_base_dn: Option<String>,
+    pub ldap_user_filter: String,
+    pub ldap_group_dn: Option<String>,
+    // Directory attributes holding the device user id, name and (optionally) card number
+    pub ldap_user_id_attribute: String,
+    pub ldap_name_attribute: String,
+    pub ldap_card_attribute: Option<String>,
+    // Spreadsheet (id or link) that attendance summaries are pushed to (sheets.rs), on demand
+    // or on a backup-style schedule ("off", "daily", "weekly" on sheets_weekday, 1 = Monday)
+    pub sheets_spreadsheet_id: Option<String>,
+    pub sheets_tab_name: String,
+    pub sheets_schedule: String,
+    pub sheets_time: String,
+    pub sheets_weekday: u32,
+    // Slack/Teams device_offline alerts (alerts.rs) fire once a device stays offline this long
+    pub alert_device_offline_minutes: u64,
+    // Outgoing mail (email.rs). smtp_security is "starttls", "tls" (implicit, usually port 465)
+    // or "none"; smtp_password goes to the OS keychain like mqtt_password.
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_security: String,
+    pub smtp_username: Option<String>,
+    #[serde(skip_serializing)]
+    pub smtp_password: Option<String>,
+    pub smtp_from: Option<String>,
+    // Attendance report PDF mailed "off", "daily" (yesterday) or "weekly" (the 7 days before
+    // email_report_weekday) at email_report_time
+    pub email_report_recipients: Vec<String>,
+    pub email_report_schedule: String,
+    pub email_report_time: String,
+    pub email_report_weekday: u32,
+    // Alert types from alerts.rs that are also mailed to email_alert_recipients
+    pub email_alert_recipients: Vec<String>,
+    pub email_alerts: Vec<String>,
+    // Printer used by printing.rs when a print command does not name one (None = OS default)
+    pub printer: Option<String>,
+    // Off-site copies over SFTP or FTPS (remote.rs): remote_protocol is "off", "sftp" or "ftps"
+    // (explicit TLS). remote_host_key pins the server - an OpenSSH public key line for SFTP, the
+    // certificate's SHA-256 fingerprint for FTPS. remote_credential (the FTPS password or the
+    // SFTP private key) goes to the OS keychain like smtp_password.
+    pub remote_protocol: String,
+    pub remote_host: Option<String>,
+    pub remote_port: Option<u16>,
+    pub remote_username: Option<String>,
+    #[serde(skip_serializing)]
+    pub remote_credential: Option<String>,
+    pub remote_host_key: Option<String>,
+    pub remote_directory: Option<String>,
+    // Upload every scheduled backup, and an attendance CSV "off", "daily" (yesterday) or
+    // "weekly" (the 7 days before remote_export_weekday) at remote_export_time
+    pub remote_upload_backups: bool,
+    pub remote_export_schedule: String,
+    pub remote_export_time: String,
+    pub remote_export_weekday: u32,
+    // S3-compatible bucket for off-machine copies (s3.rs). Without an endpoint AWS is used for
+    // s3_region; MinIO and most other servers need s3_path_style. s3_secret_access_key goes to
+    // the OS keychain.
+    pub s3_endpoint: Option<String>,
+    pub s3_region: String,
+    pub s3_bucket: Option<String>,
+    pub s3_prefix: String,
+    pub s3_path_style: bool,
+    pub s3_access_key_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub s3_secret_access_key: Option<String>,
+    // Upload scheduled backups and exported app bundles as they are written
+    pub s3_upload_backups: bool,
+    pub s3_upload_bundles: bool,
+    // In-app updates (updater.rs): update_channel is "stable" or "beta"; update_endpoint
+    // overrides the manifest URL built into the app and may use {{channel}}
+    pub update_channel: String,
+    pub update_endpoint: Option<String>,
+    pub update_auto_check: bool,
+    // Manifest of standalone backend builds (sidecar_update.rs); may use {{channel}} and
+    // {{target}}
+    pub sidecar_update_endpoint: Option<String>,
+    // Data left by older builds (legacy.rs): "migrated", "dismissed" or "none_found" once
+    // settled, and the folder it was migrated from
+    pub legacy_data_migration: Option<String>,
+    pub legacy_data_source: Option<String>,
+}
+
+impl Default for ShellSettings {
+    fn default() -> Self {
+        ShellSettings {
+            sync_alert_enabled: true,
+            sync_alert_threshold_hours: 24,
+            backend_health_path: DEFAULT_HEALTH_PATH.to_string(),
+            backend_health_token: None,
+            has_backend_health_token: false,
+            device_time_sync_interval_hours: 0,
+            adms_server_enabled: false,
+            adms_server_port: 8081,
+            door_unlock_pin: None,
+            protocol_trace_enabled: false,
+            device_poll_interval_minutes: 0,
+            device_poll_overrides: HashMap::new(),
+            drift_alert_threshold_seconds: 120,
+            drift_auto_correct: false,
+            backup_schedule: "off".to_string(),
+            backup_time: "02:00".to_string(),
+            backup_weekday: 7,
+            backup_keep_last: 7,
+            archive_after_days: 0,
+            upstream_sync_enabled: false,
+            upstream_endpoint: None,
+            upstream_token: None,
+            upstream_batch_size: 100,
+            upstream_interval_seconds: 30,
+            photo_store_max_mb: 1024,
+            database_dir: None,
+            backend_localhost_only: false,
+            backend_tls_enabled: false,
+            idle_timeout_minutes: 5,
+            idle_hide_to_tray: false,
+            export_allowed_dirs: Vec::new(),
+            mqtt_enabled: false,
+            mqtt_broker_host: None,
+            mqtt_broker_port: 1883,
+            mqtt_tls: false,
+            mqtt_ca_file: None,
+            mqtt_username: None,
+            mqtt_password: None,
+            mqtt_topic_prefix: "ztkapp".to_string(),
+            mqtt_client_id: None,
+            mqtt_qos: 0,
+            local_api_enabled: false,
+            local_api_port: 8765,
+            ipc_enabled: false,
+            ingest_dir: None,
+            ingest_interval_seconds: 30,
+            ldap_url: None,
+            ldap_starttls: false,
+            ldap_bind_dn: None,
+            ldap_bind_password: None,
+            ldap_base_dn: None,
+            ldap_user_filter: "(&(objectCategory=person)(objectClass=user))".to_string(),
+            ldap_group_dn: None,
+            ldap_user_id_attribute: "employeeID".to_string(),
+            ldap_name_attribute: "displayName".to_string(),
+            ldap_card_attribute: None,
+            sheets_spreadsheet_id: None,
+            sheets_tab_name: "Attendance".to_string(),
+            sheets_schedule: "off".to_string(),
+            sheets_time: "06:00".to_string(),
+            sheets_weekday: 1,
+            alert_device_offline_minutes: 10,
+            smtp_host: None,
+            smtp_port: 587,
+            smtp_security: "starttls".to_string(),
+            smtp_username: None,
+            smtp_password: None,
+            smtp_from: None,
+            email_report_recipients: Vec::new(),
+            email_report_schedule: "off".to_string(),
+            email_report_time: "07:00".to_string(),
+            email_report_weekday: 1,
+            email_alert_recipients: Vec::new(),
+            email_alerts: Vec::new(),
+            printer: None,
+            remote_protocol: "off".to_string(),
+            remote_host: None,
+            remote_port: None,
+            remote_username: None,
+            remote_credential: None,
+            remote_host_key: None,
+            remote_directory: None,
+            remote_upload_backups: false,
+            remote_export_schedule: "off".to_string(),
+            remote_export_time: "03:00".to_string(),
+            remote_export_weekday: 1,
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+            s3_bucket: None,
+            s3_prefix: "ztkapp".to_string(),
+            s3_path_style: false,
+            s3_access_key_id: None,
+            s3_secret_access_key: None,
+            s3_upload_backups: false,
+            s3_upload_bundles: false,
+            update_channel: "stable".to_string(),
+            update_endpoint: None,
+            update_auto_check: true,
+            sidecar_update_endpoint: None,
+            legacy_data_migration: None,
+            legacy_data_source: None,
+        }
+    }
+}
+
+pub type SharedSettings = Arc<Mutex<ShellSettings>>;
+
+fn settings_path() -> PathBuf {
+    resolve_app_data_dir().join("shell_settings.json")
+}
+
+pub fn load_settings() -> ShellSettings {
+    let path = settings_path();
+    if !path.exists() {
+        return ShellSettings::default();
+    }
+
+    match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("Failed to load shell settings from {:?}: {}", path, err);
+            append_app_log(&format!(
+                "Failed to load shell settings, using defaults: {}",
+                err
+            ));
+            ShellSettings::default()
+        }
+    }
+}
+
+pub fn save_settings(settings: &ShellSettings) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize shell settings: {}", e))?;
+    fs::write(settings_path(), content).map_err(|e| format!("Failed to save shell settings: {}", e))
+}
+
+// Push the settings that live in statics (read outside of managed state) into effect
+pub fn apply_runtime_settings(settings: &ShellSettings) {
+    crate::trace::set_trace_enabled(settings.protocol_trace_enabled);
+    crate::drift::set_drift_policy(
+        settings.drift_alert_threshold_seconds,
+        settings.drift_auto_correct,
+    );
+    crate::relocate::set_database_dir(settings.database_dir.as_ref().map(PathBuf::from));
+    crate::path_policy::set_allowed_dirs(&settings.export_allowed_dirs);
+}
+
+pub fn current_settings(settings: &SharedSettings) -> ShellSettings {
+    settings
+        .lock()
+        .map(|guard| guard.clone())
+        .unwrap_or_default()
+}
+
+#[tauri::command]
+pub fn get_shell_settings(settings: State<SharedSettings>) -> Result<ShellSettings, String> {
+    settings
+        .lock()
+        .map(|guard| guard.clone())
+        .map(with_secret_flags)
+        .map_err(|e| format!("Failed to read shell settings: {}", e))
+}
+
+// Settings as shown to the UI: whether write-only secrets are set, never their values
+fn with_secret_flags(mut settings: ShellSettings) -> ShellSettings {
+    settings.has_backend_health_token = backend_health_token().is_some();
+    settings
+}
+
+// Accepts a partial settings object; keys that are not present keep their current value
+#[tauri::command]
+pub fn update_shell_settings(
+    patch: serde_json::Value,
+    settings: State<SharedSettings>,
+) -> Result<ShellSettings, String> {
+    lock::ensure_unlocked()?;
+    let patch = patch
+        .as_object()
+        .ok_or("Settings update must be a JSON object")?;
+
+    let mut guard = settings
+        .lock()
+        .map_err(|e| format!("Failed to lock shell settings: {}", e))?;
+
+    let mut merged = serde_json::to_value(&*guard)
+        .map_err(|e| format!("Failed to serialize shell settings: {}", e))?;
+    if let Some(object) = merged.as_object_mut() {
+        for (key, value) in patch {
+            object.insert(key.clone(), value.clone());
+        }
+    }
+
+    let mut updated: ShellSettings =
+        serde_json::from_value(merged).map_err(|e| format!("Invalid settings value: {}", e))?;
+    // Pointing at another folder without moving the files would start an empty database
+    updated.database_dir = guard.database_dir.clone();
+    updated.legacy_data_migration = guard.legacy_data_migration.clone();
+    updated.legacy_data_source = guard.legacy_data_source.clone();
+    if let Some(token) = updated.upstream_token.take() {
+        set_upstream_token(&token)?;
+    }
+    if let Some(token) = updated.backend_health_token.take() {
+        set_backend_health_token(token.trim())?;
+    }
+    if let Some(password) = updated.mqtt_password.take() {
+        set_mqtt_password(&password)?;
+    }
+    if let Some(password) = updated.ldap_bind_password.take() {
+        set_ldap_password(&password)?;
+    }
+    if let Some(password) = updated.smtp_password.take() {
+        set_smtp_password(&password)?;
+    }
+    if let Some(credential) = updated.remote_credential.take() {
+        set_remote_credential(&credential)?;
+    }
+    if let Some(key) = updated.s3_secret_access_key.take() {
+        set_s3_secret_key(&key)?;
+    }
+    if let Some(pin) = updated.door_unlock_pin.take() {
+        lock::set_door_pin(&pin)?;
+    }
+    save_settings(&updated)?;
+    apply_runtime_settings(&updated);
+    *guard = updated.clone();
+
+    let keys = patch.keys().cloned().collect::<Vec<_>>().join(", ");
+    append_app_log(&format!("Shell settings updated: {}", keys));
+    record_audit("update_shell_settings", "settings", "success", Some(keys));
+    Ok(with_secret_flags(updated))
+}
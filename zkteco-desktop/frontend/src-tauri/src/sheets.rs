@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc};
+use reqwest::{Method, Url};
+use sha2::{Digest, Sha256};
+use tauri::State;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::audit::audited;
+use crate::database::run_db;
+use crate::export::{daily_rows, device_filter, parse_range, ExportRange};
+use crate::secrets::{google_sheets_grant, hex, set_google_sheets_grant};
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{append_app_log, lock};
+
+// Attendance summaries pushed to a Google Sheet for payroll. connect_google_sheets runs the
+// installed-app OAuth flow (browser sign-in, loopback redirect, PKCE) with the office's own
+// OAuth client; the client and the refresh token are kept in the OS keychain. A push replaces
+// the configured tab with one row per employee (days, punches, hours) for the period, either on
+// demand or on the sheets_schedule, which covers the month to yesterday.
+const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const REVOKE_URL: &str = "https://oauth2.googleapis.com/revoke";
+const SHEETS_API: &str = "https://sheets.googleapis.com/v4/spreadsheets";
+const SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets";
+const SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const SCHEDULER_TICK: Duration = Duration::from_secs(60);
+// Refresh a little before Google's expiry
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+static STATS: Mutex<Option<SheetsStats>> = Mutex::new(None);
+static ACCESS_TOKEN: Mutex<Option<AccessToken>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Grant {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    connected_at: DateTime<Utc>,
+}
+
+// Cached per refresh token so a profile switch never reuses another profile's access
+struct AccessToken {
+    refresh_token: String,
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: i64,
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SheetsStats {
+    connected: bool,
+    connected_at: Option<DateTime<Utc>>,
+    last_push_at: Option<DateTime<Utc>>,
+    last_push_rows: usize,
+    last_period: Option<String>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SheetsPushResult {
+    spreadsheet_id: String,
+    tab: String,
+    from: String,
+    to: String,
+    rows: usize,
+}
+
+fn update_stats(f: impl FnOnce(&mut SheetsStats)) {
+    if let Ok(mut guard) = STATS.lock() {
+        f(guard.get_or_insert_with(Default::default));
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn load_grant() -> Option<Grant> {
+    serde_json::from_str(&google_sheets_grant()?).ok()
+}
+
+fn random_bytes() -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+// Accepts the id itself or a link to the spreadsheet
+fn spreadsheet_id(settings: &ShellSettings) -> Result<String, String> {
+    let value = settings
+        .sheets_spreadsheet_id
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default();
+    let id = match value.split_once("/d/") {
+        Some((_, rest)) => rest.split('/').next().unwrap_or_default(),
+        None => value,
+    };
+    if id.is_empty() {
+        return Err("No Google spreadsheet is configured".to_string());
+    }
+    Ok(id.to_string())
+}
+
+async fn token_request(form: &[(&str, &str)]) -> Result<TokenResponse, String> {
+    let response = http_client()?
+        .post(TOKEN_URL)
+        .form(form)
+        .send()
+        .await
+        .map_err(|e| format!("Google token request failed: {}", e))?;
+    let status = response.status();
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid Google token response: {}", e))?;
+    if !status.is_success() {
+        let reason = body
+            .get("error_description")
+            .or_else(|| body.get("error"))
+            .unwrap_or(&body);
+        return Err(format!(
+            "Google rejected the token request ({}): {}",
+            status, reason
+        ));
+    }
+    serde_json::from_value(body).map_err(|e| format!("Invalid Google token response: {}", e))
+}
+
+async fn access_token() -> Result<String, String> {
+    let grant = load_grant().ok_or("Google Sheets is not connected")?;
+    if let Some(cached) = ACCESS_TOKEN.lock().ok().and_then(|guard| {
+        guard
+            .as_ref()
+            .filter(|cached| cached.refresh_token == grant.refresh_token)
+            .filter(|cached| cached.expires_at > Utc::now())
+            .map(|cached| cached.token.clone())
+    }) {
+        return Ok(cached);
+    }
+
+    let response = token_request(&[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", &grant.refresh_token),
+        ("client_id", &grant.client_id),
+        ("client_secret", &grant.client_secret),
+    ])
+    .await
+    .map_err(|e| format!("{} (reconnect Google Sheets if access was revoked)", e))?;
+    let expires_in = (response.expires_in - EXPIRY_MARGIN_SECS).max(0);
+    if let Ok(mut guard) = ACCESS_TOKEN.lock() {
+        *guard = Some(AccessToken {
+            refresh_token: grant.refresh_token,
+            token: response.access_token.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(expires_in),
+        });
+    }
+    Ok(response.access_token)
+}
+
+async fn reply(stream: &mut TcpStream, status: &str, message: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+// Wait for the browser to come back to the loopback redirect with our state
+async fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, String> {
+    loop {
+        let (mut stream, _) = listener
+            .accept()
+            .await
+            .map_err(|e| format!("Google sign-in redirect failed: {}", e))?;
+        let mut buffer = vec![0u8; 8192];
+        let read = stream.read(&mut buffer).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let params: HashMap<String, String> = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|target| Url::parse(&format!("http://127.0.0.1{}", target)).ok())
+            .map(|url| url.query_pairs().into_owned().collect())
+            .unwrap_or_default();
+        // Anything else (e.g. the browser asking for a favicon) is not the redirect
+        if params.get("state").map(String::as_str) != Some(state) {
+            reply(&mut stream, "404 Not Found", "").await;
+            continue;
+        }
+
+        return match params.get("code") {
+            Some(code) => {
+                reply(
+                    &mut stream,
+                    "200 OK",
+                    "Google Sheets is connected. You can close this tab.",
+                )
+                .await;
+                Ok(code.clone())
+            }
+            None => {
+                reply(
+                    &mut stream,
+                    "200 OK",
+                    "Google sign-in did not complete. You can close this tab.",
+                )
+                .await;
+                Err(format!(
+                    "Google sign-in was not completed: {}",
+                    params
+                        .get("error")
+                        .map(String::as_str)
+                        .unwrap_or("no code returned")
+                ))
+            }
+        };
+    }
+}
+
+async fn authorize(client_id: &str, client_secret: &str) -> Result<Grant, String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .map_err(|e| format!("Failed to open the sign-in redirect: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to open the sign-in redirect: {}", e))?
+        .port();
+    let redirect_uri = format!("http://127.0.0.1:{}", port);
+    let verifier = BASE64_URL.encode(random_bytes());
+    let challenge = BASE64_URL.encode(Sha256::digest(verifier.as_bytes()));
+    let state = hex(&random_bytes()[..16]);
+
+    let url = Url::parse_with_params(
+        AUTH_URL,
+        &[
+            ("client_id", client_id),
+            ("redirect_uri", &redirect_uri),
+            ("response_type", "code"),
+            ("scope", SCOPE),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+            ("state", &state),
+            // A refresh token is only issued with offline access on a fresh consent
+            ("access_type", "offline"),
+            ("prompt", "consent"),
+        ],
+    )
+    .map_err(|e| format!("Failed to build the sign-in link: {}", e))?;
+    tauri_plugin_opener::open_url(url.as_str(), None::<&str>)
+        .map_err(|e| format!("Failed to open the browser for Google sign-in: {}", e))?;
+
+    let code = tokio::time::timeout(SIGN_IN_TIMEOUT, wait_for_code(&listener, &state))
+        .await
+        .map_err(|_| "Google sign-in timed out".to_string())??;
+    let response = token_request(&[
+        ("grant_type", "authorization_code"),
+        ("code", &code),
+        ("code_verifier", &verifier),
+        ("redirect_uri", &redirect_uri),
+        ("client_id", client_id),
+        ("client_secret", client_secret),
+    ])
+    .await?;
+    let refresh_token = response
+        .refresh_token
+        .ok_or("Google did not issue a refresh token")?;
+    Ok(Grant {
+        client_id: client_id.to_string(),
+        client_secret: client_secret.to_string(),
+        refresh_token,
+        connected_at: Utc::now(),
+    })
+}
+
+async fn sheets_call(
+    method: Method,
+    url: Url,
+    body: Option<serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    let mut request = http_client()?
+        .request(method, url)
+        .bearer_auth(access_token().await?);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Google Sheets request failed: {}", e))?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if !status.is_success() {
+        let reason = body
+            .pointer("/error/message")
+            .and_then(|message| message.as_str())
+            .unwrap_or_default();
+        return Err(format!(
+            "Google Sheets rejected the request ({}): {}",
+            status, reason
+        ));
+    }
+    Ok(body)
+}
+
+fn api_url(spreadsheet: &str, segments: &[&str]) -> Result<Url, String> {
+    let mut url = Url::parse(SHEETS_API).map_err(|e| format!("Invalid Sheets URL: {}", e))?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid Sheets URL".to_string())?
+        .push(spreadsheet)
+        .extend(segments);
+    Ok(url)
+}
+
+async fn ensure_tab(spreadsheet: &str, tab: &str) -> Result<(), String> {
+    let mut url = api_url(spreadsheet, &[])?;
+    url.query_pairs_mut()
+        .append_pair("fields", "sheets.properties.title");
+    let metadata = sheets_call(Method::GET, url, None).await?;
+    let exists = metadata
+        .get("sheets")
+        .and_then(|sheets| sheets.as_array())
+        .into_iter()
+        .flatten()
+        .any(|sheet| sheet.pointer("/properties/title").and_then(|t| t.as_str()) == Some(tab));
+    if !exists {
+        let url = api_url(&format!("{}:batchUpdate", spreadsheet), &[])?;
+        let body = serde_json::json!({
+            "requests": [{ "addSheet": { "properties": { "title": tab } } }]
+        });
+        sheets_call(Method::POST, url, Some(body)).await?;
+    }
+    Ok(())
+}
+
+// One row per employee over the period
+async fn summary_rows(
+    range: (NaiveDate, NaiveDate),
+    devices: Option<Vec<String>>,
+) -> Result<Vec<serde_json::Value>, String> {
+    let filter = device_filter(&devices);
+    let daily = run_db(move |connection| daily_rows(connection, range, &filter)).await?;
+    let mut rows = vec![serde_json::json!([
+        "User ID", "Name", "From", "To", "Days", "Punches", "Hours"
+    ])];
+    let mut index = 0;
+    while index < daily.len() {
+        let user = &daily[index];
+        let days: Vec<_> = daily[index..]
+            .iter()
+            .take_while(|day| day.user_id == user.user_id)
+            .collect();
+        let hours: f64 = days.iter().map(|day| day.hours).sum();
+        rows.push(serde_json::json!([
+            user.user_id,
+            user.name,
+            range.0.to_string(),
+            range.1.to_string(),
+            days.len(),
+            days.iter().map(|day| day.punches).sum::<u32>(),
+            (hours * 100.0).round() / 100.0,
+        ]));
+        index += days.len();
+    }
+    Ok(rows)
+}
+
+async fn push_summary(
+    settings: &ShellSettings,
+    range: (NaiveDate, NaiveDate),
+    devices: Option<Vec<String>>,
+) -> Result<SheetsPushResult, String> {
+    let spreadsheet = spreadsheet_id(settings)?;
+    let tab = match settings.sheets_tab_name.trim() {
+        "" => "Attendance".to_string(),
+        tab => tab.to_string(),
+    };
+    let rows = summary_rows(range, devices).await?;
+    ensure_tab(&spreadsheet, &tab).await?;
+
+    let sheet_range = format!("'{}'", tab.replace('\'', "''"));
+    let url = api_url(&spreadsheet, &["values", &format!("{}:clear", sheet_range)])?;
+    sheets_call(Method::POST, url, Some(serde_json::json!({}))).await?;
+    // RAW so a name starting with '=' is stored as text, not run as a formula
+    let mut url = api_url(&spreadsheet, &["values", &format!("{}!A1", sheet_range)])?;
+    url.query_pairs_mut().append_pair("valueInputOption", "RAW");
+    sheets_call(
+        Method::PUT,
+        url,
+        Some(serde_json::json!({ "values": rows })),
+    )
+    .await?;
+
+    let result = SheetsPushResult {
+        spreadsheet_id: spreadsheet,
+        tab,
+        from: range.0.to_string(),
+        to: range.1.to_string(),
+        rows: rows.len() - 1,
+    };
+    update_stats(|stats| {
+        stats.last_push_at = Some(Utc::now());
+        stats.last_push_rows = result.rows;
+        stats.last_period = Some(format!("{} to {}", result.from, result.to));
+        stats.last_error = None;
+    });
+    Ok(result)
+}
+
+// The scheduled push the current time falls in, if one is due today
+fn scheduled_slot(config: &ShellSettings, now: DateTime<Local>) -> Option<NaiveDate> {
+    let at = NaiveTime::parse_from_str(&config.sheets_time, "%H:%M").ok()?;
+    let today = now.date_naive();
+    let due = match config.sheets_schedule.as_str() {
+        "daily" => true,
+        "weekly" => today.weekday().number_from_monday() == config.sheets_weekday,
+        _ => false,
+    };
+    (due && now.time() >= at).then_some(today)
+}
+
+pub fn spawn_sheets_scheduler(settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_slot: Option<NaiveDate> = None;
+        loop {
+            tokio::time::sleep(SCHEDULER_TICK).await;
+
+            let config = current_settings(&settings);
+            let Some(slot) = scheduled_slot(&config, Local::now()) else {
+                continue;
+            };
+            if last_slot == Some(slot) {
+                continue;
+            }
+            last_slot = Some(slot);
+
+            // The month up to yesterday; on the 1st that is the whole previous month
+            let Some(yesterday) = slot.pred_opt() else {
+                continue;
+            };
+            let range = (yesterday.with_day(1).unwrap_or(yesterday), yesterday);
+            match push_summary(&config, range, None).await {
+                Ok(result) => append_app_log(&format!(
+                    "Scheduled Google Sheets push: {} employees for {} to {}",
+                    result.rows, result.from, result.to
+                )),
+                Err(err) => {
+                    eprintln!("Scheduled Google Sheets push failed: {}", err);
+                    append_app_log(&format!("Scheduled Google Sheets push failed: {}", err));
+                    update_stats(|stats| stats.last_error = Some(err));
+                }
+            }
+        }
+    });
+}
+
+// Sign in with Google in the browser and keep the grant for this profile
+#[tauri::command]
+pub async fn connect_google_sheets(
+    client_id: String,
+    client_secret: String,
+) -> Result<SheetsStats, String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        let (client_id, client_secret) = (client_id.trim(), client_secret.trim());
+        if client_id.is_empty() || client_secret.is_empty() {
+            return Err("Google OAuth client id and secret are required".to_string());
+        }
+        let grant = authorize(client_id, client_secret).await?;
+        let value = serde_json::to_string(&grant)
+            .map_err(|e| format!("Failed to serialize Google Sheets grant: {}", e))?;
+        set_google_sheets_grant(&value)?;
+        append_app_log("Connected to Google Sheets");
+        Ok(get_google_sheets_status())
+    }
+    .await;
+    audited("connect_google_sheets", "google_sheets", result)
+}
+
+#[tauri::command]
+pub async fn disconnect_google_sheets() -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        // Revoking is best effort; the grant is forgotten here either way
+        if let Some(grant) = load_grant() {
+            if let Ok(client) = http_client() {
+                let _ = client
+                    .post(REVOKE_URL)
+                    .form(&[("token", grant.refresh_token.as_str())])
+                    .send()
+                    .await;
+            }
+        }
+        set_google_sheets_grant("")?;
+        if let Ok(mut guard) = ACCESS_TOKEN.lock() {
+            *guard = None;
+        }
+        Ok(())
+    }
+    .await;
+    audited("disconnect_google_sheets", "google_sheets", result)
+}
+
+#[tauri::command]
+pub async fn push_to_google_sheets(
+    range: ExportRange,
+    devices: Option<Vec<String>>,
+    settings: State<'_, SharedSettings>,
+) -> Result<SheetsPushResult, String> {
+    lock::ensure_unlocked()?;
+    let config = current_settings(&settings);
+    let result = async { push_summary(&config, parse_range(&range)?, devices).await }.await;
+    if let Err(err) = &result {
+        update_stats(|stats| stats.last_error = Some(err.clone()));
+    }
+    let target = config.sheets_spreadsheet_id.clone().unwrap_or_default();
+    audited("push_to_google_sheets", &target, result)
+}
+
+#[tauri::command]
+pub fn get_google_sheets_status() -> SheetsStats {
+    let grant = load_grant();
+    let mut stats = STATS
+        .lock()
+        .map(|guard| guard.clone().unwrap_or_default())
+        .unwrap_or_default();
+    stats.connected = grant.is_some();
+    stats.connected_at = grant.map(|grant| grant.connected_at);
+    stats
+}
@@ -0,0 +1,190 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_shell::process::Command;
+use tauri_plugin_shell::ShellExt;
+
+use crate::relocate::digest;
+use crate::sidecar_update::active_binary;
+use crate::{append_app_log, resolve_app_data_dir};
+
+// The zkteco-backend binary is hashed before every spawn and compared with the manifest built
+// into the shell (build.rs), so a tampered sidecar, or one damaged by an antivirus quarantine,
+// is never run. Failures are logged and reported with "sidecar-integrity-failed". The process id
+// of the running sidecar is kept in backend.pid so the headless CLI can stop it. A backend
+// installed by sidecar_update.rs runs from the data folder instead and is checked against the
+// hash recorded when its signature was verified.
+const SIDECAR_NAME: &str = "zkteco-backend";
+const PID_FILE: &str = "backend.pid";
+
+static LAST_CHECK: Mutex<Option<IntegrityCheck>> = Mutex::new(None);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct IntegrityCheck {
+    path: String,
+    expected: Option<String>, // None for development builds without a bundled sidecar
+    actual: Option<String>,
+    ok: bool,
+    error: Option<String>,
+    checked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SidecarProcess {
+    pub pid: u32,
+    pub port: u16,
+    pub started_at: DateTime<Utc>,
+}
+
+fn expected_hash() -> Option<&'static str> {
+    option_env!("ZKTECO_SIDECAR_SHA256").filter(|hash| !hash.is_empty())
+}
+
+// Tauri places external binaries next to the shell executable, without the target triple
+fn bundled_path() -> Result<PathBuf, String> {
+    let exe = env::current_exe().map_err(|e| format!("Failed to locate the app: {}", e))?;
+    let dir = exe.parent().ok_or("App executable has no parent folder")?;
+    Ok(dir.join(format!("{}{}", SIDECAR_NAME, env::consts::EXE_SUFFIX)))
+}
+
+// The binary the backend is started from
+pub fn current_binary() -> Result<PathBuf, String> {
+    match active_binary() {
+        Some((path, _)) => Ok(path),
+        None => bundled_path(),
+    }
+}
+
+fn check() -> IntegrityCheck {
+    let staged = active_binary();
+    let path = current_binary();
+    let expected = match &staged {
+        Some((_, hash)) => Some(hash.clone()),
+        None => expected_hash().map(str::to_string),
+    };
+    let actual = path.as_ref().map_err(Clone::clone).and_then(|path| {
+        if path.exists() {
+            digest(path)
+        } else {
+            Err(format!(
+                "Backend binary is missing from {:?}; it may have been quarantined by antivirus software",
+                path
+            ))
+        }
+    });
+    let error = match (&expected, &actual) {
+        (_, Err(err)) => Some(err.clone()),
+        (Some(expected), Ok(actual)) if expected != actual => Some(if staged.is_some() {
+            "Updated backend binary was modified or corrupted. Roll back the backend update \
+             to repair it"
+                .to_string()
+        } else {
+            "Backend binary does not match this release; it was modified or corrupted. \
+             Reinstall the app to repair it"
+                .to_string()
+        }),
+        _ => None,
+    };
+    IntegrityCheck {
+        path: path
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        expected,
+        actual: actual.ok(),
+        ok: error.is_none(),
+        error,
+        checked_at: Utc::now(),
+    }
+}
+
+// Called right before the sidecar is spawned; Err means it must not be started
+pub async fn verify_sidecar(app: &AppHandle) -> Result<(), String> {
+    let result = tauri::async_runtime::spawn_blocking(check)
+        .await
+        .map_err(|e| format!("Backend integrity check failed: {}", e))?;
+    if let Ok(mut guard) = LAST_CHECK.lock() {
+        *guard = Some(result.clone());
+    }
+
+    match &result.error {
+        None => {
+            if result.expected.is_none() {
+                println!("No sidecar manifest in this build, skipping integrity check");
+            }
+            Ok(())
+        }
+        Some(err) => {
+            append_app_log(&format!(
+                "Refusing to start backend {}: {} (expected {}, found {})",
+                result.path,
+                err,
+                result.expected.as_deref().unwrap_or("-"),
+                result.actual.as_deref().unwrap_or("-")
+            ));
+            if let Err(emit_err) = app.emit("sidecar-integrity-failed", &result) {
+                eprintln!(
+                    "Failed to emit sidecar-integrity-failed event: {}",
+                    emit_err
+                );
+            }
+            Err(err.clone())
+        }
+    }
+}
+
+// Same check for the headless CLI, which has no window to notify; returns the binary to run
+pub fn verified_sidecar_path() -> Result<PathBuf, String> {
+    let result = check();
+    if let Ok(mut guard) = LAST_CHECK.lock() {
+        *guard = Some(result.clone());
+    }
+    match result.error {
+        None => Ok(PathBuf::from(result.path)),
+        Some(err) => Err(err),
+    }
+}
+
+// Command for the backend binary that passed the last check: the staged update if there is
+// one, else the bundled sidecar
+pub fn backend_command(app: &AppHandle) -> Result<Command, String> {
+    match active_binary() {
+        Some((path, _)) => Ok(app.shell().command(path)),
+        None => app.shell().sidecar(SIDECAR_NAME).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn record_pid(pid: u32, port: u16) {
+    let process = SidecarProcess {
+        pid,
+        port,
+        started_at: Utc::now(),
+    };
+    let written = serde_json::to_string(&process)
+        .map_err(|e| e.to_string())
+        .and_then(|content| {
+            std::fs::write(resolve_app_data_dir().join(PID_FILE), content)
+                .map_err(|e| e.to_string())
+        });
+    if let Err(err) = written {
+        eprintln!("Failed to record backend process id: {}", err);
+    }
+}
+
+pub fn recorded_pid() -> Option<SidecarProcess> {
+    std::fs::read_to_string(resolve_app_data_dir().join(PID_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+pub fn clear_pid() {
+    let _ = std::fs::remove_file(resolve_app_data_dir().join(PID_FILE));
+}
+
+// Result of the check before the latest backend start, None before the first one
+#[tauri::command]
+pub fn get_sidecar_integrity() -> Option<IntegrityCheck> {
+    LAST_CHECK.lock().ok().and_then(|check| check.clone())
+}
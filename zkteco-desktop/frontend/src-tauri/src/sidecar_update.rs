@@ -0,0 +1,630 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use minisign_verify::{PublicKey, Signature};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::audit::audited;
+use crate::backup::{
+    snapshot_database, start_backend_after_maintenance, stop_backend_for_maintenance,
+    swap_database, validate_backup,
+};
+use crate::relocate::digest;
+use crate::service;
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::sidecar::current_binary;
+use crate::{
+    append_app_log, check_backend_health, current_backend_port, health_endpoint, lock,
+    resolve_app_data_dir, updater, BackendPort,
+};
+
+// Backend updates without a new desktop release. A manifest (sidecar_update_endpoint or the
+// built-in ZTKAPP_SIDECAR_UPDATE_ENDPOINT, with {{channel}} and {{target}} filled in) names the
+// latest zkteco-backend per platform with a minisign signature made with the app's update key.
+// The binary is downloaded to sidecar/ in the data folder, checked against the signature, and
+// the backend restarted from it. sidecar.json records the active binary and the one before it
+// (none = the one bundled with the app); a binary that doesn't come up healthy is rolled back
+// at once, and rollback_backend goes back one step by hand. For slow shop connections a
+// platform can also list zstd patches against earlier builds; the one made from the binary in
+// use is downloaded instead of the full file, and the full file is the fallback whenever a
+// patch is missing, fails to apply or doesn't reproduce the signed binary. Each update also
+// snapshots the database first, so a rollback can put back the data the older backend last
+// worked with.
+const SIDECAR_DIR: &str = "sidecar";
+const STATE_FILE: &str = "sidecar.json";
+const BUILT_IN_ENDPOINT: Option<&str> = option_env!("ZTKAPP_SIDECAR_UPDATE_ENDPOINT");
+const BUNDLED_VERSION: &str = env!("ZKTECO_SIDECAR_VERSION");
+const MANIFEST_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_BINARY_BYTES: usize = 512 * 1024 * 1024;
+// Patches reference the whole old binary, so the decoder needs a window that covers it
+const PATCH_WINDOW_LOG_MAX: u32 = 30;
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+const HEALTH_POLL: Duration = Duration::from_secs(1);
+
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+// Held for the whole stop/swap/start sequence so two swaps can't interleave
+static SWAP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StagedBinary {
+    version: String,
+    // File name inside sidecar/
+    file: String,
+    sha256: String,
+    installed_at: DateTime<Utc>,
+    // Snapshot (file name inside sidecar/) of the database just before this binary took over
+    #[serde(default)]
+    database_backup: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct SidecarState {
+    // None runs the binary bundled with the app
+    active: Option<StagedBinary>,
+    previous: Option<StagedBinary>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(default)]
+    notes: Option<String>,
+    platforms: std::collections::HashMap<String, PlatformBinary>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct PlatformBinary {
+    url: String,
+    // Signature of the full binary, which is also what a patch must reproduce
+    signature: String,
+    #[serde(default)]
+    patches: Vec<Patch>,
+}
+
+// zstd --patch-from=<old> <new>: a frame compressed against the old binary as its reference
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Patch {
+    // SHA-256 of the binary the patch applies to
+    from_sha256: String,
+    url: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SidecarUpdateCheck {
+    current_version: String,
+    bundled_version: String,
+    target: String,
+    available: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SidecarSwapResult {
+    version: String,
+    previous_version: String,
+    rolled_back: bool,
+    database_restored_from: Option<String>,
+    // The database that was replaced by the restore
+    safety_copy: Option<String>,
+    message: String,
+}
+
+fn sidecar_dir() -> PathBuf {
+    resolve_app_data_dir().join(SIDECAR_DIR)
+}
+
+fn load_state() -> SidecarState {
+    fs::read_to_string(sidecar_dir().join(STATE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &SidecarState) -> Result<(), String> {
+    let dir = sidecar_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| format!("Failed to serialize sidecar state: {}", e))?;
+    let temp = dir.join(format!("{}.tmp", STATE_FILE));
+    fs::write(&temp, content).map_err(|e| format!("Failed to write sidecar state: {}", e))?;
+    fs::rename(&temp, dir.join(STATE_FILE))
+        .map_err(|e| format!("Failed to save sidecar state: {}", e))
+}
+
+// Change sidecar.json under its lock; f returns the new state
+fn update_state(
+    f: impl FnOnce(SidecarState) -> Result<SidecarState, String>,
+) -> Result<(), String> {
+    let _guard = STATE_LOCK.lock();
+    save_state(&f(load_state())?)
+}
+
+fn version_of(binary: &Option<StagedBinary>) -> String {
+    binary
+        .as_ref()
+        .map(|binary| binary.version.clone())
+        .unwrap_or_else(|| BUNDLED_VERSION.to_string())
+}
+
+// Binary to spawn instead of the bundled one, with the hash it must have
+pub fn active_binary() -> Option<(PathBuf, String)> {
+    let _guard = STATE_LOCK.lock();
+    load_state()
+        .active
+        .map(|binary| (sidecar_dir().join(binary.file), binary.sha256))
+}
+
+// Manifest platform key, e.g. "windows-x86_64"
+fn target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+fn newer(candidate: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parts(candidate) > parts(current)
+}
+
+fn endpoint(settings: &ShellSettings) -> Result<String, String> {
+    let template = settings
+        .sidecar_update_endpoint
+        .as_deref()
+        .or(BUILT_IN_ENDPOINT)
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .ok_or("No backend update server is configured")?;
+    let url = template
+        .replace("{{channel}}", settings.update_channel.trim())
+        .replace("{{target}}", &target());
+    reqwest::Url::parse(&url).map_err(|_| format!("'{}' is not a valid update endpoint", url))?;
+    Ok(url)
+}
+
+async fn fetch_manifest(settings: &ShellSettings) -> Result<Manifest, String> {
+    let client = reqwest::Client::builder()
+        .timeout(MANIFEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .get(endpoint(settings)?)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach the backend update server: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Backend update server returned {}",
+            response.status()
+        ));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse backend update manifest: {}", e))
+}
+
+// Signatures are the base64 of a minisign .sig file, as `tauri signer sign` writes them
+fn verify_signature(bytes: &[u8], signature: &str) -> Result<(), String> {
+    let pubkey = updater::pubkey().ok_or("This build has no update signing key")?;
+    let decode = |value: &str| {
+        BASE64
+            .decode(value.trim())
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())
+    };
+    let key = decode(pubkey)
+        .and_then(|key| PublicKey::decode(&key).ok())
+        .ok_or("The built-in update signing key is invalid")?;
+    let signature = decode(signature)
+        .and_then(|signature| Signature::decode(&signature).ok())
+        .ok_or("The backend update signature is malformed")?;
+    key.verify(bytes, &signature, true).map_err(|_| {
+        "The backend update signature does not match; it was not installed".to_string()
+    })
+}
+
+async fn fetch(client: &reqwest::Client, url: &str, what: &str) -> Result<Vec<u8>, String> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", what, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Download of {} returned {}",
+            what,
+            response.status()
+        ));
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_BINARY_BYTES as u64)
+    {
+        return Err(format!("{} is larger than any backend build", what));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", what, e))?;
+    if bytes.len() > MAX_BINARY_BYTES {
+        return Err(format!("{} is larger than any backend build", what));
+    }
+    Ok(bytes.to_vec())
+}
+
+fn apply_patch(old: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoder = zstd::stream::read::Decoder::with_ref_prefix(patch, old)
+        .map_err(|e| format!("Failed to read patch: {}", e))?;
+    decoder
+        .window_log_max(PATCH_WINDOW_LOG_MAX)
+        .map_err(|e| format!("Failed to read patch: {}", e))?;
+    let mut binary = Vec::new();
+    (&mut decoder)
+        .take(MAX_BINARY_BYTES as u64 + 1)
+        .read_to_end(&mut binary)
+        .map_err(|e| format!("Failed to apply patch: {}", e))?;
+    if binary.len() > MAX_BINARY_BYTES {
+        return Err("Patched backend is larger than any backend build".to_string());
+    }
+    Ok(binary)
+}
+
+// The new binary rebuilt from the running one, when the update has a patch for it
+async fn patched(
+    client: &reqwest::Client,
+    manifest: &Manifest,
+    platform: &PlatformBinary,
+) -> Result<Option<Vec<u8>>, String> {
+    if platform.patches.is_empty() {
+        return Ok(None);
+    }
+    let current = current_binary()?;
+    let (old, old_sha256) = blocking(move || {
+        let old =
+            fs::read(&current).map_err(|e| format!("Failed to read the current backend: {}", e))?;
+        Ok((old, digest(&current)?))
+    })
+    .await?;
+    let Some(patch) = platform
+        .patches
+        .iter()
+        .find(|patch| patch.from_sha256.eq_ignore_ascii_case(&old_sha256))
+    else {
+        return Ok(None);
+    };
+    let bytes = fetch(
+        client,
+        &patch.url,
+        &format!("backend {} patch", manifest.version),
+    )
+    .await?;
+    let patch_size = bytes.len();
+    let binary = blocking(move || apply_patch(&old, &bytes)).await?;
+    verify_signature(&binary, &platform.signature)?;
+    append_app_log(&format!(
+        "Built backend {} from a {} byte patch instead of a {} byte download",
+        manifest.version,
+        patch_size,
+        binary.len()
+    ));
+    Ok(Some(binary))
+}
+
+async fn download(manifest: &Manifest) -> Result<StagedBinary, String> {
+    let platform = manifest
+        .platforms
+        .get(&target())
+        .ok_or_else(|| format!("No backend build for {} in the update", target()))?;
+    let client = reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let bytes = match patched(&client, manifest, platform).await {
+        Ok(Some(binary)) => binary,
+        result => {
+            if let Err(err) = result {
+                append_app_log(&format!(
+                    "Backend patch unusable ({}); downloading the full binary",
+                    err
+                ));
+            }
+            let bytes = fetch(
+                &client,
+                &platform.url,
+                &format!("backend {}", manifest.version),
+            )
+            .await?;
+            verify_signature(&bytes, &platform.signature)?;
+            bytes
+        }
+    };
+
+    // Version strings come from the server, so only safe characters reach the file name
+    let file_version: String = manifest
+        .version
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .collect();
+    let file = format!(
+        "zkteco-backend-{}{}",
+        file_version,
+        std::env::consts::EXE_SUFFIX
+    );
+    let dir = sidecar_dir();
+    let path = dir.join(&file);
+    let partial = dir.join(format!("{}.part", file));
+    let version = manifest.version.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        fs::write(&partial, &bytes).map_err(|e| format!("Failed to stage backend: {}", e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&partial, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to make the backend executable: {}", e))?;
+        }
+        fs::rename(&partial, &path).map_err(|e| format!("Failed to stage backend: {}", e))?;
+        let sha256 = digest(&path)?;
+        Ok(StagedBinary {
+            version,
+            file,
+            sha256,
+            installed_at: Utc::now(),
+            database_backup: None,
+        })
+    })
+    .await
+    .map_err(|e| format!("Staging task failed: {}", e))?
+}
+
+// Delete staged binaries that are neither active nor the rollback target
+fn prune() {
+    let state = load_state();
+    let keep: Vec<String> = [&state.active, &state.previous]
+        .into_iter()
+        .flatten()
+        .flat_map(|binary| [Some(binary.file.clone()), binary.database_backup.clone()])
+        .flatten()
+        .chain([STATE_FILE.to_string()])
+        .collect();
+    if let Ok(entries) = fs::read_dir(sidecar_dir()) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !keep.contains(&name) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+async fn wait_until_healthy(app: &AppHandle) -> bool {
+    let deadline = tokio::time::Instant::now() + HEALTH_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        let endpoint = health_endpoint(
+            current_backend_port(&app.state::<BackendPort>()),
+            &app.state::<SharedSettings>(),
+        );
+        if check_backend_health(&endpoint).await {
+            return true;
+        }
+        tokio::time::sleep(HEALTH_POLL).await;
+    }
+    false
+}
+
+async fn restart_and_verify(app: &AppHandle) -> Result<(), String> {
+    start_backend_after_maintenance(app).await?;
+    if wait_until_healthy(app).await {
+        Ok(())
+    } else {
+        Err("Backend did not become healthy".to_string())
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Backend update task failed: {}", e))?
+}
+
+// Stop the backend, make `next` the active binary (restoring `database` first when given) and
+// start it; if it doesn't come up, the previous binary and database are put back and started
+async fn swap_to(
+    app: &AppHandle,
+    next: SidecarState,
+    database: Option<PathBuf>,
+) -> Result<SidecarSwapResult, String> {
+    if let Some(service) = service::installed() {
+        return Err(format!(
+            "The backend runs as the {} service, which keeps the build it was installed with; remove the service to switch backends",
+            service.name
+        ));
+    }
+    let _swap = SWAP_LOCK.lock().await;
+    let before = {
+        let _guard = STATE_LOCK.lock();
+        load_state()
+    };
+    let previous_version = version_of(&before.active);
+    let version = version_of(&next.active);
+
+    if let Some(database) = database.clone() {
+        blocking(move || validate_backup(&database)).await?;
+    }
+    stop_backend_for_maintenance(app)
+        .await
+        .map_err(|e| format!("{}, backend switch aborted", e))?;
+    let safety = match database.clone() {
+        Some(database) => match blocking(move || swap_database(&database)).await {
+            Ok(safety) => Some(safety),
+            Err(err) => {
+                let _ = start_backend_after_maintenance(app).await;
+                return Err(err);
+            }
+        },
+        None => None,
+    };
+    update_state(|_| Ok(next))?;
+    append_app_log(&format!(
+        "Switching backend from {} to {}",
+        previous_version, version
+    ));
+
+    let result = match restart_and_verify(app).await {
+        Ok(()) => SidecarSwapResult {
+            message: format!("Backend {} is running", version),
+            version: version.clone(),
+            previous_version,
+            rolled_back: false,
+            database_restored_from: database.map(|path| path.to_string_lossy().to_string()),
+            safety_copy: safety.map(|path| path.to_string_lossy().to_string()),
+        },
+        Err(err) => {
+            append_app_log(&format!(
+                "Backend {} failed to start ({}); going back to {}",
+                version, err, previous_version
+            ));
+            let _ = stop_backend_for_maintenance(app).await;
+            update_state(|_| Ok(before))?;
+            if let Some(safety) = safety {
+                blocking(move || swap_database(&safety)).await?;
+            }
+            restart_and_verify(app)
+                .await
+                .map_err(|e| format!("Backend {} failed and so did going back: {}", version, e))?;
+            SidecarSwapResult {
+                message: format!(
+                    "Backend {} failed to start ({}); still running {}",
+                    version, err, previous_version
+                ),
+                version: previous_version.clone(),
+                previous_version: version,
+                rolled_back: true,
+                database_restored_from: None,
+                safety_copy: None,
+            }
+        }
+    };
+    prune();
+    append_app_log(&result.message);
+    if let Err(err) = app.emit("sidecar-updated", &result) {
+        eprintln!("Failed to emit sidecar-updated event: {}", err);
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn check_sidecar_update(
+    settings: State<'_, SharedSettings>,
+) -> Result<SidecarUpdateCheck, String> {
+    let manifest = fetch_manifest(&current_settings(&settings)).await?;
+    let current_version = version_of(&load_state().active);
+    let available =
+        newer(&manifest.version, &current_version) && manifest.platforms.contains_key(&target());
+    Ok(SidecarUpdateCheck {
+        available: available.then(|| manifest.version.clone()),
+        notes: manifest.notes.filter(|_| available),
+        current_version,
+        bundled_version: BUNDLED_VERSION.to_string(),
+        target: target(),
+    })
+}
+
+// Download, verify and switch to the newest backend on the update server
+#[tauri::command]
+pub async fn install_sidecar_update(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<SidecarSwapResult, String> {
+    lock::ensure_unlocked()?;
+    let result = async {
+        let manifest = fetch_manifest(&current_settings(&settings)).await?;
+        let state = load_state();
+        if !newer(&manifest.version, &version_of(&state.active)) {
+            return Err(format!(
+                "Backend {} is already up to date",
+                version_of(&state.active)
+            ));
+        }
+        let mut staged = download(&manifest).await?;
+        let snapshot = blocking(|| snapshot_database(&sidecar_dir()))
+            .await
+            .map_err(|e| format!("{}; the backend was not updated", e))?;
+        staged.database_backup = PathBuf::from(&snapshot.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        swap_to(
+            &app,
+            SidecarState {
+                active: Some(staged),
+                previous: state.active,
+            },
+            None,
+        )
+        .await
+    }
+    .await;
+    audited("install_sidecar_update", "backend", result)
+}
+
+// Go back to the backend that ran before the last update (the bundled one after that).
+// restore_database puts back the database snapshot taken when the current backend was
+// installed - by default whenever there is one; attendance recorded since then is re-read from
+// the devices on the next sync.
+#[tauri::command]
+pub async fn rollback_backend(
+    app: AppHandle,
+    restore_database: Option<bool>,
+) -> Result<SidecarSwapResult, String> {
+    lock::ensure_unlocked()?;
+    let state = load_state();
+    let result = async {
+        let active = state
+            .active
+            .ok_or("The backend bundled with the app is already running")?;
+        let snapshot = active
+            .database_backup
+            .map(|file| sidecar_dir().join(file))
+            .filter(|path| path.is_file());
+        let database = match (restore_database, snapshot) {
+            (Some(false), _) => None,
+            (Some(true), None) => {
+                return Err(format!(
+                    "No database snapshot was kept for backend {}",
+                    active.version
+                ))
+            }
+            (_, snapshot) => snapshot,
+        };
+        swap_to(
+            &app,
+            SidecarState {
+                active: state.previous,
+                previous: None,
+            },
+            database,
+        )
+        .await
+    }
+    .await;
+    audited("rollback_backend", "backend", result)
+}
+
+#[tauri::command]
+pub fn get_sidecar_versions() -> SidecarState {
+    let _guard = STATE_LOCK.lock();
+    load_state()
+}
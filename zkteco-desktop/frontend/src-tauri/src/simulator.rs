@@ -0,0 +1,409 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use tauri::State;
+
+use crate::append_app_log;
+use crate::lock;
+use crate::zk::{
+    build_packet, decode_c_string, decode_time, encode_time, pack_user, parse_users, put_str,
+    AttendanceRecord, DeviceUser, CMD_ACK_ERROR, CMD_ACK_OK, CMD_ATTLOG_RRQ, CMD_CLEAR_ATTLOG,
+    CMD_CONNECT, CMD_DATA, CMD_EXIT, CMD_FREE_DATA, CMD_GET_FREE_SIZES, CMD_GET_TIME,
+    CMD_GET_VERSION, CMD_OPTIONS_RRQ, CMD_PREPARE_BUFFER, CMD_REG_EVENT, CMD_SET_TIME,
+    CMD_USERTEMP_RRQ, CMD_USER_WRQ, COMMAND_HEADER_SIZE, EF_ATTLOG, START_TAG, TCP_HEADER_SIZE,
+    USER_RECORD_LARGE,
+};
+
+// In-process fake terminal for development: listens on localhost, answers the subset of the
+// native protocol the shell uses (handshake, options, sizes, clock, users, attendance, realtime
+// events) and generates punches on a timer, so work can proceed without ZKTeco hardware
+const DEFAULT_SIMULATOR_PORT: u16 = 14370;
+const DEFAULT_PUNCH_INTERVAL_SECS: u64 = 30;
+const DEFAULT_SIMULATED_USERS: u16 = 10;
+const ATTENDANCE_RECORD_SIZE: usize = 40;
+const SIMULATOR_SERIAL: &str = "SIM0000000001";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SimulatorInfo {
+    ip: String,
+    port: u16,
+    serial_number: String,
+    users: u16,
+    punch_interval_secs: u64,
+}
+
+pub struct RunningSimulator {
+    info: SimulatorInfo,
+    stop: Arc<AtomicBool>,
+}
+
+pub type SimulatorHandle = Arc<Mutex<Option<RunningSimulator>>>;
+
+struct SimulatedDevice {
+    users: Vec<DeviceUser>,
+    records: Vec<AttendanceRecord>,
+    // Seconds the simulated clock is ahead of the host (changed by CMD_SET_TIME)
+    clock_offset: i64,
+}
+
+impl SimulatedDevice {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local() + chrono::Duration::seconds(self.clock_offset)
+    }
+}
+
+type SharedDevice = Arc<Mutex<SimulatedDevice>>;
+
+fn random(seed: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(seed);
+    hasher.finish()
+}
+
+fn encode_attendance(record: &AttendanceRecord, uid: u16) -> Vec<u8> {
+    let mut raw = vec![0u8; ATTENDANCE_RECORD_SIZE];
+    raw[0..2].copy_from_slice(&uid.to_le_bytes());
+    put_str(&mut raw[2..26], &record.user_id);
+    raw[26] = record.verify_mode;
+    raw[27..31].copy_from_slice(&encode_time(record.timestamp).to_le_bytes());
+    raw[31] = record.punch;
+    raw
+}
+
+// Realtime event layout used by current firmware: 24 byte user id, verify, punch, y m d h m s
+fn encode_event(record: &AttendanceRecord) -> Vec<u8> {
+    let mut raw = vec![0u8; 32];
+    put_str(&mut raw[0..24], &record.user_id);
+    raw[24] = record.verify_mode;
+    raw[25] = record.punch;
+    let t = record.timestamp;
+    raw[26] = (t.year() - 2000) as u8;
+    raw[27] = t.month() as u8;
+    raw[28] = t.day() as u8;
+    raw[29] = t.hour() as u8;
+    raw[30] = t.minute() as u8;
+    raw[31] = t.second() as u8;
+    raw
+}
+
+fn with_size_prefix(data: Vec<u8>) -> Vec<u8> {
+    let mut out = (data.len() as u32).to_le_bytes().to_vec();
+    out.extend(data);
+    out
+}
+
+fn sizes_reply(device: &SimulatedDevice) -> Vec<u8> {
+    let mut fields = [0u32; 20];
+    fields[4] = device.users.len() as u32;
+    fields[8] = device.records.len() as u32;
+    fields[12] = device.users.iter().filter(|u| u.card != 0).count() as u32;
+    fields[14] = 3000;
+    fields[15] = 3000;
+    fields[16] = 100_000;
+    fields.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn read_packet(stream: &mut TcpStream) -> std::io::Result<(u16, u16, Vec<u8>)> {
+    let mut header = [0u8; TCP_HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    if header[0..4] != START_TAG {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "invalid start tag",
+        ));
+    }
+    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if size < COMMAND_HEADER_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "packet too short",
+        ));
+    }
+    let mut payload = vec![0u8; size];
+    stream.read_exact(&mut payload)?;
+    let command = u16::from_le_bytes([payload[0], payload[1]]);
+    let reply_id = u16::from_le_bytes([payload[6], payload[7]]);
+    Ok((command, reply_id, payload[COMMAND_HEADER_SIZE..].to_vec()))
+}
+
+// Reply code and data for one request
+fn handle_command(
+    device: &SharedDevice,
+    command: u16,
+    data: &[u8],
+    events: &mut u32,
+) -> (u16, Vec<u8>) {
+    let Ok(mut device) = device.lock() else {
+        return (CMD_ACK_ERROR, Vec::new());
+    };
+    match command {
+        CMD_GET_VERSION => (CMD_ACK_OK, b"Ver 6.60 Simulator\0".to_vec()),
+        CMD_OPTIONS_RRQ => {
+            let name = decode_c_string(data);
+            let value = match name.as_str() {
+                "~SerialNumber" => SIMULATOR_SERIAL,
+                "~DeviceName" => "ZK Simulator",
+                "~Platform" => "SIM_TFT",
+                "FaceFunOn" => "0",
+                _ => return (CMD_ACK_ERROR, Vec::new()),
+            };
+            (CMD_ACK_OK, format!("{}={}\0", name, value).into_bytes())
+        }
+        CMD_GET_FREE_SIZES => (CMD_ACK_OK, sizes_reply(&device)),
+        CMD_GET_TIME => (CMD_ACK_OK, encode_time(device.now()).to_le_bytes().to_vec()),
+        CMD_SET_TIME if data.len() >= 4 => {
+            match decode_time(u32::from_le_bytes([data[0], data[1], data[2], data[3]])) {
+                Some(time) => {
+                    device.clock_offset = (time - Local::now().naive_local()).num_seconds();
+                    (CMD_ACK_OK, Vec::new())
+                }
+                None => (CMD_ACK_ERROR, Vec::new()),
+            }
+        }
+        // Datasets are returned inline, which read_with_buffer accepts as well
+        CMD_PREPARE_BUFFER if data.len() >= 3 => {
+            let requested = u16::from_le_bytes([data[1], data[2]]);
+            let dataset = match requested {
+                CMD_ATTLOG_RRQ => device
+                    .records
+                    .iter()
+                    .map(|record| {
+                        let uid = device
+                            .users
+                            .iter()
+                            .find(|u| u.user_id == record.user_id)
+                            .map(|u| u.uid)
+                            .unwrap_or(0);
+                        encode_attendance(record, uid)
+                    })
+                    .collect::<Vec<_>>()
+                    .concat(),
+                CMD_USERTEMP_RRQ => device
+                    .users
+                    .iter()
+                    .filter_map(|user| pack_user(user, USER_RECORD_LARGE).ok())
+                    .collect::<Vec<_>>()
+                    .concat(),
+                _ => Vec::new(),
+            };
+            (CMD_DATA, with_size_prefix(dataset))
+        }
+        CMD_USER_WRQ => {
+            let (parsed, _) = parse_users(&with_size_prefix(data.to_vec()), 1);
+            match parsed.into_iter().next() {
+                Some(user) => {
+                    device.users.retain(|u| u.uid != user.uid);
+                    device.users.push(user);
+                    device.users.sort_by_key(|u| u.uid);
+                    (CMD_ACK_OK, Vec::new())
+                }
+                None => (CMD_ACK_ERROR, Vec::new()),
+            }
+        }
+        CMD_CLEAR_ATTLOG => {
+            device.records.clear();
+            (CMD_ACK_OK, Vec::new())
+        }
+        CMD_REG_EVENT if data.len() >= 4 => {
+            *events = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            (CMD_ACK_OK, Vec::new())
+        }
+        CMD_FREE_DATA => (CMD_ACK_OK, Vec::new()),
+        // Enable/disable, refresh, unlock, messages, restart... are accepted and ignored
+        _ => (CMD_ACK_OK, Vec::new()),
+    }
+}
+
+fn serve_client(mut stream: TcpStream, device: SharedDevice, stop: Arc<AtomicBool>) {
+    let session_id = (random(0) & 0x7FFF) as u16 + 1;
+    let mut events = 0u32;
+    let mut pushed = device.lock().map(|d| d.records.len()).unwrap_or(0);
+
+    while !stop.load(Ordering::Relaxed) {
+        // Wait for a request; while idle, push new punches to subscribed clients
+        let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+        let mut probe = [0u8; 1];
+        match stream.peek(&mut probe) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                if events & EF_ATTLOG != 0 {
+                    let fresh: Vec<AttendanceRecord> = match device.lock() {
+                        Ok(device) => device.records.iter().skip(pushed).cloned().collect(),
+                        Err(_) => return,
+                    };
+                    for record in &fresh {
+                        let packet =
+                            build_packet(CMD_REG_EVENT, EF_ATTLOG as u16, 0, &encode_event(record));
+                        if stream.write_all(&packet).is_err() {
+                            return;
+                        }
+                    }
+                    pushed += fresh.len();
+                }
+                continue;
+            }
+            Err(_) => return,
+        }
+
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let Ok((command, reply_id, data)) = read_packet(&mut stream) else {
+            return;
+        };
+        // Acks of pushed events need no answer
+        if command == CMD_ACK_OK {
+            continue;
+        }
+        let (reply, payload) = if command == CMD_CONNECT {
+            (CMD_ACK_OK, Vec::new())
+        } else {
+            handle_command(&device, command, &data, &mut events)
+        };
+        let packet = build_packet(reply, session_id, reply_id, &payload);
+        if stream.write_all(&packet).is_err() || command == CMD_EXIT {
+            return;
+        }
+    }
+}
+
+fn seed_device(users: u16) -> SimulatedDevice {
+    SimulatedDevice {
+        users: (1..=users)
+            .map(|uid| DeviceUser {
+                uid,
+                user_id: uid.to_string(),
+                name: format!("Sim User {}", uid),
+                privilege: 0,
+                password: String::new(),
+                card: 0,
+                group_id: "1".to_string(),
+            })
+            .collect(),
+        records: Vec::new(),
+        clock_offset: 0,
+    }
+}
+
+fn spawn_punch_generator(device: SharedDevice, interval: Duration, stop: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let mut tick = 0u64;
+        while !stop.load(Ordering::Relaxed) {
+            let mut waited = Duration::ZERO;
+            while waited < interval && !stop.load(Ordering::Relaxed) {
+                thread::sleep(POLL_INTERVAL);
+                waited += POLL_INTERVAL;
+            }
+            tick += 1;
+            if let Ok(mut device) = device.lock() {
+                if device.users.is_empty() {
+                    continue;
+                }
+                let roll = random(tick);
+                let user = device.users[(roll % device.users.len() as u64) as usize].clone();
+                let timestamp = device.now().with_nanosecond(0).unwrap_or(device.now());
+                device.records.push(AttendanceRecord {
+                    user_id: user.user_id,
+                    timestamp,
+                    verify_mode: [1u8, 2, 15][(roll >> 8) as usize % 3], // finger, card, face
+                    punch: ((roll >> 16) % 2) as u8,
+                });
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn start_device_simulator(
+    port: Option<u16>,
+    punch_interval_secs: Option<u64>,
+    users: Option<u16>,
+    handle: State<SimulatorHandle>,
+) -> Result<SimulatorInfo, String> {
+    lock::ensure_unlocked()?;
+    let mut running = handle
+        .lock()
+        .map_err(|e| format!("Failed to lock simulator state: {}", e))?;
+    if let Some(simulator) = running.as_ref() {
+        return Ok(simulator.info.clone());
+    }
+
+    let port = port.unwrap_or(DEFAULT_SIMULATOR_PORT);
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Failed to start device simulator on port {}: {}", port, e))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure simulator listener: {}", e))?;
+
+    let info = SimulatorInfo {
+        ip: "127.0.0.1".to_string(),
+        port,
+        serial_number: SIMULATOR_SERIAL.to_string(),
+        users: users.unwrap_or(DEFAULT_SIMULATED_USERS),
+        punch_interval_secs: punch_interval_secs
+            .unwrap_or(DEFAULT_PUNCH_INTERVAL_SECS)
+            .max(1),
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let device: SharedDevice = Arc::new(Mutex::new(seed_device(info.users)));
+    spawn_punch_generator(
+        device.clone(),
+        Duration::from_secs(info.punch_interval_secs),
+        stop.clone(),
+    );
+
+    let accept_stop = stop.clone();
+    thread::spawn(move || {
+        while !accept_stop.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nonblocking(false);
+                    let device = device.clone();
+                    let stop = accept_stop.clone();
+                    thread::spawn(move || serve_client(stream, device, stop));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(POLL_INTERVAL)
+                }
+                Err(e) => eprintln!("Device simulator accept failed: {}", e),
+            }
+        }
+    });
+
+    append_app_log(&format!("Device simulator listening on 127.0.0.1:{}", port));
+    *running = Some(RunningSimulator {
+        info: info.clone(),
+        stop,
+    });
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn stop_device_simulator(handle: State<SimulatorHandle>) -> Result<String, String> {
+    lock::ensure_unlocked()?;
+    let simulator = handle
+        .lock()
+        .map_err(|e| format!("Failed to lock simulator state: {}", e))?
+        .take();
+    match simulator {
+        Some(simulator) => {
+            simulator.stop.store(true, Ordering::Relaxed);
+            append_app_log("Device simulator stopped");
+            Ok(format!(
+                "Device simulator on port {} stopped",
+                simulator.info.port
+            ))
+        }
+        None => Ok("Device simulator is not running".to_string()),
+    }
+}
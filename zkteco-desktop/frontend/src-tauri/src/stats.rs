@@ -0,0 +1,186 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection};
+
+use crate::database::run_db;
+use crate::export::{device_filter, parse_range, ExportRange, RANGE_FILTER};
+
+// Dashboard aggregates computed in SQL so charts never need the raw punches
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PeriodStats {
+    period: String, // YYYY-MM-DD, or YYYY-Www for weekly grouping
+    punches: u64,
+    employees: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EmployeeStats {
+    user_id: String,
+    name: String,
+    days_present: u64,
+    punches: u64,
+    earliest_in: String,
+    latest_out: String,
+    // HH:MM averages over the days present
+    average_first_in: String,
+    average_last_out: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceVolume {
+    device_id: String,
+    punches: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttendanceStats {
+    from: String,
+    to: String,
+    group_by: String,
+    total_punches: u64,
+    periods: Vec<PeriodStats>,
+    employees: Vec<EmployeeStats>,
+    devices: Vec<DeviceVolume>,
+}
+
+fn clock(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round() as i64;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn period_stats(
+    connection: &Connection,
+    bind: (&str, &str, &Option<String>),
+    period: &str,
+) -> Result<Vec<PeriodStats>, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT {period} AS period, COUNT(*), COUNT(DISTINCT a.user_id)
+             FROM attendance_logs a
+             WHERE {filter}
+             GROUP BY period ORDER BY period",
+            period = period,
+            filter = RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    let rows = statement
+        .query_map(params![bind.0, bind.1, bind.2], |row| {
+            Ok(PeriodStats {
+                period: row.get(0)?,
+                punches: row.get(1)?,
+                employees: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance stats: {}", e))
+}
+
+fn employee_stats(
+    connection: &Connection,
+    bind: (&str, &str, &Option<String>),
+) -> Result<Vec<EmployeeStats>, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "WITH days AS (
+                SELECT a.user_id, substr(a.timestamp, 1, 10) AS day,
+                    MIN(time(a.timestamp)) AS first_in, MAX(time(a.timestamp)) AS last_out,
+                    COUNT(*) AS punches
+                FROM attendance_logs a
+                WHERE {}
+                GROUP BY a.user_id, day
+             )
+             SELECT d.user_id,
+                COALESCE((SELECT u.name FROM users u WHERE u.user_id = d.user_id LIMIT 1), ''),
+                COUNT(*), SUM(d.punches), MIN(d.first_in), MAX(d.last_out),
+                AVG(strftime('%s', '2000-01-01 ' || d.first_in) - strftime('%s', '2000-01-01')),
+                AVG(strftime('%s', '2000-01-01 ' || d.last_out) - strftime('%s', '2000-01-01'))
+             FROM days d
+             GROUP BY d.user_id
+             ORDER BY d.user_id",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    let rows = statement
+        .query_map(params![bind.0, bind.1, bind.2], |row| {
+            Ok(EmployeeStats {
+                user_id: row.get(0)?,
+                name: row.get(1)?,
+                days_present: row.get(2)?,
+                punches: row.get(3)?,
+                earliest_in: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                latest_out: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                average_first_in: row.get::<_, Option<f64>>(6)?.map(clock).unwrap_or_default(),
+                average_last_out: row.get::<_, Option<f64>>(7)?.map(clock).unwrap_or_default(),
+            })
+        })
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance stats: {}", e))
+}
+
+fn device_volumes(
+    connection: &Connection,
+    bind: (&str, &str, &Option<String>),
+) -> Result<Vec<DeviceVolume>, String> {
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT COALESCE(a.device_id, ''), COUNT(*) FROM attendance_logs a
+             WHERE {}
+             GROUP BY a.device_id ORDER BY COUNT(*) DESC",
+            RANGE_FILTER
+        ))
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    let rows = statement
+        .query_map(params![bind.0, bind.1, bind.2], |row| {
+            Ok(DeviceVolume {
+                device_id: row.get(0)?,
+                punches: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Failed to aggregate attendance: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read attendance stats: {}", e))
+}
+
+fn collect_stats(
+    connection: &Connection,
+    range: (NaiveDate, NaiveDate),
+    group_by: &str,
+    devices: &Option<String>,
+) -> Result<AttendanceStats, String> {
+    let period = match group_by {
+        "day" => "substr(a.timestamp, 1, 10)",
+        // Weeks start on Monday (SQLite's %W numbering, not ISO weeks)
+        "week" => "strftime('%Y-W%W', a.timestamp)",
+        other => {
+            return Err(format!(
+                "Unknown grouping '{}', expected day or week",
+                other
+            ))
+        }
+    };
+    let (from, to) = (range.0.to_string(), range.1.to_string());
+    let bind = (from.as_str(), to.as_str(), devices);
+    let periods = period_stats(connection, bind, period)?;
+    Ok(AttendanceStats {
+        total_punches: periods.iter().map(|p| p.punches).sum(),
+        periods,
+        employees: employee_stats(connection, bind)?,
+        devices: device_volumes(connection, bind)?,
+        from,
+        to,
+        group_by: group_by.to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_attendance_stats(
+    range: ExportRange,
+    group_by: Option<String>,
+    devices: Option<Vec<String>>,
+) -> Result<AttendanceStats, String> {
+    let range = parse_range(&range)?;
+    let group_by = group_by.unwrap_or_else(|| "day".to_string());
+    let devices = device_filter(&devices);
+    run_db(move |connection| collect_stats(connection, range, &group_by, &devices)).await
+}
@@ -0,0 +1,313 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::Connection;
+use tauri::State;
+
+use crate::backup::{apply_retention, default_backup_dir};
+use crate::database::{archive_db_path, open_read_write};
+use crate::encryption::unlock;
+use crate::lock;
+use crate::photos::{max_photo_bytes, photos_dir, run_cleanup as clean_photos};
+use crate::settings::SharedSettings;
+use crate::{append_app_log, resolve_app_data_dir, resolve_backend_db_path};
+
+// Storage wizard: analyze_storage reports what the app keeps on disk per category, run_cleanup
+// applies the selected actions and reports what each one gave back. Nothing is touched unless
+// its option is set.
+const APP_LOG: &str = "zkteco_app.log";
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StorageCategory {
+    name: String, // database, wal, logs, backups, archives, photos
+    bytes: u64,
+    files: usize,
+    paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StorageReport {
+    categories: Vec<StorageCategory>,
+    total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct CleanupOptions {
+    // Gzip zkteco_app.log into log_archive/ and start a fresh log
+    compress_logs: bool,
+    // Delete gzipped logs and protocol traces
+    delete_log_archives: bool,
+    // Keep only the newest N backups in the default backups folder
+    keep_backups: Option<usize>,
+    // VACUUM zkteco_archive.db
+    compact_archive: bool,
+    // Remove orphaned photos and trim the photo store to its cap
+    clean_photos: bool,
+    // Fold the WAL back into zkteco_app.db and truncate it
+    checkpoint_wal: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CleanupAction {
+    action: String,
+    reclaimed_bytes: u64,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CleanupReport {
+    actions: Vec<CleanupAction>,
+    reclaimed_bytes: u64,
+}
+
+fn log_archive_dir() -> PathBuf {
+    resolve_app_data_dir().join("log_archive")
+}
+
+fn traces_dir() -> PathBuf {
+    resolve_app_data_dir().join("protocol_traces")
+}
+
+// SQLite keeps the write-ahead log and shared memory index next to the database
+fn sidecar_files(database: &Path) -> Vec<PathBuf> {
+    ["-wal", "-shm"]
+        .iter()
+        .map(|suffix| {
+            let mut name = database.as_os_str().to_owned();
+            name.push(suffix);
+            PathBuf::from(name)
+        })
+        .collect()
+}
+
+// Total size and file count of files and directories (recursively)
+fn usage(paths: &[PathBuf]) -> (u64, usize) {
+    let mut total = (0, 0);
+    for path in paths {
+        let Ok(metadata) = fs::metadata(path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            let children: Vec<PathBuf> = fs::read_dir(path)
+                .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+                .unwrap_or_default();
+            let (bytes, files) = usage(&children);
+            total = (total.0 + bytes, total.1 + files);
+        } else {
+            total = (total.0 + metadata.len(), total.1 + 1);
+        }
+    }
+    total
+}
+
+fn log_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(resolve_app_data_dir())
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.push(log_archive_dir());
+    paths.push(traces_dir());
+    paths
+}
+
+fn category_paths(name: &str) -> Vec<PathBuf> {
+    let database = resolve_backend_db_path();
+    match name {
+        "database" => vec![database],
+        "wal" => sidecar_files(&database),
+        "logs" => log_paths(),
+        "backups" => vec![default_backup_dir()],
+        "archives" => {
+            let archive = archive_db_path();
+            let mut paths = sidecar_files(&archive);
+            paths.insert(0, archive);
+            paths
+        }
+        "photos" => vec![photos_dir()],
+        _ => Vec::new(),
+    }
+}
+
+const CATEGORIES: [&str; 6] = ["database", "wal", "logs", "backups", "archives", "photos"];
+
+fn category_bytes(name: &str) -> u64 {
+    usage(&category_paths(name)).0
+}
+
+fn analyze() -> StorageReport {
+    let categories: Vec<StorageCategory> = CATEGORIES
+        .iter()
+        .map(|name| {
+            let paths = category_paths(name);
+            let (bytes, files) = usage(&paths);
+            StorageCategory {
+                name: name.to_string(),
+                bytes,
+                files,
+                paths: paths
+                    .iter()
+                    .filter(|path| path.exists())
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect(),
+            }
+        })
+        .collect();
+    StorageReport {
+        total_bytes: categories.iter().map(|c| c.bytes).sum(),
+        categories,
+    }
+}
+
+fn compress_app_log() -> Result<(), String> {
+    let log = resolve_app_data_dir().join(APP_LOG);
+    if fs::metadata(&log).map(|m| m.len()).unwrap_or(0) == 0 {
+        return Ok(());
+    }
+    fs::create_dir_all(log_archive_dir())
+        .map_err(|e| format!("Failed to create log archive: {}", e))?;
+    let target = log_archive_dir().join(format!(
+        "zkteco_app_{}.log.gz",
+        Local::now().format("%Y%m%d_%H%M%S")
+    ));
+    let compressed = File::open(&log)
+        .and_then(|source| {
+            let mut encoder = GzEncoder::new(
+                BufWriter::new(File::create(&target)?),
+                Compression::default(),
+            );
+            io::copy(&mut BufReader::new(source), &mut encoder)?;
+            encoder.finish().map(drop)
+        })
+        .map_err(|e| format!("Failed to compress {}: {}", APP_LOG, e));
+    if let Err(err) = compressed {
+        let _ = fs::remove_file(&target);
+        return Err(err);
+    }
+    File::create(&log)
+        .map(drop)
+        .map_err(|e| format!("Failed to truncate {}: {}", APP_LOG, e))
+}
+
+fn delete_log_archives() -> Result<(), String> {
+    for dir in [log_archive_dir(), traces_dir()] {
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete {:?}: {}", dir, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn compact_archive() -> Result<(), String> {
+    let path = archive_db_path();
+    if !path.exists() {
+        return Ok(());
+    }
+    let connection =
+        Connection::open(&path).map_err(|e| format!("Failed to open attendance archive: {}", e))?;
+    unlock(&connection, &path)?;
+    connection
+        .execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")
+        .map_err(|e| format!("Failed to compact attendance archive: {}", e))
+}
+
+// The backend keeps running; a checkpoint it blocks is simply partial
+fn checkpoint_wal() -> Result<(), String> {
+    open_read_write()?
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| format!("Failed to checkpoint database: {}", e))
+}
+
+fn measured(
+    action: &str,
+    category: &str,
+    run: impl FnOnce() -> Result<(), String>,
+) -> CleanupAction {
+    let before = category_bytes(category);
+    let error = run().err();
+    if let Some(err) = &error {
+        eprintln!("Storage cleanup '{}' failed: {}", action, err);
+    }
+    CleanupAction {
+        action: action.to_string(),
+        reclaimed_bytes: before.saturating_sub(category_bytes(category)),
+        error,
+    }
+}
+
+fn run_blocking_actions(options: &CleanupOptions) -> Vec<CleanupAction> {
+    let mut actions = Vec::new();
+    if options.compress_logs {
+        actions.push(measured("compress_logs", "logs", compress_app_log));
+    }
+    if options.delete_log_archives {
+        actions.push(measured("delete_log_archives", "logs", delete_log_archives));
+    }
+    if let Some(keep) = options.keep_backups {
+        actions.push(measured("keep_backups", "backups", || {
+            apply_retention(keep);
+            Ok(())
+        }));
+    }
+    if options.compact_archive {
+        actions.push(measured("compact_archive", "archives", compact_archive));
+    }
+    if options.checkpoint_wal {
+        actions.push(measured("checkpoint_wal", "wal", checkpoint_wal));
+    }
+    actions
+}
+
+#[tauri::command]
+pub async fn analyze_storage() -> Result<StorageReport, String> {
+    tauri::async_runtime::spawn_blocking(analyze)
+        .await
+        .map_err(|e| format!("Storage analysis failed: {}", e))
+}
+
+#[tauri::command]
+pub async fn run_cleanup(
+    options: CleanupOptions,
+    settings: State<'_, SharedSettings>,
+) -> Result<CleanupReport, String> {
+    lock::ensure_unlocked()?;
+    let photo_cap = max_photo_bytes(&settings);
+    let blocking = options.clone();
+    let mut actions = tauri::async_runtime::spawn_blocking(move || run_blocking_actions(&blocking))
+        .await
+        .map_err(|e| format!("Storage cleanup failed: {}", e))?;
+    if options.clean_photos {
+        let before = category_bytes("photos");
+        let error = clean_photos(photo_cap).await.err();
+        actions.push(CleanupAction {
+            action: "clean_photos".to_string(),
+            reclaimed_bytes: before.saturating_sub(category_bytes("photos")),
+            error,
+        });
+    }
+
+    let report = CleanupReport {
+        reclaimed_bytes: actions.iter().map(|a| a.reclaimed_bytes).sum(),
+        actions,
+    };
+    append_app_log(&format!(
+        "Storage cleanup ({}) reclaimed {} bytes",
+        report
+            .actions
+            .iter()
+            .map(|a| a.action.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        report.reclaimed_bytes
+    ));
+    Ok(report)
+}
@@ -0,0 +1,267 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::{Emitter, State};
+
+use crate::backend_auth::backend_client;
+use crate::settings::{current_settings, SharedSettings};
+use crate::{
+    append_app_log, backend_base_url, current_backend_port, resolve_app_data_dir, BackendPort,
+};
+
+const SYNC_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SyncRecord {
+    pub timestamp: DateTime<Utc>,
+    pub record_count: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SyncState {
+    pub last_device_pull: Option<SyncRecord>,
+    pub last_upstream_push: Option<SyncRecord>,
+    // Counters from the last /attendance/stats poll, used to detect new activity
+    observed_total: Option<u64>,
+    observed_synced: Option<u64>,
+    tracking_since: DateTime<Utc>,
+    pull_alert_active: bool,
+    push_alert_active: bool,
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        SyncState {
+            last_device_pull: None,
+            last_upstream_push: None,
+            observed_total: None,
+            observed_synced: None,
+            tracking_since: Utc::now(),
+            pull_alert_active: false,
+            push_alert_active: false,
+        }
+    }
+}
+
+pub type SyncTracker = Arc<Mutex<SyncState>>;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncStatusReport {
+    last_device_pull: Option<SyncRecord>,
+    last_upstream_push: Option<SyncRecord>,
+    device_pull_stale: bool,
+    upstream_push_stale: bool,
+    alert_threshold_hours: u64,
+    tracking_since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct SyncAlert {
+    kind: String, // "device_pull", "upstream_push"
+    last_success: Option<DateTime<Utc>>,
+    threshold_hours: u64,
+    message: String,
+}
+
+#[derive(serde::Deserialize)]
+struct AttendanceStatsResponse {
+    stats: AttendanceStats,
+}
+
+#[derive(serde::Deserialize)]
+struct AttendanceStats {
+    synced: u64,
+    total: u64,
+}
+
+fn sync_state_path() -> PathBuf {
+    resolve_app_data_dir().join("sync_status.json")
+}
+
+pub fn load_sync_state() -> SyncState {
+    fs::read_to_string(sync_state_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_sync_state(state: &SyncState) {
+    match serde_json::to_string_pretty(state) {
+        Ok(content) => {
+            if let Err(err) = fs::write(sync_state_path(), content) {
+                eprintln!("Failed to persist sync status: {}", err);
+            }
+        }
+        Err(err) => eprintln!("Failed to serialize sync status: {}", err),
+    }
+}
+
+// A sync kind is stale when neither a success nor tracking start is within the threshold
+fn is_stale(
+    last: &Option<SyncRecord>,
+    tracking_since: DateTime<Utc>,
+    threshold_hours: u64,
+) -> bool {
+    let reference = last.as_ref().map(|r| r.timestamp).unwrap_or(tracking_since);
+    Utc::now().signed_duration_since(reference) > chrono::Duration::hours(threshold_hours as i64)
+}
+
+async fn fetch_attendance_stats(port: u16) -> Result<AttendanceStats, String> {
+    let client = backend_client(Duration::from_secs(10))?;
+    let response = client
+        .get(format!("{}/attendance/stats", backend_base_url(port)))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch attendance stats: {}", e))?;
+    response
+        .json::<AttendanceStatsResponse>()
+        .await
+        .map(|body| body.stats)
+        .map_err(|e| format!("Invalid attendance stats response: {}", e))
+}
+
+// Fold a fresh stats sample into the tracker; growth in total means records were pulled
+// from a device, growth in synced means records were pushed upstream
+fn record_stats_sample(state: &mut SyncState, stats: &AttendanceStats) {
+    let now = Utc::now();
+
+    if let Some(previous_total) = state.observed_total {
+        if stats.total > previous_total {
+            state.last_device_pull = Some(SyncRecord {
+                timestamp: now,
+                record_count: stats.total - previous_total,
+            });
+            state.pull_alert_active = false;
+        }
+    }
+
+    if let Some(previous_synced) = state.observed_synced {
+        if stats.synced > previous_synced {
+            state.last_upstream_push = Some(SyncRecord {
+                timestamp: now,
+                record_count: stats.synced - previous_synced,
+            });
+            state.push_alert_active = false;
+        }
+    }
+
+    state.observed_total = Some(stats.total);
+    state.observed_synced = Some(stats.synced);
+}
+
+fn collect_alerts(state: &mut SyncState, threshold_hours: u64) -> Vec<SyncAlert> {
+    let mut alerts = Vec::new();
+
+    if !state.pull_alert_active
+        && is_stale(
+            &state.last_device_pull,
+            state.tracking_since,
+            threshold_hours,
+        )
+    {
+        state.pull_alert_active = true;
+        alerts.push(SyncAlert {
+            kind: "device_pull".to_string(),
+            last_success: state.last_device_pull.as_ref().map(|r| r.timestamp),
+            threshold_hours,
+            message: format!(
+                "No attendance records have been pulled from devices in the last {} hours",
+                threshold_hours
+            ),
+        });
+    }
+
+    if !state.push_alert_active
+        && is_stale(
+            &state.last_upstream_push,
+            state.tracking_since,
+            threshold_hours,
+        )
+    {
+        state.push_alert_active = true;
+        alerts.push(SyncAlert {
+            kind: "upstream_push".to_string(),
+            last_success: state.last_upstream_push.as_ref().map(|r| r.timestamp),
+            threshold_hours,
+            message: format!(
+                "No attendance records have been pushed upstream in the last {} hours",
+                threshold_hours
+            ),
+        });
+    }
+
+    alerts
+}
+
+// Background task: poll backend stats, persist sync timestamps and raise stale-sync alerts
+pub fn spawn_sync_monitor(
+    app: tauri::AppHandle,
+    tracker: SyncTracker,
+    settings: SharedSettings,
+    backend_port: BackendPort,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(SYNC_POLL_INTERVAL).await;
+
+            let stats = fetch_attendance_stats(current_backend_port(&backend_port)).await;
+            let config = current_settings(&settings);
+
+            let alerts = match tracker.lock() {
+                Ok(mut state) => {
+                    if let Ok(stats) = &stats {
+                        record_stats_sample(&mut state, stats);
+                    }
+                    let alerts = if config.sync_alert_enabled {
+                        collect_alerts(&mut state, config.sync_alert_threshold_hours)
+                    } else {
+                        Vec::new()
+                    };
+                    save_sync_state(&state);
+                    alerts
+                }
+                Err(_) => Vec::new(),
+            };
+
+            for alert in alerts {
+                eprintln!("Sync alert: {}", alert.message);
+                append_app_log(&format!("Sync alert raised: {}", alert.message));
+                if let Err(err) = app.emit("sync-alert", &alert) {
+                    eprintln!("Failed to emit sync-alert event: {}", err);
+                }
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_sync_status(
+    tracker: State<SyncTracker>,
+    settings: State<SharedSettings>,
+) -> Result<SyncStatusReport, String> {
+    let threshold_hours = current_settings(&settings).sync_alert_threshold_hours;
+    let state = tracker
+        .lock()
+        .map_err(|e| format!("Failed to read sync status: {}", e))?;
+
+    Ok(SyncStatusReport {
+        last_device_pull: state.last_device_pull.clone(),
+        last_upstream_push: state.last_upstream_push.clone(),
+        device_pull_stale: is_stale(
+            &state.last_device_pull,
+            state.tracking_since,
+            threshold_hours,
+        ),
+        upstream_push_stale: is_stale(
+            &state.last_upstream_push,
+            state.tracking_since,
+            threshold_hours,
+        ),
+        alert_threshold_hours: threshold_hours,
+        tracking_since: state.tracking_since,
+    })
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use chrono::{
+    DateTime, Duration, Local, LocalResult, NaiveDateTime, Offset, SecondsFormat, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+use crate::registry::load_registry;
+
+// Terminals keep the wall-clock time of the site they are installed at. The IANA zone stored
+// for a device in the registry turns those readings into UTC; devices without one are read in
+// this PC's zone, which is what single-site installations expect.
+const LOCAL_ZONE: &str = "local";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NormalizedTime {
+    // RFC 3339 in UTC, e.g. "2024-05-01T01:02:11Z"
+    pub timestamp_utc: String,
+    // IANA name of the device's zone, or "local"
+    pub timezone: String,
+    // Offset in effect at the punch, e.g. "+07:00"
+    pub utc_offset: String,
+}
+
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("Unknown timezone '{}', expected an IANA name", name))
+}
+
+fn resolve<Z: TimeZone>(zone: &Z, local: &NaiveDateTime) -> Option<DateTime<Z>> {
+    match zone.from_local_datetime(local) {
+        LocalResult::Single(at) => Some(at),
+        // Hour repeated when clocks go back: take the first pass
+        LocalResult::Ambiguous(first, _) => Some(first),
+        // Hour skipped when clocks go forward: the terminal hadn't switched yet
+        LocalResult::None => zone
+            .from_local_datetime(&(*local + Duration::hours(1)))
+            .earliest(),
+    }
+}
+
+pub fn normalize(local: &NaiveDateTime, zone: Option<Tz>) -> Option<NormalizedTime> {
+    let (utc, offset, timezone) = match zone {
+        Some(tz) => {
+            let at = resolve(&tz, local)?;
+            (
+                at.with_timezone(&Utc),
+                at.offset().fix(),
+                tz.name().to_string(),
+            )
+        }
+        None => {
+            let at = resolve(&Local, local)?;
+            (
+                at.with_timezone(&Utc),
+                at.offset().fix(),
+                LOCAL_ZONE.to_string(),
+            )
+        }
+    };
+    Some(NormalizedTime {
+        timestamp_utc: utc.to_rfc3339_opts(SecondsFormat::Secs, true),
+        timezone,
+        utc_offset: offset.to_string(),
+    })
+}
+
+// Same for a stored "YYYY-MM-DD HH:MM:SS" (or ISO "T") timestamp
+pub fn normalize_stored(timestamp: &str, zone: Option<Tz>) -> Option<NormalizedTime> {
+    let value = timestamp.get(..19).unwrap_or(timestamp);
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .and_then(|local| normalize(&local, zone))
+}
+
+// Zones of the registered devices by device id, read from the registry file so background
+// jobs and exports don't need the managed state
+pub fn registered_zones() -> HashMap<String, Tz> {
+    load_registry()
+        .into_iter()
+        .filter_map(|device| {
+            let zone = device
+                .timezone
+                .as_deref()
+                .and_then(|name| name.parse().ok())?;
+            Some((device.id, zone))
+        })
+        .collect()
+}
+
+pub fn registered_zone(device_id: &str) -> Option<Tz> {
+    registered_zones().remove(device_id)
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use openssl::asn1::{Asn1Integer, Asn1Time};
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::x509::extension::{BasicConstraints, ExtendedKeyUsage, SubjectAlternativeName};
+use openssl::x509::{X509NameBuilder, X509};
+
+use crate::{append_app_log, resolve_app_data_dir};
+
+// HTTPS between the shell and the sidecar, for security policies that don't allow plain HTTP
+// even on loopback. With backend_tls_enabled the shell keeps a self-signed certificate for
+// 127.0.0.1/localhost in tls/, hands its files to the sidecar (ZKTECO_TLS_CERT/_KEY) and trusts
+// exactly that certificate for its own health checks and proxied requests.
+const CERT_FILE: &str = "backend_cert.pem";
+const KEY_FILE: &str = "backend_key.pem";
+const VALIDITY_DAYS: u32 = 825;
+// Certificates closer than this to expiry are replaced at the next backend start
+const RENEW_BEFORE_DAYS: u32 = 30;
+pub const CERT_ENV: &str = "ZKTECO_TLS_CERT";
+pub const KEY_ENV: &str = "ZKTECO_TLS_KEY";
+
+// Whether the running backend was started with TLS, and the certificate it serves
+static TLS_ACTIVE: AtomicBool = AtomicBool::new(false);
+static TRUSTED_CERT: Mutex<Option<Vec<u8>>> = Mutex::new(None);
+
+pub struct TlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TlsStatus {
+    active: bool,
+    cert_path: Option<String>,
+    fingerprint_sha256: Option<String>,
+    expires: Option<String>,
+}
+
+fn tls_dir() -> PathBuf {
+    resolve_app_data_dir().join("tls")
+}
+
+pub fn scheme() -> &'static str {
+    if TLS_ACTIVE.load(Ordering::Relaxed) {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+// PEM of the certificate the shell trusts, while TLS is active
+pub fn trusted_certificate() -> Option<Vec<u8>> {
+    if !TLS_ACTIVE.load(Ordering::Relaxed) {
+        return None;
+    }
+    TRUSTED_CERT.lock().ok().and_then(|cert| cert.clone())
+}
+
+fn openssl_error(e: openssl::error::ErrorStack) -> String {
+    format!("Failed to create backend certificate: {}", e)
+}
+
+fn generate() -> Result<(Vec<u8>, Vec<u8>), openssl::error::ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+    let mut name = X509NameBuilder::new()?;
+    name.append_entry_by_nid(Nid::COMMONNAME, "ZKTeco local backend")?;
+    let name = name.build();
+
+    let mut serial = BigNum::new()?;
+    serial.rand(127, MsbOption::MAYBE_ZERO, false)?;
+    let mut builder = X509::builder()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(Asn1Integer::from_bn(&serial)?.as_ref())?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(Asn1Time::days_from_now(0)?.as_ref())?;
+    builder.set_not_after(Asn1Time::days_from_now(VALIDITY_DAYS)?.as_ref())?;
+    builder.append_extension(BasicConstraints::new().critical().build()?)?;
+    builder.append_extension(ExtendedKeyUsage::new().server_auth().build()?)?;
+    let san = SubjectAlternativeName::new()
+        .ip("127.0.0.1")
+        .dns("localhost")
+        .build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(san)?;
+    builder.sign(&key, MessageDigest::sha256())?;
+    Ok((builder.build().to_pem()?, key.private_key_to_pem_pkcs8()?))
+}
+
+fn usable(cert_path: &Path) -> bool {
+    let Some(cert) = fs::read(cert_path)
+        .ok()
+        .and_then(|pem| X509::from_pem(&pem).ok())
+    else {
+        return false;
+    };
+    Asn1Time::days_from_now(RENEW_BEFORE_DAYS)
+        .map(|renew_at| cert.not_after() > renew_at)
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Err(err) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        eprintln!("Failed to restrict permissions of {:?}: {}", path, err);
+    }
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) {}
+
+// Certificate and key for the next backend start, created or renewed as needed
+pub fn prepare_certificate() -> Result<TlsFiles, String> {
+    let dir = tls_dir();
+    let files = TlsFiles {
+        cert_path: dir.join(CERT_FILE),
+        key_path: dir.join(KEY_FILE),
+    };
+    if !usable(&files.cert_path) || !files.key_path.exists() {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        let (cert, key) = generate().map_err(openssl_error)?;
+        fs::write(&files.key_path, key)
+            .map_err(|e| format!("Failed to save backend certificate key: {}", e))?;
+        restrict_permissions(&files.key_path);
+        fs::write(&files.cert_path, cert)
+            .map_err(|e| format!("Failed to save backend certificate: {}", e))?;
+        append_app_log(&format!(
+            "Created self-signed backend certificate at {:?}",
+            files.cert_path
+        ));
+    }
+    Ok(files)
+}
+
+// Record how the backend is being started; every later request follows this
+pub fn set_active(files: Option<&TlsFiles>) {
+    trust_certificate(files.map(|files| files.cert_path.as_path()));
+}
+
+// Same for a backend someone else started (the backend service) with this certificate
+pub fn trust_certificate(cert_path: Option<&Path>) {
+    let cert = cert_path.and_then(|path| fs::read(path).ok());
+    TLS_ACTIVE.store(cert.is_some(), Ordering::Relaxed);
+    if let Ok(mut guard) = TRUSTED_CERT.lock() {
+        *guard = cert;
+    }
+}
+
+// Before the launch-time health check: a backend left running by an earlier session serves
+// the certificate it was started with, so look for it over HTTPS when TLS is on
+pub fn trust_existing(enabled: bool) {
+    let cert_path = tls_dir().join(CERT_FILE);
+    trust_certificate((enabled && cert_path.exists()).then_some(cert_path.as_path()));
+}
+
+#[tauri::command]
+pub fn get_backend_tls_status() -> TlsStatus {
+    let cert = trusted_certificate().and_then(|pem| X509::from_pem(&pem).ok());
+    TlsStatus {
+        active: cert.is_some(),
+        cert_path: cert
+            .as_ref()
+            .map(|_| tls_dir().join(CERT_FILE).to_string_lossy().to_string()),
+        fingerprint_sha256: cert.as_ref().and_then(|cert| {
+            cert.digest(MessageDigest::sha256()).ok().map(|digest| {
+                digest
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(":")
+            })
+        }),
+        expires: cert.map(|cert| cert.not_after().to_string()),
+    }
+}
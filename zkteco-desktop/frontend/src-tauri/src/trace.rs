@@ -0,0 +1,167 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::resolve_app_data_dir;
+use crate::secrets::hex;
+
+// Debug tracing of the native protocol: when enabled every packet of a session (direction,
+// command, payload hex, timing) is written to its own JSONL file under protocol_traces/
+const MAX_TRACE_FILES: usize = 50;
+
+static TRACE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_trace_enabled(enabled: bool) {
+    TRACE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraceEntry {
+    elapsed_ms: u128,  // since the session was opened
+    delta_ms: u128,    // since the previous packet, i.e. the device response time for "rx"
+    direction: String, // "tx" or "rx"
+    command: u16,
+    session_id: u16,
+    reply_id: u16,
+    size: usize,
+    payload_hex: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TraceFile {
+    name: String,
+    size: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+pub struct SessionTrace {
+    file: File,
+    started: Instant,
+    last: Instant,
+}
+
+fn trace_dir() -> PathBuf {
+    resolve_app_data_dir().join("protocol_traces")
+}
+
+// Oldest trace files beyond MAX_TRACE_FILES are removed when a new session starts
+fn prune_traces(dir: &PathBuf) {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    if files.len() < MAX_TRACE_FILES {
+        return;
+    }
+    // Names start with the session timestamp, so they sort chronologically
+    files.sort();
+    for path in &files[..files.len() + 1 - MAX_TRACE_FILES] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+impl SessionTrace {
+    // None unless tracing is enabled (or the file can't be created)
+    pub fn open(peer: &str) -> Option<Self> {
+        if !TRACE_ENABLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        let dir = trace_dir();
+        if let Err(err) = fs::create_dir_all(&dir) {
+            eprintln!("Failed to create protocol trace directory: {}", err);
+            return None;
+        }
+        prune_traces(&dir);
+
+        let peer: String = peer
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let name = format!(
+            "{}_{}.jsonl",
+            Local::now().format("%Y%m%d_%H%M%S_%6f"),
+            peer
+        );
+        match File::create(dir.join(name)) {
+            Ok(file) => Some(SessionTrace {
+                file,
+                started: Instant::now(),
+                last: Instant::now(),
+            }),
+            Err(err) => {
+                eprintln!("Failed to create protocol trace file: {}", err);
+                None
+            }
+        }
+    }
+
+    // `payload` is the packet without the TCP header: [cmd][checksum][session][reply][data...]
+    pub fn record(&mut self, direction: &str, payload: &[u8]) {
+        if payload.len() < 8 {
+            return;
+        }
+        let now = Instant::now();
+        let word = |offset: usize| u16::from_le_bytes([payload[offset], payload[offset + 1]]);
+        let entry = TraceEntry {
+            elapsed_ms: now.duration_since(self.started).as_millis(),
+            delta_ms: now.duration_since(self.last).as_millis(),
+            direction: direction.to_string(),
+            command: word(0),
+            session_id: word(4),
+            reply_id: word(6),
+            size: payload.len() - 8,
+            payload_hex: hex(&payload[8..]),
+        };
+        self.last = now;
+        if let Ok(line) = serde_json::to_string(&entry) {
+            let _ = writeln!(self.file, "{}", line);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn list_protocol_traces() -> Result<Vec<TraceFile>, String> {
+    let entries = match fs::read_dir(trace_dir()) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to list protocol traces: {}", err)),
+    };
+    let mut files: Vec<TraceFile> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(TraceFile {
+                name: entry.file_name().to_string_lossy().to_string(),
+                size: metadata.len(),
+                modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            })
+        })
+        .collect();
+    // Most recent first
+    files.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(files)
+}
+
+// Packets of one traced session; defaults to the most recent trace
+#[tauri::command]
+pub fn get_protocol_trace(name: Option<String>) -> Result<Vec<TraceEntry>, String> {
+    let name = match name {
+        Some(name) => name,
+        None => match list_protocol_traces()?.into_iter().next() {
+            Some(latest) => latest.name,
+            None => return Ok(Vec::new()),
+        },
+    };
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid trace name: {}", name));
+    }
+    let content = fs::read_to_string(trace_dir().join(&name))
+        .map_err(|e| format!("Failed to read protocol trace {}: {}", name, e))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
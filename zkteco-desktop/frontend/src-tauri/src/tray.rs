@@ -0,0 +1,120 @@
+use tauri::image::Image;
+use tauri::menu::MenuItem;
+use tauri::tray::TrayIcon;
+use tauri::{AppHandle, Wry};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::append_app_log;
+use crate::database::{attendance_badges, run_db, AttendanceBadges};
+use crate::monitor::{BackendState, DeviceState, MonitorBus, MonitorEvent};
+
+pub const TRAY_ID: &str = "main";
+const TRAY_TITLE: &str = "ZKTeco Desktop";
+
+// Menu entries whose label/enablement follow the monitored state
+pub struct TrayStatusItems {
+    pub status: MenuItem<Wry>,
+    pub restart: MenuItem<Wry>,
+}
+
+fn backend_label(state: &BackendState) -> String {
+    match state {
+        BackendState::Starting => "Backend: starting...".to_string(),
+        BackendState::Running => "Backend: running".to_string(),
+        BackendState::Stopped => "Backend: stopped".to_string(),
+        BackendState::Failed(_) => "Backend: failed".to_string(),
+    }
+}
+
+fn device_label(devices: &DeviceState) -> String {
+    format!("{}/{} devices online", devices.online, devices.total)
+}
+
+// Greyed out copy of the app icon, shown while the backend is not serving requests
+fn dimmed_icon(icon: &Image<'_>) -> Image<'static> {
+    let rgba = icon
+        .rgba()
+        .chunks(4)
+        .flat_map(|px| {
+            let grey = ((px[0] as u32 * 30 + px[1] as u32 * 59 + px[2] as u32 * 11) / 100) as u8;
+            [grey, grey, grey, px[3] / 2]
+        })
+        .collect();
+    Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+fn apply_state(
+    app: &AppHandle,
+    tray: &TrayIcon,
+    items: &TrayStatusItems,
+    backend: &BackendState,
+    devices: Option<&DeviceState>,
+    badges: Option<&AttendanceBadges>,
+) {
+    let mut tooltip = format!("{} - {}", TRAY_TITLE, backend_label(backend));
+    let mut status = backend_label(backend);
+    if let (BackendState::Running, Some(devices)) = (backend, devices) {
+        tooltip.push_str(&format!(", {}", device_label(devices)));
+        status = format!("{} ({})", status, device_label(devices));
+        if !devices.offline.is_empty() {
+            tooltip.push_str(&format!("\nOffline: {}", devices.offline.join(", ")));
+        }
+    }
+    // Read straight from the database, so still shown while the backend is down
+    if let Some(badges) = badges {
+        tooltip.push_str(&format!(
+            "\nToday: {} punches, {} pending sync",
+            badges.today, badges.pending
+        ));
+    }
+
+    let _ = tray.set_tooltip(Some(&tooltip));
+    let _ = items.status.set_text(status);
+    let _ = items
+        .restart
+        .set_enabled(!matches!(backend, BackendState::Starting));
+
+    if let Some(icon) = app.default_window_icon() {
+        let icon = if matches!(backend, BackendState::Running) {
+            icon.clone().to_owned()
+        } else {
+            dimmed_icon(icon)
+        };
+        let _ = tray.set_icon(Some(icon));
+    }
+}
+
+// The only place that mutates the tray after startup; everything else publishes on the monitor bus
+pub fn spawn_tray_updater(app: AppHandle, bus: &MonitorBus, items: TrayStatusItems) {
+    let mut receiver = bus.subscribe();
+    tauri::async_runtime::spawn(async move {
+        let mut backend = BackendState::Starting;
+        let mut devices: Option<DeviceState> = None;
+
+        loop {
+            match receiver.recv().await {
+                Ok(MonitorEvent::Backend(state)) => backend = state,
+                Ok(MonitorEvent::Devices(state)) => devices = Some(state),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    append_app_log(&format!("Tray updater skipped {} monitor events", skipped));
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            }
+
+            let badges = run_db(attendance_badges).await.ok();
+            match app.tray_by_id(TRAY_ID) {
+                Some(tray) => apply_state(
+                    &app,
+                    &tray,
+                    &items,
+                    &backend,
+                    devices.as_ref(),
+                    badges.as_ref(),
+                ),
+                None => eprintln!("Tray icon {} not found - skipping status update", TRAY_ID),
+            }
+        }
+    });
+}
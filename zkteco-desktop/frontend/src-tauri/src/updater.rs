@@ -0,0 +1,403 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+use crate::audit::audited;
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::{append_app_log, lock};
+
+// In-app updates through tauri-plugin-updater. Each release channel ("stable" or "beta") has
+// its own manifest: update_endpoint, or the endpoint built in with ZTKAPP_UPDATE_ENDPOINT, with
+// {{channel}} filled in next to the plugin's own {{target}}, {{arch}} and {{current_version}}.
+// Packages are verified against the minisign key built in as ZTKAPP_UPDATER_PUBKEY, so a build
+// without one never installs anything. A downloaded update is installed either right away
+// (restarting the app) or when the app next quits, after the backend has been shut down.
+const CHANNELS: [&str; 2] = ["stable", "beta"];
+const BUILT_IN_ENDPOINT: Option<&str> = option_env!("ZTKAPP_UPDATE_ENDPOINT");
+const PUBKEY: Option<&str> = option_env!("ZTKAPP_UPDATER_PUBKEY");
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+const STARTUP_DELAY: Duration = Duration::from_secs(60);
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+// Progress events at most every this many bytes, so a fast download doesn't flood the webview
+const PROGRESS_STEP: u64 = 256 * 1024;
+
+static STATUS: Mutex<Option<UpdateStatus>> = Mutex::new(None);
+static AVAILABLE: Mutex<Option<Update>> = Mutex::new(None);
+static READY: Mutex<Option<ReadyUpdate>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct UpdateStatus {
+    channel: String,
+    current_version: String,
+    last_checked_at: Option<DateTime<Utc>>,
+    available: Option<AvailableUpdate>,
+    // "idle", "available", "downloading", "ready" or "scheduled" (installs on quit)
+    state: String,
+    downloaded_bytes: u64,
+    total_bytes: Option<u64>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AvailableUpdate {
+    version: String,
+    notes: Option<String>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct DownloadProgress {
+    version: String,
+    downloaded_bytes: u64,
+    total_bytes: Option<u64>,
+}
+
+struct ReadyUpdate {
+    update: Update,
+    bytes: Vec<u8>,
+    install_on_quit: bool,
+    restart: bool,
+}
+
+fn update_status(f: impl FnOnce(&mut UpdateStatus)) {
+    if let Ok(mut guard) = STATUS.lock() {
+        f(guard.get_or_insert_with(|| UpdateStatus {
+            state: "idle".to_string(),
+            ..Default::default()
+        }));
+    }
+}
+
+// Also checks backend binaries in sidecar_update.rs
+pub fn pubkey() -> Option<&'static str> {
+    PUBKEY.map(str::trim).filter(|key| !key.is_empty())
+}
+
+// Registered with the other plugins in run()
+pub fn plugin() -> tauri::plugin::TauriPlugin<tauri::Wry, tauri_plugin_updater::Config> {
+    tauri_plugin_updater::Builder::new()
+        .pubkey(pubkey().unwrap_or_default())
+        .build()
+}
+
+fn channel(settings: &ShellSettings) -> Result<String, String> {
+    let channel = settings.update_channel.trim().to_ascii_lowercase();
+    if CHANNELS.contains(&channel.as_str()) {
+        Ok(channel)
+    } else {
+        Err(format!(
+            "Unknown update channel '{}'; use stable or beta",
+            settings.update_channel
+        ))
+    }
+}
+
+fn endpoint(settings: &ShellSettings, channel: &str) -> Result<tauri::Url, String> {
+    let template = settings
+        .update_endpoint
+        .as_deref()
+        .or(BUILT_IN_ENDPOINT)
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .ok_or("No update server is configured")?;
+    let url = template.replace("{{channel}}", channel);
+    tauri::Url::parse(&url).map_err(|_| format!("'{}' is not a valid update endpoint", url))
+}
+
+fn published_at(update: &Update) -> Option<DateTime<Utc>> {
+    update
+        .date
+        .and_then(|date| DateTime::from_timestamp(date.unix_timestamp(), 0))
+}
+
+async fn check(app: &AppHandle, settings: &ShellSettings) -> Result<Option<Update>, String> {
+    let channel = channel(settings)?;
+    if pubkey().is_none() {
+        return Err("This build has no update signing key; install updates by hand".to_string());
+    }
+    let updater = app
+        .updater_builder()
+        .endpoints(vec![endpoint(settings, &channel)?])
+        .and_then(|builder| builder.timeout(CHECK_TIMEOUT).build())
+        .map_err(|e| format!("Failed to set up the updater: {}", e))?;
+    let result = updater.check().await;
+    let now = Utc::now();
+    update_status(|status| {
+        status.channel = channel.clone();
+        status.current_version = app.package_info().version.to_string();
+        status.last_checked_at = Some(now);
+    });
+    let update = match result {
+        Ok(update) => update,
+        Err(err) => {
+            let err = format!("Update check failed: {}", err);
+            update_status(|status| status.last_error = Some(err.clone()));
+            return Err(err);
+        }
+    };
+
+    let announced = update.as_ref().map(|update| AvailableUpdate {
+        version: update.version.clone(),
+        notes: update.body.clone(),
+        published_at: published_at(update),
+    });
+    let mut newly_found = false;
+    update_status(|status| {
+        newly_found = announced.as_ref().is_some_and(|found| {
+            status.available.as_ref().map(|known| &known.version) != Some(&found.version)
+        });
+        status.last_error = None;
+        // An update that is downloaded or scheduled stays so until it is installed
+        if !["ready", "scheduled", "downloading"].contains(&status.state.as_str()) || newly_found {
+            status.state = if announced.is_some() {
+                "available"
+            } else {
+                "idle"
+            }
+            .to_string();
+        }
+        status.available = announced.clone();
+    });
+    if let Ok(mut available) = AVAILABLE.lock() {
+        *available = update.clone();
+    }
+    if let Some(found) = announced.filter(|_| newly_found) {
+        append_app_log(&format!(
+            "Update {} is available on the {} channel",
+            found.version, channel
+        ));
+        if let Err(err) = app.emit("update-available", &found) {
+            eprintln!("Failed to emit update-available event: {}", err);
+        }
+    }
+    Ok(update)
+}
+
+// Check on startup and then every few hours while update_auto_check is on
+pub fn spawn_update_checker(app: AppHandle, settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        loop {
+            let config = current_settings(&settings);
+            if config.update_auto_check {
+                if let Err(err) = check(&app, &config).await {
+                    eprintln!("Automatic update check failed: {}", err);
+                }
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+// Called on the way out of the app, once the backend has stopped: installs an update that was
+// scheduled for quit (or asked for "now") and relaunches the app when a restart was asked for.
+// Windows installers exit the process themselves and relaunch the app when they finish.
+pub fn install_on_exit(app: &AppHandle) {
+    let Some(ReadyUpdate {
+        update,
+        bytes,
+        restart,
+        ..
+    }) = READY
+        .lock()
+        .ok()
+        .and_then(|mut ready| ready.take_if(|ready| ready.install_on_quit))
+    else {
+        return;
+    };
+    append_app_log(&format!("Installing update {}", update.version));
+    if let Err(err) = update.install(&bytes) {
+        eprintln!("Failed to install update: {}", err);
+        append_app_log(&format!(
+            "Failed to install update {}: {}",
+            update.version, err
+        ));
+        return;
+    }
+    if restart {
+        tauri::process::restart(&app.env());
+    }
+}
+
+#[tauri::command]
+pub async fn check_for_updates(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<UpdateStatus, String> {
+    check(&app, &current_settings(&settings)).await?;
+    Ok(snapshot(&app, &settings))
+}
+
+// Download and verify the update found by the last check; progress arrives as
+// "update-download-progress" events and completion as "update-ready"
+#[tauri::command]
+pub async fn download_update(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<UpdateStatus, String> {
+    lock::ensure_unlocked()?;
+    let update = AVAILABLE
+        .lock()
+        .ok()
+        .and_then(|available| available.clone())
+        .ok_or("No update is available; check for updates first")?;
+    let already = READY.lock().ok().is_some_and(|ready| {
+        ready
+            .as_ref()
+            .is_some_and(|r| r.update.version == update.version)
+    });
+    if already {
+        return Ok(snapshot(&app, &settings));
+    }
+
+    update_status(|status| {
+        status.state = "downloading".to_string();
+        status.downloaded_bytes = 0;
+        status.total_bytes = None;
+    });
+    let version = update.version.clone();
+    let (mut downloaded, mut reported) = (0u64, 0u64);
+    let result = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                update_status(|status| {
+                    status.downloaded_bytes = downloaded;
+                    status.total_bytes = total;
+                });
+                if downloaded - reported >= PROGRESS_STEP || Some(downloaded) == total {
+                    reported = downloaded;
+                    let _ = app.emit(
+                        "update-download-progress",
+                        DownloadProgress {
+                            version: version.clone(),
+                            downloaded_bytes: downloaded,
+                            total_bytes: total,
+                        },
+                    );
+                }
+            },
+            || {},
+        )
+        .await;
+    let bytes = match result {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            let err = format!("Failed to download update {}: {}", version, err);
+            update_status(|status| {
+                status.state = "available".to_string();
+                status.last_error = Some(err.clone());
+            });
+            append_app_log(&err);
+            return Err(err);
+        }
+    };
+
+    if let Ok(mut ready) = READY.lock() {
+        *ready = Some(ReadyUpdate {
+            update,
+            bytes,
+            install_on_quit: false,
+            restart: false,
+        });
+    }
+    update_status(|status| {
+        status.state = "ready".to_string();
+        status.last_error = None;
+    });
+    append_app_log(&format!("Downloaded update {}", version));
+    if let Err(err) = app.emit("update-ready", &version) {
+        eprintln!("Failed to emit update-ready event: {}", err);
+    }
+    Ok(snapshot(&app, &settings))
+}
+
+// when: "now" quits, installs and relaunches; "on_quit" (default) installs the next time the
+// app is closed, so a clocking station isn't interrupted mid-shift
+#[tauri::command]
+pub fn install_update(
+    app: AppHandle,
+    when: Option<String>,
+    settings: State<'_, SharedSettings>,
+) -> Result<UpdateStatus, String> {
+    lock::ensure_unlocked()?;
+    let when = when.unwrap_or_else(|| "on_quit".to_string());
+    let result = (|| {
+        let now = match when.as_str() {
+            "now" => true,
+            "on_quit" => false,
+            other => {
+                return Err(format!(
+                    "Unknown install time '{}'; use now or on_quit",
+                    other
+                ))
+            }
+        };
+        let version = {
+            let mut ready = READY
+                .lock()
+                .map_err(|_| "Update state is unavailable".to_string())?;
+            let ready = ready.as_mut().ok_or("No update has been downloaded yet")?;
+            ready.install_on_quit = true;
+            ready.restart = now;
+            ready.update.version.clone()
+        };
+        update_status(|status| status.state = "scheduled".to_string());
+        append_app_log(&format!(
+            "Update {} will be installed {}",
+            version,
+            if now { "now" } else { "when the app quits" }
+        ));
+        if now {
+            // Goes through ExitRequested, which stops the backend before install_on_exit
+            app.exit(0);
+        }
+        Ok(version)
+    })();
+    audited("install_update", &when, result)?;
+    Ok(snapshot(&app, &settings))
+}
+
+// Keep a downloaded update without installing it on quit
+#[tauri::command]
+pub fn cancel_scheduled_update(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<UpdateStatus, String> {
+    lock::ensure_unlocked()?;
+    if let Ok(mut ready) = READY.lock() {
+        if let Some(ready) = ready.as_mut() {
+            ready.install_on_quit = false;
+            ready.restart = false;
+            update_status(|status| status.state = "ready".to_string());
+        }
+    }
+    Ok(snapshot(&app, &settings))
+}
+
+fn snapshot(app: &AppHandle, settings: &SharedSettings) -> UpdateStatus {
+    let mut status = STATUS
+        .lock()
+        .ok()
+        .and_then(|status| status.clone())
+        .unwrap_or_else(|| UpdateStatus {
+            state: "idle".to_string(),
+            ..Default::default()
+        });
+    status.current_version = app.package_info().version.to_string();
+    if status.channel.is_empty() {
+        status.channel = current_settings(settings).update_channel;
+    }
+    status
+}
+
+#[tauri::command]
+pub fn get_update_status(
+    app: AppHandle,
+    settings: State<'_, SharedSettings>,
+) -> Result<UpdateStatus, String> {
+    Ok(snapshot(&app, &settings))
+}
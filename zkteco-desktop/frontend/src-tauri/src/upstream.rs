@@ -0,0 +1,394 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Notify;
+
+use crate::database::run_db;
+use crate::lock;
+use crate::secrets::upstream_token;
+use crate::settings::{current_settings, SharedSettings, ShellSettings};
+use crate::timezones::{normalize_stored, registered_zones, NormalizedTime};
+use crate::{append_app_log, resolve_app_data_dir};
+
+// Pushes new attendance to the configured HR endpoint independently of the backend. New rows
+// are copied from zkteco_app.db into a disk-backed outbox (upstream_outbox.jsonl) and sent in
+// batches; a batch only leaves the outbox once the endpoint accepted it, so records collected
+// while offline survive restarts. Delivery is at-least-once: receivers dedupe on the record id.
+const MIN_TICK: Duration = Duration::from_secs(5);
+const RETRY_BASE_SECS: u64 = 5;
+const RETRY_MAX_SECS: u64 = 30 * 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const COLLECT_LIMIT: u32 = 5000;
+
+static OUTBOX_LOCK: Mutex<()> = Mutex::new(());
+// Wakes the engine before its next tick when a flush is requested
+static WAKE: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UpstreamRecord {
+    id: i64,
+    user_id: String,
+    device_id: Option<String>,
+    serial_number: Option<String>,
+    timestamp: String,
+    method: i64,
+    action: i64,
+    // Absent on records queued before timezones were tracked
+    #[serde(flatten, default)]
+    normalized: Option<NormalizedTime>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct UpstreamState {
+    // Highest attendance id copied into the outbox; starts at the newest record on first run
+    cursor: Option<i64>,
+    attempts: u32,
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    delivered: u64,
+    rejected: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UpstreamStatus {
+    enabled: bool,
+    endpoint: Option<String>,
+    queued: usize,
+    cursor: Option<i64>,
+    attempts: u32,
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    delivered: u64,
+    rejected: u64,
+}
+
+// Payload of "upstream-sync", emitted after every delivery attempt
+#[derive(Debug, Clone, serde::Serialize)]
+struct UpstreamEvent {
+    success: bool,
+    sent: usize,
+    queued: usize,
+    error: Option<String>,
+}
+
+enum Delivery {
+    Accepted,
+    // 4xx other than 408/429: retrying the same payload won't help
+    Rejected(String),
+    Failed(String),
+}
+
+fn outbox_path() -> PathBuf {
+    resolve_app_data_dir().join("upstream_outbox.jsonl")
+}
+
+fn rejected_path() -> PathBuf {
+    resolve_app_data_dir().join("upstream_rejected.jsonl")
+}
+
+fn state_path() -> PathBuf {
+    resolve_app_data_dir().join("upstream_state.json")
+}
+
+fn load_state() -> UpstreamState {
+    fs::read_to_string(state_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &UpstreamState) {
+    let written = serde_json::to_string_pretty(state)
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(state_path(), content).map_err(|e| e.to_string()));
+    if let Err(err) = written {
+        eprintln!("Failed to save upstream sync state: {}", err);
+    }
+}
+
+fn read_outbox() -> Vec<UpstreamRecord> {
+    fs::read_to_string(outbox_path())
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn append_lines(path: &PathBuf, records: &[UpstreamRecord]) -> Result<(), String> {
+    let mut content = String::new();
+    for record in records {
+        let line = serde_json::to_string(record)
+            .map_err(|e| format!("Failed to serialize upstream record: {}", e))?;
+        content.push_str(&line);
+        content.push('\n');
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_data())
+        .map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+// Drop the first `count` records; the rest is written to a temporary file and swapped in
+fn remove_delivered(count: usize) -> Result<(), String> {
+    let mut records = read_outbox();
+    let remaining = records.split_off(count.min(records.len()));
+    let staging = outbox_path().with_extension("jsonl.tmp");
+    let _ = fs::remove_file(&staging);
+    append_lines(&staging, &remaining)?;
+    fs::rename(&staging, outbox_path()).map_err(|e| format!("Failed to update outbox: {}", e))
+}
+
+fn backoff(attempts: u32) -> chrono::Duration {
+    let secs = RETRY_BASE_SECS
+        .saturating_mul(1 << attempts.min(16))
+        .min(RETRY_MAX_SECS);
+    chrono::Duration::seconds(secs as i64)
+}
+
+// Copy attendance newer than the cursor into the outbox; returns how many were queued
+async fn collect(state: &mut UpstreamState) -> Result<usize, String> {
+    let Some(cursor) = state.cursor else {
+        let newest: i64 = run_db(|connection| {
+            connection
+                .query_row(
+                    "SELECT COALESCE(MAX(id), 0) FROM attendance_logs",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to read attendance: {}", e))
+        })
+        .await?;
+        state.cursor = Some(newest);
+        save_state(state);
+        return Ok(0);
+    };
+
+    let zones = registered_zones();
+    let records = run_db(move |connection| {
+        let mut statement = connection
+            .prepare(
+                "SELECT id, user_id, device_id, serial_number, timestamp, method, action
+                 FROM attendance_logs WHERE id > ?1 ORDER BY id LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        let rows = statement
+            .query_map(params![cursor, COLLECT_LIMIT], |row| {
+                let device_id: Option<String> = row.get(2)?;
+                let timestamp: String = row.get(4)?;
+                let zone = device_id.as_ref().and_then(|id| zones.get(id)).copied();
+                Ok(UpstreamRecord {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    normalized: normalize_stored(&timestamp, zone),
+                    device_id,
+                    serial_number: row.get(3)?,
+                    timestamp,
+                    method: row.get(5)?,
+                    action: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to read attendance: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read attendance row: {}", e))
+    })
+    .await?;
+    let Some(last) = records.last() else {
+        return Ok(0);
+    };
+
+    let newest = last.id;
+    {
+        let _guard = OUTBOX_LOCK.lock();
+        append_lines(&outbox_path(), &records)?;
+    }
+    state.cursor = Some(newest);
+    save_state(state);
+    Ok(records.len())
+}
+
+async fn deliver(endpoint: &str, batch: &[UpstreamRecord]) -> Delivery {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(err) => return Delivery::Failed(format!("Failed to create HTTP client: {}", err)),
+    };
+    let mut request = client
+        .post(endpoint)
+        .json(&serde_json::json!({ "records": batch }));
+    if let Some(token) = upstream_token() {
+        request = request.bearer_auth(token);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Delivery::Accepted,
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("Endpoint returned {}: {}", status, body.trim());
+            if status.is_client_error() && status.as_u16() != 408 && status.as_u16() != 429 {
+                Delivery::Rejected(message)
+            } else {
+                Delivery::Failed(message)
+            }
+        }
+        Err(err) => Delivery::Failed(format!("Failed to reach upstream endpoint: {}", err)),
+    }
+}
+
+// Send the oldest batch in the outbox; returns how many records left it
+async fn drain_batch(
+    app: &AppHandle,
+    config: &ShellSettings,
+    endpoint: &str,
+    state: &mut UpstreamState,
+) -> usize {
+    let batch: Vec<UpstreamRecord> = {
+        let _guard = OUTBOX_LOCK.lock();
+        read_outbox()
+            .into_iter()
+            .take(config.upstream_batch_size.max(1))
+            .collect()
+    };
+    if batch.is_empty() {
+        return 0;
+    }
+
+    let delivery = deliver(endpoint, &batch).await;
+    let (sent, error) = match delivery {
+        Delivery::Accepted => {
+            state.delivered += batch.len() as u64;
+            state.last_success_at = Some(Utc::now());
+            (batch.len(), None)
+        }
+        Delivery::Rejected(err) => {
+            // Parked for inspection so one bad batch doesn't block everything behind it
+            if let Err(write_err) = append_lines(&rejected_path(), &batch) {
+                eprintln!("{}", write_err);
+            }
+            state.rejected += batch.len() as u64;
+            append_app_log(&format!(
+                "Upstream endpoint rejected {} attendance records: {}",
+                batch.len(),
+                err
+            ));
+            (batch.len(), Some(err))
+        }
+        Delivery::Failed(err) => (0, Some(err)),
+    };
+
+    if sent > 0 {
+        let _guard = OUTBOX_LOCK.lock();
+        if let Err(err) = remove_delivered(sent) {
+            eprintln!("{}", err);
+        }
+    }
+    match (&error, sent) {
+        (Some(err), 0) => {
+            state.next_attempt_at = Some(Utc::now() + backoff(state.attempts));
+            state.attempts += 1;
+            if state.attempts == 1 {
+                append_app_log(&format!("Upstream sync failed, retrying: {}", err));
+            }
+        }
+        _ => {
+            state.attempts = 0;
+            state.next_attempt_at = None;
+        }
+    }
+    state.last_error = error.clone();
+    save_state(state);
+
+    let event = UpstreamEvent {
+        success: error.is_none(),
+        sent,
+        queued: read_outbox().len(),
+        error,
+    };
+    if let Err(err) = app.emit("upstream-sync", &event) {
+        eprintln!("Failed to emit upstream-sync event: {}", err);
+    }
+    sent
+}
+
+async fn run_cycle(app: &AppHandle, config: &ShellSettings, endpoint: &str) {
+    let mut state = load_state();
+    if let Err(err) = collect(&mut state).await {
+        eprintln!("Upstream sync could not read attendance: {}", err);
+    }
+    if state.next_attempt_at.is_some_and(|at| at > Utc::now()) {
+        return;
+    }
+    // Keep going while full batches are accepted
+    loop {
+        let sent = drain_batch(app, config, endpoint, &mut state).await;
+        if sent < config.upstream_batch_size.max(1) || state.attempts > 0 {
+            break;
+        }
+    }
+}
+
+pub fn spawn_upstream_sync(app: AppHandle, settings: SharedSettings) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = current_settings(&settings);
+            let interval = Duration::from_secs(config.upstream_interval_seconds).max(MIN_TICK);
+            let _ = tokio::time::timeout(interval, WAKE.notified()).await;
+
+            let config = current_settings(&settings);
+            let endpoint = match config.upstream_endpoint.as_deref() {
+                Some(endpoint) if config.upstream_sync_enabled && !endpoint.is_empty() => {
+                    endpoint.to_string()
+                }
+                _ => continue,
+            };
+            run_cycle(&app, &config, &endpoint).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_upstream_status(settings: State<SharedSettings>) -> Result<UpstreamStatus, String> {
+    let config = current_settings(&settings);
+    let state = load_state();
+    let queued = {
+        let _guard = OUTBOX_LOCK.lock();
+        read_outbox().len()
+    };
+    Ok(UpstreamStatus {
+        enabled: config.upstream_sync_enabled,
+        endpoint: config.upstream_endpoint,
+        queued,
+        cursor: state.cursor,
+        attempts: state.attempts,
+        next_attempt_at: state.next_attempt_at,
+        last_success_at: state.last_success_at,
+        last_error: state.last_error,
+        delivered: state.delivered,
+        rejected: state.rejected,
+    })
+}
+
+// Retry now instead of waiting out the backoff
+#[tauri::command]
+pub fn flush_upstream_queue() -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let mut state = load_state();
+    state.next_attempt_at = None;
+    save_state(&state);
+    WAKE.notify_one();
+    Ok(())
+}
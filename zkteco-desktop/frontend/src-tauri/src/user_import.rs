@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::Duration;
+
+use tauri::State;
+
+use crate::backend_auth::backend_client;
+use crate::devices::{run_native, NATIVE_TIMEOUT};
+use crate::lock;
+use crate::path_policy::{check_path, Access};
+use crate::registry::{lookup_device, DeviceEntry, DeviceRegistry};
+use crate::zk::DeviceUser;
+use crate::{append_app_log, backend_base_url, current_backend_port, BackendPort};
+
+// Employee CSV import: every row is validated first and nothing is written unless the whole
+// file is clean. Valid files go to the backend's /users/import and, optionally, straight onto
+// selected terminals over the native protocol.
+pub const MAX_USER_ID_LEN: usize = 23; // 24 byte field incl. NUL
+const MAX_NAME_LEN: usize = 23;
+const MULTIPART_BOUNDARY: &str = "----ztkapp-user-import";
+
+// CSV header for each user field; defaults to the field name itself
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub user_id: String,
+    pub name: String,
+    pub card: Option<String>,
+    pub privilege: Option<String>,
+    pub password: Option<String>,
+    pub group_id: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        ColumnMapping {
+            user_id: "user_id".to_string(),
+            name: "name".to_string(),
+            card: Some("card".to_string()),
+            privilege: Some("privilege".to_string()),
+            password: Some("password".to_string()),
+            group_id: Some("group_id".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    // Only validate and report, don't write anything
+    pub dry_run: bool,
+    // Device the users belong to in the backend; the backend's active device when absent
+    pub serial_number: Option<String>,
+    // Terminals to write the imported users to directly
+    pub push_device_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RowIssue {
+    row: usize, // 1-based line number in the file, header included
+    field: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DevicePushResult {
+    device_id: String,
+    written: usize,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ImportReport {
+    total_rows: usize,
+    valid_rows: usize,
+    errors: Vec<RowIssue>,
+    warnings: Vec<RowIssue>,
+    committed: bool,
+    backend_response: Option<serde_json::Value>,
+    pushed: Vec<DevicePushResult>,
+}
+
+// Minimal RFC 4180 reader: quoted fields may contain commas, quotes ("") and line breaks
+pub fn parse_csv(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(ch),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+fn parse_privilege(value: &str) -> Option<u8> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" | "0" | "user" => Some(0),
+        "14" | "admin" => Some(14),
+        _ => None,
+    }
+}
+
+// Validate every row; users are only returned for a file without errors
+fn validate_rows(
+    rows: &[Vec<String>],
+    mapping: &ColumnMapping,
+    report: &mut ImportReport,
+) -> Vec<DeviceUser> {
+    let Some((header, data)) = rows.split_first() else {
+        report.errors.push(RowIssue {
+            row: 1,
+            field: String::new(),
+            message: "The file is empty".to_string(),
+        });
+        return Vec::new();
+    };
+    let columns: HashMap<String, usize> = header
+        .iter()
+        .enumerate()
+        .map(|(index, name)| (name.trim().to_lowercase(), index))
+        .collect();
+    let column = |name: &str| columns.get(&name.trim().to_lowercase()).copied();
+
+    let (Some(id_col), Some(name_col)) = (column(&mapping.user_id), column(&mapping.name)) else {
+        report.errors.push(RowIssue {
+            row: 1,
+            field: String::new(),
+            message: format!(
+                "Missing required columns '{}' and/or '{}'",
+                mapping.user_id, mapping.name
+            ),
+        });
+        return Vec::new();
+    };
+    let optional = |name: &Option<String>| name.as_deref().and_then(column);
+    let (card_col, privilege_col, password_col, group_col) = (
+        optional(&mapping.card),
+        optional(&mapping.privilege),
+        optional(&mapping.password),
+        optional(&mapping.group_id),
+    );
+
+    let mut users = Vec::new();
+    let mut seen_ids = HashSet::new();
+    let mut seen_cards = HashSet::new();
+    for (index, values) in data.iter().enumerate() {
+        let row = index + 2;
+        if values.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        report.total_rows += 1;
+        let value = |col: Option<usize>| {
+            col.and_then(|c| values.get(c))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+        let mut row_errors = Vec::new();
+        let mut issue = |field: &str, message: String| {
+            row_errors.push(RowIssue {
+                row,
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        let user_id = value(Some(id_col));
+        if user_id.is_empty() {
+            issue("user_id", "User id is required".to_string());
+        } else if user_id.len() > MAX_USER_ID_LEN
+            || !user_id.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            issue(
+                "user_id",
+                format!(
+                    "'{}' must be letters/digits, at most {} characters",
+                    user_id, MAX_USER_ID_LEN
+                ),
+            );
+        } else if !seen_ids.insert(user_id.clone()) {
+            issue("user_id", format!("Duplicate user id {}", user_id));
+        }
+
+        let name = value(Some(name_col));
+        if name.is_empty() {
+            issue("name", "Name is required".to_string());
+        }
+
+        let card_text = value(card_col);
+        let card = match card_text.parse::<u32>() {
+            _ if card_text.is_empty() => 0,
+            Ok(card) if card == 0 || seen_cards.insert(card) => card,
+            Ok(card) => {
+                issue("card", format!("Card {} is used by another row", card));
+                0
+            }
+            Err(_) => {
+                issue(
+                    "card",
+                    format!("'{}' is not a valid card number", card_text),
+                );
+                0
+            }
+        };
+        let privilege = parse_privilege(&value(privilege_col)).unwrap_or_else(|| {
+            issue(
+                "privilege",
+                format!(
+                    "Unknown privilege '{}' (user or admin)",
+                    value(privilege_col)
+                ),
+            );
+            0
+        });
+
+        if !row_errors.is_empty() {
+            report.errors.extend(row_errors);
+            continue;
+        }
+        if name.len() > MAX_NAME_LEN {
+            report.warnings.push(RowIssue {
+                row,
+                field: "name".to_string(),
+                message: format!("'{}' will be shortened on the device", name),
+            });
+        }
+        users.push(DeviceUser {
+            uid: 0,
+            user_id,
+            name,
+            privilege,
+            password: value(password_col),
+            card,
+            group_id: match value(group_col) {
+                group if group.is_empty() => "1".to_string(),
+                group => group,
+            },
+        });
+    }
+    report.valid_rows = users.len();
+    users
+}
+
+async fn send_to_backend(
+    users: &[DeviceUser],
+    serial_number: Option<&str>,
+    backend_port: u16,
+) -> Result<serde_json::Value, String> {
+    let payload: Vec<serde_json::Value> = users
+        .iter()
+        .map(|user| {
+            serde_json::json!({
+                "user_id": user.user_id,
+                "name": user.name,
+                "privilege": user.privilege,
+                "group_id": user.group_id.parse::<i64>().unwrap_or(0),
+                "card": user.card,
+                "password": user.password,
+                "serial_number": serial_number,
+            })
+        })
+        .collect();
+    let file =
+        serde_json::to_string(&payload).map_err(|e| format!("Failed to serialize users: {}", e))?;
+    // /users/import takes a multipart upload of a JSON file
+    let body = format!(
+        "--{b}\r\nContent-Disposition: form-data; name=\"exact_import\"\r\n\r\n{exact}\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"users.json\"\r\n\
+         Content-Type: application/json\r\n\r\n{file}\r\n--{b}--\r\n",
+        b = MULTIPART_BOUNDARY,
+        exact = serial_number.is_some(),
+        file = file
+    );
+
+    let client = backend_client(Duration::from_secs(60))?;
+    let response = client
+        .post(format!("{}/users/import", backend_base_url(backend_port)))
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+        )
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send users to backend: {}", e))?;
+    let status = response.status();
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid import response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!(
+            "Backend rejected the import ({}): {}",
+            status,
+            json.get("error").unwrap_or(&json)
+        ));
+    }
+    Ok(json)
+}
+
+// Existing user ids keep their slot, new ones get the next free slots
+fn push_users(device: DeviceEntry, users: Vec<DeviceUser>) -> Result<usize, String> {
+    let mut session = device.open_session(NATIVE_TIMEOUT)?;
+    let (existing, record_size) = session.read_users()?;
+    let mut next_uid = existing.iter().map(|u| u.uid).max().unwrap_or(0);
+    let written = users.iter().cloned().try_for_each(|mut user| {
+        user.uid = match existing.iter().find(|u| u.user_id == user.user_id) {
+            Some(current) => current.uid,
+            None => {
+                next_uid = next_uid
+                    .checked_add(1)
+                    .ok_or_else(|| format!("No free user slot left for {}", user.user_id))?;
+                next_uid
+            }
+        };
+        session.store_user(&user, record_size)
+    });
+    // One refresh for the whole batch, also after a failed write so the stored users show up
+    session.refresh_data()?;
+    written?;
+    let _ = session.refresh_options();
+    session.disconnect()?;
+    Ok(users.len())
+}
+
+// Send validated users to the backend, then write them to the selected terminals
+pub async fn commit_users(
+    users: &[DeviceUser],
+    options: &ImportOptions,
+    registry: &DeviceRegistry,
+    backend_port: &BackendPort,
+) -> Result<(serde_json::Value, Vec<DevicePushResult>), String> {
+    let response = send_to_backend(
+        users,
+        options.serial_number.as_deref(),
+        current_backend_port(backend_port),
+    )
+    .await?;
+
+    let mut pushed = Vec::new();
+    for device_id in &options.push_device_ids {
+        let result = match lookup_device(registry, backend_port, device_id).await {
+            Ok(device) => {
+                let users = users.to_vec();
+                run_native(move || push_users(device, users)).await
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = &result {
+            append_app_log(&format!(
+                "Pushing imported users to {} failed: {}",
+                device_id, err
+            ));
+        }
+        pushed.push(DevicePushResult {
+            device_id: device_id.clone(),
+            written: *result.as_ref().unwrap_or(&0),
+            error: result.err(),
+        });
+    }
+    Ok((response, pushed))
+}
+
+#[tauri::command]
+pub async fn import_users_csv(
+    path: String,
+    mapping: Option<ColumnMapping>,
+    options: Option<ImportOptions>,
+    registry: State<'_, DeviceRegistry>,
+    backend_port: State<'_, BackendPort>,
+) -> Result<ImportReport, String> {
+    lock::ensure_unlocked()?;
+    let source = check_path(&path, Access::Read)?;
+    let content = fs::read(source).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let content = String::from_utf8_lossy(&content);
+    let mapping = mapping.unwrap_or_default();
+    let options = options.unwrap_or_default();
+
+    let mut report = ImportReport::default();
+    let users = validate_rows(&parse_csv(&content), &mapping, &mut report);
+    if !report.errors.is_empty() || options.dry_run || users.is_empty() {
+        return Ok(report);
+    }
+
+    let (response, pushed) = commit_users(&users, &options, &registry, &backend_port).await?;
+    report.backend_response = Some(response);
+    report.committed = true;
+    report.pushed = pushed;
+    append_app_log(&format!("Imported {} users from {}", users.len(), path));
+    Ok(report)
+}
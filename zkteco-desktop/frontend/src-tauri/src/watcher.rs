@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use rusqlite::{params, Connection};
+use tauri::{AppHandle, Emitter};
+
+use crate::database::run_db;
+
+// Watches attendance_logs for rows written by the backend (device pulls, ADMS pushes, imports)
+// and tells the UI with "attendance-data-changed", so dashboards refresh when data lands
+// instead of on a timer. Ids only grow, so the highest id seen is all the state needed; a lower
+// one means the database was replaced (restore, import, profile switch).
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct AttendanceDataChanged {
+    new_records: u64,
+    latest_id: i64,
+    latest_timestamp: Option<String>,
+    devices: Vec<String>,
+    // The database was swapped out; reload everything, not just the newest rows
+    reset: bool,
+}
+
+fn latest_id(connection: &Connection) -> Result<i64, String> {
+    connection
+        .query_row(
+            "SELECT COALESCE(MAX(id), 0) FROM attendance_logs",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to read latest attendance id: {}", e))
+}
+
+fn changes_since(connection: &Connection, last_id: i64) -> Result<AttendanceDataChanged, String> {
+    let latest = latest_id(connection)?;
+    if latest < last_id {
+        return Ok(AttendanceDataChanged {
+            latest_id: latest,
+            reset: true,
+            ..Default::default()
+        });
+    }
+    let (new_records, latest_timestamp, devices) = connection
+        .query_row(
+            "SELECT COUNT(*), MAX(timestamp), GROUP_CONCAT(DISTINCT device_id)
+             FROM attendance_logs WHERE id > ?1",
+            params![last_id],
+            |row| {
+                Ok((
+                    row.get::<_, u64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
+        )
+        .map_err(|e| format!("Failed to read new attendance: {}", e))?;
+    Ok(AttendanceDataChanged {
+        new_records,
+        latest_id: latest,
+        latest_timestamp,
+        devices: devices
+            .map(|devices| devices.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+        reset: false,
+    })
+}
+
+pub fn spawn_data_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        // None until the database has been read once; nothing is reported for existing rows
+        let mut last_id: Option<i64> = None;
+        let mut last_error: Option<String> = None;
+        loop {
+            tokio::time::sleep(WATCH_INTERVAL).await;
+
+            let result = match last_id {
+                None => run_db(latest_id).await.map(|latest| AttendanceDataChanged {
+                    latest_id: latest,
+                    ..Default::default()
+                }),
+                Some(since) => run_db(move |connection| changes_since(connection, since)).await,
+            };
+            // The database is missing or locked while the backend starts or is maintained
+            let changed = match result {
+                Ok(changed) => {
+                    last_error = None;
+                    changed
+                }
+                Err(err) => {
+                    if last_error.as_ref() != Some(&err) {
+                        eprintln!("Attendance watcher: {}", err);
+                        last_error = Some(err);
+                    }
+                    continue;
+                }
+            };
+
+            let report = last_id.is_some() && (changed.new_records > 0 || changed.reset);
+            last_id = Some(changed.latest_id);
+            if report {
+                if let Err(err) = app.emit("attendance-data-changed", &changed) {
+                    eprintln!("Failed to emit attendance-data-changed event: {}", err);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,440 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::audit::audited;
+use crate::monitor::{BackendState, MonitorBus, MonitorEvent};
+use crate::secrets::{hex, random_hex, set_webhook_secret, webhook_secret};
+use crate::{append_app_log, lock, resolve_app_data_dir};
+
+// Outbound webhooks: every registered URL gets a JSON POST for the events it subscribed to
+// (punch, device_offline, device_online, backend_crash, sync_complete), shaped as
+// {id, event, created_at, data}. Requests carry X-Ztkapp-Event, X-Ztkapp-Delivery,
+// X-Ztkapp-Timestamp and X-Ztkapp-Signature: "sha256=" + hex HMAC-SHA256 of
+// "<timestamp>.<body>" keyed with the webhook's secret, which is shown once on registration and
+// kept in the OS keychain. Failed deliveries are retried a few times; every attempt is appended
+// to webhook_deliveries.jsonl.
+const EVENTS: [&str; 5] = [
+    "punch",
+    "device_offline",
+    "device_online",
+    "backend_crash",
+    "sync_complete",
+];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(120),
+];
+// The delivery log is cut back to this many entries once it grows past twice that
+const DELIVERY_LOG_KEEP: usize = 500;
+
+static WEBHOOKS_LOCK: Mutex<()> = Mutex::new(());
+static DELIVERIES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Webhook {
+    id: String,
+    url: String,
+    events: Vec<String>,
+    enabled: bool,
+    created_at: DateTime<Utc>,
+}
+
+// Returned by register_webhook only; the secret can't be read back later
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RegisteredWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    secret: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeliveryRecord {
+    delivery_id: String,
+    webhook_id: String,
+    url: String,
+    event: String,
+    attempt: u32,
+    at: DateTime<Utc>,
+    success: bool,
+    status: Option<u16>,
+    error: Option<String>,
+}
+
+enum Delivery {
+    Accepted(u16),
+    // 4xx other than 408/429: retrying the same payload won't help
+    Rejected(u16, String),
+    Failed(Option<u16>, String),
+}
+
+fn webhooks_path() -> PathBuf {
+    resolve_app_data_dir().join("webhooks.json")
+}
+
+fn deliveries_path() -> PathBuf {
+    resolve_app_data_dir().join("webhook_deliveries.jsonl")
+}
+
+fn load_webhooks() -> Vec<Webhook> {
+    fs::read_to_string(webhooks_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_webhooks(webhooks: &[Webhook]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(webhooks)
+        .map_err(|e| format!("Failed to serialize webhooks: {}", e))?;
+    fs::write(webhooks_path(), content).map_err(|e| format!("Failed to save webhooks: {}", e))
+}
+
+fn sign(secret: &str, timestamp: i64, body: &str) -> Result<String, String> {
+    let key = PKey::hmac(secret.as_bytes())
+        .map_err(|e| format!("Failed to create signing key: {}", e))?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)
+        .map_err(|e| format!("Failed to create signer: {}", e))?;
+    signer
+        .update(format!("{}.{}", timestamp, body).as_bytes())
+        .map_err(|e| format!("Failed to sign payload: {}", e))?;
+    let signature = signer
+        .sign_to_vec()
+        .map_err(|e| format!("Failed to sign payload: {}", e))?;
+    Ok(hex(&signature))
+}
+
+fn record_delivery(record: &DeliveryRecord) {
+    let _guard = DELIVERIES_LOCK.lock();
+    let path = deliveries_path();
+    let appended = serde_json::to_string(record)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(err) = appended {
+        eprintln!("Failed to record webhook delivery: {}", err);
+        return;
+    }
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return;
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() > DELIVERY_LOG_KEEP * 2 {
+        let mut kept = lines[lines.len() - DELIVERY_LOG_KEEP..].join("\n");
+        kept.push('\n');
+        if let Err(err) = fs::write(&path, kept) {
+            eprintln!("Failed to trim webhook delivery log: {}", err);
+        }
+    }
+}
+
+async fn post(webhook: &Webhook, event: &str, delivery_id: &str, body: &str) -> Delivery {
+    let Some(secret) = webhook_secret(&webhook.id) else {
+        return Delivery::Failed(None, "Webhook secret is missing from the keychain".into());
+    };
+    let timestamp = Utc::now().timestamp();
+    let signature = match sign(&secret, timestamp, body) {
+        Ok(signature) => signature,
+        Err(err) => return Delivery::Failed(None, err),
+    };
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(err) => {
+            return Delivery::Failed(None, format!("Failed to create HTTP client: {}", err))
+        }
+    };
+    let request = client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-Ztkapp-Event", event)
+        .header("X-Ztkapp-Delivery", delivery_id)
+        .header("X-Ztkapp-Timestamp", timestamp.to_string())
+        .header("X-Ztkapp-Signature", format!("sha256={}", signature))
+        .body(body.to_string());
+    match request.send().await {
+        Ok(response) if response.status().is_success() => {
+            Delivery::Accepted(response.status().as_u16())
+        }
+        Ok(response) => {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            let message = format!("Endpoint returned {}: {}", status, text.trim());
+            if status.is_client_error() && status.as_u16() != 408 && status.as_u16() != 429 {
+                Delivery::Rejected(status.as_u16(), message)
+            } else {
+                Delivery::Failed(Some(status.as_u16()), message)
+            }
+        }
+        Err(err) => Delivery::Failed(None, format!("Failed to reach webhook: {}", err)),
+    }
+}
+
+// One attempt, logged; true when retrying could still help
+async fn attempt(
+    webhook: &Webhook,
+    event: &str,
+    delivery_id: &str,
+    body: &str,
+    attempt: u32,
+) -> (DeliveryRecord, bool) {
+    let (success, status, error, retry) = match post(webhook, event, delivery_id, body).await {
+        Delivery::Accepted(status) => (true, Some(status), None, false),
+        Delivery::Rejected(status, err) => (false, Some(status), Some(err), false),
+        Delivery::Failed(status, err) => (false, status, Some(err), true),
+    };
+    let record = DeliveryRecord {
+        delivery_id: delivery_id.to_string(),
+        webhook_id: webhook.id.clone(),
+        url: webhook.url.clone(),
+        event: event.to_string(),
+        attempt,
+        at: Utc::now(),
+        success,
+        status,
+        error,
+    };
+    record_delivery(&record);
+    (record, retry)
+}
+
+fn payload(delivery_id: &str, event: &str, data: &serde_json::Value) -> String {
+    serde_json::json!({
+        "id": delivery_id,
+        "event": event,
+        "created_at": Utc::now(),
+        "data": data,
+    })
+    .to_string()
+}
+
+async fn deliver(webhook: Webhook, event: &'static str, data: serde_json::Value) {
+    let delivery_id = random_hex(16);
+    let body = payload(&delivery_id, event, &data);
+    for number in 1..=RETRY_DELAYS.len() as u32 + 1 {
+        let (record, retry) = attempt(&webhook, event, &delivery_id, &body, number).await;
+        if record.success || !retry {
+            if let Some(err) = record.error {
+                append_app_log(&format!(
+                    "Webhook {} rejected {}: {}",
+                    webhook.id, event, err
+                ));
+            }
+            return;
+        }
+        match RETRY_DELAYS.get(number as usize - 1) {
+            Some(delay) => tokio::time::sleep(*delay).await,
+            None => append_app_log(&format!(
+                "Webhook {} gave up on {} after {} attempts: {}",
+                webhook.id,
+                event,
+                number,
+                record.error.unwrap_or_default()
+            )),
+        }
+    }
+}
+
+// The webhook event a monitor event maps to, with its data
+fn webhook_event(event: &MonitorEvent) -> Option<(&'static str, serde_json::Value)> {
+    match event {
+        MonitorEvent::Punch(punch) => Some(("punch", punch.clone())),
+        MonitorEvent::DeviceStatus(status) => {
+            let data = serde_json::to_value(status).ok()?;
+            let online = data.get("online").and_then(|online| online.as_bool())?;
+            Some((
+                if online {
+                    "device_online"
+                } else {
+                    "device_offline"
+                },
+                data,
+            ))
+        }
+        MonitorEvent::Backend(state @ BackendState::Failed(_)) => {
+            Some(("backend_crash", serde_json::to_value(state).ok()?))
+        }
+        MonitorEvent::SyncComplete(summary) => Some(("sync_complete", summary.clone())),
+        MonitorEvent::Backend(_) | MonitorEvent::Devices(_) | MonitorEvent::SyncFailed(_) => None,
+    }
+}
+
+pub fn spawn_webhook_dispatcher(bus: &MonitorBus) {
+    let mut receiver = bus.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    append_app_log(&format!("Webhooks missed {} monitor events", skipped));
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let Some((name, data)) = webhook_event(&event) else {
+                continue;
+            };
+            for webhook in load_webhooks()
+                .into_iter()
+                .filter(|webhook| webhook.enabled && webhook.events.iter().any(|e| e == name))
+            {
+                tauri::async_runtime::spawn(deliver(webhook, name, data.clone()));
+            }
+        }
+    });
+}
+
+fn validate(url: &str, events: &[String]) -> Result<(), String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL '{}': {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!("Webhook URL '{}' must be an http(s) address", url));
+    }
+    if events.is_empty() {
+        return Err("Choose at least one event for the webhook".to_string());
+    }
+    if let Some(unknown) = events
+        .iter()
+        .find(|event| !EVENTS.contains(&event.as_str()))
+    {
+        return Err(format!(
+            "Unknown webhook event '{}'; expected one of {}",
+            unknown,
+            EVENTS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_webhooks() -> Vec<Webhook> {
+    load_webhooks()
+}
+
+#[tauri::command]
+pub fn register_webhook(url: String, events: Vec<String>) -> Result<RegisteredWebhook, String> {
+    lock::ensure_unlocked()?;
+    let url = url.trim().to_string();
+    let result = validate(&url, &events).and_then(|_| {
+        let _guard = WEBHOOKS_LOCK.lock();
+        let webhook = Webhook {
+            id: random_hex(8),
+            url: url.clone(),
+            events: events.clone(),
+            enabled: true,
+            created_at: Utc::now(),
+        };
+        let secret = random_hex(32);
+        set_webhook_secret(&webhook.id, &secret)?;
+        let mut webhooks = load_webhooks();
+        webhooks.push(webhook.clone());
+        if let Err(err) = save_webhooks(&webhooks) {
+            let _ = set_webhook_secret(&webhook.id, "");
+            return Err(err);
+        }
+        Ok(RegisteredWebhook { webhook, secret })
+    });
+    audited("register_webhook", &url, result)
+}
+
+#[tauri::command]
+pub fn set_webhook_enabled(id: String, enabled: bool) -> Result<Webhook, String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        let _guard = WEBHOOKS_LOCK.lock();
+        let mut webhooks = load_webhooks();
+        let webhook = webhooks
+            .iter_mut()
+            .find(|webhook| webhook.id == id)
+            .ok_or_else(|| format!("Webhook {} not found", id))?;
+        webhook.enabled = enabled;
+        let updated = webhook.clone();
+        save_webhooks(&webhooks)?;
+        Ok(updated)
+    })();
+    audited(
+        if enabled {
+            "enable_webhook"
+        } else {
+            "disable_webhook"
+        },
+        &id,
+        result,
+    )
+}
+
+#[tauri::command]
+pub fn remove_webhook(id: String) -> Result<(), String> {
+    lock::ensure_unlocked()?;
+    let result = (|| {
+        let _guard = WEBHOOKS_LOCK.lock();
+        let mut webhooks = load_webhooks();
+        let before = webhooks.len();
+        webhooks.retain(|webhook| webhook.id != id);
+        if webhooks.len() == before {
+            return Err(format!("Webhook {} not found", id));
+        }
+        save_webhooks(&webhooks)?;
+        set_webhook_secret(&id, "")
+    })();
+    audited("remove_webhook", &id, result)
+}
+
+// Send a single "test" event now, without retries
+#[tauri::command]
+pub async fn test_webhook(id: String) -> Result<DeliveryRecord, String> {
+    lock::ensure_unlocked()?;
+    let webhook = load_webhooks()
+        .into_iter()
+        .find(|webhook| webhook.id == id)
+        .ok_or_else(|| format!("Webhook {} not found", id))?;
+    let delivery_id = random_hex(16);
+    let body = payload(
+        &delivery_id,
+        "test",
+        &serde_json::json!({ "message": "Test delivery from ZKTeco Desktop" }),
+    );
+    Ok(attempt(&webhook, "test", &delivery_id, &body, 1).await.0)
+}
+
+// Most recent first, optionally for one webhook
+#[tauri::command]
+pub fn get_webhook_deliveries(
+    webhook_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<DeliveryRecord>, String> {
+    let content = match fs::read_to_string(deliveries_path()) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to read webhook deliveries: {}", err)),
+    };
+    let mut records: Vec<DeliveryRecord> = content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|record: &DeliveryRecord| {
+            webhook_id
+                .as_ref()
+                .is_none_or(|id| &record.webhook_id == id)
+        })
+        .collect();
+    records.reverse();
+    if let Some(limit) = limit {
+        records.truncate(limit);
+    }
+    Ok(records)
+}
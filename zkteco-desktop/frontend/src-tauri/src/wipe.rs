@@ -0,0 +1,248 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::backup::stop_backend_for_maintenance;
+use crate::confirmation::{consume_token, ConfirmationTokens};
+use crate::encryption::{self, KEYRING_SERVICE};
+use crate::profiles::DEFAULT_PROFILE;
+use crate::relocate::DATABASE_FILES;
+use crate::secrets::{
+    alert_account, device_account, door_pin_account, google_sheets_account, health_token_account,
+    hr_adapter_account, ldap_account, local_api_account, mqtt_account, remote_account, s3_account,
+    service_token_account, smtp_account, upstream_account, webhook_account,
+};
+use crate::{get_log_file_path, lock, resolve_base_data_dir};
+
+// Decommissioning a PC: every file the app keeps (databases, backups, punch photos, logs,
+// settings and queues of all profiles, plus a relocated database folder and the backend's log)
+// is overwritten with random data and deleted, and the app's keychain entries are removed.
+// Overwriting can't reach copies an SSD's wear levelling keeps, so disk encryption is still
+// advised. The app quits afterwards so nothing writes to the data folder again.
+const WIPE_ACTION: &str = "secure_wipe";
+const WIPE_TARGET: &str = "all data";
+const OVERWRITE_CHUNK: usize = 1024 * 1024;
+const EXIT_DELAY: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct WipeResult {
+    files_wiped: usize,
+    bytes_overwritten: u64,
+    keychain_entries_removed: usize,
+    errors: Vec<String>,
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_files(&path, files),
+            Ok(_) => files.push(path),
+            Err(_) => {}
+        }
+    }
+}
+
+// Profile folders (the base one is the default profile) with the settings each one points at
+fn profile_dirs(base: &Path) -> Vec<(String, PathBuf)> {
+    let mut dirs = vec![(DEFAULT_PROFILE.to_string(), base.to_path_buf())];
+    if let Ok(entries) = fs::read_dir(base.join("profiles")) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                dirs.push((
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path(),
+                ));
+            }
+        }
+    }
+    dirs
+}
+
+fn read_json(path: &Path) -> Option<serde_json::Value> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+// Files outside the data folder: relocated databases and the backend's own log
+fn external_files(profiles: &[(String, PathBuf)]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = profiles
+        .iter()
+        .filter_map(|(_, dir)| {
+            read_json(&dir.join("shell_settings.json"))?
+                .get("database_dir")?
+                .as_str()
+                .map(PathBuf::from)
+        })
+        .flat_map(|dir| DATABASE_FILES.map(|name| dir.join(name)))
+        .collect();
+    files.extend(get_log_file_path().ok());
+    files.retain(|path| path.is_file());
+    files
+}
+
+fn keychain_accounts(profiles: &[(String, PathBuf)]) -> Vec<String> {
+    let mut accounts = vec![
+        encryption::KEYRING_ACCOUNT.to_string(),
+        lock::KEYRING_ACCOUNT.to_string(),
+    ];
+    for (profile, dir) in profiles {
+        accounts.push(upstream_account(profile));
+        accounts.push(health_token_account(profile));
+        accounts.push(mqtt_account(profile));
+        accounts.push(ldap_account(profile));
+        accounts.push(google_sheets_account(profile));
+        accounts.push(smtp_account(profile));
+        accounts.push(remote_account(profile));
+        accounts.push(s3_account(profile));
+        accounts.push(local_api_account(profile));
+        accounts.push(service_token_account(profile));
+        accounts.push(door_pin_account(profile));
+        let devices = read_json(&dir.join("device_registry.json"));
+        for device in devices
+            .as_ref()
+            .and_then(|d| d.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(reference) = device.get("credential_ref").and_then(|r| r.as_str()) {
+                accounts.push(device_account(reference));
+            }
+        }
+        let webhooks = read_json(&dir.join("webhooks.json"));
+        for webhook in webhooks
+            .as_ref()
+            .and_then(|w| w.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(id) = webhook.get("id").and_then(|id| id.as_str()) {
+                accounts.push(webhook_account(id));
+            }
+        }
+        let channels = read_json(&dir.join("alert_channels.json"));
+        for channel in channels
+            .as_ref()
+            .and_then(|c| c.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(id) = channel.get("id").and_then(|id| id.as_str()) {
+                accounts.push(alert_account(id));
+            }
+        }
+        let adapters = read_json(&dir.join("hr_adapters.json"));
+        for adapter in adapters
+            .as_ref()
+            .and_then(|a| a.as_array())
+            .into_iter()
+            .flatten()
+        {
+            if let Some(id) = adapter.get("id").and_then(|id| id.as_str()) {
+                accounts.push(hr_adapter_account(id));
+            }
+        }
+    }
+    accounts
+}
+
+fn overwrite(path: &Path) -> Result<u64, String> {
+    let size = fs::metadata(path)
+        .map_err(|e| format!("{:?}: {}", path, e))?
+        .len();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(|e| format!("{:?}: {}", path, e))?;
+    let mut chunk = vec![0u8; OVERWRITE_CHUNK];
+    let mut remaining = size;
+    while remaining > 0 {
+        let len = remaining.min(OVERWRITE_CHUNK as u64) as usize;
+        OsRng.fill_bytes(&mut chunk[..len]);
+        file.write_all(&chunk[..len])
+            .map_err(|e| format!("{:?}: {}", path, e))?;
+        remaining -= len as u64;
+    }
+    file.sync_all().map_err(|e| format!("{:?}: {}", path, e))?;
+    Ok(size)
+}
+
+fn wipe_files(base: &Path) -> WipeResult {
+    let profiles = profile_dirs(base);
+    let accounts = keychain_accounts(&profiles);
+    let mut files = external_files(&profiles);
+    collect_files(base, &mut files);
+    files.sort();
+    files.dedup();
+
+    let mut result = WipeResult::default();
+    for path in files {
+        match overwrite(&path).and_then(|size| {
+            fs::remove_file(&path)
+                .map(|_| size)
+                .map_err(|e| format!("{:?}: {}", path, e))
+        }) {
+            Ok(size) => {
+                result.files_wiped += 1;
+                result.bytes_overwritten += size;
+            }
+            Err(err) => result.errors.push(err),
+        }
+    }
+    if let Err(err) = fs::remove_dir_all(base) {
+        result
+            .errors
+            .push(format!("Failed to remove {:?}: {}", base, err));
+    }
+
+    for account in accounts {
+        let removed = keyring::Entry::new(KEYRING_SERVICE, &account)
+            .and_then(|entry| entry.delete_credential());
+        match removed {
+            Ok(()) => result.keychain_entries_removed += 1,
+            Err(keyring::Error::NoEntry) => {}
+            Err(err) => result
+                .errors
+                .push(format!("Keychain entry {}: {}", account, err)),
+        }
+    }
+    result
+}
+
+// Irreversible; needs a confirmation token for "secure_wipe" on "all data"
+#[tauri::command]
+pub async fn secure_wipe(
+    app: AppHandle,
+    confirm_token: String,
+    tokens: State<'_, ConfirmationTokens>,
+) -> Result<WipeResult, String> {
+    consume_token(&tokens, &confirm_token, WIPE_ACTION, WIPE_TARGET)?;
+    stop_backend_for_maintenance(&app)
+        .await
+        .map_err(|e| format!("{}, wipe aborted", e))?;
+
+    // Nothing is logged from here on: the log would be the first file to reappear
+    let base = resolve_base_data_dir();
+    let result = tauri::async_runtime::spawn_blocking(move || wipe_files(&base))
+        .await
+        .map_err(|e| format!("Wipe task failed: {}", e))?;
+    if let Err(err) = app.emit("data-wiped", &result) {
+        eprintln!("Failed to emit data-wiped event: {}", err);
+    }
+
+    let exit_handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(EXIT_DELAY).await;
+        exit_handle.exit(0);
+    });
+    Ok(result)
+}
@@ -0,0 +1,1220 @@
+// Minimal native ZKTeco protocol session over TCP (port 4370) or a serial link
+// (RS232/RS485 through a USB adapter), which carries the same framing.
+//
+// Packet layout on the wire:
+//   [50 50 82 7D][payload size u32 LE][cmd u16][checksum u16][session u16][reply u16][data...]
+// The checksum covers the 8 byte command header plus data.
+// Devices with a communication password (COMM key) answer CMD_CONNECT with CMD_ACK_UNAUTH and
+// expect a CMD_AUTH carrying a key derived from the password and the session id.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use encoding_rs::GBK;
+use serialport::SerialPort;
+
+use crate::trace::SessionTrace;
+
+pub const DEFAULT_DEVICE_PORT: u16 = 4370;
+pub const DEFAULT_BAUD_RATE: u32 = 115200;
+// Baud rates selectable in the terminals' communication menu
+pub const SUPPORTED_BAUD_RATES: [u32; 5] = [9600, 19200, 38400, 57600, 115200];
+
+pub const START_TAG: [u8; 4] = [0x50, 0x50, 0x82, 0x7D];
+pub const TCP_HEADER_SIZE: usize = 8;
+pub const COMMAND_HEADER_SIZE: usize = 8;
+// Largest chunk requested per CMD_READ_BUFFER round trip
+const MAX_CHUNK: usize = 0xFFC0;
+// Largest reply accepted from a device. Buffered reads come in MAX_CHUNK pieces and inline
+// CMD_DATA replies are smaller still; anything announcing more is refused before allocating.
+const MAX_REPLY_SIZE: usize = 16 * (MAX_CHUNK + COMMAND_HEADER_SIZE);
+// Largest buffered dataset accepted: well above a full attendance log (a few hundred thousand
+// 40 byte records) or a user/template table, so a bogus size fails before allocating
+const MAX_DATASET_SIZE: usize = 128 * 1024 * 1024;
+// Largest CMD_DATA packet sent when uploading
+const UPLOAD_CHUNK: usize = 1024;
+
+pub const CMD_CONNECT: u16 = 1000;
+pub const CMD_EXIT: u16 = 1001;
+pub const CMD_ENABLEDEVICE: u16 = 1002;
+pub const CMD_DISABLEDEVICE: u16 = 1003;
+pub const CMD_RESTART: u16 = 1004;
+pub const CMD_POWEROFF: u16 = 1005;
+pub const CMD_GET_VERSION: u16 = 1100;
+pub const CMD_AUTH: u16 = 1102;
+pub const CMD_FREE_DATA: u16 = 1502;
+pub const CMD_PREPARE_BUFFER: u16 = 1503;
+pub const CMD_READ_BUFFER: u16 = 1504;
+pub const CMD_PREPARE_DATA: u16 = 1500;
+pub const CMD_DATA: u16 = 1501;
+pub const CMD_USER_WRQ: u16 = 8;
+pub const CMD_USERTEMP_RRQ: u16 = 9;
+pub const CMD_OPTIONS_RRQ: u16 = 11;
+pub const CMD_OPTIONS_WRQ: u16 = 12;
+pub const CMD_ATTLOG_RRQ: u16 = 13;
+pub const CMD_CLEAR_ATTLOG: u16 = 15;
+pub const CMD_UNLOCK: u16 = 31;
+pub const CMD_OPLOG_RRQ: u16 = 34;
+pub const CMD_GET_FREE_SIZES: u16 = 50;
+pub const CMD_SMS_WRQ: u16 = 70;
+pub const CMD_UDATA_WRQ: u16 = 73;
+pub const CMD_GET_TIME: u16 = 201;
+pub const CMD_SET_TIME: u16 = 202;
+pub const CMD_REG_EVENT: u16 = 500;
+pub const CMD_REFRESHDATA: u16 = 1013;
+pub const CMD_REFRESHOPTION: u16 = 1014;
+pub const CMD_UPDATEFILE: u16 = 1700;
+pub const CMD_TMP_WRITE: u16 = 87;
+pub const CMD_GET_USERTEMP: u16 = 88;
+
+// SMS tags: personal messages are bound to users with CMD_UDATA_WRQ, public ones show to everyone
+pub const SMS_TAG_USER: u8 = 253;
+pub const SMS_TAG_PUBLIC: u8 = 254;
+const SMS_CONTENT_SIZE: usize = 61;
+
+// Template slot holding the face template on face capable firmware
+pub const FACE_TEMPLATE_INDEX: u8 = 50;
+
+// Function code for CMD_USERTEMP_RRQ selecting user records
+const FCT_USER: i32 = 5;
+
+// Realtime event flags for CMD_REG_EVENT
+pub const EF_ATTLOG: u32 = 1;
+pub const EF_UNLOCK: u32 = 32;
+pub const EF_ALARM: u32 = 512;
+// Card number of a presented card, pushed as a u32 payload
+pub const EF_HIDNUM: u32 = 1024;
+
+// Operation log entries are packed in 16 bytes:
+//   [admin u16][operation u8][time u32][user u16][params 3 x u16][pad]
+const OPLOG_RECORD_SIZE: usize = 16;
+
+pub const CMD_ACK_OK: u16 = 2000;
+pub const CMD_ACK_ERROR: u16 = 2001;
+pub const CMD_ACK_DATA: u16 = 2002;
+pub const CMD_ACK_UNAUTH: u16 = 2005;
+
+// Prefix of the error returned when the device rejects the COMM key, so callers (and the UI)
+// can tell a wrong communication password apart from network failures
+pub const AUTH_FAILED: &str = "AUTH_FAILED";
+
+// Record counters and capacities reported by CMD_GET_FREE_SIZES
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeviceSizes {
+    pub users: u32,
+    pub fingers: u32,
+    pub records: u32,
+    pub cards: u32,
+    pub users_cap: u32,
+    pub fingers_cap: u32,
+    pub records_cap: u32,
+    // Only reported by face capable firmware
+    pub faces: Option<u32>,
+    pub faces_cap: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AttendanceRecord {
+    pub user_id: String,
+    pub timestamp: NaiveDateTime,
+    pub verify_mode: u8,
+    pub punch: u8,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceUser {
+    #[serde(default)]
+    pub uid: u16, // internal slot index on the device
+    pub user_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub privilege: u8, // 0 user, 14 admin
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub card: u32,
+    #[serde(default)]
+    pub group_id: String,
+}
+
+// Old firmware packs users in 28 bytes with numeric ids, newer firmware in 72 bytes
+pub const USER_RECORD_SMALL: usize = 28;
+pub const USER_RECORD_LARGE: usize = 72;
+
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub command: u16,
+    pub session_id: u16,
+    pub data: Vec<u8>,
+}
+
+impl Reply {
+    pub fn is_ok(&self) -> bool {
+        matches!(
+            self.command,
+            CMD_ACK_OK | CMD_ACK_DATA | CMD_PREPARE_DATA | CMD_DATA
+        )
+    }
+}
+
+// Byte stream a session runs over
+enum Transport {
+    Tcp(TcpStream),
+    Serial(Box<dyn SerialPort>),
+}
+
+impl Transport {
+    // Wait up to `poll` for incoming bytes without consuming them; Ok(false) on timeout
+    fn wait_readable(&mut self, poll: Duration, timeout: Duration) -> Result<bool, String> {
+        match self {
+            Transport::Tcp(stream) => {
+                stream
+                    .set_read_timeout(Some(poll))
+                    .map_err(|e| format!("Failed to configure device socket: {}", e))?;
+                let mut probe = [0u8; 1];
+                let peeked = stream.peek(&mut probe);
+                stream
+                    .set_read_timeout(Some(timeout))
+                    .map_err(|e| format!("Failed to configure device socket: {}", e))?;
+                match peeked {
+                    Ok(0) => Err("Device closed the connection".to_string()),
+                    Ok(_) => Ok(true),
+                    Err(e)
+                        if e.kind() == std::io::ErrorKind::WouldBlock
+                            || e.kind() == std::io::ErrorKind::TimedOut =>
+                    {
+                        Ok(false)
+                    }
+                    Err(e) => Err(format!("Failed to wait for device event: {}", e)),
+                }
+            }
+            Transport::Serial(port) => {
+                let deadline = Instant::now() + poll;
+                loop {
+                    let pending = port
+                        .bytes_to_read()
+                        .map_err(|e| format!("Failed to wait for device event: {}", e))?;
+                    if pending > 0 {
+                        return Ok(true);
+                    }
+                    if Instant::now() >= deadline {
+                        return Ok(false);
+                    }
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+            }
+        }
+    }
+}
+
+impl Read for Transport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Tcp(stream) => stream.read(buf),
+            Transport::Serial(port) => port.read(buf),
+        }
+    }
+}
+
+impl Write for Transport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Transport::Tcp(stream) => stream.write(buf),
+            Transport::Serial(port) => port.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Transport::Tcp(stream) => stream.flush(),
+            Transport::Serial(port) => port.flush(),
+        }
+    }
+}
+
+pub struct ZkSession {
+    stream: Transport,
+    session_id: u16,
+    reply_id: u16,
+    timeout: Duration,
+    trace: Option<SessionTrace>,
+}
+
+// Ones-complement sum of little endian 16 bit words, as expected by the terminal firmware
+fn checksum16(payload: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in payload.chunks(2) {
+        let word = if chunk.len() == 2 {
+            u16::from_le_bytes([chunk[0], chunk[1]])
+        } else {
+            chunk[0] as u16
+        };
+        sum += word as u32;
+    }
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+pub fn build_packet(command: u16, session_id: u16, reply_id: u16, data: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(COMMAND_HEADER_SIZE + data.len());
+    payload.extend_from_slice(&command.to_le_bytes());
+    payload.extend_from_slice(&[0, 0]);
+    payload.extend_from_slice(&session_id.to_le_bytes());
+    payload.extend_from_slice(&reply_id.to_le_bytes());
+    payload.extend_from_slice(data);
+    let checksum = checksum16(&payload);
+    payload[2..4].copy_from_slice(&checksum.to_le_bytes());
+
+    let mut packet = Vec::with_capacity(TCP_HEADER_SIZE + payload.len());
+    packet.extend_from_slice(&START_TAG);
+    packet.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    packet.extend_from_slice(&payload);
+    packet
+}
+
+// Scramble the COMM key with the session id the same way the firmware does (pyzk make_commkey)
+pub fn make_commkey(key: u32, session_id: u16, ticks: u8) -> [u8; 4] {
+    let mut k: u32 = 0;
+    for i in 0..32 {
+        k <<= 1;
+        if key & (1 << i) != 0 {
+            k |= 1;
+        }
+    }
+    let k = k.wrapping_add(session_id as u32).to_le_bytes();
+    let k = [k[0] ^ b'Z', k[1] ^ b'K', k[2] ^ b'S', k[3] ^ b'O'];
+    // Swap the two 16 bit halves
+    let k = [k[2], k[3], k[0], k[1]];
+    [k[0] ^ ticks, k[1] ^ ticks, ticks, k[3] ^ ticks]
+}
+
+// Device timestamps count seconds in a calendar of 31 day months and 12 month years since 2000
+pub fn decode_time(raw: u32) -> Option<NaiveDateTime> {
+    let mut t = raw;
+    let second = t % 60;
+    t /= 60;
+    let minute = t % 60;
+    t /= 60;
+    let hour = t % 24;
+    t /= 24;
+    let day = t % 31 + 1;
+    t /= 31;
+    let month = t % 12 + 1;
+    t /= 12;
+    NaiveDate::from_ymd_opt(2000 + t as i32, month, day)?.and_hms_opt(hour, minute, second)
+}
+
+pub fn encode_time(time: NaiveDateTime) -> u32 {
+    let days = ((time.year() as u32 % 100) * 12 * 31) + (time.month0() * 31) + time.day0();
+    days * 86400 + (time.hour() * 60 + time.minute()) * 60 + time.second()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+// Attendance log layout depends on firmware: 8, 16 or 40 bytes per record
+pub fn parse_attendance_records(data: &[u8], record_count: u32) -> Vec<AttendanceRecord> {
+    if data.len() < 4 || record_count == 0 {
+        return Vec::new();
+    }
+    let total_size = read_u32(data, 0) as usize;
+    let data = &data[4..];
+    let record_size = total_size / record_count as usize;
+
+    data.chunks_exact(record_size.max(1))
+        .filter_map(|record| {
+            let (user_id, verify_mode, time, punch) = match record_size {
+                8 => (
+                    u16::from_le_bytes([record[0], record[1]]).to_string(),
+                    record[2],
+                    read_u32(record, 3),
+                    record[7],
+                ),
+                16 => (
+                    read_u32(record, 0).to_string(),
+                    record[8],
+                    read_u32(record, 4),
+                    record[9],
+                ),
+                40 => (
+                    decode_c_string(&record[2..26]),
+                    record[26],
+                    read_u32(record, 27),
+                    record[31],
+                ),
+                _ => return None,
+            };
+            Some(AttendanceRecord {
+                user_id,
+                timestamp: decode_time(time)?,
+                verify_mode,
+                punch,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OperationRecord {
+    pub admin: u16, // 0 when the device acted on its own (power on, alarm, ...)
+    pub operation: u8,
+    pub timestamp: NaiveDateTime,
+    pub user: u16,
+    pub params: [u16; 3],
+}
+
+// Description of the operation codes documented for the terminals' operation log
+pub fn describe_operation(code: u8) -> &'static str {
+    match code {
+        0 => "Power on",
+        1 => "Power off",
+        2 => "Verification failed",
+        3 => "Alarm",
+        4 => "Entered menu",
+        5 => "Changed settings",
+        6 => "Enrolled fingerprint",
+        7 => "Enrolled password",
+        8 => "Enrolled card",
+        9 => "Deleted user",
+        10 => "Deleted fingerprint",
+        11 => "Deleted password",
+        12 => "Deleted card",
+        13 => "Cleared data",
+        14..=20 => "MF card operation",
+        21 => "Set time",
+        22 => "Restored factory settings",
+        23 => "Deleted attendance records",
+        24 => "Cleared administrator privileges",
+        28 => "Door opened",
+        29 => "Tamper alarm",
+        30 => "Enrolled user",
+        _ => "Unknown operation",
+    }
+}
+
+pub fn parse_operation_records(data: &[u8]) -> Vec<OperationRecord> {
+    if data.len() < 4 {
+        return Vec::new();
+    }
+    let total_size = (read_u32(data, 0) as usize).min(data.len() - 4);
+    data[4..4 + total_size]
+        .chunks_exact(OPLOG_RECORD_SIZE)
+        .filter_map(|record| {
+            let word = |offset: usize| u16::from_le_bytes([record[offset], record[offset + 1]]);
+            Some(OperationRecord {
+                admin: word(0),
+                operation: record[2],
+                timestamp: decode_time(read_u32(record, 3))?,
+                user: word(7),
+                params: [word(9), word(11), word(13)],
+            })
+        })
+        .collect()
+}
+
+pub fn parse_users(data: &[u8], user_count: u32) -> (Vec<DeviceUser>, usize) {
+    if data.len() < 4 || user_count == 0 {
+        return (Vec::new(), USER_RECORD_LARGE);
+    }
+    let record_size = read_u32(data, 0) as usize / user_count as usize;
+    let users = data[4..]
+        .chunks_exact(record_size.max(1))
+        .filter_map(|record| match record_size {
+            USER_RECORD_SMALL => Some(DeviceUser {
+                uid: u16::from_le_bytes([record[0], record[1]]),
+                privilege: record[2],
+                password: decode_c_string(&record[3..8]),
+                name: decode_name(&record[8..16]),
+                card: read_u32(record, 16),
+                group_id: record[21].to_string(),
+                user_id: read_u32(record, 24).to_string(),
+            }),
+            USER_RECORD_LARGE => Some(DeviceUser {
+                uid: u16::from_le_bytes([record[0], record[1]]),
+                privilege: record[2],
+                password: decode_c_string(&record[3..11]),
+                name: decode_name(&record[11..35]),
+                card: read_u32(record, 35),
+                group_id: decode_c_string(&record[40..47]),
+                user_id: decode_c_string(&record[48..72]),
+            }),
+            _ => None,
+        })
+        .collect();
+    (users, record_size)
+}
+
+// Copy a string into a fixed size, NUL padded field
+pub fn put_str(buffer: &mut [u8], value: &str) {
+    let mut len = value.len().min(buffer.len());
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    buffer[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+pub fn pack_user(user: &DeviceUser, record_size: usize) -> Result<Vec<u8>, String> {
+    let mut record = vec![0u8; record_size];
+    record[0..2].copy_from_slice(&user.uid.to_le_bytes());
+    record[2] = user.privilege;
+    match record_size {
+        USER_RECORD_SMALL => {
+            let user_id: u32 = user
+                .user_id
+                .parse()
+                .map_err(|_| format!("Device only accepts numeric user ids: {}", user.user_id))?;
+            put_str(&mut record[3..8], &user.password);
+            put_name(&mut record[8..16], &user.name);
+            record[16..20].copy_from_slice(&user.card.to_le_bytes());
+            record[21] = user.group_id.parse().unwrap_or(0);
+            record[24..28].copy_from_slice(&user_id.to_le_bytes());
+        }
+        _ => {
+            put_str(&mut record[3..11], &user.password);
+            put_name(&mut record[11..35], &user.name);
+            record[35..39].copy_from_slice(&user.card.to_le_bytes());
+            put_str(&mut record[40..47], &user.group_id);
+            put_str(&mut record[48..72], &user.user_id);
+        }
+    }
+    Ok(record)
+}
+
+// User names are stored in fixed width fields: firmware for the Chinese market uses GB2312
+// (written here as GBK, its superset), newer firmware UTF-8. Names are written as GBK when every
+// character fits, otherwise as UTF-8, and always leave room for the NUL terminator.
+pub fn put_name(buffer: &mut [u8], name: &str) {
+    let capacity = buffer.len().saturating_sub(1);
+    let unmappable = GBK.encode(name).2;
+    let mut encoded = Vec::with_capacity(capacity);
+    let mut char_buf = [0u8; 4];
+    for ch in name.chars() {
+        let bytes = if unmappable {
+            ch.encode_utf8(&mut char_buf).as_bytes().to_vec()
+        } else {
+            GBK.encode(ch.encode_utf8(&mut char_buf)).0.into_owned()
+        };
+        // Truncate on a character boundary instead of leaving half a multi-byte sequence
+        if encoded.len() + bytes.len() > capacity {
+            break;
+        }
+        encoded.extend(bytes);
+    }
+    buffer.fill(0);
+    buffer[..encoded.len()].copy_from_slice(&encoded);
+}
+
+pub fn decode_name(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let raw = &data[..end];
+    let text = match std::str::from_utf8(raw) {
+        Ok(text) => text.to_string(),
+        // A UTF-8 name the device cut mid-character: keep the complete part
+        Err(err) if err.error_len().is_none() && err.valid_up_to() > 0 => {
+            String::from_utf8_lossy(&raw[..err.valid_up_to()]).to_string()
+        }
+        Err(_) => GBK.decode_without_bom_handling(raw).0.into_owned(),
+    };
+    text.trim().to_string()
+}
+
+// NUL terminated ASCII string as sent in most device replies
+pub fn decode_c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+pub fn resolve_device_addr(ip: &str, port: u16) -> Result<SocketAddr, String> {
+    (ip, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid device address {}:{}: {}", ip, port, e))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve device address {}:{}", ip, port))
+}
+
+impl ZkSession {
+    // Open the TCP socket and perform the CMD_CONNECT handshake, authenticating with the COMM key
+    // when the device asks for it (0 means no communication password)
+    pub fn connect(ip: &str, port: u16, comm_key: u32, timeout: Duration) -> Result<Self, String> {
+        let addr = resolve_device_addr(ip, port)?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)
+            .map_err(|e| format!("Failed to connect to device {}: {}", addr, e))?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)))
+            .map_err(|e| format!("Failed to configure device socket: {}", e))?;
+        let _ = stream.set_nodelay(true);
+
+        Self::handshake(Transport::Tcp(stream), &addr.to_string(), comm_key, timeout)
+    }
+
+    // Open a serial port (e.g. COM3 or /dev/ttyUSB0) and perform the same handshake as over TCP
+    pub fn connect_serial(
+        port_name: &str,
+        baud_rate: u32,
+        comm_key: u32,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        if !SUPPORTED_BAUD_RATES.contains(&baud_rate) {
+            return Err(format!("Unsupported baud rate: {}", baud_rate));
+        }
+        let port = serialport::new(port_name, baud_rate)
+            .timeout(timeout)
+            .open()
+            .map_err(|e| format!("Failed to open serial port {}: {}", port_name, e))?;
+        let _ = port.clear(serialport::ClearBuffer::All);
+
+        Self::handshake(Transport::Serial(port), port_name, comm_key, timeout)
+    }
+
+    fn handshake(
+        stream: Transport,
+        addr: &str,
+        comm_key: u32,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let mut session = ZkSession {
+            stream,
+            session_id: 0,
+            reply_id: 0,
+            timeout,
+            trace: SessionTrace::open(addr),
+        };
+
+        let mut reply = session.send_command(CMD_CONNECT, &[])?;
+        session.session_id = reply.session_id;
+        if reply.command == CMD_ACK_UNAUTH {
+            let key = make_commkey(comm_key, session.session_id, 50);
+            reply = session.send_command(CMD_AUTH, &key)?;
+            if !reply.is_ok() {
+                return Err(format!(
+                    "{}: Device {} rejected the communication key (reply code {})",
+                    AUTH_FAILED, addr, reply.command
+                ));
+            }
+        }
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device {} rejected connection (reply code {})",
+                addr, reply.command
+            ));
+        }
+
+        Ok(session)
+    }
+
+    pub fn send_command(&mut self, command: u16, data: &[u8]) -> Result<Reply, String> {
+        self.reply_id = self.reply_id.wrapping_add(1);
+        let packet = build_packet(command, self.session_id, self.reply_id, data);
+        self.write_packet(&packet)
+            .map_err(|e| format!("Failed to send command {}: {}", command, e))?;
+        self.recv_reply()
+    }
+
+    fn write_packet(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        if let Some(trace) = &mut self.trace {
+            trace.record("tx", &packet[TCP_HEADER_SIZE..]);
+        }
+        self.stream.write_all(packet)
+    }
+
+    pub fn get_firmware_version(&mut self) -> Result<String, String> {
+        let reply = self.send_command(CMD_GET_VERSION, &[])?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused firmware version request (reply code {})",
+                reply.command
+            ));
+        }
+        Ok(decode_c_string(&reply.data))
+    }
+
+    pub fn recv_reply(&mut self) -> Result<Reply, String> {
+        let mut header = [0u8; TCP_HEADER_SIZE];
+        self.stream
+            .read_exact(&mut header)
+            .map_err(|e| format!("Failed to read device reply: {}", e))?;
+        if header[0..4] != START_TAG {
+            return Err("Device reply has an invalid start tag".to_string());
+        }
+
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if size < COMMAND_HEADER_SIZE {
+            return Err(format!("Device reply too short ({} bytes)", size));
+        }
+        if size > MAX_REPLY_SIZE {
+            return Err(format!("Device reply too large ({} bytes)", size));
+        }
+
+        let mut payload = vec![0u8; size];
+        self.stream
+            .read_exact(&mut payload)
+            .map_err(|e| format!("Failed to read device reply payload: {}", e))?;
+        if let Some(trace) = &mut self.trace {
+            trace.record("rx", &payload);
+        }
+
+        Ok(Reply {
+            command: u16::from_le_bytes([payload[0], payload[1]]),
+            session_id: u16::from_le_bytes([payload[4], payload[5]]),
+            data: payload[COMMAND_HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    pub fn get_time(&mut self) -> Result<NaiveDateTime, String> {
+        let reply = self.send_command(CMD_GET_TIME, &[])?;
+        if !reply.is_ok() || reply.data.len() < 4 {
+            return Err(format!(
+                "Device refused time request (reply code {})",
+                reply.command
+            ));
+        }
+        decode_time(read_u32(&reply.data, 0))
+            .ok_or_else(|| "Device reported an invalid time".to_string())
+    }
+
+    pub fn set_time(&mut self, time: NaiveDateTime) -> Result<(), String> {
+        let reply = self.send_command(CMD_SET_TIME, &encode_time(time).to_le_bytes())?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused time update (reply code {})",
+                reply.command
+            ))
+        }
+    }
+
+    // Read a "~Name" style device option; returns the value after '='
+    pub fn read_option(&mut self, name: &str) -> Result<String, String> {
+        let mut request = name.as_bytes().to_vec();
+        request.push(0);
+        let reply = self.send_command(CMD_OPTIONS_RRQ, &request)?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused option {} (reply code {})",
+                name, reply.command
+            ));
+        }
+        let value = decode_c_string(&reply.data);
+        Ok(value
+            .split_once('=')
+            .map(|(_, value)| value.to_string())
+            .unwrap_or(value))
+    }
+
+    pub fn write_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let mut request = format!("{}={}", name, value).into_bytes();
+        request.push(0);
+        let reply = self.send_command(CMD_OPTIONS_WRQ, &request)?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused option {} (reply code {})",
+                name, reply.command
+            ));
+        }
+        Ok(())
+    }
+
+    // Make the device apply options written with write_option
+    pub fn refresh_options(&mut self) -> Result<(), String> {
+        self.send_simple(CMD_REFRESHOPTION, "option refresh")
+    }
+
+    pub fn read_sizes(&mut self) -> Result<DeviceSizes, String> {
+        let reply = self.send_command(CMD_GET_FREE_SIZES, &[])?;
+        if !reply.is_ok() || reply.data.len() < 80 {
+            return Err(format!(
+                "Device returned no capacity information (reply code {})",
+                reply.command
+            ));
+        }
+        let field = |index: usize| read_u32(&reply.data, index * 4);
+        let has_faces = reply.data.len() >= 92;
+        Ok(DeviceSizes {
+            users: field(4),
+            fingers: field(6),
+            records: field(8),
+            cards: field(12),
+            fingers_cap: field(14),
+            users_cap: field(15),
+            records_cap: field(16),
+            faces: has_faces.then(|| field(20)),
+            faces_cap: has_faces.then(|| field(22)),
+        })
+    }
+
+    // Returns the users and the record size the device uses for them
+    pub fn read_users(&mut self) -> Result<(Vec<DeviceUser>, usize), String> {
+        let sizes = self.read_sizes()?;
+        if sizes.users == 0 {
+            return Ok((Vec::new(), USER_RECORD_LARGE));
+        }
+        let data = self.read_with_buffer(CMD_USERTEMP_RRQ, FCT_USER, 0)?;
+        Ok(parse_users(&data, sizes.users))
+    }
+
+    pub fn write_user(&mut self, user: &DeviceUser, record_size: usize) -> Result<(), String> {
+        self.store_user(user, record_size)?;
+        self.refresh_data()
+    }
+
+    // write_user without the refresh, for batches that call refresh_data once at the end
+    pub fn store_user(&mut self, user: &DeviceUser, record_size: usize) -> Result<(), String> {
+        let reply = self.send_command(CMD_USER_WRQ, &pack_user(user, record_size)?)?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused user {} (reply code {})",
+                user.user_id, reply.command
+            ))
+        }
+    }
+
+    // Make the device reload its user/template tables after a write
+    pub fn refresh_data(&mut self) -> Result<(), String> {
+        let reply = self.send_command(CMD_REFRESHDATA, &[])?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused data refresh (reply code {})",
+                reply.command
+            ))
+        }
+    }
+
+    // Face support is advertised through the FaceFunOn option; terminals without it ignore
+    // template requests, so callers must check before transferring faces
+    pub fn supports_faces(&mut self) -> bool {
+        self.read_option("FaceFunOn")
+            .map(|value| value.trim() == "1")
+            .unwrap_or(false)
+    }
+
+    pub fn read_face_template(&mut self, uid: u16) -> Result<Vec<u8>, String> {
+        let mut request = uid.to_le_bytes().to_vec();
+        request.push(FACE_TEMPLATE_INDEX);
+        let reply = self.send_command(CMD_GET_USERTEMP, &request)?;
+        let command = reply.command;
+        match self.collect_data(reply)? {
+            Some(data) if !data.is_empty() => Ok(data),
+            _ => Err(format!(
+                "No face template stored for user slot {} (reply code {})",
+                uid, command
+            )),
+        }
+    }
+
+    pub fn write_face_template(&mut self, uid: u16, template: &[u8]) -> Result<(), String> {
+        self.send_buffer(template)?;
+        let mut request = uid.to_le_bytes().to_vec();
+        request.push(FACE_TEMPLATE_INDEX);
+        request.push(1); // valid flag
+        request.extend_from_slice(&(template.len() as u16).to_le_bytes());
+        let reply = self.send_command(CMD_TMP_WRITE, &request)?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused face template for user slot {} (reply code {})",
+                uid, reply.command
+            ));
+        }
+        self.refresh_data()
+    }
+
+    pub fn read_attendance(&mut self) -> Result<Vec<AttendanceRecord>, String> {
+        self.read_attendance_progress(|_, _| {})
+    }
+
+    // Calls `on_progress(records_fetched, records_total)` while the log is transferred
+    pub fn read_attendance_progress(
+        &mut self,
+        mut on_progress: impl FnMut(u32, u32),
+    ) -> Result<Vec<AttendanceRecord>, String> {
+        let sizes = self.read_sizes()?;
+        if sizes.records == 0 {
+            on_progress(0, 0);
+            return Ok(Vec::new());
+        }
+        let records = sizes.records;
+        let data = self.read_with_buffer_progress(CMD_ATTLOG_RRQ, 0, 0, |read, total| {
+            let fetched = (read as u64 * records as u64 / total.max(1) as u64) as u32;
+            on_progress(fetched, records);
+        })?;
+        on_progress(records, records);
+        Ok(parse_attendance_records(&data, records))
+    }
+
+    // Subscribe to realtime events; the device then pushes CMD_REG_EVENT packets that must be acked
+    pub fn read_operation_log(&mut self) -> Result<Vec<OperationRecord>, String> {
+        let data = self.read_with_buffer(CMD_OPLOG_RRQ, 0, 0)?;
+        Ok(parse_operation_records(&data))
+    }
+
+    pub fn register_events(&mut self, flags: u32) -> Result<(), String> {
+        let reply = self.send_command(CMD_REG_EVENT, &flags.to_le_bytes())?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused realtime event registration (reply code {})",
+                reply.command
+            ))
+        }
+    }
+
+    // Wait up to `poll` for the next pushed packet; Ok(None) when nothing arrived in time.
+    // Peeking first keeps the stream in sync when the wait times out.
+    pub fn wait_event(&mut self, poll: Duration) -> Result<Option<Reply>, String> {
+        if self.stream.wait_readable(poll, self.timeout)? {
+            self.recv_reply().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Acknowledge a pushed event without waiting for a reply
+    pub fn ack_event(&mut self) -> Result<(), String> {
+        let packet = build_packet(CMD_ACK_OK, self.session_id, 0xFFFE, &[]);
+        self.write_packet(&packet)
+            .map_err(|e| format!("Failed to acknowledge device event: {}", e))
+    }
+
+    // Read a large dataset (users, attendance log, ...) through the device's transfer buffer
+    pub fn read_with_buffer(
+        &mut self,
+        command: u16,
+        fct: i32,
+        ext: i32,
+    ) -> Result<Vec<u8>, String> {
+        self.read_with_buffer_progress(command, fct, ext, |_, _| {})
+    }
+
+    // Same as read_with_buffer, calling `on_progress(bytes_read, total_bytes)` after every chunk
+    pub fn read_with_buffer_progress(
+        &mut self,
+        command: u16,
+        fct: i32,
+        ext: i32,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<Vec<u8>, String> {
+        let mut request = Vec::with_capacity(11);
+        request.push(1u8);
+        request.extend_from_slice(&(command as i16).to_le_bytes());
+        request.extend_from_slice(&fct.to_le_bytes());
+        request.extend_from_slice(&ext.to_le_bytes());
+
+        let reply = self.send_command(CMD_PREPARE_BUFFER, &request)?;
+        if reply.command == CMD_DATA {
+            return Ok(reply.data);
+        }
+        if reply.command != CMD_ACK_OK || reply.data.len() < 5 {
+            return Err(format!(
+                "Device refused buffered read of command {} (reply code {})",
+                command, reply.command
+            ));
+        }
+
+        let total = u32::from_le_bytes([reply.data[1], reply.data[2], reply.data[3], reply.data[4]])
+            as usize;
+        if total > MAX_DATASET_SIZE {
+            return Err(format!(
+                "Device announced a dataset too large ({} bytes)",
+                total
+            ));
+        }
+        let mut dataset = Vec::with_capacity(total);
+        let mut start = 0usize;
+        while start < total {
+            let size = MAX_CHUNK.min(total - start);
+            dataset.extend(self.read_chunk(start, size)?);
+            start += size;
+            on_progress(start, total);
+        }
+
+        self.send_command(CMD_FREE_DATA, &[])?;
+        Ok(dataset)
+    }
+
+    fn read_chunk(&mut self, start: usize, size: usize) -> Result<Vec<u8>, String> {
+        let mut request = Vec::with_capacity(8);
+        request.extend_from_slice(&(start as i32).to_le_bytes());
+        request.extend_from_slice(&(size as i32).to_le_bytes());
+
+        let reply = self.send_command(CMD_READ_BUFFER, &request)?;
+        let command = reply.command;
+        self.collect_data(reply)?.ok_or_else(|| {
+            format!(
+                "Device failed to send chunk at offset {} (reply code {})",
+                start, command
+            )
+        })
+    }
+
+    // A data reply is either inline (CMD_DATA) or announced by CMD_PREPARE_DATA with its size
+    // and streamed as CMD_DATA packets terminated by an ACK; Ok(None) for any other reply
+    fn collect_data(&mut self, reply: Reply) -> Result<Option<Vec<u8>>, String> {
+        match reply.command {
+            CMD_DATA => Ok(Some(reply.data)),
+            CMD_PREPARE_DATA => {
+                let size = if reply.data.len() >= 4 {
+                    read_u32(&reply.data, 0) as usize
+                } else {
+                    0
+                };
+                if size > MAX_REPLY_SIZE {
+                    return Err(format!(
+                        "Device announced a reply too large ({} bytes)",
+                        size
+                    ));
+                }
+                let mut data = Vec::with_capacity(size);
+                while data.len() < size {
+                    let packet = self.recv_reply()?;
+                    // Empty packets would never get the transfer to its announced size
+                    if packet.command != CMD_DATA || packet.data.is_empty() {
+                        break;
+                    }
+                    if data.len() + packet.data.len() > MAX_REPLY_SIZE {
+                        return Err(format!("Device reply exceeded {} bytes", MAX_REPLY_SIZE));
+                    }
+                    data.extend(packet.data);
+                }
+                let ack = self.recv_reply()?;
+                if ack.command != CMD_ACK_OK {
+                    return Err(format!(
+                        "Device did not acknowledge data transfer (reply code {})",
+                        ack.command
+                    ));
+                }
+                Ok(Some(data))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // Stage a payload in the device's receive buffer ahead of a write command
+    fn send_buffer(&mut self, data: &[u8]) -> Result<(), String> {
+        self.send_buffer_with_progress(data, |_, _| {})
+    }
+
+    // Same as send_buffer, calling `on_progress(sent, total)` after every chunk
+    fn send_buffer_with_progress(
+        &mut self,
+        data: &[u8],
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> Result<(), String> {
+        self.send_command(CMD_FREE_DATA, &[])?;
+        let reply = self.send_command(CMD_PREPARE_DATA, &(data.len() as u32).to_le_bytes())?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused data upload (reply code {})",
+                reply.command
+            ));
+        }
+        let mut sent = 0;
+        for chunk in data.chunks(UPLOAD_CHUNK) {
+            let reply = self.send_command(CMD_DATA, chunk)?;
+            if !reply.is_ok() {
+                return Err(format!(
+                    "Device rejected uploaded data (reply code {})",
+                    reply.command
+                ));
+            }
+            sent += chunk.len();
+            on_progress(sent, data.len());
+        }
+        Ok(())
+    }
+
+    // Upload a firmware image and ask the device to install it; the terminal applies it on the
+    // next restart. Request layout: [flag u32 = 1 (firmware)][size u32][file name, NUL]
+    pub fn upload_firmware(
+        &mut self,
+        file_name: &str,
+        image: &[u8],
+        on_progress: impl FnMut(usize, usize),
+    ) -> Result<(), String> {
+        self.send_buffer_with_progress(image, on_progress)?;
+        let mut request = 1u32.to_le_bytes().to_vec();
+        request.extend_from_slice(&(image.len() as u32).to_le_bytes());
+        request.extend_from_slice(file_name.as_bytes());
+        request.push(0);
+        let reply = self.send_command(CMD_UPDATEFILE, &request)?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused the firmware image (reply code {})",
+                reply.command
+            ));
+        }
+        Ok(())
+    }
+
+    // Store a short message (60 bytes max) shown on the terminal screen for `valid_minutes`
+    // (0 = permanent); personal messages additionally get bound to the user slot `uid`
+    pub fn write_sms(
+        &mut self,
+        sms_id: u16,
+        text: &str,
+        valid_minutes: u16,
+        uid: Option<u16>,
+    ) -> Result<(), String> {
+        let mut record = Vec::with_capacity(11 + SMS_CONTENT_SIZE);
+        record.push(if uid.is_some() {
+            SMS_TAG_USER
+        } else {
+            SMS_TAG_PUBLIC
+        });
+        record.extend_from_slice(&sms_id.to_le_bytes());
+        record.extend_from_slice(&valid_minutes.to_le_bytes());
+        record.extend_from_slice(&[0, 0]);
+        record.extend_from_slice(&encode_time(chrono::Local::now().naive_local()).to_le_bytes());
+        let mut content = [0u8; SMS_CONTENT_SIZE];
+        put_str(&mut content[..SMS_CONTENT_SIZE - 1], text);
+        record.extend_from_slice(&content);
+
+        let reply = self.send_command(CMD_SMS_WRQ, &record)?;
+        if !reply.is_ok() {
+            return Err(format!(
+                "Device refused message (reply code {})",
+                reply.command
+            ));
+        }
+
+        if let Some(uid) = uid {
+            let mut binding = uid.to_le_bytes().to_vec();
+            binding.extend_from_slice(&sms_id.to_le_bytes());
+            let reply = self.send_command(CMD_UDATA_WRQ, &binding)?;
+            if !reply.is_ok() {
+                return Err(format!(
+                    "Device refused to assign message to user slot {} (reply code {})",
+                    uid, reply.command
+                ));
+            }
+        }
+        self.refresh_data()
+    }
+
+    // Lock the keypad/sensors so no punches are recorded while data is being modified
+    pub fn disable_device(&mut self) -> Result<(), String> {
+        self.send_simple(CMD_DISABLEDEVICE, "disable")
+    }
+
+    pub fn enable_device(&mut self) -> Result<(), String> {
+        self.send_simple(CMD_ENABLEDEVICE, "enable")
+    }
+
+    pub fn clear_attendance(&mut self) -> Result<(), String> {
+        self.send_simple(CMD_CLEAR_ATTLOG, "attendance log clear")
+    }
+
+    fn send_simple(&mut self, command: u16, name: &str) -> Result<(), String> {
+        let reply = self.send_command(command, &[])?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused {} (reply code {})",
+                name, reply.command
+            ))
+        }
+    }
+
+    // Trigger the door relay; the device takes the duration in tenths of a second
+    pub fn unlock(&mut self, seconds: u32) -> Result<(), String> {
+        let reply = self.send_command(CMD_UNLOCK, &(seconds * 10).to_le_bytes())?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device refused door unlock (reply code {})",
+                reply.command
+            ))
+        }
+    }
+
+    // Restart/power off end the session on the device side, so these consume it
+    pub fn restart(mut self) -> Result<(), String> {
+        self.send_simple(CMD_RESTART, "restart")
+    }
+
+    pub fn power_off(mut self) -> Result<(), String> {
+        self.send_simple(CMD_POWEROFF, "power off")
+    }
+
+    pub fn disconnect(mut self) -> Result<(), String> {
+        let reply = self.send_command(CMD_EXIT, &[])?;
+        if reply.is_ok() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Device rejected disconnect (reply code {})",
+                reply.command
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_name, put_name};
+
+    fn stored(name: &str, size: usize) -> Vec<u8> {
+        let mut buffer = vec![0xffu8; size];
+        put_name(&mut buffer, name);
+        buffer
+    }
+
+    #[test]
+    fn chinese_names_round_trip_as_gbk() {
+        let buffer = stored("张三", 24);
+        assert_eq!(&buffer[..5], &[0xd5, 0xc5, 0xc8, 0xfd, 0x00]);
+        assert_eq!(decode_name(&buffer), "张三");
+    }
+
+    #[test]
+    fn vietnamese_names_fall_back_to_utf8() {
+        let name = "Nguyễn Văn Hường";
+        let buffer = stored(name, 24);
+        assert_eq!(&buffer[..name.len()], name.as_bytes());
+        assert_eq!(decode_name(&buffer), name);
+    }
+
+    #[test]
+    fn short_field_truncates_on_a_gbk_character() {
+        // 8 byte field: 7 bytes of name and a NUL, so the fourth character doesn't fit
+        let buffer = stored("张三李四", 8);
+        assert_eq!(&buffer[6..], &[0, 0]);
+        assert_eq!(decode_name(&buffer), "张三李");
+    }
+
+    #[test]
+    fn long_field_truncates_on_a_utf8_character() {
+        // "ờ" would take bytes 22..25 of a 24 byte field, past the NUL terminator
+        let name = "Nguyễn Thị Thu Hường";
+        let buffer = stored(name, 24);
+        assert_eq!(buffer[23], 0);
+        let decoded = decode_name(&buffer);
+        assert_eq!(decoded, "Nguyễn Thị Thu Hư");
+        assert!(name.starts_with(&decoded));
+    }
+
+    #[test]
+    fn device_truncated_utf8_tail_keeps_complete_characters() {
+        // A device that cut "Nguyễn" inside "ễ" (e1 bb 85) without room for a NUL
+        let raw = &"Nguyễn".as_bytes()[..6];
+        assert_eq!(decode_name(raw), "Nguy");
+    }
+}
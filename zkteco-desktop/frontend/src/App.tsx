@@ -10,7 +10,9 @@ import { AppInitializer } from "./components/features/AppInitializer";
 import { Attendance } from "./components/features/Attendance";
 import { DeviceManagement } from "./components/features/DeviceManagement";
 import { DoorManagement } from "./components/features/DoorManagement";
+import { IdleGuard } from "./components/features/IdleGuard";
 import { LiveAttendance } from "./components/features/LiveAttendance";
+import { LockScreen } from "./components/features/LockScreen";
 import { Logs } from "./components/features/Logs";
 import { ServiceStatus } from "./components/features/ServiceStatus";
 import { Settings } from "./components/features/Settings";
@@ -26,25 +28,34 @@ function App() {
     <ThemeProvider defaultTheme="system">
       <TrayProvider>
         <DeviceProvider>
-          <AppInitializer>
-            <Router>
-              <AppLayout>
-                <Routes>
-                  <Route path="/" element={<ServiceStatus />} />
-                  <Route path="/devices" element={<DeviceManagement />} />
-                  <Route path="/users" element={<UserManagement />} />
-                  <Route path="/doors" element={<DoorManagement />} />
-                  <Route path="/attendance" element={<Attendance />} />
-                  <Route path="/live-attendance" element={<LiveAttendance />} />
-                  <Route path="/door-history" element={<DoorAccessHistory />} />
-                  <Route path="/logs" element={<Logs />} />
-                  <Route path="/settings" element={<Settings />} />
-                  <Route path="*" element={<Navigate to="/" replace />} />
-                </Routes>
-              </AppLayout>
-              <Toaster position="top-right" />
-            </Router>
-          </AppInitializer>
+          <LockScreen>
+            <AppInitializer>
+              <Router>
+                <IdleGuard />
+                <AppLayout>
+                  <Routes>
+                    <Route path="/" element={<ServiceStatus />} />
+                    <Route path="/devices" element={<DeviceManagement />} />
+                    <Route path="/users" element={<UserManagement />} />
+                    <Route path="/doors" element={<DoorManagement />} />
+                    <Route path="/attendance" element={<Attendance />} />
+                    <Route
+                      path="/live-attendance"
+                      element={<LiveAttendance />}
+                    />
+                    <Route
+                      path="/door-history"
+                      element={<DoorAccessHistory />}
+                    />
+                    <Route path="/logs" element={<Logs />} />
+                    <Route path="/settings" element={<Settings />} />
+                    <Route path="*" element={<Navigate to="/" replace />} />
+                  </Routes>
+                </AppLayout>
+                <Toaster position="top-right" />
+              </Router>
+            </AppInitializer>
+          </LockScreen>
         </DeviceProvider>
       </TrayProvider>
     </ThemeProvider>
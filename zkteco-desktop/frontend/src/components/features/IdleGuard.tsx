@@ -0,0 +1,21 @@
+import { listen } from "@tauri-apps/api/event";
+import { useEffect } from "react";
+import { useNavigate } from "react-router-dom";
+
+// Status page, which shows no employee data; the UI returns there on "session-idle"
+const SAFE_ROUTE = "/";
+
+export function IdleGuard() {
+  const navigate = useNavigate();
+
+  useEffect(() => {
+    const unlisten = listen("session-idle", () => {
+      navigate(SAFE_ROUTE, { replace: true });
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [navigate]);
+
+  return null;
+}
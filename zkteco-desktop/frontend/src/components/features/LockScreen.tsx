@@ -0,0 +1,128 @@
+import { Alert, AlertDescription } from "@/components/ui/alert";
+import { Button } from "@/components/ui/button";
+import {
+  Card,
+  CardContent,
+  CardDescription,
+  CardHeader,
+  CardTitle,
+} from "@/components/ui/card";
+import { Input } from "@/components/ui/input";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { AlertCircle, Lock } from "lucide-react";
+import { FormEvent, useEffect, useState } from "react";
+
+interface LockStatus {
+  enabled: boolean;
+  locked: boolean;
+  idle_minutes: number;
+}
+
+interface LockScreenProps {
+  children: React.ReactNode;
+}
+
+// Activity reports are throttled; the shell only needs to know someone is there
+const ACTIVITY_REPORT_INTERVAL = 30000;
+
+export function LockScreen({ children }: LockScreenProps) {
+  const [locked, setLocked] = useState(false);
+  const [pin, setPin] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const [unlocking, setUnlocking] = useState(false);
+
+  useEffect(() => {
+    invoke<LockStatus>("get_lock_status")
+      .then((status) => setLocked(status.locked))
+      .catch((err) => console.warn("Could not read app lock status:", err));
+
+    const unlistenLocked = listen("app-locked", () => {
+      setPin("");
+      setError(null);
+      setLocked(true);
+    });
+    const unlistenUnlocked = listen("app-unlocked", () => setLocked(false));
+
+    return () => {
+      unlistenLocked.then((unlisten) => unlisten());
+      unlistenUnlocked.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    let lastReport = 0;
+    const onActivity = () => {
+      const now = Date.now();
+      if (now - lastReport < ACTIVITY_REPORT_INTERVAL) {
+        return;
+      }
+      lastReport = now;
+      invoke("report_activity").catch(() => undefined);
+    };
+
+    const events = ["mousemove", "mousedown", "keydown", "wheel", "touchstart"];
+    events.forEach((name) => window.addEventListener(name, onActivity));
+    return () => {
+      events.forEach((name) => window.removeEventListener(name, onActivity));
+    };
+  }, []);
+
+  const handleUnlock = async (event: FormEvent) => {
+    event.preventDefault();
+    setUnlocking(true);
+    setError(null);
+    try {
+      await invoke("unlock_app", { pin });
+      setPin("");
+      setLocked(false);
+    } catch (err) {
+      setError(typeof err === "string" ? err : "Unlock failed");
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  if (!locked) {
+    return <>{children}</>;
+  }
+
+  return (
+    <div className="min-h-screen bg-background flex items-center justify-center p-4">
+      <Card className="w-full max-w-sm">
+        <CardHeader className="text-center">
+          <div className="flex justify-center mb-4">
+            <Lock className="h-12 w-12 text-muted-foreground" />
+          </div>
+          <CardTitle>App locked</CardTitle>
+          <CardDescription>Enter the PIN to continue</CardDescription>
+        </CardHeader>
+        <CardContent>
+          <form onSubmit={handleUnlock} className="space-y-4">
+            <Input
+              type="password"
+              autoFocus
+              value={pin}
+              onChange={(e) => setPin(e.target.value)}
+              placeholder="PIN"
+              disabled={unlocking}
+            />
+            {error && (
+              <Alert variant="destructive">
+                <AlertCircle className="h-4 w-4" />
+                <AlertDescription>{error}</AlertDescription>
+              </Alert>
+            )}
+            <Button
+              type="submit"
+              className="w-full"
+              disabled={unlocking || !pin}
+            >
+              {unlocking ? "Unlocking..." : "Unlock"}
+            </Button>
+          </form>
+        </CardContent>
+      </Card>
+    </div>
+  );
+}
@@ -3,7 +3,6 @@ import { Button } from "@/components/ui/button";
 import { Card, CardContent, CardHeader, CardTitle } from "@/components/ui/card";
 import { ScrollArea } from "@/components/ui/scroll-area";
 import { invoke } from "@tauri-apps/api/core";
-import { save } from "@tauri-apps/plugin-dialog";
 import { open } from "@tauri-apps/plugin-shell";
 import {
   AlertCircle,
@@ -69,25 +68,11 @@ export function Logs() {
 
   const exportLogs = async () => {
     try {
-      // Open save dialog
-      const filePath = await save({
-        defaultPath: `zkteco-logs-${
-          new Date().toISOString().split("T")[0]
-        }.log`,
-        filters: [
-          {
-            name: "Log Files",
-            extensions: ["log", "txt"],
-          },
-        ],
-      });
-
-      if (!filePath) {
-        return; // User cancelled
+      // The shell opens the save dialog; null when the user cancels
+      const exported = await invoke<string | null>("export_log_file");
+      if (!exported) {
+        return;
       }
-
-      // Export log file
-      await invoke("export_log_file", { destination: filePath });
       toast.success("Xuất file log thành công");
     } catch (error) {
       console.error("Failed to export logs:", error);
@@ -1,37 +1,158 @@
 import { invoke } from "@tauri-apps/api/core";
-import axios from "axios";
+import { listen } from "@tauri-apps/api/event";
+import axios, {
+  AxiosError,
+  type AxiosResponse,
+  type InternalAxiosRequestConfig,
+} from "axios";
+
+// Every API call goes through the shell (proxy_backend_request), which adds the session
+// token and follows the backend's port; backend events arrive as Tauri events re-emitted by
+// the shell's event bridge
+
+// Subscribe to shell-emitted events; the returned cleanup also cancels pending listens
+const listenAll = (
+  handlers: Record<string, (payload: unknown) => void>,
+): (() => void) => {
+  const unlisteners = Object.entries(handlers).map(([event, handler]) =>
+    listen(event, (message) => handler(message.payload)),
+  );
+  return () => {
+    unlisteners.forEach((unlisten) => unlisten.then((fn) => fn()));
+  };
+};
+
+type BackendEventsStatus = { connected: boolean };
+
+// Report the bridge's current state, then every change
+const watchEventsStatus = (
+  onOpen: (() => void) | undefined,
+  onClose: () => void,
+) => {
+  invoke<BackendEventsStatus>("get_backend_events_status")
+    .then((status) => {
+      if (status.connected) {
+        onOpen?.();
+      }
+    })
+    .catch((error) => {
+      console.warn("Could not read backend event stream status", error);
+    });
+  return (payload: unknown) => {
+    if ((payload as BackendEventsStatus).connected) {
+      onOpen?.();
+    } else {
+      onClose();
+    }
+  };
+};
 
-// API base configuration with fallbacks
-const API_HOSTS = [
-  "http://127.0.0.1:57575",
-  "http://localhost:57575",
-  "http://0.0.0.0:57575",
-];
+// The shell checks the configured health route, over TLS when that is enabled
+const backendStatusOk = (): Promise<boolean> =>
+  invoke<boolean>("check_backend_http_health");
 
-// Try to detect working API host
-let API_BASE_URL = API_HOSTS[0];
+type ProxyResponse = {
+  status: number;
+  body: unknown;
+  headers: Record<string, string>;
+};
+
+type RawBody = { contentType: string; base64: string };
+
+const stringParams = (params: Record<string, unknown>) =>
+  Object.fromEntries(
+    Object.entries(params)
+      .filter(([, value]) => value !== undefined && value !== null)
+      .map(([key, value]) => [key, String(value)]),
+  );
 
-// Function to test and set the working API host
-const detectApiHost = async (): Promise<string> => {
-  for (const host of API_HOSTS) {
+const bytesToBase64 = (bytes: Uint8Array) => {
+  let binary = "";
+  for (let i = 0; i < bytes.length; i += 0x8000) {
+    binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+  }
+  return btoa(binary);
+};
+
+const base64ToBytes = (base64: string) =>
+  Uint8Array.from(atob(base64), (char) => char.charCodeAt(0));
+
+// Multipart forms are serialized here (with their boundary) and sent as raw bytes
+const formDataBody = async (form: FormData): Promise<RawBody> => {
+  const serialized = new Response(form);
+  return {
+    contentType:
+      serialized.headers.get("content-type") ?? "multipart/form-data",
+    base64: bytesToBase64(new Uint8Array(await serialized.arrayBuffer())),
+  };
+};
+
+const shellProxyAdapter = async (
+  config: InternalAxiosRequestConfig,
+): Promise<AxiosResponse> => {
+  let body = config.data ?? null;
+  let rawBody: RawBody | null = null;
+  if (body instanceof FormData) {
+    rawBody = await formDataBody(body);
+    body = null;
+  } else if (typeof body === "string") {
     try {
-      const response = await fetch(`${host}/service/status`, {
-        method: "GET",
-        signal: AbortSignal.timeout(2000),
-      });
-      if (response.ok) {
-        console.log(`Detected working API host: ${host}`);
-        return host;
-      }
-    } catch (error) {
-      console.log(
-        `Host ${host} not reachable:`,
-        error instanceof Error ? error.message : error,
-      );
+      body = JSON.parse(body);
+    } catch {
+      // Plain text payload, forwarded as a JSON string
     }
   }
-  console.warn("No working API host found, using default");
-  return API_HOSTS[0];
+  const binary =
+    config.responseType === "blob" || config.responseType === "arraybuffer";
+
+  let proxied: ProxyResponse;
+  try {
+    proxied = await invoke<ProxyResponse>("proxy_backend_request", {
+      method: config.method ?? "get",
+      path: config.url ?? "/",
+      query: config.params ? stringParams(config.params) : null,
+      body,
+      rawBody,
+      timeoutMs: config.timeout || null,
+      binary,
+    });
+  } catch (error) {
+    // The shell gave up reaching the backend (after its own retries)
+    throw new AxiosError(
+      String(error),
+      AxiosError.ERR_NETWORK,
+      config,
+      null,
+    );
+  }
+
+  const { status, headers } = proxied;
+  let data = proxied.body;
+  if (binary && typeof data === "string") {
+    const bytes = base64ToBytes(data);
+    data =
+      config.responseType === "blob"
+        ? new Blob([bytes], { type: headers["content-type"] })
+        : bytes.buffer;
+  }
+  const response: AxiosResponse = {
+    data,
+    status,
+    statusText: String(status),
+    headers,
+    config,
+    request: null,
+  };
+  if (config.validateStatus && !config.validateStatus(status)) {
+    throw new AxiosError(
+      `Request failed with status code ${status}`,
+      status >= 500 ? AxiosError.ERR_BAD_RESPONSE : AxiosError.ERR_BAD_REQUEST,
+      config,
+      null,
+      response,
+    );
+  }
+  return response;
 };
 
 // Track backend startup attempts to prevent infinite loops
@@ -40,17 +161,17 @@ const MAX_STARTUP_ATTEMPTS = 3;
 const STARTUP_COOLDOWN = 30000; // 30 seconds
 
 export const api = axios.create({
-  baseURL: API_BASE_URL,
   // Increase default timeout to better handle large payloads
   timeout: 60000,
   headers: {
     "Content-Type": "application/json",
   },
+  adapter: shellProxyAdapter,
 });
 
 // Request interceptor
 api.interceptors.request.use(
-  (config) => {
+  async (config) => {
     console.log(
       `Making ${config.method?.toUpperCase()} request to ${config.url}`,
     );
@@ -82,8 +203,7 @@ const attemptBackendRestart = async (): Promise<boolean> => {
     await new Promise((resolve) => setTimeout(resolve, 3000));
 
     // Test if backend is responding
-    const healthResponse = await fetch(`${API_BASE_URL}/service/status`);
-    const isHealthy = healthResponse.ok;
+    const isHealthy = await backendStatusOk();
 
     if (isHealthy) {
       console.log("Backend restarted successfully");
@@ -573,40 +693,19 @@ export const devicesAPI = {
     }
   },
 
-  subscribeToEvents: ({ onEvent, onError, onOpen }: DeviceEventHandlers) => {
-    const eventSource = new EventSource(`${API_BASE_URL}/devices/events`);
-
-    if (onOpen) {
-      eventSource.addEventListener("ready", () => {
-        onOpen();
-      });
-    }
-
-    eventSource.onmessage = (event) => {
-      if (!event.data) {
-        return;
-      }
-
-      try {
-        const payload = JSON.parse(event.data) as DevicePingEvent;
-        if (payload?.type === "device_ping" && payload.device_id) {
-          onEvent(payload);
+  subscribeToEvents: ({ onEvent, onError, onOpen }: DeviceEventHandlers) =>
+    listenAll({
+      "backend-device-ping": (payload) => {
+        const event = payload as DevicePingEvent;
+        if (event?.device_id) {
+          onEvent(event);
         }
-      } catch (parseError) {
-        console.error("Failed to parse device event payload:", parseError);
-      }
-    };
-
-    eventSource.onerror = (event) => {
-      console.error("Device events SSE error:", event);
-      onError?.(event);
-      eventSource.close();
-    };
-
-    return () => {
-      eventSource.close();
-    };
-  },
+      },
+      "backend-events-status": watchEventsStatus(onOpen, () => {
+        console.error("Device event stream disconnected");
+        onError?.(new Event("disconnected"));
+      }),
+    }),
 
   // Sync all devices to external API
   syncToExternal: async () => {
@@ -1003,48 +1102,28 @@ export const liveAPI = {
     onOpen: () => void,
     deviceFilter?: string | "all", // Optional device filter
   ) => {
-    const eventSource = new EventSource(`${API_BASE_URL}/live-events`);
+    const handleRecord = (payload: unknown) => {
+      const newRecord = payload as LiveAttendanceRecord;
 
-    // Use our custom 'connected' event to confirm the connection is ready
-    eventSource.addEventListener("connected", (event) => {
-      console.log("SSE connection established:", event);
-      onOpen();
-    });
-
-    eventSource.onerror = (error) => {
-      console.error("EventSource failed:", error);
-      onError(error);
-      eventSource.close();
-    };
-
-    eventSource.addEventListener("attendance", (event) => {
-      try {
-        console.log("SSE attendance event received:", event.data);
-        const newRecord = JSON.parse(event.data) as LiveAttendanceRecord;
-        console.log("Parsed attendance record:", newRecord);
-
-        // Apply device filter if specified
-        if (deviceFilter && deviceFilter !== "all") {
-          if (newRecord.device_id !== deviceFilter) {
-            return; // Skip this record if it doesn't match the filter
-          }
+      // Apply device filter if specified
+      if (deviceFilter && deviceFilter !== "all") {
+        if (newRecord.device_id !== deviceFilter) {
+          return; // Skip this record if it doesn't match the filter
         }
-
-        onMessage(newRecord);
-      } catch (error) {
-        console.error("Failed to parse attendance event:", error);
       }
-    });
 
-    // The heartbeat event can be used to implement a client-side timeout if needed
-    eventSource.addEventListener("heartbeat", (event) => {
-      console.log("SSE heartbeat received:", event.data);
-    });
-
-    // Return a cleanup function to close the connection
-    return () => {
-      eventSource.close();
+      onMessage(newRecord);
     };
+
+    // Returns the cleanup function that stops listening
+    return listenAll({
+      "backend-attendance": handleRecord,
+      "backend-door-access": handleRecord,
+      "backend-events-status": watchEventsStatus(onOpen, () => {
+        console.error("Live event stream disconnected");
+        onError(new Event("disconnected"));
+      }),
+    });
   },
 
   // Multi-Device Live API functions
@@ -1116,60 +1195,21 @@ export const liveAPI = {
   },
 };
 
-// Health check with better error handling and retries
+// Health check with retries; the shell knows the backend's current port
 export const healthCheck = async (
   retries = 3,
   delay = 1000,
 ): Promise<boolean> => {
-  // First try to detect the correct API host
-  console.log("Detecting API host...");
-  API_BASE_URL = await detectApiHost();
-
   for (let i = 0; i < retries; i++) {
-    // Try all possible hosts on each retry
-    for (const host of API_HOSTS) {
-      try {
-        console.log(`Health check attempt ${i + 1}/${retries} on ${host}`);
-
-        // Try with fetch first as it's more direct
-        const response = await fetch(`${host}/service/status`, {
-          method: "GET",
-          headers: {
-            "Content-Type": "application/json",
-          },
-          // Timeout for fetch
-          signal: AbortSignal.timeout(5000),
-        });
-
-        console.log(
-          `Health check response status: ${response.status} for ${host}`,
-        );
-
-        if (response.ok) {
-          const data = await response.json();
-          console.log("Health check successful:", data);
-          // Update API_BASE_URL to working host
-          API_BASE_URL = host;
-          // Update axios instance
-          api.defaults.baseURL = host;
-          return true;
-        } else {
-          console.warn(
-            `Health check failed with status: ${response.status} for ${host}`,
-          );
-        }
-      } catch (error) {
-        console.error(`Health check on ${host} failed:`, error);
-
-        // Log detailed error information
-        if (error instanceof Error) {
-          console.error("Error details:", {
-            message: error.message,
-            name: error.name,
-            host: host,
-          });
-        }
+    try {
+      console.log(`Health check attempt ${i + 1}/${retries}`);
+      if (await backendStatusOk()) {
+        console.log("Health check successful");
+        return true;
       }
+      console.warn("Health check failed");
+    } catch (error) {
+      console.error("Health check failed:", error);
     }
 
     // Wait before retry (except for last attempt)
@@ -1180,7 +1220,7 @@ export const healthCheck = async (
     }
   }
 
-  console.error("All health check attempts failed on all hosts");
+  console.error("All health check attempts failed");
   return false;
 };
 